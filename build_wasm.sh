@@ -3,7 +3,21 @@ set -euo pipefail
 
 # Parse command line arguments
 BUILD_MODE="debug"
-if [ $# -gt 0 ]; then
+THREADS=0
+RUNTIME="bundler"
+FONTS=1
+usage() {
+    echo "Usage: $0 [debug|release] [--threads] [--no-fonts] [--runtime bundler|workers|deno]"
+    echo "  debug      Build in debug mode (faster compilation, larger binary)"
+    echo "  release    Build in release mode with optimizations (default)"
+    echo "  --threads  Enable the wasm-threads feature (nightly toolchain, atomics;"
+    echo "             the page must be cross-origin isolated)"
+    echo "  --no-fonts Leave out the embedded fonts for a smaller module; the"
+    echo "             host registers its fonts with register_font"
+    echo "  --runtime  JS runtime to target (default: bundler); workers and deno"
+    echo "             build without the panic hook for Cloudflare Workers / Deno"
+}
+while [ $# -gt 0 ]; do
     case "$1" in
         debug|--debug|-d)
             BUILD_MODE="debug"
@@ -11,19 +25,50 @@ if [ $# -gt 0 ]; then
         release|--release|-r)
             BUILD_MODE="release"
             ;;
+        threads|--threads|-t)
+            THREADS=1
+            ;;
+        --no-fonts)
+            FONTS=0
+            ;;
+        --runtime)
+            RUNTIME="${2:-}"
+            shift
+            ;;
+        --runtime=*)
+            RUNTIME="${1#--runtime=}"
+            ;;
         -h|--help)
-            echo "Usage: $0 [debug|release]"
-            echo "  debug    Build in debug mode (faster compilation, larger binary)"
-            echo "  release  Build in release mode with optimizations (default)"
+            usage
             exit 0
             ;;
         *)
             echo "Error: Unknown argument '$1'"
-            echo "Usage: $0 [debug|release]"
+            usage
             exit 1
             ;;
     esac
-fi
+    shift
+done
+
+# Workers and Deno have no use for the panic hook and load the module
+# themselves, so they get plain ES module bindings
+case "$RUNTIME" in
+    bundler)
+        WASM_PACK_TARGET="bundler"
+        ;;
+    workers)
+        WASM_PACK_TARGET="web"
+        ;;
+    deno)
+        WASM_PACK_TARGET="deno"
+        ;;
+    *)
+        echo "Error: Unknown runtime '$RUNTIME'"
+        usage
+        exit 1
+        ;;
+esac
 
 CRATE=wasm-wrapper
 PKG_DIR="$CRATE/pkg"
@@ -41,12 +86,33 @@ fi
 # Ensure getrandom uses the wasm_js backend for wasm32-unknown-unknown
 export RUSTFLAGS="${RUSTFLAGS:-} --cfg getrandom_backend=\"wasm_js\""
 
-# Build with wasm-pack (bundler target for better integration)
+# Threads need shared memory, so std is rebuilt with atomics on nightly
+CARGO_ARGS=""
+if [ "$RUNTIME" != "bundler" ]; then
+    echo "==> Building for $RUNTIME (no default features)…"
+    CARGO_ARGS="--no-default-features"
+    if [ "$FONTS" = "1" ]; then
+        CARGO_ARGS="$CARGO_ARGS --features fonts"
+    fi
+elif [ "$FONTS" = "0" ]; then
+    echo "==> Leaving out the embedded fonts…"
+    CARGO_ARGS="--no-default-features --features console_error_panic_hook,js-helpers"
+fi
+if [ "$THREADS" = "1" ]; then
+    echo "==> Enabling wasm threads (nightly, +atomics)…"
+    export RUSTUP_TOOLCHAIN="${RUSTUP_TOOLCHAIN:-nightly}"
+    export RUSTFLAGS="$RUSTFLAGS -C target-feature=+atomics,+bulk-memory,+mutable-globals"
+    CARGO_ARGS="$CARGO_ARGS --features wasm-threads -Z build-std=panic_abort,std"
+    WASM_OPT_FLAGS="$WASM_OPT_FLAGS --enable-threads"
+fi
+
+# Build with wasm-pack (bundler target unless another runtime was requested)
 cd "$CRATE"
 wasm-pack build \
-  --target bundler \
+  --target "$WASM_PACK_TARGET" \
   --out-dir pkg \
-  $WASM_PACK_FLAGS
+  $WASM_PACK_FLAGS \
+  -- $CARGO_ARGS
 cd ..
 
 # Optimize the generated WASM with wasm-opt
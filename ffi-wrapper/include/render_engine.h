@@ -0,0 +1,95 @@
+/* Generated by cbindgen from ffi-wrapper/src/lib.rs. Do not edit by hand. */
+
+#ifndef RENDER_ENGINE_H
+#define RENDER_ENGINE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result code returned by every fallible function
+typedef enum RenderStatus {
+  RENDER_STATUS_OK = 0,
+  // A pointer argument was null, a string was not valid UTF-8, or the
+  // format was unknown
+  RENDER_STATUS_INVALID_ARGUMENT = 1,
+  // The form failed schema validation or preprocessing
+  RENDER_STATUS_VALIDATION_ERROR = 2,
+  // Typst compilation or export failed
+  RENDER_STATUS_COMPILATION_ERROR = 3,
+  // Any other failure, including internal panics (see the crate docs on
+  // building with unwinding)
+  RENDER_STATUS_INTERNAL_ERROR = 4,
+} RenderStatus;
+
+// Output format selector, passed to the render functions as a `uint32_t`
+typedef enum RenderFormat {
+  RENDER_FORMAT_SVG = 0,
+  RENDER_FORMAT_PDF = 1,
+} RenderFormat;
+
+// A single rendered page (SVG text or PDF bytes)
+typedef struct RenderBuffer {
+  uint8_t *data;
+  size_t len;
+} RenderBuffer;
+
+// Rendered pages, owned by the caller until passed to `render_engine_free_pages`
+typedef struct RenderPages {
+  struct RenderBuffer *pages;
+  size_t count;
+} RenderPages;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Render Typst markup. SVG yields one buffer per page; PDF yields a single buffer.
+// `format` is a `RenderFormat` value.
+//
+// # Safety
+// `markup` must be a NUL-terminated UTF-8 string and `out` must point to
+// writable memory for a `RenderPages`. On success, release `out` with
+// `render_engine_free_pages`.
+enum RenderStatus render_engine_render_markup(const char *markup,
+                                              uint32_t format,
+                                              struct RenderPages *out);
+
+// Render a memo form (JSON) with the memo-loader template.
+// `format` is a `RenderFormat` value.
+//
+// # Safety
+// `input_json` must be a NUL-terminated UTF-8 string and `out` must point to
+// writable memory for a `RenderPages`. On success, release `out` with
+// `render_engine_free_pages`.
+enum RenderStatus render_engine_render_form(const char *input_json,
+                                            uint32_t format,
+                                            struct RenderPages *out);
+
+// Validate a memo form (JSON) against the official memorandum schema.
+// Returns `RENDER_STATUS_VALIDATION_ERROR` with details in the last error when invalid.
+//
+// # Safety
+// `input_json` must be a NUL-terminated UTF-8 string.
+enum RenderStatus render_engine_validate_form(const char *input_json);
+
+// Release pages produced by a render function and reset `pages` to empty.
+// Passing null or already-freed (empty) pages is a no-op.
+//
+// # Safety
+// `pages` must be null or point to a `RenderPages` filled in by this library
+// that has not been modified by the caller.
+void render_engine_free_pages(struct RenderPages *pages);
+
+// Message describing the last failure on the calling thread, or null if the
+// last call succeeded. The pointer stays valid until the next call into this
+// library on the same thread.
+const char *render_engine_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RENDER_ENGINE_H */
@@ -0,0 +1,341 @@
+//! # C ABI for Render Engine
+//!
+//! This crate exposes the render engine through a plain C interface so .NET,
+//! Java, Go and other runtimes can embed it directly. The header is generated
+//! with cbindgen (see `cbindgen.toml`) into `include/render_engine.h`.
+//!
+//! ## Conventions
+//!
+//! - All strings passed in are NUL-terminated UTF-8.
+//! - Formats are passed as a plain integer (`RENDER_FORMAT_SVG` or
+//!   `RENDER_FORMAT_PDF`); any other value is rejected as an invalid argument.
+//! - Functions return a `RenderStatus`; on failure a description is available
+//!   from `render_engine_last_error()` on the same thread.
+//! - Rendered pages are owned by the caller and must be released with
+//!   `render_engine_free_pages()`.
+//! - Panics are caught and reported as `RENDER_STATUS_INTERNAL_ERROR`. This
+//!   needs unwinding, so build with the `release-ffi` profile rather than
+//!   `--release`, whose `panic = "abort"` turns any panic into a process abort.
+//!
+//! ## Usage
+//!
+//! ```c
+//! RenderPages pages;
+//! if (render_engine_render_form(json, RENDER_FORMAT_PDF, &pages) == RENDER_STATUS_OK) {
+//!     fwrite(pages.pages[0].data, 1, pages.pages[0].len, file);
+//!     render_engine_free_pages(&pages);
+//! } else {
+//!     fprintf(stderr, "%s\n", render_engine_last_error());
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use render_engine::form_processor::validate_official_memo_schema;
+use render_engine::{render_form, render_markup, OutputFormat, RenderConfig, TypstWrapperError};
+
+/// Output format selector, passed to the render functions as a `uint32_t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    Svg = 0,
+    Pdf = 1,
+}
+
+impl RenderFormat {
+    /// Map a raw format value from C, or `None` if it names no format
+    fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(RenderFormat::Svg),
+            1 => Some(RenderFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// Result code returned by every fallible function
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStatus {
+    Ok = 0,
+    /// A pointer argument was null, a string was not valid UTF-8, or the
+    /// format was unknown
+    InvalidArgument = 1,
+    /// The form failed schema validation or preprocessing
+    ValidationError = 2,
+    /// Typst compilation or export failed
+    CompilationError = 3,
+    /// Any other failure, including internal panics (see the crate docs on
+    /// building with unwinding)
+    InternalError = 4,
+}
+
+/// A single rendered page (SVG text or PDF bytes)
+#[repr(C)]
+pub struct RenderBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Rendered pages, owned by the caller until passed to `render_engine_free_pages`
+#[repr(C)]
+pub struct RenderPages {
+    pub pages: *mut RenderBuffer,
+    pub count: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = None);
+}
+
+/// Record an error and return its status code
+fn fail(status: RenderStatus, message: String) -> RenderStatus {
+    set_last_error(message);
+    status
+}
+
+/// Borrow a C string argument as `&str`
+///
+/// # Safety
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, RenderStatus> {
+    if value.is_null() {
+        return Err(fail(RenderStatus::InvalidArgument, format!("{} must not be null", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| fail(RenderStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+fn status_for(error: &TypstWrapperError) -> RenderStatus {
+    match error {
+        TypstWrapperError::Validation(_) => RenderStatus::ValidationError,
+        TypstWrapperError::Compilation(_) => RenderStatus::CompilationError,
+        TypstWrapperError::Field { error, .. } | TypstWrapperError::Excerpt { error, .. } => status_for(error),
+        _ => RenderStatus::InternalError,
+    }
+}
+
+/// Move rendered pages into caller-owned C buffers
+fn into_pages(pages: Vec<Vec<u8>>) -> RenderPages {
+    let buffers: Box<[RenderBuffer]> = pages
+        .into_iter()
+        .map(|page| {
+            let bytes = page.into_boxed_slice();
+            let len = bytes.len();
+            RenderBuffer {
+                data: Box::into_raw(bytes) as *mut u8,
+                len,
+            }
+        })
+        .collect();
+    let count = buffers.len();
+    RenderPages {
+        pages: Box::into_raw(buffers) as *mut RenderBuffer,
+        count,
+    }
+}
+
+/// Shared implementation of the render entry points
+///
+/// # Safety
+/// `input` must be null or a NUL-terminated string; `out` must be null or valid for writes.
+unsafe fn render_with<F>(input: *const c_char, name: &str, format: u32, out: *mut RenderPages, render: F) -> RenderStatus
+where
+    F: FnOnce(&str, Option<RenderConfig>) -> Result<Vec<Vec<u8>>, TypstWrapperError>,
+{
+    clear_last_error();
+    if out.is_null() {
+        return fail(RenderStatus::InvalidArgument, "out must not be null".to_string());
+    }
+    let input = match read_str(input, name) {
+        Ok(input) => input,
+        Err(status) => return status,
+    };
+    let Some(format) = RenderFormat::from_raw(format) else {
+        return fail(RenderStatus::InvalidArgument, format!("unknown format {}", format));
+    };
+
+    let config = RenderConfig {
+        format: match format {
+            RenderFormat::Svg => OutputFormat::Svg,
+            RenderFormat::Pdf => OutputFormat::Pdf,
+        },
+        ..Default::default()
+    };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| render(input, Some(config)))) {
+        Ok(Ok(pages)) => {
+            out.write(into_pages(pages));
+            RenderStatus::Ok
+        }
+        Ok(Err(e)) => fail(status_for(&e), e.to_string()),
+        Err(_) => fail(RenderStatus::InternalError, "render panicked".to_string()),
+    }
+}
+
+/// Render Typst markup. SVG yields one buffer per page; PDF yields a single buffer.
+/// `format` is a `RenderFormat` value.
+///
+/// # Safety
+/// `markup` must be a NUL-terminated UTF-8 string and `out` must point to
+/// writable memory for a `RenderPages`. On success, release `out` with
+/// `render_engine_free_pages`.
+#[no_mangle]
+pub unsafe extern "C" fn render_engine_render_markup(
+    markup: *const c_char,
+    format: u32,
+    out: *mut RenderPages,
+) -> RenderStatus {
+    render_with(markup, "markup", format, out, render_markup)
+}
+
+/// Render a memo form (JSON) with the memo-loader template.
+/// `format` is a `RenderFormat` value.
+///
+/// # Safety
+/// `input_json` must be a NUL-terminated UTF-8 string and `out` must point to
+/// writable memory for a `RenderPages`. On success, release `out` with
+/// `render_engine_free_pages`.
+#[no_mangle]
+pub unsafe extern "C" fn render_engine_render_form(
+    input_json: *const c_char,
+    format: u32,
+    out: *mut RenderPages,
+) -> RenderStatus {
+    render_with(input_json, "input_json", format, out, render_form)
+}
+
+/// Validate a memo form (JSON) against the official memorandum schema.
+/// Returns `RENDER_STATUS_VALIDATION_ERROR` with details in the last error when invalid.
+///
+/// # Safety
+/// `input_json` must be a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn render_engine_validate_form(input_json: *const c_char) -> RenderStatus {
+    clear_last_error();
+    let input = match read_str(input_json, "input_json") {
+        Ok(input) => input,
+        Err(status) => return status,
+    };
+    match panic::catch_unwind(|| validate_official_memo_schema(input)) {
+        Ok(Ok(())) => RenderStatus::Ok,
+        Ok(Err(e)) => fail(RenderStatus::ValidationError, e.to_string()),
+        Err(_) => fail(RenderStatus::InternalError, "validation panicked".to_string()),
+    }
+}
+
+/// Release pages produced by a render function and reset `pages` to empty.
+/// Passing null or already-freed (empty) pages is a no-op.
+///
+/// # Safety
+/// `pages` must be null or point to a `RenderPages` filled in by this library
+/// that has not been modified by the caller.
+#[no_mangle]
+pub unsafe extern "C" fn render_engine_free_pages(pages: *mut RenderPages) {
+    if pages.is_null() || (*pages).pages.is_null() {
+        return;
+    }
+    let buffers = Box::from_raw(ptr::slice_from_raw_parts_mut((*pages).pages, (*pages).count));
+    for buffer in buffers.iter() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+    (*pages).pages = ptr::null_mut();
+    (*pages).count = 0;
+}
+
+/// Message describing the last failure on the calling thread, or null if the
+/// last call succeeded. The pointer stays valid until the next call into this
+/// library on the same thread.
+#[no_mangle]
+pub extern "C" fn render_engine_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_pages() -> RenderPages {
+        RenderPages {
+            pages: ptr::null_mut(),
+            count: 0,
+        }
+    }
+
+    fn last_error() -> String {
+        let message = render_engine_last_error();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_render_markup_pdf() {
+        let markup = CString::new("= Hello from C").unwrap();
+        let mut pages = empty_pages();
+
+        let status = unsafe { render_engine_render_markup(markup.as_ptr(), RenderFormat::Pdf as u32, &mut pages) };
+        assert_eq!(status, RenderStatus::Ok);
+        assert!(render_engine_last_error().is_null());
+        assert_eq!(pages.count, 1);
+
+        let pdf = unsafe { std::slice::from_raw_parts((*pages.pages).data, (*pages.pages).len) };
+        assert!(pdf.starts_with(b"%PDF"));
+
+        unsafe { render_engine_free_pages(&mut pages) };
+        assert!(pages.pages.is_null());
+        assert_eq!(pages.count, 0);
+    }
+
+    #[test]
+    fn test_render_form_svg() {
+        let json = CString::new(
+            r#"{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"Hi"}"#,
+        )
+        .unwrap();
+        let mut pages = empty_pages();
+
+        let status = unsafe { render_engine_render_form(json.as_ptr(), RenderFormat::Svg as u32, &mut pages) };
+        assert_eq!(status, RenderStatus::Ok);
+        assert!(pages.count >= 1);
+        unsafe { render_engine_free_pages(&mut pages) };
+    }
+
+    #[test]
+    fn test_errors_are_reported() {
+        let mut pages = empty_pages();
+        let status = unsafe { render_engine_render_markup(ptr::null(), RenderFormat::Svg as u32, &mut pages) };
+        assert_eq!(status, RenderStatus::InvalidArgument);
+        assert!(last_error().contains("markup"));
+
+        let markup = CString::new("#undefined-fn()").unwrap();
+        let status = unsafe { render_engine_render_markup(markup.as_ptr(), RenderFormat::Svg as u32, &mut pages) };
+        assert_eq!(status, RenderStatus::CompilationError);
+        assert!(last_error().contains("Compilation failed"));
+        assert!(pages.pages.is_null());
+
+        let status = unsafe { render_engine_render_markup(markup.as_ptr(), 7, &mut pages) };
+        assert_eq!(status, RenderStatus::InvalidArgument);
+        assert!(last_error().contains("unknown format 7"));
+        assert!(pages.pages.is_null());
+    }
+
+    #[test]
+    fn test_validate_form() {
+        let invalid = CString::new(r#"{"subject":"Missing fields"}"#).unwrap();
+        assert_eq!(unsafe { render_engine_validate_form(invalid.as_ptr()) }, RenderStatus::ValidationError);
+        assert!(!last_error().is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! # Node.js Native Bindings for Render Engine
+//!
+//! This crate exposes the render engine to Node.js as a native addon built
+//! with napi-rs, avoiding the wasm overhead and single-threading of the wasm
+//! wrapper for server-side users.
+//!
+//! ## Features
+//!
+//! - Same render/validate API as the wasm wrapper
+//! - Async methods returning Promises; renders run on the libuv thread pool
+//!   so the event loop stays free
+//! - Synchronous variants for scripts and CLIs
+//! - Safe to load from `worker_threads`: the addon is context-aware and the
+//!   engine only shares immutable embedded assets and lock-protected
+//!   registries between threads
+//!
+//! ## Usage
+//!
+//! ```javascript
+//! const { renderMarkup, renderForm, validateForm } = require('@tonguetoquill/render-engine-node');
+//!
+//! // Render Typst markup to SVG pages
+//! const pages = await renderMarkup('= Hello World', 'svg');
+//!
+//! // Render a memo form to PDF
+//! const [pdf] = await renderForm(JSON.stringify(formData), 'pdf');
+//! fs.writeFileSync('memo.pdf', pdf);
+//!
+//! // Validate a memo form before rendering, marking every invalid field
+//! const { valid, errors } = validateForm(JSON.stringify(formData));
+//! for (const error of errors) markInvalid(error.path, error.message);
+//! ```
+
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Error, Result, Status, Task};
+use napi_derive::napi;
+use render_engine::form_processor::official_memo_schema_errors;
+use render_engine::{
+    render_form as engine_render_form, render_markup as engine_render_markup, ErrorPayload, OutputFormat, RenderConfig,
+};
+
+/// What a render task compiles
+enum RenderInput {
+    Markup(String),
+    Form(String),
+}
+
+/// Render job executed on the libuv thread pool
+pub struct RenderTask {
+    input: RenderInput,
+    config: RenderConfig,
+}
+
+impl RenderTask {
+    fn new(input: RenderInput, format: Option<String>) -> Result<Self> {
+        Ok(Self {
+            input,
+            config: RenderConfig {
+                format: parse_format(format.as_deref())?,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn run(&self) -> Result<Vec<Vec<u8>>> {
+        let result = match &self.input {
+            RenderInput::Markup(markup) => engine_render_markup(markup, Some(self.config.clone())),
+            RenderInput::Form(form_json) => engine_render_form(form_json, Some(self.config.clone())),
+        };
+        result.map_err(|e| Error::new(Status::GenericFailure, format!("Render failed: {}", e)))
+    }
+}
+
+impl Task for RenderTask {
+    type Output = Vec<Vec<u8>>;
+    type JsValue = Vec<Buffer>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.run()
+    }
+
+    fn resolve(&mut self, _env: Env, pages: Self::Output) -> Result<Self::JsValue> {
+        Ok(pages.into_iter().map(Buffer::from).collect())
+    }
+}
+
+/// Result of validating a memo form
+#[napi(object)]
+pub struct ValidationResult {
+    pub valid: bool,
+    /// Every schema violation of the form, empty when it is valid
+    pub errors: Vec<FormError>,
+}
+
+/// A schema violation, shaped like the wasm wrapper's `FormError`: the
+/// engine's `ErrorPayload` with the severity diagnostics carry
+#[napi(object)]
+pub struct FormError {
+    pub kind: String,
+    pub code: String,
+    pub message: String,
+    /// JSON pointer of the offending field; absent for forms that are not JSON
+    pub path: Option<String>,
+    /// Always `"error"`
+    pub severity: String,
+}
+
+impl From<ErrorPayload> for FormError {
+    fn from(payload: ErrorPayload) -> Self {
+        Self {
+            kind: payload.kind,
+            code: payload.code,
+            message: payload.message,
+            path: payload.path,
+            severity: "error".to_string(),
+        }
+    }
+}
+
+/// Parse a format name, case-insensitive, defaulting to SVG when absent
+fn parse_format(format: Option<&str>) -> Result<OutputFormat> {
+    let Some(name) = format else {
+        return Ok(OutputFormat::Svg);
+    };
+    [OutputFormat::Svg, OutputFormat::Pdf, OutputFormat::SvgZip]
+        .into_iter()
+        .find(|candidate| name.eq_ignore_ascii_case(candidate.name()))
+        .ok_or_else(|| Error::new(Status::InvalidArg, format!("Unknown format '{}': use svg, pdf or svg-zip", name)))
+}
+
+/// Render Typst markup to SVG pages or a single PDF, resolving to an array of Buffers.
+#[napi(ts_return_type = "Promise<Array<Buffer>>")]
+pub fn render_markup(markup: String, format: Option<String>) -> Result<AsyncTask<RenderTask>> {
+    Ok(AsyncTask::new(RenderTask::new(RenderInput::Markup(markup), format)?))
+}
+
+/// Render a memo form (JSON string) to SVG pages or a single PDF, resolving to an array of Buffers.
+#[napi(ts_return_type = "Promise<Array<Buffer>>")]
+pub fn render_form(input_json: String, format: Option<String>) -> Result<AsyncTask<RenderTask>> {
+    Ok(AsyncTask::new(RenderTask::new(RenderInput::Form(input_json), format)?))
+}
+
+/// Synchronous variant of `renderMarkup`; blocks the calling thread.
+#[napi]
+pub fn render_markup_sync(markup: String, format: Option<String>) -> Result<Vec<Buffer>> {
+    let pages = RenderTask::new(RenderInput::Markup(markup), format)?.run()?;
+    Ok(pages.into_iter().map(Buffer::from).collect())
+}
+
+/// Synchronous variant of `renderForm`; blocks the calling thread.
+#[napi]
+pub fn render_form_sync(input_json: String, format: Option<String>) -> Result<Vec<Buffer>> {
+    let pages = RenderTask::new(RenderInput::Form(input_json), format)?.run()?;
+    Ok(pages.into_iter().map(Buffer::from).collect())
+}
+
+/// Validate a memo form (JSON string) against the official memorandum schema,
+/// reporting every violation rather than the first.
+#[napi]
+pub fn validate_form(input_json: String) -> ValidationResult {
+    let errors: Vec<FormError> = official_memo_schema_errors(&input_json)
+        .iter()
+        .map(|error| ErrorPayload::from(error).into())
+        .collect();
+    ValidationResult {
+        valid: errors.is_empty(),
+        errors,
+    }
+}
@@ -0,0 +1,19 @@
+//! Fuzz `DeltaParser::parse` with arbitrary text.
+//!
+//! Raw input rarely forms a valid Delta, so JSON arrays are additionally
+//! wrapped as `{"ops": [...]}` to reach the op handling code.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use render_engine::DeltaParser;
+use serde_json::Value;
+
+fuzz_target!(|data: &str| {
+    let parser = DeltaParser::new();
+    let _ = parser.parse(data);
+
+    if let Ok(ops @ Value::Array(_)) = serde_json::from_str::<Value>(data) {
+        let delta = serde_json::json!({ "ops": ops }).to_string();
+        let _ = parser.parse(&delta);
+    }
+});
@@ -0,0 +1,14 @@
+//! Fuzz `validate_and_preprocess_form_json` with arbitrary text.
+//!
+//! Preprocessed output is fed to the template as JSON, so it must always
+//! parse back.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use render_engine::form_processor::validate_and_preprocess_form_json;
+
+fuzz_target!(|data: &str| {
+    if let Ok(processed) = validate_and_preprocess_form_json(data) {
+        assert!(serde_json::from_str::<serde_json::Value>(&processed).is_ok());
+    }
+});
@@ -1,26 +1,40 @@
 #import "@preview/tonguetoquill-usaf-memo:latest": official-memorandum, indorsement
+#import "partials/letterhead.typ": letterhead
+#import "partials/body.typ": body
+#import "partials/signature.typ": signature-block
+#import "partials/footer.typ": footer
 
 
 #let input = json("input.json")
 #let try_get(key, default) = if key not in input { default } else { input.at(key) }
 
+// Source maps: when the engine asks for one, each field is wrapped in
+// metadata naming its JSON pointer, so rendered text can be traced back
+#let source-map = try_get("source-map", none)
+#let mark(field, body) = if source-map == none { body } else {
+  [#metadata(field) <source-start>#body#metadata(none) <source-end>]
+}
+#let mark-lines(field, lines) = if lines == none { none } else {
+  lines.enumerate().map(((i, line)) => mark(field + "/" + str(i), line))
+}
+
+// Parse ISO date strings (supports both YYYY-MM-DD and YYYY-MM-DDTHH:MM:SSZ formats)
+#let parse-iso-date(iso-string) = {
+  // Extract just the date part (YYYY-MM-DD) from ISO string
+  let date-part = if iso-string.contains("T") {
+    iso-string.split("T").at(0)
+  } else {
+    iso-string
+  }
+
+  // Parse using TOML date format
+  let toml-content = "date = " + date-part
+  let parsed = toml(bytes(toml-content))
+  parsed.date
+}
+
 // Parse the date from input, supporting ISO formats
 #let parsed-datetime = if "date" in input {
-  // Parse ISO date strings (supports both YYYY-MM-DD and YYYY-MM-DDTHH:MM:SSZ formats)
-  let parse-iso-date(iso-string) = {
-    // Extract just the date part (YYYY-MM-DD) from ISO string
-    let date-part = if iso-string.contains("T") {
-      iso-string.split("T").at(0)
-    } else {
-      iso-string
-    }
-    
-    // Parse using TOML date format
-    let toml-content = "date = " + date-part
-    let parsed = toml(bytes(toml-content))
-    parsed.date
-  }
-  
   let parsed-date = parse-iso-date(input.date)
   assert(type(parsed-date) == datetime, message: "Error: 'date' must be in ISO (YYYY-MM-DD) or (YYYY-MM-DDTHH:MM:SSZ) format")
   parsed-date
@@ -28,35 +42,83 @@
   datetime.today()
 }
 
+// References are strings or `(title, date, link, type)` objects: the type
+// goes before the title, the date after it in the memo's date format, and
+// the title links to the document
+#let reference(item) = if type(item) == str { item } else {
+  let title = if "link" in item { link(item.link, item.title) } else { item.title }
+  [#if "type" in item [#item.type ]#title#if "date" in item [, #parse-iso-date(item.date).display("[day padding:none] [month repr:long] [year]")]]
+}
+#let references-of(items) = if items == none { none } else { items.map(reference) }
+
+// Recipients are strings or `(office-symbol, organization, location)`
+// objects, written ORGANIZATION/OFFICE-SYMBOL with the location after a comma
+#let recipient(item) = if type(item) == str { item } else {
+  let name = ("organization", "office-symbol").filter(key => key in item).map(key => item.at(key)).join("/")
+  if "location" in item { name + ", " + item.location } else { name }
+}
+
+// Co-signers' blocks get the same labels and fields as the signature
+// block, but sign to its left or above it, so their date fields go below
+#let co-signers = try_get("co-signers", ()).map(lines => lines.enumerate().map(((i, line)) => if i == 0 {
+  box[#metadata("Signature") <signature-field>#metadata(none) <signature-block>#line]
+} else if i == lines.len() - 1 {
+  box[#place(dy: 3em, [#metadata("Date") <date-field>])#line]
+} else {
+  line
+}))
+
+// The subject doubles as the document title, which PDF viewers show and
+// PDF/X requires
+#set document(title: input.subject)
+
+// Full-page letterhead background (pre-printed stationery simulation).
+// Images have no opacity control, so a translucent white layer washes the
+// background out to a print-safe strength behind the memo content.
+#let letterhead-background = try_get("letterhead-background", none)
+#let letterhead-background-opacity = try_get("letterhead-background-opacity", 0.3)
+#set page(background: if letterhead-background != none {
+  place(top + left, image("letterheads/" + letterhead-background, width: 100%, height: 100%, fit: "stretch", alt: "Letterhead background"))
+  place(top + left, rect(width: 100%, height: 100%, fill: white.transparentize(letterhead-background-opacity * 100%)))
+})
+
+#show: footer(input, parsed-datetime)
+
+// Drafts show when they were rendered below the footer of every page
+#let draft-timestamp = try_get("draft-timestamp", none)
+#set page(foreground: if draft-timestamp != none {
+  place(bottom + left, dx: 1in, dy: -0.3in, text(size: 8pt)[DRAFT rendered #draft-timestamp])
+})
+
+// Numbered paragraphs are bookmarked with their number and opening words.
+// The marker goes inside each item's body so the items stay one list.
+#let paragraph-marked(body) = body.func() == [].func() and body.children.len() > 0 and body.children.first().has("label") and body.children.first().label == <paragraph>
+#show enum.item: it => if paragraph-marked(it.body) { it } else {
+  enum.item(it.number, [#metadata((number: it.number, body: it.body)) <paragraph>#it.body])
+}
+
 // Generate the official memorandum with validated and processed input
+#let letterhead-args = letterhead(input)
 #official-memorandum(
-  // Letterhead configuration
-  letterhead-title: try_get("letterhead-title", "DEPARTMENT OF THE AIR FORCE"),
-  letterhead-caption: try_get("letterhead-caption", "123RD EXAMPLE SQUADRON"),
-  letterhead-seal: image("assets/dod_seal.gif"),
-  letterhead-font: "Copperplate CC",
+  ..letterhead-args,
+  letterhead-title: mark("/letterhead-title", letterhead-args.letterhead-title),
+  letterhead-caption: mark("/letterhead-caption", letterhead-args.letterhead-caption),
 
   // Date
   date: parsed-datetime,
   
   // Recipients
-  memo-for: input.memo-for,
+  memo-for: mark-lines("/memo-for", input.memo-for.map(recipient)),
   
   // Sender information
-  from-block: input.from-block,
+  from-block: mark-lines("/from-block", input.from-block),
   
   // Subject line
-  subject: input.subject,
+  subject: mark("/subject", input.subject),
   
   // Optional references
-  references: try_get("references", none),
+  references: mark-lines("/references", references-of(try_get("references", none))),
   
-  // Signature block
-  signature-block: input.signature-block,
-  
-)[
-  // Body content from JSON
-  #eval(input.body_raw, mode: "markup")
-]
-
-
+  signature-block: mark-lines("/signature-block", signature-block(input)),
+  co-signers: co-signers.enumerate().map(((i, lines)) => mark-lines("/co-signers/" + str(i), lines)),
+)[#if source-map == none { body(input) } else { mark(source-map.body, body(input)) }]
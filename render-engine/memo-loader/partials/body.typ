@@ -0,0 +1,17 @@
+// Body partial: the memo body for the form `input`.
+//
+// The `font-size`, `line-spacing` and `paragraph-spacing` style tokens set
+// the body text, and headings take the accent color when `accent-on` lists
+// them.
+#let body(input) = {
+  let style = input.at("style", default: none)
+  if style == none { style = (:) }
+  let accented = "accent-color" in style and "headings" in style.at("accent-on", default: ())
+  set text(size: style.at("font-size", default: 12) * 1pt) if "font-size" in style
+  set par(leading: style.at("line-spacing", default: 0) * 1em) if "line-spacing" in style
+  set par(spacing: style.at("paragraph-spacing", default: 0) * 1em) if "paragraph-spacing" in style
+  show heading: it => if accented { text(fill: rgb(style.accent-color), it) } else { it }
+  // Bookmark the memo by its subject; body headings nest under it
+  [#metadata(input.subject) <bookmark>]
+  eval(input.body_raw, mode: "markup")
+}
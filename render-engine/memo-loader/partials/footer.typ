@@ -0,0 +1,56 @@
+// Footer partial: page numbers and running headers and footers for the form
+// `input`, applied with `#show: footer(input, date)`.
+//
+// The form preprocessor normalizes them. `page-numbering: false` turns page
+// numbers off; otherwise the number and the running texts share the left,
+// center and right slots of the header and footer, with `{page}`, `{total}`
+// and `{date}` filled in.
+#let footer(input, date) = doc => {
+  let page-numbering = input.at("page-numbering", default: none)
+  let numbered = page-numbering not in (none, false)
+  let running-header = input.at("running-header", default: none)
+  let running-footer = input.at("running-footer", default: none)
+
+  let fill-placeholders(text) = {
+    text
+      .replace("{page}", str(counter(page).get().first()))
+      .replace("{total}", str(counter(page).final().first()))
+      .replace("{date}", date.display("[day padding:none] [month repr:long] [year]"))
+  }
+
+  let page-slots(vertical, running) = context {
+    let current = here().page()
+    let slots = (left: (), center: (), right: ())
+    let shown = if running == none { false } else if running.pages == "all" { true } else if running.pages == "first" {
+      current == 1
+    } else if running.pages == "subsequent" { current > 1 } else { current in running.pages }
+    if shown {
+      for side in ("left", "center", "right") {
+        if running.at(side, default: none) != none {
+          slots.at(side).push(fill-placeholders(running.at(side)))
+        }
+      }
+    }
+    if numbered and page-numbering.position.starts-with(vertical) and (page-numbering.first-page or current > 1) {
+      slots.at(page-numbering.position.split("-").at(1)).push(fill-placeholders(page-numbering.format))
+    }
+    grid(
+      columns: (1fr, auto, 1fr),
+      align(left, slots.left.join(h(1em))),
+      align(center, slots.center.join(h(1em))),
+      align(right, slots.right.join(h(1em))),
+    )
+  }
+
+  set page(..if page-numbering == false { (numbering: none, footer: none) })
+  set page(..if running-header != none or (numbered and page-numbering.position.starts-with("top")) {
+    (header: page-slots("top", running-header))
+  })
+  set page(..if running-footer != none or (numbered and page-numbering.position.starts-with("bottom")) {
+    (footer: page-slots("bottom", running-footer))
+  })
+  if numbered {
+    counter(page).update(page-numbering.start)
+  }
+  doc
+}
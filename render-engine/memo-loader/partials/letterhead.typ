@@ -0,0 +1,25 @@
+// Letterhead partial: the letterhead arguments of `official-memorandum`
+// for the form `input`.
+//
+// The `letterhead-font-size` and `seal-size` style tokens size the title
+// and seal, and the accent color applies to the title and caption unless
+// `accent-on` leaves out the letterhead.
+#let letterhead(input) = {
+  let style = input.at("style", default: none)
+  if style == none { style = (:) }
+  let accent = if "accent-color" in style and "letterhead" in style.at("accent-on", default: ("letterhead",)) {
+    rgb(style.accent-color)
+  }
+  let title = input.at("letterhead-title", default: "DEPARTMENT OF THE AIR FORCE")
+  let caption = input.at("letterhead-caption", default: "123RD EXAMPLE SQUADRON")
+  (
+    letterhead-title: if "letterhead-font-size" in style or accent != none {
+      text(size: style.at("letterhead-font-size", default: 12) * 1pt, fill: if accent != none { accent } else { black }, title)
+    } else { title },
+    letterhead-caption: if accent != none { text(fill: accent, caption) } else { caption },
+    letterhead-seal: if "seal-size" in style {
+      image("/assets/dod_seal.gif", width: style.seal-size * 1in, alt: "Department of Defense seal")
+    } else { image("/assets/dod_seal.gif", alt: "Department of Defense seal") },
+    letterhead-font: "Copperplate CC",
+  )
+}
@@ -0,0 +1,10 @@
+// Signature partial: the lines of the signature block for the form `input`.
+//
+// The first line is labelled so hosts can place a signature stamp over it.
+// Fillable PDF output gets a signature field above it and a date field to
+// its left.
+#let signature-block(input) = input.signature-block.enumerate().map(((i, line)) => if i == 0 {
+  box[#place(dx: -2in, [#metadata("Date") <date-field>])#metadata("Signature") <signature-field>#metadata(none) <signature-block>#line]
+} else {
+  line
+})
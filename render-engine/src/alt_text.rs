@@ -0,0 +1,113 @@
+//! Alternative text of images.
+//!
+//! Section 508 review expects every image in a memo to describe itself. An
+//! image's `alt` text, from `image(.., alt: ..)` in markup or the `alt`
+//! attribute of a Delta image embed, is written to PDF output by the PDF
+//! export. The SVG export drops it, so each SVG `<image>` gets it back as
+//! `role="img"`, an `aria-label` and a `<title>`. Images without alt text
+//! produce a warning that reaches render observers and editor diagnostics.
+
+use typst::diag::SourceDiagnostic;
+use typst::layout::{Frame, FrameItem, Page, PagedDocument};
+use typst::syntax::Span;
+
+/// A page's SVG with the alt text of its images
+pub(crate) fn svg_with_alt_text(svg: String, page: &Page) -> String {
+    let mut alts = Vec::new();
+    images(&page.frame, &mut |item| alts.push(item.0.alt().map(escape)));
+    if alts.iter().all(Option::is_none) {
+        return svg;
+    }
+
+    // The export writes the page's images in frame order, before any glyph
+    // definitions that may hold images of their own
+    let mut result = String::with_capacity(svg.len());
+    let mut rest = svg.as_str();
+    for alt in alts {
+        let Some(start) = rest.find("<image ") else {
+            break;
+        };
+        let Some(end) = rest[start..].find("/>").map(|end| start + end) else {
+            break;
+        };
+        result.push_str(&rest[..end]);
+        match alt {
+            Some(alt) => result.push_str(&format!(" role=\"img\" aria-label=\"{alt}\"><title>{alt}</title></image>")),
+            None => result.push_str("/>"),
+        }
+        rest = &rest[end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Warnings for the images of `document` without alt text, one per image
+/// in the source
+pub(crate) fn missing_alt_text(document: &PagedDocument) -> Vec<SourceDiagnostic> {
+    let mut spans: Vec<Span> = Vec::new();
+    for page in &document.pages {
+        images(&page.frame, &mut |(image, span)| {
+            if image.alt().is_none() && !spans.contains(&span) {
+                spans.push(span);
+            }
+        });
+    }
+    spans
+        .into_iter()
+        .map(|span| {
+            SourceDiagnostic::warning(span, "image has no alt text")
+                .with_hint("describe the image with `alt`, e.g. `image(\"seal.png\", alt: \"Unit seal\")`")
+        })
+        .collect()
+}
+
+/// Call `found` with every image of `frame` in drawing order
+fn images(frame: &Frame, found: &mut impl FnMut((&typst::visualize::Image, Span))) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => images(&group.frame, found),
+            FrameItem::Image(image, _, span) => found((image, *span)),
+            _ => {}
+        }
+    }
+}
+
+/// `text` escaped for XML text and attribute values
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typst_wrapper::TypstWrapper;
+
+    const MARKUP: &str = "#image(\"/assets/dod_seal.gif\", width: 1in, alt: \"Seal & <crest>\")\n#image(\"/assets/dod_seal.gif\", width: 2in)";
+
+    #[test]
+    fn test_svg_gets_alt_text() {
+        let document = TypstWrapper::compile_markup(MARKUP).unwrap();
+        let svg = svg_with_alt_text(typst_svg::svg(&document.pages[0]), &document.pages[0]);
+        assert_eq!(svg.matches("<image ").count(), 2);
+        assert!(svg.contains("role=\"img\" aria-label=\"Seal &amp; &lt;crest&gt;\"><title>Seal &amp; &lt;crest&gt;</title></image>"), "{}", svg);
+        assert_eq!(svg.matches("aria-label").count(), 1);
+    }
+
+    #[test]
+    fn test_images_without_alt_text_warn() {
+        let document = TypstWrapper::compile_markup(MARKUP).unwrap();
+        let warnings = missing_alt_text(&document);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "image has no alt text");
+    }
+}
@@ -0,0 +1,323 @@
+//! Review comments drawn over the document.
+//!
+//! During coordination, reviewers leave notes on a memo without editing it.
+//! Each [`Annotation`] is placed either on a rectangle of a page or on the
+//! first occurrence of a piece of text, and set with
+//! [`RenderConfig::annotations`](crate::RenderConfig::annotations). SVG
+//! pages get an overlay layer with a highlight and the note below it; PDF
+//! output gets standard square annotations carrying the author and note,
+//! which viewers list as comments and print with the page.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use typst::layout::{Abs, Frame, FrameItem, PagedDocument, Point, Transform};
+
+use crate::pdf::{exported_page, text_string, PdfUpdate};
+use crate::text_layout::text_bounds;
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Highlight fill, as `#rrggbb` for SVG and RGB components for PDF
+const FILL: (&str, &str) = ("#ffd54f", "1 0.835 0.31");
+/// Highlight outline
+const OUTLINE: (&str, &str) = ("#f9a825", "0.976 0.659 0.145");
+/// Opacity of the highlight fill
+const FILL_OPACITY: f64 = 0.35;
+/// Font size of the note in SVG overlays
+const NOTE_SIZE: f64 = 7.0;
+
+/// A review comment
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, Annotation, RenderConfig};
+///
+/// let config = RenderConfig {
+///     annotations: vec![Annotation::anchored("Hello", "Say hi instead")],
+///     ..RenderConfig::default()
+/// };
+/// let pages = render_markup("Hello World", Some(config)).unwrap();
+/// assert!(String::from_utf8_lossy(&pages[0]).contains("Say hi instead"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Page index, starting at 0. Required with `rect`; with `anchor`, only
+    /// this page is searched.
+    #[serde(default)]
+    pub page: Option<usize>,
+    /// Region in points from the top left corner of the page
+    #[serde(default)]
+    pub rect: Option<AnnotationRect>,
+    /// Text the note is about; its first occurrence within one line is used
+    #[serde(default)]
+    pub anchor: Option<String>,
+    #[serde(default)]
+    pub author: String,
+    pub note: String,
+}
+
+/// A rectangle of a page, in points from its top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Annotation {
+    /// A note about the first occurrence of `anchor`
+    pub fn anchored(anchor: impl Into<String>, note: impl Into<String>) -> Self {
+        Self {
+            page: None,
+            rect: None,
+            anchor: Some(anchor.into()),
+            author: String::new(),
+            note: note.into(),
+        }
+    }
+
+    /// The note as shown to readers, prefixed with the author if known
+    fn label(&self) -> String {
+        match self.author.trim() {
+            "" => self.note.clone(),
+            author => format!("{}: {}", author, self.note),
+        }
+    }
+}
+
+/// An annotation with its place in the document
+struct Placed<'a> {
+    annotation: &'a Annotation,
+    page: usize,
+    rect: AnnotationRect,
+}
+
+/// Find where every annotation goes, failing on the first that cannot be
+/// placed
+fn place<'a>(document: &PagedDocument, annotations: &'a [Annotation]) -> Result<Vec<Placed<'a>>, TypstWrapperError> {
+    let invalid = |index: usize, problem: String| TypstWrapperError::Validation(format!("Annotation {}: {}", index + 1, problem));
+    let mut placed = Vec::with_capacity(annotations.len());
+    for (index, annotation) in annotations.iter().enumerate() {
+        if annotation.page.is_some_and(|page| page >= document.pages.len()) {
+            return Err(invalid(index, format!("the document has no page index {}", annotation.page.unwrap_or_default())));
+        }
+        let (page, rect) = match (&annotation.rect, &annotation.anchor) {
+            (Some(rect), None) => {
+                let page = annotation.page.ok_or_else(|| invalid(index, "a rect needs a page".to_string()))?;
+                if !(rect.width >= 0.0 && rect.height >= 0.0) {
+                    return Err(invalid(index, "the rect has a negative size".to_string()));
+                }
+                (page, *rect)
+            }
+            (None, Some(anchor)) if !anchor.trim().is_empty() => {
+                find(document, anchor, annotation.page).ok_or_else(|| invalid(index, format!("anchor text '{}' not found", anchor)))?
+            }
+            (None, Some(_)) => return Err(invalid(index, "the anchor text is empty".to_string())),
+            _ => return Err(invalid(index, "set either a rect or an anchor".to_string())),
+        };
+        placed.push(Placed { annotation, page, rect });
+    }
+    Ok(placed)
+}
+
+/// Check that every annotation can be placed in `document`
+pub(crate) fn check(document: &PagedDocument, annotations: &[Annotation]) -> Result<(), TypstWrapperError> {
+    place(document, annotations).map(drop)
+}
+
+/// Page and bounds of the first occurrence of `anchor` within a text run
+fn find(document: &PagedDocument, anchor: &str, page: Option<usize>) -> Option<(usize, AnnotationRect)> {
+    document.pages.iter().enumerate().filter(|(index, _)| page.is_none_or(|page| page == *index)).find_map(|(index, page)| {
+        find_in_frame(&page.frame, Transform::identity(), anchor).map(|(min, max)| {
+            let rect = AnnotationRect {
+                x: min.x.to_pt(),
+                y: min.y.to_pt(),
+                width: (max.x - min.x).to_pt(),
+                height: (max.y - min.y).to_pt(),
+            };
+            (index, rect)
+        })
+    })
+}
+
+fn find_in_frame(frame: &Frame, transform: Transform, anchor: &str) -> Option<(Point, Point)> {
+    frame.items().find_map(|(pos, item)| {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => find_in_frame(&group.frame, transform.pre_concat(group.transform), anchor),
+            FrameItem::Text(text) => {
+                let start = text.text.find(anchor)?;
+                let end = start + anchor.len();
+                // The glyphs shaped from the anchor's bytes
+                let (mut x, mut from, mut to) = (Abs::zero(), None, Abs::zero());
+                for glyph in &text.glyphs {
+                    let advance = glyph.x_advance.at(text.size);
+                    let range = glyph.range();
+                    if range.start < end && range.end > start {
+                        from.get_or_insert(x);
+                        to = x + advance;
+                    }
+                    x += advance;
+                }
+                Some(text_bounds(text, transform, from?, to))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// A page's SVG with the annotations on it drawn on top
+pub(crate) fn svg_with_annotations(svg: String, document: &PagedDocument, page: usize, annotations: &[Annotation]) -> String {
+    let Ok(placed) = place(document, annotations) else {
+        return svg;
+    };
+    let mut layer = String::new();
+    for placed in placed.iter().filter(|placed| placed.page == page) {
+        let AnnotationRect { x, y, width, height } = placed.rect;
+        let label = escape(&placed.annotation.label());
+        let _ = write!(
+            layer,
+            "<g class=\"annotation\"><title>{label}</title>\
+             <rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" fill=\"{}\" fill-opacity=\"{FILL_OPACITY}\" \
+             stroke=\"{}\" stroke-width=\"0.75\"/>\
+             <text x=\"{x}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{NOTE_SIZE}\" fill=\"#5d4037\" \
+             stroke=\"#fff\" stroke-width=\"2\" paint-order=\"stroke\">{label}</text></g>",
+            FILL.0,
+            OUTLINE.0,
+            y + height + NOTE_SIZE + 1.0,
+        );
+    }
+    match svg.rfind("</svg>") {
+        Some(end) if !layer.is_empty() => {
+            let mut svg = svg;
+            svg.insert_str(end, &format!("<g class=\"annotations\">{}</g>", layer));
+            svg
+        }
+        _ => svg,
+    }
+}
+
+/// Add the annotations to an exported PDF of the document pages `exported`
+/// as square annotations, leaving out those on other pages
+pub(crate) fn add_to_pdf(
+    document: &PagedDocument,
+    pdf: Vec<u8>,
+    annotations: &[Annotation],
+    exported: Range<usize>,
+) -> Result<Vec<u8>, TypstWrapperError> {
+    if annotations.is_empty() {
+        return Ok(pdf);
+    }
+    let placed = place(document, annotations)?;
+    let error = |e: String| TypstWrapperError::OutputFormat(format!("PDF annotations could not be written: {}", e));
+    let mut update = PdfUpdate::parse(&pdf).map_err(error)?;
+    let pages = update.pages().map_err(error)?;
+    for placed in &placed {
+        let Some(index) = exported_page(placed.page, &exported) else {
+            continue;
+        };
+        let page = *pages.get(index).ok_or_else(|| error("missing page".to_string()))?;
+        let (_, page_height) = update.page_size(page).ok_or_else(|| error("missing page size".to_string()))?;
+        let AnnotationRect { x, y, width, height } = placed.rect;
+        let bottom = page_height - y - height;
+
+        let mut content = String::new();
+        let _ = writeln!(content, "q /Fill gs {} rg 0 0 {:.3} {:.3} re f Q", FILL.1, width, height);
+        let _ = writeln!(
+            content,
+            "q {} RG 0.75 w 0.375 0.375 {:.3} {:.3} re S Q",
+            OUTLINE.1,
+            (width - 0.75).max(0.0),
+            (height - 0.75).max(0.0)
+        );
+        let appearance = update.allocate();
+        update.set(
+            appearance,
+            format!(
+                "<< /Type /XObject /Subtype /Form /BBox [0 0 {:.3} {:.3}] \
+                 /Resources << /ExtGState << /Fill << /ca {} >> >> >> /Length {} >>\nstream\n{}endstream",
+                width,
+                height,
+                FILL_OPACITY,
+                content.len(),
+                content
+            ),
+        );
+
+        let annotation = update.allocate();
+        update.set(
+            annotation,
+            format!(
+                "<<\n  /Type /Annot\n  /Subtype /Square\n  /Rect [{:.3} {:.3} {:.3} {:.3}]\n  /F 4\n  /Contents {}\n  \
+                 /T {}\n  /C [{}]\n  /IC [{}]\n  /AP << /N {} 0 R >>\n>>",
+                x,
+                bottom,
+                x + width,
+                bottom + height,
+                text_string(&placed.annotation.note),
+                text_string(&placed.annotation.author),
+                OUTLINE.1,
+                FILL.1,
+                appearance
+            ),
+        );
+        update.add_annotation(page, annotation).map_err(error)?;
+    }
+    Ok(update.finish().0)
+}
+
+/// `text` escaped for XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(all(test, feature = "font-times"))]
+mod tests {
+    use super::*;
+    use crate::pdf::dict_value;
+    use crate::typst_wrapper::TypstWrapper;
+    use crate::{render_markup, OutputFormat, RenderConfig};
+
+    #[test]
+    fn test_place_anchor_and_rect() {
+        let markup = "#set page(width: 200pt, height: 100pt, margin: 10pt)\n#set text(size: 10pt)\nHello World";
+        let document = TypstWrapper::compile_markup(markup).unwrap();
+        let rect = AnnotationRect { x: 5.0, y: 5.0, width: 20.0, height: 10.0 };
+        let annotations = [
+            Annotation::anchored("World", "Be specific"),
+            Annotation { page: Some(0), rect: Some(rect), anchor: None, ..Annotation::anchored("", "Margin note") },
+        ];
+        let placed = place(&document, &annotations).unwrap();
+        let world = placed[0].rect;
+        assert_eq!(placed[0].page, 0);
+        assert!(world.x > 30.0 && world.x < 45.0 && world.width > 20.0 && world.width < 35.0, "{:?}", world);
+        assert_eq!(placed[1].rect, rect);
+
+        let missing = [Annotation::anchored("Goodbye", "?")];
+        assert!(matches!(place(&document, &missing), Err(TypstWrapperError::Validation(_))));
+        let no_page = [Annotation { rect: Some(rect), anchor: None, ..Annotation::anchored("", "?") }];
+        assert!(matches!(place(&document, &no_page), Err(TypstWrapperError::Validation(_))));
+    }
+
+    #[test]
+    fn test_annotations_in_svg_and_pdf() {
+        let annotation = Annotation { author: "Maj Smith".to_string(), ..Annotation::anchored("Hello", "Use <b> & say hi") };
+        let config = RenderConfig { annotations: vec![annotation], ..RenderConfig::default() };
+        let svg = String::from_utf8(render_markup("Hello World", Some(config.clone())).unwrap().remove(0)).unwrap();
+        assert!(svg.contains("<title>Maj Smith: Use &lt;b&gt; &amp; say hi</title>"), "{}", svg);
+
+        let config = RenderConfig { format: OutputFormat::Pdf, ..config };
+        let pdf = render_markup("Hello World", Some(config)).unwrap().remove(0);
+        let update = PdfUpdate::parse(&pdf).unwrap();
+        let page = update.object(update.pages().unwrap()[0]).unwrap();
+        let annotations = crate::pdf::references(dict_value(&page, "Annots").unwrap());
+        let annotation = update.object(*annotations.last().unwrap()).unwrap();
+        assert_eq!(dict_value(&annotation, "Subtype"), Some("/Square"));
+        assert_eq!(dict_value(&annotation, "T"), Some("(Maj Smith)"));
+
+        let config = RenderConfig { annotations: vec![Annotation::anchored("Goodbye", "?")], ..RenderConfig::default() };
+        assert!(matches!(render_markup("Hello World", Some(config)), Err(TypstWrapperError::Validation(_))));
+    }
+}
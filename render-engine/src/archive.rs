@@ -0,0 +1,128 @@
+//! ZIP packaging of rendered files.
+
+use std::io::{Cursor, Write};
+
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::text_layout::PageText;
+use crate::TypstWrapperError;
+
+/// Write `files` (name and contents) into a ZIP archive
+///
+/// SVG and JSON are deflated; PDFs are stored since their streams are
+/// already compressed. Entries carry the ZIP epoch (1980-01-01) as their
+/// timestamp so identical inputs produce identical archives.
+pub(crate) fn zip<'a>(files: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Result<Vec<u8>, TypstWrapperError> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        let method = if name.ends_with(".pdf") {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        writer
+            .start_file(name, SimpleFileOptions::default().compression_method(method))
+            .map_err(std::io::Error::other)?;
+        writer.write_all(contents)?;
+    }
+    let archive = writer.finish().map_err(std::io::Error::other)?;
+    Ok(archive.into_inner())
+}
+
+/// `manifest.json` of an SVG ZIP
+#[derive(Serialize)]
+struct SvgManifest {
+    pages: Vec<SvgManifestPage>,
+}
+
+/// A page of an SVG ZIP, sized in points
+#[derive(Serialize)]
+struct SvgManifestPage {
+    file: String,
+    width: f64,
+    height: f64,
+    /// The page's text layout file, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+/// Package SVG pages as `page-001.svg`, `page-002.svg`, ... with a
+/// `manifest.json` listing each file with its `(width, height)` in points,
+/// and the text layout of each page as `page-001.json`, ... if given
+pub(crate) fn svg_zip(
+    pages: &[Vec<u8>],
+    sizes: impl IntoIterator<Item = (f64, f64)>,
+    text: Option<&[PageText]>,
+) -> Result<Vec<u8>, TypstWrapperError> {
+    let manifest = SvgManifest {
+        pages: sizes
+            .into_iter()
+            .enumerate()
+            .map(|(index, (width, height))| SvgManifestPage {
+                file: format!("page-{:03}.svg", index + 1),
+                width,
+                height,
+                text: text.map(|_| format!("page-{:03}.json", index + 1)),
+            })
+            .collect(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::other)?;
+    let text_json = text
+        .unwrap_or_default()
+        .iter()
+        .map(serde_json::to_vec)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(std::io::Error::other)?;
+    let mut files = Vec::new();
+    for (index, page) in manifest.pages.iter().enumerate() {
+        files.push((page.file.as_str(), pages[index].as_slice()));
+        if let (Some(name), Some(json)) = (&page.text, text_json.get(index)) {
+            files.push((name.as_str(), json.as_slice()));
+        }
+    }
+    files.push(("manifest.json", manifest_json.as_slice()));
+    zip(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_svg_zip_has_pages_and_manifest() {
+        let markup = "#set page(width: 100pt, height: 200pt)\n= One\n#pagebreak()\n= Two";
+        let config = crate::RenderConfig { format: crate::OutputFormat::SvgZip, ..Default::default() };
+        let output = crate::render_markup(markup, Some(config)).unwrap();
+        assert_eq!(output.len(), 1);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(&output[0])).unwrap();
+        let names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        assert_eq!(names, ["page-001.svg", "page-002.svg", "manifest.json"]);
+
+        let mut manifest = String::new();
+        archive.by_name("manifest.json").unwrap().read_to_string(&mut manifest).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["pages"][1]["file"], "page-002.svg");
+        assert_eq!(manifest["pages"][1]["width"], 100.0);
+        assert_eq!(manifest["pages"][1]["height"], 200.0);
+        assert!(manifest["pages"][1].get("text").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_svg_zip_text_layout() {
+        let config = crate::RenderConfig { format: crate::OutputFormat::SvgZip, text_layout: true, ..Default::default() };
+        let output = crate::render_markup("Hello", Some(config)).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(&output[0])).unwrap();
+        let names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        assert_eq!(names, ["page-001.svg", "page-001.json", "manifest.json"]);
+
+        let mut text = String::new();
+        archive.by_name("page-001.json").unwrap().read_to_string(&mut text).unwrap();
+        let text: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(text["runs"][0]["text"], "Hello");
+    }
+}
@@ -0,0 +1,146 @@
+//! Precompiled memo template artifacts.
+//!
+//! [`export_template_artifact`] compiles the memo-loader template once and
+//! records the package sources it resolved. A host caches the bytes (e.g. in
+//! IndexedDB or on disk) and passes them to [`import_template_artifact`] at
+//! its next start, which parses exactly those sources into the shared cache
+//! up front. The first render then skips package resolution and finds every
+//! template source already parsed.
+//!
+//! Typst syntax trees cannot be serialized, so importing still parses the
+//! sources, but only them and without compiling. Artifacts record the engine
+//! version and a hash of every source: an artifact from another engine build
+//! is rejected so the host can export a fresh one.
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{FileId, Source, VirtualPath};
+
+use crate::assets;
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// Version of the artifact layout, bumped on incompatible changes
+const ARTIFACT_FORMAT: u32 = 1;
+
+/// Serialized form of a template artifact
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TemplateArtifact {
+    format: u32,
+    engine: String,
+    files: Vec<ArtifactFile>,
+}
+
+/// A package source the template needs
+#[derive(Debug, Serialize, Deserialize)]
+struct ArtifactFile {
+    /// Package spec, e.g. `@preview/tonguetoquill-usaf-memo:0.1.0`
+    package: String,
+    /// Path within the package
+    path: String,
+    /// Hash of the source text, checked on import
+    hash: String,
+}
+
+/// Compile the memo template and serialize the sources it needs
+#[instrument]
+pub fn export_template_artifact() -> Result<Vec<u8>, TypstWrapperError> {
+    serde_json::to_vec(&template_artifact()?).map_err(|e| TypstWrapperError::Validation(format!("Template artifact: {}", e)))
+}
+
+/// Compile the memo template and record the sources it needs
+pub(crate) fn template_artifact() -> Result<TemplateArtifact, TypstWrapperError> {
+    let mut files: Vec<ArtifactFile> = TypstWrapper::template_sources()?
+        .iter()
+        .filter_map(|source| {
+            let package = source.id().package()?;
+            Some(ArtifactFile {
+                package: package.to_string(),
+                path: source.id().vpath().as_rootless_path().to_string_lossy().into_owned(),
+                hash: text_hash(source.text()),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| (&a.package, &a.path).cmp(&(&b.package, &b.path)));
+
+    Ok(TemplateArtifact {
+        format: ARTIFACT_FORMAT,
+        engine: env!("CARGO_PKG_VERSION").to_string(),
+        files,
+    })
+}
+
+/// Parse the sources recorded in an artifact from [`export_template_artifact`]
+/// into the shared cache, returning how many were loaded
+///
+/// Fails without loading anything if the artifact is malformed, comes from
+/// another engine version or names sources this build does not embed.
+#[instrument(skip_all, fields(len = bytes.len()))]
+pub fn import_template_artifact(bytes: &[u8]) -> Result<usize, TypstWrapperError> {
+    let artifact: TemplateArtifact = serde_json::from_slice(bytes)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid template artifact: {}", e)))?;
+    load_template_artifact(&artifact)
+}
+
+/// Parse the sources recorded in `artifact` into the shared cache, see
+/// [`import_template_artifact`]
+pub(crate) fn load_template_artifact(artifact: &TemplateArtifact) -> Result<usize, TypstWrapperError> {
+    let invalid = |message: String| TypstWrapperError::Validation(format!("Invalid template artifact: {}", message));
+    if artifact.format != ARTIFACT_FORMAT || artifact.engine != env!("CARGO_PKG_VERSION") {
+        return Err(invalid(format!(
+            "built by engine {} (format {}), this is engine {}",
+            artifact.engine,
+            artifact.format,
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    let sources = artifact
+        .files
+        .iter()
+        .map(|file| {
+            let spec: PackageSpec = file.package.parse().map_err(|e| invalid(format!("{}: {}", file.package, e)))?;
+            let text = assets::resolve_package_file(&spec, &file.path)
+                .filter(|text| text_hash(text) == file.hash)
+                .ok_or_else(|| invalid(format!("{}/{} does not match this build", file.package, file.path)))?;
+            Ok(Source::new(FileId::new(Some(spec), VirtualPath::new(&file.path)), text.to_string()))
+        })
+        .collect::<Result<Vec<_>, TypstWrapperError>>()?;
+
+    let count = sources.len();
+    sources.into_iter().for_each(TypstWrapper::cache_package_source);
+    debug!(count, "template artifact imported");
+    Ok(count)
+}
+
+/// Stable hex hash of a source text
+fn text_hash(text: &str) -> String {
+    format!("{:032x}", typst::utils::hash128(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_artifact_round_trip() {
+        let bytes = export_template_artifact().unwrap();
+        let artifact: TemplateArtifact = serde_json::from_slice(&bytes).unwrap();
+        assert!(artifact.files.iter().any(|file| file.path == "src/lib.typ"));
+        assert_eq!(import_template_artifact(&bytes).unwrap(), artifact.files.len());
+    }
+
+    #[test]
+    fn test_stale_artifacts_are_rejected() {
+        let mut artifact: TemplateArtifact = serde_json::from_slice(&export_template_artifact().unwrap()).unwrap();
+        artifact.files[0].hash = text_hash("edited");
+        let result = import_template_artifact(&serde_json::to_vec(&artifact).unwrap());
+        assert!(matches!(result, Err(TypstWrapperError::Validation(message)) if message.contains("does not match")));
+
+        artifact.engine = "0.0.0".to_string();
+        let result = import_template_artifact(&serde_json::to_vec(&artifact).unwrap());
+        assert!(matches!(result, Err(TypstWrapperError::Validation(message)) if message.contains("engine 0.0.0")));
+
+        assert!(import_template_artifact(b"not json").is_err());
+    }
+}
@@ -1,6 +1,13 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::LazyLock;
-use typst::syntax::package::PackageSpec;
+use thiserror::Error;
+use typst::foundations::Bytes;
+use typst::syntax::package::{PackageSpec, PackageVersion};
+use typst::text::{Font, FontInfo};
+
+use crate::engine::{self, PackageFiles};
+use crate::packages;
 
 /// String asset entry containing the content and original path
 #[derive(Debug, Clone)]
@@ -30,17 +37,71 @@ pub struct BinaryAssetResult {
     pub path: &'static str,
 }
 
+/// Kind of an asset listed in the asset manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetKind {
+    /// Typst template source (e.g., the memo-loader main template)
+    Template,
+    /// JSON schema used for form validation
+    Schema,
+    /// File belonging to an embedded Typst package
+    Package,
+    /// Font file (ttf, otf, ttc)
+    Font,
+    /// Embedded image (e.g., seals)
+    Image,
+    /// Runtime-registered letterhead background
+    Letterhead,
+    /// Runtime-registered image or other file, by path
+    File,
+}
+
+/// Manifest entry describing a single asset
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetInfo {
+    /// Registry key of an embedded asset, name of a letterhead background
+    /// or path of a registered file
+    pub name: String,
+    pub kind: AssetKind,
+    /// Source path an embedded asset was included from, or the path
+    /// documents read a registered asset from
+    pub path: String,
+    /// MIME type, from the file extension or, for letterheads, the content
+    pub mime: &'static str,
+    /// Size in bytes
+    pub size: usize,
+}
+
+/// Listing of everything bundled with (or registered into) the engine
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetManifest {
+    /// All assets, sorted by name
+    pub assets: Vec<AssetInfo>,
+    /// Total size of the embedded assets in bytes, which the engine's
+    /// binary or wasm module carries on top of its code
+    pub embedded_size: usize,
+    /// Font family names available to documents
+    pub fonts: Vec<String>,
+    /// Names of embedded seal images
+    pub seals: Vec<String>,
+    /// Names of registered letterhead backgrounds
+    pub letterheads: Vec<String>,
+}
+
 /// Replaces 'latest' with this version in package imports
-const PACKAGE_VERSION: &'static str = "0.1.0";
+const PACKAGE_VERSION: &str = "0.1.0";
 
 /// Rewrite any `:latest` package imports in the provided Typst markup to a
 /// concrete version to satisfy Typst's version parser. This only targets the
 /// tonguetoquill-usaf-memo package in the preview namespace.
 pub fn rewrite_latest_imports(markup: &str) -> String {
-    markup.replace(
-        "@preview/tonguetoquill-usaf-memo:latest",
-        &format!("@preview/tonguetoquill-usaf-memo:{}", PACKAGE_VERSION),
-    )
+    markup.replace("@preview/tonguetoquill-usaf-memo:latest", &memo_package_spec())
+}
+
+/// Spec of the embedded memo package
+pub(crate) fn memo_package_spec() -> String {
+    format!("@preview/tonguetoquill-usaf-memo:{}", PACKAGE_VERSION)
 }
 
 /// Static string asset registry
@@ -52,6 +113,22 @@ static STRING_ASSET_REGISTRY: LazyLock<HashMap<&'static str, StringAsset>> = Laz
         content: include_str!("../memo-loader/main.typ"),
         path: "../memo-loader/main.typ",
     });
+    assets.insert("memo-loader-letterhead", StringAsset {
+        content: include_str!("../memo-loader/partials/letterhead.typ"),
+        path: "../memo-loader/partials/letterhead.typ",
+    });
+    assets.insert("memo-loader-body", StringAsset {
+        content: include_str!("../memo-loader/partials/body.typ"),
+        path: "../memo-loader/partials/body.typ",
+    });
+    assets.insert("memo-loader-signature", StringAsset {
+        content: include_str!("../memo-loader/partials/signature.typ"),
+        path: "../memo-loader/partials/signature.typ",
+    });
+    assets.insert("memo-loader-footer", StringAsset {
+        content: include_str!("../memo-loader/partials/footer.typ"),
+        path: "../memo-loader/partials/footer.typ",
+    });
     // Official memorandum JSON schema asset
     assets.insert("official-memo-schema", StringAsset {
         content: include_str!("../../DESIGN/official-memorandum-schema.json"),
@@ -87,21 +164,25 @@ static BINARY_ASSET_REGISTRY: LazyLock<HashMap<&'static str, BinaryAsset>> = Laz
         path: "memo-loader/assets/dod_seal.gif",
     });
     
+    #[cfg(feature = "font-arial")]
     assets.insert("arial.ttf", BinaryAsset {
         content: include_bytes!("../memo-loader/assets/arial.ttf"),
         path: "memo-loader/assets/arial.ttf",
     });
     
+    #[cfg(feature = "font-times")]
     assets.insert("times.ttf", BinaryAsset {
         content: include_bytes!("../memo-loader/assets/times.ttf"),
         path: "memo-loader/assets/times.ttf",
     });
     
+    #[cfg(feature = "font-times")]
     assets.insert("Times.ttc", BinaryAsset {
         content: include_bytes!("../memo-loader/assets/Times.ttc"),
         path: "memo-loader/assets/Times.ttc",
     });
     
+    #[cfg(feature = "font-copperplate")]
     assets.insert("CopperplateCC-Heavy.otf", BinaryAsset {
         content: include_bytes!("../memo-loader/assets/CopperplateCC-Heavy.otf"),
         path: "memo-loader/assets/CopperplateCC-Heavy.otf",
@@ -110,6 +191,285 @@ static BINARY_ASSET_REGISTRY: LazyLock<HashMap<&'static str, BinaryAsset>> = Laz
     assets
 });
 
+/// Virtual directory that runtime-registered letterhead backgrounds are served from
+pub const LETTERHEAD_DIR: &str = "letterheads";
+
+/// Caps applied to runtime asset registration, protecting memory (especially
+/// in wasm) from oversized or excessive uploads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistrationLimits {
+    /// Maximum size of a single registered asset in bytes
+    pub max_asset_bytes: usize,
+    /// Maximum combined size of all registered assets in bytes
+    pub max_total_bytes: usize,
+    /// Maximum number of registered assets
+    pub max_count: usize,
+}
+
+impl Default for RegistrationLimits {
+    fn default() -> Self {
+        Self {
+            max_asset_bytes: 16 * 1024 * 1024,
+            max_total_bytes: 64 * 1024 * 1024,
+            max_count: 64,
+        }
+    }
+}
+
+/// The registry a runtime registration goes to; a name only replaces an
+/// entry of the same kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationKind {
+    Letterhead,
+    Asset,
+    Package,
+    Font,
+}
+
+/// Errors raised when a runtime registration would exceed the configured limits
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationError {
+    #[error("Asset '{name}' is {size} bytes, exceeding the per-asset limit of {limit} bytes")]
+    AssetTooLarge { name: String, size: usize, limit: usize },
+    #[error("Registering '{name}' would bring registered assets to {total} bytes, exceeding the limit of {limit} bytes")]
+    TotalSizeExceeded { name: String, total: usize, limit: usize },
+    #[error("Cannot register '{name}': the limit of {limit} registered assets has been reached")]
+    TooManyAssets { name: String, limit: usize },
+}
+
+impl RegistrationLimits {
+    /// Check whether registering `size` bytes under `name` as a `kind` fits
+    /// within these limits, given the kinds, names and sizes of the currently
+    /// registered assets. Replacing an existing asset of the same kind
+    /// releases its previous size and count.
+    pub fn check<'a>(
+        &self,
+        kind: RegistrationKind,
+        name: &str,
+        size: usize,
+        registered: impl IntoIterator<Item = (RegistrationKind, &'a str, usize)>,
+    ) -> Result<(), RegistrationError> {
+        if size > self.max_asset_bytes {
+            return Err(RegistrationError::AssetTooLarge {
+                name: name.to_string(),
+                size,
+                limit: self.max_asset_bytes,
+            });
+        }
+
+        let (count, total) = registered
+            .into_iter()
+            .filter(|(existing_kind, existing, _)| (*existing_kind, *existing) != (kind, name))
+            .fold((0, 0), |(count, total), (_, _, bytes)| (count + 1, total + bytes));
+
+        if count + 1 > self.max_count {
+            return Err(RegistrationError::TooManyAssets {
+                name: name.to_string(),
+                limit: self.max_count,
+            });
+        }
+        if total + size > self.max_total_bytes {
+            return Err(RegistrationError::TotalSizeExceeded {
+                name: name.to_string(),
+                total: total + size,
+                limit: self.max_total_bytes,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Replace the limits applied to subsequent runtime registrations with the
+/// current [`RenderEngine`](crate::RenderEngine)
+pub fn set_registration_limits(limits: RegistrationLimits) {
+    engine::current().set_registration_limits(limits)
+}
+
+/// Get the limits currently applied to runtime registrations
+pub fn registration_limits() -> RegistrationLimits {
+    engine::current().registration_limits()
+}
+
+/// Register (or replace) a letterhead background image under `name` with the
+/// current [`RenderEngine`](crate::RenderEngine), subject to its registration
+/// limits
+pub fn register_letterhead(name: &str, data: Vec<u8>) -> Result<(), RegistrationError> {
+    let engine = engine::current();
+    let _registering = engine.registration_lock();
+    let limits = engine.registration_limits();
+    let (files, packages, fonts) = (engine.assets(), engine.local_packages(), engine.fonts());
+    engine.update_letterheads(|registry| {
+        let registered = registered_sizes(registry, &files, &packages, &fonts);
+        limits.check(RegistrationKind::Letterhead, name, data.len(), entries(&registered))?;
+        registry.insert(name.to_string(), Bytes::new(data));
+        Ok(())
+    })
+}
+
+/// Register (or replace) an image or other file at the rootless `path`
+/// with the current [`RenderEngine`](crate::RenderEngine), subject to its
+/// registration limits
+pub fn register_asset(path: &str, data: Vec<u8>) -> Result<(), RegistrationError> {
+    let engine = engine::current();
+    let _registering = engine.registration_lock();
+    let limits = engine.registration_limits();
+    let (letterheads, packages, fonts) = (engine.letterheads(), engine.local_packages(), engine.fonts());
+    engine.update_assets(|registry| {
+        let registered = registered_sizes(&letterheads, registry, &packages, &fonts);
+        limits.check(RegistrationKind::Asset, path, data.len(), entries(&registered))?;
+        registry.insert(path.to_string(), Bytes::new(data));
+        Ok(())
+    })
+}
+
+/// Remove a registered file, returning whether it existed
+pub fn unregister_asset(path: &str) -> bool {
+    let path = path.trim_start_matches('/');
+    engine::current().update_assets(|registry| Ok(registry.remove(path).is_some())).unwrap_or(false)
+}
+
+/// The paths of the registered files, sorted
+pub fn registered_asset_paths() -> Vec<String> {
+    registered_files().into_iter().map(|(path, _)| path).collect()
+}
+
+/// The registered files, sorted by path
+pub(crate) fn registered_files() -> Vec<(String, Bytes)> {
+    let mut files: Vec<_> = engine::current().assets().iter().map(|(path, bytes)| (path.clone(), bytes.clone())).collect();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files
+}
+
+/// Kinds, names and sizes of the registered letterhead backgrounds, files,
+/// local packages and fonts, which share the registration limits
+pub(crate) fn registered_sizes(
+    letterheads: &HashMap<String, Bytes>,
+    files: &HashMap<String, Bytes>,
+    packages: &HashMap<PackageSpec, PackageFiles>,
+    fonts: &HashMap<String, Vec<Font>>,
+) -> Vec<(RegistrationKind, String, usize)> {
+    letterheads
+        .iter()
+        .map(|(name, bytes)| (RegistrationKind::Letterhead, name.clone(), bytes.len()))
+        .chain(files.iter().map(|(path, bytes)| (RegistrationKind::Asset, path.clone(), bytes.len())))
+        .chain(packages.iter().map(|(spec, files)| (RegistrationKind::Package, spec.to_string(), packages::package_size(files))))
+        .chain(fonts.iter().map(|(key, fonts)| (RegistrationKind::Font, key.clone(), fonts.first().map_or(0, |font| font.data().len()))))
+        .collect()
+}
+
+/// The entries of [`registered_sizes`] as [`RegistrationLimits::check`] takes them
+pub(crate) fn entries(registered: &[(RegistrationKind, String, usize)]) -> impl Iterator<Item = (RegistrationKind, &str, usize)> {
+    registered.iter().map(|(kind, name, size)| (*kind, name.as_str(), *size))
+}
+
+/// Remove a registered letterhead background, returning whether it existed
+pub fn unregister_letterhead(name: &str) -> bool {
+    engine::current().unregister_letterhead(name)
+}
+
+/// Check if a letterhead background has been registered
+pub fn letterhead_exists(name: &str) -> bool {
+    engine::current().letterheads().contains_key(name)
+}
+
+/// Get the names of all registered letterhead backgrounds
+pub fn get_letterhead_names() -> Vec<String> {
+    engine::current().letterheads().keys().cloned().collect()
+}
+
+/// The registered letterhead backgrounds, sorted by name
+pub(crate) fn registered_letterheads() -> Vec<(String, Bytes)> {
+    let mut letterheads: Vec<_> = engine::current()
+        .letterheads()
+        .iter()
+        .map(|(name, bytes)| (name.clone(), bytes.clone()))
+        .collect();
+    letterheads.sort_by(|(a, _), (b, _)| a.cmp(b));
+    letterheads
+}
+
+/// Register (or replace) several letterhead backgrounds, files, local
+/// packages and fonts at once, subject to `limits`; none are registered if
+/// any would exceed them
+pub(crate) fn register_assets(
+    letterheads: Vec<(String, Vec<u8>)>,
+    files: Vec<(String, Vec<u8>)>,
+    local_packages: Vec<(PackageSpec, PackageFiles)>,
+    fonts: Vec<(String, Vec<Font>)>,
+    limits: RegistrationLimits,
+) -> Result<(), RegistrationError> {
+    let engine = engine::current();
+    let _registering = engine.registration_lock();
+    engine.update_letterheads(|registry| {
+        engine.update_assets(|file_registry| {
+            engine.update_local_packages(|packages| {
+                engine.update_fonts(|font_registry| {
+                    for (name, data) in letterheads {
+                        let registered = registered_sizes(registry, file_registry, packages, font_registry);
+                        limits.check(RegistrationKind::Letterhead, &name, data.len(), entries(&registered))?;
+                        registry.insert(name, Bytes::new(data));
+                    }
+                    for (path, data) in files {
+                        let registered = registered_sizes(registry, file_registry, packages, font_registry);
+                        limits.check(RegistrationKind::Asset, &path, data.len(), entries(&registered))?;
+                        file_registry.insert(path, Bytes::new(data));
+                    }
+                    for (spec, files) in local_packages {
+                        let registered = registered_sizes(registry, file_registry, packages, font_registry);
+                        let size = packages::package_size(&files);
+                        limits.check(RegistrationKind::Package, &spec.to_string(), size, entries(&registered))?;
+                        packages.insert(spec, files);
+                    }
+                    for (key, faces) in fonts {
+                        let registered = registered_sizes(registry, file_registry, packages, font_registry);
+                        let size = faces.first().map_or(0, |font| font.data().len());
+                        limits.check(RegistrationKind::Font, &key, size, entries(&registered))?;
+                        font_registry.insert(key, faces);
+                    }
+                    Ok(())
+                })
+            })
+        })
+    })
+}
+
+/// Fingerprint of the registered letterhead backgrounds, files, local
+/// packages, fonts, custom memo template and partial overrides, which
+/// changes whenever one is registered, replaced or removed
+pub(crate) fn registration_fingerprint() -> u128 {
+    let registry = engine::current().letterheads();
+    let mut entries: Vec<_> = registry.iter().collect();
+    entries.sort_by_key(|(name, _)| name.as_str());
+    let partials = engine::current().template_partials();
+    let template = engine::current().memo_template().map(|files| {
+        let mut files: Vec<_> = files.iter().map(|(path, data)| (path.clone(), data.clone())).collect();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        files
+    });
+    let fonts: Vec<String> = crate::fonts::registered_fonts().into_iter().map(|(key, _)| key).collect();
+    typst::utils::hash128(&(entries, registered_files(), packages::registered_local_packages(), template, partials, fonts))
+}
+
+/// Fingerprint of the embedded memo template and package sources, so
+/// development builds that edit them without bumping the version are told
+/// apart
+pub(crate) fn template_fingerprint() -> u128 {
+    static FINGERPRINT: LazyLock<u128> = LazyLock::new(|| {
+        let mut sources: Vec<_> = STRING_ASSET_REGISTRY.iter().map(|(key, asset)| (*key, asset.content)).collect();
+        sources.sort_unstable();
+        typst::utils::hash128(&sources)
+    });
+    *FINGERPRINT
+}
+
+/// Resolve a letterhead background by virtual path (e.g., "letterheads/<name>")
+pub fn resolve_letterhead(path: &str) -> Option<Bytes> {
+    let name = path.strip_prefix(LETTERHEAD_DIR)?.strip_prefix('/')?;
+    engine::current().letterheads().get(name).cloned()
+}
+
 /// Load a string asset by key
 pub fn load_string_asset(key: &str) -> Option<StringAssetResult> {
     STRING_ASSET_REGISTRY.get(key).map(|asset| StringAssetResult {
@@ -126,19 +486,68 @@ pub fn load_binary_asset(key: &str) -> Option<BinaryAssetResult> {
     })
 }
 
-/// Resolve package file content by package spec and path
-pub fn resolve_package_file(spec: &PackageSpec, path: &str) -> Option<&'static str> {
-
+/// Versions of a package embedded in the engine, oldest first
+fn embedded_package_versions(spec: &PackageSpec) -> Vec<PackageVersion> {
     if spec.namespace == "preview" && spec.name == "tonguetoquill-usaf-memo" {
-        match path {
-            "typst.toml" => load_string_asset("package-typst-toml").map(|a| a.content),
-            "src/lib.typ" => load_string_asset("package-lib").map(|a| a.content),
-            "src/utils.typ" => load_string_asset("package-utils").map(|a: StringAssetResult| a.content),
-            _ => None,
-        }
+        PACKAGE_VERSION.parse().into_iter().collect()
     } else {
-        None
+        Vec::new()
+    }
+}
+
+/// The newest embedded version of the package of `spec`, if any is embedded
+pub fn latest_package_version(spec: &PackageSpec) -> Option<PackageVersion> {
+    embedded_package_versions(spec).into_iter().max()
+}
+
+/// The embedded version of the package of `spec` to use for it: the
+/// requested version if it is embedded, otherwise the compatible version
+/// closest to it
+///
+/// Versions are compatible when their major versions match and, before 1.0,
+/// their minor versions too. Of those, the newest version not newer than the
+/// requested one is preferred over the oldest newer one.
+pub fn compatible_package_version(spec: &PackageSpec) -> Option<PackageVersion> {
+    let requested = spec.version;
+    let compatible: Vec<PackageVersion> = embedded_package_versions(spec)
+        .into_iter()
+        .filter(|version| version.major == requested.major && (requested.major != 0 || version.minor == requested.minor))
+        .collect();
+    let older = compatible.iter().filter(|&&version| version <= requested).max();
+    older.or_else(|| compatible.iter().min()).copied()
+}
+
+/// Resolve package file content by package spec and path
+///
+/// Only the embedded versions resolve; see
+/// [`compatible_package_version`] for serving other versions.
+pub fn resolve_package_file(spec: &PackageSpec, path: &str) -> Option<&'static str> {
+    if !embedded_package_versions(spec).contains(&spec.version) {
+        return None;
     }
+    match path {
+        "typst.toml" => load_string_asset("package-typst-toml").map(|a| a.content),
+        "src/lib.typ" => load_string_asset("package-lib").map(|a| a.content),
+        "src/utils.typ" => load_string_asset("package-utils").map(|a: StringAssetResult| a.content),
+        _ => None,
+    }
+}
+
+/// A package manifest with its version replaced by `version`, so an
+/// embedded package can stand in for a compatible version
+pub(crate) fn manifest_with_version(manifest: &str, version: PackageVersion) -> String {
+    let mut replaced = false;
+    manifest
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if !replaced && key.trim() == "version" => {
+                replaced = true;
+                format!("version = \"{}\"", version)
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// Resolve binary asset by path
@@ -149,7 +558,7 @@ pub fn resolve_binary_asset(path: &str) -> Option<&'static [u8]> {
             return Some(asset.content);
         }
         // Also check for relative path matches (e.g., "assets/dod_seal.gif" matching "memo-loader/assets/dod_seal.gif")
-        if path.ends_with(&asset.path.split('/').last().unwrap_or("")) && 
+        if path.ends_with(&asset.path.split('/').next_back().unwrap_or("")) && 
            asset.path.ends_with(path) {
             return Some(asset.content);
         }
@@ -165,6 +574,133 @@ pub fn get_font_assets() -> Vec<BinaryAssetResult> {
         .collect()
 }
 
+/// Classify an embedded string asset by its registry key
+fn string_asset_kind(key: &str) -> AssetKind {
+    if key.starts_with("package-") {
+        AssetKind::Package
+    } else if key.ends_with("-schema") {
+        AssetKind::Schema
+    } else {
+        AssetKind::Template
+    }
+}
+
+/// Classify an embedded binary asset by its file extension
+fn binary_asset_kind(path: &str) -> AssetKind {
+    if path.ends_with(".ttf") || path.ends_with(".otf") || path.ends_with(".ttc") {
+        AssetKind::Font
+    } else {
+        AssetKind::Image
+    }
+}
+
+/// MIME type of an asset at `path` holding `data`
+fn mime_type(path: &str, data: &[u8]) -> &'static str {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("typ") => "text/x-typst",
+        Some("json") => "application/json",
+        Some("toml") => "application/toml",
+        Some("txt") => "text/plain",
+        Some("csv") => "text/csv",
+        Some("xml") => "application/xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("pdf") => "application/pdf",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("ttc") => "font/collection",
+        Some("woff") => "font/woff",
+        // Letterhead backgrounds are registered by name, without extension
+        _ if data.starts_with(b"\x89PNG") => "image/png",
+        _ if data.starts_with(b"\xFF\xD8\xFF") => "image/jpeg",
+        _ if data.starts_with(b"GIF8") => "image/gif",
+        _ if data.starts_with(b"%PDF") => "application/pdf",
+        _ if data.starts_with(b"<svg") || data.starts_with(b"<?xml") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// List the embedded and registered assets, sorted by name
+pub fn list_assets() -> Vec<AssetInfo> {
+    let mut assets: Vec<AssetInfo> = STRING_ASSET_REGISTRY
+        .iter()
+        .map(|(key, asset)| AssetInfo {
+            name: key.to_string(),
+            kind: string_asset_kind(key),
+            path: asset.path.to_string(),
+            mime: mime_type(asset.path, asset.content.as_bytes()),
+            size: asset.content.len(),
+        })
+        .chain(BINARY_ASSET_REGISTRY.iter().map(|(key, asset)| AssetInfo {
+            name: key.to_string(),
+            kind: binary_asset_kind(asset.path),
+            path: asset.path.to_string(),
+            mime: mime_type(asset.path, asset.content),
+            size: asset.content.len(),
+        }))
+        .chain(
+            engine::current()
+                .letterheads()
+                .iter()
+                .map(|(name, data)| AssetInfo {
+                    name: name.clone(),
+                    kind: AssetKind::Letterhead,
+                    path: format!("{}/{}", LETTERHEAD_DIR, name),
+                    mime: mime_type(name, data),
+                    size: data.len(),
+                }),
+        )
+        .chain(registered_files().into_iter().map(|(path, data)| AssetInfo {
+            name: path.clone(),
+            kind: AssetKind::File,
+            mime: mime_type(&path, &data),
+            path,
+            size: data.len(),
+        }))
+        .collect();
+    assets.sort_by(|a, b| a.name.cmp(&b.name));
+    assets
+}
+
+/// Build the manifest of embedded and registered assets
+pub fn get_asset_manifest() -> AssetManifest {
+    let assets = list_assets();
+    let embedded_size = STRING_ASSET_REGISTRY.values().map(|asset| asset.content.len())
+        .chain(BINARY_ASSET_REGISTRY.values().map(|asset| asset.content.len()))
+        .sum();
+
+    let fonts: BTreeSet<String> = get_font_assets()
+        .iter()
+        .flat_map(|font| FontInfo::iter(font.content))
+        .map(|info| info.family)
+        .chain(crate::fonts::registered_font_families())
+        .collect();
+
+    let seals = assets
+        .iter()
+        .filter(|asset| asset.kind == AssetKind::Image)
+        .map(|asset| asset.name.clone())
+        .collect();
+
+    let letterheads = assets
+        .iter()
+        .filter(|asset| asset.kind == AssetKind::Letterhead)
+        .map(|asset| asset.name.clone())
+        .collect();
+
+    AssetManifest {
+        assets,
+        embedded_size,
+        fonts: fonts.into_iter().collect(),
+        seals,
+        letterheads,
+    }
+}
+
 /// Get all available string asset keys
 pub fn get_string_asset_keys() -> Vec<&'static str> {
     STRING_ASSET_REGISTRY.keys().copied().collect()
@@ -206,6 +742,7 @@ mod tests {
     }
     
     #[test]
+    #[cfg(feature = "font-arial")]
     fn test_load_existing_binary_asset() {
         let result = load_binary_asset("arial.ttf");
         assert!(result.is_some());
@@ -224,7 +761,7 @@ mod tests {
     
     #[test]
     fn test_binary_asset_exists() {
-        assert!(binary_asset_exists("arial.ttf"));
+        assert_eq!(binary_asset_exists("arial.ttf"), cfg!(feature = "font-arial"));
         assert!(binary_asset_exists("dod_seal.gif"));
         assert!(!binary_asset_exists("nonexistent"));
     }
@@ -241,10 +778,10 @@ mod tests {
     #[test]
     fn test_get_binary_asset_keys() {
         let keys = get_binary_asset_keys();
-        assert!(keys.contains(&"arial.ttf"));
-        assert!(keys.contains(&"times.ttf"));
-        assert!(keys.contains(&"Times.ttc"));
-        assert!(keys.contains(&"CopperplateCC-Heavy.otf"));
+        assert_eq!(keys.contains(&"arial.ttf"), cfg!(feature = "font-arial"));
+        assert_eq!(keys.contains(&"times.ttf"), cfg!(feature = "font-times"));
+        assert_eq!(keys.contains(&"Times.ttc"), cfg!(feature = "font-times"));
+        assert_eq!(keys.contains(&"CopperplateCC-Heavy.otf"), cfg!(feature = "font-copperplate"));
         assert!(keys.contains(&"dod_seal.gif"));
     }
     
@@ -275,13 +812,121 @@ mod tests {
     }
     
     #[test]
+    fn test_letterhead_registry() {
+        register_letterhead("assets-test-letterhead", vec![1, 2, 3]).unwrap();
+        assert!(letterhead_exists("assets-test-letterhead"));
+        assert!(get_letterhead_names().contains(&"assets-test-letterhead".to_string()));
+
+        let data = resolve_letterhead("letterheads/assets-test-letterhead");
+        assert_eq!(data.as_deref(), Some(&[1u8, 2, 3][..]));
+        assert!(resolve_letterhead("assets-test-letterhead").is_none());
+
+        assert!(unregister_letterhead("assets-test-letterhead"));
+        assert!(!letterhead_exists("assets-test-letterhead"));
+    }
+
+    #[test]
+    fn test_registration_limits() {
+        let limits = RegistrationLimits {
+            max_asset_bytes: 10,
+            max_total_bytes: 15,
+            max_count: 2,
+        };
+        
+        let asset = RegistrationKind::Asset;
+        assert!(limits.check(asset, "a", 10, []).is_ok());
+        assert!(matches!(
+            limits.check(asset, "a", 11, []),
+            Err(RegistrationError::AssetTooLarge { size: 11, limit: 10, .. })
+        ));
+        assert!(matches!(
+            limits.check(asset, "b", 8, [(asset, "a", 8)]),
+            Err(RegistrationError::TotalSizeExceeded { total: 16, limit: 15, .. })
+        ));
+        assert!(matches!(
+            limits.check(asset, "c", 1, [(asset, "a", 1), (asset, "b", 1)]),
+            Err(RegistrationError::TooManyAssets { limit: 2, .. })
+        ));
+        
+        // Replacing an existing asset releases its previous size and slot
+        assert!(limits.check(asset, "a", 10, [(asset, "a", 8), (asset, "b", 5)]).is_ok());
+        // A letterhead of the same name is another entry
+        let letterhead = RegistrationKind::Letterhead;
+        assert!(matches!(
+            limits.check(asset, "a", 10, [(letterhead, "a", 8)]),
+            Err(RegistrationError::TotalSizeExceeded { total: 18, limit: 15, .. })
+        ));
+        assert!(matches!(
+            limits.check(asset, "a", 1, [(letterhead, "a", 1), (asset, "b", 1)]),
+            Err(RegistrationError::TooManyAssets { limit: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_registrations_count_every_kind() {
+        let engine = crate::RenderEngine::new();
+        let gif = load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        engine.set_registration_limits(RegistrationLimits { max_count: 1, ..RegistrationLimits::default() });
+        engine.register_letterhead("seal.gif", gif.clone()).unwrap();
+        // An asset named like the letterhead does not replace it
+        let result = engine.register_asset("seal.gif", gif.clone());
+        assert!(matches!(result, Err(crate::TypstWrapperError::Registration(RegistrationError::TooManyAssets { .. }))));
+
+        // Registrations at the same time count each other
+        let engine = crate::RenderEngine::new();
+        engine.set_registration_limits(RegistrationLimits { max_count: 4, ..RegistrationLimits::default() });
+        let registered = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|index| {
+                    let (engine, gif) = (&engine, gif.clone());
+                    scope.spawn(move || match index % 2 {
+                        0 => engine.register_letterhead(&format!("seal-{}.gif", index), gif).is_ok(),
+                        _ => engine.register_asset(&format!("seal-{}.gif", index), gif).is_ok(),
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).filter(|registered| *registered).count()
+        });
+        assert_eq!(registered, 4);
+        assert_eq!(engine.letterheads().len() + engine.assets().len(), 4);
+    }
+
+    #[test]
+    fn test_asset_manifest() {
+        let manifest = get_asset_manifest();
+        
+        let seal = manifest.assets.iter().find(|a| a.name == "dod_seal.gif").unwrap();
+        assert_eq!(seal.kind, AssetKind::Image);
+        assert_eq!(seal.size, load_binary_asset("dod_seal.gif").unwrap().content.len());
+        
+        assert_eq!((seal.path.as_str(), seal.mime), ("memo-loader/assets/dod_seal.gif", "image/gif"));
+        
+        let schema = manifest.assets.iter().find(|a| a.name == "official-memo-schema").unwrap();
+        assert_eq!(schema.kind, AssetKind::Schema);
+        assert_eq!(schema.mime, "application/json");
+        assert_eq!(manifest.assets.iter().find(|a| a.name == "memo-loader-main").unwrap().mime, "text/x-typst");
+        
+        let embedded: usize = manifest.assets.iter()
+            .filter(|a| !matches!(a.kind, AssetKind::Letterhead | AssetKind::File))
+            .map(|a| a.size)
+            .sum();
+        assert_eq!(manifest.embedded_size, embedded);
+        assert_eq!(mime_type("412tw", b"\x89PNG\r\n"), "image/png");
+        assert_eq!(mime_type("notes", b"plain"), "application/octet-stream");
+        
+        assert_eq!(manifest.fonts.contains(&"Copperplate CC".to_string()), cfg!(feature = "font-copperplate"));
+        assert_eq!(manifest.seals, vec!["dod_seal.gif".to_string()]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "font-times", feature = "font-arial", feature = "font-copperplate"))]
     fn test_get_font_assets() {
         let fonts = get_font_assets();
         assert_eq!(fonts.len(), 4);
         
         // Check that all expected fonts are present
         let font_names: Vec<&str> = fonts.iter().map(|f| {
-            f.path.split('/').last().unwrap()
+            f.path.split('/').next_back().unwrap()
         }).collect();
         
         assert!(font_names.contains(&"arial.ttf"));
@@ -289,4 +934,30 @@ mod tests {
         assert!(font_names.contains(&"Times.ttc"));
         assert!(font_names.contains(&"CopperplateCC-Heavy.otf"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(not(any(feature = "font-times", feature = "font-arial", feature = "font-copperplate")))]
+    fn test_renders_without_embedded_fonts() {
+        assert!(get_font_assets().is_empty());
+        assert!(get_asset_manifest().fonts.is_empty());
+
+        // Builds without fonts still render; text needs runtime-registered fonts
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let pdf = crate::render_markup("#rect(width: 2cm)", Some(config)).unwrap().remove(0);
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+    
+    #[test]
+    fn test_compatible_package_version() {
+        let version = |spec: &str| compatible_package_version(&spec.parse().unwrap()).map(|version| version.to_string());
+        assert_eq!(version("@preview/tonguetoquill-usaf-memo:0.1.0").as_deref(), Some("0.1.0"));
+        assert_eq!(version("@preview/tonguetoquill-usaf-memo:0.1.7").as_deref(), Some("0.1.0"));
+        assert_eq!(version("@preview/tonguetoquill-usaf-memo:0.2.0"), None);
+        assert_eq!(version("@preview/other-package:0.1.0"), None);
+
+        let spec: PackageSpec = "@preview/tonguetoquill-usaf-memo:0.1.7".parse().unwrap();
+        assert!(resolve_package_file(&spec, "src/lib.typ").is_none());
+        let manifest = manifest_with_version("[package]\nname = \"memo\"\nversion = \"0.1.0\"", spec.version);
+        assert_eq!(manifest, "[package]\nname = \"memo\"\nversion = \"0.1.7\"");
+    }
+}
@@ -0,0 +1,167 @@
+//! Attribution of form compile errors to form fields.
+//!
+//! Typst reports errors at spans in the memo template, which say little to
+//! someone filling in a form. The template reads each field with
+//! `input.<key>`, `input.at("<key>")` or `try_get("<key>", ..)`, and style
+//! tokens with `style.<key>`, so the span of an error, or of a call on its
+//! trace, that reads exactly one field names the field responsible. The
+//! body is evaluated as markup; when it came from a Delta body, the Delta is
+//! converted again with source markers and compiled on its own to find the
+//! op the error lies in.
+
+use serde_json::Value as JsonValue;
+use typst::diag::SourceDiagnostic;
+use typst::layout::PagedDocument;
+use typst::World;
+
+use crate::delta_parser::DeltaParser;
+use crate::form_processor::{self, Content, ContentFormat};
+use crate::typst_wrapper::{TypstWorld, TypstWrapper};
+
+/// The form field, as a JSON pointer, and the Delta op of the body that
+/// `errors` of compiling a form world lie in
+pub(crate) fn attribute(world: &TypstWorld, errors: &[SourceDiagnostic]) -> Option<(String, Option<usize>)> {
+    let form: JsonValue = serde_json::from_str(world.form_input()?).ok()?;
+    let field = errors.iter().find_map(|error| field_of(world, error))?;
+    if field != "/body_raw" {
+        return Some((field, None));
+    }
+
+    // The preprocessor fills `body_raw` from `body` unless the form set it
+    let body = form.get("body").and_then(|body| serde_json::from_value::<Content>(body.clone()).ok());
+    let Some(body) = body.filter(|body| form_processor::process_content(body).ok().as_deref() == form["body_raw"].as_str())
+    else {
+        return Some((field, None));
+    };
+    let op = match body.format {
+        ContentFormat::Delta => delta_op(&body.data),
+        ContentFormat::Markup => None,
+    };
+    Some(("/body".to_string(), op))
+}
+
+/// The field read by the first span of `error` or its trace that reads
+/// exactly one field
+fn field_of(world: &TypstWorld, error: &SourceDiagnostic) -> Option<String> {
+    std::iter::once(error.span).chain(error.trace.iter().map(|point| point.span)).find_map(|span| {
+        let source = world.source(span.id()?).ok()?;
+        let fields = field_reads(source.text().get(source.range(span)?)?);
+        match fields.as_slice() {
+            [field] => Some(field.clone()),
+            _ => None,
+        }
+    })
+}
+
+/// JSON pointers of the distinct fields Typst code reads
+fn field_reads(code: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for (prefix, parent) in [("input.", ""), ("try_get(", ""), ("style.", "/style")] {
+        for (start, _) in code.match_indices(prefix) {
+            let preceded_by_ident = code[..start].chars().next_back().is_some_and(is_ident);
+            let rest = &code[start + prefix.len()..];
+            let key = match rest.strip_prefix("at(").or_else(|| (prefix == "try_get(").then_some(rest)) {
+                Some(args) => args.strip_prefix('"').and_then(|args| args.split('"').next()),
+                None => rest.split(|c: char| !is_ident(c)).next().filter(|key| !key.is_empty()),
+            };
+            if let Some(key) = key.filter(|_| !preceded_by_ident) {
+                let field = format!("{}/{}", parent, key);
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+        }
+    }
+    fields
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// The Delta op the first error of compiling the Delta on its own lies in
+fn delta_op(delta: &str) -> Option<usize> {
+    let markup = DeltaParser::new().with_source_markers().parse(delta).ok()?;
+    let world = TypstWrapper::markup_world(&markup);
+    let errors = typst::compile::<PagedDocument>(&world).output.err()?;
+    let error = errors.first()?;
+    let source = world.source(error.span.id()?).ok()?;
+    op_at(&markup, source.range(error.span)?.start)
+}
+
+/// The innermost op whose source markers enclose byte `offset` of marked
+/// Delta markup
+fn op_at(markup: &str, offset: usize) -> Option<usize> {
+    let mut open = Vec::new();
+    for (start, _) in markup.get(..offset)?.match_indices("#metadata(") {
+        let rest = &markup[start + "#metadata(".len()..];
+        if rest.starts_with("none) <source-end>") {
+            open.pop();
+        } else if let Some((op, label)) = rest.split_once(')') {
+            if let (Ok(op), true) = (op.parse(), label.starts_with(" <source-start>")) {
+                open.push(op);
+            }
+        }
+    }
+    open.last().copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use crate::typst_wrapper::TypstWrapperError;
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal().subject("Errors").body_raw("Fine")
+    }
+
+    fn failing_field(form: &str) -> (String, Option<usize>, String) {
+        match crate::render_form(form, None) {
+            Err(TypstWrapperError::Field { field, op, error }) => (field, op, error.to_string()),
+            other => panic!("unexpected result {:?}", other.map(|pages| pages.len())),
+        }
+    }
+
+    #[test]
+    fn test_field_reads() {
+        assert_eq!(field_reads("input.memo-for"), ["/memo-for"]);
+        assert_eq!(field_reads(r#"try_get("references", none)"#), ["/references"]);
+        assert_eq!(field_reads(r#"rgb(style.accent-color) + input.at("style")"#), ["/style", "/style/accent-color"]);
+        assert!(field_reads("myinput.subject").is_empty());
+    }
+
+    #[test]
+    fn test_markup_body_errors_name_body_raw() {
+        let form = form().body_raw("Fine #undefined-function()").to_json();
+        let (field, op, error) = failing_field(&form);
+        assert_eq!((field.as_str(), op), ("/body_raw", None));
+        assert!(error.contains("undefined-function"), "{}", error);
+    }
+
+    #[test]
+    fn test_delta_body_errors_name_the_op() {
+        let delta = r#"{"ops":[{"insert":"Fine "},{"insert":{"image":"missing.png"}},{"insert":"\n"}]}"#;
+        let form = form().body_delta(delta).to_json();
+        let (field, op, _) = failing_field(&form);
+        assert_eq!((field.as_str(), op), ("/body", Some(1)));
+        assert_eq!(
+            crate::render_form(&form, None).unwrap_err().to_string().split(": ").next(),
+            Some("/body (Delta op 1)")
+        );
+    }
+
+    #[test]
+    fn test_template_errors_name_the_field() {
+        let form = form().date("2024-13-45").to_json();
+        assert_eq!(failing_field(&form).0, "/date");
+    }
+
+    #[test]
+    fn test_op_at() {
+        let markup = "#metadata(0) <source-start>a#metadata(none) <source-end>#metadata(1) <source-start>b";
+        assert_eq!(op_at(markup, markup.len() - 1), Some(1));
+        assert_eq!(op_at(markup, 28), Some(0));
+        assert_eq!(op_at(markup, 0), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! Rendering many memos into one ZIP archive.
+//!
+//! [`render_batch`] renders a list of [`FormJob`]s and packages the results
+//! under caller-chosen file names, e.g. for end-of-day bulk generation. With
+//! the `parallel` feature the memos are rendered on the rayon thread pool.
+
+use std::collections::HashSet;
+
+use tracing::instrument;
+
+use crate::archive;
+use crate::engine;
+use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWrapper, TypstWrapperError};
+
+/// One memo of a batch
+#[derive(Debug, Clone)]
+pub struct FormJob {
+    /// Name of the output in the archive, e.g. `"2025-09-09/ops-memo.pdf"`.
+    /// SVG renders with several pages are stored as `<stem>-001.svg`,
+    /// `<stem>-002.svg`, ... instead.
+    pub file_name: String,
+    /// Memo form JSON
+    pub json: String,
+    /// Render configuration (defaults to SVG)
+    pub config: Option<RenderConfig>,
+}
+
+/// Render every job and return a ZIP archive of the outputs, in job order
+///
+/// Fails with [`TypstWrapperError::Batch`] naming the first job (in job
+/// order) that failed, and with a validation error if two jobs share a
+/// file name.
+#[instrument(skip_all, fields(jobs = jobs.len()))]
+pub fn render_batch(jobs: Vec<FormJob>) -> Result<Vec<u8>, TypstWrapperError> {
+    let mut names = HashSet::new();
+    if let Some(job) = jobs.iter().find(|job| !names.insert(job.file_name.as_str())) {
+        return Err(TypstWrapperError::Validation(format!("Duplicate batch file name: {}", job.file_name)));
+    }
+
+    // Pool threads start outside the caller's engine scope
+    let engine = engine::current();
+    let render = |job: &FormJob| {
+        engine.scope(|| TypstWrapper::render_form(&job.json, job.config.clone())).map_err(|error| TypstWrapperError::Batch {
+            file_name: job.file_name.clone(),
+            error: Box::new(error),
+        })
+    };
+    #[cfg(feature = "parallel")]
+    let rendered: Vec<_> = {
+        use rayon::prelude::*;
+        jobs.par_iter().map(render).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let rendered: Vec<_> = jobs.iter().map(render).collect();
+
+    let mut files = Vec::new();
+    for (job, pages) in jobs.iter().zip(rendered) {
+        let pages = pages?;
+        let is_svg = matches!(job.config.as_ref().map_or(OutputFormat::Svg, |c| c.format), OutputFormat::Svg);
+        if is_svg && pages.len() > 1 {
+            let stem = job.file_name.strip_suffix(".svg").unwrap_or(&job.file_name);
+            files.extend(
+                pages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, page)| (format!("{}-{:03}.svg", stem, index + 1), page)),
+            );
+        } else {
+            files.extend(pages.into_iter().map(|page| (job.file_name.clone(), page)));
+        }
+    }
+    archive::zip(files.iter().map(|(name, contents)| (name.as_str(), contents.as_slice())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use std::io::{Cursor, Read};
+
+    fn memo(subject: &str) -> String {
+        MemoBuilder::minimal().subject(subject).to_json()
+    }
+
+    #[test]
+    fn test_batch_archive_contains_named_outputs() {
+        let pdf = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
+        let jobs = vec![
+            FormJob { file_name: "a/first.pdf".to_string(), json: memo("First"), config: Some(pdf.clone()) },
+            FormJob { file_name: "second.svg".to_string(), json: memo("Second"), config: None },
+        ];
+        let bytes = render_batch(jobs).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut pdf = Vec::new();
+        archive.by_name("a/first.pdf").unwrap().read_to_end(&mut pdf).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        let mut svg = String::new();
+        archive.by_name("second.svg").unwrap().read_to_string(&mut svg).unwrap();
+        assert!(svg.starts_with("<svg"));
+    }
+
+    #[test]
+    fn test_batch_reports_failing_job() {
+        let jobs = vec![
+            FormJob { file_name: "ok.svg".to_string(), json: memo("Fine"), config: None },
+            FormJob { file_name: "bad.svg".to_string(), json: "{}".to_string(), config: None },
+        ];
+        let result = render_batch(jobs);
+        assert!(matches!(result, Err(TypstWrapperError::Batch { ref file_name, .. }) if file_name == "bad.svg"));
+
+        let duplicate = FormJob { file_name: "same.svg".to_string(), json: memo("Twice"), config: None };
+        let result = render_batch(vec![duplicate.clone(), duplicate]);
+        assert!(matches!(result, Err(TypstWrapperError::Validation(_))));
+    }
+}
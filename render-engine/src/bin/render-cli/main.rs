@@ -0,0 +1,257 @@
+//! # render-cli
+//!
+//! Command-line front end for the render engine, for scripts and CI pipelines
+//! that need to generate memos without the wasm wrapper.
+//!
+//! ## Usage
+//!
+//! ```text
+//! # Render Typst markup to a PDF file
+//! render-cli markup file.typ -f pdf -o out.pdf
+//!
+//! # Render a memo form to one SVG file per page
+//! render-cli form memo.json -f svg -o dir/
+//!
+//! # Re-render a memo form whenever the input changes
+//! render-cli watch input.json --template memo -o preview.pdf
+//!
+//! # Keep a warm engine process speaking JSON-RPC on stdin/stdout
+//! render-cli serve --stdio
+//! ```
+//!
+//! Render settings can be loaded from a JSON or TOML file holding a
+//! serialized `RenderConfig` with `--config settings.toml`; `-f` overrides
+//! its `format`.
+//!
+//! PDF output is written to the `-o` file (default: `<input stem>.pdf`).
+//! SVG output is written to the `-o` directory (default: the current
+//! directory) as `<input stem>-<page>.svg`.
+//!
+//! When an input fails to render, its diagnostics are printed to stderr with
+//! source excerpts (colored on terminals unless `NO_COLOR` is set).
+
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use render_engine::diagnostics::format_pretty;
+use render_engine::{diagnose_form, diagnose_markup, render_form, render_markup, Diagnostic, OutputFormat, RenderConfig, Severity};
+
+mod serve;
+mod watch;
+
+#[derive(Parser)]
+#[command(name = "render-cli", version, about = "Render Typst markup and memo forms to PDF or SVG")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a Typst markup file
+    Markup(RenderArgs),
+    /// Render a memo form JSON file with the memo-loader template
+    Form(RenderArgs),
+    /// Re-render an input file whenever it changes
+    Watch(watch::WatchArgs),
+    /// Run a long-lived JSON-RPC server
+    Serve(serve::ServeArgs),
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Input file
+    input: PathBuf,
+
+    /// Output format [default: the config's format, or pdf]
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Render settings file (JSON, or TOML with a .toml extension)
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Output file (PDF) or directory (SVG)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// How an input file is turned into a document
+#[derive(Clone, Copy)]
+enum Mode {
+    /// Input is Typst markup
+    Markup,
+    /// Input is memo form JSON rendered with the memo-loader template
+    Form,
+}
+
+impl Mode {
+    fn render(self, input: &str, config: RenderConfig) -> Result<Vec<Vec<u8>>, String> {
+        let result = match self {
+            Mode::Markup => render_markup(input, Some(config)),
+            Mode::Form => render_form(input, Some(config)),
+        };
+        result.map_err(|e| e.to_string())
+    }
+
+    fn diagnose(self, input: &str) -> Vec<Diagnostic> {
+        match self {
+            Mode::Markup => diagnose_markup(input),
+            Mode::Form => diagnose_form(input),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Pdf,
+    Svg,
+    /// One ZIP of SVG pages with a manifest.json
+    SvgZip,
+}
+
+impl From<OutputFormat> for Format {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Pdf => Format::Pdf,
+            OutputFormat::Svg => Format::Svg,
+            OutputFormat::SvgZip => Format::SvgZip,
+        }
+    }
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Pdf => OutputFormat::Pdf,
+            Format::Svg => OutputFormat::Svg,
+            Format::SvgZip => OutputFormat::SvgZip,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        Command::Markup(args) => run(args, Mode::Markup),
+        Command::Form(args) => run(args, Mode::Form),
+        Command::Watch(args) => watch::run(args),
+        Command::Serve(args) => serve::run(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Render a single input file and print the written paths
+fn run(args: &RenderArgs, mode: Mode) -> Result<(), String> {
+    let config = match &args.config {
+        Some(path) => load_config(path)?,
+        None => RenderConfig { format: OutputFormat::Pdf, ..Default::default() },
+    };
+    let format = args.format.unwrap_or(config.format.into());
+    for path in render_file(&args.input, mode, format, config, args.output.as_deref())? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Read a serialized render config, as TOML for `.toml` files and JSON otherwise
+fn load_config(path: &Path) -> Result<RenderConfig, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let config = if path.extension().is_some_and(|extension| extension == "toml") {
+        toml::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    };
+    config.map_err(|e| format!("invalid config {}: {}", path.display(), e))
+}
+
+/// Read the input, render it with `config` in `format` and write the pages,
+/// returning the written paths
+fn render_file(
+    input_path: &Path,
+    mode: Mode,
+    format: Format,
+    config: RenderConfig,
+    output: Option<&Path>,
+) -> Result<Vec<PathBuf>, String> {
+    let input = fs::read_to_string(input_path)
+        .map_err(|e| format!("failed to read {}: {}", input_path.display(), e))?;
+
+    let config = RenderConfig { format: format.into(), ..config };
+    let pages = mode.render(&input, config).map_err(|message| {
+        let diagnostics = mode.diagnose(&input);
+        let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+        if errors == 0 {
+            return message;
+        }
+        let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        eprintln!("{}", format_pretty(&diagnostics, &input_path.display().to_string(), &input, color));
+        format!(
+            "could not render {} due to {} previous error{}",
+            input_path.display(),
+            errors,
+            if errors == 1 { "" } else { "s" }
+        )
+    })?;
+
+    write_pages(input_path, format, output, &pages)
+}
+
+/// Write rendered pages according to the output format, returning the written paths
+fn write_pages(
+    input_path: &Path,
+    format: Format,
+    output: Option<&Path>,
+    pages: &[Vec<u8>],
+) -> Result<Vec<PathBuf>, String> {
+    let stem = input_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output".to_string());
+
+    let written = match format {
+        Format::Pdf | Format::SvgZip => {
+            let extension = if matches!(format, Format::Pdf) { "pdf" } else { "zip" };
+            let path = output
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from(format!("{}.{}", stem, extension)));
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                create_dir(parent)?;
+            }
+            write_file(&path, &pages[0])?;
+            vec![path]
+        }
+        Format::Svg => {
+            let dir = output.map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+            create_dir(&dir)?;
+            let mut paths = Vec::with_capacity(pages.len());
+            for (index, page) in pages.iter().enumerate() {
+                let path = dir.join(format!("{}-{}.svg", stem, index + 1));
+                write_file(&path, page)?;
+                paths.push(path);
+            }
+            paths
+        }
+    };
+
+    Ok(written)
+}
+
+fn create_dir(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(path, bytes).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
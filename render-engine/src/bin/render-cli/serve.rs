@@ -0,0 +1,303 @@
+//! Long-running JSON-RPC 2.0 server over stdio.
+//!
+//! Editor plugins and desktop apps keep one warm engine process instead of
+//! paying cold-start per invocation. Messages are newline-delimited: each
+//! line on stdin is one request, and each response is written as one line
+//! on stdout. Notifications (requests without an `id`) get no response.
+//!
+//! ## Methods
+//!
+//! - `render` — `{"markup": "..."}` or `{"form": {...} | "..."}`, plus an
+//!   optional `"format": "svg" | "pdf" | "svg-zip"` and any other field of a
+//!   serialized `RenderConfig`, such as `watermark`. Returns
+//!   `{"format", "page_count", "pages"}` with base64-encoded pages.
+//! - `validate` — `{"form": {...} | "..."}`. Returns `{"valid", "errors"}`.
+//! - `convert` — `{"delta": {...} | "..."}`. Returns `{"markup"}` with the
+//!   Quill Delta converted to Typst markup.
+//! - `diagnose` — `{"markup": "..."}` or `{"form": {...} | "..."}`. Returns
+//!   `{"diagnostics"}` (see `render_engine::diagnostics`).
+//! - `shutdown` — returns `null`, then the server exits.
+
+use std::io::{self, BufRead, Write};
+
+use base64::Engine as _;
+use clap::Args;
+use render_engine::form_processor::official_memo_schema_errors;
+use render_engine::{diagnose_form, diagnose_markup, DeltaParser, RenderConfig};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{Format, Mode};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Render (compilation or form validation) failure
+const RENDER_FAILED: i64 = -32000;
+/// Delta conversion failure
+const CONVERT_FAILED: i64 = -32001;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Speak newline-delimited JSON-RPC on stdin/stdout
+    #[arg(long, required = true)]
+    stdio: bool,
+}
+
+/// Error produced by a method handler
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Outcome of handling one line
+enum Reply {
+    /// Write this response and keep serving
+    Continue(Option<JsonValue>),
+    /// Write this response and exit
+    Shutdown(JsonValue),
+}
+
+/// Serve requests from stdin until EOF or `shutdown`
+pub fn run(_args: &ServeArgs) -> Result<(), String> {
+    // Pay the first-render setup cost before the first request arrives
+    if let Err(e) = render_engine::prewarm() {
+        eprintln!("warning: prewarm failed: {}", e);
+    }
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| format!("failed to read stdin: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, shutdown) = match handle_line(&line) {
+            Reply::Continue(response) => (response, false),
+            Reply::Shutdown(response) => (Some(response), true),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)
+                .and_then(|_| stdout.flush())
+                .map_err(|e| format!("failed to write stdout: {}", e))?;
+        }
+        if shutdown {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse and dispatch a single request line
+fn handle_line(line: &str) -> Reply {
+    let request: JsonValue = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Reply::Continue(Some(error_response(JsonValue::Null, PARSE_ERROR, &e.to_string()))),
+    };
+
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(JsonValue::as_str) else {
+        return Reply::Continue(Some(error_response(
+            id.unwrap_or(JsonValue::Null),
+            INVALID_REQUEST,
+            "missing method",
+        )));
+    };
+    let params = request.get("params").cloned().unwrap_or(JsonValue::Null);
+
+    let result = match method {
+        "render" => render(&params),
+        "validate" => validate(&params),
+        "convert" => convert(&params),
+        "diagnose" => diagnose(&params),
+        "shutdown" => {
+            return Reply::Shutdown(success_response(id.unwrap_or(JsonValue::Null), JsonValue::Null));
+        }
+        other => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+    };
+
+    // Notifications never receive a response
+    let Some(id) = id else {
+        return Reply::Continue(None);
+    };
+    Reply::Continue(Some(match result {
+        Ok(value) => success_response(id, value),
+        Err(e) => error_response(id, e.code, &e.message),
+    }))
+}
+
+fn render(params: &JsonValue) -> Result<JsonValue, RpcError> {
+    let format = match params.get("format") {
+        None | Some(JsonValue::Null) => Format::Svg,
+        Some(value) => match value.as_str() {
+            Some(f) if f.eq_ignore_ascii_case("svg") => Format::Svg,
+            Some(f) if f.eq_ignore_ascii_case("pdf") => Format::Pdf,
+            Some(f) if f.eq_ignore_ascii_case("svg-zip") => Format::SvgZip,
+            _ => return Err(RpcError::new(INVALID_PARAMS, "format must be 'svg', 'pdf' or 'svg-zip'")),
+        },
+    };
+
+    let (mode, input) = if let Some(markup) = params.get("markup") {
+        let markup = markup
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "markup must be a string"))?;
+        (Mode::Markup, markup.to_string())
+    } else if let Some(form) = params.get("form") {
+        (Mode::Form, json_text(form))
+    } else {
+        return Err(RpcError::new(INVALID_PARAMS, "expected 'markup' or 'form'"));
+    };
+
+    // The format is matched above without regard to case
+    let mut settings = params.clone();
+    if let Some(settings) = settings.as_object_mut() {
+        settings.remove("format");
+    }
+    let config: RenderConfig = serde_json::from_value(settings)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid render settings: {}", e)))?;
+    let config = RenderConfig { format: format.into(), ..config };
+    let pages = mode
+        .render(&input, config)
+        .map_err(|message| RpcError::new(RENDER_FAILED, message))?;
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    Ok(json!({
+        "format": match format {
+            Format::Svg => "svg",
+            Format::Pdf => "pdf",
+            Format::SvgZip => "svg-zip",
+        },
+        "page_count": pages.len(),
+        "pages": pages.iter().map(|page| engine.encode(page)).collect::<Vec<_>>(),
+    }))
+}
+
+fn validate(params: &JsonValue) -> Result<JsonValue, RpcError> {
+    let form = params
+        .get("form")
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected 'form'"))?;
+
+    let errors: Vec<String> = official_memo_schema_errors(&json_text(form)).iter().map(ToString::to_string).collect();
+    Ok(json!({ "valid": errors.is_empty(), "errors": errors }))
+}
+
+fn convert(params: &JsonValue) -> Result<JsonValue, RpcError> {
+    let delta = params
+        .get("delta")
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected 'delta'"))?;
+
+    let markup = DeltaParser::new()
+        .parse(&json_text(delta))
+        .map_err(|e| RpcError::new(CONVERT_FAILED, e.to_string()))?;
+    Ok(json!({ "markup": markup }))
+}
+
+fn diagnose(params: &JsonValue) -> Result<JsonValue, RpcError> {
+    let diagnostics = if let Some(markup) = params.get("markup") {
+        let markup = markup
+            .as_str()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "markup must be a string"))?;
+        diagnose_markup(markup)
+    } else if let Some(form) = params.get("form") {
+        diagnose_form(&json_text(form))
+    } else {
+        return Err(RpcError::new(INVALID_PARAMS, "expected 'markup' or 'form'"));
+    };
+    Ok(json!({ "diagnostics": diagnostics }))
+}
+
+/// Accept JSON documents either inline or as an already-serialized string
+fn json_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn success_response(id: JsonValue, result: JsonValue) -> JsonValue {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: JsonValue, code: i64, message: &str) -> JsonValue {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(line: &str) -> JsonValue {
+        match handle_line(line) {
+            Reply::Continue(Some(response)) | Reply::Shutdown(response) => response,
+            Reply::Continue(None) => panic!("expected a response for {}", line),
+        }
+    }
+
+    #[test]
+    fn test_render_markup() {
+        let response = respond(r#"{"jsonrpc":"2.0","id":1,"method":"render","params":{"markup":"= Hi","format":"pdf"}}"#);
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["format"], "pdf");
+        assert_eq!(response["result"]["page_count"], 1);
+    }
+
+    #[test]
+    fn test_validate_and_convert() {
+        let response = respond(r#"{"jsonrpc":"2.0","id":2,"method":"validate","params":{"form":{"subject":"S"}}}"#);
+        assert_eq!(response["result"]["valid"], false);
+
+        let response = respond(
+            r#"{"jsonrpc":"2.0","id":3,"method":"convert","params":{"delta":{"ops":[{"insert":"Hi","attributes":{"bold":true}}]}}}"#,
+        );
+        assert_eq!(response["result"]["markup"], "*Hi*");
+
+        let response = respond(r##"{"jsonrpc":"2.0","id":4,"method":"diagnose","params":{"markup":"= A\n#bad()"}}"##);
+        assert_eq!(response["result"]["diagnostics"][0]["range"]["start"]["line"], 1);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        assert_eq!(respond("not json")["error"]["code"], PARSE_ERROR);
+        assert_eq!(respond(r#"{"jsonrpc":"2.0","id":4}"#)["error"]["code"], INVALID_REQUEST);
+        assert_eq!(
+            respond(r#"{"jsonrpc":"2.0","id":5,"method":"nope"}"#)["error"]["code"],
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            respond(r#"{"jsonrpc":"2.0","id":6,"method":"render","params":{}}"#)["error"]["code"],
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            respond(r##"{"jsonrpc":"2.0","id":7,"method":"render","params":{"markup":"#bad()"}}"##)["error"]["code"],
+            RENDER_FAILED
+        );
+        assert_eq!(
+            respond(r#"{"jsonrpc":"2.0","id":8,"method":"render","params":{"markup":"= Hi","duplex":"yes"}}"#)["error"]["code"],
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn test_notifications_and_shutdown() {
+        assert!(matches!(
+            handle_line(r#"{"jsonrpc":"2.0","method":"validate","params":{"form":{}}}"#),
+            Reply::Continue(None)
+        ));
+        assert!(matches!(
+            handle_line(r#"{"jsonrpc":"2.0","id":8,"method":"shutdown"}"#),
+            Reply::Shutdown(_)
+        ));
+    }
+}
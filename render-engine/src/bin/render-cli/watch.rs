@@ -0,0 +1,112 @@
+//! Watch mode: re-render an input file whenever it changes.
+//!
+//! Changes are detected by polling the input's modification time. A change
+//! only triggers a recompile once the file has been quiet for the debounce
+//! window, so editors that write in several steps cause a single render.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::Args;
+
+use render_engine::RenderConfig;
+
+use crate::{render_file, Format, Mode};
+
+/// How often the input file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Input file
+    input: PathBuf,
+
+    /// Template used to render the input: `memo` renders form JSON with the
+    /// memo-loader template, `none` compiles the input as Typst markup.
+    /// Defaults to `memo` for .json inputs and `none` otherwise.
+    #[arg(short, long)]
+    template: Option<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Pdf)]
+    format: Format,
+
+    /// Output file (PDF) or directory (SVG)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Quiet period in milliseconds before a change triggers a recompile
+    #[arg(long, default_value_t = 250)]
+    debounce_ms: u64,
+}
+
+/// Render once, then keep re-rendering on change until interrupted
+pub fn run(args: &WatchArgs) -> Result<(), String> {
+    let mode = resolve_mode(&args.input, args.template.as_deref())?;
+    let debounce = Duration::from_millis(args.debounce_ms);
+
+    eprintln!("watching {} (press Ctrl+C to stop)", args.input.display());
+    let mut last_rendered = modified_time(&args.input);
+    render_once(args, mode);
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let modified = modified_time(&args.input);
+        if modified == last_rendered {
+            continue;
+        }
+
+        // Wait for the file to settle before recompiling
+        let mut settled = modified;
+        let mut quiet_since = Instant::now();
+        while quiet_since.elapsed() < debounce {
+            thread::sleep(POLL_INTERVAL.min(debounce));
+            let current = modified_time(&args.input);
+            if current != settled {
+                settled = current;
+                quiet_since = Instant::now();
+            }
+        }
+
+        last_rendered = settled;
+        render_once(args, mode);
+    }
+}
+
+/// Map the `--template` option (or the input extension) to a render mode
+fn resolve_mode(input: &Path, template: Option<&str>) -> Result<Mode, String> {
+    match template {
+        Some("memo") => Ok(Mode::Form),
+        Some("none") => Ok(Mode::Markup),
+        Some(other) => Err(format!(
+            "unknown template '{}' (expected 'memo' or 'none')",
+            other
+        )),
+        None if input.extension().is_some_and(|ext| ext == "json") => Ok(Mode::Form),
+        None => Ok(Mode::Markup),
+    }
+}
+
+/// Render the input and report the outcome without stopping the watch loop
+fn render_once(args: &WatchArgs, mode: Mode) {
+    let started = Instant::now();
+    match render_file(&args.input, mode, args.format, RenderConfig::default(), args.output.as_deref()) {
+        Ok(paths) => {
+            let targets: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+            eprintln!(
+                "rendered {} in {} ms",
+                targets.join(", "),
+                started.elapsed().as_millis()
+            );
+        }
+        Err(message) => eprintln!("error: {}", message),
+    }
+}
+
+/// Modification time of the input, or `None` while it is missing or unreadable
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
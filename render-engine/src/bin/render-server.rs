@@ -0,0 +1,67 @@
+//! # render-server
+//!
+//! Runs the HTTP render service (see `render_engine::server`).
+//!
+//! Configuration is read from the environment:
+//!
+//! - `RENDER_SERVER_ADDR` — listen address (default `0.0.0.0:8080`)
+//! - `RENDER_SERVER_MAX_CONCURRENCY` — concurrent renders (default: CPU count)
+//! - `RENDER_SERVER_TIMEOUT_MS` — per-request time budget (default `30000`)
+//! - `RENDER_SERVER_MAX_BODY_BYTES` — request body limit (default 10 MiB)
+//! - `RENDER_SERVER_SNAPSHOT` — engine snapshot file to restore registered
+//!   letterheads and limits from (see `render_engine::engine_snapshot`)
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+use render_engine::server::{serve, ServerConfig};
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let defaults = ServerConfig::default();
+    let addr: SocketAddr = env_or("RENDER_SERVER_ADDR", "0.0.0.0:8080".parse().unwrap())?;
+    let config = ServerConfig {
+        max_concurrent_renders: env_or("RENDER_SERVER_MAX_CONCURRENCY", defaults.max_concurrent_renders)?,
+        request_timeout: env_or("RENDER_SERVER_TIMEOUT_MS", defaults.request_timeout.as_millis() as u64)
+            .map(Duration::from_millis)?,
+        max_body_bytes: env_or("RENDER_SERVER_MAX_BODY_BYTES", defaults.max_body_bytes)?,
+    };
+
+    if let Ok(path) = std::env::var("RENDER_SERVER_SNAPSHOT") {
+        let bytes = std::fs::read(&path).map_err(|e| format!("failed to read snapshot {}: {}", path, e))?;
+        let restored = render_engine::restore_engine_snapshot(&bytes).map_err(|e| format!("failed to restore snapshot {}: {}", path, e))?;
+        eprintln!("restored {} letterheads and {} local packages from {}", restored.letterheads, restored.local_packages, path);
+    }
+
+    // Pay the first-render setup cost before accepting requests
+    if let Err(e) = render_engine::prewarm() {
+        eprintln!("warning: prewarm failed: {}", e);
+    }
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("failed to start runtime: {}", e))?;
+    eprintln!("render-server listening on {}", addr);
+    runtime
+        .block_on(serve(addr, config))
+        .map_err(|e| format!("server failed: {}", e))
+}
+
+/// Parse an environment variable, falling back to `default` when unset
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("invalid value for {}: '{}'", name, value)),
+        Err(_) => Ok(default),
+    }
+}
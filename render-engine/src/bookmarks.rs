@@ -0,0 +1,368 @@
+//! PDF bookmarks for memos and packages.
+//!
+//! Typst bookmarks headings on its own, but the parts reviewers jump between
+//! in a long package (the subject, each indorsement, attachments) are not
+//! headings. Such parts are labelled with [`BOOKMARK_LABEL`] on a
+//! `metadata` element whose value is the bookmark title, or a dictionary
+//! with a `title` and a `level` (1 by default):
+//!
+//! ```typst
+//! #metadata((title: "1st Ind", level: 1)) <bookmark>
+//! ```
+//!
+//! When a document has such labels, the PDF outline is rebuilt from the
+//! labels and headings in document order. Headings nest under the label
+//! before them, so a memo's body headings appear below its subject.
+//!
+//! The memo template also marks every numbered paragraph with
+//! [`PARAGRAPH_LABEL`]. Paragraphs nest under the label before them and
+//! under the paragraph they are indented in, titled with their number and
+//! opening words, e.g. `2. The unit will...`. They are numbered through the
+//! section at each indent, unless the item sets its own number.
+//!
+//! [`RenderConfig::generate_outline`](crate::RenderConfig::generate_outline)
+//! turns the outline off, including the one Typst writes for headings.
+
+use std::ops::Range;
+
+use typst::foundations::{Content, Label, NativeElement, SequenceElem, Selector, StyleChain, Value};
+use typst::introspection::MetadataElem;
+use typst::layout::{Abs, PagedDocument};
+use typst::model::{EnumItem, HeadingElem, ListItem, ParbreakElem};
+use typst::utils::PicoStr;
+
+use crate::locate::{BOOKMARK_LABEL, PARAGRAPH_LABEL};
+use crate::pdf::{dict_value, exported_page, text_string, with_entry, without_entry, PdfUpdate};
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Characters of a paragraph's text its bookmark shows
+const PARAGRAPH_TITLE_LENGTH: usize = 48;
+/// Largest difference in points between the indents of sibling paragraphs
+const INDENT_TOLERANCE: f64 = 0.5;
+
+/// An entry of the outline, with the entries nested under it
+#[derive(Debug, PartialEq)]
+struct Bookmark {
+    title: String,
+    level: usize,
+    /// Page index, starting at 0
+    page: usize,
+    /// Position on the page, in points from the top left corner
+    x: f64,
+    y: f64,
+    children: Vec<Bookmark>,
+}
+
+impl Bookmark {
+    fn len(&self) -> usize {
+        1 + self.children.iter().map(Bookmark::len).sum::<usize>()
+    }
+}
+
+/// Rebuild the outline of an exported PDF of the document pages `exported`
+/// when `document` has labelled bookmarks, returning the PDF unchanged
+/// otherwise
+pub(crate) fn add_outline(document: &PagedDocument, pdf: Vec<u8>, exported: Range<usize>) -> Result<Vec<u8>, TypstWrapperError> {
+    let tree = on_pages(outline(document), &exported);
+    if tree.is_empty() {
+        return Ok(pdf);
+    }
+    let error = |e: String| TypstWrapperError::OutputFormat(format!("PDF bookmarks could not be written: {}", e));
+    let mut update = PdfUpdate::parse(&pdf).map_err(error)?;
+    let pages = update.pages().map_err(error)?;
+    let heights: Vec<f64> = pages.iter().map(|page| update.page_size(*page).map_or(0.0, |(_, height)| height)).collect();
+
+    let root = update.allocate();
+    let first = write_items(&mut update, &tree, root, &pages, &heights);
+    let last = first + tree[..tree.len() - 1].iter().map(Bookmark::len).sum::<usize>() as u32;
+    update.set(
+        root,
+        format!("<<\n  /Type /Outlines\n  /First {} 0 R\n  /Last {} 0 R\n  /Count {}\n>>", first, last, tree.len()),
+    );
+
+    let catalog = update.object(update.root()).ok_or_else(|| error("missing catalog".to_string()))?;
+    let catalog = with_entry(&catalog, "Outlines", &format!("{} 0 R", root))
+        .and_then(|catalog| with_entry(&catalog, "PageMode", "/UseOutlines"))
+        .ok_or_else(|| error("invalid catalog".to_string()))?;
+    let root = update.root();
+    update.set(root, catalog);
+    Ok(update.finish().0)
+}
+
+/// The bookmarks of `tree` on the document pages `exported`, with the page
+/// indices of the exported PDF. Bookmarks nested under one on another page
+/// take its place, as Typst does for headings.
+fn on_pages(tree: Vec<Bookmark>, exported: &Range<usize>) -> Vec<Bookmark> {
+    tree.into_iter()
+        .flat_map(|bookmark| {
+            let children = on_pages(bookmark.children, exported);
+            match exported_page(bookmark.page, exported) {
+                Some(page) => vec![Bookmark { page, children, ..bookmark }],
+                None => children,
+            }
+        })
+        .collect()
+}
+
+/// Remove the outline Typst writes for headings from an exported PDF
+pub(crate) fn remove_outline(pdf: Vec<u8>) -> Result<Vec<u8>, TypstWrapperError> {
+    let error = |e: String| TypstWrapperError::OutputFormat(format!("PDF bookmarks could not be removed: {}", e));
+    let mut update = PdfUpdate::parse(&pdf).map_err(error)?;
+    let root = update.root();
+    let catalog = update.object(root).ok_or_else(|| error("missing catalog".to_string()))?;
+    if dict_value(&catalog, "Outlines").is_none() {
+        return Ok(pdf);
+    }
+    let mut catalog = without_entry(&catalog, "Outlines").ok_or_else(|| error("invalid catalog".to_string()))?;
+    if dict_value(&catalog, "PageMode") == Some("/UseOutlines") {
+        catalog = without_entry(&catalog, "PageMode").ok_or_else(|| error("invalid catalog".to_string()))?;
+    }
+    update.set(root, catalog);
+    Ok(update.finish().0)
+}
+
+/// Write `items` and their children as siblings under `parent`, returning
+/// the object number of the first. Each item is followed by its descendants
+/// in object number order.
+fn write_items(update: &mut PdfUpdate, items: &[Bookmark], parent: u32, pages: &[u32], heights: &[f64]) -> u32 {
+    let mut numbers: Vec<u32> = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let number = update.allocate();
+        numbers.push(number);
+        let mut object = format!("<<\n  /Title {}\n  /Parent {} 0 R\n", text_string(&item.title), parent);
+        if index > 0 {
+            object.push_str(&format!("  /Prev {} 0 R\n", numbers[index - 1]));
+        }
+        if index + 1 < items.len() {
+            object.push_str(&format!("  /Next {} 0 R\n", number + item.len() as u32));
+        }
+        if let (Some(page), Some(height)) = (pages.get(item.page), heights.get(item.page)) {
+            object.push_str(&format!("  /Dest [{} 0 R /XYZ {} {} 0]\n", page, item.x, height - item.y));
+        }
+        if !item.children.is_empty() {
+            let first = write_items(update, &item.children, number, pages, heights);
+            let last = first + item.children[..item.children.len() - 1].iter().map(Bookmark::len).sum::<usize>() as u32;
+            object.push_str(&format!(
+                "  /First {} 0 R\n  /Last {} 0 R\n  /Count -{}\n",
+                first,
+                last,
+                item.children.len()
+            ));
+        }
+        object.push_str(">>");
+        update.set(number, object);
+    }
+    numbers[0]
+}
+
+/// The outline of `document`, or nothing when it has no labelled bookmarks
+fn outline(document: &PagedDocument) -> Vec<Bookmark> {
+    let introspector = &document.introspector;
+    let label = Selector::Label(Label::new(PicoStr::intern(BOOKMARK_LABEL)));
+    if introspector.query(&label).is_empty() {
+        return Vec::new();
+    }
+
+    let mut tree: Vec<Bookmark> = Vec::new();
+    // Level of the latest labelled bookmark, which headings nest under
+    let mut section_level = 0;
+    // Indent and number of the latest paragraph at each depth of the section
+    let mut paragraphs: Vec<(f64, i64)> = Vec::new();
+    let paragraph = Selector::Label(Label::new(PicoStr::intern(PARAGRAPH_LABEL)));
+    let selector = Selector::Or([label, paragraph, HeadingElem::elem().select()].into());
+    for element in introspector.query(&selector).iter() {
+        let Some(location) = element.location() else {
+            continue;
+        };
+        let position = introspector.position(location);
+        let is_paragraph = element.label().is_some_and(|label| label.resolve().as_str() == PARAGRAPH_LABEL);
+        let (title, level) = if is_paragraph {
+            let Some((number, body)) = element.to_packed::<MetadataElem>().and_then(|metadata| numbered(&metadata.value))
+            else {
+                continue;
+            };
+            let x = position.point.x.to_pt();
+            while paragraphs.last().is_some_and(|(indent, _)| *indent > x + INDENT_TOLERANCE) {
+                paragraphs.pop();
+            }
+            match paragraphs.last_mut() {
+                Some((indent, latest)) if (*indent - x).abs() <= INDENT_TOLERANCE => *latest = number.unwrap_or(*latest + 1),
+                _ => paragraphs.push((x, number.unwrap_or(1))),
+            }
+            let number = paragraphs.last().map_or(1, |(_, number)| *number);
+            (format!("{}. {}", number, paragraph_title(&body)), section_level + paragraphs.len())
+        } else if let Some(heading) = element.to_packed::<HeadingElem>() {
+            let bookmarked = heading
+                .bookmarked(StyleChain::default())
+                .unwrap_or_else(|| heading.outlined(StyleChain::default()));
+            if !bookmarked {
+                continue;
+            }
+            (heading.body.plain_text().trim().to_string(), section_level + heading.resolve_level(StyleChain::default()).get())
+        } else if let Some((title, level)) = element.to_packed::<MetadataElem>().and_then(|metadata| labelled(&metadata.value)) {
+            section_level = level;
+            paragraphs.clear();
+            (title, level)
+        } else {
+            continue;
+        };
+
+        let y = (position.point.y - Abs::pt(10.0)).max(Abs::zero());
+        let bookmark = Bookmark {
+            title,
+            level,
+            page: position.page.get() - 1,
+            x: position.point.x.to_pt(),
+            y: y.to_pt(),
+            children: Vec::new(),
+        };
+        // Nest under the latest entry of each lower level
+        let mut siblings = &mut tree;
+        while siblings.last().is_some_and(|last| last.level < level) {
+            siblings = &mut siblings.last_mut().unwrap().children;
+        }
+        siblings.push(bookmark);
+    }
+    tree
+}
+
+/// Title and level of a labelled bookmark's metadata value
+fn labelled(value: &Value) -> Option<(String, usize)> {
+    match value {
+        Value::Str(title) => Some((title.to_string(), 1)),
+        Value::Dict(dict) => {
+            let title = match dict.get("title").ok()? {
+                Value::Str(title) => title.to_string(),
+                Value::Content(content) => content.plain_text().to_string(),
+                _ => return None,
+            };
+            let level = match dict.get("level") {
+                Ok(Value::Int(level)) => usize::try_from(*level).ok().filter(|level| *level > 0)?,
+                Ok(_) => return None,
+                Err(_) => 1,
+            };
+            Some((title, level))
+        }
+        Value::Content(content) => Some((content.plain_text().to_string(), 1)),
+        _ => None,
+    }
+}
+
+/// Number, if the item sets one, and body of a numbered paragraph's
+/// metadata value
+fn numbered(value: &Value) -> Option<(Option<i64>, Content)> {
+    let Value::Dict(dict) = value else {
+        return None;
+    };
+    let number = match dict.get("number") {
+        Ok(Value::Int(number)) => Some(*number),
+        _ => None,
+    };
+    match dict.get("body").ok()? {
+        Value::Content(body) => Some((number, body.clone())),
+        Value::Str(body) => Some((number, typst::text::TextElem::packed(body.clone()))),
+        _ => None,
+    }
+}
+
+/// The text of a paragraph up to the paragraphs nested in it, cut to
+/// [`PARAGRAPH_TITLE_LENGTH`] characters
+fn paragraph_title(body: &Content) -> String {
+    let children = match body.to_packed::<SequenceElem>() {
+        Some(sequence) => sequence.children.iter().collect(),
+        None => vec![body],
+    };
+    let mut text = String::new();
+    for child in children {
+        if child.is::<EnumItem>() || child.is::<ListItem>() || child.is::<ParbreakElem>() {
+            break;
+        }
+        text.push_str(&child.plain_text());
+    }
+    let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if words.chars().count() <= PARAGRAPH_TITLE_LENGTH {
+        return words;
+    }
+    let cut: String = words.chars().take(PARAGRAPH_TITLE_LENGTH).collect();
+    format!("{}...", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::dict_value;
+    use crate::test_utils::MemoBuilder;
+    use crate::typst_wrapper::TypstWrapper;
+
+    #[test]
+    fn test_outline_nests_headings_under_labels() {
+        let markup = "#metadata(\"Memo\") <bookmark>\n= Background\n== Detail\n= Request\n#pagebreak()\n\
+                      #metadata((title: [1st Ind], level: 1)) <bookmark>\n= Reply\n\
+                      #metadata((title: \"Atch 1\", level: 2)) <bookmark>";
+        let document = TypstWrapper::compile_markup(markup).unwrap();
+        let tree = outline(&document);
+        let titles = |items: &[Bookmark]| items.iter().map(|item| item.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&tree), ["Memo", "1st Ind"]);
+        assert_eq!(titles(&tree[0].children), ["Background", "Request"]);
+        assert_eq!(titles(&tree[0].children[0].children), ["Detail"]);
+        assert_eq!(titles(&tree[1].children), ["Reply", "Atch 1"]);
+        assert_eq!(tree[1].page, 1);
+
+        assert!(outline(&TypstWrapper::compile_markup("= Only headings").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_memo_paragraphs_nest_under_the_subject() {
+        let form = MemoBuilder::minimal()
+            .subject("Paragraphs")
+            .body_raw("+ Purpose of this memorandum.\n+ Background of the request, which runs on for longer than a bookmark shows.\n  + First detail.\n  + Second detail.\n+ Request.\n\n= Heading")
+            .to_json();
+        let tree = outline(&TypstWrapper::compile_form(&form).unwrap());
+        assert_eq!(tree.len(), 1);
+        let titles = |items: &[Bookmark]| items.iter().map(|item| item.title.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            titles(&tree[0].children),
+            [
+                "1. Purpose of this memorandum.",
+                "2. Background of the request, which runs on for lon...",
+                "3. Request.",
+                "Heading"
+            ]
+        );
+        assert_eq!(titles(&tree[0].children[1].children), ["1. First detail.", "2. Second detail."]);
+        let (first, second) = (&tree[0].children[0], &tree[0].children[1]);
+        assert!(second.y > first.y && second.children[0].x > second.x, "{:?}", tree);
+    }
+
+    #[test]
+    fn test_outlines_can_be_turned_off() {
+        let markup = "#metadata(\"Memo\") <bookmark>\n= Background";
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, generate_outline: false, ..Default::default() };
+        for markup in [markup, "= Only headings"] {
+            let pdf = crate::render_markup(markup, Some(config.clone())).unwrap().remove(0);
+            let update = PdfUpdate::parse(&pdf).unwrap();
+            let catalog = update.object(update.root()).unwrap();
+            assert!(dict_value(&catalog, "Outlines").is_none() && dict_value(&catalog, "PageMode").is_none(), "{}", catalog);
+        }
+    }
+
+    #[test]
+    fn test_pdf_outline() {
+        let markup = "#metadata(\"Memo\") <bookmark>\n= Background\n#pagebreak()\n#metadata(\"1st Ind\") <bookmark>";
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let pdf = crate::render_markup(markup, Some(config)).unwrap().remove(0);
+
+        let update = PdfUpdate::parse(&pdf).unwrap();
+        let catalog = update.object(update.root()).unwrap();
+        assert_eq!(dict_value(&catalog, "PageMode"), Some("/UseOutlines"));
+        let root = update.object(dict_value(&catalog, "Outlines").and_then(crate::pdf::reference).unwrap()).unwrap();
+        assert_eq!(dict_value(&root, "Count"), Some("2"));
+        let first = update.object(dict_value(&root, "First").and_then(crate::pdf::reference).unwrap()).unwrap();
+        assert_eq!(dict_value(&first, "Title"), Some("(Memo)"));
+        assert_eq!(dict_value(&first, "Count"), Some("-1"));
+        let last = update.object(dict_value(&root, "Last").and_then(crate::pdf::reference).unwrap()).unwrap();
+        assert_eq!(dict_value(&last, "Title"), Some("(1st Ind)"));
+        let pages = update.pages().unwrap();
+        assert!(dict_value(&last, "Dest").unwrap().starts_with(&format!("[{} 0 R /XYZ", pages[1])));
+    }
+}
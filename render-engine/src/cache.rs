@@ -0,0 +1,484 @@
+//! Render output caches.
+//!
+//! Hosts install a [`CacheProvider`] to keep rendered output across sessions:
+//! IndexedDB in the browser, a directory on disk natively ([`DiskCache`]).
+//! Interactive applications can also keep recent renders in memory with
+//! [`set_output_cache_limits`], which evicts the least recently used ones
+//! beyond a number of entries or bytes. Before rendering,
+//! [`render_markup`](crate::render_markup),
+//! [`render_form`](crate::render_form) and their variants look the input up
+//! by key, in memory first, and skip compilation entirely on a hit; after a
+//! successful render they store the output under that key. Renders with
+//! [`RenderConfig::cache`] turned off bypass both.
+//!
+//! Keys are derived from the engine version, the input kind and text, the
+//! output format, the watermark, the running header and footer, the duplex
+//! option, the print profile, whether SVG links, text layouts and form fields
+//! are added, the review annotations, the font substitutions, the style
+//! tokens, the color mode, the date of today and the engine's registrations
+//! (letterhead backgrounds, files, local packages, fonts, the memo template
+//! and its partials), so they change whenever anything that affects the
+//! output does; without [`RenderConfig::today`], they change every day. Renders
+//! that ask for today's date at an offset from UTC
+//! (`datetime.today(offset: ..)`) are not stored, since that date changes at
+//! another hour than the local one. Keys only contain ASCII letters, digits,
+//! `-` and `.`. The same hash is available without a provider through
+//! [`hash_markup_render`] and [`hash_form_render`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, PoisonError, RwLock};
+
+use tracing::debug;
+
+use crate::assets;
+use crate::fonts::FontSubstitution;
+use crate::metrics::{PageSize, RenderOutput};
+use crate::today;
+use crate::typst_wrapper::RenderConfig;
+
+/// Storage for rendered output, keyed by strings
+///
+/// Both methods are called on the rendering thread, so they should be quick.
+/// Failures are the provider's to handle: a failed `get` is a miss and a
+/// failed `put` is simply not cached.
+pub trait CacheProvider: Send + Sync {
+    /// The bytes previously stored under `key`, if any
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `bytes` under `key`, replacing any previous value
+    fn put(&self, key: &str, bytes: &[u8]);
+}
+
+static CACHE_PROVIDER: LazyLock<RwLock<Option<Arc<dyn CacheProvider>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Serve and store renders through `provider`, replacing any previous provider
+pub fn set_cache_provider<P>(provider: P)
+where
+    P: CacheProvider + 'static,
+{
+    *CACHE_PROVIDER.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(provider));
+}
+
+/// Stop using the provider installed with [`set_cache_provider`]
+pub fn clear_cache_provider() {
+    *CACHE_PROVIDER.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+fn provider() -> Option<Arc<dyn CacheProvider>> {
+    CACHE_PROVIDER.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Limits of the in-memory output cache
+///
+/// # Examples
+/// ```
+/// use render_engine::{set_output_cache_limits, OutputCacheLimits};
+///
+/// set_output_cache_limits(OutputCacheLimits {
+///     max_entries: 16,
+///     ..OutputCacheLimits::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputCacheLimits {
+    /// Most renders kept
+    pub max_entries: usize,
+    /// Most bytes of output kept; larger renders are not cached
+    pub max_bytes: usize,
+}
+
+impl Default for OutputCacheLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 64,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Recently used renders, evicting the least recently used beyond the limits
+struct MemoryCache {
+    limits: OutputCacheLimits,
+    entries: HashMap<String, MemoryEntry>,
+    bytes: usize,
+    /// Incremented on every use, so the smallest `used` is the oldest
+    clock: u64,
+}
+
+struct MemoryEntry {
+    render: CachedRender,
+    used: u64,
+}
+
+impl MemoryCache {
+    fn new(limits: OutputCacheLimits) -> Self {
+        Self { limits, entries: HashMap::new(), bytes: 0, clock: 0 }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CachedRender> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used = self.clock;
+        Some(entry.render.clone())
+    }
+
+    fn insert(&mut self, key: &str, render: CachedRender) {
+        self.remove(key);
+        if render.bytes() > self.limits.max_bytes || self.limits.max_entries == 0 {
+            return;
+        }
+        self.clock += 1;
+        self.bytes += render.bytes();
+        self.entries.insert(key.to_string(), MemoryEntry { render, used: self.clock });
+        self.evict();
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.render.bytes();
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.limits.max_entries || self.bytes > self.limits.max_bytes {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(key, _)| key.clone()) else {
+                break;
+            };
+            debug!(key = oldest, "evicted from output cache");
+            self.remove(&oldest);
+        }
+    }
+}
+
+static MEMORY_CACHE: LazyLock<Mutex<Option<MemoryCache>>> = LazyLock::new(|| Mutex::new(None));
+
+fn memory_cache() -> std::sync::MutexGuard<'static, Option<MemoryCache>> {
+    MEMORY_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keep recent renders in memory within `limits`, evicting renders already
+/// kept if the new limits are lower
+pub fn set_output_cache_limits(limits: OutputCacheLimits) {
+    let mut cache = memory_cache();
+    match cache.as_mut() {
+        Some(cache) => {
+            cache.limits = limits;
+            cache.evict();
+        }
+        None => *cache = Some(MemoryCache::new(limits)),
+    }
+}
+
+/// Stop keeping renders in memory and free the ones kept
+pub fn clear_output_cache() {
+    *memory_cache() = None;
+}
+
+/// The cache key of a render, or `None` when it is not cached: no cache is
+/// set up or the render bypasses caching
+pub(crate) fn key(kind: &str, input: &str, config: &RenderConfig) -> Option<String> {
+    if !config.cache || (memory_cache().is_none() && provider().is_none()) {
+        return None;
+    }
+    let hash = fingerprint(kind, input, config);
+    Some(format!("render-engine-{}-{}-{}-{:032x}", env!("CARGO_PKG_VERSION"), kind, config.format.name(), hash))
+}
+
+/// Hash of everything that determines the output of a render, see the
+/// [module documentation](self)
+pub(crate) fn fingerprint(kind: &str, input: &str, config: &RenderConfig) -> u128 {
+    let watermark = config
+        .watermark
+        .as_ref()
+        .map(|watermark| (&watermark.text, &watermark.color, watermark.opacity.to_bits()));
+    let layout = (
+        &config.header,
+        &config.footer,
+        config.duplex,
+        &config.print,
+        config.svg_links,
+        config.scale.to_bits(),
+        config.text_layout,
+        config.form_fields,
+        config.embed_form,
+        &config.font_substitutions,
+        config.strict_packages,
+        config.color_mode,
+    );
+    let annotations = serde_json::to_string(&config.annotations).ok();
+    let style = serde_json::to_string(&config.style).ok();
+    let versions = (env!("CARGO_PKG_VERSION"), assets::template_fingerprint(), assets::registration_fingerprint());
+    let output = (
+        &config.metadata,
+        today::key(config.today.as_deref()),
+        config.generate_outline,
+        &config.page_range,
+        config.stack_pages,
+        config.svg_text,
+        &config.id_prefix,
+    );
+    typst::utils::hash128(&(kind, input, config.format.name(), watermark, layout, output, annotations, style, versions))
+}
+
+/// A stable hash of the output of rendering Typst markup with `config`,
+/// as 32 hexadecimal digits
+///
+/// Equal hashes mean equal output, so callers can deduplicate stored renders
+/// and skip renders they already have without producing any bytes. The hash
+/// covers the engine version and embedded template as well as the input and
+/// configuration, and changes when a letterhead background, file, local
+/// package, font, memo template or partial is registered.
+///
+/// # Examples
+/// ```
+/// use render_engine::{hash_markup_render, OutputFormat, RenderConfig};
+///
+/// let svg = hash_markup_render("= Hello", None);
+/// assert_eq!(svg, hash_markup_render("= Hello", None));
+/// let pdf = RenderConfig { format: OutputFormat::Pdf, ..RenderConfig::default() };
+/// assert_ne!(svg, hash_markup_render("= Hello", Some(&pdf)));
+/// ```
+pub fn hash_markup_render(markup: &str, config: Option<&RenderConfig>) -> String {
+    hash_render("markup", markup, config)
+}
+
+/// A stable hash of the output of rendering a memo form (JSON), see
+/// [`hash_markup_render`]
+///
+/// The JSON is hashed by value, so forms that only differ in whitespace or
+/// key order hash the same. Input that is not valid JSON is hashed as text.
+pub fn hash_form_render(json_input: &str, config: Option<&RenderConfig>) -> String {
+    let normalized = serde_json::from_str::<serde_json::Value>(json_input)
+        .ok()
+        .and_then(|value| serde_json::to_string(&value).ok());
+    hash_render("form", normalized.as_deref().unwrap_or(json_input), config)
+}
+
+fn hash_render(kind: &str, input: &str, config: Option<&RenderConfig>) -> String {
+    let default = RenderConfig::default();
+    format!("{:032x}", fingerprint(kind, input, config.unwrap_or(&default)))
+}
+
+/// A cached render: the sizes of the document's pages, the font
+/// substitutions it used and the exported pages
+#[derive(Clone)]
+pub(crate) struct CachedRender {
+    pub(crate) page_sizes: Vec<PageSize>,
+    pub(crate) font_substitutions: Vec<FontSubstitution>,
+    pub(crate) pages: Vec<Vec<u8>>,
+}
+
+impl CachedRender {
+    fn bytes(&self) -> usize {
+        self.pages.iter().map(Vec::len).sum()
+    }
+}
+
+/// Look `key` up in memory, then with the installed provider
+pub(crate) fn load(key: &str) -> Option<CachedRender> {
+    if let Some(cached) = memory_cache().as_mut().and_then(|cache| cache.get(key)) {
+        debug!(key, "output cache hit");
+        return Some(cached);
+    }
+    let bytes = provider()?.get(key)?;
+    let cached = decode(&bytes);
+    debug!(key, hit = cached.is_some(), "persistent cache lookup");
+    if let (Some(cache), Some(cached)) = (memory_cache().as_mut(), &cached) {
+        cache.insert(key, cached.clone());
+    }
+    cached
+}
+
+/// Store a render under `key` in memory and with the installed provider
+pub(crate) fn store(key: &str, output: &RenderOutput) {
+    let cached = CachedRender {
+        page_sizes: output.page_sizes.clone(),
+        font_substitutions: output.font_substitutions.clone(),
+        pages: output.pages.clone(),
+    };
+    if let Some(provider) = provider() {
+        provider.put(key, &encode(&cached));
+    }
+    if let Some(cache) = memory_cache().as_mut() {
+        cache.insert(key, cached);
+    }
+}
+
+/// First bytes of an encoded render. Entries written before page sizes were
+/// stored start with a small page count and those written before font
+/// substitutions were stored with `REC2`; both are misses.
+const ENTRY_MAGIC: &[u8; 4] = b"REC3";
+
+/// Serialize a render as [`ENTRY_MAGIC`], the little-endian `u32` document
+/// page count and each page's width and height as `f64`s, the `u32` count of
+/// font substitutions and each one's families as `u32` length and UTF-8
+/// bytes, then the `u32` exported page count and each page as a `u32` length
+/// and its bytes
+fn encode(cached: &CachedRender) -> Vec<u8> {
+    let CachedRender { page_sizes, font_substitutions, pages } = cached;
+    let mut bytes = Vec::with_capacity(16 + 16 * page_sizes.len() + pages.iter().map(|page| 4 + page.len()).sum::<usize>());
+    bytes.extend_from_slice(ENTRY_MAGIC);
+    bytes.extend_from_slice(&(page_sizes.len() as u32).to_le_bytes());
+    for size in page_sizes {
+        bytes.extend_from_slice(&size.width.to_le_bytes());
+        bytes.extend_from_slice(&size.height.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(font_substitutions.len() as u32).to_le_bytes());
+    for family in font_substitutions.iter().flat_map(|substitution| [&substitution.from, &substitution.to]) {
+        bytes.extend_from_slice(&(family.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(family.as_bytes());
+    }
+    bytes.extend_from_slice(&(pages.len() as u32).to_le_bytes());
+    for page in pages {
+        bytes.extend_from_slice(&(page.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(page);
+    }
+    bytes
+}
+
+/// Parse the output of [`encode`], rejecting truncated or trailing data
+fn decode(mut bytes: &[u8]) -> Option<CachedRender> {
+    fn take_u32(bytes: &mut &[u8]) -> Option<usize> {
+        let (head, rest) = bytes.split_first_chunk::<4>()?;
+        *bytes = rest;
+        Some(u32::from_le_bytes(*head) as usize)
+    }
+
+    fn take_f64(bytes: &mut &[u8]) -> Option<f64> {
+        let (head, rest) = bytes.split_first_chunk::<8>()?;
+        *bytes = rest;
+        Some(f64::from_le_bytes(*head))
+    }
+
+    fn take_bytes<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let len = take_u32(bytes)?;
+        if bytes.len() < len {
+            return None;
+        }
+        let (head, rest) = bytes.split_at(len);
+        *bytes = rest;
+        Some(head)
+    }
+
+    fn take_string(bytes: &mut &[u8]) -> Option<String> {
+        String::from_utf8(take_bytes(bytes)?.to_vec()).ok()
+    }
+
+    bytes = bytes.strip_prefix(ENTRY_MAGIC)?;
+    let document_pages = take_u32(&mut bytes)?;
+    let mut page_sizes = Vec::new();
+    for _ in 0..document_pages {
+        page_sizes.push(PageSize { width: take_f64(&mut bytes)?, height: take_f64(&mut bytes)? });
+    }
+    let mut font_substitutions = Vec::new();
+    for _ in 0..take_u32(&mut bytes)? {
+        font_substitutions.push(FontSubstitution { from: take_string(&mut bytes)?, to: take_string(&mut bytes)? });
+    }
+    let mut pages = Vec::new();
+    for _ in 0..take_u32(&mut bytes)? {
+        pages.push(take_bytes(&mut bytes)?.to_vec());
+    }
+    (bytes.is_empty() && !pages.is_empty()).then_some(CachedRender { page_sizes, font_substitutions, pages })
+}
+
+/// A [`CacheProvider`] storing each entry as a file in a directory
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DiskCache {
+    /// Use `dir` for the cache, creating it if needed
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CacheProvider for DiskCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.dir.join(key)).ok()
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) {
+        // Write then rename so concurrent readers never see a partial entry
+        let path = self.dir.join(key);
+        let partial = self.dir.join(format!("{}.partial", key));
+        let result = std::fs::write(&partial, bytes).and_then(|()| std::fs::rename(&partial, &path));
+        if let Err(e) = result {
+            debug!(key, error = %e, "could not write cache entry");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_hashes() {
+        let hash = hash_markup_render("= Hello", None);
+        assert_eq!(hash.len(), 32);
+        assert_eq!(hash, hash_markup_render("= Hello", Some(&RenderConfig::default())));
+        assert_ne!(hash, hash_markup_render("= Hello!", None));
+        let config = RenderConfig { svg_links: false, ..RenderConfig::default() };
+        assert_ne!(hash, hash_markup_render("= Hello", Some(&config)));
+
+        let form = hash_form_render(r#"{"subject": "Hash", "body_raw": "Body"}"#, None);
+        assert_eq!(form, hash_form_render("{ \"body_raw\": \"Body\",\n  \"subject\": \"Hash\" }", None));
+        assert_ne!(form, hash_markup_render(r#"{"body_raw":"Body","subject":"Hash"}"#, None));
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let render = |bytes: usize| CachedRender { page_sizes: Vec::new(), font_substitutions: Vec::new(), pages: vec![vec![0; bytes]] };
+        let mut cache = MemoryCache::new(OutputCacheLimits { max_entries: 2, max_bytes: 100 });
+        cache.insert("a", render(10));
+        cache.insert("b", render(10));
+        assert!(cache.get("a").is_some());
+        cache.insert("c", render(10));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+
+        // Too large to keep at all, then large enough to push out the rest
+        cache.insert("d", render(101));
+        assert!(cache.get("d").is_none());
+        cache.insert("e", render(95));
+        assert_eq!((cache.entries.len(), cache.bytes), (1, 95));
+        assert!(cache.get("e").is_some());
+
+        let bypass = RenderConfig { cache: false, ..RenderConfig::default() };
+        assert!(key("markup", "= Hello", &bypass).is_none());
+    }
+
+    #[test]
+    fn test_encoding_round_trips() {
+        let pages = vec![b"<svg>1</svg>".to_vec(), Vec::new(), b"<svg>3</svg>".to_vec()];
+        let sizes = [PageSize { width: 612.0, height: 792.0 }, PageSize { width: 595.28, height: 841.89 }];
+        let substitutions = vec![FontSubstitution { from: "Times New Roman".to_string(), to: "Times".to_string() }];
+        let render = CachedRender { page_sizes: sizes.to_vec(), font_substitutions: substitutions.clone(), pages: pages.clone() };
+        let cached = decode(&encode(&render)).unwrap();
+        assert_eq!(cached.page_sizes, sizes);
+        assert_eq!(cached.font_substitutions, substitutions);
+        assert_eq!(cached.pages, pages);
+    }
+
+    #[test]
+    fn test_corrupt_entries_are_misses() {
+        let render = |pages: Vec<Vec<u8>>| CachedRender {
+            page_sizes: vec![PageSize { width: 612.0, height: 792.0 }],
+            font_substitutions: Vec::new(),
+            pages,
+        };
+        let bytes = encode(&render(vec![b"%PDF-1.7".to_vec()]));
+        assert!(decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(decode(&[bytes.as_slice(), b"x"].concat()).is_none());
+        assert!(decode(&encode(&render(Vec::new()))).is_none());
+        // Entries from before page sizes were stored
+        assert!(decode(&[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, b'x']).is_none());
+    }
+}
@@ -0,0 +1,125 @@
+//! What the running engine can do.
+//!
+//! Integrators that ship against several builds of the engine, with and
+//! without optional Cargo features, or that register letterheads, files,
+//! packages and templates at runtime, can feature-detect with [`capabilities`]
+//! instead of guessing from version numbers.
+
+use serde::Serialize;
+
+use crate::template::{self, Partial};
+use crate::{assets, packages};
+
+/// Output formats every build supports, by the names the wasm, server and
+/// CLI interfaces use
+const OUTPUT_FORMATS: [&str; 3] = ["svg", "pdf", "svg-zip"];
+
+/// The formats, fonts, templates, packages and optional features of the
+/// current engine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Version of the render engine crate
+    pub version: String,
+    /// Output format names: `svg`, `pdf` and `svg-zip`
+    pub output_formats: Vec<String>,
+    /// Font families documents can use
+    pub fonts: Vec<String>,
+    /// Template memo forms render with: `memo-loader`, or `custom` after
+    /// [`set_memo_template`](crate::set_memo_template)
+    pub memo_template: String,
+    /// Partials of the memo template that can be overridden, and which of
+    /// them are
+    pub template_partials: Vec<String>,
+    pub overridden_partials: Vec<String>,
+    /// Packages markup can import, embedded ones first, as
+    /// `@namespace/name:version`
+    pub packages: Vec<String>,
+    /// Registered letterhead backgrounds
+    pub letterheads: Vec<String>,
+    /// Paths of registered images and other files
+    pub assets: Vec<String>,
+    pub features: Features,
+}
+
+/// Optional features and whether this build has them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Features {
+    /// PDF/X print output, see [`print`](crate::print)
+    pub pdf_x: bool,
+    /// PDF/A archival output
+    pub pdf_a: bool,
+    /// PAdES signatures of rendered PDFs (Cargo feature `signing`)
+    pub signing: bool,
+    /// Fonts installed on the host; the engine only uses embedded fonts
+    pub system_fonts: bool,
+    /// Word document import (Cargo feature `docx`)
+    pub docx: bool,
+    /// Multi-threaded export and font parsing (Cargo feature `parallel`)
+    pub parallel: bool,
+    /// The HTTP render service (Cargo feature `server`)
+    pub server: bool,
+}
+
+/// The capabilities of the current engine
+///
+/// # Examples
+/// ```
+/// let capabilities = render_engine::capabilities();
+/// assert!(capabilities.output_formats.iter().any(|format| format == "pdf"));
+/// if !capabilities.features.signing {
+///     println!("signing is not available in this build");
+/// }
+/// ```
+pub fn capabilities() -> Capabilities {
+    let manifest = assets::get_asset_manifest();
+    let mut packages = vec![assets::memo_package_spec()];
+    packages.extend(packages::local_package_specs());
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        output_formats: OUTPUT_FORMATS.iter().map(|format| format.to_string()).collect(),
+        fonts: manifest.fonts,
+        memo_template: if template::has_custom_memo_template() { "custom" } else { "memo-loader" }.to_string(),
+        template_partials: Partial::ALL.iter().map(|partial| partial.name().to_string()).collect(),
+        overridden_partials: template::template_partial_overrides()
+            .into_iter()
+            .map(|(partial, _)| partial.name().to_string())
+            .collect(),
+        packages,
+        letterheads: manifest.letterheads,
+        assets: assets::registered_asset_paths(),
+        features: Features {
+            pdf_x: true,
+            pdf_a: false,
+            signing: cfg!(feature = "signing"),
+            system_fonts: false,
+            docx: cfg!(feature = "docx"),
+            parallel: cfg!(feature = "parallel"),
+            server: cfg!(feature = "server"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderEngine;
+
+    #[test]
+    fn test_capabilities_follow_the_engine() {
+        let engine = RenderEngine::new();
+        let before = engine.capabilities();
+        assert_eq!(before.memo_template, "memo-loader");
+        assert_eq!(before.fonts.iter().any(|family| family == "Copperplate CC"), cfg!(feature = "font-copperplate"));
+        assert_eq!(before.packages, ["@preview/tonguetoquill-usaf-memo:0.1.0"]);
+        assert!(before.overridden_partials.is_empty());
+
+        engine.set_template_partial(Partial::Footer, "#let footer(input, date) = doc => doc").unwrap();
+        let gif = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        engine.register_letterhead("stationery.gif", gif.clone()).unwrap();
+        engine.register_asset("/emblems/unit.gif", gif).unwrap();
+        let after = engine.capabilities();
+        assert_eq!(after.overridden_partials, ["footer"]);
+        assert_eq!(after.letterheads, ["stationery.gif"]);
+        assert_eq!(after.assets, ["emblems/unit.gif"]);
+    }
+}
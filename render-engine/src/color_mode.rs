@@ -0,0 +1,235 @@
+//! Grayscale and high-contrast output.
+//!
+//! Drafts printed on monochrome printers come out more predictably when the
+//! engine converts colors itself, and readers who need more contrast than
+//! the memo's colors give can ask for it. [`RenderConfig::color_mode`]
+//! rewrites the colors of the compiled document before export, so SVG and
+//! PDF output match:
+//!
+//! - [`ColorMode::Grayscale`] turns text, shapes, gradients and raster
+//!   images, the letterhead seal included, into shades of gray.
+//! - [`ColorMode::HighContrast`] turns text and shapes black or white,
+//!   whichever is closer, and raises the contrast of grayscale images.
+//!
+//! Transparency is kept. SVG images and tiling patterns are left as they
+//! are.
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Bytes, Dict, FromValue, Smart, Value};
+use typst::layout::{Frame, FrameItem, PagedDocument};
+use typst::visualize::{Color, Gradient, Image, ImageKind, Paint, PixelFormat, RasterImage};
+
+use crate::typst_wrapper::TypstWrapperError;
+
+/// How the colors of the output are rendered
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, ColorMode, RenderConfig};
+///
+/// let config = RenderConfig { color_mode: ColorMode::Grayscale, ..RenderConfig::default() };
+/// let pages = render_markup("#text(fill: red)[Draft]", Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    /// Colors as the document sets them
+    #[default]
+    Color,
+    /// Shades of gray, for monochrome printers
+    Grayscale,
+    /// Black and white text and shapes, and grayscale images with more
+    /// contrast
+    HighContrast,
+}
+
+/// Rewrite the colors of `document` for `mode`
+pub(crate) fn apply(document: &mut PagedDocument, mode: ColorMode) -> Result<(), TypstWrapperError> {
+    if mode == ColorMode::Color {
+        return Ok(());
+    }
+    for page in &mut document.pages {
+        convert_frame(&mut page.frame, mode)?;
+        if let Smart::Custom(Some(fill)) = &mut page.fill {
+            convert_paint(fill, mode);
+        }
+    }
+    Ok(())
+}
+
+fn convert_frame(frame: &mut Frame, mode: ColorMode) -> Result<(), TypstWrapperError> {
+    let mut items: Vec<_> = frame.items().cloned().collect();
+    for (_, item) in &mut items {
+        match item {
+            FrameItem::Group(group) => convert_frame(&mut group.frame, mode)?,
+            FrameItem::Text(text) => {
+                convert_paint(&mut text.fill, mode);
+                if let Some(stroke) = &mut text.stroke {
+                    convert_paint(&mut stroke.paint, mode);
+                }
+            }
+            FrameItem::Shape(shape, _) => {
+                if let Some(fill) = &mut shape.fill {
+                    convert_paint(fill, mode);
+                }
+                if let Some(stroke) = &mut shape.stroke {
+                    convert_paint(&mut stroke.paint, mode);
+                }
+            }
+            FrameItem::Image(image, ..) => convert_image(image, mode)?,
+            FrameItem::Link(..) | FrameItem::Tag(_) => {}
+        }
+    }
+    frame.clear();
+    frame.push_multiple(items);
+    Ok(())
+}
+
+fn convert_paint(paint: &mut Paint, mode: ColorMode) {
+    match paint {
+        Paint::Solid(color) => *color = convert_color(*color, mode),
+        Paint::Gradient(gradient) => {
+            let stops = |stops: &mut Vec<(Color, _)>| {
+                for (color, _) in stops {
+                    *color = convert_color(*color, mode);
+                }
+            };
+            match gradient {
+                Gradient::Linear(linear) => stops(&mut std::sync::Arc::make_mut(linear).stops),
+                Gradient::Radial(radial) => stops(&mut std::sync::Arc::make_mut(radial).stops),
+                Gradient::Conic(conic) => stops(&mut std::sync::Arc::make_mut(conic).stops),
+            }
+        }
+        Paint::Tiling(_) => {}
+    }
+}
+
+fn convert_color(color: Color, mode: ColorMode) -> Color {
+    let alpha = color.alpha().unwrap_or(1.0);
+    let gray = color.to_luma();
+    let color = match mode {
+        ColorMode::HighContrast if gray.to_vec4()[0] < 0.5 => Color::BLACK,
+        ColorMode::HighContrast => Color::WHITE,
+        _ => gray,
+    };
+    color.with_alpha(alpha)
+}
+
+/// Gray levels of high-contrast images are spread this much further from
+/// the middle gray
+const IMAGE_CONTRAST: f32 = 1.5;
+
+fn convert_image(image: &mut Image, mode: ColorMode) -> Result<(), TypstWrapperError> {
+    let ImageKind::Raster(raster) = image.kind() else {
+        return Ok(());
+    };
+    let alpha = raster.dynamic().color().has_alpha();
+    let mut pixels = if alpha {
+        raster.dynamic().to_luma_alpha8().into_raw()
+    } else {
+        raster.dynamic().to_luma8().into_raw()
+    };
+    if mode == ColorMode::HighContrast {
+        let step = if alpha { 2 } else { 1 };
+        for gray in pixels.iter_mut().step_by(step) {
+            *gray = ((f32::from(*gray) - 127.5) * IMAGE_CONTRAST + 127.5).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    let mut format = Dict::new();
+    format.insert("encoding".into(), Value::Str(if alpha { "lumaa8" } else { "luma8" }.into()));
+    format.insert("width".into(), Value::Int(raster.width().into()));
+    format.insert("height".into(), Value::Int(raster.height().into()));
+    let format = PixelFormat::from_value(Value::Dict(format))
+        .map_err(|e| TypstWrapperError::OutputFormat(format!("Image could not be converted: {}", e.message())))?;
+    let gray = RasterImage::new(Bytes::new(pixels), format, Smart::Auto)
+        .map_err(|e| TypstWrapperError::OutputFormat(format!("Image could not be converted: {}", e)))?;
+    *image = Image::new(gray, image.alt().map(Into::into), image.scaling());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWrapper};
+
+    const MARKUP: &str = "#set page(width: 2in, height: 3in, margin: 0.1in)\n#text(fill: rgb(\"#d00000\"))[Red]\n#rect(fill: rgb(\"#c0c0ff\"))\n#image(\"/assets/dod_seal.gif\", width: 1in)";
+
+    #[cfg(feature = "font-times")]
+    fn colors(frame: &Frame, found: &mut Vec<Color>) {
+        for (_, item) in frame.items() {
+            match item {
+                FrameItem::Group(group) => colors(&group.frame, found),
+                FrameItem::Text(text) => found.extend(solid(&text.fill)),
+                FrameItem::Shape(shape, _) => found.extend(shape.fill.as_ref().and_then(solid)),
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(feature = "font-times")]
+    fn raster_images(frame: &Frame, found: &mut Vec<RasterImage>) {
+        for (_, item) in frame.items() {
+            match item {
+                FrameItem::Group(group) => raster_images(&group.frame, found),
+                FrameItem::Image(image, ..) => {
+                    if let ImageKind::Raster(raster) = image.kind() {
+                        found.push(raster.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(feature = "font-times")]
+    fn solid(paint: &Paint) -> Option<Color> {
+        match paint {
+            Paint::Solid(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "font-times")]
+    fn converted(mode: ColorMode) -> PagedDocument {
+        let mut document = TypstWrapper::compile_markup(MARKUP).unwrap();
+        apply(&mut document, mode).unwrap();
+        document
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_grayscale_converts_colors_and_images() {
+        let document = converted(ColorMode::Grayscale);
+        let mut found = Vec::new();
+        colors(&document.pages[0].frame, &mut found);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|color| matches!(color, Color::Luma(_))), "{:?}", found);
+        let mut images = Vec::new();
+        raster_images(&document.pages[0].frame, &mut images);
+        assert_eq!(images.len(), 1);
+        assert!(!images[0].dynamic().color().has_color());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_high_contrast_uses_black_and_white() {
+        let document = converted(ColorMode::HighContrast);
+        let mut found = Vec::new();
+        colors(&document.pages[0].frame, &mut found);
+        assert_eq!(found, [Color::BLACK, Color::WHITE]);
+    }
+
+    #[test]
+    fn test_color_mode_applies_to_svg_and_pdf() {
+        for format in [OutputFormat::Svg, OutputFormat::Pdf] {
+            let render = |color_mode| {
+                let config = RenderConfig { format, color_mode, ..RenderConfig::default() };
+                TypstWrapper::render_markup(MARKUP, Some(config)).unwrap()
+            };
+            let color = render(ColorMode::Color);
+            assert_ne!(render(ColorMode::Grayscale), color);
+            assert_ne!(render(ColorMode::HighContrast), render(ColorMode::Grayscale));
+        }
+    }
+}
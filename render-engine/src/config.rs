@@ -0,0 +1,395 @@
+//! Building render configurations.
+//!
+//! [`RenderConfig`] is a plain struct, and nothing stops a caller from
+//! asking for a PDF/X print profile with SVG output or a text layout with
+//! PDF output until the render fails or quietly ignores the option.
+//! [`RenderConfigBuilder`] starts from the defaults, takes the options one
+//! at a time and checks in [`build`](RenderConfigBuilder::build) that they
+//! can be used together, reporting every conflict at once.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::annotations::Annotation;
+use crate::color_mode::ColorMode;
+use crate::metadata::DocumentMetadata;
+use crate::print::PrintProfile;
+use crate::running::RunningText;
+use crate::style::StyleConfig;
+use crate::svg_ids;
+use crate::svg_scale;
+use crate::svg_text::SvgTextMode;
+use crate::today;
+use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWrapperError};
+use crate::watermark::Watermark;
+
+impl RenderConfig {
+    /// A builder starting from the default configuration
+    pub fn builder() -> RenderConfigBuilder {
+        RenderConfigBuilder::default()
+    }
+}
+
+/// Builder of a validated [`RenderConfig`]
+///
+/// # Examples
+/// ```
+/// use render_engine::{OutputFormat, RenderConfig, Watermark};
+///
+/// let config = RenderConfig::builder()
+///     .format(OutputFormat::Pdf)
+///     .watermark(Watermark::new("DRAFT"))
+///     .form_fields(true)
+///     .build()
+///     .unwrap();
+/// assert!(config.form_fields);
+///
+/// let conflict = RenderConfig::builder().format(OutputFormat::Svg).form_fields(true).build();
+/// assert!(conflict.unwrap_err().to_string().contains("form fields need PDF output"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RenderConfigBuilder {
+    config: RenderConfig,
+}
+
+impl RenderConfigBuilder {
+    /// Output format, SVG by default
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.config.format = format;
+        self
+    }
+
+    /// Text stamped diagonally across every page
+    pub fn watermark(mut self, watermark: Watermark) -> Self {
+        self.config.watermark = Some(watermark);
+        self
+    }
+
+    /// Running header
+    pub fn header(mut self, header: RunningText) -> Self {
+        self.config.header = Some(header);
+        self
+    }
+
+    /// Running footer
+    pub fn footer(mut self, footer: RunningText) -> Self {
+        self.config.footer = Some(footer);
+        self
+    }
+
+    /// Start every `<section-start>` on an odd page
+    pub fn duplex(mut self, duplex: bool) -> Self {
+        self.config.duplex = duplex;
+        self
+    }
+
+    /// Export PDF/X for a printing condition; needs PDF output
+    pub fn print(mut self, profile: PrintProfile) -> Self {
+        self.config.print = Some(profile);
+        self
+    }
+
+    /// Make links clickable in SVG pages, on by default
+    pub fn svg_links(mut self, svg_links: bool) -> Self {
+        self.config.svg_links = svg_links;
+        self
+    }
+
+    /// Render SVG text as outlines or selectable; selectable text needs SVG
+    /// or SVG ZIP output
+    pub fn svg_text(mut self, svg_text: SvgTextMode) -> Self {
+        self.config.svg_text = svg_text;
+        self
+    }
+
+    /// Display size of SVG pages relative to their size in points; needs
+    /// SVG or SVG ZIP output
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.config.scale = scale;
+        self
+    }
+
+    /// Export only the pages in `range`, counted from 0; needs SVG or SVG
+    /// ZIP output
+    pub fn page_range(mut self, range: Range<usize>) -> Self {
+        self.config.page_range = Some(range);
+        self
+    }
+
+    /// Stack the pages into one SVG; needs SVG output
+    pub fn stack_pages(mut self, stack_pages: bool) -> Self {
+        self.config.stack_pages = stack_pages;
+        self
+    }
+
+    /// Put `prefix` in front of every id in SVG output
+    pub fn id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config.id_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Date `datetime.today()` returns in templates, as `YYYY-MM-DD`
+    pub fn today(mut self, today: impl Into<String>) -> Self {
+        self.config.today = Some(today.into());
+        self
+    }
+
+    /// Add text layouts to SVG ZIPs; needs SVG ZIP output
+    pub fn text_layout(mut self, text_layout: bool) -> Self {
+        self.config.text_layout = text_layout;
+        self
+    }
+
+    /// Add a review comment
+    pub fn annotation(mut self, annotation: Annotation) -> Self {
+        self.config.annotations.push(annotation);
+        self
+    }
+
+    /// Replace the review comments
+    pub fn annotations(mut self, annotations: Vec<Annotation>) -> Self {
+        self.config.annotations = annotations;
+        self
+    }
+
+    /// Add fillable signature and date fields; needs PDF output
+    pub fn form_fields(mut self, form_fields: bool) -> Self {
+        self.config.form_fields = form_fields;
+        self
+    }
+
+    /// Attach the form JSON to the PDF output of form renders; needs PDF
+    /// output
+    pub fn embed_form(mut self, embed_form: bool) -> Self {
+        self.config.embed_form = embed_form;
+        self
+    }
+
+    /// Title, author, subject, keywords and creation date of PDF output
+    pub fn metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.config.metadata = Some(metadata);
+        self
+    }
+
+    /// Bookmark headings, sections and numbered paragraphs in PDF output, on
+    /// by default
+    pub fn generate_outline(mut self, generate_outline: bool) -> Self {
+        self.config.generate_outline = generate_outline;
+        self
+    }
+
+    /// Render the font family `from` with the fonts of the family `to`
+    pub fn substitute_font(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.config.font_substitutions.insert(from.into(), to.into());
+        self
+    }
+
+    /// Replace the font substitutions
+    pub fn font_substitutions(mut self, substitutions: BTreeMap<String, String>) -> Self {
+        self.config.font_substitutions = substitutions;
+        self
+    }
+
+    /// Use the output caches, on by default
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.config.cache = cache;
+        self
+    }
+
+    /// Fail on package versions that are not embedded
+    pub fn strict_packages(mut self, strict_packages: bool) -> Self {
+        self.config.strict_packages = strict_packages;
+        self
+    }
+
+    /// Style tokens for form renders
+    pub fn style(mut self, style: StyleConfig) -> Self {
+        self.config.style = Some(style);
+        self
+    }
+
+    /// Render in color, grayscale or high contrast
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.config.color_mode = color_mode;
+        self
+    }
+
+    /// The configuration, or a validation error listing every option that
+    /// is invalid or conflicts with another
+    pub fn build(self) -> Result<RenderConfig, TypstWrapperError> {
+        let config = self.config;
+        let mut problems = Vec::new();
+        let pdf = matches!(config.format, OutputFormat::Pdf);
+
+        if let Some(Err(TypstWrapperError::Validation(problem))) = config.watermark.as_ref().map(Watermark::validate) {
+            problems.push(problem);
+        }
+        if let Some(profile) = &config.print {
+            if !pdf {
+                problems.push("print profiles need PDF output".to_string());
+            }
+            if let Some(Err(problem)) = profile.icc_profile.as_deref().map(PrintProfile::icc_components) {
+                problems.push(problem);
+            }
+            if !config.annotations.is_empty() {
+                problems.push("PDF/X does not allow review annotations on the page".to_string());
+            }
+            if config.form_fields {
+                problems.push("PDF/X does not allow form fields on the page".to_string());
+            }
+            if config.embed_form {
+                problems.push("PDF/X does not allow embedded files".to_string());
+            }
+        }
+        if config.form_fields && !pdf {
+            problems.push("form fields need PDF output".to_string());
+        }
+        if config.embed_form && !pdf {
+            problems.push("embedding the form needs PDF output".to_string());
+        }
+        if let Err(TypstWrapperError::Validation(problem)) = svg_scale::validate(config.scale) {
+            problems.push(problem);
+        } else if pdf && config.scale != 1.0 {
+            problems.push("scaling pages needs SVG output".to_string());
+        }
+        if let Some(metadata) = &config.metadata {
+            if !pdf {
+                problems.push("document metadata needs PDF output".to_string());
+            }
+            if let Err(TypstWrapperError::Validation(problem)) = metadata.validate() {
+                problems.push(problem);
+            }
+        }
+        if let Some(range) = &config.page_range {
+            if range.is_empty() {
+                problems.push(format!("page range {}..{} is empty", range.start, range.end));
+            }
+        }
+        if pdf && config.svg_text == SvgTextMode::Selectable {
+            problems.push("selectable text needs SVG or SVG ZIP output".to_string());
+        }
+        if let Some(Err(TypstWrapperError::Validation(problem))) = config.id_prefix.as_deref().map(svg_ids::validate) {
+            problems.push(problem);
+        }
+        if let Some(Err(TypstWrapperError::Validation(problem))) = config.today.as_deref().map(today::date) {
+            problems.push(problem);
+        }
+        if config.stack_pages && !matches!(config.format, OutputFormat::Svg) {
+            problems.push("stacking pages needs SVG output".to_string());
+        }
+        if config.text_layout && !matches!(config.format, OutputFormat::SvgZip) {
+            problems.push("text layouts need SVG ZIP output".to_string());
+        }
+        for (from, to) in &config.font_substitutions {
+            if from.trim().is_empty() || to.trim().is_empty() {
+                problems.push("font substitutions need a family on both sides".to_string());
+            } else if from.eq_ignore_ascii_case(to) {
+                problems.push(format!("font family '{}' is substituted with itself", from));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(TypstWrapperError::Validation(format!("Invalid render config: {}", problems.join("; "))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(builder: RenderConfigBuilder) -> String {
+        match builder.build() {
+            Err(TypstWrapperError::Validation(message)) => message,
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_builder_defaults_match_default_config() {
+        let config = RenderConfig::builder().build().unwrap();
+        let default = RenderConfig::default();
+        assert!(matches!(config.format, OutputFormat::Svg));
+        assert_eq!((config.svg_links, config.cache), (default.svg_links, default.cache));
+        assert_eq!(config.color_mode, ColorMode::Color);
+    }
+
+    #[test]
+    fn test_builder_reports_every_conflict() {
+        let message = problems(
+            RenderConfig::builder()
+                .print(PrintProfile::default())
+                .form_fields(true)
+                .embed_form(true)
+                .metadata(DocumentMetadata { date: Some("yesterday".to_string()), ..DocumentMetadata::default() })
+                .text_layout(true)
+                .watermark(Watermark { opacity: 2.0, ..Watermark::new("DRAFT") }),
+        );
+        for problem in [
+            "Invalid watermark opacity 2",
+            "print profiles need PDF output",
+            "PDF/X does not allow form fields",
+            "form fields need PDF output",
+            "PDF/X does not allow embedded files",
+            "embedding the form needs PDF output",
+            "document metadata needs PDF output",
+            "Invalid metadata date 'yesterday'",
+            "text layouts need SVG ZIP output",
+        ] {
+            assert!(message.contains(problem), "{} not in {}", problem, message);
+        }
+    }
+
+    #[test]
+    fn test_builder_checks_svg_options() {
+        let message = problems(RenderConfig::builder().scale(0.0).today("tomorrow"));
+        assert!(message.contains("Invalid today date 'tomorrow'"), "{}", message);
+        assert!(message.contains("Invalid scale 0"), "{}", message);
+        let builder = RenderConfig::builder().format(OutputFormat::Pdf).scale(2.0).page_range(1..1).stack_pages(true).id_prefix("1");
+        let message = problems(builder);
+        assert!(message.contains("stacking pages needs SVG output"), "{}", message);
+        assert!(message.contains("Invalid id prefix '1'"), "{}", message);
+        assert!(message.contains("scaling pages needs SVG output"), "{}", message);
+        assert!(message.contains("page range 1..1 is empty"), "{}", message);
+        assert_eq!(RenderConfig::builder().scale(0.25).build().unwrap().scale, 0.25);
+    }
+
+    #[test]
+    fn test_config_serializes_to_json_and_toml() {
+        let config = RenderConfig::builder()
+            .format(OutputFormat::SvgZip)
+            .text_layout(true)
+            .watermark(Watermark::new("DRAFT"))
+            .color_mode(ColorMode::HighContrast)
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!((json["format"].as_str(), json["color_mode"].as_str()), (Some("svg-zip"), Some("high-contrast")));
+        let back: RenderConfig = serde_json::from_value(json).unwrap();
+        assert_eq!((back.format, back.watermark), (config.format, config.watermark));
+
+        let toml: RenderConfig = toml::from_str("format = \"pdf\"\nduplex = true\ntoday = \"2025-03-07\"\n\n[watermark]\ntext = \"COPY\"\n").unwrap();
+        assert_eq!(toml.today.as_deref(), Some("2025-03-07"));
+        assert_eq!(toml.format, OutputFormat::Pdf);
+        assert!(toml.duplex && toml.svg_links && toml.cache);
+        assert_eq!(toml.watermark.unwrap().opacity, Watermark::new("COPY").opacity);
+
+        let png = serde_json::from_str::<RenderConfig>(r#"{"format": "png"}"#).unwrap_err().to_string();
+        assert!(png.contains("unknown variant `png`, expected one of `svg`, `pdf`, `svg-zip`"), "{}", png);
+    }
+
+    #[test]
+    fn test_builder_checks_font_substitutions() {
+        let message = problems(RenderConfig::builder().substitute_font("Times", "times"));
+        assert!(message.contains("'Times' is substituted with itself"), "{}", message);
+        let config = RenderConfig::builder()
+            .format(OutputFormat::SvgZip)
+            .text_layout(true)
+            .substitute_font("Times New Roman", "Times")
+            .build()
+            .unwrap();
+        assert_eq!(config.font_substitutions["Times New Roman"], "Times");
+    }
+}
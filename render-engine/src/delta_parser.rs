@@ -1,35 +1,37 @@
-/// A parser for the render engine.
-/// Converts Quill Delta syntax into Typst markup.
-/// 
-/// This module provides functionality to parse Quill Delta JSON format and convert it
-/// into Typst markup language according to the official Quill Delta specification
-/// (https://quilljs.com/docs/delta/). It supports:
-/// 
-/// - Text formatting (bold, italic, underline, strikethrough, code)
-/// - Paragraphs with proper line breaks
-/// - Bullet lists (nested)
-/// - Ordered lists (nested)
-/// - Headers (levels 1-6)
-/// - Blockquotes
-/// - Code blocks
-/// - Image embeds
-/// 
-/// # Example
-/// 
-/// ```
-/// use render_engine::DeltaParser;
-/// 
-/// let parser = DeltaParser::new();
-/// let delta_json = r#"{"ops":[{"insert":"Hello "},{"insert":"world","attributes":{"bold":true}}]}"#;
-/// let typst_markup = parser.parse(delta_json).unwrap();
-/// assert_eq!(typst_markup, "Hello *world*");
-/// ```
-
-
+//! A parser for the render engine.
+//! Converts Quill Delta syntax into Typst markup.
+//! 
+//! This module provides functionality to parse Quill Delta JSON format and convert it
+//! into Typst markup language according to the official Quill Delta specification
+//! (https://quilljs.com/docs/delta/). It supports:
+//! 
+//! - Text formatting (bold, italic, underline, strikethrough, code)
+//! - Paragraphs with proper line breaks
+//! - Bullet lists (nested)
+//! - Ordered lists (nested)
+//! - Headers (levels 1-6)
+//! - Blockquotes
+//! - Code blocks
+//! - Image embeds, including images inlined as `data:` URLs
+//! 
+//! # Example
+//! 
+//! ```
+//! use render_engine::DeltaParser;
+//! 
+//! let parser = DeltaParser::new();
+//! let delta_json = r#"{"ops":[{"insert":"Hello "},{"insert":"world","attributes":{"bold":true}}]}"#;
+//! let typst_markup = parser.parse(delta_json).unwrap();
+//! assert_eq!(typst_markup, "Hello *world*");
+//! ```
+
+use base64::Engine as _;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use thiserror::Error;
 
+use crate::watermark::typst_string;
+
 #[derive(Error, Debug)]
 pub enum ParserError {
     #[error("Invalid Quill Delta format: {0}")]
@@ -38,12 +40,43 @@ pub enum ParserError {
     UnsupportedOperation(String),
     #[error("JSON parsing error: {0}")]
     JsonError(#[from] serde_json::Error),
+    /// A form that does not match the memo schema, with the JSON pointer of
+    /// the offending value
+    #[error("Form JSON does not match schema: {message} at {path}")]
+    Schema { path: String, message: String },
+}
+
+/// Largest Delta document [`DeltaParser::parse`] accepts, in bytes
+///
+/// Deltas come straight from the browser, so the limit keeps a hostile
+/// document from exhausting memory. JSON nesting is separately bounded by
+/// `serde_json`'s recursion limit.
+pub const MAX_DELTA_BYTES: usize = 8 * 1024 * 1024;
+
+/// Deepest heading level Quill emits
+const MAX_HEADER_LEVEL: u64 = 6;
+
+/// Deepest list indentation Quill emits
+const MAX_INDENT_LEVEL: u64 = 8;
+
+/// Directory the images of `data:` URLs in a Delta are served from
+pub const DELTA_IMAGE_DIR: &str = "delta-images";
+
+/// A Delta converted to Typst markup, with the images it inlines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeltaDocument {
+    /// Typst markup of the Delta
+    pub markup: String,
+    /// Bytes of the images inlined as `data:` URLs, by the path the markup
+    /// reads them from, e.g. `/delta-images/<hash>.png`
+    pub images: BTreeMap<String, Vec<u8>>,
 }
 
 /// Parser for converting Quill Delta to Typst markup
 pub struct DeltaParser {
-    /// Stack to track nested list types and levels
-    list_stack: Vec<ListType>,
+    /// Mark the markup of each op with its index, see
+    /// [`with_source_markers`](Self::with_source_markers)
+    source_markers: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -54,29 +87,87 @@ enum ListType {
 
 impl DeltaParser {
     pub fn new() -> Self {
-        Self {
-            list_stack: Vec::new(),
+        Self { source_markers: false }
+    }
+
+    /// Surround the markup of each inserted text or embed with `metadata`
+    /// labelled `<source-start>`, holding the index of its op, and
+    /// `<source-end>`, so [source maps](crate::source_map) can tell which op
+    /// put the text on the page. Code blocks are left unmarked.
+    pub fn with_source_markers(mut self) -> Self {
+        self.source_markers = true;
+        self
+    }
+
+    /// `markup` surrounded by the source markers of op `index`, if enabled
+    fn mark(&self, index: usize, markup: String) -> String {
+        match self.source_markers {
+            true => format!("#metadata({}) <source-start>{}#metadata(none) <source-end>", index, markup),
+            false => markup,
         }
     }
 
     /// Parse a Quill Delta JSON string and convert to Typst markup
+    ///
+    /// Images inlined as `data:` URLs are read from the paths in
+    /// [`DeltaDocument::images`], which only the renders of forms serve; use
+    /// [`parse_document`](Self::parse_document) to get their bytes.
     pub fn parse(&self, delta_json: &str) -> Result<String, ParserError> {
+        self.parse_document(delta_json).map(|document| document.markup)
+    }
+
+    /// Parse a Quill Delta JSON string into Typst markup and the images it
+    /// inlines as `data:` URLs
+    ///
+    /// Quill inserts pasted and uploaded images as base64 `data:` URLs, which
+    /// Typst cannot read. Each becomes an `#image` of a path under
+    /// `/delta-images`, named by the hash of its bytes, and the bytes are
+    /// returned for the caller to serve at that path.
+    ///
+    /// # Examples
+    /// ```
+    /// use render_engine::DeltaParser;
+    ///
+    /// let delta = r#"{"ops":[{"insert":{"image":"data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg'/%3E"}}]}"#;
+    /// let document = DeltaParser::new().parse_document(delta).unwrap();
+    /// let (path, svg) = document.images.iter().next().unwrap();
+    /// assert_eq!(document.markup, format!("#image(\"{}\")", path));
+    /// assert_eq!(svg, b"<svg xmlns='http://www.w3.org/2000/svg'/>");
+    /// ```
+    pub fn parse_document(&self, delta_json: &str) -> Result<DeltaDocument, ParserError> {
+        if delta_json.len() > MAX_DELTA_BYTES {
+            return Err(ParserError::InvalidFormat(format!(
+                "Delta is {} bytes, exceeding the {} byte limit",
+                delta_json.len(),
+                MAX_DELTA_BYTES
+            )));
+        }
+
         // Parse JSON directly since quill-delta-rs expects a different format
         let json_value: Value = serde_json::from_str(delta_json)?;
-        self.convert_json_to_typst(&json_value)
+        let mut images = BTreeMap::new();
+        let markup = self.convert_json_to_typst(&json_value, &mut images)?;
+        Ok(DeltaDocument { markup, images })
     }
 
-    /// Convert JSON Delta format to Typst markup
-    fn convert_json_to_typst(&self, json_value: &Value) -> Result<String, ParserError> {
+    /// Convert JSON Delta format to Typst markup, collecting the images of
+    /// `data:` URLs into `images`
+    fn convert_json_to_typst(
+        &self,
+        json_value: &Value,
+        images: &mut BTreeMap<String, Vec<u8>>,
+    ) -> Result<String, ParserError> {
         let mut result = String::new();
         let mut current_line = String::new();
+        // The current line without source markers, for code blocks
+        let mut plain_line = String::new();
         let mut in_list = false;
         
         // Get operations from JSON
         let ops = json_value["ops"].as_array()
             .ok_or_else(|| ParserError::InvalidFormat("Missing ops array".to_string()))?;
         
-        for op in ops {
+        for (index, op) in ops.iter().enumerate() {
             if let Some(insert) = op.get("insert") {
                 let attributes = op.get("attributes")
                     .and_then(|v| v.as_object())
@@ -87,10 +178,13 @@ impl DeltaParser {
                         // Check if this is a newline with line formatting
                         if text == "\n" {
                             if let Some(attrs) = &attributes {
+                                if attrs.get("code-block").and_then(|v| v.as_bool()).unwrap_or(false) {
+                                    current_line.clone_from(&plain_line);
+                                }
                                 // Handle line-level formatting (lists, headers, etc.)
                                 let formatted_line = self.handle_line_formatting(&current_line, attrs)?;
                                 
-                                if let Some(list_info) = self.extract_list_info(attrs) {
+                                if let Some(list_info) = self.extract_list_info(attrs)? {
                                     // This is a list item
                                     let list_item = self.format_list_item(&current_line, &list_info, attrs)?;
                                     result.push_str(&list_item);
@@ -98,7 +192,7 @@ impl DeltaParser {
                                 } else {
                                     // End any current list
                                     if in_list {
-                                        result.push_str("\n");
+                                        result.push('\n');
                                         in_list = false;
                                     }
                                     
@@ -110,33 +204,36 @@ impl DeltaParser {
                                     }
                                     
                                     if !current_line.is_empty() || !formatted_line.is_empty() {
-                                        result.push_str("\n");
+                                        result.push('\n');
                                     }
                                 }
                             } else {
                                 // Regular newline without formatting
                                 if in_list {
-                                    result.push_str("\n");
+                                    result.push('\n');
                                     in_list = false;
                                 }
                                 if !current_line.is_empty() {
                                     result.push_str(&current_line);
-                                    result.push_str("\n");
+                                    result.push('\n');
                                 } else {
-                                    result.push_str("\n");
+                                    result.push('\n');
                                 }
                             }
                             current_line.clear();
+                            plain_line.clear();
                         } else {
                             // Regular text content
                             let formatted_text = self.apply_text_formatting(text, &attributes)?;
-                            current_line.push_str(&formatted_text);
+                            plain_line.push_str(&formatted_text);
+                            current_line.push_str(&self.mark(index, formatted_text));
                         }
                     }
                     Value::Object(embed) => {
                         // Handle embedded objects (images, etc.)
-                        let embed_typst = self.handle_embed(embed)?;
-                        current_line.push_str(&embed_typst);
+                        let embed_typst = self.handle_embed(embed, &attributes, images)?;
+                        plain_line.push_str(&embed_typst);
+                        current_line.push_str(&self.mark(index, embed_typst));
                     }
                     _ => {
                         return Err(ParserError::InvalidFormat(
@@ -213,6 +310,12 @@ impl DeltaParser {
 
         // Handle headers
         if let Some(header_level) = attributes.get("header").and_then(|v| v.as_u64()) {
+            if !(1..=MAX_HEADER_LEVEL).contains(&header_level) {
+                return Err(ParserError::InvalidFormat(format!(
+                    "Header level {} is out of range (1-{})",
+                    header_level, MAX_HEADER_LEVEL
+                )));
+            }
             let header_prefix = "=".repeat(header_level as usize);
             result = format!("{} {}", header_prefix, result);
         }
@@ -231,25 +334,31 @@ impl DeltaParser {
     }
 
     /// Extract list information from attributes
-    fn extract_list_info(&self, attributes: &HashMap<String, Value>) -> Option<ListInfo> {
+    fn extract_list_info(&self, attributes: &HashMap<String, Value>) -> Result<Option<ListInfo>, ParserError> {
         if let Some(list_type) = attributes.get("list") {
             let indent_level = attributes
                 .get("indent")
                 .and_then(|v| v.as_u64())
-                .unwrap_or(0) as usize;
+                .unwrap_or(0);
+            if indent_level > MAX_INDENT_LEVEL {
+                return Err(ParserError::InvalidFormat(format!(
+                    "List indent {} is out of range (0-{})",
+                    indent_level, MAX_INDENT_LEVEL
+                )));
+            }
 
-            let list_type = match list_type.as_str()? {
-                "bullet" => ListType::Bullet,
-                "ordered" => ListType::Ordered,
-                _ => return None,
+            let list_type = match list_type.as_str() {
+                Some("bullet") => ListType::Bullet,
+                Some("ordered") => ListType::Ordered,
+                _ => return Ok(None),
             };
 
-            Some(ListInfo {
+            Ok(Some(ListInfo {
                 list_type,
-                indent_level,
-            })
+                indent_level: indent_level as usize,
+            }))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -270,10 +379,28 @@ impl DeltaParser {
     }
 
     /// Handle embedded objects
-    fn handle_embed(&self, embed: &serde_json::Map<String, Value>) -> Result<String, ParserError> {
+    fn handle_embed(
+        &self,
+        embed: &serde_json::Map<String, Value>,
+        attributes: &Option<HashMap<String, Value>>,
+        images: &mut BTreeMap<String, Vec<u8>>,
+    ) -> Result<String, ParserError> {
         // Handle different types of embeds
         if let Some(image_url) = embed.get("image").and_then(|v| v.as_str()) {
-            Ok(format!("#image(\"{}\")", image_url))
+            let image_url = match image_url.strip_prefix("data:") {
+                Some(data_url) => {
+                    let (extension, data) = decode_data_url(data_url)?;
+                    let path = format!("/{}/{:032x}.{}", DELTA_IMAGE_DIR, typst::utils::hash128(&data), extension);
+                    images.insert(path.clone(), data);
+                    path
+                }
+                None => image_url.to_string(),
+            };
+            // Alt text is an attribute of the embed op
+            match attributes.as_ref().and_then(|attrs| attrs.get("alt")).and_then(|v| v.as_str()) {
+                Some(alt) => Ok(format!("#image(\"{}\", alt: {})", image_url, typst_string(alt))),
+                None => Ok(format!("#image(\"{}\")", image_url)),
+            }
         } else {
             Err(ParserError::UnsupportedOperation(
                 format!("Unsupported embed type: {:?}", embed)
@@ -282,6 +409,51 @@ impl DeltaParser {
     }
 }
 
+/// The file extension and bytes of the image in `data_url`, the part of a
+/// `data:` URL after the scheme
+fn decode_data_url(data_url: &str) -> Result<(&'static str, Vec<u8>), ParserError> {
+    let invalid = |reason: &str| ParserError::InvalidFormat(format!("Invalid image data URL: {}", reason));
+    let (header, payload) = data_url.split_once(',').ok_or_else(|| invalid("missing ','"))?;
+    let mut parameters = header.split(';');
+    let mime = parameters.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let base64 = parameters.any(|parameter| parameter.trim().eq_ignore_ascii_case("base64"));
+    let extension = match mime.as_str() {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => return Err(invalid(&format!("unsupported image type '{}'", mime))),
+    };
+    let data = if base64 {
+        // Data URLs pasted from elsewhere may be wrapped over several lines
+        let payload: String = payload.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| invalid(&e.to_string()))?
+    } else {
+        percent_decode(payload).ok_or_else(|| invalid("bad percent-encoding"))?
+    };
+    Ok((extension, data))
+}
+
+/// The bytes of percent-encoded `text`, `None` if an escape is malformed
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(bytes)
+}
+
 #[derive(Debug, Clone)]
 struct ListInfo {
     list_type: ListType,
@@ -300,7 +472,7 @@ mod tests {
 
     #[test]
     fn test_basic_text_parsing() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Hello, World!"}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -309,7 +481,7 @@ mod tests {
 
     #[test]
     fn test_bold_text() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Bold text","attributes":{"bold":true}}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -318,7 +490,7 @@ mod tests {
 
     #[test]
     fn test_italic_text() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Italic text","attributes":{"italic":true}}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -327,7 +499,7 @@ mod tests {
 
     #[test]
     fn test_underline_text() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Underlined text","attributes":{"underline":true}}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -336,7 +508,7 @@ mod tests {
 
     #[test]
     fn test_strikethrough_text() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Strikethrough text","attributes":{"strike":true}}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -345,7 +517,7 @@ mod tests {
 
     #[test]
     fn test_combined_formatting() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Bold and italic","attributes":{"bold":true,"italic":true}}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -354,7 +526,7 @@ mod tests {
 
     #[test]
     fn test_bullet_list() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Item 1"},{"attributes":{"list":"bullet"},"insert":"\n"},{"insert":"Item 2"},{"attributes":{"list":"bullet"},"insert":"\n"}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -364,7 +536,7 @@ mod tests {
 
     #[test]
     fn test_ordered_list() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"First item"},{"attributes":{"list":"ordered"},"insert":"\n"},{"insert":"Second item"},{"attributes":{"list":"ordered"},"insert":"\n"}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
@@ -374,11 +546,71 @@ mod tests {
 
     #[test]
     fn test_nested_list() {
-        let mut parser = DeltaParser::new();
+        let parser = DeltaParser::new();
         let delta_json = r#"{"ops":[{"insert":"Top level"},{"attributes":{"list":"bullet"},"insert":"\n"},{"insert":"Nested item"},{"attributes":{"list":"bullet","indent":1},"insert":"\n"}]}"#;
         
         let result = parser.parse(delta_json).unwrap();
         assert!(result.contains("- Top level"));
         assert!(result.contains("  - Nested item"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rejects_out_of_range_line_attributes() {
+        let parser = DeltaParser::new();
+        let header = r#"{"ops":[{"insert":"Title"},{"attributes":{"header":18446744073709551615},"insert":"\n"}]}"#;
+        assert!(matches!(parser.parse(header), Err(ParserError::InvalidFormat(_))));
+        
+        let indent = r#"{"ops":[{"insert":"Item"},{"attributes":{"list":"bullet","indent":4294967296},"insert":"\n"}]}"#;
+        assert!(matches!(parser.parse(indent), Err(ParserError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_rejects_oversized_and_deeply_nested_input() {
+        let parser = DeltaParser::new();
+        let oversized = format!(r#"{{"ops":[{{"insert":"{}"}}]}}"#, "a".repeat(MAX_DELTA_BYTES));
+        assert!(matches!(parser.parse(&oversized), Err(ParserError::InvalidFormat(_))));
+        
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(matches!(parser.parse(&nested), Err(ParserError::JsonError(_))));
+    }
+
+    #[test]
+    fn test_image_alt_text() {
+        let parser = DeltaParser::new();
+        let delta_json = r#"{"ops":[{"insert":{"image":"seal.png"},"attributes":{"alt":"Unit \"seal\""}},{"insert":{"image":"logo.png"}},{"insert":"\n"}]}"#;
+
+        let result = parser.parse(delta_json).unwrap();
+        assert!(result.contains(r#"#image("seal.png", alt: "Unit \"seal\"")"#), "{}", result);
+        assert!(result.contains(r#"#image("logo.png")"#), "{}", result);
+    }
+
+    #[test]
+    fn test_data_url_images() {
+        let parser = DeltaParser::new();
+        let delta_json = r#"{"ops":[{"insert":{"image":"data:image/png;base64,iVBO\nRw0KGgo="},"attributes":{"alt":"Pasted"}},{"insert":{"image":"data:image/png;base64,iVBORw0KGgo="}},{"insert":"\n"}]}"#;
+
+        let document = parser.parse_document(delta_json).unwrap();
+        assert_eq!(document.images.len(), 1);
+        let (path, data) = document.images.iter().next().unwrap();
+        assert!(path.starts_with("/delta-images/") && path.ends_with(".png"), "{}", path);
+        assert_eq!(data, b"\x89PNG\r\n\x1a\n");
+        assert_eq!(document.markup, format!("#image(\"{path}\", alt: \"Pasted\")#image(\"{path}\")"));
+
+        for url in ["data:image/png;base64,***", "data:text/html,<p>", "data:image/png", "data:image/svg+xml,%3"] {
+            let delta_json = serde_json::json!({"ops": [{"insert": {"image": url}}]}).to_string();
+            assert!(matches!(parser.parse(&delta_json), Err(ParserError::InvalidFormat(_))), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_source_markers() {
+        let parser = DeltaParser::new().with_source_markers();
+        let delta_json = r#"{"ops":[{"insert":"Title"},{"attributes":{"header":1},"insert":"\n"},{"insert":"Hello "},{"insert":"world","attributes":{"bold":true}},{"insert":"\n"},{"insert":"let x"},{"attributes":{"code-block":true},"insert":"\n"}]}"#;
+
+        let result = parser.parse(delta_json).unwrap();
+        assert!(result.starts_with("= #metadata(0) <source-start>Title#metadata(none) <source-end>\n"), "{}", result);
+        assert!(result.contains("#metadata(3) <source-start>*world*#metadata(none) <source-end>"), "{}", result);
+        assert!(result.ends_with("```\nlet x\n```"), "{}", result);
+        assert_eq!(DeltaParser::new().parse(delta_json).unwrap().matches("metadata").count(), 0);
+    }
+}
@@ -0,0 +1,495 @@
+//! Editor diagnostics.
+//!
+//! Produces LSP-style diagnostics (range, severity, message, related
+//! information) for Typst markup and memo form JSON so editors can underline
+//! problems with a single call. Positions are zero-based lines and UTF-16
+//! code unit columns, matching the Language Server Protocol defaults.
+//!
+//! For markup, ranges point into the markup. For forms, ranges point into
+//! the form JSON text: schema violations and Delta conversion failures are
+//! located at the offending value, while Typst errors raised by the memo
+//! template are reported at the start of the form.
+//!
+//! [`format_pretty`] renders diagnostics for terminals and logs, with source
+//! excerpts, underlines and hints.
+
+use std::fmt::Write as _;
+use std::ops;
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use typst::syntax::Source;
+
+use crate::assets;
+use crate::delta_parser::DeltaParser;
+use crate::form_processor::{self, Content, ContentFormat};
+
+/// Zero-based position in a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    /// Offset within the line in UTF-16 code units
+    pub character: usize,
+}
+
+/// Half-open range between two positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Which stage produced a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSource {
+    /// Typst compilation
+    Typst,
+    /// Memo form JSON parsing or schema validation
+    Schema,
+    /// Quill Delta to Typst conversion
+    Delta,
+}
+
+/// Secondary location that helps explain a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RelatedInformation {
+    pub message: String,
+    /// File the location refers to, e.g. `main.typ` or a package path
+    pub path: String,
+    pub range: Range,
+}
+
+/// A problem found in the input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub source: DiagnosticSource,
+    pub message: String,
+    /// Suggestions for fixing the problem
+    pub hints: Vec<String>,
+    pub related: Vec<RelatedInformation>,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(range: Range, source: DiagnosticSource, message: String) -> Self {
+        Self {
+            range,
+            severity: Severity::Error,
+            source,
+            message,
+            hints: Vec::new(),
+            related: Vec::new(),
+        }
+    }
+}
+
+/// Convert a byte range in `source` to a position range
+pub(crate) fn source_range(source: &Source, bytes: ops::Range<usize>) -> Range {
+    Range {
+        start: position(source, bytes.start),
+        end: position(source, bytes.end),
+    }
+}
+
+fn position(source: &Source, byte: usize) -> Position {
+    let byte = byte.min(source.len_bytes());
+    let line = source.byte_to_line(byte).unwrap_or(0);
+    let line_start = source.line_to_byte(line).unwrap_or(0);
+    let utf16 = |idx| source.byte_to_utf16(idx).unwrap_or(0);
+    Position {
+        line,
+        character: utf16(byte) - utf16(line_start),
+    }
+}
+
+/// Check a memo form without compiling it: JSON syntax, schema conformance,
+/// letterhead references and Delta body conversion
+///
+/// Returns the diagnostics together with the indexed form text so callers
+/// can locate further problems.
+pub(crate) fn check_form(form_json: &str) -> (Source, Vec<Diagnostic>) {
+    let source = Source::detached(form_json);
+
+    let instance: JsonValue = match serde_json::from_str(form_json) {
+        Ok(instance) => instance,
+        Err(e) => {
+            let line = e.line().saturating_sub(1);
+            let byte = source
+                .line_column_to_byte(line, e.column().saturating_sub(1))
+                .or_else(|| source.line_to_byte(line))
+                .unwrap_or(form_json.len());
+            let diagnostic = Diagnostic::error(
+                source_range(&source, byte..byte),
+                DiagnosticSource::Schema,
+                format!("Invalid form JSON: {}", e),
+            );
+            return (source, vec![diagnostic]);
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    let at = |pointer: &str| source_range(&source, locate_pointer(form_json, pointer));
+
+    let validator = form_processor::memo_schema_validator();
+    match validator {
+        Ok(validator) => {
+            for error in validator.iter_errors(&instance) {
+                let pointer = error.instance_path.to_string();
+                diagnostics.push(Diagnostic::error(at(&pointer), DiagnosticSource::Schema, error.to_string()));
+            }
+        }
+        Err(e) => diagnostics.push(Diagnostic::error(at(""), DiagnosticSource::Schema, e.to_string())),
+    }
+
+    if let Some(name) = instance.get("letterhead-background").and_then(JsonValue::as_str) {
+        if !assets::letterhead_exists(name) {
+            diagnostics.push(Diagnostic::error(
+                at("/letterhead-background"),
+                DiagnosticSource::Schema,
+                format!("Unknown letterhead background: '{}'", name),
+            ));
+        }
+    }
+
+    if let Some(Ok(content)) = instance.get("body").map(|body| serde_json::from_value::<Content>(body.clone())) {
+        if content.format == ContentFormat::Delta {
+            if let Err(e) = DeltaParser::new().parse(&content.data) {
+                diagnostics.push(Diagnostic::error(at("/body/data"), DiagnosticSource::Delta, e.to_string()));
+            }
+        }
+    }
+
+    (source, diagnostics)
+}
+
+/// Render diagnostics as a human-readable report with source excerpts
+///
+/// `source` is the text the diagnostic ranges point into (the markup, or the
+/// form JSON for form diagnostics) and `path` is the name shown for it. With
+/// `color`, the report uses ANSI escapes for terminals.
+///
+/// ```text
+/// error: unknown variable: undefined-fn
+///  --> memo.typ:3:2
+///   |
+/// 3 | #undefined-fn()
+///   |  ^^^^^^^^^^^^
+///   |
+///   = hint: if you meant to use subtraction, try adding spaces around the minus sign: `undefined - fn`
+/// ```
+pub fn format_pretty(diagnostics: &[Diagnostic], path: &str, source: &str, color: bool) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut out = String::new();
+    for (index, diagnostic) in diagnostics.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let (label, label_color) = match diagnostic.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+        };
+        let start = diagnostic.range.start;
+        let line_number = (start.line + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+        let bar = paint("1;34", "|");
+
+        let _ = writeln!(out, "{}{}", paint(label_color, label), paint("1", &format!(": {}", diagnostic.message)));
+        let _ = writeln!(out, "{}{} {}:{}:{}", gutter, paint("1;34", "-->"), path, start.line + 1, start.character + 1);
+
+        if let Some(line) = lines.get(start.line) {
+            // Ranges spanning several lines are underlined to the end of the first
+            let end = if diagnostic.range.end.line == start.line {
+                diagnostic.range.end.character
+            } else {
+                usize::MAX
+            };
+            let (text, offset, width) = excerpt(line, start.character, end);
+            let _ = writeln!(out, "{} {}", gutter, bar);
+            let _ = writeln!(out, "{} {} {}", paint("1;34", &line_number), bar, text);
+            let underline = format!("{}{}", " ".repeat(offset), "^".repeat(width.max(1)));
+            let _ = writeln!(out, "{} {} {}", gutter, bar, paint(label_color, &underline));
+        }
+
+        if !diagnostic.hints.is_empty() || !diagnostic.related.is_empty() {
+            let _ = writeln!(out, "{} {}", gutter, bar);
+        }
+        for hint in &diagnostic.hints {
+            let _ = writeln!(out, "{} {} {}", gutter, paint("1;36", "= hint:"), hint);
+        }
+        for related in &diagnostic.related {
+            let _ = writeln!(
+                out,
+                "{} {} {} ({}:{}:{})",
+                gutter,
+                paint("1;34", "= note:"),
+                related.message,
+                related.path,
+                related.range.start.line + 1,
+                related.range.start.character + 1
+            );
+        }
+    }
+    out
+}
+
+/// Expand tabs in `line` and locate the UTF-16 column range `start..end`,
+/// returning the display text with the underline offset and width in columns
+fn excerpt(line: &str, start: usize, end: usize) -> (String, usize, usize) {
+    let mut text = String::new();
+    let (mut utf16, mut column) = (0usize, 0usize);
+    let (mut offset, mut underline_end) = (None, None);
+    for c in line.chars() {
+        if utf16 >= start && offset.is_none() {
+            offset = Some(column);
+        }
+        if utf16 >= end && underline_end.is_none() {
+            underline_end = Some(column);
+        }
+        if c == '\t' {
+            text.push_str("    ");
+            column += 4;
+        } else {
+            text.push(c);
+            column += 1;
+        }
+        utf16 += c.len_utf16();
+    }
+    let offset = offset.unwrap_or(column);
+    let width = underline_end.unwrap_or(column).saturating_sub(offset);
+    (text, offset, width)
+}
+
+/// Byte range of the value addressed by a JSON pointer in `text`
+///
+/// Falls back to the closest enclosing value that exists. The document root
+/// is narrowed to its first character so whole-document errors do not
+/// underline everything. `text` must already be known to be valid JSON.
+fn locate_pointer(text: &str, pointer: &str) -> ops::Range<usize> {
+    let mut scanner = JsonScanner { text: text.as_bytes(), pos: 0 };
+    scanner.skip_whitespace();
+    let root = scanner.pos;
+
+    let mut found = None;
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        let start = scanner.pos;
+        if !scanner.enter(text, &segment) {
+            scanner.pos = start;
+            break;
+        }
+        found = Some(scanner.pos);
+    }
+
+    match found {
+        Some(start) => {
+            scanner.pos = start;
+            scanner.skip_value();
+            start..scanner.pos
+        }
+        None => root..(root + 1).min(text.len()),
+    }
+}
+
+/// Minimal cursor over JSON text used to find value spans
+struct JsonScanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl JsonScanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `byte` (after whitespace) if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let matched = self.peek() == Some(byte);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn skip_string(&mut self) {
+        self.pos += 1;
+        while let Some(byte) = self.peek() {
+            self.pos += if byte == b'\\' { 2 } else { 1 };
+            if byte == b'"' {
+                break;
+            }
+        }
+    }
+
+    /// Skip the value at the cursor, leaving the cursor just past it
+    fn skip_value(&mut self) {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'"') => self.skip_string(),
+            Some(open @ (b'{' | b'[')) => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                if self.eat(close) {
+                    return;
+                }
+                loop {
+                    if open == b'{' {
+                        self.skip_whitespace();
+                        self.skip_string();
+                        self.eat(b':');
+                    }
+                    self.skip_value();
+                    if !self.eat(b',') {
+                        self.eat(close);
+                        return;
+                    }
+                }
+            }
+            Some(_) => {
+                while !matches!(self.peek(), None | Some(b',' | b']' | b'}' | b' ' | b'\t' | b'\r' | b'\n')) {
+                    self.pos += 1;
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Move the cursor from a container to the start of its child `segment`
+    fn enter(&mut self, text: &str, segment: &str) -> bool {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                while !self.eat(b'}') {
+                    self.skip_whitespace();
+                    let key_start = self.pos;
+                    self.skip_string();
+                    let key: Option<String> = serde_json::from_str(&text[key_start..self.pos]).ok();
+                    self.eat(b':');
+                    self.skip_whitespace();
+                    if key.as_deref() == Some(segment) {
+                        return true;
+                    }
+                    self.skip_value();
+                    if !self.eat(b',') {
+                        break;
+                    }
+                }
+                false
+            }
+            Some(b'[') => {
+                let Ok(index) = segment.parse::<usize>() else {
+                    return false;
+                };
+                self.pos += 1;
+                if self.eat(b']') {
+                    return false;
+                }
+                for _ in 0..index {
+                    self.skip_value();
+                    if !self.eat(b',') {
+                        return false;
+                    }
+                }
+                self.skip_whitespace();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_pointer() {
+        let text = r#"{ "a": [1, {"b/c": "x"}], "d": true }"#;
+        assert_eq!(&text[locate_pointer(text, "/a")], r#"[1, {"b/c": "x"}]"#);
+        assert_eq!(&text[locate_pointer(text, "/a/1/b~1c")], r#""x""#);
+        assert_eq!(&text[locate_pointer(text, "/d")], "true");
+        assert_eq!(&text[locate_pointer(text, "/a/5")], r#"[1, {"b/c": "x"}]"#);
+        assert_eq!(&text[locate_pointer(text, "")], "{");
+    }
+
+    #[test]
+    fn test_positions_use_utf16_columns() {
+        let source = Source::detached("ab\n\u{1F600}x");
+        let range = source_range(&source, 7..8);
+        assert_eq!(range.start, Position { line: 1, character: 2 });
+        assert_eq!(range.end, Position { line: 1, character: 3 });
+    }
+
+    #[test]
+    fn test_format_pretty() {
+        let source = "= Title\n\t#bad(1)";
+        let mut diagnostic = Diagnostic::error(
+            source_range(&Source::detached(source), 9..13),
+            DiagnosticSource::Typst,
+            "unknown variable: bad".to_string(),
+        );
+        diagnostic.hints.push("check the spelling".to_string());
+        let report = format_pretty(&[diagnostic], "doc.typ", source, false);
+        assert_eq!(
+            report,
+            "error: unknown variable: bad\n \
+             --> doc.typ:2:2\n  \
+             |\n\
+             2 |     #bad(1)\n  \
+             |     ^^^^\n  \
+             |\n  \
+             = hint: check the spelling\n"
+        );
+        assert!(format_pretty(&[], "doc.typ", source, true).is_empty());
+    }
+
+    #[test]
+    fn test_check_form_locates_schema_errors() {
+        let form = "{\n  \"memo-for\": [\"X\"],\n  \"from-block\": [\"A\"],\n  \"subject\": 42,\n  \"signature-block\": [\"N\", \"T\"]\n}";
+        let (_, diagnostics) = check_form(form);
+        let subject = diagnostics
+            .iter()
+            .find(|d| d.message.contains("42"))
+            .expect("subject type error is reported");
+        assert_eq!(subject.source, DiagnosticSource::Schema);
+        assert_eq!(subject.range.start, Position { line: 3, character: 13 });
+        assert_eq!(subject.range.end, Position { line: 3, character: 15 });
+    }
+
+    #[test]
+    fn test_check_form_reports_delta_and_syntax_errors() {
+        let form = r#"{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body":{"format":"delta","data":"{}"}}"#;
+        let (_, diagnostics) = check_form(form);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source, DiagnosticSource::Delta);
+
+        let (_, diagnostics) = check_form("{\n  \"subject\": }");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Draft renders of memo forms.
+//!
+//! A form with `"draft": true` renders so that nobody mistakes it for the
+//! final memo: it gets a `DRAFT` watermark unless the form sets its own, the
+//! `//SIGNED//` markers of its signature lines are left out, and the foot of
+//! every page says when the draft was rendered. Drafts are never served from
+//! or stored in the output caches, since the render time is part of the
+//! output.
+
+use serde_json::Value as JsonValue;
+
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Watermark text of drafts without a `watermark` field
+pub(crate) const WATERMARK: &str = "DRAFT";
+
+/// Marker of an electronically signed signature block
+const SIGNED_MARKER: &str = "//SIGNED//";
+
+/// Whether the form JSON asks for a draft
+pub(crate) fn is_draft(json_input: &str) -> bool {
+    serde_json::from_str::<JsonValue>(json_input).is_ok_and(|form| form["draft"] == JsonValue::Bool(true))
+}
+
+/// Prepare preprocessed form JSON of a draft for the template: drop the
+/// `//SIGNED//` markers of the signature lines, and lines left empty by
+/// that, and add the render time as `draft-timestamp`. Other forms are
+/// returned as they are.
+pub(crate) fn prepare(processed_input: String) -> Result<String, TypstWrapperError> {
+    if !is_draft(&processed_input) {
+        return Ok(processed_input);
+    }
+    let mut form: JsonValue = serde_json::from_str(&processed_input)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid form JSON: {}", e)))?;
+    unsigned(&mut form["signature-block"]);
+    if let Some(blocks) = form.get_mut("co-signers").and_then(JsonValue::as_array_mut) {
+        blocks.iter_mut().for_each(unsigned);
+    }
+    form["draft-timestamp"] = JsonValue::String(timestamp());
+    Ok(form.to_string())
+}
+
+/// Remove the `//SIGNED//` markers of the signature lines in `lines`
+fn unsigned(lines: &mut JsonValue) {
+    if let Some(lines) = lines.as_array_mut() {
+        lines.retain_mut(|line| match line.as_str() {
+            Some(text) if text.contains(SIGNED_MARKER) => {
+                let text = text.replace(SIGNED_MARKER, "").trim().to_string();
+                *line = JsonValue::String(text);
+                line.as_str().is_some_and(|text| !text.is_empty())
+            }
+            _ => true,
+        });
+    }
+}
+
+/// The current UTC time to the minute, e.g. `2024-01-15 09:30Z`
+fn timestamp() -> String {
+    let Some(now) = crate::print::utc_now() else {
+        return "unknown time".to_string();
+    };
+    let [year, month, day, hour, minute] = [
+        now.year(),
+        now.month().map(i32::from),
+        now.day().map(i32::from),
+        now.hour().map(i32::from),
+        now.minute().map(i32::from),
+    ]
+    .map(Option::unwrap_or_default);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}Z", year, month, day, hour, minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use crate::typst_wrapper::TypstWrapper;
+
+    /// A signed memo, as a draft unless `draft` is turned off
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal()
+            .subject("Draft")
+            .signature_block(["//SIGNED//", "FIRST M. LAST, Capt, USAF //SIGNED//", "Duty Title"])
+            .co_signers([&["JOHN A. DOE, Col, USAF", "//SIGNED//", "Title"]])
+            .set("draft", true)
+    }
+
+    #[test]
+    fn test_drafts_drop_signed_markers() {
+        let processed: JsonValue = serde_json::from_str(&TypstWrapper::validate_form(&form().to_json()).unwrap()).unwrap();
+        assert_eq!(processed["signature-block"], serde_json::json!(["FIRST M. LAST, Capt, USAF", "Duty Title"]));
+        assert_eq!(processed["co-signers"][0], serde_json::json!(["JOHN A. DOE, Col, USAF", "Title"]));
+        let timestamp = processed["draft-timestamp"].as_str().unwrap();
+        assert!(timestamp.len() == 17 && timestamp.ends_with('Z'), "{}", timestamp);
+
+        let final_form = form().set("draft", false).to_json();
+        let processed: JsonValue = serde_json::from_str(&TypstWrapper::validate_form(&final_form).unwrap()).unwrap();
+        assert_eq!(processed["signature-block"][0], SIGNED_MARKER);
+        assert!(processed.get("draft-timestamp").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_drafts_are_watermarked_and_stamped() {
+        use crate::text_layout::form_text_layout;
+
+        let text = |form: &str| {
+            let layout = form_text_layout(form).unwrap();
+            layout[0].runs.iter().map(|run| run.text.clone()).collect::<Vec<_>>().join(" ")
+        };
+        let draft = text(&form().to_json());
+        assert!(draft.contains("DRAFT rendered"), "{}", draft);
+        assert!(!draft.contains(SIGNED_MARKER), "{}", draft);
+        let final_text = text(&form().remove("draft").to_json());
+        assert!(!final_text.contains("DRAFT") && final_text.contains(SIGNED_MARKER), "{}", final_text);
+
+        let processed = TypstWrapper::validate_form(&form().to_json()).unwrap();
+        let watermark = crate::watermark::form_watermark(&processed).unwrap();
+        assert_eq!(watermark.map(|watermark| watermark.text), Some(WATERMARK.to_string()));
+        let own = form().set("watermark", "REVIEW").to_json();
+        let processed = TypstWrapper::validate_form(&own).unwrap();
+        assert_eq!(crate::watermark::form_watermark(&processed).unwrap().unwrap().text, "REVIEW");
+    }
+}
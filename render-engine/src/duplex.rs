@@ -0,0 +1,84 @@
+//! Blank pages for double-sided printing.
+//!
+//! A routing package holds several memos and indorsements one after the
+//! other. Printed double-sided, each of them should start on the front of a
+//! sheet. With [`RenderConfig::duplex`](crate::RenderConfig::duplex) set, a
+//! blank page is inserted before every element labelled
+//! [`SECTION_START_LABEL`] that would otherwise land on an even page.
+
+use typst::foundations::{Content, Label, Selector};
+use typst::introspection::Introspector;
+use typst::layout::{Frame, Page, PagedDocument};
+use typst::utils::PicoStr;
+
+use crate::locate::SECTION_START_LABEL;
+
+/// Insert blank pages so every labelled section starts on an odd page,
+/// returning the number of inserted pages
+pub(crate) fn insert_blank_pages(document: &mut PagedDocument) -> usize {
+    let introspector = &document.introspector;
+    let mut starts: Vec<usize> = introspector
+        .query(&Selector::Label(Label::new(PicoStr::intern(SECTION_START_LABEL))))
+        .iter()
+        .filter_map(|element| element.location())
+        .map(|location| introspector.page(location).get() - 1)
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+
+    let original = document.pages.len();
+    let mut pages = Vec::with_capacity(original + starts.len());
+    for (index, page) in std::mem::take(&mut document.pages).into_iter().enumerate() {
+        if pages.len() % 2 == 1 && starts.binary_search(&index).is_ok() {
+            pages.push(Page {
+                frame: Frame::hard(page.frame.size()),
+                fill: page.fill.clone(),
+                numbering: None,
+                supplement: Content::empty(),
+                number: page.number.saturating_sub(1),
+            });
+        }
+        pages.push(page);
+    }
+    document.pages = pages;
+    if document.pages.len() > original {
+        // Links and outline entries must point at the shifted pages
+        document.introspector = Introspector::paged(&document.pages);
+    }
+    document.pages.len() - original
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typst_wrapper::TypstWrapper;
+
+    #[test]
+    fn test_sections_start_on_odd_pages() {
+        // Sections start on pages 1, 2 and 5; the second and fifth move
+        let markup = "[A] <section-start>\n#pagebreak()\n[B] <section-start>\n#pagebreak()\n[B2]\n#pagebreak()\n[B3]\n\
+                      #pagebreak()\n[C] <section-start>";
+        let mut document = TypstWrapper::compile_markup(markup).unwrap();
+        assert_eq!(insert_blank_pages(&mut document), 2);
+        assert_eq!(document.pages.len(), 7);
+        assert!(document.pages[1].frame.items().next().is_none());
+        assert!(document.pages[5].frame.items().next().is_none());
+        let starts = crate::locate::locate_markup_label(markup, SECTION_START_LABEL).unwrap();
+        assert_eq!(starts.iter().map(|start| start.page).collect::<Vec<_>>(), [0, 1, 4]);
+        let shifted: Vec<usize> = document
+            .introspector
+            .query(&Selector::Label(Label::new(PicoStr::intern(SECTION_START_LABEL))))
+            .iter()
+            .filter_map(|element| element.location())
+            .map(|location| document.introspector.page(location).get())
+            .collect();
+        assert_eq!(shifted, [1, 3, 7]);
+
+        let config = crate::RenderConfig { duplex: true, ..crate::RenderConfig::default() };
+        assert_eq!(crate::render_markup(markup, Some(config)).unwrap().len(), 7);
+
+        let mut unlabelled = TypstWrapper::compile_markup("A\n#pagebreak()\nB").unwrap();
+        assert_eq!(insert_blank_pages(&mut unlabelled), 0);
+        assert_eq!(unlabelled.pages.len(), 2);
+    }
+}
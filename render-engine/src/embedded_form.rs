@@ -0,0 +1,207 @@
+//! Memo form JSON embedded in PDF output.
+//!
+//! A PDF is often the only copy of a memo that survives, so with
+//! [`RenderConfig::embed_form`](crate::RenderConfig::embed_form) set, a form
+//! render attaches the form JSON it was rendered from as the embedded file
+//! [`EMBEDDED_FORM_NAME`], along with the [`SCHEMA_VERSION`] it was
+//! validated against. [`extract_form`] reads them back, so the structured
+//! editor can reopen the final PDF, including after it was signed.
+//!
+//! The form is stored as it was rendered: the form JSON with the running
+//! header, footer and style of the config moved into it, reformatted
+//! without insignificant whitespace. Rendering it again gives the same
+//! memo.
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::form_processor::SCHEMA_VERSION;
+use crate::pdf::{dict_value, reference, references, text_string, with_entry, PdfUpdate};
+use crate::typst_wrapper::TypstWrapperError;
+
+/// File name of the embedded form JSON
+pub const EMBEDDED_FORM_NAME: &str = "memo-form.json";
+
+/// A memo form read back from a PDF
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmbeddedForm {
+    /// The form JSON, ready for `render_form`
+    pub form: String,
+    /// Version of the memo schema the form was validated against
+    pub schema_version: Option<String>,
+}
+
+/// The form JSON as it is embedded
+pub(crate) fn canonical_form(json_input: &str) -> Result<String, TypstWrapperError> {
+    serde_json::from_str::<JsonValue>(json_input)
+        .map(|form| form.to_string())
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid form JSON: {}", e)))
+}
+
+/// Attach `form` to a PDF exported by the engine as [`EMBEDDED_FORM_NAME`]
+pub(crate) fn attach(pdf: Vec<u8>, form: &str) -> Result<Vec<u8>, TypstWrapperError> {
+    let error = |e: &str| TypstWrapperError::OutputFormat(format!("The form JSON could not be embedded: {}", e));
+    let mut update = PdfUpdate::parse(&pdf).map_err(|e| error(&e))?;
+    let root = update.root();
+    let catalog = update.object(root).ok_or_else(|| error("missing catalog"))?;
+
+    // Hex keeps the stream ASCII, which the PDF reader expects
+    let hex: String = form.bytes().map(|byte| format!("{:02X}", byte)).collect();
+    let file = update.allocate();
+    update.set(
+        file,
+        format!(
+            "<<\n  /Type /EmbeddedFile\n  /Subtype /application#2Fjson\n  /Params << /Size {} >>\n  /Filter /ASCIIHexDecode\n  /Length {}\n>>\nstream\n{}>\nendstream",
+            form.len(),
+            hex.len() + 1,
+            hex
+        ),
+    );
+    let spec = update.allocate();
+    update.set(
+        spec,
+        format!(
+            "<<\n  /Type /Filespec\n  /F {name}\n  /UF {name}\n  /Desc (Memo form JSON)\n  /AFRelationship /Source\n  /SchemaVersion {}\n  /EF << /F {file} 0 R /UF {file} 0 R >>\n>>",
+            text_string(SCHEMA_VERSION),
+            name = text_string(EMBEDDED_FORM_NAME),
+        ),
+    );
+
+    let embedded = format!("<< /Names [{} {} 0 R] >>", text_string(EMBEDDED_FORM_NAME), spec);
+    let catalog = match dict_value(&catalog, "Names") {
+        None => with_entry(&catalog, "Names", &format!("<< /EmbeddedFiles {} >>", embedded)),
+        Some(names) => {
+            let (number, names) = match indirect(names) {
+                Some(number) => (Some(number), update.object(number).ok_or_else(|| error("missing name tree"))?),
+                None => (None, names.to_string()),
+            };
+            if dict_value(&names, "EmbeddedFiles").is_some() {
+                return Err(error("the PDF already has embedded files"));
+            }
+            let names = with_entry(&names, "EmbeddedFiles", &embedded).ok_or_else(|| error("invalid name tree"))?;
+            match number {
+                Some(number) => {
+                    update.set(number, names);
+                    Some(catalog)
+                }
+                None => with_entry(&catalog, "Names", &names),
+            }
+        }
+    };
+    let catalog = catalog
+        .and_then(|catalog| with_entry(&catalog, "AF", &format!("[{} 0 R]", spec)))
+        .ok_or_else(|| error("invalid catalog"))?;
+    update.set(root, catalog);
+    Ok(update.finish().0)
+}
+
+/// The object number of `value` if it is a reference rather than a direct
+/// dictionary holding one
+fn indirect(value: &str) -> Option<u32> {
+    reference(value).filter(|_| !value.starts_with("<<"))
+}
+
+/// Read the memo form embedded in a PDF rendered with
+/// [`RenderConfig::embed_form`](crate::RenderConfig::embed_form)
+///
+/// # Examples
+/// ```
+/// use render_engine::embedded_form::extract_form;
+/// use render_engine::{render_form, OutputFormat, RenderConfig};
+///
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Reopened",
+///     "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+/// let config = RenderConfig { format: OutputFormat::Pdf, embed_form: true, ..RenderConfig::default() };
+/// let pdf = render_form(form, Some(config)).unwrap().remove(0);
+///
+/// let embedded = extract_form(&pdf).unwrap();
+/// assert!(embedded.form.contains("Reopened"));
+/// ```
+pub fn extract_form(pdf: &[u8]) -> Result<EmbeddedForm, TypstWrapperError> {
+    let error = |e: &str| TypstWrapperError::Validation(format!("No memo form could be read from the PDF: {}", e));
+    let update = PdfUpdate::parse(pdf).map_err(|e| error(&e))?;
+    // Dictionary values may be given directly or as a reference
+    let resolve = |value: &str| match indirect(value) {
+        Some(number) => update.object(number),
+        None => Some(value.to_string()),
+    };
+    let catalog = update.object(update.root()).ok_or_else(|| error("missing catalog"))?;
+    let names = dict_value(&catalog, "Names").and_then(resolve).ok_or_else(|| error("no embedded files"))?;
+    let files = dict_value(&names, "EmbeddedFiles").and_then(resolve).ok_or_else(|| error("no embedded files"))?;
+    let array = dict_value(&files, "Names").ok_or_else(|| error("no embedded files"))?;
+    let name = text_string(EMBEDDED_FORM_NAME);
+    let spec = array
+        .find(&name)
+        .and_then(|at| references(&array[at + name.len()..]).first().copied())
+        .and_then(|number| update.object(number))
+        .ok_or_else(|| error(&format!("no {} attached", EMBEDDED_FORM_NAME)))?;
+    let schema_version = dict_value(&spec, "SchemaVersion").map(|version| version.trim_matches(['(', ')']).to_string());
+    let file = dict_value(&spec, "EF")
+        .and_then(|ef| dict_value(ef, "F"))
+        .and_then(reference)
+        .and_then(|number| update.object(number))
+        .ok_or_else(|| error("missing file stream"))?;
+    if dict_value(&file, "Filter") != Some("/ASCIIHexDecode") {
+        return Err(error("unsupported stream encoding"));
+    }
+    let start = file.find("stream").ok_or_else(|| error("missing file stream"))? + "stream".len();
+    let end = file.rfind("endstream").ok_or_else(|| error("missing file stream"))?;
+    let digits: Vec<u8> = file[start..end]
+        .bytes()
+        .take_while(|&byte| byte != b'>')
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| error("invalid file stream"))?;
+    let form = String::from_utf8(bytes).map_err(|_| error("the form is not UTF-8"))?;
+    Ok(EmbeddedForm { form, schema_version })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use crate::{render_form, OutputFormat, RenderConfig};
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::delta().subject("Überprüfung (Round Trip)")
+    }
+
+    fn pdf_config() -> RenderConfig {
+        RenderConfig { format: OutputFormat::Pdf, embed_form: true, ..RenderConfig::default() }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pdf = render_form(&form().to_json(), Some(pdf_config())).unwrap().remove(0);
+        let embedded = extract_form(&pdf).unwrap();
+        assert_eq!(embedded.schema_version.as_deref(), Some(SCHEMA_VERSION));
+        let round_trip: JsonValue = serde_json::from_str(&embedded.form).unwrap();
+        assert_eq!(round_trip, form().build());
+        // The embedded form renders the same memo again
+        assert_eq!(render_form(&embedded.form, Some(pdf_config())).unwrap()[0].len(), pdf.len());
+    }
+
+    #[test]
+    fn test_forms_are_embedded_on_request() {
+        let plain = RenderConfig { format: OutputFormat::Pdf, ..RenderConfig::default() };
+        let pdf = render_form(&form().to_json(), Some(plain)).unwrap().remove(0);
+        assert!(extract_form(&pdf).unwrap_err().to_string().contains("no embedded files"));
+        let markup = crate::render_markup("Markup", Some(pdf_config())).unwrap().remove(0);
+        assert!(extract_form(&markup).is_err());
+    }
+
+    #[test]
+    fn test_config_overrides_are_embedded() {
+        let config = RenderConfig {
+            footer: Some(crate::RunningText { center: Some("CUI".to_string()), ..crate::RunningText::default() }),
+            ..pdf_config()
+        };
+        let pdf = render_form(&form().to_json(), Some(config)).unwrap().remove(0);
+        let embedded: JsonValue = serde_json::from_str(&extract_form(&pdf).unwrap().form).unwrap();
+        assert_eq!(embedded["running-footer"]["center"], "CUI");
+    }
+}
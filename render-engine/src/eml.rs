@@ -0,0 +1,173 @@
+//! Email (RFC 5322 / MIME) output.
+//!
+//! [`render_form_eml`] renders a memo form to PDF and wraps it in a
+//! `multipart/mixed` message whose subject is the memo subject, ready to hand
+//! to an SMTP service or save as a `.eml` draft.
+
+use base64::Engine;
+use serde_json::Value;
+use tracing::instrument;
+
+use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWrapper, TypstWrapperError};
+
+/// Message fields that don't come from the memo
+///
+/// `date` is an RFC 5322 date such as `Tue, 9 Sep 2025 14:00:00 +0000`. It
+/// is not taken from the engine's clock, so without one the message has no
+/// `Date` header and the submission server adds it.
+#[derive(Debug, Clone, Default)]
+pub struct EmlOptions {
+    /// `From` mailbox, e.g. `Jane Doe <jane.doe@example.mil>`
+    pub from: Option<String>,
+    /// `To` mailboxes
+    pub to: Vec<String>,
+    /// `Cc` mailboxes
+    pub cc: Vec<String>,
+    /// `Date` header value
+    pub date: Option<String>,
+    /// Plain-text message body (defaults to a one-line note)
+    pub body: Option<String>,
+    /// Attachment file name (defaults to `memo.pdf`)
+    pub filename: Option<String>,
+}
+
+/// Body used when [`EmlOptions::body`] is not set
+const DEFAULT_BODY: &str = "The memorandum is attached.";
+
+/// Render a memo form to PDF and return an `.eml` message with it attached
+///
+/// Fails with a validation error if a header value in `options` contains a
+/// line break.
+#[instrument(skip_all, fields(json_len = json_input.len()))]
+pub fn render_form_eml(json_input: &str, options: &EmlOptions) -> Result<Vec<u8>, TypstWrapperError> {
+    let headers = [options.from.as_deref(), options.date.as_deref(), options.filename.as_deref()];
+    let mailboxes = options.to.iter().chain(&options.cc).map(String::as_str);
+    if let Some(value) = headers.into_iter().flatten().chain(mailboxes).find(|v| v.contains(['\r', '\n'])) {
+        return Err(TypstWrapperError::Validation(format!("Header value contains a line break: {:?}", value)));
+    }
+
+    let config = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
+    let pdf = TypstWrapper::render_form(json_input, Some(config))?.remove(0);
+    let subject = serde_json::from_str::<Value>(json_input)
+        .ok()
+        .and_then(|form| form.get("subject")?.as_str().map(str::to_string))
+        .unwrap_or_default();
+    Ok(message(&subject, &pdf, options).into_bytes())
+}
+
+/// Assemble the MIME message
+fn message(subject: &str, pdf: &[u8], options: &EmlOptions) -> String {
+    let boundary = format!("render-engine-{:032x}", typst::utils::hash128(pdf));
+    let filename = options.filename.as_deref().unwrap_or("memo.pdf").replace(['"', '\\'], "_");
+    let body = options.body.as_deref().unwrap_or(DEFAULT_BODY);
+
+    let mut headers = Vec::new();
+    if let Some(from) = &options.from {
+        headers.push(format!("From: {}", from));
+    }
+    if !options.to.is_empty() {
+        headers.push(format!("To: {}", options.to.join(", ")));
+    }
+    if !options.cc.is_empty() {
+        headers.push(format!("Cc: {}", options.cc.join(", ")));
+    }
+    if let Some(date) = &options.date {
+        headers.push(format!("Date: {}", date));
+    }
+    headers.push(format!("Subject: {}", encode_header(subject)));
+    headers.push("MIME-Version: 1.0".to_string());
+    headers.push(format!("Content-Type: multipart/mixed; boundary=\"{}\"", boundary));
+
+    let parts = [
+        format!(
+            "Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            base64_lines(body.as_bytes())
+        ),
+        format!(
+            "Content-Type: application/pdf; name=\"{0}\"\r\nContent-Disposition: attachment; filename=\"{0}\"\r\nContent-Transfer-Encoding: base64\r\n\r\n{1}",
+            filename,
+            base64_lines(pdf)
+        ),
+    ];
+
+    let mut message = headers.join("\r\n");
+    message.push_str("\r\n\r\n");
+    for part in parts {
+        message.push_str(&format!("--{}\r\n{}\r\n", boundary, part));
+    }
+    message.push_str(&format!("--{}--\r\n", boundary));
+    message
+}
+
+/// A header value as-is when it is printable ASCII, otherwise as an
+/// RFC 2047 encoded word. Line breaks become spaces.
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::engine::general_purpose::STANDARD.encode(value))
+    }
+}
+
+/// Base64 wrapped at 76 characters per line (RFC 2045)
+fn base64_lines(bytes: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    encoded
+        .as_bytes()
+        .chunks(76)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+
+    fn form() -> String {
+        MemoBuilder::minimal().subject("Quarterly Readiness Report").to_json()
+    }
+
+    #[test]
+    fn test_eml_has_subject_and_pdf_attachment() {
+        let options = EmlOptions {
+            from: Some("Jane Doe <jane.doe@example.mil>".to_string()),
+            to: vec!["ops@example.mil".to_string(), "cc@example.mil".to_string()],
+            filename: Some("report.pdf".to_string()),
+            ..EmlOptions::default()
+        };
+        let eml = String::from_utf8(render_form_eml(&form(), &options).unwrap()).unwrap();
+        let (headers, body) = eml.split_once("\r\n\r\n").unwrap();
+
+        assert!(headers.contains("From: Jane Doe <jane.doe@example.mil>\r\n"));
+        assert!(headers.contains("To: ops@example.mil, cc@example.mil\r\n"));
+        assert!(headers.contains("Subject: Quarterly Readiness Report\r\n"));
+        assert!(!headers.contains("Date:"));
+        assert!(body.contains("Content-Disposition: attachment; filename=\"report.pdf\""));
+
+        // The attachment decodes back to the PDF
+        let attachment = body.split("Content-Transfer-Encoding: base64\r\n\r\n").nth(2).unwrap();
+        let encoded: String = attachment.split("\r\n--").next().unwrap().split("\r\n").collect();
+        let pdf = base64::engine::general_purpose::STANDARD.decode(encoded).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(eml.lines().all(|line| line.len() <= 998));
+    }
+
+    #[test]
+    fn test_non_ascii_subjects_are_encoded() {
+        assert_eq!(encode_header("Plain"), "Plain");
+        assert_eq!(encode_header("Café"), "=?UTF-8?B?Q2Fmw6k=?=");
+        assert_eq!(encode_header("Two\nlines"), "Two lines");
+    }
+
+    #[test]
+    fn test_header_injection_is_rejected() {
+        let options = EmlOptions {
+            to: vec!["ops@example.mil\r\nBcc: attacker@example.com".to_string()],
+            ..EmlOptions::default()
+        };
+        assert!(matches!(render_form_eml(&form(), &options), Err(TypstWrapperError::Validation(_))));
+    }
+}
@@ -0,0 +1,553 @@
+//! Independent render engines.
+//!
+//! The free functions of this crate render with one engine shared by the
+//! whole process. A server rendering for several tenants needs engines that
+//! do not see each other's registrations: a [`RenderEngine`] owns its
+//! letterhead backgrounds, [assets](crate::register_asset),
+//! [fonts](crate::fonts), [local packages](crate::packages),
+//! [memo template](crate::template) and its partials, registration limits,
+//! [clock](crate::today) and parsed template sources.
+//! Inside [`RenderEngine::scope`] the free functions (renders, diagnostics,
+//! registrations, snapshots) act on that engine instead of the shared one.
+//!
+//! # Thread safety
+//!
+//! `RenderEngine` is `Send` and `Sync` and cheap to clone; clones share their
+//! state. One engine can serve many threads and several engines can render
+//! at the same time. Everything engines share is immutable once loaded and
+//! read without locks: the embedded fonts and their font book, the Typst
+//! standard library, the embedded assets and the form schema.
+//!
+//! An engine's own state is copy-on-write. Each render takes a snapshot of
+//! the registered letterheads, files, local packages and parsed sources when
+//! it starts, so compiles never wait on each other or on registrations; a
+//! letterhead registered while renders are running is used by the renders
+//! started afterwards.
+//!
+//! The output caches, the cache provider and the metrics callback are set up
+//! once per process and serve every engine. Cache keys include the engine's
+//! letterheads, files, local packages, fonts, memo template and partials,
+//! so engines never get each other's output.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError, RwLock};
+
+use typst::foundations::Bytes;
+use typst::text::Font;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{FileId, Source};
+
+use crate::assets::{RegistrationError, RegistrationLimits};
+use crate::capabilities::Capabilities;
+use crate::diagnostics::Diagnostic;
+use crate::fonts::FontSet;
+use crate::metrics::RenderOutput;
+use crate::session::RenderSession;
+use crate::template::Partial;
+use crate::typst_wrapper::{RenderConfig, TypstWrapperError};
+use crate::today::{self, Clock, HostTime};
+
+/// Letterhead backgrounds by name
+pub(crate) type Letterheads = Arc<HashMap<String, Bytes>>;
+
+/// Images and other files registered at runtime, by path
+pub(crate) type RegisteredAssets = Arc<HashMap<String, Bytes>>;
+
+/// Parsed package sources, so each file is parsed once per engine
+pub(crate) type PackageSources = Arc<HashMap<FileId, Source>>;
+
+/// Files of a local package or memo template by path within it
+pub(crate) type PackageFiles = Arc<HashMap<String, Bytes>>;
+
+/// Fonts registered at runtime, by registration key
+pub(crate) type RegisteredFonts = Arc<HashMap<String, Vec<Font>>>;
+
+/// Registered `@local` packages
+pub(crate) type LocalPackages = Arc<HashMap<PackageSpec, PackageFiles>>;
+
+/// Sources of the overridden memo template partials
+pub(crate) type TemplatePartials = Arc<BTreeMap<Partial, String>>;
+
+/// A render engine with its own registrations, see the
+/// [module documentation](self)
+///
+/// # Examples
+/// ```
+/// use render_engine::RenderEngine;
+///
+/// let tenant = RenderEngine::new();
+/// let seal = render_engine::assets::load_binary_asset("dod_seal.gif").unwrap();
+/// tenant.register_letterhead("tenant.gif", seal.content.to_vec()).unwrap();
+///
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Tenant",
+///     "signature-block": ["NAME", "TITLE"], "body_raw": "Body", "letterhead-background": "tenant.gif"}"#;
+/// assert!(tenant.render_form(form, None).is_ok());
+/// // Other engines do not know the letterhead
+/// assert!(render_engine::render_form(form, None).is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RenderEngine {
+    state: Arc<EngineState>,
+}
+
+#[derive(Debug, Default)]
+struct EngineState {
+    letterheads: RwLock<Letterheads>,
+    assets: RwLock<RegisteredAssets>,
+    fonts: RwLock<RegisteredFonts>,
+    /// The embedded and registered fonts, if any are registered
+    font_set: RwLock<Option<Arc<FontSet>>>,
+    local_packages: RwLock<LocalPackages>,
+    memo_template: RwLock<Option<PackageFiles>>,
+    template_partials: RwLock<TemplatePartials>,
+    limits: RwLock<RegistrationLimits>,
+    /// Clock renders are dated with, the system clock when unset
+    clock: RwLock<Option<Clock>>,
+    /// Held while a registration checks the limits and registers, so
+    /// registrations at the same time count each other
+    registering: Mutex<()>,
+    package_sources: RwLock<PackageSources>,
+}
+
+/// The registrations a world is built from, telling a
+/// [`RenderSession`](crate::RenderSession) whether its world is out of date
+#[derive(Clone)]
+pub(crate) struct Registrations {
+    letterheads: Letterheads,
+    assets: RegisteredAssets,
+    font_set: Option<Arc<FontSet>>,
+    local_packages: LocalPackages,
+    memo_template: Option<PackageFiles>,
+    template_partials: TemplatePartials,
+}
+
+impl Registrations {
+    /// Whether nothing was registered or removed between `self` and `other`.
+    /// Registries are replaced, never changed in place, while a copy is held.
+    pub(crate) fn same(&self, other: &Self) -> bool {
+        fn same_option<T>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+        }
+        Arc::ptr_eq(&self.letterheads, &other.letterheads)
+            && Arc::ptr_eq(&self.assets, &other.assets)
+            && same_option(&self.font_set, &other.font_set)
+            && Arc::ptr_eq(&self.local_packages, &other.local_packages)
+            && same_option(&self.memo_template, &other.memo_template)
+            && Arc::ptr_eq(&self.template_partials, &other.template_partials)
+    }
+}
+
+/// The engine of the free functions
+static SHARED: LazyLock<RenderEngine> = LazyLock::new(RenderEngine::new);
+
+thread_local! {
+    /// The engine of the innermost `RenderEngine::scope` on this thread
+    static CURRENT: RefCell<Option<RenderEngine>> = const { RefCell::new(None) };
+}
+
+/// The engine the free functions act on: the one of the innermost
+/// [`RenderEngine::scope`] on this thread, or the shared engine
+pub(crate) fn current() -> RenderEngine {
+    CURRENT.with(|current| current.borrow().clone()).unwrap_or_else(|| SHARED.clone())
+}
+
+impl RenderEngine {
+    /// An engine without registered letterheads and with the default
+    /// registration limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The engine the free functions use outside of any scope
+    pub fn shared() -> Self {
+        SHARED.clone()
+    }
+
+    /// Run `f` with this engine as the one the crate's free functions act on
+    /// in this thread
+    ///
+    /// Scopes nest, and the previous engine is restored when `f` returns or
+    /// panics. Threads spawned by `f` start outside the scope.
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<RenderEngine>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.borrow_mut().replace(self.clone())));
+        f()
+    }
+
+    /// Render Typst markup, see [`render_markup`](crate::render_markup)
+    pub fn render_markup(&self, markup: &str, config: Option<RenderConfig>) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        self.scope(|| crate::render_markup(markup, config))
+    }
+
+    /// Render a memo form, see [`render_form`](crate::render_form)
+    pub fn render_form(&self, json_input: &str, config: Option<RenderConfig>) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        self.scope(|| crate::render_form(json_input, config))
+    }
+
+    /// Render Typst markup to SVG strings, see
+    /// [`render_markup_svg_strings`](crate::render_markup_svg_strings)
+    pub fn render_markup_svg_strings(&self, markup: &str, config: Option<RenderConfig>) -> Result<Vec<String>, TypstWrapperError> {
+        self.scope(|| crate::render_markup_svg_strings(markup, config))
+    }
+
+    /// Render a memo form to SVG strings, see
+    /// [`render_form_svg_strings`](crate::render_form_svg_strings)
+    pub fn render_form_svg_strings(&self, json_input: &str, config: Option<RenderConfig>) -> Result<Vec<String>, TypstWrapperError> {
+        self.scope(|| crate::render_form_svg_strings(json_input, config))
+    }
+
+    /// What this engine can do, see [`capabilities`](crate::capabilities())
+    pub fn capabilities(&self) -> Capabilities {
+        self.scope(crate::capabilities)
+    }
+
+    /// Render Typst markup with metrics, see
+    /// [`render_markup_with_metrics`](crate::render_markup_with_metrics)
+    pub fn render_markup_with_metrics(&self, markup: &str, config: Option<RenderConfig>) -> Result<RenderOutput, TypstWrapperError> {
+        self.scope(|| crate::render_markup_with_metrics(markup, config))
+    }
+
+    /// Render a memo form with metrics, see
+    /// [`render_form_with_metrics`](crate::render_form_with_metrics)
+    pub fn render_form_with_metrics(&self, json_input: &str, config: Option<RenderConfig>) -> Result<RenderOutput, TypstWrapperError> {
+        self.scope(|| crate::render_form_with_metrics(json_input, config))
+    }
+
+    /// Collect diagnostics for Typst markup, see
+    /// [`diagnose_markup`](crate::diagnose_markup)
+    pub fn diagnose_markup(&self, markup: &str) -> Vec<Diagnostic> {
+        self.scope(|| crate::diagnose_markup(markup))
+    }
+
+    /// Collect diagnostics for a memo form, see
+    /// [`diagnose_form`](crate::diagnose_form)
+    pub fn diagnose_form(&self, json_input: &str) -> Vec<Diagnostic> {
+        self.scope(|| crate::diagnose_form(json_input))
+    }
+
+    /// Generate the Typst source a memo form renders as with this engine's
+    /// template, see [`generate_typst`](crate::generate_typst)
+    pub fn generate_typst(&self, json_input: &str) -> Result<String, TypstWrapperError> {
+        self.scope(|| crate::generate_typst(json_input))
+    }
+
+    /// Parse the fonts and this engine's template sources ahead of the first
+    /// render, see [`prewarm`](crate::prewarm)
+    pub fn prewarm(&self) -> Result<(), TypstWrapperError> {
+        self.scope(crate::prewarm)
+    }
+
+    /// Start a [`RenderSession`] with this engine, rendering with `config`
+    /// or the default configuration
+    pub fn session(&self, config: Option<RenderConfig>) -> RenderSession {
+        RenderSession::with_engine(self.clone(), config)
+    }
+
+    /// Register a letterhead background with this engine, see
+    /// [`register_letterhead`](crate::register_letterhead)
+    pub fn register_letterhead(&self, name: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+        self.scope(|| crate::register_letterhead(name, data))
+    }
+
+    /// Remove a letterhead background from this engine, returning whether it
+    /// was registered
+    pub fn unregister_letterhead(&self, name: &str) -> bool {
+        self.update_letterheads(|letterheads| Ok(letterheads.remove(name).is_some())).unwrap_or(false)
+    }
+
+    /// Register an image or other file with this engine, see
+    /// [`register_asset`](crate::register_asset)
+    pub fn register_asset(&self, path: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+        self.scope(|| crate::register_asset(path, data))
+    }
+
+    /// Remove a registered file from this engine, see
+    /// [`unregister_asset`](crate::unregister_asset)
+    pub fn unregister_asset(&self, path: &str) -> bool {
+        self.scope(|| crate::unregister_asset(path))
+    }
+
+    /// Register a font with this engine, see
+    /// [`register_font`](crate::register_font)
+    pub fn register_font(&self, data: Vec<u8>) -> Result<Vec<String>, TypstWrapperError> {
+        self.scope(|| crate::register_font(data))
+    }
+
+    /// The font families renders with this engine can select, see
+    /// [`list_fonts`](crate::list_fonts)
+    pub fn list_fonts(&self) -> Vec<crate::FontFamily> {
+        self.scope(crate::list_fonts)
+    }
+
+    /// Remove the fonts of a family from this engine, see
+    /// [`unregister_font`](crate::unregister_font)
+    pub fn unregister_font(&self, family: &str) -> bool {
+        self.scope(|| crate::unregister_font(family))
+    }
+
+    /// Register a local package with this engine, see
+    /// [`register_local_package`](crate::packages::register_local_package)
+    pub fn register_local_package(&self, files: Vec<(String, Vec<u8>)>) -> Result<String, TypstWrapperError> {
+        self.scope(|| crate::packages::register_local_package(files))
+    }
+
+    /// Remove a local package from this engine, see
+    /// [`unregister_local_package`](crate::packages::unregister_local_package)
+    pub fn unregister_local_package(&self, spec: &str) -> bool {
+        self.scope(|| crate::packages::unregister_local_package(spec))
+    }
+
+    /// Render forms with a custom memo template, see
+    /// [`set_memo_template`](crate::template::set_memo_template)
+    pub fn set_memo_template(&self, files: Vec<(String, Vec<u8>)>) -> Result<(), TypstWrapperError> {
+        self.scope(|| crate::template::set_memo_template(files))
+    }
+
+    /// Go back to the embedded memo template, returning whether a custom one
+    /// was set
+    pub fn reset_memo_template(&self) -> bool {
+        self.set_memo_template_files(None).is_some()
+    }
+
+    /// Override one partial of the memo template, see
+    /// [`set_template_partial`](crate::template::set_template_partial)
+    pub fn set_template_partial(&self, partial: Partial, source: &str) -> Result<(), TypstWrapperError> {
+        self.scope(|| crate::template::set_template_partial(partial, source))
+    }
+
+    /// Go back to the embedded source of a partial, returning whether it was
+    /// overridden
+    pub fn reset_template_partial(&self, partial: Partial) -> bool {
+        self.set_template_partial_source(partial, None).is_some()
+    }
+
+    /// Replace the limits applied to this engine's registrations
+    pub fn set_registration_limits(&self, limits: RegistrationLimits) {
+        *self.state.limits.write().unwrap_or_else(PoisonError::into_inner) = limits;
+    }
+
+    /// The limits applied to this engine's registrations
+    pub fn registration_limits(&self) -> RegistrationLimits {
+        *self.state.limits.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Date this engine's renders with `clock`, see
+    /// [`set_clock`](crate::today::set_clock)
+    pub fn set_clock<F>(&self, clock: F)
+    where
+        F: Fn() -> HostTime + Send + Sync + 'static,
+    {
+        *self.state.clock.write().unwrap_or_else(PoisonError::into_inner) = Some(Clock::new(clock));
+    }
+
+    /// Go back to the system clock in UTC
+    pub fn clear_clock(&self) {
+        *self.state.clock.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// The current time from this engine's clock, or the system clock in UTC
+    pub(crate) fn now(&self) -> Option<HostTime> {
+        let clock = self.state.clock.read().unwrap_or_else(PoisonError::into_inner).clone();
+        today::time(clock.as_ref())
+    }
+
+    /// Hold off other registrations with this engine until the guard drops,
+    /// so a registration checks the limits against all the others
+    pub(crate) fn registration_lock(&self) -> MutexGuard<'_, ()> {
+        self.state.registering.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Everything registered as of now that renders read
+    pub(crate) fn registrations(&self) -> Registrations {
+        Registrations {
+            letterheads: self.letterheads(),
+            assets: self.assets(),
+            font_set: self.font_set(),
+            local_packages: self.local_packages(),
+            memo_template: self.memo_template(),
+            template_partials: self.template_partials(),
+        }
+    }
+
+    /// The registered letterhead backgrounds as of now
+    pub(crate) fn letterheads(&self) -> Letterheads {
+        self.state.letterheads.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Change a copy of the registered letterheads with `update`, keeping it
+    /// only if `update` succeeds
+    pub(crate) fn update_letterheads<T>(
+        &self,
+        update: impl FnOnce(&mut HashMap<String, Bytes>) -> Result<T, RegistrationError>,
+    ) -> Result<T, RegistrationError> {
+        update_registry(&self.state.letterheads, update)
+    }
+
+    /// The registered images and other files as of now
+    pub(crate) fn assets(&self) -> RegisteredAssets {
+        self.state.assets.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Change a copy of the registered files with `update`, keeping it only
+    /// if `update` succeeds
+    pub(crate) fn update_assets<T>(
+        &self,
+        update: impl FnOnce(&mut HashMap<String, Bytes>) -> Result<T, RegistrationError>,
+    ) -> Result<T, RegistrationError> {
+        update_registry(&self.state.assets, update)
+    }
+
+    /// The registered fonts as of now
+    pub(crate) fn fonts(&self) -> RegisteredFonts {
+        self.state.fonts.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// The embedded and registered fonts as of now, `None` without
+    /// registered fonts
+    pub(crate) fn font_set(&self) -> Option<Arc<FontSet>> {
+        self.state.font_set.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Change a copy of the registered fonts with `update`, keeping it and
+    /// the font set built from it only if `update` succeeds
+    pub(crate) fn update_fonts<T>(
+        &self,
+        update: impl FnOnce(&mut HashMap<String, Vec<Font>>) -> Result<T, RegistrationError>,
+    ) -> Result<T, RegistrationError> {
+        // Holding the registry lock keeps the set in step with it
+        let mut registry = self.state.fonts.write().unwrap_or_else(PoisonError::into_inner);
+        let mut updated = HashMap::clone(&registry);
+        let result = update(&mut updated)?;
+        let font_set = (!updated.is_empty()).then(|| Arc::new(FontSet::new(crate::typst_wrapper::embedded_fonts(), &updated)));
+        *self.state.font_set.write().unwrap_or_else(PoisonError::into_inner) = font_set;
+        *registry = Arc::new(updated);
+        Ok(result)
+    }
+
+    /// The registered local packages as of now
+    pub(crate) fn local_packages(&self) -> LocalPackages {
+        self.state.local_packages.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Change a copy of the registered local packages with `update`, keeping
+    /// it only if `update` succeeds
+    pub(crate) fn update_local_packages<T>(
+        &self,
+        update: impl FnOnce(&mut HashMap<PackageSpec, PackageFiles>) -> Result<T, RegistrationError>,
+    ) -> Result<T, RegistrationError> {
+        update_registry(&self.state.local_packages, update)
+    }
+
+    /// The files of the custom memo template, if one is set
+    pub(crate) fn memo_template(&self) -> Option<PackageFiles> {
+        self.state.memo_template.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Replace the custom memo template, returning the previous one
+    pub(crate) fn set_memo_template_files(&self, template: Option<PackageFiles>) -> Option<PackageFiles> {
+        std::mem::replace(&mut *self.state.memo_template.write().unwrap_or_else(PoisonError::into_inner), template)
+    }
+
+    /// The overridden template partials as of now
+    pub(crate) fn template_partials(&self) -> TemplatePartials {
+        self.state.template_partials.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Override a template partial, or go back to the embedded one for
+    /// `None`, returning the previous override
+    pub(crate) fn set_template_partial_source(&self, partial: Partial, source: Option<String>) -> Option<String> {
+        let mut partials = self.state.template_partials.write().unwrap_or_else(PoisonError::into_inner);
+        let partials = Arc::make_mut(&mut partials);
+        match source {
+            Some(source) => partials.insert(partial, source),
+            None => partials.remove(&partial),
+        }
+    }
+
+    /// The package sources parsed so far
+    pub(crate) fn package_sources(&self) -> PackageSources {
+        self.state.package_sources.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Keep a parsed package source for later renders
+    pub(crate) fn cache_package_source(&self, source: Source) {
+        let mut sources = self.state.package_sources.write().unwrap_or_else(PoisonError::into_inner);
+        Arc::make_mut(&mut sources).insert(source.id(), source);
+    }
+}
+
+/// Replace the registry behind `lock` with a copy changed by `update`, if
+/// `update` succeeds
+fn update_registry<K: Clone + Eq + std::hash::Hash, V: Clone, T>(
+    lock: &RwLock<Arc<HashMap<K, V>>>,
+    update: impl FnOnce(&mut HashMap<K, V>) -> Result<T, RegistrationError>,
+) -> Result<T, RegistrationError> {
+    let mut registry = lock.write().unwrap_or_else(PoisonError::into_inner);
+    let mut updated = HashMap::clone(&registry);
+    let result = update(&mut updated)?;
+    *registry = Arc::new(updated);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets;
+    use crate::test_utils::MemoBuilder;
+
+    fn form() -> String {
+        MemoBuilder::minimal().subject("Engines").set("letterhead-background", "engine-test.gif").to_json()
+    }
+
+    #[test]
+    fn test_engines_are_isolated() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<RenderEngine>();
+
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        let engine = RenderEngine::new();
+        engine.register_letterhead("engine-test.gif", seal).unwrap();
+        assert!(engine.scope(|| assets::letterhead_exists("engine-test.gif")));
+        assert!(!assets::letterhead_exists("engine-test.gif"));
+        assert!(!RenderEngine::new().scope(|| assets::letterhead_exists("engine-test.gif")));
+
+        assert!(engine.render_form(&form(), None).is_ok());
+        assert!(matches!(RenderEngine::new().render_form(&form(), None), Err(TypstWrapperError::Validation(_))));
+        // Scopes restore the previous engine
+        engine.scope(|| RenderEngine::new().scope(|| assert!(!assets::letterhead_exists("engine-test.gif"))));
+        assert!(engine.scope(|| assets::letterhead_exists("engine-test.gif")));
+
+        engine.set_registration_limits(RegistrationLimits { max_count: 1, ..RegistrationLimits::default() });
+        assert!(engine.register_letterhead("second.gif", assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec()).is_err());
+        assert_eq!(RenderEngine::new().registration_limits(), RegistrationLimits::default());
+        assert!(engine.unregister_letterhead("engine-test.gif"));
+        assert!(!engine.unregister_letterhead("engine-test.gif"));
+    }
+
+    #[test]
+    fn test_concurrent_renders() {
+        let shared = RenderEngine::new();
+        let handles: Vec<_> = (0..4)
+            .map(|index| {
+                // Half the threads share one engine, the others have their own
+                let engine = if index % 2 == 0 { shared.clone() } else { RenderEngine::new() };
+                std::thread::spawn(move || engine.render_markup(&format!("= Thread {}", index), None).map(|pages| pages.len()))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), 1);
+        }
+    }
+}
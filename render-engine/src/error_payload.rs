@@ -0,0 +1,180 @@
+//! Machine-readable errors.
+//!
+//! HTTP services and JavaScript hosts want to branch on what went wrong
+//! without matching message text. [`TypstWrapperError`] and [`ParserError`]
+//! serialize to an [`ErrorPayload`] with a stable shape:
+//!
+//! ```json
+//! {"kind": "validation", "code": "schema_mismatch", "path": "/subject",
+//!  "message": "/subject: Validation failed: Form JSON does not match schema: ..."}
+//! ```
+//!
+//! `kind` and `code` only ever gain new values. An error about one form
+//! field carries the field's JSON pointer as `path`, and the Delta op of the
+//! body as `op`; an error of one batch job carries the job's `file` name,
+//! and a form compile error the generated Typst around it as `excerpt`.
+//! All keep the `kind` and `code` of the underlying error.
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::assets::RegistrationError;
+use crate::delta_parser::ParserError;
+use crate::typst_wrapper::TypstWrapperError;
+
+/// The serialized form of an engine error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorPayload {
+    /// Broad class of the error: `validation`, `parser`, `compilation`,
+    /// `font`, `output`, `file_not_found`, `io`, `registration` or
+    /// `cancelled`
+    pub kind: String,
+    /// Specific, stable error code within the kind, such as
+    /// `schema_mismatch` or `asset_too_large`
+    pub code: String,
+    /// Human-readable description, as the error displays
+    pub message: String,
+    /// JSON pointer of the form field the error concerns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Index of the Delta op of the body the error lies in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub op: Option<usize>,
+    /// File name of the batch job that failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Numbered lines of the generated Typst around a form compile error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
+}
+
+impl ErrorPayload {
+    fn new(kind: &str, code: &str, message: String) -> Self {
+        Self {
+            kind: kind.to_string(),
+            code: code.to_string(),
+            message,
+            path: None,
+            op: None,
+            file: None,
+            excerpt: None,
+        }
+    }
+}
+
+impl From<&TypstWrapperError> for ErrorPayload {
+    fn from(error: &TypstWrapperError) -> Self {
+        let message = error.to_string();
+        let (kind, code) = match error {
+            TypstWrapperError::Compilation(_) => ("compilation", "compilation_failed"),
+            TypstWrapperError::Font(_) => ("font", "font_unavailable"),
+            TypstWrapperError::OutputFormat(_) => ("output", "export_failed"),
+            TypstWrapperError::FileNotFound(_) => ("file_not_found", "file_not_found"),
+            TypstWrapperError::Io(_) => ("io", "io_error"),
+            TypstWrapperError::Validation(_) => ("validation", "invalid_input"),
+            TypstWrapperError::Registration(error) => ("registration", registration_code(error)),
+            TypstWrapperError::Cancelled => ("cancelled", "cancelled"),
+            TypstWrapperError::Batch { file_name, error } => {
+                return Self { file: Some(file_name.clone()), message, ..Self::from(error.as_ref()) };
+            }
+            TypstWrapperError::Excerpt { excerpt, error } => {
+                return Self { excerpt: Some(excerpt.clone()), message, ..Self::from(error.as_ref()) };
+            }
+            // Schema errors of a field arrive as its validation error
+            TypstWrapperError::Field { field, op, error } => {
+                let inner = match error.as_ref() {
+                    TypstWrapperError::Validation(text) if text.starts_with("Form JSON does not match schema") => {
+                        Self::new("validation", "schema_mismatch", String::new())
+                    }
+                    error => Self::from(error),
+                };
+                return Self { path: Some(field.clone()), op: *op, message, ..inner };
+            }
+        };
+        Self::new(kind, code, message)
+    }
+}
+
+impl From<&ParserError> for ErrorPayload {
+    fn from(error: &ParserError) -> Self {
+        let message = error.to_string();
+        match error {
+            ParserError::InvalidFormat(_) => Self::new("parser", "invalid_format", message),
+            ParserError::UnsupportedOperation(_) => Self::new("parser", "unsupported_operation", message),
+            ParserError::JsonError(_) => Self::new("parser", "invalid_json", message),
+            ParserError::Schema { path, .. } => Self {
+                path: Some(path.clone()).filter(|path| !path.is_empty()),
+                ..Self::new("validation", "schema_mismatch", message)
+            },
+        }
+    }
+}
+
+fn registration_code(error: &RegistrationError) -> &'static str {
+    match error {
+        RegistrationError::AssetTooLarge { .. } => "asset_too_large",
+        RegistrationError::TotalSizeExceeded { .. } => "total_size_exceeded",
+        RegistrationError::TooManyAssets { .. } => "too_many_assets",
+    }
+}
+
+impl Serialize for TypstWrapperError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorPayload::from(self).serialize(serializer)
+    }
+}
+
+impl Serialize for ParserError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorPayload::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_errors_name_the_field() {
+        let form = MemoBuilder::minimal().set("subject", 7).to_json();
+        let error = crate::render_form(&form, None).unwrap_err();
+        let payload = serde_json::to_value(&error).unwrap();
+        assert_eq!(payload["kind"], "validation");
+        assert_eq!(payload["code"], "schema_mismatch");
+        assert_eq!(payload["path"], "/subject");
+        assert_eq!(payload["message"], error.to_string());
+
+        let parser_error = crate::form_processor::validate_official_memo_schema(&form).unwrap_err();
+        assert_eq!(serde_json::to_value(&parser_error).unwrap()["path"], "/subject");
+    }
+
+    #[test]
+    fn test_nested_errors_keep_their_kind() {
+        let field = TypstWrapperError::Field {
+            field: "/body".to_string(),
+            op: Some(2),
+            error: Box::new(TypstWrapperError::Compilation("unknown variable".to_string())),
+        };
+        let batch = TypstWrapperError::Batch { file_name: "memo.pdf".to_string(), error: Box::new(field) };
+        assert_eq!(
+            serde_json::to_value(&batch).unwrap(),
+            json!({
+                "kind": "compilation",
+                "code": "compilation_failed",
+                "message": "memo.pdf: /body (Delta op 2): Compilation failed: unknown variable",
+                "path": "/body",
+                "op": 2,
+                "file": "memo.pdf"
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_round_trips() {
+        let error = ParserError::UnsupportedOperation("retain".to_string());
+        let payload: ErrorPayload = serde_json::from_value(serde_json::to_value(&error).unwrap()).unwrap();
+        assert_eq!(payload, ErrorPayload::from(&error));
+        assert_eq!((payload.kind.as_str(), payload.code.as_str()), ("parser", "unsupported_operation"));
+    }
+}
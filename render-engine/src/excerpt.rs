@@ -0,0 +1,110 @@
+//! Excerpts of the generated Typst around form compile errors.
+//!
+//! A form renders as the memo template with the form's values, and a Delta
+//! body as markup converted from it, so the lines an error points at are
+//! nowhere in the input. Form compile errors carry the few lines around the
+//! first error instead, from the template file it lies in or, for errors
+//! in the body, from the body's generated markup.
+
+use std::fmt::Write;
+
+use serde_json::Value as JsonValue;
+use typst::diag::SourceDiagnostic;
+use typst::layout::PagedDocument;
+use typst::syntax::{Source, Span};
+use typst::World;
+
+use crate::typst_wrapper::{TypstWorld, TypstWrapper};
+
+/// Lines shown before and after the line of the error
+const CONTEXT_LINES: usize = 2;
+
+/// The generated source around the first of `errors` of compiling a form
+/// world, given the form `field` the errors were attributed to
+pub(crate) fn form_excerpt(world: &TypstWorld, errors: &[SourceDiagnostic], field: Option<&str>) -> Option<String> {
+    let form: JsonValue = serde_json::from_str(world.form_input()?).ok()?;
+    if let (Some("/body" | "/body_raw"), Some(body)) = (field, form["body_raw"].as_str()) {
+        if let Some(excerpt) = body_excerpt(body) {
+            return Some(excerpt);
+        }
+    }
+    let span = errors.first()?.span;
+    let source = world.source(span.id()?).ok()?;
+    let path = if source.id() == world.main() { "main.typ".to_string() } else { display_path(&source) };
+    excerpt(&source, span, &path)
+}
+
+/// The body markup around the first error of compiling it on its own
+fn body_excerpt(body: &str) -> Option<String> {
+    let world = TypstWrapper::markup_world(body);
+    let errors = typst::compile::<PagedDocument>(&world).output.err()?;
+    let span = errors.first()?.span;
+    excerpt(&world.source(span.id()?).ok()?, span, "body_raw")
+}
+
+/// Path of a template or package source, with the package of package files
+fn display_path(source: &Source) -> String {
+    let id = source.id();
+    let path = id.vpath().as_rootless_path().display();
+    match id.package() {
+        Some(package) => format!("{}/{}", package, path),
+        None => path.to_string(),
+    }
+}
+
+/// Numbered lines of `source` around `span`, the line of the span marked
+/// with `>`
+fn excerpt(source: &Source, span: Span, path: &str) -> Option<String> {
+    let start = source.range(span)?.start;
+    let line = source.byte_to_line(start)?;
+    let lines: Vec<&str> = source.text().lines().collect();
+    let first = line.saturating_sub(CONTEXT_LINES);
+    let last = (line + CONTEXT_LINES).min(lines.len().saturating_sub(1));
+    let width = (last + 1).to_string().len();
+
+    let mut out = format!("{}:{}", path, line + 1);
+    for (index, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+        let marker = if index == line { '>' } else { ' ' };
+        let _ = write!(out, "\n{} {:>width$} | {}", marker, index + 1, text);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::MemoBuilder;
+    use crate::typst_wrapper::TypstWrapperError;
+
+    fn form(body: &str) -> MemoBuilder {
+        MemoBuilder::minimal().subject("Errors").body_raw(&format!("First line\n\nSecond line {}\n\nLast line", body))
+    }
+
+    fn excerpt_of(form: &str) -> String {
+        let mut error = crate::render_form(form, None).unwrap_err();
+        loop {
+            error = match error {
+                TypstWrapperError::Field { error, .. } => *error,
+                TypstWrapperError::Excerpt { excerpt, .. } => return excerpt,
+                other => panic!("no excerpt in {}", other),
+            };
+        }
+    }
+
+    #[test]
+    fn test_body_errors_show_the_body() {
+        let excerpt = excerpt_of(&form("#undefined-function()").to_json());
+        assert!(excerpt.starts_with("body_raw:3\n"), "{}", excerpt);
+        assert!(excerpt.contains("> 3 | Second line #undefined-function()"), "{}", excerpt);
+        assert!(excerpt.contains("  5 | Last line"), "{}", excerpt);
+    }
+
+    #[test]
+    fn test_template_errors_show_the_template() {
+        let form = form("").date("2024-13-45").to_json();
+        let excerpt = excerpt_of(&form);
+        assert!(excerpt.lines().next().is_some_and(|line| line.starts_with("main.typ:")), "{}", excerpt);
+        assert_eq!(excerpt.lines().filter(|line| line.starts_with('>')).count(), 1, "{}", excerpt);
+        let message = crate::render_form(&form, None).unwrap_err().to_string();
+        assert!(message.contains(&excerpt), "{}", message);
+    }
+}
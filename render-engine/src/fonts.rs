@@ -0,0 +1,489 @@
+//! Runtime font registration and font family substitution.
+//!
+//! The engine embeds Arial, Times and Copperplate. Units with their own
+//! approved typefaces register them at runtime with [`register_font`], as
+//! TrueType, OpenType or WOFF data; renders started afterwards can select
+//! the registered families by name. Registered fonts of a family and style
+//! the engine embeds do not replace the embedded ones. Hosts that fetch
+//! fonts on demand look up the families a document is missing with
+//! [`unknown_font_families`].
+//!
+//! Documents written for office fonts name families the engine may not have,
+//! or that an organization wants replaced by its own typeface. With
+//! [`RenderConfig::font_substitutions`](crate::RenderConfig::font_substitutions)
+//! a family is rendered with the fonts of another one, matching weight and
+//! style as Typst would within a family:
+//!
+//! ```
+//! use render_engine::{render_markup_with_metrics, FontSubstitution, RenderConfig};
+//!
+//! let mut config = RenderConfig::default();
+//! config.font_substitutions.insert("Helvetica".to_string(), "Arial".to_string());
+//! let output = render_markup_with_metrics("#set text(font: \"Helvetica\")\nHello", Some(config)).unwrap();
+//! assert_eq!(output.font_substitutions, [FontSubstitution { from: "Helvetica".into(), to: "Arial".into() }]);
+//! ```
+//!
+//! Substitutions that no text of the document ended up using are left out of
+//! [`RenderOutput::font_substitutions`](crate::RenderOutput::font_substitutions).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Mutex, PoisonError};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use typst::foundations::Bytes;
+use typst::layout::{Frame, FrameItem, PagedDocument};
+use typst::text::{Font, FontBook, FontStyle};
+use typst::utils::{hash128, LazyHash};
+
+use crate::assets::{self, RegistrationKind};
+use crate::diagnostics::Diagnostic;
+use crate::engine;
+use crate::typst_wrapper::TypstWrapperError;
+use crate::woff;
+
+/// Start of the Typst warning about a font family no font provides
+const UNKNOWN_FAMILY: &str = "unknown font family: ";
+
+/// Register a TrueType, OpenType (also collections) or WOFF font with the
+/// current [`RenderEngine`](crate::RenderEngine), subject to its
+/// registration limits, returning the families it holds
+///
+/// Registering a font with the same faces (family, style, weight, stretch
+/// and coverage) as a registered one replaces it. Fails on WOFF2 and on
+/// data that holds no font.
+///
+/// # Examples
+/// ```
+/// # let bytes = render_engine::assets::load_binary_asset("CopperplateCC-Heavy.otf").unwrap().content.to_vec();
+/// let families = render_engine::register_font(bytes).unwrap();
+/// assert_eq!(families, ["Copperplate CC"]);
+/// ```
+pub fn register_font(data: Vec<u8>) -> Result<Vec<String>, TypstWrapperError> {
+    let (key, fonts) = prepare(data)?;
+    let families = families(&fonts);
+    let size = fonts[0].data().len();
+    let engine = engine::current();
+    let _registering = engine.registration_lock();
+    let limits = engine.registration_limits();
+    let (letterheads, files, packages) = (engine.letterheads(), engine.assets(), engine.local_packages());
+    engine.update_fonts(|registry| {
+        let registered = assets::registered_sizes(&letterheads, &files, &packages, registry);
+        limits.check(RegistrationKind::Font, &key, size, assets::entries(&registered))?;
+        registry.insert(key.clone(), fonts);
+        Ok(())
+    })?;
+    info!(font = %key, ?families, size, "registered font");
+    Ok(families)
+}
+
+/// The registration key and the faces of font `data`, decoding WOFF
+pub(crate) fn prepare(data: Vec<u8>) -> Result<(String, Vec<Font>), TypstWrapperError> {
+    if woff::is_woff2(&data) {
+        return Err(TypstWrapperError::Font(
+            "WOFF2 fonts are not supported; register the font as TTF, OTF or WOFF".to_string(),
+        ));
+    }
+    let data = match woff::decode(&data) {
+        Some(decoded) => decoded.map_err(TypstWrapperError::Font)?,
+        None => data,
+    };
+    let fonts: Vec<Font> = Font::iter(Bytes::new(data)).collect();
+    let Some(first) = fonts.first() else {
+        return Err(TypstWrapperError::Font(
+            "not a TrueType, OpenType or WOFF font".to_string(),
+        ));
+    };
+    // Named for limit errors; fonts with the same faces replace each other
+    let infos: Vec<_> = fonts.iter().map(Font::info).collect();
+    let key = format!("font:{}:{:016x}", first.info().family, hash128(&infos) as u64);
+    Ok((key, fonts))
+}
+
+/// The registered fonts, each as the data of its file, sorted by key
+pub(crate) fn registered_fonts() -> Vec<(String, Bytes)> {
+    let mut fonts: Vec<_> = engine::current()
+        .fonts()
+        .iter()
+        .map(|(key, fonts)| (key.clone(), fonts[0].data().clone()))
+        .collect();
+    fonts.sort_by(|(a, _), (b, _)| a.cmp(b));
+    fonts
+}
+
+/// Remove the registered fonts that hold `family` (ignoring case),
+/// returning whether there were any
+pub fn unregister_font(family: &str) -> bool {
+    engine::current()
+        .update_fonts(|registry| {
+            let before = registry.len();
+            registry.retain(|_, fonts| !fonts.iter().any(|font| font.info().family.eq_ignore_ascii_case(family)));
+            Ok(registry.len() < before)
+        })
+        .unwrap_or(false)
+}
+
+/// A font family documents can select, with its faces
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FontFamily {
+    pub name: String,
+    /// Faces sorted by style (normal, italic, oblique), weight and stretch
+    pub faces: Vec<FontFace>,
+}
+
+/// A style, weight and stretch of a font family
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FontFace {
+    /// `"normal"`, `"italic"` or `"oblique"`
+    pub style: &'static str,
+    /// From 100 (thin) to 900 (black); 400 is regular and 700 bold
+    pub weight: u16,
+    /// Width relative to the normal width, from 0.5 to 2
+    pub stretch: f64,
+    /// Whether the face was registered with
+    /// [`register_font`] rather than embedded
+    pub registered: bool,
+}
+
+/// The font families renders with the current engine can select: the
+/// embedded ones and those registered with [`register_font`], sorted by name
+///
+/// # Examples
+/// ```
+/// let fonts = render_engine::list_fonts();
+/// let times = fonts.iter().find(|family| family.name == "Times New Roman").unwrap();
+/// assert!(times.faces.iter().any(|face| face.style == "normal" && face.weight == 400));
+/// ```
+pub fn list_fonts() -> Vec<FontFamily> {
+    let registered = engine::current().fonts();
+    let embedded = crate::typst_wrapper::embedded_fonts().iter().map(|font| (font, false));
+    let mut families: BTreeMap<String, Vec<FontFace>> = BTreeMap::new();
+    for (font, registered) in embedded.chain(registered.values().flatten().map(|font| (font, true))) {
+        let info = font.info();
+        let face = FontFace {
+            style: match info.variant.style {
+                FontStyle::Normal => "normal",
+                FontStyle::Italic => "italic",
+                FontStyle::Oblique => "oblique",
+            },
+            weight: info.variant.weight.to_number(),
+            stretch: info.variant.stretch.to_ratio().get(),
+            registered,
+        };
+        let faces = families.entry(info.family.clone()).or_default();
+        if !faces.contains(&face) {
+            faces.push(face);
+        }
+    }
+    families
+        .into_iter()
+        .map(|(name, mut faces)| {
+            let style = |face: &FontFace| ["normal", "italic", "oblique"].iter().position(|style| *style == face.style);
+            faces.sort_by(|a, b| (style(a), a.weight).cmp(&(style(b), b.weight)).then(a.stretch.total_cmp(&b.stretch)));
+            FontFamily { name, faces }
+        })
+        .collect()
+}
+
+/// The font families a render's `diagnostics` report as unknown, in lower
+/// case and sorted
+///
+/// Hosts that fetch fonts on demand register these before rendering again.
+///
+/// # Examples
+/// ```
+/// let diagnostics = render_engine::diagnose_markup("#set text(font: \"Garamond\")\nHello");
+/// assert_eq!(render_engine::unknown_font_families(&diagnostics), ["garamond"]);
+/// ```
+pub fn unknown_font_families(diagnostics: &[Diagnostic]) -> Vec<String> {
+    let families: BTreeSet<String> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.message.strip_prefix(UNKNOWN_FAMILY))
+        .map(str::to_string)
+        .collect();
+    families.into_iter().collect()
+}
+
+/// The families of the fonts registered with the current engine, sorted
+pub fn registered_font_families() -> Vec<String> {
+    families(engine::current().fonts().values().flatten())
+}
+
+/// The distinct families of `fonts`, sorted
+fn families<'a>(fonts: impl IntoIterator<Item = &'a Font>) -> Vec<String> {
+    let families: BTreeSet<String> = fonts.into_iter().map(|font| font.info().family.clone()).collect();
+    families.into_iter().collect()
+}
+
+/// The embedded fonts followed by the fonts registered with an engine, and
+/// the book describing them
+#[derive(Debug)]
+pub(crate) struct FontSet {
+    pub(crate) fonts: Vec<Font>,
+    pub(crate) book: LazyHash<FontBook>,
+}
+
+impl FontSet {
+    /// The `embedded` fonts and the `registered` ones, ordered by their
+    /// registration keys so every render lists them alike
+    pub(crate) fn new(embedded: &[Font], registered: &HashMap<String, Vec<Font>>) -> Self {
+        let mut keys: Vec<&String> = registered.keys().collect();
+        keys.sort();
+        let fonts: Vec<Font> = embedded
+            .iter()
+            .chain(keys.into_iter().flat_map(|key| &registered[key]))
+            .cloned()
+            .collect();
+        let book = LazyHash::new(FontBook::from_fonts(fonts.iter()));
+        Self { fonts, book }
+    }
+}
+
+/// A family that was rendered with the fonts of another
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FontSubstitution {
+    /// The family the document asked for
+    pub from: String,
+    /// The family it was rendered with
+    pub to: String,
+}
+
+/// A font book in which substituted families select the fonts of their
+/// replacements
+///
+/// The book lists every font under its own family first, with the
+/// substituted families renamed so they can no longer be selected by name,
+/// followed by an alias entry for each font of a replacement family under
+/// the family it replaces.
+pub(crate) struct SubstitutedBook {
+    pub(crate) book: LazyHash<FontBook>,
+    /// Number of fonts before the aliases
+    fonts: usize,
+    aliases: Vec<Alias>,
+    /// Aliases Typst loaded, by index into `aliases`
+    loaded: Mutex<BTreeSet<usize>>,
+}
+
+struct Alias {
+    /// Index of the font the alias stands for
+    font: usize,
+    substitution: FontSubstitution,
+}
+
+impl SubstitutedBook {
+    /// The book for `fonts` with `substitutions` (requested family to
+    /// replacement family) applied
+    ///
+    /// Fails if a replacement family is not among `fonts`.
+    pub(crate) fn new(fonts: &[Font], substitutions: &BTreeMap<String, String>) -> Result<Self, TypstWrapperError> {
+        let replaced: HashSet<String> = substitutions.keys().map(|from| from.to_lowercase()).collect();
+        let mut book = FontBook::new();
+        for font in fonts {
+            let mut info = font.info().clone();
+            if replaced.contains(&info.family.to_lowercase()) {
+                // Not a name any document can ask for
+                info.family = format!("\u{0}{}", info.family);
+            }
+            book.push(info);
+        }
+
+        let mut aliases = Vec::new();
+        for (from, to) in substitutions {
+            let start = aliases.len();
+            for (index, font) in fonts.iter().enumerate() {
+                if font.info().family.eq_ignore_ascii_case(to) {
+                    let mut info = font.info().clone();
+                    info.family = from.clone();
+                    book.push(info);
+                    aliases.push(Alias {
+                        font: index,
+                        substitution: FontSubstitution { from: from.clone(), to: to.clone() },
+                    });
+                }
+            }
+            if aliases.len() == start {
+                return Err(TypstWrapperError::Validation(format!(
+                    "Cannot substitute font '{}': font family '{}' is not available",
+                    from, to
+                )));
+            }
+        }
+
+        Ok(Self {
+            book: LazyHash::new(book),
+            fonts: fonts.len(),
+            aliases,
+            loaded: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// The index of the font behind book entry `index`, noting aliases
+    pub(crate) fn font_index(&self, index: usize) -> Option<usize> {
+        if index < self.fonts {
+            return Some(index);
+        }
+        let alias = index - self.fonts;
+        let font = self.aliases.get(alias)?.font;
+        self.loaded.lock().unwrap_or_else(PoisonError::into_inner).insert(alias);
+        Some(font)
+    }
+
+    /// The substitutions whose fonts text of `document` is set in, in the
+    /// order of the requested families
+    ///
+    /// Typst also loads fonts to try them as fallbacks, so a loaded alias
+    /// only counts if its font was used.
+    pub(crate) fn report(&self, document: &PagedDocument, fonts: &[Font]) -> Vec<FontSubstitution> {
+        let mut used = HashSet::new();
+        for page in &document.pages {
+            collect_fonts(&page.frame, &mut used);
+        }
+        let loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        let substitutions: BTreeSet<&FontSubstitution> = loaded
+            .iter()
+            .map(|&alias| &self.aliases[alias])
+            .filter(|alias| used.contains(&hash128(&fonts[alias.font])))
+            .map(|alias| &alias.substitution)
+            .collect();
+        substitutions.into_iter().cloned().collect()
+    }
+}
+
+/// Collect the hashes of the fonts text in `frame` is set in
+fn collect_fonts(frame: &Frame, used: &mut HashSet<u128>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_fonts(&group.frame, used),
+            FrameItem::Text(text) => {
+                used.insert(hash128(&text.font));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "font-arial"))]
+mod tests {
+    use super::*;
+    use crate::{render_form_with_metrics, render_markup_with_metrics, RenderConfig, RenderEngine};
+    use crate::assets::{RegistrationError, RegistrationLimits};
+
+    const UNEMBEDDED: &str = "#set text(font: \"Arual\")\nHello";
+
+    /// Arial renamed to a family the engine does not embed
+    fn unembedded_font() -> Vec<u8> {
+        let mut data = assets::load_binary_asset("arial.ttf").unwrap().content.to_vec();
+        // The name table holds the family in ASCII and in UTF-16
+        for (from, to) in [(b"Arial".to_vec(), b"Arual".to_vec()), (b"\0A\0r\0i\0a\0l".to_vec(), b"\0A\0r\0u\0a\0l".to_vec())] {
+            while let Some(at) = data.windows(from.len()).position(|window| window == from) {
+                data[at..at + to.len()].copy_from_slice(&to);
+            }
+        }
+        data
+    }
+
+    /// Whether markup using the unembedded family warns about it with `engine`
+    fn warns_about_family(engine: &RenderEngine) -> bool {
+        unknown_font_families(&engine.diagnose_markup(UNEMBEDDED)) == ["arual"]
+    }
+
+    #[test]
+    fn test_registered_fonts_can_be_selected() {
+        let engine = RenderEngine::new();
+        assert!(warns_about_family(&engine));
+        assert_eq!(engine.register_font(unembedded_font()).unwrap(), ["Arual"]);
+        assert!(!warns_about_family(&engine));
+        assert!(warns_about_family(&RenderEngine::new()));
+        assert!(engine.scope(registered_font_families).contains(&"Arual".to_string()));
+        assert!(engine.scope(|| assets::get_asset_manifest().fonts).contains(&"Arual".to_string()));
+
+        // The same font again, packed as WOFF, is the same registration
+        assert_eq!(engine.register_font(crate::woff::pack(&unembedded_font())).unwrap(), ["Arual"]);
+        assert_eq!(engine.fonts().len(), 1);
+        assert!(engine.unregister_font("arual"));
+        assert!(!engine.unregister_font("arual"));
+        assert!(warns_about_family(&engine));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_list_fonts() {
+        let engine = RenderEngine::new();
+        let family = |name: &str| engine.scope(list_fonts).into_iter().find(|family| family.name == name);
+        let arial = family("Arial").unwrap();
+        assert!(arial.faces.contains(&FontFace { style: "normal", weight: 400, stretch: 1.0, registered: false }));
+        assert!(family("Times New Roman").is_some());
+        let times = family("Times").unwrap();
+        assert_eq!(times.faces.iter().map(|face| (face.style, face.weight)).collect::<Vec<_>>(), [
+            ("normal", 400),
+            ("normal", 700),
+            ("italic", 400),
+            ("italic", 700)
+        ]);
+        assert!(family("Arual").is_none());
+
+        engine.register_font(unembedded_font()).unwrap();
+        let arual = family("Arual").unwrap();
+        assert_eq!(arual.faces, [FontFace { style: "normal", weight: 400, stretch: 1.0, registered: true }]);
+        let names: Vec<String> = engine.scope(list_fonts).into_iter().map(|family| family.name).collect();
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn test_invalid_fonts_are_rejected() {
+        let engine = RenderEngine::new();
+        for data in [b"not a font".to_vec(), b"wOF2\0\x01\0\0".to_vec(), b"wOFF\0\x01\0\0".to_vec()] {
+            assert!(matches!(engine.register_font(data), Err(TypstWrapperError::Font(_))));
+        }
+        engine.set_registration_limits(RegistrationLimits { max_asset_bytes: 1024, ..RegistrationLimits::default() });
+        let result = engine.register_font(unembedded_font());
+        assert!(matches!(result, Err(TypstWrapperError::Registration(RegistrationError::AssetTooLarge { .. }))));
+        assert!(engine.fonts().is_empty());
+    }
+
+    #[test]
+    fn test_registered_fonts_are_in_snapshots() {
+        let engine = RenderEngine::new();
+        engine.register_font(unembedded_font()).unwrap();
+        let snapshot = engine.scope(crate::engine_snapshot).unwrap();
+        let restored = RenderEngine::new();
+        assert_eq!(restored.scope(|| crate::restore_engine_snapshot(&snapshot)).unwrap().fonts, 1);
+        assert!(!warns_about_family(&restored));
+    }
+
+    fn config(substitutions: &[(&str, &str)]) -> RenderConfig {
+        RenderConfig {
+            font_substitutions: substitutions.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect(),
+            ..RenderConfig::default()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_substitutions_replace_families() {
+        let markup = "#set text(font: \"Times New Roman\")\nSerif #text(font: \"Courier\")[Mono]";
+        let plain = render_markup_with_metrics(markup, None).unwrap();
+        assert!(plain.font_substitutions.is_empty());
+
+        // An embedded family is replaced as well as a missing one
+        let output = render_markup_with_metrics(markup, Some(config(&[("Times New Roman", "Arial"), ("courier", "Times")]))).unwrap();
+        assert_ne!(output.pages, plain.pages);
+        let substitution = |from: &str, to: &str| FontSubstitution { from: from.to_string(), to: to.to_string() };
+        assert_eq!(output.font_substitutions, [substitution("Times New Roman", "Arial"), substitution("courier", "Times")]);
+
+        // Substitutions for families the document does not use are not reported
+        let output = render_markup_with_metrics("Hello", Some(config(&[("Garamond", "Times")]))).unwrap();
+        assert!(output.font_substitutions.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_unknown_replacement_family() {
+        let result = render_markup_with_metrics("Hello", Some(config(&[("Arial", "Corporate Sans")])));
+        assert!(matches!(result, Err(TypstWrapperError::Validation(message)) if message.contains("Corporate Sans")));
+
+        let form = crate::test_utils::MemoBuilder::minimal().subject("Fonts").to_json();
+        let output = render_form_with_metrics(&form, Some(config(&[("Times", "Arial")]))).unwrap();
+        assert_eq!(output.font_substitutions, [FontSubstitution { from: "Times".to_string(), to: "Arial".to_string() }]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Fillable signature and date fields in PDF output.
+//!
+//! E-signature tools route a PDF by its form fields: an empty signature
+//! field is where a signer applies a digital signature, and a date field is
+//! filled in when they do. With
+//! [`RenderConfig::form_fields`](crate::RenderConfig::form_fields) set, an
+//! AcroForm field is added at every `metadata` element labelled
+//! [`SIGNATURE_FIELD_LABEL`] or [`DATE_FIELD_LABEL`], with its lower left
+//! corner at the element. The metadata value may name the field and size
+//! it:
+//!
+//! ```typst
+//! #metadata((name: "Approver", width: 2in, height: 0.5in)) <signature-field>
+//! ```
+//!
+//! The memo template puts a signature field above the signature block and a
+//! date field to its left, and the same fields on each co-signer's block,
+//! with the date field below it.
+//!
+//! Signature fields are unsigned placeholders (`/FT /Sig` without a value)
+//! with an empty appearance, as PDF 2.0 requires of every widget, so that
+//! strict signing tools accept them and draw the signature in their place.
+
+use std::ops::Range;
+
+use typst::foundations::{Label, Selector, Value};
+use typst::introspection::MetadataElem;
+use typst::layout::PagedDocument;
+use typst::utils::PicoStr;
+
+use crate::locate::{DATE_FIELD_LABEL, SIGNATURE_FIELD_LABEL};
+use crate::pdf::{dict_value, exported_page, reference_array, text_string, with_entry, PdfUpdate};
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Default size of a signature field in points, matching a signature stamp
+const SIGNATURE_SIZE: (f64, f64) = (144.0, 40.0);
+/// Default size of a date field in points
+const DATE_SIZE: (f64, f64) = (108.0, 18.0);
+/// Display format of date fields, as the day, full month name and year of
+/// a memo date
+const DATE_FORMAT: &str = "d mmmm yyyy";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    Signature,
+    Date,
+}
+
+/// A field to add, positioned by its lower left corner
+#[derive(Debug, PartialEq)]
+struct Field {
+    kind: FieldKind,
+    name: String,
+    /// Page index, starting at 0
+    page: usize,
+    /// Lower left corner, in points from the top left corner of the page
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// The fields of `document` in document order, with unique names
+fn fields(document: &PagedDocument) -> Vec<Field> {
+    let introspector = &document.introspector;
+    let label = |name: &str| Selector::Label(Label::new(PicoStr::intern(name)));
+    let selector = Selector::Or([label(SIGNATURE_FIELD_LABEL), label(DATE_FIELD_LABEL)].into());
+    let mut fields: Vec<Field> = Vec::new();
+    for element in introspector.query(&selector).iter() {
+        let (Some(location), Some(metadata)) = (element.location(), element.to_packed::<MetadataElem>()) else {
+            continue;
+        };
+        let (kind, (width, height), default_name) = match element.label().map(|label| label.resolve()) {
+            Some(name) if name.as_str() == DATE_FIELD_LABEL => (FieldKind::Date, DATE_SIZE, "Date"),
+            _ => (FieldKind::Signature, SIGNATURE_SIZE, "Signature"),
+        };
+        let position = introspector.position(location);
+        let mut field = Field {
+            kind,
+            name: default_name.to_string(),
+            page: position.page.get() - 1,
+            x: position.point.x.to_pt(),
+            y: position.point.y.to_pt(),
+            width,
+            height,
+        };
+        match &metadata.value {
+            Value::Str(name) => field.name = name.to_string(),
+            Value::Dict(dict) => {
+                if let Ok(Value::Str(name)) = dict.get("name") {
+                    field.name = name.to_string();
+                }
+                for (key, size) in [("width", &mut field.width), ("height", &mut field.height)] {
+                    if let Ok(Value::Length(length)) = dict.get(key) {
+                        if length.abs.to_pt() > 0.0 && length.em.get() == 0.0 {
+                            *size = length.abs.to_pt();
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        // Fields with the same name are one field in PDF
+        let base = field.name.clone();
+        let mut count = 1;
+        while fields.iter().any(|other| other.name == field.name) {
+            count += 1;
+            field.name = format!("{} {}", base, count);
+        }
+        fields.push(field);
+    }
+    fields
+}
+
+/// Add the labelled signature and date fields of `document` on the pages
+/// `exported` to its exported PDF of those pages
+pub(crate) fn add_fields(document: &PagedDocument, pdf: Vec<u8>, exported: Range<usize>) -> Result<Vec<u8>, TypstWrapperError> {
+    let mut fields = fields(document);
+    fields.retain(|field| exported.contains(&field.page));
+    if fields.is_empty() {
+        return Ok(pdf);
+    }
+    let error = |e: &str| TypstWrapperError::OutputFormat(format!("PDF form fields could not be written: {}", e));
+    let mut update = PdfUpdate::parse(&pdf).map_err(|e| error(&e))?;
+    let pages = update.pages().map_err(|e| error(&e))?;
+    let root = update.root();
+    let catalog = update.object(root).ok_or_else(|| error("missing catalog"))?;
+    if dict_value(&catalog, "AcroForm").is_some() {
+        return Err(error("the PDF already has a form"));
+    }
+
+    let font = update.allocate();
+    update.set(font, "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>");
+    let mut numbers = Vec::with_capacity(fields.len());
+    for field in &fields {
+        let index = exported_page(field.page, &exported).ok_or_else(|| error("missing page"))?;
+        let page = *pages.get(index).ok_or_else(|| error("missing page"))?;
+        let (_, page_height) = update.page_size(page).ok_or_else(|| error("missing page size"))?;
+        let bottom = page_height - field.y;
+        let mut object = format!(
+            "<<\n  /Type /Annot\n  /Subtype /Widget\n  /T {}\n  /Rect [{:.3} {:.3} {:.3} {:.3}]\n  /F 4\n  /P {} 0 R\n",
+            text_string(&field.name),
+            field.x,
+            bottom,
+            field.x + field.width,
+            bottom + field.height,
+            page
+        );
+        match field.kind {
+            FieldKind::Signature => {
+                // Signers replace the empty appearance with the signature's
+                let appearance = update.allocate();
+                update.set(
+                    appearance,
+                    format!(
+                        "<< /Type /XObject /Subtype /Form /BBox [0 0 {:.3} {:.3}] /Length 0 >>\nstream\n\nendstream",
+                        field.width, field.height
+                    ),
+                );
+                object.push_str(&format!("  /FT /Sig\n  /AP << /N {} 0 R >>\n", appearance));
+            }
+            FieldKind::Date => object.push_str(&format!(
+                "  /FT /Tx\n  /DA (/Helv 10 Tf 0 g)\n  /AA <<\n    /K << /S /JavaScript /JS (AFDate_KeystrokeEx\\(\"{f}\"\\);) >>\n    \
+                 /F << /S /JavaScript /JS (AFDate_FormatEx\\(\"{f}\"\\);) >>\n  >>\n",
+                f = DATE_FORMAT
+            )),
+        }
+        object.push_str(">>");
+        let number = update.allocate();
+        update.set(number, object);
+        update.add_annotation(page, number).map_err(|e| error(&e))?;
+        numbers.push(number);
+    }
+
+    let form = update.allocate();
+    update.set(
+        form,
+        format!(
+            "<<\n  /Fields {}\n  /DA (/Helv 0 Tf 0 g)\n  /DR << /Font << /Helv {} 0 R >> >>\n  /NeedAppearances true\n>>",
+            reference_array(&numbers),
+            font
+        ),
+    );
+    let catalog = with_entry(&catalog, "AcroForm", &format!("{} 0 R", form)).ok_or_else(|| error("invalid catalog"))?;
+    update.set(root, catalog);
+    Ok(update.finish().0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{reference, references};
+    use crate::test_utils::MemoBuilder;
+    use crate::typst_wrapper::TypstWrapper;
+    use crate::{render_form, render_markup, OutputFormat, RenderConfig};
+
+    /// The form fields of an exported PDF as `(name, type)`, e.g. `("Date",
+    /// "/Tx")`
+    fn pdf_fields(pdf: &[u8]) -> Vec<(String, String)> {
+        let update = PdfUpdate::parse(pdf).unwrap();
+        let catalog = update.object(update.root()).unwrap();
+        let form = update.object(dict_value(&catalog, "AcroForm").and_then(reference).unwrap()).unwrap();
+        references(dict_value(&form, "Fields").unwrap())
+            .into_iter()
+            .map(|number| {
+                let field = update.object(number).unwrap();
+                (dict_value(&field, "T").unwrap().to_string(), dict_value(&field, "FT").unwrap().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_labelled_fields() {
+        let markup = "#set page(margin: 1in)\n#v(1in)\n#metadata(none) <signature-field>Signed\n\
+                      #metadata((name: \"Signature\", width: 3in)) <signature-field>\n#metadata(\"Signed on\") <date-field>";
+        let document = TypstWrapper::compile_markup(markup).unwrap();
+        let fields = fields(&document);
+        let names: Vec<_> = fields.iter().map(|field| (field.name.as_str(), field.kind)).collect();
+        assert_eq!(
+            names,
+            [("Signature", FieldKind::Signature), ("Signature 2", FieldKind::Signature), ("Signed on", FieldKind::Date)]
+        );
+        assert_eq!((fields[0].x, fields[0].width, fields[0].height), (72.0, 144.0, 40.0));
+        assert!(fields[0].y >= 144.0 && fields[0].y < 160.0, "{:?}", fields[0]);
+        assert_eq!(fields[1].width, 216.0);
+        assert_eq!((fields[2].width, fields[2].height), DATE_SIZE);
+    }
+
+    #[test]
+    fn test_pdf_form_fields() {
+        let markup = "#metadata(none) <signature-field>\n#metadata(none) <date-field>";
+        let config = RenderConfig { format: OutputFormat::Pdf, form_fields: true, ..RenderConfig::default() };
+        let pdf = render_markup(markup, Some(config.clone())).unwrap().remove(0);
+        assert_eq!(pdf_fields(&pdf), [("(Signature)".to_string(), "/Sig".to_string()), ("(Date)".to_string(), "/Tx".to_string())]);
+        let update = PdfUpdate::parse(&pdf).unwrap();
+        let catalog = update.object(update.root()).unwrap();
+        let form = update.object(dict_value(&catalog, "AcroForm").and_then(reference).unwrap()).unwrap();
+        let signature = update.object(references(dict_value(&form, "Fields").unwrap())[0]).unwrap();
+        assert!(dict_value(&signature, "V").is_none(), "{}", signature);
+        let appearance = dict_value(&signature, "AP").and_then(|ap| dict_value(ap, "N")).and_then(reference).unwrap();
+        assert!(update.object(appearance).unwrap().contains("/BBox [0 0 144.000 40.000]"));
+
+        let plain = RenderConfig { format: OutputFormat::Pdf, ..RenderConfig::default() };
+        let pdf = render_markup(markup, Some(plain)).unwrap().remove(0);
+        assert!(!String::from_utf8_lossy(&pdf).contains("/AcroForm"));
+
+        let form = MemoBuilder::minimal().subject("Fields");
+        let pdf = render_form(&form.to_json(), Some(config.clone())).unwrap().remove(0);
+        assert_eq!(pdf_fields(&pdf), [("(Date)".to_string(), "/Tx".to_string()), ("(Signature)".to_string(), "/Sig".to_string())]);
+
+        let form = form.co_signers([&["JOHN A. DOE, Col, USAF", "Duty Title"]]).to_json();
+        let pdf = render_form(&form, Some(config)).unwrap().remove(0);
+        let names: Vec<_> = pdf_fields(&pdf).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names.len(), 4);
+        for name in ["(Signature)", "(Signature 2)", "(Date)", "(Date 2)"] {
+            assert!(names.iter().any(|field| field == name), "{} not in {:?}", name, names);
+        }
+    }
+}
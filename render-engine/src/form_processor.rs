@@ -10,28 +10,42 @@
 //! "delta", the data is expected to be a Quill Delta JSON string and will be
 //! converted to Typst markup via `DeltaParser`.
 
+use std::sync::LazyLock;
+
+use base64::Engine as _;
 use serde::{Deserialize, Serialize};
 
-use crate::delta_parser::{DeltaParser, ParserError};
+use crate::delta_parser::{DeltaDocument, DeltaParser, ParserError};
 use serde_json::Value as JsonValue;
 use crate::assets;
 
+/// Version of the official memo schema, its top-level `version`
+pub const SCHEMA_VERSION: &str = "1.0.0";
+
+/// Largest form JSON document accepted for validation and preprocessing, in bytes
+pub const MAX_FORM_JSON_BYTES: usize = 16 * 1024 * 1024;
+
+/// Official memo schema, parsed once on first use
+static MEMO_SCHEMA: LazyLock<Result<JsonValue, String>> =
+	LazyLock::new(|| load_official_memo_schema_value().map_err(|e| e.to_string()));
+
+/// Official memo schema, compiled once on first use
+static MEMO_SCHEMA_VALIDATOR: LazyLock<Result<jsonschema::Validator, String>> = LazyLock::new(|| {
+	let schema = MEMO_SCHEMA.as_ref().map_err(Clone::clone)?;
+	jsonschema::validator_for(schema).map_err(|e| format!("Invalid schema: {}", e))
+});
+
 /// Supported content formats from the schema.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum ContentFormat {
 	/// Typst markup provided directly
+	#[default]
 	Markup,
 	/// Quill Delta JSON that will be converted to Typst markup
 	Delta,
 }
 
-impl Default for ContentFormat {
-	fn default() -> Self {
-		ContentFormat::Markup
-	}
-}
-
 /// Schema-conformant `content` object.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Content {
@@ -48,15 +62,44 @@ pub struct Content {
 /// - For `delta`, this treats `content.data` as Quill Delta JSON and converts
 ///   it to Typst markup using `DeltaParser`.
 pub fn process_content(content: &Content) -> Result<String, ParserError> {
+	process_content_with(content, &DeltaParser::new()).map(|document| document.markup)
+}
+
+/// Process a `content` object, converting Delta with `parser`
+fn process_content_with(content: &Content, parser: &DeltaParser) -> Result<DeltaDocument, ParserError> {
 	match content.format {
-		ContentFormat::Markup => Ok(content.data.clone()),
-		ContentFormat::Delta => {
-			let mut parser = DeltaParser::new();
-			parser.parse(&content.data)
-		}
+		ContentFormat::Markup => Ok(DeltaDocument { markup: content.data.clone(), ..DeltaDocument::default() }),
+		ContentFormat::Delta => parser.parse_document(&content.data),
 	}
 }
 
+/// Key of the processed form JSON holding the images a Delta body inlines
+/// as `data:` URLs, base64 encoded by the path the body reads them from
+const DELTA_IMAGES_KEY: &str = "delta-images";
+
+/// Take the images of a Delta body out of `processed` form JSON, returning
+/// the JSON without them and the images by rootless path
+pub(crate) fn take_delta_images(processed: String) -> (String, Vec<(String, Vec<u8>)>) {
+	// Most forms have none, so skip parsing them
+	if !processed.contains(DELTA_IMAGES_KEY) {
+		return (processed, Vec::new());
+	}
+	let Ok(mut value) = serde_json::from_str::<JsonValue>(&processed) else {
+		return (processed, Vec::new());
+	};
+	let Some(JsonValue::Object(images)) = value.as_object_mut().and_then(|obj| obj.remove(DELTA_IMAGES_KEY)) else {
+		return (processed, Vec::new());
+	};
+	let images = images
+		.into_iter()
+		.filter_map(|(path, data)| {
+			let data = base64::engine::general_purpose::STANDARD.decode(data.as_str()?).ok()?;
+			Some((path.trim_start_matches('/').to_string(), data))
+		})
+		.collect();
+	(value.to_string(), images)
+}
+
 /// Convenience helper to process a JSON string representing the `content`
 /// object as described in the schema.
 ///
@@ -71,45 +114,116 @@ pub fn process_content_json(json: &str) -> Result<String, ParserError> {
 
 /// Validate an incoming form JSON string against the official memorandum JSON schema.
 ///
-/// Returns Ok(()) if valid; otherwise returns an error summarizing the first few validation errors.
+/// Returns Ok(()) if valid; otherwise returns a `ParserError::Schema` with the first
+/// validation error and the JSON pointer of the value it concerns.
 pub fn validate_official_memo_schema(form_json: &str) -> Result<(), ParserError> {
-	// Obtain the schema value, with graceful fallback if the file is not strictly valid JSON.
-	let schema_json: JsonValue = load_official_memo_schema_value()?;
+	check_form_size(form_json)?;
+	let validator = memo_schema_validator()?;
 	let instance: JsonValue = serde_json::from_str(form_json)
 		.map_err(|e| ParserError::InvalidFormat(format!("Invalid form JSON: {}", e)))?;
 
-	if let Err(err) = jsonschema::validate(&schema_json, &instance) {
-		let summary = format!("{} at {}", err, err.instance_path);
-		return Err(ParserError::InvalidFormat(format!(
-			"Form JSON does not match schema: {}",
-			summary
-		)));
+	if let Err(err) = validator.validate(&instance) {
+		return Err(ParserError::Schema {
+			path: err.instance_path.to_string(),
+			message: err.to_string(),
+		});
 	}
 
 	Ok(())
 }
 
+/// Validate a form JSON string like [`validate_official_memo_schema`],
+/// returning every schema violation rather than the first, so editors can
+/// mark all invalid fields at once.
+///
+/// Forms that are too large or not JSON give that one error. An empty list
+/// means the form is valid.
+pub fn official_memo_schema_errors(form_json: &str) -> Vec<ParserError> {
+	let instance = check_form_size(form_json).and_then(|()| {
+		serde_json::from_str::<JsonValue>(form_json)
+			.map_err(|e| ParserError::InvalidFormat(format!("Invalid form JSON: {}", e)))
+	});
+	let (validator, instance) = match memo_schema_validator().and_then(|validator| instance.map(|instance| (validator, instance))) {
+		Ok(checked) => checked,
+		Err(e) => return vec![e],
+	};
+	validator
+		.iter_errors(&instance)
+		.map(|err| ParserError::Schema {
+			path: err.instance_path.to_string(),
+			message: err.to_string(),
+		})
+		.collect()
+}
+
 /// Preprocess a full form JSON string:
 /// - If `body_raw` is missing or empty, and a `body` content object is present,
-///   converts it to Typst markup and sets `body_raw`.
+///   converts it to Typst markup and sets `body_raw`. Images a Delta body
+///   inlines as `data:` URLs are kept in the JSON for the render to serve.
+/// - If `letterhead-background` is set, checks that it names a registered
+///   letterhead background.
+/// - Fills in the defaults of `page-numbering` and checks its format.
+/// - Fills in `running-header` and `running-footer` from the form fields
+///   they refer to.
+///
 /// Returns the updated JSON string.
 pub fn preprocess_form_json(form_json: &str) -> Result<String, ParserError> {
+	preprocess(form_json, &DeltaParser::new(), false)
+}
+
+/// Validate and preprocess form JSON for a [source map](crate::source_map):
+/// a Delta body is converted with source markers, and `source-map` tells
+/// the template to mark the fields it shows, with `body` naming the field
+/// the body came from.
+pub(crate) fn validate_and_preprocess_for_source_map(form_json: &str) -> Result<String, ParserError> {
+	validate_official_memo_schema(form_json)?;
+	preprocess(form_json, &DeltaParser::new().with_source_markers(), true)
+}
+
+fn preprocess(form_json: &str, parser: &DeltaParser, source_map: bool) -> Result<String, ParserError> {
+	check_form_size(form_json)?;
 	let mut input_value: JsonValue = serde_json::from_str(form_json)
 		.map_err(|e| ParserError::InvalidFormat(format!("Invalid form JSON: {}", e)))?;
 
+	if let Some(name) = input_value.get("letterhead-background").and_then(|v| v.as_str()) {
+		if !assets::letterhead_exists(name) {
+			return Err(ParserError::InvalidFormat(format!(
+				"Unknown letterhead background: '{}'",
+				name
+			)));
+		}
+	}
+
+	normalize_page_numbering(&mut input_value)?;
+	normalize_running_text(&mut input_value)?;
+
 	// Determine whether to populate body_raw
 	let should_fill_body_raw = match input_value.get("body_raw") {
 		Some(v) => v.is_null() || (v.is_string() && v.as_str().unwrap_or("").is_empty()),
 		None => true,
 	};
 
+	if source_map {
+		let body = if should_fill_body_raw { "/body" } else { "/body_raw" };
+		if let Some(obj) = input_value.as_object_mut() {
+			obj.insert("source-map".to_string(), serde_json::json!({ "body": body }));
+		}
+	}
+
 	if should_fill_body_raw {
 		if let Some(body_val) = input_value.get("body") {
 			if let Ok(content_obj) = serde_json::from_value::<Content>(body_val.clone()) {
-				match process_content(&content_obj) {
-					Ok(markup) => {
+				match process_content_with(&content_obj, parser) {
+					Ok(DeltaDocument { markup, images }) => {
 						if let Some(obj) = input_value.as_object_mut() {
 							obj.insert("body_raw".to_string(), JsonValue::String(markup));
+							if !images.is_empty() {
+								let images = images
+									.into_iter()
+									.map(|(path, data)| (path, base64::engine::general_purpose::STANDARD.encode(data).into()))
+									.collect();
+								obj.insert(DELTA_IMAGES_KEY.to_string(), JsonValue::Object(images));
+							}
 						}
 					}
 					Err(e) => {
@@ -127,6 +241,178 @@ pub fn preprocess_form_json(form_json: &str) -> Result<String, ParserError> {
 		.map_err(|e| ParserError::InvalidFormat(format!("Failed to serialize processed input: {}", e)))
 }
 
+/// Fill in the defaults the official memo schema gives for the fields a
+/// form leaves out, so clients can show the document a render produces:
+/// `letterhead-title`, `references: null`, the `format` of the body, the
+/// color and opacity of a watermark, and so on. `page-numbering: true`
+/// becomes its full settings.
+///
+/// Fields the form sets are kept and fields without a default, such as
+/// `date`, stay unset. The form is validated first, and the result renders
+/// as the form does.
+pub fn apply_defaults(form_json: &str) -> Result<String, ParserError> {
+	validate_official_memo_schema(form_json)?;
+	let schema = MEMO_SCHEMA.as_ref().map_err(|message| ParserError::InvalidFormat(message.clone()))?;
+	let mut form: JsonValue = serde_json::from_str(form_json)
+		.map_err(|e| ParserError::InvalidFormat(format!("Invalid form JSON: {}", e)))?;
+	fill_defaults(&mut form, schema, schema);
+	normalize_page_numbering(&mut form)?;
+	serde_json::to_string(&form)
+		.map_err(|e| ParserError::InvalidFormat(format!("Failed to serialize processed input: {}", e)))
+}
+
+/// Give the object `value` the defaults of the properties `schema` lists
+/// that it lacks, and its present properties their own defaults. `root`
+/// is the schema `$ref`s point into.
+fn fill_defaults(value: &mut JsonValue, schema: &JsonValue, root: &JsonValue) {
+	let schema = resolve(schema, root);
+	let Some(properties) = schema.get("properties").and_then(JsonValue::as_object) else {
+		// Settings given as an object or a shorthand, like `page-numbering`
+		let mut branches = schema.get("oneOf").and_then(JsonValue::as_array).into_iter().flatten();
+		if let Some(branch) = branches.find(|branch| resolve(branch, root).get("properties").is_some()) {
+			fill_defaults(value, branch, root);
+		}
+		return;
+	};
+	let Some(object) = value.as_object_mut() else {
+		return;
+	};
+	for (key, property) in properties {
+		match object.get_mut(key) {
+			Some(field) => fill_defaults(field, property, root),
+			None => {
+				let property = resolve(property, root);
+				// Some defaults are `null` where the field must be set when present
+				match property.get("default") {
+					Some(JsonValue::Null) if !allows_null(property, root) => {}
+					Some(default) => {
+						object.insert(key.clone(), default.clone());
+					}
+					None => {}
+				}
+			}
+		}
+	}
+}
+
+/// `schema`, or the definition it refers to with `$ref`
+fn resolve<'a>(schema: &'a JsonValue, root: &'a JsonValue) -> &'a JsonValue {
+	match schema.get("$ref").and_then(JsonValue::as_str).and_then(|pointer| pointer.strip_prefix('#')) {
+		Some(pointer) => root.pointer(pointer).unwrap_or(schema),
+		None => schema,
+	}
+}
+
+/// Whether `schema` accepts `null`
+fn allows_null(schema: &JsonValue, root: &JsonValue) -> bool {
+	let schema = resolve(schema, root);
+	let typed_null = match schema.get("type") {
+		Some(JsonValue::String(kind)) => kind == "null",
+		Some(JsonValue::Array(kinds)) => kinds.iter().any(|kind| kind == "null"),
+		_ => false,
+	};
+	let branches = schema.get("oneOf").and_then(JsonValue::as_array).into_iter().flatten();
+	typed_null || branches.into_iter().any(|branch| allows_null(branch, root))
+}
+
+/// Placeholders a `page-numbering` format may contain
+const PAGE_NUMBER_PLACEHOLDERS: [&str; 2] = ["{page}", "{total}"];
+
+/// Give `page-numbering` every setting the template reads:
+/// - `true` becomes the default numbering, `false` and `null` are kept
+/// - missing `start`, `format`, `position` and `first-page` are filled in
+/// - `format` must show `{page}` and may only use the known placeholders
+fn normalize_page_numbering(input_value: &mut JsonValue) -> Result<(), ParserError> {
+	let Some(numbering) = input_value.get_mut("page-numbering") else {
+		return Ok(());
+	};
+	if *numbering == JsonValue::Bool(true) {
+		*numbering = JsonValue::Object(Default::default());
+	}
+	let Some(settings) = numbering.as_object_mut() else {
+		return Ok(());
+	};
+
+	// Memos number pages from 2, flush right at the bottom (AFH 33-337)
+	let defaults = [
+		("start", JsonValue::from(1)),
+		("format", JsonValue::from("{page}")),
+		("position", JsonValue::from("bottom-right")),
+		("first-page", JsonValue::from(false)),
+	];
+	for (key, value) in defaults {
+		settings.entry(key).or_insert(value);
+	}
+
+	let format = settings.get("format").and_then(JsonValue::as_str).unwrap_or_default();
+	if !format.contains("{page}") {
+		return Err(ParserError::InvalidFormat(format!(
+			"Page number format '{}' does not contain {{page}}",
+			format
+		)));
+	}
+	check_placeholders(format, &PAGE_NUMBER_PLACEHOLDERS, "page number format")
+}
+
+/// Placeholders running headers and footers may contain; the template fills
+/// in the page numbers and the date
+const RUNNING_TEXT_PLACEHOLDERS: [&str; 5] = ["{page}", "{total}", "{date}", "{office-symbol}", "{subject}"];
+
+/// Prepare `running-header` and `running-footer` for the template:
+/// - `pages` defaults to `"all"`
+/// - `{office-symbol}` and `{subject}` are replaced with the first line of
+///   the from block and the subject
+/// - other placeholders must be ones the template fills in
+fn normalize_running_text(input_value: &mut JsonValue) -> Result<(), ParserError> {
+	let field = |key: &str| input_value.get(key).and_then(JsonValue::as_str).unwrap_or_default().to_string();
+	let subject = field("subject");
+	let office_symbol = input_value
+		.get("from-block")
+		.and_then(|block| block.get(0))
+		.and_then(JsonValue::as_str)
+		.unwrap_or_default()
+		.to_string();
+
+	for key in ["running-header", "running-footer"] {
+		let Some(settings) = input_value.get_mut(key).and_then(JsonValue::as_object_mut) else {
+			continue;
+		};
+		if settings.get("pages").is_none_or(JsonValue::is_null) {
+			settings.insert("pages".to_string(), JsonValue::from("all"));
+		}
+		for side in ["left", "center", "right"] {
+			let Some(text) = settings.get_mut(side) else {
+				continue;
+			};
+			let Some(value) = text.as_str() else {
+				continue;
+			};
+			check_placeholders(value, &RUNNING_TEXT_PLACEHOLDERS, key)?;
+			*text = JsonValue::from(value.replace("{office-symbol}", &office_symbol).replace("{subject}", &subject));
+		}
+	}
+	Ok(())
+}
+
+/// Check that every `{...}` in `text` is one of the `allowed` placeholders
+fn check_placeholders(text: &str, allowed: &[&str], what: &str) -> Result<(), ParserError> {
+	let mut rest = text;
+	while let Some(open) = rest.find('{') {
+		let end = rest[open..].find('}').map_or(rest.len(), |close| open + close + 1);
+		let placeholder = &rest[open..end];
+		if !allowed.contains(&placeholder) {
+			return Err(ParserError::InvalidFormat(format!(
+				"Unknown placeholder '{}' in {}: use {}",
+				placeholder,
+				what,
+				allowed.join(", ")
+			)));
+		}
+		rest = &rest[end..];
+	}
+	Ok(())
+}
+
 /// Validate the form JSON against the schema and then preprocess it.
 /// Returns the updated JSON string ready for rendering.
 pub fn validate_and_preprocess_form_json(form_json: &str) -> Result<String, ParserError> {
@@ -134,6 +420,25 @@ pub fn validate_and_preprocess_form_json(form_json: &str) -> Result<String, Pars
 	preprocess_form_json(form_json)
 }
 
+/// The compiled official memo schema
+pub(crate) fn memo_schema_validator() -> Result<&'static jsonschema::Validator, ParserError> {
+	MEMO_SCHEMA_VALIDATOR
+		.as_ref()
+		.map_err(|message| ParserError::InvalidFormat(message.clone()))
+}
+
+/// Reject form documents larger than [`MAX_FORM_JSON_BYTES`] before parsing them
+fn check_form_size(form_json: &str) -> Result<(), ParserError> {
+	if form_json.len() > MAX_FORM_JSON_BYTES {
+		return Err(ParserError::InvalidFormat(format!(
+			"Form JSON is {} bytes, exceeding the {} byte limit",
+			form_json.len(),
+			MAX_FORM_JSON_BYTES
+		)));
+	}
+	Ok(())
+}
+
 /// Attempt to load and parse the official memo schema from the repository file.
 /// Falls back to a minimal equivalent schema if parsing fails due to formatting issues
 /// (e.g., trailing commas or incomplete braces). This ensures validation can proceed.
@@ -202,5 +507,170 @@ mod tests {
 			Err(e) => panic!("Unexpected error: {:?}", e),
 		}
 	}
-}
 
+	#[test]
+	fn rejects_hostile_form_input() {
+		let oversized = format!(r#"{{"subject":"{}"}}"#, "a".repeat(MAX_FORM_JSON_BYTES));
+		assert!(validate_and_preprocess_form_json(&oversized).is_err());
+
+		let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+		assert!(validate_and_preprocess_form_json(&nested).is_err());
+
+		// A valid form whose Delta body is malformed fails cleanly
+		let bad_delta = r#"{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body":{"format":"delta","data":"{\"ops\":[{\"insert\":\"x\"},{\"insert\":\"\\n\",\"attributes\":{\"header\":99}}]}"}}"#;
+		let err = validate_and_preprocess_form_json(bad_delta).unwrap_err().to_string();
+		assert!(err.contains("Header level"), "Unexpected error: {}", err);
+	}
+
+	#[test]
+	fn normalizes_page_numbering() {
+		let form = |numbering: &str| {
+			format!(
+				r#"{{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"B","page-numbering":{}}}"#,
+				numbering
+			)
+		};
+		let numbering = |numbering: &str| -> JsonValue {
+			let processed = validate_and_preprocess_form_json(&form(numbering)).unwrap();
+			serde_json::from_str::<JsonValue>(&processed).unwrap()["page-numbering"].clone()
+		};
+
+		assert_eq!(
+			numbering("true"),
+			serde_json::json!({"start": 1, "format": "{page}", "position": "bottom-right", "first-page": false})
+		);
+		let custom = numbering(r#"{"format": "Page {page} of {total}", "position": "top-center", "start": 3}"#);
+		assert_eq!(custom["format"], "Page {page} of {total}");
+		assert_eq!(custom["position"], "top-center");
+		assert_eq!(custom["start"], 3);
+		assert_eq!(custom["first-page"], false);
+		assert_eq!(numbering("false"), JsonValue::Bool(false));
+
+		for format in [r#""Page""#, r#""{page} of {pages}""#, r#""{page} {""#] {
+			let err = validate_and_preprocess_form_json(&form(&format!(r#"{{"format": {}}}"#, format)));
+			assert!(err.is_err(), "accepted {}", format);
+		}
+		assert!(validate_and_preprocess_form_json(&form(r#"{"position": "middle"}"#)).is_err());
+	}
+
+	#[test]
+	fn fills_in_running_text() {
+		let form = |running: &str| {
+			format!(
+				r#"{{"memo-for":["X"],"from-block":["ORG/SYM","Unit"],"subject":"Budget","signature-block":["N","T"],"body_raw":"B","running-footer":{}}}"#,
+				running
+			)
+		};
+		let processed = validate_and_preprocess_form_json(&form(r#"{"left": "{office-symbol} - {subject}", "right": "{page}"}"#)).unwrap();
+		let footer = serde_json::from_str::<JsonValue>(&processed).unwrap()["running-footer"].clone();
+		assert_eq!(footer, serde_json::json!({"left": "ORG/SYM - Budget", "right": "{page}", "pages": "all"}));
+
+		assert!(validate_and_preprocess_form_json(&form(r#"{"left": "{author}"}"#)).is_err());
+		assert!(validate_and_preprocess_form_json(&form(r#"{"left": "x", "pages": "last"}"#)).is_err());
+	}
+
+	#[test]
+	fn accepts_string_and_object_references() {
+		let form = |references: &str| {
+			format!(
+				r#"{{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"B","references":{}}}"#,
+				references
+			)
+		};
+		assert!(validate_official_memo_schema(&form(r#"["AFI 33-360", {"title": "Tongue and Quill"}]"#)).is_ok());
+		assert!(validate_official_memo_schema(&form(
+			r#"[{"title": "T", "date": "2015-05-27", "link": "https://example.mil", "type": "AFH 33-337"}]"#
+		))
+		.is_ok());
+		for invalid in [r#"[{"date": "2015-05-27"}]"#, r#"[{"title": "T", "date": "May 2015"}]"#, r#"[{"title": "T", "url": "x"}]"#, "[7]"] {
+			let err = validate_official_memo_schema(&form(invalid)).unwrap_err();
+			assert!(matches!(err, ParserError::Schema { ref path, .. } if path == "/references/0"), "{} gave {:?}", invalid, err);
+		}
+	}
+
+	#[test]
+	fn reports_every_schema_error() {
+		let valid = r#"{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"B"}"#;
+		assert!(official_memo_schema_errors(valid).is_empty());
+
+		let invalid = r#"{"memo-for":[],"from-block":["A"],"subject":"","signature-block":["N","T"],"body_raw":"B"}"#;
+		let mut paths: Vec<String> = official_memo_schema_errors(invalid)
+			.into_iter()
+			.map(|err| match err {
+				ParserError::Schema { path, .. } => path,
+				err => panic!("Unexpected error: {:?}", err),
+			})
+			.collect();
+		paths.sort();
+		assert_eq!(paths, ["/memo-for", "/subject"]);
+
+		let errors = official_memo_schema_errors("{");
+		assert!(matches!(errors[..], [ParserError::InvalidFormat(_)]), "{:?}", errors);
+	}
+
+	#[test]
+	fn applies_schema_defaults() {
+		let form = r#"{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],
+			"body":{"data":"B"},"page-numbering":true,"watermark":{"text":"DRAFT"},"running-footer":{"right":"{page}"}}"#;
+		let normalized: JsonValue = serde_json::from_str(&apply_defaults(form).unwrap()).unwrap();
+		assert_eq!(normalized["letterhead-title"], "DEPARTMENT OF THE AIR FORCE");
+		assert_eq!(normalized["letterhead-background-opacity"], 0.3);
+		assert_eq!(normalized["references"], JsonValue::Null);
+		assert_eq!(normalized["draft"], false);
+		assert_eq!(normalized["body"], serde_json::json!({"format": "markup", "data": "B"}));
+		assert_eq!(normalized["watermark"], serde_json::json!({"text": "DRAFT", "color": "#808080", "opacity": 0.25}));
+		assert_eq!(normalized["page-numbering"]["position"], "bottom-right");
+		assert_eq!(normalized["running-footer"]["pages"], "all");
+		// Required fields keep their values, fields without a valid default stay unset
+		assert_eq!(normalized["subject"], "S");
+		assert!(normalized.get("date").is_none() && normalized.get("co-signers").is_none());
+		assert!(validate_official_memo_schema(&normalized.to_string()).is_ok());
+
+		assert!(apply_defaults(r#"{"subject": "S"}"#).is_err());
+	}
+
+	#[test]
+	fn schema_version_matches_schema() {
+		let schema = load_official_memo_schema_value().unwrap();
+		assert_eq!(schema["version"], SCHEMA_VERSION);
+	}
+
+	#[test]
+	fn validates_co_signer_blocks() {
+		let form = |co_signers: &str| {
+			format!(
+				r#"{{"memo-for":["X"],"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"B","co-signers":{}}}"#,
+				co_signers
+			)
+		};
+		assert!(validate_official_memo_schema(&form(r#"[["N1","T1"],["N2","T2","Org"]]"#)).is_ok());
+		for (invalid, path) in [
+			("[]", "/co-signers"),
+			(r#"[["N","T"],["N","T"],["N","T"],["N","T"]]"#, "/co-signers"),
+			(r#"[["N","T"],["N"]]"#, "/co-signers/1"),
+			(r#"[["N", 7]]"#, "/co-signers/0/1"),
+		] {
+			let err = validate_official_memo_schema(&form(invalid)).unwrap_err();
+			assert!(matches!(err, ParserError::Schema { path: ref p, .. } if p == path), "{} gave {:?}", invalid, err);
+		}
+	}
+
+	#[test]
+	fn accepts_string_and_object_recipients() {
+		let form = |recipients: &str| {
+			format!(
+				r#"{{"memo-for":{},"from-block":["A"],"subject":"S","signature-block":["N","T"],"body_raw":"B"}}"#,
+				recipients
+			)
+		};
+		assert!(validate_official_memo_schema(&form(r#"["ORG/SYMBOL", {"office-symbol": "CC"}]"#)).is_ok());
+		assert!(validate_official_memo_schema(&form(
+			r#"[{"organization": "HQ AETC", "office-symbol": "A1", "location": "JBSA-Randolph TX"}]"#
+		))
+		.is_ok());
+		for invalid in [r#"[{"location": "Maxwell AFB AL"}]"#, r#"[{"organization": ""}]"#, r#"[{"organization": "42 ABW", "name": "x"}]"#] {
+			let err = validate_official_memo_schema(&form(invalid)).unwrap_err();
+			assert!(matches!(err, ParserError::Schema { ref path, .. } if path == "/memo-for/0"), "{} gave {:?}", invalid, err);
+		}
+	}
+}
@@ -0,0 +1,351 @@
+//! Word (.docx) memo importer.
+//!
+//! Reads the paragraphs of `word/document.xml` and hands them to the same
+//! block recognition as the plain-text importer, one line per paragraph or
+//! line break. Body paragraphs keep bold, italic, underline and strikeout,
+//! Word list levels become paragraph levels, and headings become bold
+//! paragraphs. Tables, headers, footers and images are not imported.
+
+use std::io::{Cursor, Read};
+
+use roxmltree::{Document, Node};
+
+use super::text::{import_lines, is_label, label_rest, list_paragraph, paragraph_marker, TRAILING_LABELS};
+use super::{escape_markup, ImportError, ImportResult, Importer};
+
+const WORDML: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// Largest `word/document.xml` that is read, to bound memory on hostile input
+const MAX_DOCUMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// [`Importer`] for Word documents
+///
+/// # Examples
+/// ```no_run
+/// use render_engine::import::{DocxImporter, Importer};
+///
+/// let bytes = std::fs::read("memo.docx").unwrap();
+/// let result = DocxImporter.import(&bytes).unwrap();
+/// println!("{}", result.form);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DocxImporter;
+
+impl Importer for DocxImporter {
+    fn import(&self, bytes: &[u8]) -> Result<ImportResult, ImportError> {
+        let xml = read_document_xml(bytes)?;
+        let document = Document::parse(&xml).map_err(|e| ImportError::InvalidDocument(e.to_string()))?;
+        let body = document
+            .root_element()
+            .children()
+            .find(|node| is_w(node, "body"))
+            .ok_or_else(|| ImportError::InvalidDocument("word/document.xml has no body".to_string()))?;
+
+        let mut paragraphs = Vec::new();
+        let mut tables = 0;
+        collect_paragraphs(body, &mut paragraphs, &mut tables);
+
+        // One line per paragraph line; blank lines where the text importer
+        // expects a block to end
+        let mut texts: Vec<String> = Vec::new();
+        let mut owners: Vec<Option<usize>> = Vec::new();
+        for (index, paragraph) in paragraphs.iter().enumerate() {
+            let text = paragraph.text();
+            let blank = texts.last().is_some_and(|last| !last.trim().is_empty());
+            if text.trim().is_empty() || (blank && paragraph.starts_block(&text)) {
+                texts.push(String::new());
+                owners.push(None);
+            }
+            if text.trim().is_empty() {
+                continue;
+            }
+            for line in text.split('\n') {
+                texts.push(line.trim_end().to_string());
+                owners.push(Some(index));
+            }
+            // The subject is a single paragraph; the body follows directly
+            if label_rest(&text, &["subject:", "subj:"]).is_some() {
+                texts.push(String::new());
+                owners.push(None);
+            }
+        }
+
+        let lines: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let mut result = import_lines(&lines, |block| {
+            let mut owned: Vec<usize> = block.iter().filter_map(|&line| owners[line]).collect();
+            owned.dedup();
+            owned.into_iter().map(|index| paragraphs[index].markup()).collect()
+        });
+
+        let headings = paragraphs.iter().filter(|paragraph| paragraph.heading).count();
+        if headings > 0 {
+            result.report.warnings.push(format!("{} heading(s) converted to bold paragraphs", headings));
+        }
+        if tables > 0 {
+            result.report.warnings.push(format!("Skipped {} table(s)", tables));
+        }
+        Ok(result)
+    }
+}
+
+/// The text of `word/document.xml` in a .docx archive
+fn read_document_xml(bytes: &[u8]) -> Result<String, ImportError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| ImportError::InvalidDocument(format!("not a .docx file: {}", e)))?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .map_err(|_| ImportError::InvalidDocument("missing word/document.xml".to_string()))?;
+    let mut xml = String::new();
+    entry
+        .take(MAX_DOCUMENT_BYTES)
+        .read_to_string(&mut xml)
+        .map_err(|e| ImportError::InvalidDocument(format!("unreadable word/document.xml: {}", e)))?;
+    Ok(xml)
+}
+
+/// Run formatting that is carried into the memo body
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strike: bool,
+}
+
+/// A Word paragraph: its runs of text (line breaks as `\n`) and layout
+#[derive(Debug, Default)]
+struct Paragraph {
+    runs: Vec<(Format, String)>,
+    /// Word list level (`w:ilvl`) of a numbered or bulleted paragraph
+    list_level: Option<usize>,
+    heading: bool,
+}
+
+impl Paragraph {
+    fn text(&self) -> String {
+        self.runs.iter().map(|(_, text)| text.as_str()).collect()
+    }
+
+    /// Whether the paragraph starts a new block even without an empty
+    /// paragraph before it
+    fn starts_block(&self, text: &str) -> bool {
+        self.heading
+            || self.list_level.is_some()
+            || paragraph_marker(text.trim_start()).is_some()
+            || is_label(text.trim_start())
+            || label_rest(text.trim_start(), &TRAILING_LABELS).is_some()
+    }
+
+    /// Body markup of the paragraph. A typed paragraph number is dropped
+    /// like in plain-text memos; Word list levels map to paragraph levels.
+    fn markup(&self) -> String {
+        let text = self.text();
+        let trimmed = text.trim_start();
+        let (level, skip) = match paragraph_marker(trimmed) {
+            Some((level, rest)) => (level, text.len() - rest.len()),
+            None => (self.list_level.map_or(0, |level| level + 1), text.len() - trimmed.len()),
+        };
+        let markup = runs_markup(&self.runs, skip);
+        if self.heading {
+            format!("#strong[{}]", markup)
+        } else {
+            list_paragraph(level, markup)
+        }
+    }
+}
+
+/// Collect the paragraphs under `node` in document order, counting the
+/// tables that are skipped
+fn collect_paragraphs(node: Node, paragraphs: &mut Vec<Paragraph>, tables: &mut usize) {
+    for child in node.children().filter(Node::is_element) {
+        if is_w(&child, "p") {
+            paragraphs.push(read_paragraph(child));
+        } else if is_w(&child, "tbl") {
+            *tables += 1;
+        } else {
+            // Content controls and similar wrappers
+            collect_paragraphs(child, paragraphs, tables);
+        }
+    }
+}
+
+fn read_paragraph(node: Node) -> Paragraph {
+    let mut paragraph = Paragraph::default();
+    if let Some(properties) = child_w(node, "pPr") {
+        let style = child_w(properties, "pStyle").and_then(|style| attr_w(style, "val")).unwrap_or_default();
+        paragraph.heading = style.starts_with("Heading") || style == "Title";
+        paragraph.list_level = child_w(properties, "numPr").map(|numbering| {
+            child_w(numbering, "ilvl")
+                .and_then(|level| attr_w(level, "val"))
+                .and_then(|level| level.parse().ok())
+                .unwrap_or(0)
+        });
+    }
+    // Runs directly in the paragraph or in hyperlinks, insertions and
+    // fields, but not in text boxes (which hold paragraphs of their own)
+    let runs = node
+        .descendants()
+        .filter(|run| is_w(run, "r") && run.ancestors().find(|a| is_w(a, "p")) == Some(node));
+    for run in runs {
+        let format = child_w(run, "rPr").map(read_format).unwrap_or_default();
+        let mut text = String::new();
+        for part in run.children().filter(Node::is_element) {
+            match part.tag_name().name() {
+                "t" => text.push_str(part.text().unwrap_or_default()),
+                "tab" => text.push(' '),
+                "br" | "cr" => text.push('\n'),
+                "noBreakHyphen" | "softHyphen" => text.push('-'),
+                _ => {}
+            }
+        }
+        match paragraph.runs.last_mut() {
+            Some((last, previous)) if *last == format => previous.push_str(&text),
+            _ if !text.is_empty() => paragraph.runs.push((format, text)),
+            _ => {}
+        }
+    }
+    paragraph
+}
+
+/// Bold, italic, underline and strikeout of `w:rPr`
+fn read_format(properties: Node) -> Format {
+    let toggle = |name: &str| {
+        child_w(properties, name).is_some_and(|node| {
+            !matches!(attr_w(node, "val"), Some("0" | "false" | "off" | "none"))
+        })
+    };
+    Format {
+        bold: toggle("b"),
+        italic: toggle("i"),
+        underline: toggle("u"),
+        strike: toggle("strike") || toggle("dstrike"),
+    }
+}
+
+/// Typst markup of formatted runs, leaving out the first `skip` bytes of
+/// their text
+fn runs_markup(runs: &[(Format, String)], mut skip: usize) -> String {
+    let mut markup = String::new();
+    for (format, text) in runs {
+        let text = if skip >= text.len() {
+            skip -= text.len();
+            continue;
+        } else {
+            let rest = &text[skip..];
+            skip = 0;
+            rest
+        };
+        let mut segment = text.split('\n').map(escape_markup).collect::<Vec<_>>().join(" #linebreak() ");
+        if text.trim().is_empty() || *format == Format::default() {
+            // A function call directly before `.` or `(` would continue it
+            if markup.ends_with(']') && segment.starts_with(['.', '(']) {
+                markup.push(';');
+            }
+            markup.push_str(&segment);
+            continue;
+        }
+        for (on, function) in [
+            (format.bold, "strong"),
+            (format.italic, "emph"),
+            (format.underline, "underline"),
+            (format.strike, "strike"),
+        ] {
+            if on {
+                segment = format!("#{}[{}]", function, segment);
+            }
+        }
+        markup.push_str(&segment);
+    }
+    markup.trim_end().to_string()
+}
+
+fn is_w(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(WORDML)
+}
+
+fn child_w<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is_w(child, name))
+}
+
+fn attr_w<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute((WORDML, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// A minimal .docx archive with `body` as the document body XML
+    fn docx(body: &str) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("word/document.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        write!(
+            writer,
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:document xmlns:w="{}"><w:body>{}</w:body></w:document>"#,
+            WORDML, body
+        )
+        .unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn p(text: &str) -> String {
+        format!("<w:p><w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>", text)
+    }
+
+    fn list_item(level: usize, runs: &str) -> String {
+        format!(r#"<w:p><w:pPr><w:numPr><w:ilvl w:val="{}"/><w:numId w:val="1"/></w:numPr></w:pPr>{}</w:p>"#, level, runs)
+    }
+
+    fn memo() -> Vec<u8> {
+        docx(&[
+            p("MEMORANDUM FOR 123 ES/CC"),
+            p(""),
+            p("FROM: 456 ES/DO"),
+            p("1234 Example Street"),
+            p(""),
+            p("SUBJECT: Migrated Memo"),
+            r#"<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Background</w:t></w:r></w:p>"#.to_string(),
+            list_item(
+                0,
+                r#"<w:r><w:t xml:space="preserve">This is </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>important</w:t></w:r><w:r><w:rPr><w:b w:val="0"/></w:rPr><w:t>.</w:t></w:r>"#,
+            ),
+            list_item(1, r#"<w:r><w:rPr><w:i/><w:u w:val="single"/></w:rPr><w:t>See</w:t></w:r><w:r><w:t xml:space="preserve"> the #1 item.</w:t></w:r>"#),
+            p("3. A typed number costs $5."),
+            "<w:tbl><w:tr><w:tc><w:p/></w:tc></w:tr></w:tbl>".to_string(),
+            p("FIRST M. LAST, Capt, USAF"),
+            p("Director of Operations"),
+        ]
+        .concat())
+    }
+
+    #[test]
+    fn test_docx_memo_is_imported() {
+        let ImportResult { form, report } = DocxImporter.import(&memo()).unwrap();
+        assert_eq!(form["memo-for"], json!(["123 ES/CC"]));
+        assert_eq!(form["from-block"], json!(["456 ES/DO", "1234 Example Street"]));
+        assert_eq!(form["subject"], "Migrated Memo");
+        assert_eq!(
+            form["body"]["data"],
+            "#strong[Background]\n\n\
+             This is #strong[important];.\n\n\
+             + #underline[#emph[See]] the \\#1 item.\n\n\
+             A typed number costs \\$5."
+        );
+        assert_eq!(form["signature-block"], json!(["FIRST M. LAST, Capt, USAF", "Director of Operations"]));
+        assert_eq!(report.warnings, ["1 heading(s) converted to bold paragraphs", "Skipped 1 table(s)"]);
+        crate::render_form(&form.to_string(), None).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_docx_is_rejected() {
+        assert!(matches!(DocxImporter.import(b"not a zip"), Err(ImportError::InvalidDocument(_))));
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("other.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        assert!(matches!(DocxImporter.import(&bytes), Err(ImportError::InvalidDocument(e)) if e.contains("word/document.xml")));
+    }
+}
@@ -0,0 +1,120 @@
+//! Importers that turn existing memos into memo form JSON.
+//!
+//! Importers are best-effort: they return the form they could recover
+//! together with an [`ImportReport`] saying how sure they are of each field,
+//! so a UI can ask the user to review before rendering.
+//!
+//! - [`import_text`] reads pasted plain-text memos in the AFH 33-337 layout
+//! - [`DocxImporter`] (feature `docx`) reads Word documents
+//!
+//! Hosts that accept uploads pick an [`Importer`] by file type.
+
+#[cfg(feature = "docx")]
+mod docx;
+mod text;
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+#[cfg(feature = "docx")]
+pub use docx::DocxImporter;
+pub use text::import_text;
+
+/// Reads one kind of existing memo document
+pub trait Importer {
+    /// Recover memo form JSON from the document bytes
+    ///
+    /// Fails only when the bytes are not a document of this kind; missing
+    /// or unrecognized memo parts are reported in [`ImportReport`] instead.
+    fn import(&self, bytes: &[u8]) -> Result<ImportResult, ImportError>;
+}
+
+/// Errors that prevent a document from being read at all
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Input is not valid UTF-8 text")]
+    NotText,
+    #[error("Invalid document: {0}")]
+    InvalidDocument(String),
+}
+
+/// [`Importer`] for plain-text memos, see [`import_text`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextImporter;
+
+impl Importer for TextImporter {
+    fn import(&self, bytes: &[u8]) -> Result<ImportResult, ImportError> {
+        let text = std::str::from_utf8(bytes).map_err(|_| ImportError::NotText)?;
+        Ok(import_text(text))
+    }
+}
+
+/// A recovered memo form and how it was recovered
+#[derive(Debug, Clone)]
+pub struct ImportResult {
+    /// Memo form JSON, possibly missing required fields
+    pub form: JsonValue,
+    pub report: ImportReport,
+}
+
+/// Confidence of an import
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    /// Overall confidence from 0 (nothing recognized) to 1
+    pub confidence: f64,
+    /// Confidence per form field that was looked for, from 0 (not found)
+    /// to 1 (found under its expected label)
+    pub fields: BTreeMap<String, f64>,
+    /// Things the user should review, e.g. guessed or skipped sections
+    pub warnings: Vec<String>,
+}
+
+/// Escape plain text for use as Typst markup
+pub(crate) fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut line_start = true;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let special = matches!(c, '\\' | '#' | '*' | '_' | '`' | '$' | '<' | '>' | '@' | '[' | ']' | '~')
+            || (c == '/' && chars.peek() == Some(&'/'))
+            || (line_start && matches!(c, '=' | '-' | '+' | '/'));
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        // A number followed by a dot at the start of a line is an enum item
+        if line_start && c.is_ascii_digit() {
+            while let Some(&digit) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                escaped.push(digit);
+                chars.next();
+            }
+            if chars.peek() == Some(&'.') {
+                escaped.push('\\');
+            }
+        }
+        line_start = c == '\n' || (line_start && c == ' ');
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_markup() {
+        assert_eq!(escape_markup("Cost: $5 #1 *not bold*"), "Cost: \\$5 \\#1 \\*not bold\\*");
+        assert_eq!(escape_markup("see https://example.mil"), "see https:\\//example.mil");
+        assert_eq!(escape_markup("= Not a heading\n- nor a list\n12. nor enum"), "\\= Not a heading\n\\- nor a list\n12\\. nor enum");
+        assert_eq!(escape_markup("a - b + c"), "a - b + c");
+    }
+
+    #[test]
+    fn test_text_importer_rejects_binary() {
+        assert!(matches!(TextImporter.import(&[0xff, 0xfe, 0x00]), Err(ImportError::NotText)));
+        assert!(TextImporter.import(b"SUBJECT: Test").is_ok());
+    }
+}
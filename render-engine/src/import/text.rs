@@ -0,0 +1,468 @@
+//! Plain-text memo importer.
+//!
+//! Recognizes the labelled blocks of a memo pasted from a legacy document:
+//! letterhead and date, `MEMORANDUM FOR`, `FROM:`, `SUBJECT:`, optional
+//! `References:`, numbered body paragraphs and the signature block.
+//! Attachment, courtesy copy and distribution lists have no form field and
+//! are reported as skipped.
+
+use serde_json::{json, Map, Value as JsonValue};
+
+use super::{escape_markup, ImportReport, ImportResult};
+
+/// Labels that end the body; the form has no field for them
+pub(super) const TRAILING_LABELS: [&str; 5] = ["attachment", "cc:", "distribution", "courtesy cop", "atch"];
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// Import a plain-text memo, returning the best-effort form and a report
+///
+/// # Examples
+/// ```
+/// use render_engine::import::import_text;
+///
+/// let memo = "MEMORANDUM FOR 123 ES/CC\n\nFROM: 456 ES/CC\n\nSUBJECT: Test\n\n\
+///             1. First paragraph.\n\nFIRST M. LAST, Capt, USAF\nDuty Title";
+/// let result = import_text(memo);
+/// assert_eq!(result.form["subject"], "Test");
+/// assert_eq!(result.form["signature-block"][1], "Duty Title");
+/// ```
+pub fn import_text(text: &str) -> ImportResult {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    import_lines(&lines, |block| paragraphs(&block.iter().map(|&index| lines[index].trim()).collect::<Vec<_>>()))
+}
+
+/// Import a memo given as lines, with blank lines separating blocks
+///
+/// `body` turns a block of body lines (indices into `lines`) into the Typst
+/// markup of its paragraphs, so importers of formatted documents can keep
+/// the formatting the plain lines lost.
+pub(super) fn import_lines(lines: &[&str], body: impl Fn(&[usize]) -> Vec<String>) -> ImportResult {
+    let mut form = Map::new();
+    let mut report = ImportReport::default();
+
+    // Letterhead and date come before MEMORANDUM FOR
+    let memo_for_line = lines.iter().position(|line| label_rest(line, &["memorandum for"]).is_some());
+    let header = &lines[..memo_for_line.unwrap_or(0)];
+    import_header(header, &mut form, &mut report);
+
+    // Labelled blocks, in document order
+    let mut cursor = memo_for_line.unwrap_or(0);
+    for (field, labels) in [
+        ("memo-for", &["memorandum for"][..]),
+        ("from-block", &["from:", "from"][..]),
+        ("subject", &["subject:", "subj:"][..]),
+    ] {
+        let found = lines[cursor..]
+            .iter()
+            .position(|line| label_rest(line, labels).is_some())
+            .map(|offset| cursor + offset);
+        let Some(start) = found else {
+            report.fields.insert(field.to_string(), 0.0);
+            report.warnings.push(format!("No {} block found", labels[0].trim_end_matches(':').to_uppercase()));
+            continue;
+        };
+        let (block, end) = read_block(lines, start, labels);
+        cursor = end;
+        let value = if field == "subject" {
+            JsonValue::from(block.join(" "))
+        } else {
+            JsonValue::from(block.clone())
+        };
+        report.fields.insert(field.to_string(), if block.is_empty() { 0.0 } else { 1.0 });
+        if !block.is_empty() {
+            form.insert(field.to_string(), value);
+        }
+    }
+
+    // Optional references right after the subject
+    let mut body_start = cursor;
+    if let Some(start) = lines[cursor..].iter().position(|line| !line.trim().is_empty()) {
+        let start = cursor + start;
+        if label_rest(lines[start], &["references:", "reference:"]).is_some() {
+            let (block, end) = read_block(lines, start, &["references:", "reference:"]);
+            let references = split_references(&block);
+            report.fields.insert("references".to_string(), 1.0);
+            form.insert("references".to_string(), JsonValue::from(references));
+            body_start = end;
+        }
+    }
+
+    import_body_and_signature(lines, body_start, body, &mut form, &mut report);
+
+    let required = ["memo-for", "from-block", "subject", "signature-block", "body"];
+    report.confidence = required.iter().map(|field| report.fields.get(*field).copied().unwrap_or(0.0)).sum::<f64>()
+        / required.len() as f64;
+    ImportResult {
+        form: JsonValue::Object(form),
+        report,
+    }
+}
+
+/// Take the letterhead title and caption and the date from the lines above
+/// MEMORANDUM FOR
+fn import_header(header: &[&str], form: &mut Map<String, JsonValue>, report: &mut ImportReport) {
+    let mut letterhead = Vec::new();
+    for line in header.iter().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if let Some(date) = parse_date(line) {
+            form.insert("date".to_string(), JsonValue::from(date));
+            report.fields.insert("date".to_string(), 1.0);
+        } else if is_mostly_uppercase(line) {
+            letterhead.push(line);
+        }
+    }
+    if let Some(title) = letterhead.first() {
+        form.insert("letterhead-title".to_string(), JsonValue::from(*title));
+        report.fields.insert("letterhead-title".to_string(), 0.5);
+    }
+    if let Some(caption) = letterhead.get(1) {
+        form.insert("letterhead-caption".to_string(), JsonValue::from(*caption));
+        report.fields.insert("letterhead-caption".to_string(), 0.5);
+    }
+    if letterhead.len() > 2 {
+        report.warnings.push(format!("Skipped letterhead lines: {}", letterhead[2..].join(" / ")));
+    }
+}
+
+/// Split the lines from `body_start` on into body paragraphs, the
+/// signature block and trailing sections
+fn import_body_and_signature(
+    lines: &[&str],
+    body_start: usize,
+    body: impl Fn(&[usize]) -> Vec<String>,
+    form: &mut Map<String, JsonValue>,
+    report: &mut ImportReport,
+) {
+    let text = |index: usize| lines[index].trim();
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    let mut current = Vec::new();
+    for index in body_start..lines.len() {
+        if !text(index).is_empty() {
+            current.push(index);
+        } else if !current.is_empty() {
+            blocks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+
+    if let Some(index) = blocks.iter().position(|block| label_rest(text(block[0]), &TRAILING_LABELS).is_some()) {
+        for block in blocks.drain(index..) {
+            report.warnings.push(format!("Skipped trailing section: {}", text(block[0])));
+        }
+    }
+
+    // The signature is the last block, or the last lines of the last block
+    // when nothing separates it from the body
+    let last: Vec<&str> = blocks.last().map(|block| block.iter().map(|&i| text(i)).collect()).unwrap_or_default();
+    let signature_len = if !last.is_empty() && is_signature(&last) {
+        Some(last.len())
+    } else {
+        (2..=4.min(last.len().saturating_sub(1)))
+            .rev()
+            .find(|&len| {
+                let tail = &last[last.len() - len..];
+                tail[0].contains(", ") && is_signature(tail)
+            })
+    };
+    match signature_len {
+        Some(len) => {
+            let signature = last[last.len() - len..].to_vec();
+            if let Some(block) = blocks.last_mut() {
+                block.truncate(block.len() - len);
+                if block.is_empty() {
+                    blocks.pop();
+                }
+            }
+            let confidence = if signature.len() >= 2 { 0.75 } else { 0.5 };
+            if signature.len() < 2 {
+                report.warnings.push("Signature block has a single line; add the duty title".to_string());
+            }
+            report.fields.insert("signature-block".to_string(), confidence);
+            form.insert("signature-block".to_string(), JsonValue::from(signature));
+        }
+        None => {
+            report.fields.insert("signature-block".to_string(), 0.0);
+            report.warnings.push("No signature block found after the body".to_string());
+        }
+    }
+
+    let paragraphs: Vec<String> = blocks.iter().flat_map(|block| body(block)).collect();
+    report.fields.insert("body".to_string(), if paragraphs.is_empty() { 0.0 } else { 1.0 });
+    if paragraphs.is_empty() {
+        report.warnings.push("No body paragraphs found".to_string());
+    } else {
+        form.insert("body".to_string(), json!({ "format": "markup", "data": paragraphs.join("\n\n") }));
+    }
+}
+
+/// The Typst markup of each paragraph in a block. Numbered paragraphs lose
+/// their numbers (the template numbers them); lettered and parenthesized
+/// subparagraphs become nested list items.
+fn paragraphs(block: &[&str]) -> Vec<String> {
+    let mut items: Vec<(usize, String)> = Vec::new();
+    for line in block {
+        match paragraph_marker(line) {
+            Some((level, text)) => items.push((level, text.to_string())),
+            None => match items.last_mut() {
+                Some((_, text)) => {
+                    text.push(' ');
+                    text.push_str(line);
+                }
+                None => items.push((0, line.to_string())),
+            },
+        }
+    }
+    items
+        .into_iter()
+        .map(|(level, text)| list_paragraph(level, escape_markup(&text)))
+        .collect()
+}
+
+/// Body markup of a paragraph at `level` (see [`paragraph_marker`]):
+/// numbered paragraphs are plain, deeper levels nested list items
+pub(super) fn list_paragraph(level: usize, markup: String) -> String {
+    match level {
+        0 | 1 => markup,
+        level => format!("{}+ {}", "  ".repeat(level - 2), markup),
+    }
+}
+
+/// Split a numbered paragraph marker off `line`: `1.` is level 1, `a.` level
+/// 2, `(1)` level 3 and `(a)` level 4
+pub(super) fn paragraph_marker(line: &str) -> Option<(usize, &str)> {
+    let (marker, text) = line.split_once(' ')?;
+    let level = if let Some(inner) = marker.strip_prefix('(').and_then(|m| m.strip_suffix(')')) {
+        if inner.chars().all(|c| c.is_ascii_digit()) && !inner.is_empty() {
+            3
+        } else if inner.len() == 1 && inner.chars().all(|c| c.is_ascii_lowercase()) {
+            4
+        } else {
+            return None;
+        }
+    } else {
+        let inner = marker.strip_suffix('.')?;
+        if inner.chars().all(|c| c.is_ascii_digit()) && !inner.is_empty() && inner.len() <= 2 {
+            1
+        } else if inner.len() == 1 && inner.chars().all(|c| c.is_ascii_lowercase()) {
+            2
+        } else {
+            return None;
+        }
+    };
+    Some((level, text.trim_start()))
+}
+
+/// Whether a final block looks like a signature block: a few short lines
+/// starting with an uppercase name and no paragraph number
+fn is_signature(block: &[&str]) -> bool {
+    block.len() <= 5
+        && paragraph_marker(block[0]).is_none()
+        && is_mostly_uppercase(block[0])
+        && block.iter().all(|line| line.len() <= 80)
+}
+
+/// The lines of the block labelled at `lines[start]`: the text after the
+/// label, then the following lines up to a blank line or the next label
+fn read_block(lines: &[&str], start: usize, labels: &[&str]) -> (Vec<String>, usize) {
+    let mut block = Vec::new();
+    let first = label_rest(lines[start], labels).unwrap_or_default();
+    if !first.is_empty() {
+        block.push(first.to_string());
+    }
+    let mut end = start + 1;
+    while let Some(line) = lines.get(end) {
+        let line = line.trim();
+        if line.is_empty() && !block.is_empty() {
+            break;
+        }
+        if is_label(line) {
+            break;
+        }
+        if !line.is_empty() {
+            block.push(line.to_string());
+        }
+        end += 1;
+    }
+    (block, end)
+}
+
+/// Whether `line` starts any block label
+pub(super) fn is_label(line: &str) -> bool {
+    label_rest(
+        line,
+        &["memorandum for", "from:", "subject:", "subj:", "references:", "reference:"],
+    )
+    .is_some()
+}
+
+/// The text after a label at the start of `line` (case-insensitive), with
+/// any colon and surrounding whitespace removed
+pub(super) fn label_rest<'a>(line: &'a str, labels: &[&str]) -> Option<&'a str> {
+    let trimmed = line.trim_start();
+    labels.iter().find_map(|label| {
+        let head = trimmed.get(..label.len())?;
+        if !head.eq_ignore_ascii_case(label) {
+            return None;
+        }
+        let rest = &trimmed[label.len()..];
+        // `FROM` without a colon must be a whole word
+        if !label.ends_with(':') && rest.starts_with(|c: char| c.is_alphanumeric()) {
+            return None;
+        }
+        Some(rest.trim_start_matches(':').trim())
+    })
+}
+
+/// Group `(a) ...` reference lines with their continuation lines
+fn split_references(block: &[String]) -> Vec<String> {
+    let mut references: Vec<String> = Vec::new();
+    for line in block {
+        let starts_reference = line.starts_with('(') && line.find(')').is_some_and(|end| end <= 3);
+        match references.last_mut() {
+            Some(last) if !starts_reference => {
+                last.push(' ');
+                last.push_str(line);
+            }
+            _ => references.push(line.to_string()),
+        }
+    }
+    references
+        .into_iter()
+        .map(|reference| match reference.split_once(") ") {
+            Some((marker, text)) if marker.starts_with('(') && marker.len() <= 3 => text.to_string(),
+            _ => reference,
+        })
+        .collect()
+}
+
+/// Whether most letters of `line` are uppercase
+fn is_mostly_uppercase(line: &str) -> bool {
+    let letters: Vec<char> = line.chars().filter(|c| c.is_alphabetic()).collect();
+    !letters.is_empty() && letters.iter().filter(|c| c.is_uppercase()).count() * 10 >= letters.len() * 6
+}
+
+/// Parse `9 September 2025`, `September 9, 2025`, `9 Sep 25` or
+/// `2025-09-09` into `YYYY-MM-DD`
+fn parse_date(line: &str) -> Option<String> {
+    let words: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let month = |word: &str| {
+        let word = word.trim_end_matches('.').to_lowercase();
+        (word.len() >= 3)
+            .then(|| MONTHS.iter().position(|month| month.starts_with(&word)))
+            .flatten()
+            .map(|index| index as u32 + 1)
+    };
+    let number = |word: &str| word.parse::<u32>().ok();
+    let (year, month, day) = match words[..] {
+        [iso] => {
+            let mut parts = iso.split('-').map(number);
+            (parts.next()??, parts.next()??, parts.next()??)
+        }
+        [day, m, year] if number(day).is_some() => (number(year)?, month(m)?, number(day)?),
+        [m, day, year] => (number(year)?, month(m)?, number(day)?),
+        _ => return None,
+    };
+    let year = if year < 100 { 2000 + year } else { year };
+    ((1..=12).contains(&month) && (1..=31).contains(&day) && (1900..=2999).contains(&year))
+        .then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY_MEMO: &str = "\
+                    DEPARTMENT OF THE AIR FORCE
+                  123RD EXAMPLE SQUADRON (ACC)
+
+                                                  9 September 2025
+
+MEMORANDUM FOR 123 ES/CC
+               456 ES/CC
+
+FROM:  123 ES/DO
+       1234 Example Street
+       Base AFB ST 12345-6789
+
+SUBJECT: Format for the Official
+Memorandum
+
+References: (a) AFH 33-337, The Tongue and Quill,
+    27 May 2015
+(b) DAFI 33-360, Publications and Forms Management
+
+1. This is the first paragraph. It wraps onto
+a second line.
+
+2. Subparagraphs follow:
+a. First subparagraph costs $5.
+(1) Deeper still.
+
+FIRST M. LAST, Capt, USAF
+Director of Operations
+
+Attachment:
+1. Example Attachment
+
+cc:
+789 ES/CC
+";
+
+    #[test]
+    fn test_legacy_memo_is_imported() {
+        let ImportResult { form, report } = import_text(LEGACY_MEMO);
+        assert_eq!(form["letterhead-title"], "DEPARTMENT OF THE AIR FORCE");
+        assert_eq!(form["letterhead-caption"], "123RD EXAMPLE SQUADRON (ACC)");
+        assert_eq!(form["date"], "2025-09-09");
+        assert_eq!(form["memo-for"], json!(["123 ES/CC", "456 ES/CC"]));
+        assert_eq!(form["from-block"], json!(["123 ES/DO", "1234 Example Street", "Base AFB ST 12345-6789"]));
+        assert_eq!(form["subject"], "Format for the Official Memorandum");
+        assert_eq!(
+            form["references"],
+            json!(["AFH 33-337, The Tongue and Quill, 27 May 2015", "DAFI 33-360, Publications and Forms Management"])
+        );
+        assert_eq!(
+            form["body"]["data"],
+            "This is the first paragraph. It wraps onto a second line.\n\n\
+             Subparagraphs follow:\n\n\
+             + First subparagraph costs \\$5.\n\n  \
+             + Deeper still."
+        );
+        assert_eq!(form["signature-block"], json!(["FIRST M. LAST, Capt, USAF", "Director of Operations"]));
+        assert_eq!(report.warnings, ["Skipped trailing section: Attachment:", "Skipped trailing section: cc:"]);
+        assert!(report.confidence > 0.9);
+    }
+
+    #[test]
+    fn test_imported_memo_renders() {
+        let form = import_text(LEGACY_MEMO).form.to_string();
+        crate::render_form(&form, None).unwrap();
+    }
+
+    #[test]
+    fn test_missing_blocks_lower_confidence() {
+        let ImportResult { form, report } = import_text("Just some notes\nwithout any structure.");
+        assert!(form.get("subject").is_none());
+        assert_eq!(report.fields["subject"], 0.0);
+        assert!(report.confidence < 0.5);
+        assert!(report.warnings.iter().any(|w| w == "No SUBJECT block found"));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("9 September 2025").as_deref(), Some("2025-09-09"));
+        assert_eq!(parse_date("Sept. 9, 2025").as_deref(), Some("2025-09-09"));
+        assert_eq!(parse_date("15 Jan 24").as_deref(), Some("2024-01-15"));
+        assert_eq!(parse_date("2025-09-09").as_deref(), Some("2025-09-09"));
+        assert_eq!(parse_date("123 ES/CC"), None);
+    }
+}
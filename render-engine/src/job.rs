@@ -0,0 +1,284 @@
+//! Renders split into short steps.
+//!
+//! Hosts with a CPU-time budget per event-loop turn (Cloudflare Workers, Deno
+//! Deploy) or a UI thread to keep responsive cannot afford one long blocking
+//! render. A [`RenderJob`] performs the same work as
+//! [`render_markup_with_metrics`](crate::render_markup_with_metrics) and
+//! [`render_form_with_metrics`](crate::render_form_with_metrics), but one stage
+//! at a time: preparing the sources, compiling, then exporting one SVG page
+//! per step (PDF and SVG ZIP output are exported in a single step). The host
+//! yields to its event loop between calls to [`RenderJob::step`].
+//!
+//! Typst compilation itself cannot be interrupted, so the compile step is the
+//! longest one; prewarming the engine keeps it short.
+
+use std::sync::atomic::Ordering;
+
+use tracing::debug;
+use typst::layout::PagedDocument;
+use web_time::Instant;
+
+use crate::engine::{self, RenderEngine};
+use crate::fonts::FontSubstitution;
+use crate::metrics::{self, PageSize, RenderMetrics, RenderOutput};
+use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWorld, TypstWrapper, TypstWrapperError};
+use crate::{annotations, color_mode, duplex, embedded_form, metadata, print, running, style, svg_ids, svg_scale, svg_stack, today, watermark};
+
+/// What a job renders
+enum Input {
+    Markup(String),
+    Form(String),
+}
+
+/// The work the next step performs
+enum Stage {
+    Prepare(Input),
+    Compile(Box<TypstWorld>),
+    Export { document: Box<PagedDocument>, next: usize },
+    Done,
+}
+
+/// A render performed in steps, see the [module documentation](self)
+///
+/// # Examples
+/// ```
+/// use render_engine::RenderJob;
+///
+/// let mut job = RenderJob::markup("= One\n#pagebreak()\n= Two", None);
+/// let output = loop {
+///     if let Some(output) = job.step().unwrap() {
+///         break output;
+///     }
+///     // Yield to the event loop here
+/// };
+/// assert_eq!(output.pages.len(), 2);
+/// assert!(job.is_finished());
+/// ```
+pub struct RenderJob {
+    /// The engine current when the job was created, which every step uses
+    engine: RenderEngine,
+    stage: Stage,
+    config: RenderConfig,
+    started: Instant,
+    pages: Vec<Vec<u8>>,
+    page_sizes: Vec<PageSize>,
+    font_substitutions: Vec<FontSubstitution>,
+    /// Form JSON to attach to PDF output, see [`RenderConfig::embed_form`]
+    source_form: Option<String>,
+    metrics: RenderMetrics,
+}
+
+impl RenderJob {
+    /// Prepare a render of Typst markup
+    pub fn markup(markup: &str, config: Option<RenderConfig>) -> Self {
+        Self::new(Input::Markup(markup.to_string()), config)
+    }
+
+    /// Prepare a render of a memo form (JSON) with the memo-loader template
+    pub fn form(json_input: &str, config: Option<RenderConfig>) -> Self {
+        Self::new(Input::Form(json_input.to_string()), config)
+    }
+
+    fn new(input: Input, config: Option<RenderConfig>) -> Self {
+        Self {
+            engine: engine::current(),
+            stage: Stage::Prepare(input),
+            config: config.unwrap_or_default(),
+            started: Instant::now(),
+            pages: Vec::new(),
+            page_sizes: Vec::new(),
+            font_substitutions: Vec::new(),
+            source_form: None,
+            metrics: RenderMetrics::default(),
+        }
+    }
+
+    /// Whether the job has produced its output or failed
+    pub fn is_finished(&self) -> bool {
+        matches!(self.stage, Stage::Done)
+    }
+
+    /// Perform the next step of the render
+    ///
+    /// Returns `Ok(None)` while there is work left and `Ok(Some(output))`
+    /// once the render is complete. `total_ms` in the metrics includes the
+    /// time the host spent between steps.
+    ///
+    /// # Panics
+    /// If called again after the job returned its output or an error.
+    pub fn step(&mut self) -> Result<Option<RenderOutput>, TypstWrapperError> {
+        let engine = self.engine.clone();
+        let result = engine.scope(|| self.advance());
+        if result.is_err() {
+            self.stage = Stage::Done;
+        }
+        result
+    }
+
+    fn advance(&mut self) -> Result<Option<RenderOutput>, TypstWrapperError> {
+        match std::mem::replace(&mut self.stage, Stage::Done) {
+            Stage::Prepare(input) => {
+                let started = Instant::now();
+                let mut world = match input {
+                    Input::Markup(markup) => TypstWrapper::markup_world(&markup),
+                    Input::Form(json_input) => {
+                        let json_input = running::move_into_form(&json_input, &mut self.config)?;
+                        let json_input = style::move_into_form(&json_input, &mut self.config)?;
+                        let processed_input = TypstWrapper::validate_form(&json_input)?;
+                        if self.config.watermark.is_none() {
+                            self.config.watermark = watermark::form_watermark(&processed_input)?;
+                        }
+                        self.config.metadata = metadata::form_metadata(&processed_input, self.config.metadata.take())?;
+                        if self.config.today.is_none() {
+                            self.config.today = today::form_today(&processed_input);
+                        }
+                        if self.config.embed_form {
+                            self.source_form = Some(embedded_form::canonical_form(&json_input)?);
+                        }
+                        TypstWrapper::form_world(processed_input)?
+                    }
+                };
+                svg_scale::validate(self.config.scale)?;
+                self.config.id_prefix.as_deref().map(svg_ids::validate).transpose()?;
+                world.configure(&self.config)?;
+                self.metrics.parse_ms = metrics::elapsed_ms(started);
+                self.stage = Stage::Compile(Box::new(world));
+                Ok(None)
+            }
+            Stage::Compile(world) => {
+                let started = Instant::now();
+                let mut document = TypstWrapper::compile(&world, &mut ())?;
+                if self.config.header.is_some() || self.config.footer.is_some() {
+                    running::apply(&mut document, &self.config)?;
+                }
+                if let Some(watermark) = &self.config.watermark {
+                    watermark::apply(&mut document, watermark, self.config.print.is_some())?;
+                }
+                if self.config.duplex {
+                    duplex::insert_blank_pages(&mut document);
+                }
+                color_mode::apply(&mut document, self.config.color_mode)?;
+                if let Some(metadata) = &self.config.metadata {
+                    metadata::apply(&mut document, metadata)?;
+                }
+                print::prepare(&mut document, &self.config)?;
+                annotations::check(&document, &self.config.annotations)?;
+                self.metrics.compile_ms = metrics::elapsed_ms(started);
+                self.metrics.pages = document.pages.len();
+                self.page_sizes = metrics::page_sizes(&document);
+                self.font_substitutions = world.font_substitutions(&document);
+                self.metrics.cache_hits = world.cache_hits.load(Ordering::Relaxed);
+                self.metrics.cache_misses = world.cache_misses.load(Ordering::Relaxed);
+                let next = TypstWrapper::exported_pages(&document, &self.config)?.start;
+                self.stage = Stage::Export { document: Box::new(document), next };
+                Ok(None)
+            }
+            Stage::Export { document, next } => {
+                let started = Instant::now();
+                let range = TypstWrapper::exported_pages(&document, &self.config)?;
+                match self.config.format {
+                    OutputFormat::Svg => {
+                        if next >= range.end {
+                            return Err(TypstWrapperError::Compilation("No pages to render".to_string()));
+                        }
+                        self.pages.push(TypstWrapper::page_svg(&document, next, &self.config));
+                    }
+                    OutputFormat::Pdf | OutputFormat::SvgZip => {
+                        self.pages = TypstWrapper::export(&document, &self.config, self.source_form.as_deref(), None, &mut ())?;
+                    }
+                }
+                self.metrics.export_ms += metrics::elapsed_ms(started);
+                if matches!(self.config.format, OutputFormat::Svg) && next + 1 < range.end {
+                    self.stage = Stage::Export { document, next: next + 1 };
+                    return Ok(None);
+                }
+                if matches!(self.config.format, OutputFormat::Svg) && self.config.stack_pages {
+                    let sizes = &self.page_sizes[range.clone()];
+                    let id_prefix = self.config.id_prefix.as_deref().unwrap_or_default();
+                    self.pages = vec![svg_stack::stack(&self.pages, sizes, range.start, self.config.scale, id_prefix)];
+                }
+                Ok(Some(self.complete()))
+            }
+            Stage::Done => panic!("RenderJob::step called after the job finished"),
+        }
+    }
+
+    /// Assemble the output once every page is exported
+    fn complete(&mut self) -> RenderOutput {
+        let mut metrics = std::mem::take(&mut self.metrics);
+        metrics.total_ms = metrics::elapsed_ms(self.started);
+        metrics.bytes = self.pages.iter().map(Vec::len).sum();
+        debug!(?metrics, "render job finished");
+        metrics::report(&metrics);
+        RenderOutput {
+            pages: std::mem::take(&mut self.pages),
+            page_sizes: std::mem::take(&mut self.page_sizes),
+            font_substitutions: std::mem::take(&mut self.font_substitutions),
+            metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Step `job` to completion, counting the steps taken
+    fn run(job: &mut RenderJob) -> (Result<RenderOutput, TypstWrapperError>, usize) {
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            match job.step() {
+                Ok(None) => continue,
+                Ok(Some(output)) => return (Ok(output), steps),
+                Err(e) => return (Err(e), steps),
+            }
+        }
+    }
+
+    #[test]
+    fn test_svg_pages_are_exported_one_per_step() {
+        let markup = "= One\n#pagebreak()\n= Two\n#pagebreak()\n= Three";
+        let (output, steps) = run(&mut RenderJob::markup(markup, None));
+        let output = output.unwrap();
+        assert_eq!(steps, 5);
+        assert_eq!(output.metrics.pages, 3);
+        assert_eq!(output.pages, crate::render_markup(markup, None).unwrap());
+    }
+
+    #[test]
+    fn test_page_ranges_are_exported_one_per_step() {
+        let markup = "= One\n#pagebreak()\n= Two\n#pagebreak()\n= Three";
+        let config = RenderConfig { page_range: Some(1..2), ..Default::default() };
+        let (output, steps) = run(&mut RenderJob::markup(markup, Some(config.clone())));
+        assert_eq!(steps, 3);
+        assert_eq!(output.unwrap().pages, crate::render_markup(markup, Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_stacked_pages_are_exported_one_per_step() {
+        let markup = "= One\n#pagebreak()\n= Two";
+        let config = RenderConfig { stack_pages: true, ..Default::default() };
+        let (output, steps) = run(&mut RenderJob::markup(markup, Some(config.clone())));
+        assert_eq!(steps, 4);
+        assert_eq!(output.unwrap().pages, crate::render_markup(markup, Some(config)).unwrap());
+    }
+
+    #[test]
+    fn test_pdf_is_exported_in_one_step() {
+        let config = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
+        let (output, steps) = run(&mut RenderJob::markup("= PDF job", Some(config)));
+        let output = output.unwrap();
+        assert_eq!(steps, 3);
+        assert!(output.pages[0].starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_errors_finish_the_job() {
+        let mut job = RenderJob::form("{not json", None);
+        let (result, steps) = run(&mut job);
+        assert!(matches!(result, Err(TypstWrapperError::Validation(_))));
+        assert_eq!(steps, 1);
+        assert!(job.is_finished());
+    }
+}
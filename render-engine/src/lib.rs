@@ -11,12 +11,258 @@ pub use typst_wrapper::{
 
 // Re-export parser types
 pub use delta_parser::{
+    DeltaDocument,
     DeltaParser,
     ParserError,
 };
 
+mod alt_text;
+mod archive;
+mod attribution;
+mod bookmarks;
+mod draft;
+mod duplex;
+mod excerpt;
+mod form_fields;
+mod links;
+mod pdf;
+mod resolver;
+mod svg_cache;
+mod svg_ids;
+mod svg_scale;
+mod svg_stack;
+mod woff;
+pub mod annotations;
+pub mod artifact;
 pub mod assets;
-pub mod macros;
+pub mod batch;
+pub mod cache;
+pub mod capabilities;
+pub mod color_mode;
+pub mod config;
+pub mod diagnostics;
+pub mod embedded_form;
+pub mod engine;
+pub mod error_payload;
+pub mod eml;
+pub mod fonts;
+pub mod import;
+pub mod job;
+pub mod locate;
+pub mod metadata;
+pub mod metrics;
+pub mod observer;
+pub mod packages;
+pub mod print;
+pub mod queue;
+pub mod running;
+pub mod session;
+pub mod snapshot;
+pub mod source_map;
+pub mod style;
+pub mod svg_text;
+pub mod template;
+pub mod text_layout;
+pub mod today;
+pub mod visual_diff;
+pub mod watermark;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "regression")]
+pub mod regression;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+// Re-export diagnostics types
+pub use diagnostics::{
+    Diagnostic,
+    Severity,
+};
+
+// Re-export metrics types
+pub use metrics::{
+    set_metrics_callback,
+    clear_metrics_callback,
+    PageSize,
+    RenderMetrics,
+    RenderOutput,
+};
+
+// Re-export host clock types
+pub use today::{
+    set_clock,
+    clear_clock,
+    HostTime,
+};
+
+// Re-export template artifact functions
+pub use artifact::{
+    export_template_artifact,
+    import_template_artifact,
+};
+
+// Re-export engine snapshot functions
+pub use snapshot::{
+    engine_snapshot,
+    restore_engine_snapshot,
+    RestoredState,
+};
+
+// Re-export batch rendering types
+pub use batch::{
+    render_batch,
+    FormJob,
+};
+
+// Re-export email output types
+pub use eml::{
+    render_form_eml,
+    EmlOptions,
+};
+
+// Re-export cache types
+pub use cache::{
+    set_cache_provider,
+    clear_cache_provider,
+    hash_form_render,
+    hash_markup_render,
+    set_output_cache_limits,
+    clear_output_cache,
+    CacheProvider,
+    OutputCacheLimits,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use cache::DiskCache;
+
+// Re-export the host file resolver hook
+pub use resolver::{clear_file_resolver, set_file_resolver, FileResolver};
+
+// Re-export font registration and substitution types
+pub use fonts::{list_fonts, register_font, registered_font_families, unknown_font_families, unregister_font, FontFace, FontFamily, FontSubstitution};
+
+// Re-export local package functions
+pub use packages::{
+    register_local_package,
+    unregister_local_package,
+};
+
+// Re-export memo template functions
+pub use template::{
+    reset_memo_template,
+    reset_template_partial,
+    set_memo_template,
+    set_template_partial,
+    Partial,
+};
+
+// Re-export engine instance types
+pub use engine::RenderEngine;
+
+// Re-export lifecycle hook types
+pub use observer::{CancelToken, RenderObserver};
+
+// Re-export stepped render types
+pub use job::RenderJob;
+
+// Re-export warm render session types
+pub use session::{render_markup_diff, render_markup_diff_observed, RenderDiff, RenderSession};
+
+// Re-export render queue types
+pub use queue::{
+    Priority,
+    QueueEvent,
+    RenderQueue,
+    Ticket,
+};
+
+// Re-export watermark types
+pub use watermark::Watermark;
+
+// Re-export running header and footer types
+pub use running::{
+    PageSelection,
+    RunningText,
+};
+
+// Re-export style token types
+pub use style::{
+    AccentTarget,
+    StyleConfig,
+};
+
+// Re-export capability report types
+pub use capabilities::{
+    capabilities,
+    Capabilities,
+    Features,
+};
+
+// Re-export color mode types
+pub use color_mode::ColorMode;
+
+// Re-export SVG text mode types
+pub use svg_text::SvgTextMode;
+
+// Re-export the config builder
+pub use config::RenderConfigBuilder;
+
+// Re-export the serialized error type
+pub use error_payload::ErrorPayload;
+
+// Re-export print profile types
+pub use print::PrintProfile;
+
+// Re-export document metadata types
+pub use metadata::DocumentMetadata;
+
+// Re-export text layout types
+pub use text_layout::{
+    form_text_layout,
+    markup_text_layout,
+    PageText,
+    TextRun,
+};
+
+// Re-export source map types
+pub use source_map::{
+    form_source_map,
+    PageSourceMap,
+    SourceRegion,
+};
+
+// Re-export review annotation types
+pub use annotations::{
+    Annotation,
+    AnnotationRect,
+};
+
+// Re-export visual diff types
+pub use visual_diff::{
+    diff_forms,
+    diff_markup,
+    DiffRegion,
+    PageChanges,
+    VisualDiff,
+};
+
+// Re-export element location types
+pub use locate::{
+    locate_form_label,
+    locate_markup_label,
+    ElementPosition,
+};
+
+// Re-export runtime registration limit types
+pub use assets::{
+    RegistrationError,
+    RegistrationKind,
+    RegistrationLimits,
+};
+
+// Re-export the removal and listing of runtime-registered files
+pub use assets::{registered_asset_paths, unregister_asset};
 
 /// Render Typst markup to bytes (returns array of pages for SVG, single item for PDF)
 /// 
@@ -45,7 +291,7 @@ pub mod macros;
 /// let svg_pages = render_markup(markup, None).unwrap();
 /// 
 /// // Render as PDF
-/// let config = RenderConfig { format: OutputFormat::Pdf };
+/// let config = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
 /// let pdf = render_markup(markup, Some(config)).unwrap();
 /// ```
 pub fn render_markup(
@@ -55,6 +301,57 @@ pub fn render_markup(
     typst_wrapper::TypstWrapper::render_markup(markup, config)
 }
 
+/// Render Typst markup and report how the render went
+/// 
+/// Same as [`render_markup`], but also returns the [`RenderMetrics`] of the
+/// render (stage timings, page count, output size and cache usage).
+/// 
+/// # Examples
+/// ```
+/// use render_engine::render_markup_with_metrics;
+/// 
+/// let output = render_markup_with_metrics("= Hello", None).unwrap();
+/// assert_eq!(output.metrics.pages, output.pages.len());
+/// println!("compiled in {:.1} ms", output.metrics.compile_ms);
+/// ```
+pub fn render_markup_with_metrics(
+    markup: &str,
+    config: Option<RenderConfig>,
+) -> Result<RenderOutput, TypstWrapperError> {
+    typst_wrapper::TypstWrapper::render_markup_with_metrics(markup, config)
+}
+
+/// Render Typst markup, reporting its progress to a [`RenderObserver`]
+/// 
+/// The observer hears when compilation starts, about any warnings or errors,
+/// about each exported page and when the render finishes. Its hooks can stop
+/// the render, which then fails with `TypstWrapperError::Cancelled`.
+/// 
+/// # Examples
+/// ```
+/// use std::ops::ControlFlow;
+/// use render_engine::{render_markup_observed, RenderObserver};
+/// 
+/// struct Progress;
+/// 
+/// impl RenderObserver for Progress {
+///     fn on_page_exported(&mut self, index: usize, count: usize, _page: &[u8]) -> ControlFlow<()> {
+///         println!("exported page {} of {}", index + 1, count);
+///         ControlFlow::Continue(())
+///     }
+/// }
+/// 
+/// let output = render_markup_observed("= Hello", None, &mut Progress).unwrap();
+/// assert_eq!(output.pages.len(), 1);
+/// ```
+pub fn render_markup_observed(
+    markup: &str,
+    config: Option<RenderConfig>,
+    observer: &mut dyn RenderObserver,
+) -> Result<RenderOutput, TypstWrapperError> {
+    typst_wrapper::TypstWrapper::render_markup_observed(markup, config, observer)
+}
+
 /// Render a Typst form from JSON input
 /// 
 /// # Arguments
@@ -84,7 +381,7 @@ pub fn render_markup(
 /// let svg_pages = render_form(json_input, None).unwrap();
 /// 
 /// // Render the form as PDF
-/// let config = RenderConfig { format: OutputFormat::Pdf };
+/// let config = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
 /// let pdf = render_form(json_input, Some(config)).unwrap();
 /// ```
 pub fn render_form(
@@ -92,4 +389,219 @@ pub fn render_form(
     config: Option<RenderConfig>,
 ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
     typst_wrapper::TypstWrapper::render_form(json_input, config)
-}
\ No newline at end of file
+}
+
+/// Render a Typst form from JSON input and report how the render went
+/// 
+/// Same as [`render_form`], but also returns the [`RenderMetrics`] of the
+/// render. Form validation and preprocessing count towards `parse_ms`.
+pub fn render_form_with_metrics(
+    json_input: &str,
+    config: Option<RenderConfig>,
+) -> Result<RenderOutput, TypstWrapperError> {
+    typst_wrapper::TypstWrapper::render_form_with_metrics(json_input, config)
+}
+
+/// Render a Typst form from JSON input, reporting its progress to a
+/// [`RenderObserver`]
+/// 
+/// Forms that fail validation never reach `on_compile_start`, but
+/// `on_finish` is still called with the error. Diagnostics raised by the memo
+/// template are located at the start of the form, as in [`diagnose_form`].
+pub fn render_form_observed(
+    json_input: &str,
+    config: Option<RenderConfig>,
+    observer: &mut dyn RenderObserver,
+) -> Result<RenderOutput, TypstWrapperError> {
+    typst_wrapper::TypstWrapper::render_form_observed(json_input, config, observer)
+}
+
+/// Render Typst markup to SVG pages as strings
+/// 
+/// Same as [`render_markup`] with [`OutputFormat::Svg`], whatever format
+/// `config` asks for, but with the pages already decoded.
+/// 
+/// # Examples
+/// ```
+/// let pages = render_engine::render_markup_svg_strings("= Hello", None).unwrap();
+/// assert!(pages[0].starts_with("<svg"));
+/// ```
+pub fn render_markup_svg_strings(
+    markup: &str,
+    config: Option<RenderConfig>,
+) -> Result<Vec<String>, TypstWrapperError> {
+    svg_strings(render_markup(markup, Some(svg_config(config)))?)
+}
+
+/// Render a memo form to SVG pages as strings
+/// 
+/// Same as [`render_form`] with [`OutputFormat::Svg`], whatever format
+/// `config` asks for, but with the pages already decoded.
+pub fn render_form_svg_strings(
+    json_input: &str,
+    config: Option<RenderConfig>,
+) -> Result<Vec<String>, TypstWrapperError> {
+    svg_strings(render_form(json_input, Some(svg_config(config)))?)
+}
+
+fn svg_config(config: Option<RenderConfig>) -> RenderConfig {
+    RenderConfig { format: OutputFormat::Svg, ..config.unwrap_or_default() }
+}
+
+fn svg_strings(pages: Vec<Vec<u8>>) -> Result<Vec<String>, TypstWrapperError> {
+    pages
+        .into_iter()
+        .map(|page| String::from_utf8(page).map_err(|e| TypstWrapperError::OutputFormat(format!("SVG is not UTF-8: {}", e))))
+        .collect()
+}
+
+/// Generate the Typst source a memo form renders as
+/// 
+/// The form is validated and preprocessed as for [`render_form`], with a
+/// Delta body converted to markup, and inlined into the main file of the
+/// memo template together with the partials it imports. The result compiles
+/// on its own given the memo package and the template's assets, so it can be
+/// kept for audit, compiled elsewhere or inspected to debug layout issues.
+/// 
+/// # Examples
+/// ```
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Audit",
+///     "signature-block": ["NAME", "TITLE"], "body": {"format": "delta", "data": "{\"ops\":[{\"insert\":\"Hello\\n\"}]}"}}"#;
+/// let source = render_engine::generate_typst(form).unwrap();
+/// assert!(source.contains("#official-memorandum("));
+/// assert!(render_engine::render_markup(&source, None).is_ok());
+/// ```
+pub fn generate_typst(json_input: &str) -> Result<String, TypstWrapperError> {
+    typst_wrapper::TypstWrapper::generate_typst(json_input)
+}
+
+/// Fill in the defaults of the fields a memo form leaves out
+/// 
+/// Returns the form as JSON with every field the official memo schema gives
+/// a default added, such as `"references": null` and the `format` of the
+/// body, and `"page-numbering": true` expanded to its settings. Editors can
+/// show it as the effective document; it renders as the form does.
+/// 
+/// # Examples
+/// ```
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Defaults",
+///     "signature-block": ["NAME", "TITLE"], "body": {"data": "Hello"}}"#;
+/// let normalized: serde_json::Value = serde_json::from_str(&render_engine::normalize_form(form).unwrap()).unwrap();
+/// assert_eq!(normalized["references"], serde_json::Value::Null);
+/// assert_eq!(normalized["body"]["format"], "markup");
+/// ```
+pub fn normalize_form(json_input: &str) -> Result<String, TypstWrapperError> {
+    Ok(form_processor::apply_defaults(json_input)?)
+}
+
+/// Perform the one-time setup of the first render ahead of time
+/// 
+/// The first render otherwise pays for font parsing, template parsing and
+/// package resolution all at once. Call this during startup or idle time so
+/// the first user-visible render is fast. Calling it again is cheap.
+/// 
+/// # Returns
+/// * `Ok(())` - The engine is warm
+/// * `Err(TypstWrapperError)` - The embedded memo template failed to compile
+/// 
+/// # Examples
+/// ```
+/// render_engine::prewarm().unwrap();
+/// ```
+pub fn prewarm() -> Result<(), TypstWrapperError> {
+    typst_wrapper::TypstWrapper::prewarm()
+}
+
+/// Collect editor diagnostics for Typst markup
+/// 
+/// Compiles the markup without exporting it and reports every error and
+/// warning with its range in the markup. An empty result means the markup
+/// compiles cleanly.
+/// 
+/// # Examples
+/// ```
+/// use render_engine::{diagnose_markup, Severity};
+/// 
+/// let diagnostics = diagnose_markup("= Title\n#undefined-fn()");
+/// assert_eq!(diagnostics[0].severity, Severity::Error);
+/// assert_eq!(diagnostics[0].range.start.line, 1);
+/// ```
+pub fn diagnose_markup(markup: &str) -> Vec<Diagnostic> {
+    typst_wrapper::TypstWrapper::diagnose_markup(markup)
+}
+
+/// Collect editor diagnostics for a memo form (JSON)
+/// 
+/// Reports JSON syntax errors, schema violations, unknown letterhead
+/// backgrounds and Delta conversion failures located in the form text. When
+/// the form is otherwise valid it is compiled with the memo template and any
+/// Typst errors are reported at the start of the form.
+pub fn diagnose_form(json_input: &str) -> Vec<Diagnostic> {
+    typst_wrapper::TypstWrapper::diagnose_form(json_input)
+}
+
+/// Register a full-page letterhead background image for form renders
+/// 
+/// Forms select a registered background by name through the
+/// `letterhead-background` field; it is drawn behind every page, washed out
+/// according to `letterhead-background-opacity` (default 0.3).
+/// Registering an existing name replaces the previous image.
+/// 
+/// # Arguments
+/// * `name` - Name forms use to reference the background (letters, digits, `-`, `_`, `.`)
+/// * `data` - Image bytes (PNG, JPEG, GIF, WebP or SVG)
+/// 
+/// # Returns
+/// * `Ok(())` - The background is available to subsequent renders
+/// * `Err(TypstWrapperError)` - Invalid name, unsupported image data, or
+///   `TypstWrapperError::Registration` if the active [`RegistrationLimits`] would be exceeded
+pub fn register_letterhead(name: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+    typst_wrapper::TypstWrapper::register_letterhead(name, data)
+}
+
+/// Register an image or other file, such as a unit emblem, a signature
+/// image or an attachment, that renders read at `path`
+/// 
+/// Markup reads it at `path`, e.g. `#image("emblems/unit.png")`. Form
+/// bodies are evaluated inside the memo template, so they use the path from
+/// the root, `#image("/emblems/unit.png")`. Registering an existing path
+/// replaces the previous file; embedded template files take precedence.
+/// 
+/// # Arguments
+/// * `path` - `/`-separated names of letters, digits, `-`, `_` and `.`,
+///   optionally starting with `/`, outside `letterheads/`
+/// * `data` - The file's bytes
+/// 
+/// # Returns
+/// * `Ok(())` - The file is available to subsequent renders
+/// * `Err(TypstWrapperError)` - Invalid path, or
+///   `TypstWrapperError::Registration` if the active [`RegistrationLimits`] would be exceeded
+/// 
+/// # Examples
+/// ```
+/// let seal = render_engine::assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+/// render_engine::register_asset("emblems/unit.gif", seal).unwrap();
+/// let pages = render_engine::render_markup("#image(\"emblems/unit.gif\", alt: \"Unit emblem\")", None).unwrap();
+/// assert!(render_engine::unregister_asset("emblems/unit.gif"));
+/// ```
+pub fn register_asset(path: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+    typst_wrapper::TypstWrapper::register_asset(path, data)
+}
+
+/// Configure the caps applied to runtime asset registration
+/// 
+/// Limits apply to subsequent registrations only; assets that are already
+/// registered are kept even if they exceed the new limits.
+/// 
+/// # Examples
+/// ```
+/// use render_engine::{set_registration_limits, RegistrationLimits};
+/// 
+/// set_registration_limits(RegistrationLimits {
+///     max_asset_bytes: 4 * 1024 * 1024,
+///     ..RegistrationLimits::default()
+/// });
+/// ```
+pub fn set_registration_limits(limits: RegistrationLimits) {
+    assets::set_registration_limits(limits)
+}
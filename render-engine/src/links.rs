@@ -0,0 +1,97 @@
+//! Clickable links in SVG pages.
+//!
+//! The SVG export draws links as plain text. Unless
+//! [`RenderConfig::svg_links`](crate::RenderConfig::svg_links) is turned
+//! off (e.g. for print), every link area of a page becomes an invisible
+//! rectangle inside an `<a>` element on top of the page content. Links to
+//! URLs keep their URL; links within the document point at `#page-N`, the
+//! 1-based page they lead to, for the host to scroll to.
+
+use std::fmt::Write;
+
+use typst::introspection::Introspector;
+use typst::layout::{Frame, FrameItem, Page, Transform};
+use typst::model::Destination;
+
+/// A page's SVG with its links added
+pub(crate) fn svg_with_links(svg: String, page: &Page, introspector: &Introspector) -> String {
+    let mut anchors = String::new();
+    collect(&page.frame, Transform::identity(), introspector, &mut anchors);
+    if anchors.is_empty() {
+        return svg;
+    }
+    match svg.rfind("</svg>") {
+        Some(end) => {
+            let mut svg = svg;
+            svg.insert_str(end, &anchors);
+            svg
+        }
+        None => svg,
+    }
+}
+
+/// Append an `<a>` element for every link in `frame`
+fn collect(frame: &Frame, transform: Transform, introspector: &Introspector, anchors: &mut String) {
+    for (pos, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => collect(&group.frame, transform.pre_concat(group.transform), introspector, anchors),
+            FrameItem::Link(destination, size) => {
+                let href = match destination {
+                    Destination::Url(url) => escape(url.as_str()),
+                    Destination::Position(position) => format!("#page-{}", position.page),
+                    Destination::Location(location) => format!("#page-{}", introspector.page(*location)),
+                };
+                let _ = write!(
+                    anchors,
+                    "<a href=\"{href}\" xlink:href=\"{href}\"><rect width=\"{}\" height=\"{}\" \
+                     transform=\"matrix({} {} {} {} {} {})\" fill=\"#000\" fill-opacity=\"0\"/></a>",
+                    size.x.to_pt(),
+                    size.y.to_pt(),
+                    transform.sx.get(),
+                    transform.ky.get(),
+                    transform.kx.get(),
+                    transform.sy.get(),
+                    transform.tx.to_pt(),
+                    transform.ty.to_pt(),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `text` escaped for an XML attribute value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{render_markup, RenderConfig};
+
+    #[test]
+    fn test_svg_links() {
+        let markup = "#link(\"https://example.com/?a=1&b=2\")[Example]\n#pagebreak()\n= Target <target>\n#link(<target>)[Back]";
+        let pages = render_markup(markup, None).unwrap();
+        let first = String::from_utf8(pages[0].clone()).unwrap();
+        assert!(first.contains("<a href=\"https://example.com/?a=1&amp;b=2\""), "{}", first);
+        assert!(first.trim_end().ends_with("</a></svg>"));
+        let second = String::from_utf8(pages[1].clone()).unwrap();
+        assert!(second.contains("<a href=\"#page-2\""));
+
+        let config = RenderConfig { svg_links: false, ..RenderConfig::default() };
+        let plain = render_markup(markup, Some(config)).unwrap();
+        assert!(!String::from_utf8_lossy(&plain[0]).contains("<a "));
+    }
+}
@@ -0,0 +1,146 @@
+//! Positions of labelled elements in the laid-out document.
+//!
+//! Post-processing that draws on the rendered pages, such as a signature
+//! stamp, needs to know where the template put things. The memo template
+//! labels the signature block `<signature-block>`; any label in markup can
+//! be located the same way.
+
+use serde::Serialize;
+use tracing::instrument;
+use typst::foundations::{Label, Selector};
+use typst::layout::PagedDocument;
+use typst::utils::PicoStr;
+
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// Label of the first line of each memo signature block, the co-signers'
+/// before the signer's
+pub const SIGNATURE_BLOCK_LABEL: &str = "signature-block";
+
+/// Label of `metadata` elements that become PDF bookmarks, such as the memo
+/// subject (see [`crate::bookmarks`])
+pub const BOOKMARK_LABEL: &str = "bookmark";
+
+/// Label of the `metadata` the memo template puts at the start of each
+/// numbered paragraph, which PDF bookmarks list (see [`crate::bookmarks`])
+pub const PARAGRAPH_LABEL: &str = "paragraph";
+
+/// Label marking where each memo or indorsement of a package starts, see
+/// [`RenderConfig::duplex`](crate::RenderConfig::duplex)
+pub const SECTION_START_LABEL: &str = "section-start";
+
+/// Label of `metadata` elements where PDF output gets an empty signature
+/// field, see [`RenderConfig::form_fields`](crate::RenderConfig::form_fields)
+pub const SIGNATURE_FIELD_LABEL: &str = "signature-field";
+
+/// Label of `metadata` elements where PDF output gets a date field, see
+/// [`RenderConfig::form_fields`](crate::RenderConfig::form_fields)
+pub const DATE_FIELD_LABEL: &str = "date-field";
+
+/// Label of `metadata` naming the form field or Delta op the content after
+/// it comes from, see [`crate::source_map`]
+pub const SOURCE_START_LABEL: &str = "source-start";
+
+/// Label of the `metadata` ending the content of a `<source-start>` marker
+pub const SOURCE_END_LABEL: &str = "source-end";
+
+/// Where a labelled element starts on its page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ElementPosition {
+    /// Page index, starting at 0
+    pub page: usize,
+    /// Distance from the left edge of the page, in points
+    pub x: f64,
+    /// Distance from the top edge of the page, in points
+    pub y: f64,
+}
+
+/// Positions of the elements labelled `label` in rendered Typst markup, in
+/// document order
+///
+/// # Examples
+/// ```
+/// use render_engine::locate::locate_markup_label;
+///
+/// let positions = locate_markup_label("#v(1in)\n[Here] <here>", "here").unwrap();
+/// assert_eq!(positions[0].page, 0);
+/// ```
+#[instrument(skip_all, fields(label))]
+pub fn locate_markup_label(markup: &str, label: &str) -> Result<Vec<ElementPosition>, TypstWrapperError> {
+    let document = TypstWrapper::compile_markup(markup)?;
+    Ok(positions(&document, label))
+}
+
+/// Positions of the elements labelled `label` in a rendered memo form, e.g.
+/// [`SIGNATURE_BLOCK_LABEL`]
+#[instrument(skip_all, fields(label))]
+pub fn locate_form_label(json_input: &str, label: &str) -> Result<Vec<ElementPosition>, TypstWrapperError> {
+    let document = TypstWrapper::compile_form(json_input)?;
+    Ok(positions(&document, label))
+}
+
+fn positions(document: &PagedDocument, label: &str) -> Vec<ElementPosition> {
+    if label.is_empty() {
+        return Vec::new();
+    }
+    let introspector = &document.introspector;
+    introspector
+        .query(&Selector::Label(Label::new(PicoStr::intern(label))))
+        .iter()
+        .filter_map(|element| element.location())
+        .map(|location| {
+            let position = introspector.position(location);
+            ElementPosition {
+                page: position.page.get() - 1,
+                x: position.point.x.to_pt(),
+                y: position.point.y.to_pt(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal().subject("Signature Placement")
+    }
+
+    #[test]
+    fn test_locate_markup_label() {
+        let markup = "#set page(width: 200pt, height: 200pt, margin: 20pt)\n#v(50pt)\n#align(right, box[#metadata(none) <target>Target])\n#pagebreak()\n#metadata(none) <target>";
+        let positions = locate_markup_label(markup, "target").unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!((positions[0].page, positions[1].page), (0, 1));
+        assert!(positions[0].x > 100.0, "{:?}", positions);
+        assert!((positions[0].y - 70.0).abs() < 0.01, "{:?}", positions);
+        assert!(locate_markup_label(markup, "missing").unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_signature_block_is_labelled() {
+        let positions = locate_form_label(&form().to_json(), SIGNATURE_BLOCK_LABEL).unwrap();
+        assert_eq!(positions.len(), 1);
+        // Right-aligned in the lower part of a US Letter page
+        assert!(positions[0].x > 300.0 && positions[0].y > 200.0 && positions[0].y < 720.0, "{:?}", positions[0]);
+    }
+
+    #[test]
+    fn test_co_signers_sign_beside_and_above() {
+        let co_signer: &[&str] = &["JOHN A. DOE, Col, USAF", "Coordinating Official"];
+        let beside = form().co_signers([co_signer]).to_json();
+        let positions = locate_form_label(&beside, SIGNATURE_BLOCK_LABEL).unwrap();
+        assert_eq!(positions.len(), 2);
+        assert!((positions[0].y - positions[1].y).abs() < 0.01, "{:?}", positions);
+        assert!(positions[0].x < 100.0 && positions[1].x > 300.0, "{:?}", positions);
+
+        let stacked = form().co_signers([&["A. B. ONE, Lt Col, USAF", "Director"], co_signer]).to_json();
+        let positions = locate_form_label(&stacked, SIGNATURE_BLOCK_LABEL).unwrap();
+        assert_eq!(positions.len(), 3);
+        assert!((positions[0].y - positions[1].y).abs() < 0.01, "{:?}", positions);
+        assert!(positions[2].y > positions[1].y + 40.0 && positions[2].x > 300.0, "{:?}", positions);
+    }
+}
@@ -0,0 +1,201 @@
+//! PDF document metadata.
+//!
+//! Document management systems index PDFs by the title, author, subject,
+//! keywords and creation date in their document information. A
+//! [`DocumentMetadata`] in
+//! [`RenderConfig::metadata`](crate::RenderConfig::metadata) or, for memo
+//! forms, the `metadata` field of the form JSON sets them; fields set in the
+//! config take precedence over those of the form.
+//!
+//! Without metadata, markup renders keep what their `#set document(..)`
+//! rules set and memo forms are titled with their subject. Without a date,
+//! PDFs carry no creation date, except PDF/X output, which is dated with the
+//! time of the render.
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Datetime, Smart};
+use typst::layout::PagedDocument;
+
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Title, author, subject, keywords and creation date of a PDF
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, DocumentMetadata, OutputFormat, RenderConfig};
+///
+/// let config = RenderConfig {
+///     format: OutputFormat::Pdf,
+///     metadata: Some(DocumentMetadata {
+///         title: Some("Annual Review".to_string()),
+///         author: vec!["FIRST M. LAST".to_string()],
+///         keywords: vec!["review".to_string(), "2024".to_string()],
+///         date: Some("2024-01-15".to_string()),
+///         ..DocumentMetadata::default()
+///     }),
+///     ..RenderConfig::default()
+/// };
+/// let pdf = render_markup("= Hello", Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Vec<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    /// Creation date as `YYYY-MM-DD`, or `YYYY-MM-DDTHH:MM:SS` in UTC with
+    /// an optional trailing `Z`
+    pub date: Option<String>,
+}
+
+impl DocumentMetadata {
+    /// Check that the metadata can be written
+    pub(crate) fn validate(&self) -> Result<(), TypstWrapperError> {
+        self.date.as_deref().map(|date| parse_date(date, "metadata date")).transpose().map(|_| ())
+    }
+
+    /// These fields, with the unset ones taken from `fallback`
+    fn or(self, fallback: DocumentMetadata) -> DocumentMetadata {
+        let or_vec = |fields: Vec<String>, fallback: Vec<String>| if fields.is_empty() { fallback } else { fields };
+        DocumentMetadata {
+            title: self.title.or(fallback.title),
+            author: or_vec(self.author, fallback.author),
+            subject: self.subject.or(fallback.subject),
+            keywords: or_vec(self.keywords, fallback.keywords),
+            date: self.date.or(fallback.date),
+        }
+    }
+}
+
+/// Combine the `metadata` field of preprocessed form JSON with the metadata
+/// of the config, whose fields take precedence
+pub(crate) fn form_metadata(
+    processed_input: &str,
+    config: Option<DocumentMetadata>,
+) -> Result<Option<DocumentMetadata>, TypstWrapperError> {
+    #[derive(Deserialize)]
+    struct Form {
+        metadata: Option<DocumentMetadata>,
+    }
+
+    let form: Form = serde_json::from_str(processed_input)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid metadata: {}", e)))?;
+    Ok(match (config, form.metadata) {
+        (Some(config), Some(form)) => Some(config.or(form)),
+        (config, form) => config.or(form),
+    })
+}
+
+/// Set the document information of `document` from `metadata`
+pub(crate) fn apply(document: &mut PagedDocument, metadata: &DocumentMetadata) -> Result<(), TypstWrapperError> {
+    let info = &mut document.info;
+    if let Some(title) = &metadata.title {
+        info.title = Some(title.into());
+    }
+    if !metadata.author.is_empty() {
+        info.author = metadata.author.iter().map(Into::into).collect();
+    }
+    if let Some(subject) = &metadata.subject {
+        info.description = Some(subject.into());
+    }
+    if !metadata.keywords.is_empty() {
+        info.keywords = metadata.keywords.iter().map(Into::into).collect();
+    }
+    if let Some(date) = &metadata.date {
+        info.date = Smart::Custom(Some(parse_date(date, "metadata date")?));
+    }
+    Ok(())
+}
+
+/// Parse `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS[Z]`, naming `field` in the
+/// error
+pub(crate) fn parse_date(date: &str, field: &str) -> Result<Datetime, TypstWrapperError> {
+    let error = || {
+        TypstWrapperError::Validation(format!(
+            "Invalid {} '{}': use YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS",
+            field, date
+        ))
+    };
+    let number = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse::<i32>().ok()).flatten();
+    let (day, time) = match date.split_once('T') {
+        Some((day, time)) => (day, Some(time.strip_suffix('Z').unwrap_or(time))),
+        None => (date, None),
+    };
+    let parts = |text: &str, separator: char| -> Option<[i32; 3]> {
+        let fields: Vec<i32> = text.split(separator).map(number).collect::<Option<_>>()?;
+        fields.try_into().ok()
+    };
+    let [year, month, day] = parts(day, '-').ok_or_else(error)?;
+    let (month, day) = (u8::try_from(month).map_err(|_| error())?, u8::try_from(day).map_err(|_| error())?);
+    match time {
+        None => Datetime::from_ymd(year, month, day),
+        Some(time) => {
+            let [hour, minute, second] = parts(time, ':').ok_or_else(error)?.map(|field| u8::try_from(field).unwrap_or(u8::MAX));
+            Datetime::from_ymd_hms(year, month, day, hour, minute, second)
+        }
+    }
+    .ok_or_else(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use crate::{render_form, render_markup, OutputFormat, RenderConfig};
+
+    fn pdf_config(metadata: Option<DocumentMetadata>) -> RenderConfig {
+        RenderConfig { format: OutputFormat::Pdf, metadata, ..RenderConfig::default() }
+    }
+
+    fn pdf_text(pdf: &[u8]) -> String {
+        String::from_utf8_lossy(pdf).into_owned()
+    }
+
+    #[test]
+    fn test_markup_metadata() {
+        let metadata = DocumentMetadata {
+            title: Some("Annual Review".to_string()),
+            author: vec!["Alice".to_string(), "Bob".to_string()],
+            subject: Some("Findings".to_string()),
+            keywords: vec!["review".to_string(), "audit".to_string()],
+            date: Some("2024-01-15T09:30:00Z".to_string()),
+        };
+        let pdf = pdf_text(&render_markup("#set document(title: \"Old\")\nHello", Some(pdf_config(Some(metadata)))).unwrap()[0]);
+        for expected in [
+            "/Title (Annual Review)",
+            "/Author (Alice, Bob)",
+            "/Subject (Findings)",
+            "/Keywords (review, audit)",
+            "/CreationDate (D:20240115093000",
+        ] {
+            assert!(pdf.contains(expected), "{} missing", expected);
+        }
+        assert!(!pdf.contains("/Title (Old)"));
+    }
+
+    #[test]
+    fn test_config_fields_take_precedence_over_the_form() {
+        let form = MemoBuilder::minimal()
+            .subject("Memo Subject")
+            .set("metadata", serde_json::json!({"author": ["Form Author"], "keywords": ["form"], "date": "2024-02-01"}))
+            .to_json();
+        let pdf = pdf_text(&render_form(&form, Some(pdf_config(None))).unwrap()[0]);
+        assert!(pdf.contains("/Title (Memo Subject)") && pdf.contains("/Author (Form Author)"));
+        assert!(pdf.contains("/CreationDate (D:20240201"));
+
+        let config = DocumentMetadata { author: vec!["Config Author".to_string()], ..DocumentMetadata::default() };
+        let pdf = pdf_text(&render_form(&form, Some(pdf_config(Some(config)))).unwrap()[0]);
+        assert!(pdf.contains("/Author (Config Author)") && pdf.contains("/Keywords (form)"));
+    }
+
+    #[test]
+    fn test_invalid_dates_fail() {
+        for date in ["2024-13-01", "15.01.2024", "2024-01-15T25:00:00", "2024-01-15T09:30"] {
+            let metadata = DocumentMetadata { date: Some(date.to_string()), ..DocumentMetadata::default() };
+            let result = render_markup("Hello", Some(pdf_config(Some(metadata))));
+            assert!(matches!(result, Err(TypstWrapperError::Validation(_))), "{}", date);
+        }
+        assert!(parse_date("2024-01-15", "date").is_ok() && parse_date("2024-01-15T09:30:00", "date").is_ok());
+    }
+}
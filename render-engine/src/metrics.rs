@@ -0,0 +1,150 @@
+//! Per-render performance metrics.
+//!
+//! Every successful render produces a [`RenderMetrics`] with stage timings,
+//! output sizes and package source cache usage. The metrics are returned by
+//! [`render_markup_with_metrics`](crate::render_markup_with_metrics) and
+//! [`render_form_with_metrics`](crate::render_form_with_metrics), and every
+//! render (including plain `render_markup`/`render_form`) delivers them to the
+//! callback installed with [`set_metrics_callback`].
+
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+use serde::{Deserialize, Serialize};
+use typst::layout::PagedDocument;
+
+use crate::fonts::FontSubstitution;
+use web_time::Instant;
+
+/// Timings and sizes of a single render
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenderMetrics {
+    /// Form validation and preprocessing plus source setup, in milliseconds
+    pub parse_ms: f64,
+    /// Typst compilation (layout), in milliseconds
+    pub compile_ms: f64,
+    /// SVG or PDF export, in milliseconds
+    pub export_ms: f64,
+    /// Wall time of the whole render, in milliseconds
+    pub total_ms: f64,
+    /// Number of pages in the compiled document
+    pub pages: usize,
+    /// Total size of the exported output in bytes
+    pub bytes: usize,
+    /// Package source lookups served from the shared parse cache
+    pub cache_hits: usize,
+    /// Package source lookups that had to parse the source
+    pub cache_misses: usize,
+    /// Whether the output was served by the persistent cache provider
+    /// without compiling (see [`crate::cache`])
+    pub from_cache: bool,
+}
+
+/// Pages produced by a render together with its metrics
+#[derive(Debug, Clone)]
+pub struct RenderOutput {
+    /// Rendered pages (one per page for SVG, a single item for PDF)
+    pub pages: Vec<Vec<u8>>,
+    /// Size of every page of the document, also for PDF and SVG ZIP output
+    pub page_sizes: Vec<PageSize>,
+    /// The configured font substitutions the document's text uses
+    pub font_substitutions: Vec<FontSubstitution>,
+    pub metrics: RenderMetrics,
+}
+
+/// Size of a page in points, for mapping clicks and scaling viewers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The sizes of the pages of `document`
+pub(crate) fn page_sizes(document: &PagedDocument) -> Vec<PageSize> {
+    document
+        .pages
+        .iter()
+        .map(|page| PageSize {
+            width: page.frame.width().to_pt(),
+            height: page.frame.height().to_pt(),
+        })
+        .collect()
+}
+
+type MetricsCallback = Arc<dyn Fn(&RenderMetrics) + Send + Sync>;
+
+static METRICS_CALLBACK: LazyLock<RwLock<Option<MetricsCallback>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Deliver the metrics of every subsequent successful render to `callback`,
+/// replacing any previous callback
+///
+/// The callback runs on the rendering thread after export, so it should be
+/// cheap (e.g. record into a histogram or push onto a channel).
+pub fn set_metrics_callback<F>(callback: F)
+where
+    F: Fn(&RenderMetrics) + Send + Sync + 'static,
+{
+    *METRICS_CALLBACK.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(callback));
+}
+
+/// Stop delivering metrics to the callback installed with [`set_metrics_callback`]
+pub fn clear_metrics_callback() {
+    *METRICS_CALLBACK.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Pass metrics to the installed callback, if any
+pub(crate) fn report(metrics: &RenderMetrics) {
+    let callback = METRICS_CALLBACK.read().unwrap_or_else(PoisonError::into_inner).clone();
+    if let Some(callback) = callback {
+        callback(metrics);
+    }
+}
+
+/// Milliseconds elapsed since `start`
+pub(crate) fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_render_reports_metrics() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        set_metrics_callback(move |metrics| sink.lock().unwrap().push(metrics.clone()));
+
+        let markup = "= Metrics callback test";
+        let output = crate::render_markup_with_metrics(markup, None).unwrap();
+        clear_metrics_callback();
+
+        let metrics = &output.metrics;
+        assert_eq!(metrics.pages, 1);
+        assert_eq!(metrics.bytes, output.pages[0].len());
+        assert!(metrics.total_ms >= metrics.compile_ms);
+        // Other tests may render concurrently, so look for this render only
+        assert!(seen.lock().unwrap().iter().any(|m| m.bytes == metrics.bytes));
+    }
+
+    #[test]
+    fn test_page_sizes() {
+        let markup = "#set page(width: 200pt, height: 100pt)\nFirst\n#pagebreak()\n#set page(width: 300pt)\nSecond";
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let output = crate::render_markup_with_metrics(markup, Some(config)).unwrap();
+        assert_eq!(output.pages.len(), 1);
+        assert_eq!(
+            output.page_sizes,
+            [PageSize { width: 200.0, height: 100.0 }, PageSize { width: 300.0, height: 100.0 }]
+        );
+    }
+
+    #[test]
+    fn test_package_sources_are_cached() {
+        let markup = r#"#import "@preview/tonguetoquill-usaf-memo:latest": official-memorandum"#;
+        crate::render_markup(markup, None).unwrap();
+        let metrics = crate::render_markup_with_metrics(markup, None).unwrap().metrics;
+        assert!(metrics.cache_hits > 0);
+        assert_eq!(metrics.cache_misses, 0);
+    }
+}
@@ -0,0 +1,243 @@
+//! Render lifecycle hooks.
+//!
+//! A [`RenderObserver`] passed to
+//! [`render_markup_observed`](crate::render_markup_observed) or
+//! [`render_form_observed`](crate::render_form_observed) is called as the
+//! render progresses, so hosts can drive progress bars and logging without
+//! polling. Hooks that return [`ControlFlow`] can stop the render early, in
+//! which case it fails with [`TypstWrapperError::Cancelled`].
+//!
+//! A [`CancelToken`] is an observer another thread or task cancels, for
+//! previews that go stale while they render. Observers combine as pairs, so
+//! `(token, &mut observer)` both reports progress and can be cancelled.
+
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::diagnostics::Diagnostic;
+use crate::metrics::RenderMetrics;
+use crate::TypstWrapperError;
+
+/// Callbacks for the stages of a render
+///
+/// Every method has a default that does nothing and continues, so
+/// implementations only override the hooks they need. `()` is the observer
+/// that ignores everything.
+pub trait RenderObserver {
+    /// The input is parsed and compilation is about to start.
+    /// Return `ControlFlow::Break(())` to cancel before compiling.
+    fn on_compile_start(&mut self) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Compilation produced warnings or errors, located as by
+    /// [`diagnose_markup`](crate::diagnose_markup) and
+    /// [`diagnose_form`](crate::diagnose_form). Not called for clean compiles.
+    fn on_diagnostics(&mut self, _diagnostics: &[Diagnostic]) {}
+
+    /// Page `index` of `count` was exported. For PDF output the whole
+    /// document is reported once as page 0 of 1; SVG ZIP output reports
+    /// the SVG pages before they are archived. With the `parallel`
+    /// feature, SVG pages are exported together and reported afterwards.
+    /// Return `ControlFlow::Break(())` to cancel the remaining export.
+    fn on_page_exported(&mut self, _index: usize, _count: usize, _page: &[u8]) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// The render ended, successfully or not. Always the last call.
+    fn on_finish(&mut self, _result: Result<&RenderMetrics, &TypstWrapperError>) {}
+}
+
+impl RenderObserver for () {}
+
+impl<O: RenderObserver + ?Sized> RenderObserver for &mut O {
+    fn on_compile_start(&mut self) -> ControlFlow<()> {
+        (**self).on_compile_start()
+    }
+
+    fn on_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+        (**self).on_diagnostics(diagnostics)
+    }
+
+    fn on_page_exported(&mut self, index: usize, count: usize, page: &[u8]) -> ControlFlow<()> {
+        (**self).on_page_exported(index, count, page)
+    }
+
+    fn on_finish(&mut self, result: Result<&RenderMetrics, &TypstWrapperError>) {
+        (**self).on_finish(result)
+    }
+}
+
+/// The first observer hears each hook before the second; either can cancel
+/// the render, and hooks the first cancels at do not reach the second
+impl<A: RenderObserver, B: RenderObserver> RenderObserver for (A, B) {
+    fn on_compile_start(&mut self) -> ControlFlow<()> {
+        self.0.on_compile_start()?;
+        self.1.on_compile_start()
+    }
+
+    fn on_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+        self.0.on_diagnostics(diagnostics);
+        self.1.on_diagnostics(diagnostics);
+    }
+
+    fn on_page_exported(&mut self, index: usize, count: usize, page: &[u8]) -> ControlFlow<()> {
+        self.0.on_page_exported(index, count, page)?;
+        self.1.on_page_exported(index, count, page)
+    }
+
+    fn on_finish(&mut self, result: Result<&RenderMetrics, &TypstWrapperError>) {
+        self.0.on_finish(result);
+        self.1.on_finish(result);
+    }
+}
+
+/// Cancels the renders it observes once [`cancel`](Self::cancel) is called
+/// on any of its clones
+///
+/// Renders check the token before compiling and after every exported page,
+/// so a cancelled render stops at the next of those and fails with
+/// [`TypstWrapperError::Cancelled`]; a compile in progress runs to its end.
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup_observed, CancelToken, TypstWrapperError};
+///
+/// let token = CancelToken::new();
+/// let stale = token.clone();
+/// // E.g. from the editor thread when the user types on
+/// stale.cancel();
+/// let result = render_markup_observed("= Preview", None, &mut token.clone());
+/// assert!(matches!(result, Err(TypstWrapperError::Cancelled)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// A token not yet cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the renders observed by this token and its clones
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn control(&self) -> ControlFlow<()> {
+        match self.is_cancelled() {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    }
+}
+
+impl RenderObserver for CancelToken {
+    fn on_compile_start(&mut self) -> ControlFlow<()> {
+        self.control()
+    }
+
+    fn on_page_exported(&mut self, _index: usize, _count: usize, _page: &[u8]) -> ControlFlow<()> {
+        self.control()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the hooks it sees and cancels after `cancel_after` pages
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        cancel_after: Option<usize>,
+    }
+
+    impl RenderObserver for Recorder {
+        fn on_compile_start(&mut self) -> ControlFlow<()> {
+            self.events.push("compile".to_string());
+            ControlFlow::Continue(())
+        }
+
+        fn on_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+            self.events.push(format!("diagnostics {}", diagnostics.len()));
+        }
+
+        fn on_page_exported(&mut self, index: usize, count: usize, _page: &[u8]) -> ControlFlow<()> {
+            self.events.push(format!("page {}/{}", index + 1, count));
+            match self.cancel_after {
+                Some(limit) if index + 1 >= limit => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        }
+
+        fn on_finish(&mut self, result: Result<&RenderMetrics, &TypstWrapperError>) {
+            self.events.push(match result {
+                Ok(metrics) => format!("finish {} pages", metrics.pages),
+                Err(e) => format!("finish error: {}", e),
+            });
+        }
+    }
+
+    const TWO_PAGES: &str = "= One\n#pagebreak()\n= Two";
+
+    #[test]
+    fn test_hooks_are_called_in_order() {
+        let mut recorder = Recorder::default();
+        let output = crate::render_markup_observed(TWO_PAGES, None, &mut recorder).unwrap();
+        assert_eq!(output.pages.len(), 2);
+        assert_eq!(recorder.events, ["compile", "page 1/2", "page 2/2", "finish 2 pages"]);
+    }
+
+    #[test]
+    fn test_errors_reach_diagnostics_and_finish() {
+        let mut recorder = Recorder::default();
+        assert!(crate::render_markup_observed("#undefined-fn()", None, &mut recorder).is_err());
+        assert_eq!(recorder.events[..2], ["compile", "diagnostics 1"]);
+        assert!(recorder.events[2].starts_with("finish error: Compilation failed"));
+    }
+
+    #[test]
+    fn test_observer_can_cancel() {
+        let mut recorder = Recorder {
+            cancel_after: Some(1),
+            ..Recorder::default()
+        };
+        let result = crate::render_markup_observed(TWO_PAGES, None, &mut recorder);
+        assert!(matches!(result, Err(TypstWrapperError::Cancelled)));
+        assert_eq!(recorder.events, ["compile", "page 1/2", "finish error: Render cancelled"]);
+    }
+
+    /// Cancels `token` when the first page is exported
+    struct CancelOnFirstPage(CancelToken);
+
+    impl RenderObserver for CancelOnFirstPage {
+        fn on_page_exported(&mut self, _index: usize, _count: usize, _page: &[u8]) -> ControlFlow<()> {
+            self.0.cancel();
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn test_cancel_token_stops_render() {
+        let config = crate::RenderConfig { cache: false, ..crate::RenderConfig::default() };
+        let token = CancelToken::new();
+        let mut recorder = Recorder::default();
+        let mut observer = (CancelOnFirstPage(token.clone()), (&mut recorder, token.clone()));
+        let result = crate::render_markup_observed(TWO_PAGES, Some(config.clone()), &mut observer);
+        assert!(matches!(result, Err(TypstWrapperError::Cancelled)));
+        assert_eq!(recorder.events, ["compile", "page 1/2", "finish error: Render cancelled"]);
+
+        // Cancelled tokens stay cancelled
+        let mut recorder = Recorder::default();
+        let result = crate::render_markup_observed(TWO_PAGES, Some(config), &mut (token, &mut recorder));
+        assert!(matches!(result, Err(TypstWrapperError::Cancelled)));
+        assert_eq!(recorder.events, ["finish error: Render cancelled"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Local template packages.
+//!
+//! Organizations keep their own Typst packages: letterhead variants, shared
+//! signature blocks, unit-specific templates. Registering one makes
+//! `#import "@local/<name>:<version>"` resolve to it in renders of the
+//! current [`RenderEngine`](crate::RenderEngine), without publishing it or
+//! passing it off as a `@preview` package:
+//!
+//! ```
+//! use render_engine::packages::register_local_package;
+//!
+//! let spec = register_local_package(vec![
+//!     ("typst.toml".to_string(), b"[package]\nname = \"org-kit\"\nversion = \"1.0.0\"\nentrypoint = \"lib.typ\"".to_vec()),
+//!     ("lib.typ".to_string(), b"#let unit = [42nd Wing]".to_vec()),
+//! ])
+//! .unwrap();
+//! assert_eq!(spec, "@local/org-kit:1.0.0");
+//! assert!(render_engine::render_markup("#import \"@local/org-kit:1.0.0\": unit\n#unit", None).is_ok());
+//! ```
+//!
+//! A package is its files by path, with the `typst.toml` manifest at the
+//! root naming it and its entrypoint. Packages vendored next to the host can
+//! be registered from their directory with `register_local_package_dir`.
+//! Each package counts as one asset of its total size against the
+//! [`RegistrationLimits`](crate::RegistrationLimits) it shares with the
+//! letterhead backgrounds.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::info;
+use typst::foundations::Bytes;
+use typst::syntax::package::{PackageManifest, PackageSpec};
+
+use crate::assets::{self, RegistrationKind};
+use crate::engine::{self, PackageFiles};
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Namespace of the packages registered at runtime
+pub const LOCAL_NAMESPACE: &str = "local";
+
+/// Register (or replace) a local package with the current
+/// [`RenderEngine`](crate::RenderEngine), returning its spec, e.g.
+/// `@local/org-kit:1.0.0`
+///
+/// `files` pairs paths within the package, such as `src/lib.typ`, with their
+/// content. Fails if the manifest is missing or invalid, a path leaves the
+/// package, the entrypoint is missing or the registration limits would be
+/// exceeded.
+pub fn register_local_package(files: Vec<(String, Vec<u8>)>) -> Result<String, TypstWrapperError> {
+    let (spec, files) = prepare(files)?;
+    let size = package_size(&files);
+    let engine = engine::current();
+    let _registering = engine.registration_lock();
+    let limits = engine.registration_limits();
+    let (letterheads, registered_files, fonts) = (engine.letterheads(), engine.assets(), engine.fonts());
+    engine.update_local_packages(|packages| {
+        let registered = assets::registered_sizes(&letterheads, &registered_files, packages, &fonts);
+        limits.check(RegistrationKind::Package, &spec.to_string(), size, assets::entries(&registered))?;
+        packages.insert(spec.clone(), files);
+        Ok(())
+    })?;
+    info!(package = %spec, size, "registered local package");
+    Ok(spec.to_string())
+}
+
+/// Register (or replace) the local package in `dir`, see
+/// [`register_local_package`]
+///
+/// Files and directories whose names start with a dot are left out.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn register_local_package_dir(dir: impl AsRef<std::path::Path>) -> Result<String, TypstWrapperError> {
+    fn collect(dir: &std::path::Path, prefix: &str, files: &mut Vec<(String, Vec<u8>)>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                collect(&entry.path(), &format!("{}/", path), files)?;
+            } else {
+                files.push((path, std::fs::read(entry.path())?));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(dir.as_ref(), "", &mut files)?;
+    register_local_package(files)
+}
+
+/// Remove a registered local package by its spec, returning whether it was
+/// registered
+pub fn unregister_local_package(spec: &str) -> bool {
+    let Ok(spec) = spec.parse::<PackageSpec>() else {
+        return false;
+    };
+    engine::current()
+        .update_local_packages(|packages| Ok(packages.remove(&spec).is_some()))
+        .unwrap_or(false)
+}
+
+/// The specs of the registered local packages, sorted
+pub fn local_package_specs() -> Vec<String> {
+    let mut specs: Vec<String> = engine::current().local_packages().keys().map(ToString::to_string).collect();
+    specs.sort();
+    specs
+}
+
+/// The registered local packages with their files, sorted by spec and path
+pub(crate) fn registered_local_packages() -> Vec<(String, Vec<(String, Bytes)>)> {
+    let mut packages: Vec<_> = engine::current()
+        .local_packages()
+        .iter()
+        .map(|(spec, files)| {
+            let mut files: Vec<_> = files.iter().map(|(path, data)| (path.clone(), data.clone())).collect();
+            files.sort_by(|(a, _), (b, _)| a.cmp(b));
+            (spec.to_string(), files)
+        })
+        .collect();
+    packages.sort_by(|(a, _), (b, _)| a.cmp(b));
+    packages
+}
+
+/// Total size of a package's files in bytes
+pub(crate) fn package_size(files: &HashMap<String, Bytes>) -> usize {
+    files.values().map(Bytes::len).sum()
+}
+
+/// Check the files of a package and read its spec from the manifest
+pub(crate) fn prepare(files: Vec<(String, Vec<u8>)>) -> Result<(PackageSpec, PackageFiles), TypstWrapperError> {
+    let invalid = |message: String| TypstWrapperError::Validation(format!("Invalid local package: {}", message));
+    let normalized = normalize_files(files).map_err(|path| invalid(format!("'{}' is not a path within the package", path)))?;
+
+    let manifest = normalized.get("typst.toml").ok_or_else(|| invalid("typst.toml is missing".to_string()))?;
+    let manifest = std::str::from_utf8(manifest).map_err(|_| invalid("typst.toml is not UTF-8".to_string()))?;
+    let manifest: PackageManifest = toml::from_str(manifest).map_err(|e| invalid(format!("typst.toml: {}", e.message())))?;
+    // Parsed rather than built to check the name
+    let spec: PackageSpec = format!("@{}/{}:{}", LOCAL_NAMESPACE, manifest.package.name, manifest.package.version)
+        .parse()
+        .map_err(|e| invalid(format!("typst.toml: {}", e)))?;
+    let entrypoint = manifest.package.entrypoint.trim_start_matches("./");
+    if !normalized.contains_key(entrypoint) {
+        return Err(invalid(format!("{} has no entrypoint '{}'", spec, entrypoint)));
+    }
+    Ok((spec, Arc::new(normalized)))
+}
+
+/// Key files by their normalized relative paths, failing with the first
+/// path that is absolute or leaves the root
+pub(crate) fn normalize_files(files: Vec<(String, Vec<u8>)>) -> Result<HashMap<String, Bytes>, String> {
+    let mut normalized = HashMap::with_capacity(files.len());
+    for (path, data) in files {
+        let parts: Vec<&str> = path.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".").collect();
+        if parts.is_empty() || parts.contains(&"..") || path.starts_with(['/', '\\']) {
+            return Err(path);
+        }
+        normalized.insert(parts.join("/"), Bytes::new(data));
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderEngine;
+
+    fn package(name: &str, version: &str, lib: &str) -> Vec<(String, Vec<u8>)> {
+        let manifest = format!("[package]\nname = \"{}\"\nversion = \"{}\"\nentrypoint = \"src/lib.typ\"", name, version);
+        vec![
+            ("typst.toml".to_string(), manifest.into_bytes()),
+            ("./src/lib.typ".to_string(), lib.as_bytes().to_vec()),
+            ("src/unit.typ".to_string(), b"#let unit = [42nd Wing]".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn test_local_packages_resolve() {
+        let engine = RenderEngine::new();
+        let spec = engine.register_local_package(package("org-kit", "1.2.0", "#import \"unit.typ\": unit")).unwrap();
+        assert_eq!(spec, "@local/org-kit:1.2.0");
+        assert_eq!(engine.scope(local_package_specs), ["@local/org-kit:1.2.0"]);
+
+        let markup = "#import \"@local/org-kit:1.2.0\": unit\nSigned for #unit";
+        let pages = engine.render_markup(markup, None).unwrap();
+        assert!(String::from_utf8_lossy(&pages[0]).contains("<svg"));
+        // Other engines and other versions do not see it
+        assert!(RenderEngine::new().render_markup(markup, None).is_err());
+        assert!(engine.render_markup("#import \"@local/org-kit:1.3.0\": unit", None).is_err());
+
+        assert!(engine.unregister_local_package(&spec));
+        assert!(!engine.unregister_local_package(&spec));
+        assert!(engine.render_markup(markup, None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_packages_are_rejected() {
+        let mut escaping = package("org-kit", "1.0.0", "");
+        escaping.push(("../secrets.typ".to_string(), Vec::new()));
+        let mut no_manifest = package("org-kit", "1.0.0", "");
+        no_manifest.remove(0);
+        let mut no_entrypoint = package("org-kit", "1.0.0", "");
+        no_entrypoint.remove(1);
+        for files in [escaping, no_manifest, no_entrypoint, package("Org Kit", "1.0.0", "")] {
+            let result = RenderEngine::new().register_local_package(files);
+            assert!(matches!(result, Err(TypstWrapperError::Validation(_))), "{:?}", result);
+        }
+
+        let engine = RenderEngine::new();
+        engine.set_registration_limits(crate::RegistrationLimits { max_asset_bytes: 16, ..Default::default() });
+        let result = engine.register_local_package(package("org-kit", "1.0.0", ""));
+        assert!(matches!(result, Err(TypstWrapperError::Registration(_))));
+    }
+}
@@ -0,0 +1,485 @@
+//! Incremental updates of rendered PDFs.
+//!
+//! Post-processing such as signing and PDF/X conversion must not rewrite the bytes of the
+//! rendered PDF, so changes are appended as an incremental update: changed
+//! and new objects, a cross-reference section for them and a trailer that
+//! points back to the previous one. The reader only understands what the
+//! engine writes (classic cross-reference tables and plain dictionaries),
+//! plus the updates made here.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+/// A PDF together with the objects to append to it
+pub(crate) struct PdfUpdate<'a> {
+    original: &'a [u8],
+    /// Byte offset of each object in `original`, by object number
+    offsets: Vec<Option<usize>>,
+    /// Object number of the document catalog
+    root: u32,
+    /// Other trailer entries to carry over (`/Info`, `/ID`)
+    info: Option<String>,
+    id: Option<String>,
+    /// Offset of the last cross-reference section
+    prev: usize,
+    /// Objects to write, in order
+    objects: Vec<(u32, Vec<u8>)>,
+}
+
+impl<'a> PdfUpdate<'a> {
+    /// Read the cross-reference sections and trailer of `pdf`
+    pub(crate) fn parse(pdf: &'a [u8]) -> Result<Self, String> {
+        let tail = &pdf[pdf.len().saturating_sub(1024)..];
+        let at = find_last(tail, b"startxref").ok_or("no startxref")?;
+        let prev: usize = ascii(&tail[at + 9..])
+            .split_whitespace()
+            .next()
+            .and_then(|offset| offset.parse().ok())
+            .ok_or("invalid startxref")?;
+
+        let mut update = PdfUpdate {
+            original: pdf,
+            offsets: Vec::new(),
+            root: 0,
+            info: None,
+            id: None,
+            prev,
+            objects: Vec::new(),
+        };
+        // Newer sections come first and win over the ones they replace
+        let mut section = Some(prev);
+        let mut first = true;
+        while let Some(offset) = section {
+            let trailer = update.read_xref(offset)?;
+            if first {
+                let root = dict_value(&trailer, "Root").ok_or("trailer has no /Root")?;
+                update.root = reference(root).ok_or("invalid /Root")?;
+                update.info = dict_value(&trailer, "Info").map(str::to_string);
+                update.id = dict_value(&trailer, "ID").map(str::to_string);
+                first = false;
+            }
+            section = dict_value(&trailer, "Prev").and_then(|prev| prev.parse().ok());
+        }
+        Ok(update)
+    }
+
+    /// Record the entries of the cross-reference table at `offset` that are
+    /// not known yet, returning its trailer dictionary
+    fn read_xref(&mut self, offset: usize) -> Result<String, String> {
+        let rest = self.original.get(offset..).ok_or("startxref is out of range")?;
+        if !rest.starts_with(b"xref") {
+            return Err("cross-reference streams are not supported".to_string());
+        }
+        let end = find(rest, b"trailer").ok_or("no trailer")?;
+        let table = ascii(&rest[4..end]);
+        let mut lines = table.lines().map(str::trim).filter(|line| !line.is_empty());
+        while let Some(header) = lines.next() {
+            let mut header = header.split_whitespace().map(str::parse::<usize>);
+            let (Some(Ok(start)), Some(Ok(count))) = (header.next(), header.next()) else {
+                return Err("invalid cross-reference subsection".to_string());
+            };
+            for number in start..start + count {
+                let entry = lines.next().ok_or("truncated cross-reference table")?;
+                let mut fields = entry.split_whitespace();
+                let entry_offset = fields.next().and_then(|offset| offset.parse().ok());
+                let in_use = fields.nth(1) == Some("n");
+                if self.offsets.len() <= number {
+                    self.offsets.resize(number + 1, None);
+                }
+                if in_use && self.offsets[number].is_none() {
+                    self.offsets[number] = entry_offset;
+                }
+            }
+        }
+        let trailer = ascii(&rest[end + 7..]);
+        let start = trailer.find("<<").ok_or("invalid trailer")?;
+        Ok(trailer[start..start + dict_len(&trailer[start..]).ok_or("invalid trailer")?].to_string())
+    }
+
+    /// Object number of the document catalog
+    pub(crate) fn root(&self) -> u32 {
+        self.root
+    }
+
+    /// The trailer's reference to the document information dictionary
+    pub(crate) fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
+    /// The body of object `number` between `obj` and `endobj`, as last
+    /// written in the original or this update
+    pub(crate) fn object(&self, number: u32) -> Option<String> {
+        if let Some((_, body)) = self.objects.iter().rev().find(|(n, _)| *n == number) {
+            return Some(String::from_utf8_lossy(body).into_owned());
+        }
+        let offset = (*self.offsets.get(number as usize)?)?;
+        let rest = self.original.get(offset..)?;
+        let start = find(rest, b"obj")? + 3;
+        let end = find(rest, b"endobj")?;
+        Some(ascii(rest.get(start..end)?).trim().to_string())
+    }
+
+    /// The object numbers of the pages, in order
+    pub(crate) fn pages(&self) -> Result<Vec<u32>, String> {
+        let catalog = self.object(self.root).ok_or("missing catalog")?;
+        let tree = dict_value(&catalog, "Pages").and_then(reference).ok_or("catalog has no /Pages")?;
+        let mut pages = Vec::new();
+        self.collect_pages(tree, &mut pages, 0)?;
+        Ok(pages)
+    }
+
+    fn collect_pages(&self, node: u32, pages: &mut Vec<u32>, depth: usize) -> Result<(), String> {
+        if depth > 32 {
+            return Err("page tree is too deep".to_string());
+        }
+        let object = self.object(node).ok_or("missing page tree node")?;
+        match dict_value(&object, "Kids") {
+            Some(kids) => {
+                for kid in references(kids) {
+                    self.collect_pages(kid, pages, depth + 1)?;
+                }
+            }
+            None => pages.push(node),
+        }
+        Ok(())
+    }
+
+    /// Width and height of a page's media box, in points
+    pub(crate) fn page_size(&self, page: u32) -> Option<(f64, f64)> {
+        let object = self.object(page)?;
+        let media_box: Vec<f64> = dict_value(&object, "MediaBox")?
+            .trim_matches(['[', ']'])
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match media_box[..] {
+            [left, bottom, right, top] => Some((right - left, top - bottom)),
+            _ => None,
+        }
+    }
+
+    /// Append annotation object `annotation` to the annotations of `page`
+    pub(crate) fn add_annotation(&mut self, page: u32, annotation: u32) -> Result<(), String> {
+        let object = self.object(page).ok_or("missing page")?;
+        let mut annotations = match dict_value(&object, "Annots") {
+            Some(value) if value.starts_with('[') => references(value),
+            Some(_) => return Err("indirect page /Annots are not supported".to_string()),
+            None => Vec::new(),
+        };
+        annotations.push(annotation);
+        let object = with_entry(&object, "Annots", &reference_array(&annotations)).ok_or("invalid page")?;
+        self.set(page, object);
+        Ok(())
+    }
+
+    /// A new object number
+    pub(crate) fn allocate(&mut self) -> u32 {
+        let next = self.size() as u32;
+        self.objects.push((next, Vec::new()));
+        next
+    }
+
+    /// Set the body of object `number`, replacing the original object or an
+    /// earlier body from this update
+    pub(crate) fn set(&mut self, number: u32, body: impl Into<Vec<u8>>) {
+        let body = body.into();
+        match self.objects.iter_mut().find(|(n, _)| *n == number) {
+            Some((_, existing)) => *existing = body,
+            None => self.objects.push((number, body)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        let written = self.objects.iter().map(|(n, _)| *n as usize + 1).max().unwrap_or(0);
+        self.offsets.len().max(written)
+    }
+
+    /// The original PDF with the update appended, and the offset of each
+    /// appended object
+    pub(crate) fn finish(mut self) -> (Vec<u8>, Vec<(u32, usize)>) {
+        self.objects.sort_by_key(|(number, _)| *number);
+        let size = self.size();
+        let mut pdf = self.original.to_vec();
+        if !pdf.ends_with(b"\n") {
+            pdf.push(b'\n');
+        }
+        let mut offsets = Vec::new();
+        for (number, body) in &self.objects {
+            offsets.push((*number, pdf.len()));
+            pdf.extend_from_slice(format!("{} 0 obj\n", number).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(b"\nendobj\n\n");
+        }
+
+        let xref = pdf.len();
+        let mut table = String::from("xref\n");
+        // One subsection per run of consecutive object numbers
+        let mut run = 0;
+        while run < offsets.len() {
+            let mut end = run + 1;
+            while end < offsets.len() && offsets[end].0 == offsets[end - 1].0 + 1 {
+                end += 1;
+            }
+            let _ = writeln!(table, "{} {}", offsets[run].0, end - run);
+            for (_, offset) in &offsets[run..end] {
+                let _ = write!(table, "{:010} 00000 n\r\n", offset);
+            }
+            run = end;
+        }
+        let _ = write!(table, "trailer\n<<\n  /Size {}\n  /Root {} 0 R\n", size, self.root);
+        if let Some(info) = &self.info {
+            let _ = writeln!(table, "  /Info {}", info);
+        }
+        if let Some(id) = &self.id {
+            let _ = writeln!(table, "  /ID {}", id);
+        }
+        let _ = write!(table, "  /Prev {}\n>>\nstartxref\n{}\n%%EOF\n", self.prev, xref);
+        pdf.extend_from_slice(table.as_bytes());
+        (pdf, offsets)
+    }
+}
+
+/// The raw value of `key` in the dictionary that `object` starts with
+pub(crate) fn dict_value<'d>(object: &'d str, key: &str) -> Option<&'d str> {
+    entries(object)?.into_iter().find(|(name, _)| *name == key).map(|(_, value)| value)
+}
+
+/// `object`'s dictionary with `key` set to `value`, keeping what follows
+/// the dictionary (such as a stream)
+pub(crate) fn with_entry(object: &str, key: &str, value: &str) -> Option<String> {
+    replace_entry(object, key, Some(value))
+}
+
+/// `object`'s dictionary without `key`
+pub(crate) fn without_entry(object: &str, key: &str) -> Option<String> {
+    replace_entry(object, key, None)
+}
+
+fn replace_entry(object: &str, key: &str, value: Option<&str>) -> Option<String> {
+    let start = object.find("<<")?;
+    let len = dict_len(&object[start..])?;
+    let mut dict = String::from("<<\n");
+    for (name, existing) in entries(object)? {
+        if name != key {
+            let _ = writeln!(dict, "  /{} {}", name, existing);
+        }
+    }
+    if let Some(value) = value {
+        let _ = writeln!(dict, "  /{} {}", key, value);
+    }
+    dict.push_str(">>");
+    Some(format!("{}{}{}", &object[..start], dict, &object[start + len..]))
+}
+
+/// The object numbers of the indirect references in `value`
+pub(crate) fn references(value: &str) -> Vec<u32> {
+    let tokens: Vec<&str> = value.split(|c: char| c.is_whitespace() || "[]".contains(c)).filter(|t| !t.is_empty()).collect();
+    tokens
+        .windows(3)
+        .filter(|window| window[2] == "R")
+        .filter_map(|window| window[0].parse().ok().filter(|_| window[1].parse::<u32>().is_ok()))
+        .collect()
+}
+
+/// The object number of a single indirect reference
+pub(crate) fn reference(value: &str) -> Option<u32> {
+    match references(value)[..] {
+        [number] => Some(number),
+        _ => None,
+    }
+}
+
+/// An array of references to the objects `numbers`
+pub(crate) fn reference_array(numbers: &[u32]) -> String {
+    let references: Vec<String> = numbers.iter().map(|number| format!("{} 0 R", number)).collect();
+    format!("[{}]", references.join(" "))
+}
+
+/// Index among the pages of an exported PDF of the document page `page`,
+/// the PDF having the document pages `exported`
+pub(crate) fn exported_page(page: usize, exported: &Range<usize>) -> Option<usize> {
+    exported.contains(&page).then(|| page - exported.start)
+}
+
+/// A PDF text string: literal for ASCII, UTF-16 hex otherwise
+pub(crate) fn text_string(text: &str) -> String {
+    if text.is_ascii() {
+        let mut literal = String::from("(");
+        for c in text.chars() {
+            if matches!(c, '(' | ')' | '\\') {
+                literal.push('\\');
+            }
+            literal.push(c);
+        }
+        literal.push(')');
+        literal
+    } else {
+        let mut hex = String::from("<FEFF");
+        for unit in text.encode_utf16() {
+            let _ = write!(hex, "{:04X}", unit);
+        }
+        hex.push('>');
+        hex
+    }
+}
+
+/// Top-level `(key, raw value)` pairs of the dictionary `object` starts with
+fn entries(object: &str) -> Option<Vec<(&str, &str)>> {
+    let start = object.find("<<")?;
+    let dict = &object[start..start + dict_len(&object[start..])?];
+    let inner = &dict[2..dict.len() - 2];
+    let mut entries = Vec::new();
+    let mut rest = inner.trim_start();
+    while let Some(after) = rest.strip_prefix('/') {
+        let key_len = after.find(|c: char| c.is_whitespace() || "/[<(".contains(c)).unwrap_or(after.len());
+        let key = &after[..key_len];
+        let value_start = after[key_len..].trim_start();
+        let value_len = value_len(value_start)?;
+        entries.push((key, value_start[..value_len].trim_end()));
+        rest = value_start[value_len..].trim_start();
+    }
+    Some(entries)
+}
+
+/// Length of the single value (including a trailing `N G R`) at the start
+/// of `text`
+fn value_len(text: &str) -> Option<usize> {
+    if text.starts_with("<<") {
+        return dict_len(text);
+    }
+    let bytes = text.as_bytes();
+    match bytes.first()? {
+        b'[' | b'(' | b'<' => {
+            let (open, close) = match bytes[0] {
+                b'[' => (b'[', b']'),
+                b'(' => (b'(', b')'),
+                _ => (b'<', b'>'),
+            };
+            let mut depth = 0;
+            let mut escaped = false;
+            for (i, &b) in bytes.iter().enumerate() {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' && open == b'(' {
+                    escaped = true;
+                } else if b == open {
+                    depth += 1;
+                } else if b == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i + 1);
+                    }
+                }
+            }
+            None
+        }
+        b'/' => Some(1 + text[1..].find(|c: char| c.is_whitespace() || "/[]<>()".contains(c)).unwrap_or(text.len() - 1)),
+        _ => {
+            // A number, keyword or indirect reference
+            let token = |s: &str| s.find(|c: char| c.is_whitespace() || "/[]<>()".contains(c)).unwrap_or(s.len());
+            let first = token(text);
+            let rest = text[first..].trim_start();
+            let generation = token(rest);
+            let after = rest[generation..].trim_start();
+            let is_reference = text[..first].parse::<u32>().is_ok()
+                && rest[..generation].parse::<u32>().is_ok()
+                && after.starts_with('R')
+                && after[1..].chars().next().is_none_or(|c| c.is_whitespace() || "/[]<>()".contains(c));
+            if is_reference {
+                return Some(text.len() - after.len() + 1);
+            }
+            Some(first)
+        }
+    }
+}
+
+/// Length of the `<< ... >>` dictionary at the start of `text`
+fn dict_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    let mut in_string = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_string > 0 => i += 1,
+            b'(' => in_string += 1,
+            b')' if in_string > 0 => in_string -= 1,
+            b'<' if in_string == 0 && bytes.get(i + 1) == Some(&b'<') => {
+                depth += 1;
+                i += 1;
+            }
+            b'>' if in_string == 0 && bytes.get(i + 1) == Some(&b'>') => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_entries() {
+        let object = "<<\n  /Type /Page\n  /Parent 3 0 R\n  /MediaBox [0 0 612 792]\n  /Resources << /Font << /F1 5 0 R >> >>\n  /Title (a \\) b)\n>>";
+        assert_eq!(dict_value(object, "Type"), Some("/Page"));
+        assert_eq!(dict_value(object, "Parent"), Some("3 0 R"));
+        assert_eq!(dict_value(object, "MediaBox"), Some("[0 0 612 792]"));
+        assert_eq!(dict_value(object, "Resources"), Some("<< /Font << /F1 5 0 R >> >>"));
+        assert_eq!(dict_value(object, "Title"), Some("(a \\) b)"));
+        assert_eq!(dict_value(object, "F1"), None);
+        let updated = with_entry(object, "Annots", "[9 0 R]").unwrap();
+        assert_eq!(dict_value(&updated, "Annots"), Some("[9 0 R]"));
+        assert_eq!(dict_value(&updated, "Parent"), Some("3 0 R"));
+        let removed = without_entry(&updated, "Parent").unwrap();
+        assert_eq!(dict_value(&removed, "Parent"), None);
+        assert_eq!(dict_value(&removed, "Annots"), Some("[9 0 R]"));
+        assert_eq!(references("[4 0 R 12 0 R]"), [4, 12]);
+        assert_eq!(text_string("Ünit (1)"), "<FEFF00DC006E006900740020002800310029>");
+        assert_eq!(text_string("a (b)"), "(a \\(b\\))");
+    }
+
+    #[test]
+    fn test_incremental_update_is_readable() {
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let pdf = crate::render_markup("= One\n#pagebreak()\n= Two", Some(config)).unwrap().remove(0);
+
+        let mut update = PdfUpdate::parse(&pdf).unwrap();
+        let pages = update.pages().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(update.page_size(pages[0]), Some((595.2756, 841.8898)));
+        let page = update.object(pages[1]).unwrap();
+        update.set(pages[1], with_entry(&page, "Rotate", "90").unwrap());
+        let extra = update.allocate();
+        update.set(extra, "(added)");
+        let (updated, _) = update.finish();
+        assert!(updated.starts_with(&pdf));
+
+        // The update chains back to the original sections
+        let reread = PdfUpdate::parse(&updated).unwrap();
+        assert_eq!(reread.pages().unwrap(), pages);
+        assert_eq!(dict_value(&reread.object(pages[1]).unwrap(), "Rotate"), Some("90"));
+        assert_eq!(reread.object(extra).as_deref(), Some("(added)"));
+        assert_eq!(reread.object(pages[0]), PdfUpdate::parse(&pdf).unwrap().object(pages[0]));
+    }
+}
@@ -0,0 +1,561 @@
+//! PDF/X print-ready output.
+//!
+//! Print shops ask for PDF/X: embedded fonts, an output intent whose ICC
+//! profile describes the printing condition, trim boxes and no live
+//! transparency. With a [`PrintProfile`] in
+//! [`RenderConfig::print`](crate::RenderConfig::print), PDF output becomes
+//! PDF/X-4 with flattened transparency:
+//!
+//! - Before export, translucent colors and images that sit on blank paper
+//!   are blended with the paper color, and the watermark is drawn beneath the
+//!   page content so it blends with the paper as well. Transparency over
+//!   other content cannot be flattened without rasterizing the page and is
+//!   reported, together with every other requirement the document misses, as
+//!   a single [`TypstWrapperError::OutputFormat`].
+//! - After export, the PDF/X identification, the output intent and the trim
+//!   boxes are added as an incremental update.
+//!
+//! SVG images are not looked into; one that mentions opacity, masks or
+//! `rgba()` colors is reported as possibly transparent.
+
+use base64::Engine as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use typst::foundations::{Bytes, Datetime, Dict, FromValue, Smart, Value};
+use typst::layout::{Frame, FrameItem, PagedDocument, Point, Transform};
+use typst::visualize::{
+    Color, ColorSpace, Curve, CurveItem, Geometry, Image, ImageKind, Paint, PixelFormat, RasterImage, WeightedColor,
+};
+
+use crate::pdf::{dict_value, reference, with_entry, without_entry, PdfUpdate};
+use crate::typst_wrapper::{OutputFormat, RenderConfig, TypstWrapperError};
+
+/// PDF/X version written to the metadata
+const PDFX_VERSION: &str = "PDF/X-4";
+
+/// The printing condition a PDF/X file is prepared for
+///
+/// Without an ICC profile, the sRGB profile the PDF export embeds for its
+/// colors describes the output. In JSON, `icc_profile` is base64-encoded.
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, OutputFormat, PrintProfile, RenderConfig};
+///
+/// let config = RenderConfig {
+///     format: OutputFormat::Pdf,
+///     print: Some(PrintProfile::default()),
+///     ..RenderConfig::default()
+/// };
+/// let pdf = render_markup("#set document(title: \"Flyer\")\n= Hello", Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintProfile {
+    /// Name of the printing condition, such as `FOGRA39` or `CGATS TR 001`
+    pub output_condition_identifier: String,
+    /// Human-readable description of the printing condition
+    pub output_condition: Option<String>,
+    /// Registry the identifier comes from, such as `http://www.color.org`
+    pub registry_name: Option<String>,
+    /// ICC output profile of the printing condition (RGB, CMYK or gray)
+    #[serde(serialize_with = "serialize_base64", deserialize_with = "deserialize_base64")]
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl Default for PrintProfile {
+    fn default() -> Self {
+        Self {
+            output_condition_identifier: "sRGB IEC61966-2.1".to_string(),
+            output_condition: None,
+            registry_name: None,
+            icc_profile: None,
+        }
+    }
+}
+
+impl PrintProfile {
+    /// Number of color components of the ICC profile, checking its header
+    pub(crate) fn icc_components(icc: &[u8]) -> Result<u8, String> {
+        if icc.len() < 128 || &icc[36..40] != b"acsp" {
+            return Err("the ICC profile is not a valid ICC profile".to_string());
+        }
+        match &icc[16..20] {
+            b"RGB " => Ok(3),
+            b"CMYK" => Ok(4),
+            b"GRAY" => Ok(1),
+            space => Err(format!(
+                "the ICC profile's color space '{}' is not RGB, CMYK or gray",
+                String::from_utf8_lossy(space).trim()
+            )),
+        }
+    }
+}
+
+fn serialize_base64<S: Serializer>(icc: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+    icc.as_ref()
+        .map(|icc| base64::engine::general_purpose::STANDARD.encode(icc))
+        .serialize(serializer)
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|icc| base64::engine::general_purpose::STANDARD.decode(icc).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Flatten the transparency of a compiled document for `config.print`,
+/// reporting every PDF/X requirement it cannot meet
+pub(crate) fn prepare(document: &mut PagedDocument, config: &RenderConfig) -> Result<(), TypstWrapperError> {
+    let Some(profile) = &config.print else {
+        return Ok(());
+    };
+    let mut problems = Vec::new();
+    if !matches!(config.format, OutputFormat::Pdf) {
+        problems.push("print profiles need PDF output".to_string());
+    }
+    if let Some(Err(e)) = profile.icc_profile.as_deref().map(PrintProfile::icc_components) {
+        problems.push(e);
+    }
+    if !config.annotations.is_empty() {
+        problems.push("PDF/X does not allow review annotations on the page".to_string());
+    }
+    if config.form_fields {
+        problems.push("PDF/X does not allow form fields on the page".to_string());
+    }
+    if document.info.title.is_none() {
+        problems.push("PDF/X needs a document title (set one with `#set document(title: ..)`)".to_string());
+    }
+    for (index, page) in document.pages.iter_mut().enumerate() {
+        let paper = match &page.fill {
+            Smart::Custom(Some(Paint::Solid(color))) if color.alpha().is_none_or(|alpha| alpha >= 1.0) => *color,
+            _ => Color::WHITE,
+        };
+        let mut flattener = Flattener { paper, ink: Vec::new(), problems: Vec::new() };
+        flattener.frame(&mut page.frame, Transform::identity());
+        for problem in flattener.problems {
+            let problem = format!("page {}: {}", index + 1, problem);
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(TypstWrapperError::OutputFormat(format!(
+            "the PDF/X print profile cannot be satisfied: {}",
+            problems.join("; ")
+        )))
+    }
+}
+
+/// The current time, for the creation and modification dates PDF/X requires
+pub(crate) fn timestamp() -> Option<typst_pdf::Timestamp> {
+    utc_now().map(typst_pdf::Timestamp::new_utc)
+}
+
+/// The current UTC date and time, from the host's clock if it installed
+/// one (see [`today`](crate::today))
+pub(crate) fn utc_now() -> Option<Datetime> {
+    utc_datetime(crate::today::now()?.timestamp_ms.div_euclid(1000))
+}
+
+/// The UTC date and time `seconds` after 1970-01-01T00:00:00Z
+pub(crate) fn utc_datetime(seconds: i64) -> Option<Datetime> {
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let era_days = days + 719_468;
+    let era = era_days.div_euclid(146_097);
+    let day_of_era = era_days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    Datetime::from_ymd_hms(
+        year as i32,
+        month as u8,
+        day as u8,
+        (time / 3600) as u8,
+        (time % 3600 / 60) as u8,
+        (time % 60) as u8,
+    )
+}
+
+/// Turn a PDF exported as PDF/A-2b into PDF/X-4 for `profile`
+pub(crate) fn finish_pdf(pdf: &[u8], profile: &PrintProfile) -> Result<Vec<u8>, TypstWrapperError> {
+    let error = |e: String| TypstWrapperError::OutputFormat(format!("PDF/X conversion failed: {}", e));
+    let mut update = PdfUpdate::parse(pdf).map_err(error)?;
+    let catalog = update.object(update.root()).ok_or_else(|| error("missing catalog".to_string()))?;
+
+    // Replace the PDF/A output intent with one for the printing condition
+    let intents = dict_value(&catalog, "OutputIntents").ok_or_else(|| error("no output intent".to_string()))?;
+    let mut destination = dict_value(intents, "DestOutputProfile")
+        .and_then(reference)
+        .ok_or_else(|| error("no output profile".to_string()))?;
+    if let Some(icc) = &profile.icc_profile {
+        let components = PrintProfile::icc_components(icc).map_err(error)?;
+        destination = update.allocate();
+        let mut stream = format!("<<\n  /N {}\n  /Length {}\n>>\nstream\n", components, icc.len()).into_bytes();
+        stream.extend_from_slice(icc);
+        stream.extend_from_slice(b"\nendstream");
+        update.set(destination, stream);
+    }
+    let mut intent = format!(
+        "<<\n    /Type /OutputIntent\n    /S /GTS_PDFX\n    /OutputConditionIdentifier {}\n",
+        crate::pdf::text_string(&profile.output_condition_identifier)
+    );
+    for (key, value) in [("OutputCondition", &profile.output_condition), ("RegistryName", &profile.registry_name)] {
+        if let Some(value) = value {
+            intent.push_str(&format!("    /{} {}\n", key, crate::pdf::text_string(value)));
+        }
+    }
+    intent.push_str(&format!("    /DestOutputProfile {} 0 R\n  >>", destination));
+    let catalog = with_entry(&catalog, "OutputIntents", &format!("[{}]", intent))
+        .ok_or_else(|| error("invalid catalog".to_string()))?;
+    let root = update.root();
+    update.set(root, catalog.clone());
+
+    // Identify the file as PDF/X in its metadata and document information
+    let metadata = dict_value(&catalog, "Metadata").and_then(reference).ok_or_else(|| error("no metadata".to_string()))?;
+    let xmp = update.object(metadata).ok_or_else(|| error("missing metadata".to_string()))?;
+    update.set(metadata, pdfx_metadata(&xmp).ok_or_else(|| error("invalid metadata".to_string()))?);
+    if let Some(info) = update.info().and_then(reference) {
+        let object = update.object(info).ok_or_else(|| error("missing document information".to_string()))?;
+        let object = with_entry(&object, "GTS_PDFXVersion", &crate::pdf::text_string(PDFX_VERSION))
+            .and_then(|object| with_entry(&object, "Trapped", "/False"))
+            .ok_or_else(|| error("invalid document information".to_string()))?;
+        update.set(info, object);
+    }
+
+    // Trim to the media box and drop the page transparency groups, which
+    // have nothing left to blend
+    for page in update.pages().map_err(error)? {
+        let object = update.object(page).ok_or_else(|| error("missing page".to_string()))?;
+        let media_box = dict_value(&object, "MediaBox").ok_or_else(|| error("page has no /MediaBox".to_string()))?;
+        let object = with_entry(&object, "TrimBox", media_box)
+            .and_then(|object| without_entry(&object, "Group"))
+            .ok_or_else(|| error("invalid page".to_string()))?;
+        update.set(page, object);
+    }
+
+    let (mut pdf, _) = update.finish();
+    // PDF/X-4 is based on PDF 1.6; the header has the same length either way
+    if pdf.starts_with(b"%PDF-1.7") {
+        pdf[5..8].copy_from_slice(b"1.6");
+    }
+    Ok(pdf)
+}
+
+/// A metadata stream object identifying the file as PDF/X instead of PDF/A
+fn pdfx_metadata(object: &str) -> Option<String> {
+    let start = object.find("stream")? + "stream".len();
+    let end = object.rfind("endstream")?;
+    let mut xmp = object[start..end].trim_matches(['\r', '\n']).to_string();
+    for element in ["pdfaid:part", "pdfaid:conformance"] {
+        if let Some(open) = xmp.find(&format!("<{}>", element)) {
+            let close = xmp[open..].find(&format!("</{}>", element))? + open + element.len() + 3;
+            xmp.replace_range(open..close, "");
+        }
+    }
+    xmp = xmp.replace("<pdf:PDFVersion>1.7</pdf:PDFVersion>", "<pdf:PDFVersion>1.6</pdf:PDFVersion>");
+    let description = format!(
+        "<rdf:Description rdf:about=\"\" xmlns:pdfxid=\"http://www.npes.org/pdfx/ns/id/\" \
+         xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"><pdfxid:GTS_PDFXVersion>{}</pdfxid:GTS_PDFXVersion>\
+         <pdf:Trapped>False</pdf:Trapped></rdf:Description>",
+        PDFX_VERSION
+    );
+    xmp.insert_str(xmp.find("</rdf:RDF>")?, &description);
+    let dict = with_entry(&object[..object.find("stream")?], "Length", &xmp.len().to_string())?;
+    Some(format!("{}\nstream\n{}\nendstream", dict.trim_end(), xmp))
+}
+
+/// A rectangle placed on the page, as its four corners in page coordinates
+#[derive(Clone, Copy)]
+struct Region([Point; 4]);
+
+impl Region {
+    /// The rectangle from `min` to `max` in local coordinates, after `transform`
+    fn new(min: Point, max: Point, transform: Transform) -> Self {
+        Region(
+            [min, Point::new(max.x, min.y), max, Point::new(min.x, max.y)].map(|corner| corner.transform(transform)),
+        )
+    }
+
+    /// Whether the regions intersect, by looking for an edge that separates
+    /// them (rotated text covers a thin band, not its whole bounding box)
+    fn overlaps(&self, other: &Region) -> bool {
+        !self.separates(other) && !other.separates(self)
+    }
+
+    /// Whether one of this region's edges separates it from `other`
+    fn separates(&self, other: &Region) -> bool {
+        (0..4).any(|i| {
+            let (start, end) = (self.0[i], self.0[(i + 1) % 4]);
+            let normal = ((start.y - end.y).to_pt(), (end.x - start.x).to_pt());
+            if normal == (0.0, 0.0) {
+                return false;
+            }
+            let extent = |region: &Region| {
+                region.0.iter().map(|point| point.x.to_pt() * normal.0 + point.y.to_pt() * normal.1).fold(
+                    (f64::INFINITY, f64::NEG_INFINITY),
+                    |(min, max), projected| (min.min(projected), max.max(projected)),
+                )
+            };
+            let ((own_min, own_max), (other_min, other_max)) = (extent(self), extent(other));
+            own_max <= other_min || other_max <= own_min
+        })
+    }
+}
+
+/// Walks a page in drawing order, flattening what sits on blank paper
+struct Flattener {
+    paper: Color,
+    /// Everything drawn so far
+    ink: Vec<Region>,
+    problems: Vec<String>,
+}
+
+impl Flattener {
+    fn frame(&mut self, frame: &mut Frame, transform: Transform) {
+        let mut items: Vec<(Point, FrameItem)> = frame.items().cloned().collect();
+        for (pos, item) in &mut items {
+            let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+            match item {
+                FrameItem::Group(group) => self.frame(&mut group.frame, transform.pre_concat(group.transform)),
+                FrameItem::Text(text) => {
+                    let spread = text.stroke.as_ref().map_or(Default::default(), |stroke| stroke.thickness);
+                    let region = Region::new(
+                        Point::new(-spread, -text.size - spread),
+                        Point::new(text.width() + spread, text.size * 0.3 + spread),
+                        transform,
+                    );
+                    let on_paper = self.on_paper(region);
+                    self.paint(&mut text.fill, on_paper, "text");
+                    if let Some(stroke) = &mut text.stroke {
+                        self.paint(&mut stroke.paint, on_paper, "text outline");
+                    }
+                    self.ink.push(region);
+                }
+                FrameItem::Shape(shape, _) => {
+                    let (min, max) = geometry_bounds(&shape.geometry);
+                    let spread = shape.stroke.as_ref().map_or(Default::default(), |stroke| stroke.thickness / 2.0);
+                    let region = Region::new(
+                        Point::new(min.x - spread, min.y - spread),
+                        Point::new(max.x + spread, max.y + spread),
+                        transform,
+                    );
+                    let on_paper = self.on_paper(region);
+                    if let Some(fill) = &mut shape.fill {
+                        self.paint(fill, on_paper, "shape");
+                    }
+                    if let Some(stroke) = &mut shape.stroke {
+                        self.paint(&mut stroke.paint, on_paper, "line");
+                    }
+                    self.ink.push(region);
+                }
+                FrameItem::Image(image, size, _) => {
+                    let region = Region::new(Point::zero(), size.to_point(), transform);
+                    let on_paper = self.on_paper(region);
+                    self.image(image, on_paper);
+                    self.ink.push(region);
+                }
+                FrameItem::Link(..) | FrameItem::Tag(_) => {}
+            }
+        }
+        frame.clear();
+        frame.push_multiple(items);
+    }
+
+    /// Whether nothing has been drawn under `region` yet
+    fn on_paper(&self, region: Region) -> bool {
+        !self.ink.iter().any(|ink| ink.overlaps(&region))
+    }
+
+    fn paint(&mut self, paint: &mut Paint, on_paper: bool, what: &str) {
+        match paint {
+            Paint::Solid(color) if color.alpha().is_some_and(|alpha| alpha < 1.0) => {
+                if on_paper {
+                    *color = blend(*color, self.paper);
+                } else {
+                    self.problems.push(format!("translucent {} over other content", what));
+                }
+            }
+            Paint::Solid(_) => {}
+            Paint::Gradient(gradient) => {
+                if gradient.stops_ref().iter().any(|(color, _)| color.alpha().is_some_and(|alpha| alpha < 1.0)) {
+                    self.problems.push(format!("{} with a translucent gradient", what));
+                }
+            }
+            Paint::Tiling(tiling) => {
+                if frame_has_transparency(tiling.frame()) {
+                    self.problems.push(format!("{} with a translucent tiling", what));
+                }
+            }
+        }
+    }
+
+    fn image(&mut self, image: &mut Image, on_paper: bool) {
+        match image.kind() {
+            ImageKind::Raster(raster) if raster.dynamic().color().has_alpha() => {
+                let pixels = raster.dynamic().to_rgba8().into_raw();
+                let translucent = pixels.chunks_exact(4).any(|pixel| pixel[3] < 255);
+                if translucent && !on_paper {
+                    self.problems.push("translucent image over other content".to_string());
+                    return;
+                }
+                let [paper_r, paper_g, paper_b, _] = self.paper.to_rgb().to_vec4_u8();
+                let mut rgb = Vec::with_capacity(pixels.len() / 4 * 3);
+                for pixel in pixels.chunks_exact(4) {
+                    let alpha = u32::from(pixel[3]);
+                    for (channel, paper) in pixel[..3].iter().zip([paper_r, paper_g, paper_b]) {
+                        rgb.push(((u32::from(*channel) * alpha + u32::from(paper) * (255 - alpha) + 127) / 255) as u8);
+                    }
+                }
+                match opaque_image(image, raster, rgb) {
+                    Ok(opaque) => *image = opaque,
+                    Err(e) => self.problems.push(format!("image could not be flattened: {}", e)),
+                }
+            }
+            ImageKind::Raster(_) => {}
+            ImageKind::Svg(svg) => {
+                let data = svg.data().as_slice();
+                let mentions = |needle: &[u8]| data.windows(needle.len()).any(|window| window == needle);
+                if mentions(b"opacity") || mentions(b"mask") || mentions(b"rgba(") {
+                    self.problems.push("SVG image that may be transparent".to_string());
+                }
+            }
+        }
+    }
+}
+
+/// `image` with its pixels replaced by the opaque `rgb` ones
+fn opaque_image(image: &Image, raster: &RasterImage, rgb: Vec<u8>) -> Result<Image, String> {
+    let mut format = Dict::new();
+    format.insert("encoding".into(), Value::Str("rgb8".into()));
+    format.insert("width".into(), Value::Int(raster.width().into()));
+    format.insert("height".into(), Value::Int(raster.height().into()));
+    let format = PixelFormat::from_value(Value::Dict(format)).map_err(|e| e.message().to_string())?;
+    let icc = raster.icc().cloned().map_or(Smart::Auto, Smart::Custom);
+    let raster = RasterImage::new(Bytes::new(rgb), format, icc).map_err(|e| e.to_string())?;
+    Ok(Image::new(raster, image.alt().map(Into::into), image.scaling()))
+}
+
+/// `color` without its transparency, as it looks on `paper`
+fn blend(color: Color, paper: Color) -> Color {
+    let alpha = f64::from(color.alpha().unwrap_or(1.0));
+    let opaque = color.with_alpha(1.0);
+    let space = if color.space() == ColorSpace::Cmyk { ColorSpace::Cmyk } else { ColorSpace::Srgb };
+    Color::mix_iter([WeightedColor::new(opaque, alpha), WeightedColor::new(paper, 1.0 - alpha)], space)
+        .unwrap_or(opaque)
+}
+
+/// Whether anything in `frame` is translucent
+fn frame_has_transparency(frame: &Frame) -> bool {
+    let translucent = |paint: &Paint| match paint {
+        Paint::Solid(color) => color.alpha().is_some_and(|alpha| alpha < 1.0),
+        Paint::Gradient(gradient) => gradient.stops_ref().iter().any(|(color, _)| color.alpha().is_some_and(|alpha| alpha < 1.0)),
+        Paint::Tiling(tiling) => frame_has_transparency(tiling.frame()),
+    };
+    frame.items().any(|(_, item)| match item {
+        FrameItem::Group(group) => frame_has_transparency(&group.frame),
+        FrameItem::Text(text) => translucent(&text.fill) || text.stroke.as_ref().is_some_and(|stroke| translucent(&stroke.paint)),
+        FrameItem::Shape(shape, _) => {
+            shape.fill.as_ref().is_some_and(translucent) || shape.stroke.as_ref().is_some_and(|stroke| translucent(&stroke.paint))
+        }
+        FrameItem::Image(image, ..) => match image.kind() {
+            ImageKind::Raster(raster) => raster.dynamic().color().has_alpha(),
+            ImageKind::Svg(_) => true,
+        },
+        FrameItem::Link(..) | FrameItem::Tag(_) => false,
+    })
+}
+
+/// Bounds of a shape's geometry relative to its position
+fn geometry_bounds(geometry: &Geometry) -> (Point, Point) {
+    match geometry {
+        Geometry::Line(to) => (to.min(Point::zero()), to.max(Point::zero())),
+        Geometry::Rect(size) => (Point::zero(), size.to_point()),
+        Geometry::Curve(Curve(items)) => {
+            let mut points = items.iter().flat_map(|item| match item {
+                CurveItem::Move(point) | CurveItem::Line(point) => vec![*point],
+                CurveItem::Cubic(a, b, c) => vec![*a, *b, *c],
+                CurveItem::Close => vec![],
+            });
+            let first = points.next().unwrap_or_default();
+            points.fold((first, first), |(min, max), point| (min.min(point), max.max(point)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print_config() -> RenderConfig {
+        RenderConfig { format: OutputFormat::Pdf, print: Some(PrintProfile::default()), ..RenderConfig::default() }
+    }
+
+    #[test]
+    fn test_print_profile_serialization() {
+        let profile: PrintProfile =
+            serde_json::from_str(r#"{"output_condition_identifier": "FOGRA39", "icc_profile": "AAEC"}"#).unwrap();
+        assert_eq!(profile.output_condition_identifier, "FOGRA39");
+        assert_eq!(profile.icc_profile, Some(vec![0, 1, 2]));
+        assert_eq!(serde_json::from_value::<PrintProfile>(serde_json::to_value(&profile).unwrap()).unwrap(), profile);
+        assert!(PrintProfile::icc_components(&[0; 16]).is_err());
+
+        let mut icc = vec![0; 128];
+        icc[16..20].copy_from_slice(b"CMYK");
+        icc[36..40].copy_from_slice(b"acsp");
+        assert_eq!(PrintProfile::icc_components(&icc), Ok(4));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_pdfx_output() {
+        // The translucent rectangle and the watermark sit on blank paper
+        let markup = "#set document(title: \"Flyer\")\n= Hello\n#rect(width: 2cm, fill: red.transparentize(50%))";
+        let config = RenderConfig { watermark: Some(crate::Watermark::default()), ..print_config() };
+        let pdf = crate::render_markup(markup, Some(config)).unwrap().remove(0);
+        assert!(pdf.starts_with(b"%PDF-1.6"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(!text.contains("/SMask") && !text.contains("/ca 0."));
+
+        let update = PdfUpdate::parse(&pdf).unwrap();
+        let catalog = update.object(update.root()).unwrap();
+        let intents = dict_value(&catalog, "OutputIntents").unwrap();
+        assert!(intents.contains("/S /GTS_PDFX") && !intents.contains("GTS_PDFA1"));
+        let page = update.object(update.pages().unwrap()[0]).unwrap();
+        assert_eq!(dict_value(&page, "TrimBox"), dict_value(&page, "MediaBox"));
+        assert_eq!(dict_value(&page, "Group"), None);
+        let info = update.object(update.info().and_then(reference).unwrap()).unwrap();
+        assert_eq!(dict_value(&info, "Trapped"), Some("/False"));
+        assert!(dict_value(&info, "CreationDate").is_some());
+        let metadata = update.object(dict_value(&catalog, "Metadata").and_then(reference).unwrap()).unwrap();
+        assert!(metadata.contains("<pdfxid:GTS_PDFXVersion>PDF/X-4</pdfxid:GTS_PDFXVersion>"));
+        assert!(!metadata.contains("pdfaid:part"));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_unmet_requirements_are_reported() {
+        let markup = "= Hello\n#place(top, rect(width: 100%, height: 2cm, fill: blue.transparentize(50%)))";
+        let Err(TypstWrapperError::OutputFormat(message)) = crate::render_markup(markup, Some(print_config())) else {
+            panic!("expected the print profile to be rejected");
+        };
+        assert!(message.contains("document title"), "{}", message);
+        assert!(message.contains("page 1: translucent shape over other content"), "{}", message);
+
+        let config = RenderConfig { format: OutputFormat::Svg, ..print_config() };
+        assert!(crate::render_markup("#set document(title: \"T\")\nA", Some(config)).is_err());
+    }
+
+    #[test]
+    fn test_memo_seal_is_flattened() {
+        let form = crate::test_utils::MemoBuilder::minimal().to_json();
+        let pdf = crate::render_form(&form, Some(print_config())).unwrap().remove(0);
+        assert!(!String::from_utf8_lossy(&pdf).contains("/SMask"));
+    }
+}
@@ -0,0 +1,217 @@
+//! A prioritized queue of stepped renders.
+//!
+//! An editor renders a preview after every pause in typing and an export
+//! when the user asks for one. Rendering them in arrival order lets a slow
+//! export hold up the preview the user is looking at, and renders previews
+//! nobody will see because a newer one is already waiting. A
+//! [`RenderQueue`] runs its requests as [`RenderJob`]s, one step per call to
+//! [`RenderQueue::step`], always advancing the highest priority request
+//! (the oldest among equals). A request that arrives with the same
+//! coalescing key as one still queued or running replaces it, so only the
+//! latest preview of a document is rendered.
+
+use tracing::debug;
+
+use crate::job::RenderJob;
+use crate::metrics::RenderOutput;
+use crate::typst_wrapper::{RenderConfig, TypstWrapperError};
+
+/// How urgently a request should be rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Work nobody is waiting for, such as prerendering
+    Low,
+    /// Exports and other renders the user asked for
+    #[default]
+    Normal,
+    /// Previews the user is looking at
+    High,
+}
+
+/// Identifies a submitted request in the events of its queue
+pub type Ticket = u64;
+
+/// What happened to a request
+#[derive(Debug)]
+pub enum QueueEvent {
+    /// The render completed
+    Finished { ticket: Ticket, output: RenderOutput },
+    /// The render failed
+    Failed { ticket: Ticket, error: TypstWrapperError },
+    /// A newer request with the same coalescing key replaced this one before
+    /// it finished
+    Superseded { ticket: Ticket },
+}
+
+impl QueueEvent {
+    /// The request the event is about
+    pub fn ticket(&self) -> Ticket {
+        match self {
+            QueueEvent::Finished { ticket, .. } | QueueEvent::Failed { ticket, .. } | QueueEvent::Superseded { ticket } => {
+                *ticket
+            }
+        }
+    }
+}
+
+struct Queued {
+    ticket: Ticket,
+    priority: Priority,
+    key: Option<String>,
+    job: RenderJob,
+}
+
+/// Renders requests in priority order, see the [module documentation](self)
+///
+/// # Examples
+/// ```
+/// use render_engine::{Priority, QueueEvent, RenderQueue};
+///
+/// let mut queue = RenderQueue::new();
+/// let export = queue.submit_markup("= Export", None, Priority::Normal, None);
+/// let stale = queue.submit_markup("= Draf", None, Priority::High, Some("preview"));
+/// let preview = queue.submit_markup("= Draft", None, Priority::High, Some("preview"));
+///
+/// let events = queue.run_until_idle();
+/// let order: Vec<_> = events.iter().map(QueueEvent::ticket).collect();
+/// assert_eq!(order, [stale, preview, export]);
+/// assert!(matches!(events[0], QueueEvent::Superseded { .. }));
+/// ```
+#[derive(Default)]
+pub struct RenderQueue {
+    queued: Vec<Queued>,
+    /// Events not yet returned by `step`
+    events: Vec<QueueEvent>,
+    next_ticket: Ticket,
+}
+
+impl RenderQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a render of Typst markup
+    ///
+    /// A queued request with the same `key` is dropped and reported as
+    /// [`QueueEvent::Superseded`].
+    pub fn submit_markup(&mut self, markup: &str, config: Option<RenderConfig>, priority: Priority, key: Option<&str>) -> Ticket {
+        self.submit(RenderJob::markup(markup, config), priority, key)
+    }
+
+    /// Queue a render of a memo form (JSON), see [`submit_markup`](Self::submit_markup)
+    pub fn submit_form(&mut self, json_input: &str, config: Option<RenderConfig>, priority: Priority, key: Option<&str>) -> Ticket {
+        self.submit(RenderJob::form(json_input, config), priority, key)
+    }
+
+    fn submit(&mut self, job: RenderJob, priority: Priority, key: Option<&str>) -> Ticket {
+        if let Some(key) = key {
+            let superseded: Vec<Ticket> = self
+                .queued
+                .iter()
+                .filter(|queued| queued.key.as_deref() == Some(key))
+                .map(|queued| queued.ticket)
+                .collect();
+            for ticket in superseded {
+                debug!(ticket, key, "render request superseded");
+                self.remove(ticket);
+                self.events.push(QueueEvent::Superseded { ticket });
+            }
+        }
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.queued.push(Queued { ticket, priority, key: key.map(str::to_string), job });
+        ticket
+    }
+
+    /// Drop a queued or running request without reporting it, returning
+    /// whether it was still queued
+    pub fn cancel(&mut self, ticket: Ticket) -> bool {
+        self.remove(ticket)
+    }
+
+    fn remove(&mut self, ticket: Ticket) -> bool {
+        let before = self.queued.len();
+        self.queued.retain(|queued| queued.ticket != ticket);
+        self.queued.len() < before
+    }
+
+    /// Number of requests still to be rendered
+    pub fn pending(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Whether nothing is queued and every event has been returned
+    pub fn is_idle(&self) -> bool {
+        self.queued.is_empty() && self.events.is_empty()
+    }
+
+    /// Return the oldest unreported event, or advance the most urgent
+    /// request by one step and return its outcome if it finished
+    ///
+    /// Returns `None` while the request is still in progress and when the
+    /// queue is idle; hosts yield to their event loop between calls.
+    pub fn step(&mut self) -> Option<QueueEvent> {
+        if !self.events.is_empty() {
+            return Some(self.events.remove(0));
+        }
+        // Most urgent first, then oldest; tickets increase with submission
+        let index = (0..self.queued.len())
+            .max_by_key(|&index| (self.queued[index].priority, std::cmp::Reverse(self.queued[index].ticket)))?;
+        let queued = &mut self.queued[index];
+        let ticket = queued.ticket;
+        let event = match queued.job.step() {
+            Ok(None) => return None,
+            Ok(Some(output)) => QueueEvent::Finished { ticket, output },
+            Err(error) => QueueEvent::Failed { ticket, error },
+        };
+        self.queued.remove(index);
+        Some(event)
+    }
+
+    /// Step until the queue is idle, returning the events in order
+    pub fn run_until_idle(&mut self) -> Vec<QueueEvent> {
+        let mut events = Vec::new();
+        while !self.is_idle() {
+            events.extend(self.step());
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_higher_priority_requests_run_first() {
+        let mut queue = RenderQueue::new();
+        let low = queue.submit_markup("Low", None, Priority::Low, None);
+        let first = queue.submit_markup("First", None, Priority::Normal, None);
+        let second = queue.submit_markup("Second", None, Priority::Normal, None);
+        // Start the first normal request, then let a preview overtake it
+        assert!(queue.step().is_none());
+        let preview = queue.submit_markup("Preview", None, Priority::High, None);
+        let order: Vec<Ticket> = queue.run_until_idle().iter().map(QueueEvent::ticket).collect();
+        assert_eq!(order, [preview, first, second, low]);
+    }
+
+    #[test]
+    fn test_coalescing_and_failures() {
+        let mut queue = RenderQueue::new();
+        let first = queue.submit_markup("One", None, Priority::High, Some("doc"));
+        assert!(queue.step().is_none());
+        // Replaces the running preview
+        let second = queue.submit_markup("#invalid(", None, Priority::High, Some("doc"));
+        let other = queue.submit_markup("Other", None, Priority::High, Some("other"));
+        assert_eq!(queue.pending(), 2);
+        let events = queue.run_until_idle();
+        assert!(matches!(events[0], QueueEvent::Superseded { ticket } if ticket == first));
+        assert!(matches!(events[1], QueueEvent::Failed { ticket, .. } if ticket == second));
+        assert!(matches!(&events[2], QueueEvent::Finished { ticket, output } if *ticket == other && output.pages.len() == 1));
+
+        let cancelled = queue.submit_form("{}", None, Priority::Normal, None);
+        assert!(queue.cancel(cancelled));
+        assert!(!queue.cancel(cancelled));
+        assert!(queue.is_idle() && queue.step().is_none());
+    }
+}
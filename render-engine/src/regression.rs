@@ -0,0 +1,450 @@
+//! Golden-image visual regression harness (feature `regression`).
+//!
+//! Renders a corpus of fixtures, rasterizes every page and compares it with
+//! a stored golden PNG using a perceptual color difference, so template
+//! changes cannot silently shift the layout.
+//!
+//! Fixtures are files in `RegressionConfig::fixtures_dir`: `*.json` files are
+//! rendered as memo forms and `*.typ` files as markup. Golden images live in
+//! `RegressionConfig::golden_dir` as `<fixture>-<page>.png` (pages counted
+//! from 1). For every page that does not match, the rendered page and a diff
+//! image (changed pixels in red over a faded copy of the page) are written to
+//! `RegressionConfig::artifacts_dir`.
+//!
+//! Run with `RegressionConfig::update` set to accept the current output as
+//! the new golden images.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+pub use tiny_skia::Pixmap;
+use tiny_skia::IntSize;
+
+use crate::typst_wrapper::TypstWrapper;
+use crate::TypstWrapperError;
+
+/// Largest possible YIQ delta between two colors
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+/// Harness configuration
+#[derive(Debug, Clone)]
+pub struct RegressionConfig {
+    /// Directory containing `*.json` form and `*.typ` markup fixtures
+    pub fixtures_dir: PathBuf,
+    /// Directory containing golden `<fixture>-<page>.png` images
+    pub golden_dir: PathBuf,
+    /// Directory receiving rendered and diff images for failing pages
+    pub artifacts_dir: PathBuf,
+    /// Raster resolution in pixels per typographic point
+    pub pixel_per_pt: f32,
+    /// Per-pixel perceptual color tolerance, from 0 (exact) to 1 (anything)
+    pub color_tolerance: f32,
+    /// Fraction of differing pixels a page may have and still pass
+    pub max_diff_ratio: f64,
+    /// Overwrite golden images with the current output instead of comparing
+    pub update: bool,
+}
+
+impl Default for RegressionConfig {
+    fn default() -> Self {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        Self {
+            fixtures_dir: root.join("tests/regression/fixtures"),
+            golden_dir: root.join("tests/regression/golden"),
+            artifacts_dir: root.join("target/regression"),
+            pixel_per_pt: 1.0,
+            color_tolerance: 0.1,
+            max_diff_ratio: 0.0005,
+            update: false,
+        }
+    }
+}
+
+/// Errors that stop the harness itself (as opposed to failing fixtures)
+#[derive(Error, Debug)]
+pub enum RegressionError {
+    #[error("I/O error at {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid PNG at {path}: {message}")]
+    Png { path: PathBuf, message: String },
+}
+
+/// Outcome for a single rendered page
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageStatus {
+    /// Matches the golden image within the configured thresholds
+    Passed { diff_ratio: f64 },
+    /// Differs from the golden image beyond the configured thresholds
+    Failed { diff_ratio: f64 },
+    /// The page size no longer matches the golden image
+    SizeMismatch { golden: (u32, u32), actual: (u32, u32) },
+    /// No golden image exists for this page
+    MissingGolden,
+    /// The golden image was (re)written in update mode
+    Updated,
+}
+
+impl PageStatus {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, PageStatus::Passed { .. } | PageStatus::Updated)
+    }
+}
+
+/// Outcome for one fixture
+#[derive(Debug, Clone)]
+pub struct FixtureResult {
+    /// Fixture file stem
+    pub name: String,
+    /// Per-page outcomes, in page order
+    pub pages: Vec<PageStatus>,
+    /// Golden images for pages the fixture no longer renders
+    pub extra_goldens: Vec<PathBuf>,
+    /// Render failure, if the fixture could not be rendered
+    pub error: Option<String>,
+}
+
+impl FixtureResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.extra_goldens.is_empty() && self.pages.iter().all(PageStatus::is_ok)
+    }
+}
+
+/// Outcome of a full harness run
+#[derive(Debug, Clone, Default)]
+pub struct RegressionReport {
+    pub fixtures: Vec<FixtureResult>,
+}
+
+impl RegressionReport {
+    pub fn passed(&self) -> bool {
+        self.fixtures.iter().all(FixtureResult::passed)
+    }
+
+    /// Fixtures with at least one problem
+    pub fn failures(&self) -> impl Iterator<Item = &FixtureResult> {
+        self.fixtures.iter().filter(|fixture| !fixture.passed())
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failures().count();
+        writeln!(f, "{} fixtures, {} failed", self.fixtures.len(), failed)?;
+        for fixture in self.failures() {
+            if let Some(error) = &fixture.error {
+                writeln!(f, "  {}: render failed: {}", fixture.name, error)?;
+            }
+            for (index, status) in fixture.pages.iter().enumerate() {
+                let page = index + 1;
+                match status {
+                    PageStatus::Failed { diff_ratio } => {
+                        writeln!(f, "  {} page {}: {:.3}% of pixels differ", fixture.name, page, diff_ratio * 100.0)?
+                    }
+                    PageStatus::SizeMismatch { golden, actual } => writeln!(
+                        f,
+                        "  {} page {}: size changed from {}x{} to {}x{}",
+                        fixture.name, page, golden.0, golden.1, actual.0, actual.1
+                    )?,
+                    PageStatus::MissingGolden => writeln!(f, "  {} page {}: no golden image", fixture.name, page)?,
+                    PageStatus::Passed { .. } | PageStatus::Updated => {}
+                }
+            }
+            if !fixture.extra_goldens.is_empty() {
+                writeln!(
+                    f,
+                    "  {}: renders {} pages but has {} more golden images",
+                    fixture.name,
+                    fixture.pages.len(),
+                    fixture.extra_goldens.len()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pixel-level comparison of two equally sized images
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    /// Changed pixels in red over a faded copy of the actual image
+    pub diff: Pixmap,
+}
+
+impl ImageDiff {
+    pub fn ratio(&self) -> f64 {
+        self.differing_pixels as f64 / self.total_pixels.max(1) as f64
+    }
+}
+
+/// Render Typst markup and rasterize every page
+pub fn rasterize_markup(markup: &str, pixel_per_pt: f32) -> Result<Vec<Pixmap>, TypstWrapperError> {
+    let document = TypstWrapper::compile_markup(markup)?;
+    Ok(document.pages.iter().map(|page| typst_render::render(page, pixel_per_pt)).collect())
+}
+
+/// Render a memo form (JSON) and rasterize every page
+pub fn rasterize_form(json_input: &str, pixel_per_pt: f32) -> Result<Vec<Pixmap>, TypstWrapperError> {
+    let document = TypstWrapper::compile_form(json_input)?;
+    Ok(document.pages.iter().map(|page| typst_render::render(page, pixel_per_pt)).collect())
+}
+
+/// Compare two images pixel by pixel using a perceptual (YIQ) color distance
+///
+/// Returns `None` when the images differ in size.
+pub fn compare_images(golden: &Pixmap, actual: &Pixmap, color_tolerance: f32) -> Option<ImageDiff> {
+    if golden.width() != actual.width() || golden.height() != actual.height() {
+        return None;
+    }
+
+    let max_delta = MAX_YIQ_DELTA * color_tolerance * color_tolerance;
+    let mut differing_pixels = 0;
+    let mut diff = Vec::with_capacity(actual.data().len());
+    for (expected, found) in golden.pixels().iter().zip(actual.pixels()) {
+        let expected = yiq(on_white(*expected));
+        let found = yiq(on_white(*found));
+        let (dy, di, dq) = (expected.0 - found.0, expected.1 - found.1, expected.2 - found.2);
+        if 0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq > max_delta {
+            differing_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let faded = (255.0 + (found.0 - 255.0) * 0.1) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    let size = IntSize::from_wh(actual.width(), actual.height())?;
+    Some(ImageDiff {
+        differing_pixels,
+        total_pixels: golden.pixels().len(),
+        diff: Pixmap::from_vec(diff, size)?,
+    })
+}
+
+/// Composite a premultiplied pixel over white
+fn on_white(pixel: tiny_skia::PremultipliedColorU8) -> [f32; 3] {
+    let backdrop = 255.0 - pixel.alpha() as f32;
+    [
+        pixel.red() as f32 + backdrop,
+        pixel.green() as f32 + backdrop,
+        pixel.blue() as f32 + backdrop,
+    ]
+}
+
+fn yiq([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
+    (
+        r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23,
+        r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9,
+        r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+    )
+}
+
+/// Render every fixture and compare it against its golden images
+pub fn run(config: &RegressionConfig) -> Result<RegressionReport, RegressionError> {
+    let mut fixtures = Vec::new();
+    for path in fixture_paths(&config.fixtures_dir)? {
+        fixtures.push(run_fixture(config, &path)?);
+    }
+    Ok(RegressionReport { fixtures })
+}
+
+/// Fixture files in `dir`, sorted by name
+fn fixture_paths(dir: &Path) -> Result<Vec<PathBuf>, RegressionError> {
+    let entries = fs::read_dir(dir).map_err(|source| io_error(dir, source))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|source| io_error(dir, source))?.path();
+        if matches!(path.extension().and_then(|ext| ext.to_str()), Some("json" | "typ")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn run_fixture(config: &RegressionConfig, path: &Path) -> Result<FixtureResult, RegressionError> {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let input = fs::read_to_string(path).map_err(|source| io_error(path, source))?;
+
+    let rendered = if path.extension().is_some_and(|ext| ext == "json") {
+        rasterize_form(&input, config.pixel_per_pt)
+    } else {
+        rasterize_markup(&input, config.pixel_per_pt)
+    };
+    let pages = match rendered {
+        Ok(pages) => pages,
+        Err(e) => {
+            return Ok(FixtureResult {
+                name,
+                pages: Vec::new(),
+                extra_goldens: Vec::new(),
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
+    let mut statuses = Vec::with_capacity(pages.len());
+    for (index, actual) in pages.iter().enumerate() {
+        let file_name = format!("{}-{}.png", name, index + 1);
+        let golden_path = config.golden_dir.join(&file_name);
+
+        if config.update {
+            write_png(&golden_path, actual)?;
+            statuses.push(PageStatus::Updated);
+            continue;
+        }
+
+        let status = if golden_path.exists() {
+            let golden = read_png(&golden_path)?;
+            match compare_images(&golden, actual, config.color_tolerance) {
+                Some(diff) if diff.ratio() <= config.max_diff_ratio => PageStatus::Passed {
+                    diff_ratio: diff.ratio(),
+                },
+                Some(diff) => {
+                    write_png(&config.artifacts_dir.join(format!("{}-{}.diff.png", name, index + 1)), &diff.diff)?;
+                    PageStatus::Failed {
+                        diff_ratio: diff.ratio(),
+                    }
+                }
+                None => PageStatus::SizeMismatch {
+                    golden: (golden.width(), golden.height()),
+                    actual: (actual.width(), actual.height()),
+                },
+            }
+        } else {
+            PageStatus::MissingGolden
+        };
+        if !status.is_ok() {
+            write_png(&config.artifacts_dir.join(format!("{}-{}.actual.png", name, index + 1)), actual)?;
+        }
+        statuses.push(status);
+    }
+
+    // Golden images past the last rendered page mean the page count changed
+    let mut extra_goldens = Vec::new();
+    let mut page = pages.len() + 1;
+    loop {
+        let golden_path = config.golden_dir.join(format!("{}-{}.png", name, page));
+        if !golden_path.exists() {
+            break;
+        }
+        if config.update {
+            fs::remove_file(&golden_path).map_err(|source| io_error(&golden_path, source))?;
+        } else {
+            extra_goldens.push(golden_path);
+        }
+        page += 1;
+    }
+
+    Ok(FixtureResult {
+        name,
+        pages: statuses,
+        extra_goldens,
+        error: None,
+    })
+}
+
+fn read_png(path: &Path) -> Result<Pixmap, RegressionError> {
+    let data = fs::read(path).map_err(|source| io_error(path, source))?;
+    Pixmap::decode_png(&data).map_err(|e| RegressionError::Png {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn write_png(path: &Path, pixmap: &Pixmap) -> Result<(), RegressionError> {
+    let data = pixmap.encode_png().map_err(|e| RegressionError::Png {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|source| io_error(parent, source))?;
+    }
+    fs::write(path, data).map_err(|source| io_error(path, source))
+}
+
+fn io_error(path: &Path, source: io::Error) -> RegressionError {
+    RegressionError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh scratch directory for one test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("render-engine-regression-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("fixtures")).unwrap();
+        dir
+    }
+
+    fn config_in(dir: &Path) -> RegressionConfig {
+        RegressionConfig {
+            fixtures_dir: dir.join("fixtures"),
+            golden_dir: dir.join("golden"),
+            artifacts_dir: dir.join("artifacts"),
+            pixel_per_pt: 0.5,
+            ..RegressionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_images() {
+        let pages = rasterize_markup("#set page(width: 100pt, height: 100pt)\n= Hello", 1.0).unwrap();
+        let same = compare_images(&pages[0], &pages[0], 0.1).unwrap();
+        assert_eq!(same.differing_pixels, 0);
+
+        let other = rasterize_markup("#set page(width: 100pt, height: 100pt)\n= Goodbye", 1.0).unwrap();
+        let diff = compare_images(&pages[0], &other[0], 0.1).unwrap();
+        assert!(diff.differing_pixels > 0);
+        assert_eq!(diff.total_pixels, 100 * 100);
+
+        let resized = rasterize_markup("#set page(width: 50pt, height: 100pt)\n= Hello", 1.0).unwrap();
+        assert!(compare_images(&pages[0], &resized[0], 0.1).is_none());
+    }
+
+    #[test]
+    fn test_run_detects_regressions() {
+        let dir = scratch_dir("run");
+        let fixture = dir.join("fixtures/note.typ");
+        fs::write(&fixture, "#set page(width: 200pt, height: 100pt)\n= Original").unwrap();
+
+        // Missing goldens fail until they are recorded in update mode
+        let config = config_in(&dir);
+        let report = run(&config).unwrap();
+        assert_eq!(report.fixtures[0].pages, vec![PageStatus::MissingGolden]);
+        assert!(!report.passed());
+
+        let report = run(&RegressionConfig { update: true, ..config.clone() }).unwrap();
+        assert!(report.passed());
+        assert!(dir.join("golden/note-1.png").exists());
+        assert!(run(&config).unwrap().passed());
+
+        // A layout change is reported with diff artifacts
+        fs::write(&fixture, "#set page(width: 200pt, height: 100pt)\n= Changed heading").unwrap();
+        let report = run(&config).unwrap();
+        assert!(matches!(report.fixtures[0].pages[0], PageStatus::Failed { .. }));
+        assert!(dir.join("artifacts/note-1.diff.png").exists());
+        assert!(dir.join("artifacts/note-1.actual.png").exists());
+        assert!(report.to_string().contains("note page 1"));
+
+        // A dropped page leaves an extra golden image behind
+        fs::write(&fixture, "#set page(width: 200pt, height: 100pt)\nA #pagebreak() B").unwrap();
+        run(&RegressionConfig { update: true, ..config.clone() }).unwrap();
+        fs::write(&fixture, "#set page(width: 200pt, height: 100pt)\nA").unwrap();
+        assert_eq!(run(&config).unwrap().fixtures[0].extra_goldens.len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
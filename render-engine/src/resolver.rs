@@ -0,0 +1,109 @@
+//! Files resolved by the host.
+//!
+//! Markup that imports, includes or reads a file the engine neither embeds
+//! nor has registered fails with "file not found". Hosts that keep such
+//! files elsewhere, on a server, in IndexedDB or in a document store,
+//! install a [`FileResolver`] with [`set_file_resolver`]. The engine asks it
+//! for every file nothing else provides, including the files of packages it
+//! does not have, and each render asks at most once per file.
+//!
+//! Renders are synchronous, so resolvers answer from memory or block; a
+//! browser host fetches the files a document needs ahead of the render.
+//! Cache keys cover the input, not the resolved files, so hosts whose files
+//! change turn [`RenderConfig::cache`](crate::RenderConfig::cache) off for
+//! those renders.
+
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+use typst::foundations::Bytes;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::FileId;
+
+/// Source of the files renders need that the engine does not have
+///
+/// Closures taking the path and package work as resolvers.
+///
+/// # Examples
+/// ```
+/// use render_engine::{clear_file_resolver, render_markup, set_file_resolver};
+/// use typst::syntax::package::PackageSpec;
+///
+/// set_file_resolver(|path: &str, package: Option<&PackageSpec>| match (path, package) {
+///     ("units.typ", None) => Some(b"#let wing = [42nd Air Base Wing]".to_vec()),
+///     _ => None,
+/// });
+/// assert!(render_markup("#import \"units.typ\": wing\n#wing", None).is_ok());
+/// clear_file_resolver();
+/// ```
+pub trait FileResolver: Send + Sync {
+    /// The bytes of the file at the rootless `path`, within `package` for
+    /// package files, or `None` if the resolver does not have it
+    fn resolve(&self, path: &str, package: Option<&PackageSpec>) -> Option<Vec<u8>>;
+}
+
+impl<F> FileResolver for F
+where
+    F: Fn(&str, Option<&PackageSpec>) -> Option<Vec<u8>> + Send + Sync,
+{
+    fn resolve(&self, path: &str, package: Option<&PackageSpec>) -> Option<Vec<u8>> {
+        self(path, package)
+    }
+}
+
+static FILE_RESOLVER: LazyLock<RwLock<Option<Arc<dyn FileResolver>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Ask `resolver` for the files renders cannot find, replacing any previous
+/// resolver
+pub fn set_file_resolver<R>(resolver: R)
+where
+    R: FileResolver + 'static,
+{
+    *FILE_RESOLVER.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(resolver));
+}
+
+/// Stop using the resolver installed with [`set_file_resolver`]
+pub fn clear_file_resolver() {
+    *FILE_RESOLVER.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// The file `id` from the installed resolver, if there is one and it has
+/// the file
+pub(crate) fn resolve(id: FileId) -> Option<Bytes> {
+    let resolver = FILE_RESOLVER.read().unwrap_or_else(PoisonError::into_inner).clone()?;
+    let path = id.vpath().as_rootless_path().to_string_lossy();
+    resolver.resolve(&path, id.package()).map(Bytes::new)
+}
+
+#[cfg(all(test, feature = "font-times"))]
+mod tests {
+    use super::*;
+    use crate::{render_markup, RenderConfig, SvgTextMode};
+
+    #[test]
+    fn test_resolver_serves_missing_files() {
+        // The resolver serves every render, so this is the only test that
+        // sets it, and it only has files no other test uses
+        set_file_resolver(|path: &str, package: Option<&PackageSpec>| match (path, package) {
+            ("resolver-test/units.typ", None) => Some(b"#let wing = [Resolved wing]".to_vec()),
+            ("resolver-test/units.json", None) => Some(br#"{"name": "Resolved data"}"#.to_vec()),
+            ("typst.toml", Some(spec)) if spec.name == "resolver-test" => {
+                Some(b"[package]\nname = \"resolver-test\"\nversion = \"0.1.0\"\nentrypoint = \"lib.typ\"".to_vec())
+            }
+            ("lib.typ", Some(spec)) if spec.name == "resolver-test" => Some(b"#let unit = [Resolved package]".to_vec()),
+            _ => None,
+        });
+        let markup = "#import \"resolver-test/units.typ\": wing\n#import \"@preview/resolver-test:0.1.0\": unit\n\
+                      #wing #json(\"/resolver-test/units.json\").name #unit";
+        let config = RenderConfig { svg_text: SvgTextMode::Selectable, cache: false, ..RenderConfig::default() };
+        let rendered = render_markup(markup, Some(config.clone()));
+        let missing = render_markup("#image(\"resolver-test/missing.png\")", Some(config.clone()));
+        clear_file_resolver();
+
+        let svg = String::from_utf8(rendered.unwrap().remove(0)).unwrap();
+        for text in ["Resolved wing", "Resolved data", "Resolved package"] {
+            assert!(svg.contains(text), "{}", text);
+        }
+        assert!(missing.is_err());
+        assert!(render_markup(markup, Some(config)).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Running headers and footers.
+//!
+//! A [`RunningText`] puts short lines such as an office symbol, a subject
+//! slug, a CUI marking or the date at the left, center and right of the page
+//! header or footer, on all pages or a [`PageSelection`].
+//!
+//! Memo forms set them with the `running-header` and `running-footer` fields,
+//! which the form preprocessor normalizes for the memo-loader template. For
+//! forms, [`RenderConfig::header`](crate::RenderConfig::header) and
+//! [`RenderConfig::footer`](crate::RenderConfig::footer) replace those fields;
+//! for markup they are drawn over the compiled pages, half an inch from the
+//! top and bottom edges.
+//!
+//! Texts may contain placeholders: `{page}` and `{total}` everywhere, and in
+//! forms also `{date}`, `{office-symbol}` (first line of the from block) and
+//! `{subject}`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use typst::layout::{PagedDocument, Point};
+
+use crate::typst_wrapper::{RenderConfig, TypstWrapper, TypstWrapperError};
+use crate::watermark::typst_string;
+
+/// Placeholders markup renders fill in
+const MARKUP_PLACEHOLDERS: [&str; 2] = ["{page}", "{total}"];
+
+/// Text at the sides and center of a page header or footer
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, PageSelection, RenderConfig, RunningText};
+///
+/// let config = RenderConfig {
+///     header: Some(RunningText { center: Some("CUI".to_string()), ..RunningText::default() }),
+///     footer: Some(RunningText {
+///         right: Some("{page} of {total}".to_string()),
+///         pages: PageSelection::Subsequent,
+///         ..RunningText::default()
+///     }),
+///     ..RenderConfig::default()
+/// };
+/// let pages = render_markup("= Hello", Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunningText {
+    pub left: Option<String>,
+    pub center: Option<String>,
+    pub right: Option<String>,
+    /// Pages that show the text
+    pub pages: PageSelection,
+}
+
+impl RunningText {
+    /// The texts at the left, center and right
+    fn slots(&self) -> [Option<&str>; 3] {
+        [self.left.as_deref(), self.center.as_deref(), self.right.as_deref()]
+    }
+}
+
+/// Pages a running header or footer appears on
+///
+/// Serialized as `"all"`, `"first"`, `"subsequent"` or an array of 1-based
+/// page numbers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "PageSelectionRepr", into = "PageSelectionRepr")]
+pub enum PageSelection {
+    #[default]
+    All,
+    First,
+    /// Every page but the first
+    Subsequent,
+    /// The listed pages, counting from 1
+    Pages(Vec<usize>),
+}
+
+impl PageSelection {
+    /// Whether the page at `index` (from 0) is selected
+    fn contains(&self, index: usize) -> bool {
+        match self {
+            Self::All => true,
+            Self::First => index == 0,
+            Self::Subsequent => index > 0,
+            Self::Pages(pages) => pages.contains(&(index + 1)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PageSelectionRepr {
+    Named(NamedSelection),
+    Pages(Vec<usize>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum NamedSelection {
+    All,
+    First,
+    Subsequent,
+}
+
+impl From<PageSelectionRepr> for PageSelection {
+    fn from(repr: PageSelectionRepr) -> Self {
+        match repr {
+            PageSelectionRepr::Named(NamedSelection::All) => Self::All,
+            PageSelectionRepr::Named(NamedSelection::First) => Self::First,
+            PageSelectionRepr::Named(NamedSelection::Subsequent) => Self::Subsequent,
+            PageSelectionRepr::Pages(pages) => Self::Pages(pages),
+        }
+    }
+}
+
+impl From<PageSelection> for PageSelectionRepr {
+    fn from(selection: PageSelection) -> Self {
+        match selection {
+            PageSelection::All => Self::Named(NamedSelection::All),
+            PageSelection::First => Self::Named(NamedSelection::First),
+            PageSelection::Subsequent => Self::Named(NamedSelection::Subsequent),
+            PageSelection::Pages(pages) => Self::Pages(pages),
+        }
+    }
+}
+
+/// Move the header and footer of `config` into form JSON as its
+/// `running-header` and `running-footer`, so the template draws them
+pub(crate) fn move_into_form(json_input: &str, config: &mut RenderConfig) -> Result<String, TypstWrapperError> {
+    if config.header.is_none() && config.footer.is_none() {
+        return Ok(json_input.to_string());
+    }
+    let mut form: JsonValue = serde_json::from_str(json_input)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid form JSON: {}", e)))?;
+    let Some(fields) = form.as_object_mut() else {
+        return Ok(json_input.to_string());
+    };
+    for (key, running) in [("running-header", config.header.take()), ("running-footer", config.footer.take())] {
+        if let Some(running) = running {
+            let value = serde_json::to_value(running).map_err(|e| TypstWrapperError::Validation(e.to_string()))?;
+            fields.insert(key.to_string(), value);
+        }
+    }
+    Ok(form.to_string())
+}
+
+/// Draw the header and footer of `config` on the pages of a markup render
+pub(crate) fn apply(document: &mut PagedDocument, config: &RenderConfig) -> Result<(), TypstWrapperError> {
+    let running = [("top", config.header.as_ref()), ("bottom", config.footer.as_ref())];
+    for text in running.iter().filter_map(|(_, running)| *running).flat_map(RunningText::slots).flatten() {
+        check_placeholders(text)?;
+    }
+
+    let total = document.pages.len();
+    for (index, page) in document.pages.iter_mut().enumerate() {
+        let size = page.frame.size();
+        let mut markup = format!(
+            "#set page(width: {}pt, height: {}pt, margin: 0pt, fill: none)\n#set text(font: \"Times\", size: 12pt)\n",
+            size.x.to_pt(),
+            size.y.to_pt()
+        );
+        let mut any = false;
+        for (edge, running) in running {
+            let Some(running) = running.filter(|running| running.pages.contains(index)) else {
+                continue;
+            };
+            let [left, center, right] = running.slots().map(|text| {
+                text.map_or("none".to_string(), |text| {
+                    typst_string(&text.replace("{page}", &(index + 1).to_string()).replace("{total}", &total.to_string()))
+                })
+            });
+            markup.push_str(&format!(
+                "#place({edge}, dy: {dy}, block(width: 100%, inset: (x: 1in), grid(columns: (1fr, auto, 1fr), align(left, {left}), align(center, {center}), align(right, {right}))))\n",
+                dy = if edge == "top" { "0.5in" } else { "-0.5in" },
+            ));
+            any = true;
+        }
+        if any {
+            let overlay = TypstWrapper::compile_markup(&markup)?.pages.swap_remove(0).frame;
+            page.frame.push_frame(Point::zero(), overlay);
+        }
+    }
+    Ok(())
+}
+
+/// Reject placeholders markup renders cannot fill in
+fn check_placeholders(text: &str) -> Result<(), TypstWrapperError> {
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let end = rest[open..].find('}').map_or(rest.len(), |close| open + close + 1);
+        let placeholder = &rest[open..end];
+        if !MARKUP_PLACEHOLDERS.contains(&placeholder) {
+            return Err(TypstWrapperError::Validation(format!(
+                "Unknown placeholder '{}' in running text: markup renders fill in {{page}} and {{total}}",
+                placeholder
+            )));
+        }
+        rest = &rest[end..];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_selection_serialization() {
+        for (selection, json) in [
+            (PageSelection::All, r#""all""#),
+            (PageSelection::Subsequent, r#""subsequent""#),
+            (PageSelection::Pages(vec![1, 3]), "[1,3]"),
+        ] {
+            assert_eq!(serde_json::to_string(&selection).unwrap(), json);
+            assert_eq!(serde_json::from_str::<PageSelection>(json).unwrap(), selection);
+        }
+        let running: RunningText = serde_json::from_str(r#"{"center": "CUI"}"#).unwrap();
+        assert_eq!(running.pages, PageSelection::All);
+        assert!(PageSelection::First.contains(0) && !PageSelection::First.contains(1));
+    }
+
+    #[test]
+    fn test_markup_running_text() {
+        let markup = "= One\n#pagebreak()\n= Two";
+        let plain = crate::render_markup(markup, None).unwrap();
+        let config = RenderConfig {
+            footer: Some(RunningText {
+                right: Some("{page} of {total}".to_string()),
+                pages: PageSelection::Pages(vec![2]),
+                ..RunningText::default()
+            }),
+            ..RenderConfig::default()
+        };
+        let pages = crate::render_markup(markup, Some(config)).unwrap();
+        assert_eq!(pages[0], plain[0]);
+        assert!(pages[1].len() > plain[1].len());
+
+        let config = RenderConfig {
+            header: Some(RunningText { left: Some("{subject}".to_string()), ..RunningText::default() }),
+            ..RenderConfig::default()
+        };
+        assert!(matches!(crate::render_markup(markup, Some(config)), Err(TypstWrapperError::Validation(_))));
+    }
+
+    #[test]
+    fn test_config_replaces_form_fields() {
+        let mut config = RenderConfig {
+            header: Some(RunningText { center: Some("CUI".to_string()), ..RunningText::default() }),
+            ..RenderConfig::default()
+        };
+        let form = move_into_form(r#"{"subject": "S", "running-header": {"left": "old"}}"#, &mut config).unwrap();
+        let form: JsonValue = serde_json::from_str(&form).unwrap();
+        assert_eq!(form["running-header"]["center"], "CUI");
+        assert_eq!(form["running-header"]["left"], JsonValue::Null);
+        assert!(config.header.is_none());
+    }
+}
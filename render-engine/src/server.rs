@@ -0,0 +1,479 @@
+//! Optional HTTP render service (feature `server`).
+//!
+//! Exposes the render engine as a small JSON API so it can be deployed as a
+//! microservice:
+//!
+//! - `POST /render/markup` — `{"markup": "...", "format": "svg" | "pdf" | "svg-zip"}`
+//! - `POST /render/form` — `{"form": {...memo form...}, "format": "svg" | "pdf" | "svg-zip"}`
+//! - `POST /validate` — `{"form": {...memo form...}}`
+//!
+//! Render requests may add any field of a serialized [`RenderConfig`] next
+//! to `markup` or `form`:
+//!
+//! - a `watermark` (`{"text", "color", "opacity"}`, see
+//!   [`Watermark`](crate::Watermark)) and a running `header` and `footer`
+//!   (see [`RunningText`](crate::RunningText)), which take precedence over
+//!   the ones set in the form
+//! - `"duplex": true` to start every labelled section on an odd page
+//! - a `print` profile (see [`PrintProfile`](crate::PrintProfile)) to export
+//!   PDF/X
+//! - `"svg_links": false` to leave links in SVG pages unclickable
+//! - `"svg_text": "selectable"` to add selectable text to SVG pages (see
+//!   [`SvgTextMode`](crate::SvgTextMode))
+//! - `"scale": 2` to display SVG pages at twice their size in points
+//! - `"text_layout": true` to add each page's text runs to SVG ZIPs
+//! - review `annotations` (see [`Annotation`](crate::Annotation))
+//! - `"form_fields": true` to add fillable signature and date fields to PDFs
+//! - `"embed_form": true` to attach the form JSON to PDFs of forms
+//! - PDF `metadata` (see [`DocumentMetadata`](crate::DocumentMetadata)),
+//!   which takes precedence over the form's
+//! - `"generate_outline": false` to leave PDFs without bookmarks
+//! - a `page_range` (`{"start": 0, "end": 1}`) to export only some pages
+//! - `"stack_pages": true` to stack SVG pages into one SVG
+//! - an `id_prefix` for the ids of SVG output
+//! - `"today": "2025-03-07"` to fix the date `datetime.today()` returns
+//! - `font_substitutions` mapping font families to the families to render
+//!   them with
+//! - `"cache": false` to render even if the output is cached
+//! - `"strict_packages": true` to fail on package versions that are not
+//!   embedded instead of using a compatible one
+//! - a `color_mode` of `"grayscale"` or `"high-contrast"` (see
+//!   [`ColorMode`](crate::ColorMode))
+//!
+//! Form render requests may add `style` tokens (see
+//! [`StyleConfig`](crate::StyleConfig)), which replace the form's.
+//!
+//! Render endpoints respond with `{"format", "page_count", "pages",
+//! "page_sizes", "font_substitutions"}` where each page is base64-encoded,
+//! `page_sizes` holds the `{"width", "height"}` of every document page in
+//! points and `font_substitutions` the `{"from", "to"}` substitutions the
+//! document used. Failures, including malformed request bodies, respond
+//! with `{"error": "..."}`; render failures also carry the error as
+//! `details` (see [`ErrorPayload`]), and those caused by the input a
+//! `report` with the diagnostics formatted as text (see
+//! `diagnostics::format_pretty`).
+//! Validation responses list the same `details` for their `errors`.
+//!
+//! Renders run on blocking worker threads. At most
+//! `ServerConfig::max_concurrent_renders` run at once; a request that cannot
+//! start and finish within `ServerConfig::request_timeout` fails with
+//! `503 Service Unavailable` (still queued) or `504 Gateway Timeout` (still
+//! rendering). A timed-out render is cancelled with a [`CancelToken`] and
+//! frees its slot at its next page or before it compiles; a compile already
+//! running still runs to its end (Typst stops runaway loops itself).
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::diagnostics::{format_pretty, Diagnostic};
+use crate::error_payload::ErrorPayload;
+use crate::form_processor;
+use crate::{CancelToken, FontSubstitution, OutputFormat, PageSize, RenderConfig, RenderOutput, TypstWrapperError};
+
+/// HTTP service configuration
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Maximum number of renders running at the same time
+    pub max_concurrent_renders: usize,
+    /// Time budget for a render request, including time spent queued
+    pub request_timeout: Duration,
+    /// Maximum accepted request body size in bytes
+    pub max_body_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_renders: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            request_timeout: Duration::from_secs(30),
+            max_body_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// Body of `POST /render/markup`
+#[derive(Debug, Deserialize)]
+pub struct MarkupRequest {
+    pub markup: String,
+    /// Render settings, given next to `markup`
+    #[serde(flatten)]
+    pub config: RenderConfig,
+}
+
+/// Body of `POST /render/form`
+#[derive(Debug, Deserialize)]
+pub struct FormRequest {
+    pub form: JsonValue,
+    /// Render settings, given next to `form`
+    #[serde(flatten)]
+    pub config: RenderConfig,
+}
+
+/// Body of `POST /validate`
+#[derive(Debug, Deserialize)]
+pub struct ValidateRequest {
+    pub form: JsonValue,
+}
+
+/// Successful render response
+#[derive(Debug, Serialize)]
+pub struct RenderResponse {
+    pub format: &'static str,
+    pub page_count: usize,
+    /// Base64-encoded pages (one per SVG page, a single item for PDF)
+    pub pages: Vec<String>,
+    /// Size of every page of the document in points
+    pub page_sizes: Vec<PageSize>,
+    /// The requested font substitutions the document's text uses
+    pub font_substitutions: Vec<FontSubstitution>,
+}
+
+/// Validation response
+#[derive(Debug, Serialize)]
+pub struct ValidateResponse {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    /// The errors as kind, code, message and path (see [`ErrorPayload`])
+    pub details: Vec<ErrorPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+    /// The error as kind, code, message and path, for render failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<ErrorPayload>,
+    /// Diagnostics with source excerpts, when the input caused the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<String>,
+}
+
+/// A failed render with its diagnostics report, if any
+struct RenderFailure {
+    error: TypstWrapperError,
+    report: Option<String>,
+}
+
+impl RenderFailure {
+    /// Attach a report of `diagnose(input)` to input-related failures
+    fn new(error: TypstWrapperError, path: &str, input: &str, diagnose: fn(&str) -> Vec<Diagnostic>) -> Self {
+        let report = match error {
+            TypstWrapperError::Compilation(_)
+            | TypstWrapperError::Validation(_)
+            | TypstWrapperError::Field { .. }
+            | TypstWrapperError::Excerpt { .. } => {
+                Some(diagnose(input)).filter(|d| !d.is_empty()).map(|d| format_pretty(&d, path, input, false))
+            }
+            _ => None,
+        };
+        Self { error, report }
+    }
+}
+
+struct ServerState {
+    renders: Arc<Semaphore>,
+    request_timeout: Duration,
+}
+
+impl ServerState {
+    fn new(config: &ServerConfig) -> Self {
+        Self {
+            renders: Arc::new(Semaphore::new(config.max_concurrent_renders.max(1))),
+            request_timeout: config.request_timeout,
+        }
+    }
+}
+
+/// Build the service router
+pub fn router(config: ServerConfig) -> Router {
+    let state = Arc::new(ServerState::new(&config));
+
+    Router::new()
+        .route("/render/markup", post(render_markup))
+        .route("/render/form", post(render_form))
+        .route("/validate", post(validate))
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .with_state(state)
+}
+
+/// Bind `addr` and serve requests until the process exits
+pub async fn serve(addr: SocketAddr, config: ServerConfig) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(config)).await
+}
+
+async fn render_markup(
+    State(state): State<Arc<ServerState>>,
+    request: Result<Json<MarkupRequest>, JsonRejection>,
+) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(rejection) => return rejection_response(rejection),
+    };
+    let config = RenderConfig { style: None, ..request.config };
+    run_render(&state, config.format, move |token| {
+        crate::render_markup_observed(&request.markup, Some(config), token)
+            .map_err(|e| RenderFailure::new(e, "main.typ", &request.markup, crate::diagnose_markup))
+    })
+    .await
+}
+
+async fn render_form(
+    State(state): State<Arc<ServerState>>,
+    request: Result<Json<FormRequest>, JsonRejection>,
+) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(rejection) => return rejection_response(rejection),
+    };
+    let config = request.config;
+    let form_json = request.form.to_string();
+    run_render(&state, config.format, move |token| {
+        crate::render_form_observed(&form_json, Some(config), token)
+            .map_err(|e| RenderFailure::new(e, "form.json", &form_json, crate::diagnose_form))
+    })
+    .await
+}
+
+async fn validate(request: Result<Json<ValidateRequest>, JsonRejection>) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(rejection) => return rejection_response(rejection),
+    };
+    let errors = form_processor::official_memo_schema_errors(&request.form.to_string());
+    let response = ValidateResponse {
+        valid: errors.is_empty(),
+        errors: errors.iter().map(ToString::to_string).collect(),
+        details: errors.iter().map(ErrorPayload::from).collect(),
+    };
+    Json(response).into_response()
+}
+
+/// Run a render on a blocking thread under the concurrency limit and request
+/// deadline, cancelling it through its token once the deadline passes
+async fn run_render<F>(state: &ServerState, format: OutputFormat, render: F) -> Response
+where
+    F: FnOnce(&mut CancelToken) -> Result<RenderOutput, RenderFailure> + Send + 'static,
+{
+    let deadline = Instant::now() + state.request_timeout;
+
+    let permit = match tokio::time::timeout_at(deadline, state.renders.clone().acquire_owned()).await {
+        Ok(Ok(permit)) => permit,
+        _ => return error_response(StatusCode::SERVICE_UNAVAILABLE, "Render capacity exhausted, try again later"),
+    };
+
+    // The permit moves into the worker so it is only released once the render
+    // actually finishes, even if the request has already timed out
+    let token = CancelToken::new();
+    let mut render_token = token.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        render(&mut render_token)
+    });
+
+    match tokio::time::timeout_at(deadline, task).await {
+        Ok(Ok(Ok(output))) => {
+            let engine = base64::engine::general_purpose::STANDARD;
+            Json(RenderResponse {
+                format: format.name(),
+                page_count: output.pages.len(),
+                pages: output.pages.iter().map(|page| engine.encode(page)).collect(),
+                page_sizes: output.page_sizes,
+                font_substitutions: output.font_substitutions,
+            })
+            .into_response()
+        }
+        Ok(Ok(Err(failure))) => {
+            let status = match failure.error {
+                TypstWrapperError::Compilation(_)
+                | TypstWrapperError::Validation(_)
+                | TypstWrapperError::Field { .. }
+                | TypstWrapperError::Excerpt { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let body = ErrorResponse {
+                error: failure.error.to_string(),
+                details: Some(ErrorPayload::from(&failure.error)),
+                report: failure.report,
+            };
+            (status, Json(body)).into_response()
+        }
+        Ok(Err(e)) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("Render task failed: {}", e)),
+        Err(_) => {
+            token.cancel();
+            error_response(StatusCode::GATEWAY_TIMEOUT, "Render timed out")
+        }
+    }
+}
+
+/// Respond to a request body the `Json` extractor rejected
+fn rejection_response(rejection: JsonRejection) -> Response {
+    error_response(rejection.status(), &rejection.body_text())
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+            details: None,
+            report: None,
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use crate::test_utils::MemoBuilder;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn post_json(path: &str, body: JsonValue) -> (StatusCode, JsonValue) {
+        post_body(router(ServerConfig::default()), path, body.to_string()).await
+    }
+
+    async fn post_body(router: Router, path: &str, body: String) -> (StatusCode, JsonValue) {
+        let request = Request::post(path)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    fn memo_form() -> JsonValue {
+        MemoBuilder::minimal().subject("Server Test").body_raw("Rendered over HTTP.").build()
+    }
+
+    #[tokio::test]
+    async fn test_render_markup_endpoint() {
+        let (status, body) = post_json(
+            "/render/markup",
+            serde_json::json!({"markup": "= Hello", "format": "pdf"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["format"], "pdf");
+        assert_eq!(body["page_count"], 1);
+        assert_eq!(body["page_sizes"].as_array().unwrap().len(), 1);
+
+        let pdf = base64::engine::general_purpose::STANDARD
+            .decode(body["pages"][0].as_str().unwrap())
+            .unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[tokio::test]
+    async fn test_render_form_endpoint() {
+        let (status, body) = post_json("/render/form", serde_json::json!({"form": memo_form()})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["format"], "svg");
+    }
+
+    #[tokio::test]
+    async fn test_render_settings_sit_next_to_the_input() {
+        let (status, body) = post_json(
+            "/render/markup",
+            serde_json::json!({"markup": "= Hello", "format": "svg-zip", "text_layout": true, "color_mode": "grayscale"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["format"], "svg-zip");
+        assert_eq!(body["page_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_render_errors_are_unprocessable() {
+        let (status, body) = post_json("/render/markup", serde_json::json!({"markup": "#undefined-fn()"})).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("Compilation failed"));
+        assert_eq!(body["details"]["code"], "compilation_failed");
+        assert!(body["report"].as_str().unwrap().contains("--> main.typ:1:2"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_endpoint() {
+        let (_, valid) = post_json("/validate", serde_json::json!({"form": memo_form()})).await;
+        assert_eq!(valid["valid"], true);
+
+        let (_, invalid) = post_json("/validate", serde_json::json!({"form": {"subject": "Missing fields"}})).await;
+        assert_eq!(invalid["valid"], false);
+        assert!(!invalid["errors"].as_array().unwrap().is_empty());
+        assert_eq!(invalid["details"][0]["code"], "schema_mismatch");
+    }
+
+    /// One render at a time, with a deadline of `timeout`
+    fn limited(timeout: Duration) -> ServerConfig {
+        ServerConfig { max_concurrent_renders: 1, request_timeout: timeout, ..ServerConfig::default() }
+    }
+
+    #[tokio::test]
+    async fn test_queued_renders_fail_when_capacity_is_exhausted() {
+        let state = ServerState::new(&limited(Duration::from_millis(20)));
+        let _busy = state.renders.clone().acquire_owned().await.unwrap();
+
+        let response = run_render(&state, OutputFormat::Svg, |_| unreachable!("the render never starts")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_renders_are_cancelled() {
+        let state = ServerState::new(&limited(Duration::from_millis(20)));
+        let (cancelled, observed) = std::sync::mpsc::channel();
+
+        // A render that only stops once its token is cancelled
+        let response = run_render(&state, OutputFormat::Svg, move |token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            cancelled.send(()).unwrap();
+            Err(RenderFailure { error: TypstWrapperError::Cancelled, report: None })
+        })
+        .await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        // The cancelled render gives its slot back
+        observed.recv_timeout(Duration::from_secs(5)).unwrap();
+        let permit = tokio::time::timeout(Duration::from_secs(5), state.renders.clone().acquire_owned()).await;
+        assert!(permit.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_slow_renders_time_out() {
+        let router = router(limited(Duration::from_millis(1)));
+        let (status, body) = post_body(router, "/render/form", serde_json::json!({"form": memo_form()}).to_string()).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["error"], "Render timed out");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_bodies_are_json_errors() {
+        let (status, body) = post_body(router(ServerConfig::default()), "/render/markup", "{\"markup\":".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("JSON"), "{}", body);
+
+        let (status, body) = post_json("/validate", serde_json::json!({"subject": "No form"})).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("form"), "{}", body);
+    }
+}
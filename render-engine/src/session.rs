@@ -0,0 +1,374 @@
+//! Renders that keep their Typst world warm.
+//!
+//! Every render builds a new Typst world, so a live preview re-rendering on
+//! each keystroke parses the whole document again and lays it out from
+//! scratch, then exports every page again. A [`RenderSession`] keeps the
+//! world of its last render: the next one swaps in the new markup or form
+//! JSON and Typst reparses only the edited part. The template sources and
+//! the font book with its substitutions stay with the world. Exporting SVG
+//! pages takes most of a render, so the session also keeps the SVG of each
+//! page and exports only the pages an edit changed.
+//!
+//! Rendering the same input again returns the last output without
+//! compiling, unless the configuration turns the output cache off, the
+//! form is a draft, the date changed since for renders without a fixed
+//! `today` or the render asked for the date at an offset from UTC, and
+//! [`RenderSession::changed_pages`] tells an editor which pages to redraw.
+//!
+//! A session renders with the engine it was started with. When that engine
+//! registers or removes a letterhead, file, font, local package, memo
+//! template or partial, the session's next render starts a new world.
+//! Switching between markup and forms does too.
+
+use typst::foundations::Datetime;
+use web_time::Instant;
+
+use crate::cache::CachedRender;
+use crate::draft;
+use crate::engine::{self, Registrations, RenderEngine};
+use crate::metrics::RenderOutput;
+use crate::observer::RenderObserver;
+use crate::svg_cache::SvgCache;
+use crate::today;
+use crate::typst_wrapper::{RenderConfig, TypstWorld, TypstWrapper, TypstWrapperError};
+
+/// Repeated renders sharing one Typst world, see the
+/// [module documentation](self)
+///
+/// # Examples
+/// ```
+/// use render_engine::RenderSession;
+///
+/// let mut session = RenderSession::new(None);
+/// for markup in ["= Draft", "= Draft\nFirst", "= Draft\nFirst paragraph"] {
+///     let pages = session.render_markup(markup).unwrap();
+///     assert_eq!(pages.len(), 1);
+/// }
+/// ```
+pub struct RenderSession {
+    /// The engine current when the session was started, which every render
+    /// uses
+    engine: RenderEngine,
+    config: RenderConfig,
+    /// The world of the last render and the registrations it was built from
+    warm: Option<TypstWorld>,
+    registrations: Registrations,
+    svgs: SvgCache,
+    /// The input and output of the last render
+    last: Option<LastRender>,
+    /// Hashes of the pages of the last render, and the pages that differ
+    /// from the render before
+    page_hashes: Vec<u128>,
+    changed_pages: Vec<usize>,
+}
+
+/// A render a session can repeat without compiling
+struct LastRender {
+    form: bool,
+    input: String,
+    /// The date of today it was rendered for, see [`today::key`]
+    today: Option<Datetime>,
+    output: CachedRender,
+}
+
+impl RenderSession {
+    /// Start a session rendering with `config`, or the default
+    /// configuration, on the current engine
+    pub fn new(config: Option<RenderConfig>) -> Self {
+        Self::with_engine(engine::current(), config)
+    }
+
+    pub(crate) fn with_engine(engine: RenderEngine, config: Option<RenderConfig>) -> Self {
+        Self {
+            registrations: engine.registrations(),
+            engine,
+            config: config.unwrap_or_default(),
+            warm: None,
+            svgs: SvgCache::default(),
+            last: None,
+            page_hashes: Vec::new(),
+            changed_pages: Vec::new(),
+        }
+    }
+
+    /// The configuration renders use
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+
+    /// Render with `config` from now on
+    pub fn set_config(&mut self, config: RenderConfig) {
+        self.config = config;
+        self.last = None;
+    }
+
+    /// Drop the kept world and pages, so the next render starts from
+    /// scratch
+    pub fn reset(&mut self) {
+        self.warm = None;
+        self.svgs = SvgCache::default();
+        self.last = None;
+        self.page_hashes.clear();
+    }
+
+    /// Indices of the pages of the last render that look different from
+    /// the render before it, including pages it added; pages it dropped
+    /// show in its page count. Every page after the first render and after
+    /// renders served from the output cache.
+    ///
+    /// # Examples
+    /// ```
+    /// use render_engine::RenderSession;
+    ///
+    /// let mut session = RenderSession::new(None);
+    /// session.render_markup("= One\n#pagebreak()\n= Two").unwrap();
+    /// assert_eq!(session.changed_pages(), [0, 1]);
+    /// session.render_markup("= One\n#pagebreak()\n= Two, edited").unwrap();
+    /// assert_eq!(session.changed_pages(), [1]);
+    /// ```
+    pub fn changed_pages(&self) -> &[usize] {
+        &self.changed_pages
+    }
+
+    /// Render Typst markup, see [`render_markup`](crate::render_markup)
+    pub fn render_markup(&mut self, markup: &str) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        self.render_markup_with_metrics(markup).map(|output| output.pages)
+    }
+
+    /// Render a memo form, see [`render_form`](crate::render_form)
+    pub fn render_form(&mut self, json_input: &str) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        self.render_form_with_metrics(json_input).map(|output| output.pages)
+    }
+
+    /// Render Typst markup, returning the pages with the render's metrics
+    pub fn render_markup_with_metrics(&mut self, markup: &str) -> Result<RenderOutput, TypstWrapperError> {
+        self.render_markup_observed(markup, &mut ())
+    }
+
+    /// Render a memo form, returning the pages with the render's metrics
+    pub fn render_form_with_metrics(&mut self, json_input: &str) -> Result<RenderOutput, TypstWrapperError> {
+        self.render_form_observed(json_input, &mut ())
+    }
+
+    /// Render Typst markup, reporting progress to `observer`
+    pub fn render_markup_observed(
+        &mut self,
+        markup: &str,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        self.render(false, markup, observer)
+    }
+
+    /// Render a memo form, reporting progress to `observer`
+    pub fn render_form_observed(
+        &mut self,
+        json_input: &str,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        self.render(true, json_input, observer)
+    }
+
+    fn render(&mut self, form: bool, input: &str, observer: &mut dyn RenderObserver) -> Result<RenderOutput, TypstWrapperError> {
+        self.prepare();
+        // Renders without a fixed date change at midnight, as in the output cache
+        let today = self.engine.scope(|| today::key(self.config.today.as_deref()));
+        if let Some(last) = self.last.as_ref().filter(|last| last.form == form && last.input == input && last.today == today) {
+            self.changed_pages.clear();
+            let result = TypstWrapper::replay(last.output.clone(), Instant::now(), observer);
+            return TypstWrapper::finish(result, observer);
+        }
+        self.last = None;
+
+        let (warm, svgs, config) = (&mut self.warm, &self.svgs, self.config.clone());
+        let result = self.engine.scope(|| match form {
+            true => TypstWrapper::render_form_in(warm, Some(svgs), input, config, observer),
+            false => TypstWrapper::render_markup_in(warm, Some(svgs), input, config, observer),
+        });
+        let output = result?;
+
+        let page_hashes = match (output.metrics.from_cache, self.warm.as_mut()) {
+            (false, Some(world)) => std::mem::take(&mut world.page_hashes),
+            _ => Vec::new(),
+        };
+        self.changed_pages = (0..output.page_sizes.len())
+            .filter(|&index| page_hashes.get(index).is_none() || self.page_hashes.get(index) != page_hashes.get(index))
+            .collect();
+        self.page_hashes = page_hashes;
+        // Repeat only what the output cache would serve
+        let dated_at_offset = self.warm.as_ref().is_some_and(TypstWorld::dated_at_offset);
+        if self.config.cache && !(form && draft::is_draft(input)) && !dated_at_offset {
+            let rendered = CachedRender {
+                page_sizes: output.page_sizes.clone(),
+                font_substitutions: output.font_substitutions.clone(),
+                pages: output.pages.clone(),
+            };
+            self.last = Some(LastRender { form, input: input.to_string(), today, output: rendered });
+        }
+        Ok(output)
+    }
+
+    /// Start a render: drop the kept world if the engine's registrations
+    /// changed since it was built
+    fn prepare(&mut self) {
+        self.svgs.begin_render();
+        let registrations = self.engine.registrations();
+        if !registrations.same(&self.registrations) {
+            self.warm = None;
+            self.last = None;
+            self.registrations = registrations;
+        }
+    }
+}
+
+/// The render of a document with the pages that changed since an earlier
+/// version, see [`render_markup_diff`]
+#[derive(Debug, Clone)]
+pub struct RenderDiff {
+    /// The render of the new version
+    pub output: RenderOutput,
+    /// Indices of the pages of the new version that look different from
+    /// the old one's, including pages it added; pages it dropped show in
+    /// its page count
+    pub changed_pages: Vec<usize>,
+}
+
+/// Render `new_markup` and report which of its pages differ from the ones
+/// of `old_markup`, so a frontend swaps only the affected SVGs
+///
+/// Both versions are rendered with `config` and without the output cache;
+/// the pages the versions share are exported once. Editors rendering every
+/// revision keep a [`RenderSession`] instead, which reports the same with
+/// [`RenderSession::changed_pages`] without rendering the old version again.
+///
+/// # Examples
+/// ```
+/// use render_engine::render_markup_diff;
+///
+/// let old = "= Scope\n#pagebreak()\n= Budget";
+/// let diff = render_markup_diff(old, "= Scope\n#pagebreak()\n= Budget, revised", None).unwrap();
+/// assert_eq!((diff.output.pages.len(), diff.changed_pages), (2, vec![1]));
+/// ```
+pub fn render_markup_diff(
+    old_markup: &str,
+    new_markup: &str,
+    config: Option<RenderConfig>,
+) -> Result<RenderDiff, TypstWrapperError> {
+    render_markup_diff_observed(old_markup, new_markup, config, &mut ())
+}
+
+/// Like [`render_markup_diff`], reporting the progress of the render of
+/// `new_markup` to `observer`
+pub fn render_markup_diff_observed(
+    old_markup: &str,
+    new_markup: &str,
+    config: Option<RenderConfig>,
+    observer: &mut dyn RenderObserver,
+) -> Result<RenderDiff, TypstWrapperError> {
+    // Cached renders carry no page hashes to compare
+    let config = RenderConfig { cache: false, ..config.unwrap_or_default() };
+    let mut session = RenderSession::new(Some(config));
+    session.render_markup(old_markup)?;
+    let output = session.render_markup_observed(new_markup, observer)?;
+    Ok(RenderDiff { output, changed_pages: session.changed_pages().to_vec() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets;
+    use crate::test_utils::MemoBuilder;
+    #[cfg(feature = "font-times")]
+    use crate::{OutputFormat, SvgTextMode};
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal().subject("Session")
+    }
+
+    #[cfg(feature = "font-times")]
+    fn svg(pages: Vec<Vec<u8>>) -> String {
+        String::from_utf8(pages.into_iter().next().unwrap()).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_session_renders_match_fresh_renders() {
+        let config = RenderConfig { svg_text: SvgTextMode::Selectable, cache: false, ..RenderConfig::default() };
+        let mut session = RenderSession::new(Some(config.clone()));
+        for markup in ["First draft", "First draft, edited", "#set page(height: 3in)\nSecond draft"] {
+            let pages = session.render_markup(markup).unwrap();
+            assert_eq!(pages, crate::render_markup(markup, Some(config.clone())).unwrap(), "{}", markup);
+        }
+        assert!(session.render_markup("#unknown").is_err());
+        assert!(svg(session.render_markup("Recovered").unwrap()).contains("Recovered"));
+
+        for body in ["Opening", "Opening and more"] {
+            let form = form().body_raw(body).to_json();
+            assert_eq!(session.render_form(&form).unwrap(), crate::render_form(&form, Some(config.clone())).unwrap());
+        }
+        assert!(svg(session.render_markup("Back to markup").unwrap()).contains("Back to markup"));
+
+        session.set_config(RenderConfig { format: OutputFormat::Pdf, ..config });
+        assert!(session.render_form(&form().to_json()).unwrap()[0].starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_session_reports_changed_pages() {
+        let config = RenderConfig { cache: false, ..RenderConfig::default() };
+        let mut session = RenderSession::new(Some(config.clone()));
+        session.render_markup("= A\n#pagebreak()\n= B\n#pagebreak()\n= C").unwrap();
+        assert_eq!(session.changed_pages(), [0, 1, 2]);
+        session.render_markup("= A\n#pagebreak()\n= B, edited\n#pagebreak()\n= C").unwrap();
+        assert_eq!(session.changed_pages(), [1]);
+        session.render_markup("= A\n#pagebreak()\n= B, edited\n#pagebreak()\n= C\n#pagebreak()\n= D").unwrap();
+        assert_eq!(session.changed_pages(), [3]);
+        let pages = session.render_markup("= A\n#pagebreak()\n= B, edited").unwrap();
+        assert_eq!((pages.len(), session.changed_pages()), (2, &[][..]));
+
+        // The same input again is not compiled when output may be cached
+        session.set_config(RenderConfig { cache: true, ..config });
+        let first = session.render_markup_with_metrics("= Repeated").unwrap();
+        let again = session.render_markup_with_metrics("= Repeated").unwrap();
+        assert!(again.metrics.from_cache);
+        assert_eq!((again.pages, session.changed_pages()), (first.pages, &[][..]));
+    }
+
+    #[test]
+    fn test_markup_diff_ignores_cache() {
+        let old = "= Diff\n#pagebreak()\n= Unchanged";
+        let config = RenderConfig { cache: true, ..RenderConfig::default() };
+        render_markup_diff(old, old, Some(config.clone())).unwrap();
+        let diff = render_markup_diff(old, old, Some(config.clone())).unwrap();
+        assert_eq!((diff.output.pages.len(), diff.changed_pages), (2, vec![]));
+        let diff = render_markup_diff(old, "= Diff, edited\n#pagebreak()\n= Unchanged", Some(config)).unwrap();
+        assert_eq!(diff.changed_pages, [0]);
+    }
+
+    #[test]
+    fn test_session_sees_new_registrations() {
+        let engine = RenderEngine::new();
+        let mut session = engine.session(None);
+        let form = form().set("letterhead-background", "session.gif").to_json();
+        assert!(session.render_form(&form).is_err());
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        engine.register_letterhead("session.gif", seal).unwrap();
+        assert!(session.render_form(&form).is_ok());
+        assert!(engine.unregister_letterhead("session.gif"));
+        assert!(session.render_form(&form).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_session_renders_again_on_a_new_day() {
+        let engine = RenderEngine::new();
+        let config = RenderConfig { svg_text: SvgTextMode::Selectable, ..RenderConfig::default() };
+        let mut session = engine.session(Some(config));
+        let markup = "#datetime.today().display()";
+        // 2024-12-31T23:30:00Z, and an hour later
+        engine.set_clock(|| crate::HostTime { timestamp_ms: 1_735_687_800_000, utc_offset_minutes: 0 });
+        let svg = String::from_utf8(session.render_markup(markup).unwrap().remove(0)).unwrap();
+        assert!(svg.contains("2024-12-31"));
+        engine.set_clock(|| crate::HostTime { timestamp_ms: 1_735_691_400_000, utc_offset_minutes: 0 });
+        let svg = String::from_utf8(session.render_markup(markup).unwrap().remove(0)).unwrap();
+        assert!(svg.contains("2025-01-01"), "{}", svg);
+    }
+}
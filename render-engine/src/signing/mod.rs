@@ -0,0 +1,522 @@
+//! PAdES digital signatures for rendered PDFs.
+//!
+//! [`sign_pdf`] appends an invisible signature field to a rendered PDF as an
+//! incremental update and fills it with a detached CMS signature
+//! (`ETSI.CAdES.detached`, PAdES baseline B-B) over the rest of the file.
+//! The signature is made by a [`Signer`]: [`KeySigner`] signs with an RSA
+//! key in memory, and HSM or CAC flows implement [`Signer`] themselves.
+//! Signers that can't be called synchronously, such as WebCrypto or a
+//! remote signing service, use [`PreparedSignature`] to get the bytes to
+//! sign and finish the PDF once the signature arrives.
+//!
+//! The signing time is only recorded when [`SignOptions::signing_time`] is
+//! set; it is not taken from the clock renders are dated with.
+//!
+//! [`stamp_form_signature`] adds a visible signature image, name and date
+//! over the memo signature block, before or after signing.
+
+mod stamp;
+
+use std::ops::Range;
+
+use cms::cert::{CertificateChoices, IssuerAndSerialNumber};
+use cms::content_info::{CmsVersion, ContentInfo};
+use cms::signed_data::{CertificateSet, EncapsulatedContentInfo, SignedData, SignerIdentifier, SignerInfo, SignerInfos};
+use der::asn1::{ObjectIdentifier, OctetString, SetOfVec};
+use der::{Any, Decode, Encode};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::{SignatureEncoding, Signer as _};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use x509_cert::attr::Attribute;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+pub use stamp::{stamp_form_signature, stamp_signature, SignatureStamp};
+
+use crate::pdf::{dict_value, reference, reference_array, references, text_string, with_entry, PdfUpdate};
+
+const ID_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const ID_CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const ID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const ID_SIGNING_CERTIFICATE_V2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.47");
+const ID_SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const SHA_256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const ECDSA_WITH_SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+
+/// Bytes reserved for the CMS signature in the PDF; enough for a 4096-bit
+/// RSA signature with a chain of several certificates
+const SIGNATURE_CAPACITY: usize = 16 * 1024;
+
+/// Errors while signing a PDF
+#[derive(Error, Debug)]
+pub enum SigningError {
+    #[error("Cannot sign this PDF: {0}")]
+    InvalidPdf(String),
+    #[error("Invalid private key: {0}")]
+    InvalidKey(String),
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+    #[error("Signer failed: {0}")]
+    Signer(String),
+    #[error("Signature of {0} bytes does not fit the space reserved in the PDF")]
+    TooLarge(usize),
+    #[error("Invalid signing time {0:?}; expected YYYY-MM-DDTHH:MM:SSZ")]
+    InvalidTime(String),
+    #[error("Invalid signature stamp: {0}")]
+    InvalidStamp(String),
+}
+
+/// Signature algorithm a [`Signer`] uses, always over a SHA-256 digest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    #[default]
+    RsaSha256,
+    /// ECDSA with SHA-256, DER-encoded signature
+    EcdsaSha256,
+}
+
+/// Makes the signature of a PDF
+pub trait Signer {
+    /// DER-encoded certificates: the signing certificate, then the rest of
+    /// its chain
+    fn certificates(&self) -> &[Vec<u8>];
+
+    /// Algorithm of the signatures returned by [`Signer::sign`]
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::RsaSha256
+    }
+
+    /// Sign `data` with the signing certificate's key, hashing it with SHA-256
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError>;
+}
+
+/// [`Signer`] with an RSA private key in memory
+pub struct KeySigner {
+    key: rsa::pkcs1v15::SigningKey<Sha256>,
+    certificates: Vec<Vec<u8>>,
+}
+
+impl KeySigner {
+    /// Load a PKCS#8 or PKCS#1 PEM private key and the PEM certificates of
+    /// its chain, signing certificate first
+    pub fn from_pem(key_pem: &str, certificates_pem: &str) -> Result<Self, SigningError> {
+        let key = rsa::RsaPrivateKey::from_pkcs8_pem(key_pem)
+            .map_err(|e| e.to_string())
+            .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(key_pem).map_err(|e| e.to_string()))
+            .map_err(SigningError::InvalidKey)?;
+        if certificates_pem.trim().is_empty() {
+            return Err(SigningError::InvalidCertificate("no certificate given".to_string()));
+        }
+        let certificates = Certificate::load_pem_chain(certificates_pem.as_bytes())
+            .map_err(|e| SigningError::InvalidCertificate(e.to_string()))?
+            .iter()
+            .map(|certificate| certificate.to_der().map_err(|e| SigningError::InvalidCertificate(e.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            key: rsa::pkcs1v15::SigningKey::new(key),
+            certificates,
+        })
+    }
+}
+
+impl Signer for KeySigner {
+    fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SigningError> {
+        let signature = self.key.try_sign(data).map_err(|e| SigningError::Signer(e.to_string()))?;
+        Ok(signature.to_vec())
+    }
+}
+
+/// Signature dictionary entries shown by PDF viewers
+#[derive(Debug, Clone, Default)]
+pub struct SignOptions {
+    /// Name of the signer (defaults to the certificate's subject)
+    pub name: Option<String>,
+    /// Reason for signing, e.g. `Approved for release`
+    pub reason: Option<String>,
+    /// Where the memo was signed
+    pub location: Option<String>,
+    /// How to reach the signer
+    pub contact_info: Option<String>,
+    /// Signing time in UTC, `YYYY-MM-DDTHH:MM:SSZ`
+    pub signing_time: Option<String>,
+}
+
+/// Sign a rendered PDF
+///
+/// # Examples
+/// ```no_run
+/// use render_engine::signing::{sign_pdf, KeySigner, SignOptions};
+///
+/// let pdf = std::fs::read("memo.pdf").unwrap();
+/// let signer = KeySigner::from_pem(
+///     &std::fs::read_to_string("key.pem").unwrap(),
+///     &std::fs::read_to_string("chain.pem").unwrap(),
+/// )
+/// .unwrap();
+/// let options = SignOptions { reason: Some("Approved for release".into()), ..Default::default() };
+/// std::fs::write("memo-signed.pdf", sign_pdf(&pdf, &signer, &options).unwrap()).unwrap();
+/// ```
+pub fn sign_pdf(pdf: &[u8], signer: &dyn Signer, options: &SignOptions) -> Result<Vec<u8>, SigningError> {
+    let prepared = PreparedSignature::prepare(pdf, signer.certificates(), signer.algorithm(), options)?;
+    let signature = signer.sign(prepared.to_be_signed())?;
+    prepared.finish(&signature)
+}
+
+/// A PDF with room for a signature, waiting for the signature value
+///
+/// Send [`PreparedSignature::to_be_signed`] to the signer and pass its
+/// signature to [`PreparedSignature::finish`].
+#[derive(Debug, Clone)]
+pub struct PreparedSignature {
+    pdf: Vec<u8>,
+    /// Hex string holding the signature, including its `<` and `>`
+    contents: Range<usize>,
+    certificates: Vec<Certificate>,
+    algorithm: SignatureAlgorithm,
+    signed_attributes: SetOfVec<Attribute>,
+    to_be_signed: Vec<u8>,
+}
+
+impl PreparedSignature {
+    /// Add an empty signature field to `pdf` for the first of
+    /// `certificates` (DER, signing certificate first)
+    pub fn prepare(
+        pdf: &[u8],
+        certificates: &[Vec<u8>],
+        algorithm: SignatureAlgorithm,
+        options: &SignOptions,
+    ) -> Result<Self, SigningError> {
+        let certificates = certificates
+            .iter()
+            .map(|der| Certificate::from_der(der).map_err(|e| SigningError::InvalidCertificate(e.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signing_certificate = certificates
+            .first()
+            .ok_or_else(|| SigningError::InvalidCertificate("no certificate given".to_string()))?;
+        let signing_time = options.signing_time.as_deref().map(pdf_date).transpose()?;
+
+        let (pdf, contents) = add_signature_field(pdf, signing_certificate, options, signing_time)?;
+        let mut digest = Sha256::new();
+        digest.update(&pdf[..contents.start]);
+        digest.update(&pdf[contents.end..]);
+        let signed_attributes = signed_attributes(&digest.finalize(), signing_certificate)?;
+        let to_be_signed = signed_attributes.to_der().map_err(|e| SigningError::Signer(e.to_string()))?;
+        Ok(Self {
+            pdf,
+            contents,
+            certificates,
+            algorithm,
+            signed_attributes,
+            to_be_signed,
+        })
+    }
+
+    /// The DER signed attributes, which the signer hashes with SHA-256 and
+    /// signs
+    pub fn to_be_signed(&self) -> &[u8] {
+        &self.to_be_signed
+    }
+
+    /// The signed PDF, given the signature of [`Self::to_be_signed`]
+    pub fn finish(self, signature: &[u8]) -> Result<Vec<u8>, SigningError> {
+        let cms = self.cms(signature).map_err(|e| SigningError::Signer(e.to_string()))?;
+        let hex_capacity = self.contents.len() - 2;
+        if cms.len() * 2 > hex_capacity {
+            return Err(SigningError::TooLarge(cms.len()));
+        }
+        let mut pdf = self.pdf;
+        let hex: String = cms.iter().map(|byte| format!("{:02X}", byte)).collect();
+        pdf[self.contents.start + 1..self.contents.start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(pdf)
+    }
+
+    /// The DER `ContentInfo` of the detached signed data
+    fn cms(&self, signature: &[u8]) -> der::Result<Vec<u8>> {
+        let signer = &self.certificates[0];
+        let sha256 = AlgorithmIdentifierOwned { oid: ID_SHA_256, parameters: None };
+        let signature_algorithm = match self.algorithm {
+            SignatureAlgorithm::RsaSha256 => AlgorithmIdentifierOwned {
+                oid: SHA_256_WITH_RSA,
+                parameters: Some(Any::from(der::asn1::Null)),
+            },
+            SignatureAlgorithm::EcdsaSha256 => AlgorithmIdentifierOwned {
+                oid: ECDSA_WITH_SHA_256,
+                parameters: None,
+            },
+        };
+        let signer_info = SignerInfo {
+            version: CmsVersion::V1,
+            sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: signer.tbs_certificate.issuer.clone(),
+                serial_number: signer.tbs_certificate.serial_number.clone(),
+            }),
+            digest_alg: sha256.clone(),
+            signed_attrs: Some(self.signed_attributes.clone()),
+            signature_algorithm,
+            signature: OctetString::new(signature)?,
+            unsigned_attrs: None,
+        };
+        let certificates = self.certificates.iter().cloned().map(CertificateChoices::Certificate).collect::<Vec<_>>();
+        let signed_data = SignedData {
+            version: CmsVersion::V1,
+            digest_algorithms: SetOfVec::try_from(vec![sha256])?,
+            encap_content_info: EncapsulatedContentInfo { econtent_type: ID_DATA, econtent: None },
+            certificates: Some(CertificateSet(SetOfVec::try_from(certificates)?)),
+            crls: None,
+            signer_infos: SignerInfos(SetOfVec::try_from(vec![signer_info])?),
+        };
+        ContentInfo {
+            content_type: ID_SIGNED_DATA,
+            content: Any::encode_from(&signed_data)?,
+        }
+        .to_der()
+    }
+}
+
+/// CMS signed attributes for a PAdES signature of a PDF with `digest`
+fn signed_attributes(digest: &[u8], signer: &Certificate) -> Result<SetOfVec<Attribute>, SigningError> {
+    let encode = || -> der::Result<SetOfVec<Attribute>> {
+        let attribute = |oid, value: Any| -> der::Result<Attribute> {
+            Ok(Attribute { oid, values: SetOfVec::try_from(vec![value])? })
+        };
+        // SigningCertificateV2 ::= SEQUENCE { certs SEQUENCE OF ESSCertIDv2 },
+        // ESSCertIDv2 ::= SEQUENCE { certHash OCTET STRING } for SHA-256
+        let certificate_hash = Sha256::digest(signer.to_der()?);
+        let ess_cert_id = vec![Any::encode_from(&OctetString::new(certificate_hash.as_slice())?)?];
+        let signing_certificate = vec![Any::encode_from(&vec![Any::encode_from(&ess_cert_id)?])?];
+        SetOfVec::try_from(vec![
+            attribute(ID_CONTENT_TYPE, Any::encode_from(&ID_DATA)?)?,
+            attribute(ID_MESSAGE_DIGEST, Any::encode_from(&OctetString::new(digest)?)?)?,
+            attribute(ID_SIGNING_CERTIFICATE_V2, Any::encode_from(&signing_certificate)?)?,
+        ])
+    };
+    encode().map_err(|e| SigningError::Signer(e.to_string()))
+}
+
+/// Append an invisible signature field with an empty signature to `pdf`,
+/// returning the new PDF and the range of the signature hex string
+fn add_signature_field(
+    pdf: &[u8],
+    signer: &Certificate,
+    options: &SignOptions,
+    signing_time: Option<String>,
+) -> Result<(Vec<u8>, Range<usize>), SigningError> {
+    let invalid = |message: &str| SigningError::InvalidPdf(message.to_string());
+    let mut update = PdfUpdate::parse(pdf).map_err(SigningError::InvalidPdf)?;
+    let page = *update.pages().map_err(SigningError::InvalidPdf)?.first().ok_or_else(|| invalid("no pages"))?;
+    let root = update.root();
+    let catalog = update.object(root).ok_or_else(|| invalid("missing catalog"))?;
+
+    // Existing form fields, e.g. from an earlier signature
+    let (acro_form, mut fields) = match dict_value(&catalog, "AcroForm") {
+        Some(value) if value.starts_with("<<") => (None, references(dict_value(value, "Fields").unwrap_or_default())),
+        Some(value) => {
+            let number = reference(value).ok_or_else(|| invalid("invalid /AcroForm"))?;
+            let form = update.object(number).ok_or_else(|| invalid("missing /AcroForm"))?;
+            (Some((number, form.clone())), references(dict_value(&form, "Fields").unwrap_or_default()))
+        }
+        None => (None, Vec::new()),
+    };
+
+    let name = options.name.clone().unwrap_or_else(|| common_name(signer));
+    let mut signature = format!(
+        "<<\n  /Type /Sig\n  /Filter /Adobe.PPKLite\n  /SubFilter /ETSI.CAdES.detached\n  \
+         /ByteRange [0 0000000000 0000000000 0000000000]\n  /Contents <{}>\n  /Name {}\n",
+        "0".repeat(SIGNATURE_CAPACITY * 2),
+        text_string(&name)
+    );
+    for (key, value) in [
+        ("Reason", &options.reason),
+        ("Location", &options.location),
+        ("ContactInfo", &options.contact_info),
+    ] {
+        if let Some(value) = value {
+            signature.push_str(&format!("  /{} {}\n", key, text_string(value)));
+        }
+    }
+    if let Some(time) = &signing_time {
+        signature.push_str(&format!("  /M ({})\n", time));
+    }
+    signature.push_str(">>");
+    let signature_number = update.allocate();
+    update.set(signature_number, signature);
+
+    let field = update.allocate();
+    update.set(
+        field,
+        format!(
+            "<<\n  /Type /Annot\n  /Subtype /Widget\n  /FT /Sig\n  /T {}\n  /V {} 0 R\n  /F 132\n  /Rect [0 0 0 0]\n  /P {} 0 R\n>>",
+            text_string(&format!("Signature{}", fields.len() + 1)),
+            signature_number,
+            page
+        ),
+    );
+    fields.push(field);
+
+    update.add_annotation(page, field).map_err(SigningError::InvalidPdf)?;
+
+    let fields = reference_array(&fields);
+    match acro_form {
+        Some((number, form)) => {
+            let form = with_entry(&form, "Fields", &fields).ok_or_else(|| invalid("invalid /AcroForm"))?;
+            let form = with_entry(&form, "SigFlags", "3").ok_or_else(|| invalid("invalid /AcroForm"))?;
+            update.set(number, form);
+        }
+        None => {
+            let form = format!("<< /Fields {} /SigFlags 3 >>", fields);
+            update.set(root, with_entry(&catalog, "AcroForm", &form).ok_or_else(|| invalid("invalid catalog"))?);
+        }
+    }
+
+    let (mut pdf, offsets) = update.finish();
+    let object_start = offsets.iter().find(|(number, _)| *number == signature_number).map(|(_, offset)| *offset);
+    let object_start = object_start.ok_or_else(|| invalid("signature was not written"))?;
+    let find = |needle: &[u8]| {
+        pdf[object_start..].windows(needle.len()).position(|window| window == needle).map(|at| object_start + at + needle.len())
+    };
+    let byte_range = find(b"/ByteRange [0 ").ok_or_else(|| invalid("signature was not written"))?;
+    let contents_start = find(b"/Contents ").ok_or_else(|| invalid("signature was not written"))?;
+    let contents = contents_start..contents_start + SIGNATURE_CAPACITY * 2 + 2;
+    let ranges = format!("{:010} {:010} {:010}", contents.start, contents.end, pdf.len() - contents.end);
+    pdf[byte_range..byte_range + ranges.len()].copy_from_slice(ranges.as_bytes());
+    Ok((pdf, contents))
+}
+
+/// The subject common name of `certificate`, or the whole subject if it
+/// has none
+fn common_name(certificate: &Certificate) -> String {
+    const COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+    let subject = &certificate.tbs_certificate.subject;
+    subject
+        .0
+        .iter()
+        .flat_map(|name| name.0.iter())
+        .find(|attribute| attribute.oid == COMMON_NAME)
+        .and_then(|attribute| std::str::from_utf8(attribute.value.value()).ok().map(str::to_string))
+        .unwrap_or_else(|| subject.to_string())
+}
+
+/// PDF date of an RFC 3339 UTC timestamp
+fn pdf_date(time: &str) -> Result<String, SigningError> {
+    let invalid = || SigningError::InvalidTime(time.to_string());
+    let bytes = time.as_bytes();
+    let layout = b"0000-00-00T00:00:00Z";
+    if bytes.len() != layout.len() {
+        return Err(invalid());
+    }
+    let mut date = String::from("D:");
+    for (&c, &expected) in bytes.iter().zip(layout) {
+        match expected {
+            b'0' if c.is_ascii_digit() => date.push(c as char),
+            b'0' => return Err(invalid()),
+            _ if c.eq_ignore_ascii_case(&expected) => {}
+            _ => return Err(invalid()),
+        }
+    }
+    date.push('Z');
+    Ok(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs1v15::{Signature, VerifyingKey};
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::signature::Verifier;
+
+    use super::*;
+
+    const KEY: &str = include_str!("../../tests/fixtures/signing/key.pem");
+    const CERTIFICATE: &str = include_str!("../../tests/fixtures/signing/cert.pem");
+
+    fn rendered_pdf() -> Vec<u8> {
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        crate::render_markup("= Signed\n#pagebreak()\nPage two", Some(config)).unwrap().remove(0)
+    }
+
+    /// The byte ranges and CMS signature of the last signature in `pdf`
+    fn signature_of(pdf: &[u8]) -> (Vec<u8>, SignedData) {
+        let at = pdf.windows(12).rposition(|window| window == b"/ByteRange [").unwrap() + 12;
+        let end = at + pdf[at..].iter().position(|&b| b == b']').unwrap();
+        let ranges: Vec<usize> =
+            std::str::from_utf8(&pdf[at..end]).unwrap().split_whitespace().map(|n| n.parse().unwrap()).collect();
+        let signed = [&pdf[ranges[0]..ranges[0] + ranges[1]], &pdf[ranges[2]..ranges[2] + ranges[3]]].concat();
+        let hex = std::str::from_utf8(&pdf[ranges[1] + 1..ranges[2] - 1]).unwrap();
+        let bytes: Vec<u8> = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect();
+        // The reserved space is padded with zeros after the CMS value
+        let content_info = ContentInfo::decode(&mut der::SliceReader::new(&bytes).unwrap()).unwrap();
+        let signed_data = content_info.content.decode_as::<SignedData>().unwrap();
+        (signed, signed_data)
+    }
+
+    #[test]
+    fn test_signature_verifies() {
+        let pdf = rendered_pdf();
+        let signer = KeySigner::from_pem(KEY, CERTIFICATE).unwrap();
+        let options = SignOptions {
+            reason: Some("Approved for release".to_string()),
+            signing_time: Some("2025-09-09T14:00:00Z".to_string()),
+            ..SignOptions::default()
+        };
+        let signed = sign_pdf(&pdf, &signer, &options).unwrap();
+        assert!(signed.starts_with(&pdf));
+        let text = String::from_utf8_lossy(&signed);
+        assert!(text.contains("/M (D:20250909140000Z)"));
+        assert!(text.contains("/Reason (Approved for release)"));
+        assert!(text.contains("/Name (Test Signer)"));
+
+        let (signed_bytes, signed_data) = signature_of(&signed);
+        let signer_info = signed_data.signer_infos.0.get(0).unwrap();
+        let attributes = signer_info.signed_attrs.as_ref().unwrap();
+        let digest = attributes.iter().find(|a| a.oid == ID_MESSAGE_DIGEST).unwrap().values.get(0).unwrap();
+        assert_eq!(digest.value(), Sha256::digest(&signed_bytes).as_slice());
+
+        let certificate = Certificate::load_pem_chain(CERTIFICATE.as_bytes()).unwrap().remove(0);
+        let spki = certificate.tbs_certificate.subject_public_key_info.to_der().unwrap();
+        let public_key = rsa::RsaPublicKey::from_public_key_der(&spki).unwrap();
+        let signature = Signature::try_from(signer_info.signature.as_bytes()).unwrap();
+        VerifyingKey::<Sha256>::new(public_key).verify(&attributes.to_der().unwrap(), &signature).unwrap();
+    }
+
+    #[test]
+    fn test_second_signature_adds_a_field() {
+        let signer = KeySigner::from_pem(KEY, CERTIFICATE).unwrap();
+        let once = sign_pdf(&rendered_pdf(), &signer, &SignOptions::default()).unwrap();
+        let twice = sign_pdf(&once, &signer, &SignOptions::default()).unwrap();
+        assert!(twice.starts_with(&once));
+        let update = PdfUpdate::parse(&twice).unwrap();
+        let catalog = update.object(update.root()).unwrap();
+        let form = dict_value(&catalog, "AcroForm").unwrap();
+        assert_eq!(references(dict_value(form, "Fields").unwrap()).len(), 2);
+        let (signed_bytes, _) = signature_of(&twice);
+        assert_eq!(signed_bytes.len() + SIGNATURE_CAPACITY * 2 + 2, twice.len());
+    }
+
+    #[test]
+    fn test_prepared_signature_with_external_signer() {
+        let signer = KeySigner::from_pem(KEY, CERTIFICATE).unwrap();
+        let prepared =
+            PreparedSignature::prepare(&rendered_pdf(), signer.certificates(), SignatureAlgorithm::RsaSha256, &SignOptions::default())
+                .unwrap();
+        let signature = signer.sign(prepared.to_be_signed()).unwrap();
+        assert!(prepared.clone().finish(&vec![0; SIGNATURE_CAPACITY]).is_err());
+        assert!(prepared.finish(&signature).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_inputs_are_rejected() {
+        assert!(matches!(KeySigner::from_pem("not a key", CERTIFICATE), Err(SigningError::InvalidKey(_))));
+        assert!(matches!(KeySigner::from_pem(KEY, ""), Err(SigningError::InvalidCertificate(_))));
+        let signer = KeySigner::from_pem(KEY, CERTIFICATE).unwrap();
+        assert!(matches!(sign_pdf(b"%PDF-1.7", &signer, &SignOptions::default()), Err(SigningError::InvalidPdf(_))));
+        assert_eq!(pdf_date("2025-01-02T03:04:05Z").unwrap(), "D:20250102030405Z");
+        assert!(pdf_date("2025-01-02 03:04:05").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Visible signature stamps.
+//!
+//! A stamp is a printable annotation showing a handwritten signature image
+//! with the signer's name and the date. It is appended as an incremental
+//! update, so it can be added after [`sign_pdf`](super::sign_pdf) without
+//! touching the signed bytes.
+
+use std::fmt::Write;
+
+use crate::locate::{locate_form_label, ElementPosition, SIGNATURE_BLOCK_LABEL};
+use crate::pdf::{text_string, PdfUpdate};
+
+use super::SigningError;
+
+/// Font size of the stamp text, in points
+const FONT_SIZE: f64 = 8.0;
+const LINE_HEIGHT: f64 = 9.5;
+
+/// Appearance of a signature stamp
+#[derive(Debug, Clone)]
+pub struct SignatureStamp {
+    /// PNG or JPEG image of the signature
+    pub image: Option<Vec<u8>>,
+    /// Name shown under the image
+    pub name: Option<String>,
+    /// Date shown under the name, as the caller wants it displayed
+    pub date: Option<String>,
+    /// Width of the stamp, in points
+    pub width: f64,
+    /// Height of the stamp, in points
+    pub height: f64,
+}
+
+impl Default for SignatureStamp {
+    fn default() -> Self {
+        Self {
+            image: None,
+            name: None,
+            date: None,
+            width: 144.0,
+            height: 40.0,
+        }
+    }
+}
+
+/// Stamp a signature appearance onto `pdf` with its lower left corner at
+/// `position`
+///
+/// Positions come from [`locate_form_label`] or
+/// [`locate_markup_label`](crate::locate_markup_label) on the document the
+/// PDF was rendered from. The stamp sits on top of the element's first
+/// line, in the space left for the handwritten signature.
+pub fn stamp_signature(pdf: &[u8], position: &ElementPosition, stamp: &SignatureStamp) -> Result<Vec<u8>, SigningError> {
+    if stamp.image.is_none() && stamp.name.is_none() && stamp.date.is_none() {
+        return Err(SigningError::InvalidStamp("the stamp has no image or text".to_string()));
+    }
+    if !(stamp.width > 0.0 && stamp.height > 0.0) {
+        return Err(SigningError::InvalidStamp("the stamp must have a positive size".to_string()));
+    }
+
+    let mut update = PdfUpdate::parse(pdf).map_err(SigningError::InvalidPdf)?;
+    let pages = update.pages().map_err(SigningError::InvalidPdf)?;
+    let page = *pages
+        .get(position.page)
+        .ok_or_else(|| SigningError::InvalidStamp(format!("the PDF has no page {}", position.page + 1)))?;
+    let (_, page_height) = update
+        .page_size(page)
+        .ok_or_else(|| SigningError::InvalidPdf("page has no /MediaBox".to_string()))?;
+
+    let lines: Vec<&str> = [stamp.name.as_deref(), stamp.date.as_deref()].into_iter().flatten().collect();
+    let text_height = lines.len() as f64 * LINE_HEIGHT;
+
+    let mut resources = String::new();
+    let mut content = String::new();
+    if let Some(image) = &stamp.image {
+        let (number, width, height) = add_image(&mut update, image)?;
+        // Fit the image above the text, keeping its aspect ratio
+        let available = (stamp.height - text_height).max(0.0);
+        let scale = (stamp.width / width as f64).min(available / height as f64);
+        let _ = write!(resources, " /XObject << /Im0 {} 0 R >>", number);
+        let _ = writeln!(
+            content,
+            "q {:.3} 0 0 {:.3} 0 {:.3} cm /Im0 Do Q",
+            width as f64 * scale,
+            height as f64 * scale,
+            text_height
+        );
+    }
+    if !lines.is_empty() {
+        let font = update.allocate();
+        update.set(font, "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>");
+        let _ = write!(resources, " /Font << /Helv {} 0 R >>", font);
+        let _ = writeln!(content, "BT /Helv {} Tf 0 g", FONT_SIZE);
+        for (i, line) in lines.iter().enumerate() {
+            let baseline = text_height - (i as f64 + 1.0) * LINE_HEIGHT + 2.0;
+            let _ = writeln!(content, "1 0 0 1 0 {:.3} Tm {} Tj", baseline, win_ansi_string(line));
+        }
+        content.push_str("ET\n");
+    }
+
+    let appearance = update.allocate();
+    let mut stream = format!(
+        "<< /Type /XObject /Subtype /Form /BBox [0 0 {w:.3} {h:.3}] /Resources <<{}>> /Length {} >>\nstream\n",
+        resources,
+        content.len(),
+        w = stamp.width,
+        h = stamp.height
+    );
+    stream.push_str(&content);
+    stream.push_str("endstream");
+    update.set(appearance, stream);
+
+    let left = position.x;
+    let bottom = page_height - position.y;
+    let annotation = update.allocate();
+    let description = match &stamp.name {
+        Some(name) => format!("Signature of {}", name),
+        None => "Signature".to_string(),
+    };
+    update.set(
+        annotation,
+        format!(
+            "<<\n  /Type /Annot\n  /Subtype /Stamp\n  /Rect [{:.3} {:.3} {:.3} {:.3}]\n  /F 132\n  /Contents {}\n  /AP << /N {} 0 R >>\n>>",
+            left,
+            bottom,
+            left + stamp.width,
+            bottom + stamp.height,
+            text_string(&description),
+            appearance
+        ),
+    );
+    update.add_annotation(page, annotation).map_err(SigningError::InvalidPdf)?;
+    Ok(update.finish().0)
+}
+
+/// Stamp a signature appearance over the signature block of a PDF rendered
+/// from the memo form `json_input`
+///
+/// # Examples
+/// ```no_run
+/// use render_engine::signing::{stamp_form_signature, SignatureStamp};
+///
+/// # let (json, signed_pdf) = (String::new(), Vec::new());
+/// let stamp = SignatureStamp {
+///     image: Some(std::fs::read("signature.png").unwrap()),
+///     name: Some("FIRST M. LAST".to_string()),
+///     date: Some("9 September 2025".to_string()),
+///     ..SignatureStamp::default()
+/// };
+/// let stamped = stamp_form_signature(&signed_pdf, &json, &stamp).unwrap();
+/// ```
+pub fn stamp_form_signature(pdf: &[u8], json_input: &str, stamp: &SignatureStamp) -> Result<Vec<u8>, SigningError> {
+    let positions = locate_form_label(json_input, SIGNATURE_BLOCK_LABEL).map_err(|e| SigningError::InvalidStamp(e.to_string()))?;
+    let position = positions
+        .last()
+        .ok_or_else(|| SigningError::InvalidStamp("the memo has no signature block".to_string()))?;
+    stamp_signature(pdf, position, stamp)
+}
+
+/// Add a PNG or JPEG as an image XObject, with a soft mask if it has
+/// transparency, returning its object number and pixel size
+fn add_image(update: &mut PdfUpdate, bytes: &[u8]) -> Result<(u32, u32, u32), SigningError> {
+    let image = image::load_from_memory(bytes).map_err(|e| SigningError::InvalidStamp(format!("unreadable image: {}", e)))?;
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let rgb: Vec<u8> = rgba.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    let alpha: Vec<u8> = rgba.pixels().map(|pixel| pixel[3]).collect();
+
+    let image_object = |color_space: &str, data: &[u8], extra: &str| {
+        let data = miniz_oxide::deflate::compress_to_vec_zlib(data, 6);
+        let mut object = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} /BitsPerComponent 8 /Filter /FlateDecode{} /Length {} >>\nstream\n",
+            width,
+            height,
+            color_space,
+            extra,
+            data.len()
+        )
+        .into_bytes();
+        object.extend_from_slice(&data);
+        object.extend_from_slice(b"\nendstream");
+        object
+    };
+
+    let mask = if alpha.iter().any(|&a| a < 255) {
+        let mask = update.allocate();
+        update.set(mask, image_object("DeviceGray", &alpha, ""));
+        format!(" /SMask {} 0 R", mask)
+    } else {
+        String::new()
+    };
+    let number = update.allocate();
+    update.set(number, image_object("DeviceRGB", &rgb, &mask));
+    Ok((number, width, height))
+}
+
+/// A literal string in the standard fonts' WinAnsi encoding, with `?` for
+/// characters it lacks
+fn win_ansi_string(text: &str) -> String {
+    let mut literal = String::from("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            ' '..='~' => literal.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(literal, "\\{:03o}", c as u32);
+            }
+            _ => literal.push('?'),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{dict_value, references};
+    use crate::test_utils::MemoBuilder;
+
+    fn form() -> String {
+        MemoBuilder::minimal().subject("Stamped Memo").to_json()
+    }
+
+    /// A 2x1 PNG with one transparent pixel
+    fn png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let image = image::RgbaImage::from_raw(2, 1, vec![0, 0, 0, 255, 0, 0, 0, 0]).unwrap();
+        image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_stamp_over_signature_block() {
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let pdf = crate::render_form(&form(), Some(config)).unwrap().remove(0);
+        let stamp = SignatureStamp {
+            image: Some(png()),
+            name: Some("FIRST M. LAST".to_string()),
+            date: Some("9 Sep 2025".to_string()),
+            ..SignatureStamp::default()
+        };
+        let stamped = stamp_form_signature(&pdf, &form(), &stamp).unwrap();
+        assert!(stamped.starts_with(&pdf));
+
+        let update = PdfUpdate::parse(&stamped).unwrap();
+        let page = update.pages().unwrap()[0];
+        let annotations = references(dict_value(&update.object(page).unwrap(), "Annots").unwrap());
+        let annotation = update.object(*annotations.last().unwrap()).unwrap();
+        assert_eq!(dict_value(&annotation, "Subtype"), Some("/Stamp"));
+        assert_eq!(dict_value(&annotation, "Contents"), Some("(Signature of FIRST M. LAST)"));
+
+        // Above the signature block, at its left edge
+        let position = locate_form_label(&form(), SIGNATURE_BLOCK_LABEL).unwrap()[0];
+        let rect: Vec<f64> =
+            dict_value(&annotation, "Rect").unwrap().trim_matches(['[', ']']).split_whitespace().map(|n| n.parse().unwrap()).collect();
+        let (_, height) = update.page_size(page).unwrap();
+        assert!((rect[0] - position.x).abs() < 0.01);
+        assert!((rect[1] - (height - position.y)).abs() < 0.01);
+        assert!((rect[3] - rect[1] - 40.0).abs() < 0.01);
+        assert!(String::from_utf8_lossy(&stamped).contains("/SMask"));
+    }
+
+    #[test]
+    fn test_invalid_stamps_are_rejected() {
+        let position = ElementPosition { page: 0, x: 0.0, y: 0.0 };
+        let config = crate::RenderConfig { format: crate::OutputFormat::Pdf, ..Default::default() };
+        let pdf = crate::render_markup("Hi", Some(config)).unwrap().remove(0);
+        assert!(matches!(stamp_signature(&pdf, &position, &SignatureStamp::default()), Err(SigningError::InvalidStamp(_))));
+        let stamp = SignatureStamp { image: Some(b"not an image".to_vec()), ..SignatureStamp::default() };
+        assert!(matches!(stamp_signature(&pdf, &position, &stamp), Err(SigningError::InvalidStamp(_))));
+        let stamp = SignatureStamp { name: Some("Name".to_string()), ..SignatureStamp::default() };
+        let beyond = ElementPosition { page: 3, ..position };
+        assert!(matches!(stamp_signature(&pdf, &beyond, &stamp), Err(SigningError::InvalidStamp(_))));
+        assert_eq!(win_ansi_string("Zoë (ok) ✓"), "(Zo\\353 \\(ok\\) ?)");
+    }
+}
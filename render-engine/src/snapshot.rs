@@ -0,0 +1,274 @@
+//! Engine state snapshots.
+//!
+//! A fresh engine knows nothing of what the host set up: registered
+//! letterhead backgrounds, files, local packages and fonts, a custom memo template and
+//! overridden partials, registration limits, and the template sources
+//! parsed into the shared cache. [`engine_snapshot`] serializes that state
+//! so a server can warm new workers from it, and a wasm host can keep it in
+//! IndexedDB and bring it back with [`restore_engine_snapshot`] on the next
+//! page load instead of registering everything again.
+//!
+//! The template sources are recorded as a [template
+//! artifact](crate::artifact); rendered output cached by a
+//! [`CacheProvider`](crate::cache::CacheProvider) lives with the host and is
+//! not part of a snapshot. Like artifacts, snapshots are tied to the engine
+//! version that took them.
+
+use std::collections::BTreeMap;
+
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use typst::foundations::Bytes;
+use tracing::{debug, instrument};
+
+use crate::artifact::{self, TemplateArtifact};
+use crate::assets::{self, RegistrationLimits};
+use crate::engine;
+use crate::fonts;
+use crate::packages;
+use crate::template::{self, Partial};
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// Version of the snapshot layout, bumped on incompatible changes
+const SNAPSHOT_FORMAT: u32 = 6;
+
+/// Serialized form of the engine state
+#[derive(Serialize, Deserialize)]
+struct EngineSnapshot {
+    format: u32,
+    engine: String,
+    limits: RegistrationLimits,
+    letterheads: Vec<SnapshotLetterhead>,
+    /// Registered images and other files
+    assets: Vec<SnapshotFile>,
+    local_packages: Vec<SnapshotPackage>,
+    /// Registered fonts, base64-encoded
+    fonts: Vec<String>,
+    memo_template: Option<Vec<SnapshotFile>>,
+    template_partials: BTreeMap<Partial, String>,
+    template: TemplateArtifact,
+}
+
+/// A registered letterhead background, base64-encoded
+#[derive(Serialize, Deserialize)]
+struct SnapshotLetterhead {
+    name: String,
+    data: String,
+}
+
+/// A registered local package with its base64-encoded files
+#[derive(Serialize, Deserialize)]
+struct SnapshotPackage {
+    spec: String,
+    files: Vec<SnapshotFile>,
+}
+
+/// A registered file or a file of a package or template, base64-encoded
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    path: String,
+    data: String,
+}
+
+/// What [`restore_engine_snapshot`] brought back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RestoredState {
+    /// Letterhead backgrounds now registered
+    pub letterheads: usize,
+    /// Images and other files now registered
+    pub assets: usize,
+    /// Local packages now registered
+    pub local_packages: usize,
+    /// Fonts now registered
+    pub fonts: usize,
+    /// Whether a custom memo template is now set
+    pub memo_template: bool,
+    /// Template partials now overridden
+    pub template_partials: usize,
+    /// Template sources parsed into the shared cache
+    pub template_sources: usize,
+}
+
+/// Serialize the registered assets, registration limits and template
+/// sources of this engine
+///
+/// Compiles the memo template once to find the sources it needs, like
+/// [`export_template_artifact`](crate::export_template_artifact).
+///
+/// # Examples
+/// ```
+/// use render_engine::{engine_snapshot, restore_engine_snapshot};
+///
+/// let snapshot = engine_snapshot().unwrap();
+/// // In a new worker
+/// let restored = restore_engine_snapshot(&snapshot).unwrap();
+/// assert!(restored.template_sources > 0);
+/// ```
+#[instrument]
+pub fn engine_snapshot() -> Result<Vec<u8>, TypstWrapperError> {
+    let snapshot = EngineSnapshot {
+        format: SNAPSHOT_FORMAT,
+        engine: env!("CARGO_PKG_VERSION").to_string(),
+        limits: assets::registration_limits(),
+        letterheads: assets::registered_letterheads()
+            .into_iter()
+            .map(|(name, bytes)| SnapshotLetterhead {
+                name,
+                data: base64::engine::general_purpose::STANDARD.encode(bytes.as_slice()),
+            })
+            .collect(),
+        assets: encode_files(assets::registered_files()),
+        local_packages: packages::registered_local_packages()
+            .into_iter()
+            .map(|(spec, files)| SnapshotPackage {
+                spec,
+                files: encode_files(files),
+            })
+            .collect(),
+        fonts: fonts::registered_fonts()
+            .into_iter()
+            .map(|(_, data)| base64::engine::general_purpose::STANDARD.encode(data.as_slice()))
+            .collect(),
+        memo_template: engine::current().memo_template().map(|files| {
+            let mut files: Vec<_> = files.iter().map(|(path, data)| (path.clone(), data.clone())).collect();
+            files.sort_by(|(a, _), (b, _)| a.cmp(b));
+            encode_files(files)
+        }),
+        template_partials: BTreeMap::clone(&engine::current().template_partials()),
+        template: artifact::template_artifact()?,
+    };
+    serde_json::to_vec(&snapshot).map_err(|e| TypstWrapperError::Validation(format!("Engine snapshot: {}", e)))
+}
+
+/// Restore the state recorded by [`engine_snapshot`]
+///
+/// The snapshot's registration limits replace the current ones, its
+/// letterhead backgrounds, files, local packages and fonts are registered
+/// (replacing any of the same name), its memo template replaces the current one, its
+/// partial overrides replace those of the same partial and its
+/// template sources are parsed into the shared cache. Fails without
+/// registering anything if the snapshot is malformed, comes from another
+/// engine version or holds assets the limits do not allow.
+#[instrument(skip_all, fields(len = bytes.len()))]
+pub fn restore_engine_snapshot(bytes: &[u8]) -> Result<RestoredState, TypstWrapperError> {
+    let invalid = |message: String| TypstWrapperError::Validation(format!("Invalid engine snapshot: {}", message));
+    let snapshot: EngineSnapshot = serde_json::from_slice(bytes).map_err(|e| invalid(e.to_string()))?;
+    if snapshot.format != SNAPSHOT_FORMAT || snapshot.engine != env!("CARGO_PKG_VERSION") {
+        return Err(invalid(format!(
+            "taken by engine {} (format {}), this is engine {}",
+            snapshot.engine,
+            snapshot.format,
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+    let letterheads = snapshot
+        .letterheads
+        .into_iter()
+        .map(|letterhead| {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(&letterhead.data)
+                .map_err(|e| invalid(format!("letterhead '{}': {}", letterhead.name, e)))?;
+            TypstWrapper::check_letterhead(&letterhead.name, &data)?;
+            Ok((letterhead.name, data))
+        })
+        .collect::<Result<Vec<_>, TypstWrapperError>>()?;
+    let files = decode_files(snapshot.assets)
+        .map_err(|e| invalid(format!("asset: {}", e)))?
+        .into_iter()
+        .map(|(path, data)| Ok((TypstWrapper::check_asset_path(&path)?, data)))
+        .collect::<Result<Vec<_>, TypstWrapperError>>()?;
+    let local_packages = snapshot
+        .local_packages
+        .into_iter()
+        .map(|package| {
+            let files = decode_files(package.files).map_err(|e| invalid(format!("package {}: {}", package.spec, e)))?;
+            packages::prepare(files)
+        })
+        .collect::<Result<Vec<_>, TypstWrapperError>>()?;
+    let fonts = snapshot
+        .fonts
+        .into_iter()
+        .map(|data| {
+            let data = base64::engine::general_purpose::STANDARD.decode(&data).map_err(|e| invalid(format!("font: {}", e)))?;
+            fonts::prepare(data)
+        })
+        .collect::<Result<Vec<_>, TypstWrapperError>>()?;
+    let memo_template = match snapshot.memo_template {
+        Some(files) => Some(template::template_files(decode_files(files).map_err(|e| invalid(format!("memo template: {}", e)))?)?),
+        None => None,
+    };
+    let restored = RestoredState {
+        letterheads: letterheads.len(),
+        assets: files.len(),
+        local_packages: local_packages.len(),
+        fonts: fonts.len(),
+        memo_template: memo_template.is_some(),
+        template_partials: snapshot.template_partials.len(),
+        template_sources: 0,
+    };
+
+    let template_sources = artifact::load_template_artifact(&snapshot.template)?;
+    assets::register_assets(letterheads, files, local_packages, fonts, snapshot.limits).map_err(TypstWrapperError::Registration)?;
+    assets::set_registration_limits(snapshot.limits);
+    engine::current().set_memo_template_files(memo_template);
+    for (partial, source) in snapshot.template_partials {
+        engine::current().set_template_partial_source(partial, Some(source));
+    }
+    debug!(letterheads = restored.letterheads, assets = restored.assets, local_packages = restored.local_packages, fonts = restored.fonts, template_sources, "engine snapshot restored");
+    Ok(RestoredState { template_sources, ..restored })
+}
+
+fn encode_files(files: Vec<(String, Bytes)>) -> Vec<SnapshotFile> {
+    files
+        .into_iter()
+        .map(|(path, data)| SnapshotFile { path, data: base64::engine::general_purpose::STANDARD.encode(data.as_slice()) })
+        .collect()
+}
+
+fn decode_files(files: Vec<SnapshotFile>) -> Result<Vec<(String, Vec<u8>)>, String> {
+    files
+        .into_iter()
+        .map(|file| {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(&file.data)
+                .map_err(|e| format!("file '{}': {}", file.path, e))?;
+            Ok((file.path, data))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let bytes = engine_snapshot().unwrap();
+        let mut snapshot: EngineSnapshot = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(snapshot.limits, assets::registration_limits());
+
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content;
+        snapshot.letterheads = vec![SnapshotLetterhead {
+            name: "snapshot-test.gif".to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(seal),
+        }];
+        let restored = restore_engine_snapshot(&serde_json::to_vec(&snapshot).unwrap()).unwrap();
+        assert_eq!(restored.letterheads, 1);
+        assert!(restored.template_sources > 0);
+        assert!(assets::letterhead_exists("snapshot-test.gif"));
+        assert!(assets::unregister_letterhead("snapshot-test.gif"));
+
+        snapshot.letterheads[0].data = base64::engine::general_purpose::STANDARD.encode(b"not an image");
+        assert!(restore_engine_snapshot(&serde_json::to_vec(&snapshot).unwrap()).is_err());
+        assert!(!assets::letterhead_exists("snapshot-test.gif"));
+    }
+
+    #[test]
+    fn test_foreign_snapshots_are_rejected() {
+        let mut snapshot: EngineSnapshot = serde_json::from_slice(&engine_snapshot().unwrap()).unwrap();
+        snapshot.engine = "0.0.0".to_string();
+        let result = restore_engine_snapshot(&serde_json::to_vec(&snapshot).unwrap());
+        assert!(matches!(result, Err(TypstWrapperError::Validation(message)) if message.contains("engine 0.0.0")));
+        assert!(restore_engine_snapshot(b"{}").is_err());
+    }
+}
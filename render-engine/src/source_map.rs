@@ -0,0 +1,205 @@
+//! Source maps from rendered pages back to the form.
+//!
+//! An editor showing a memo preview next to its form wants a click on the
+//! preview to select the field it came from, and a Delta body editor wants
+//! to scroll to the op. [`form_source_map`] renders a form with every field
+//! the template shows wrapped in `<source-start>` and `<source-end>`
+//! metadata and the Delta ops of the body marked the same way, then gathers
+//! the bounding box of what each field and op drew on each page.
+//!
+//! Fields are named by JSON pointers into the form (`/subject`,
+//! `/memo-for/0`, `/body`); a body given as `body_raw` maps to `/body_raw`
+//! and has no ops.
+
+use serde::Serialize;
+use tracing::instrument;
+use typst::foundations::Value;
+use typst::introspection::{MetadataElem, Tag};
+use typst::layout::{Abs, Frame, FrameItem, Page, PagedDocument, Point, Transform};
+
+use crate::form_processor;
+use crate::locate::{SOURCE_END_LABEL, SOURCE_START_LABEL};
+use crate::text_layout::{round, text_bounds};
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// The source regions of a page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageSourceMap {
+    /// Page size in points
+    pub width: f64,
+    pub height: f64,
+    /// Regions in the order they first appear on the page
+    pub regions: Vec<SourceRegion>,
+}
+
+/// What one form field, or one Delta op of the body, drew on a page, with
+/// its bounding box in points from the top left corner of the page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceRegion {
+    /// JSON pointer of the form field
+    pub field: String,
+    /// Index of the Delta op within the field, for a Delta body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub op: Option<usize>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The source map of every page of a rendered memo form
+///
+/// # Examples
+/// ```
+/// use render_engine::form_source_map;
+///
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Mapped",
+///     "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+/// let pages = form_source_map(form).unwrap();
+/// assert!(pages[0].regions.iter().any(|region| region.field == "/subject"));
+/// ```
+#[instrument(skip_all)]
+pub fn form_source_map(json_input: &str) -> Result<Vec<PageSourceMap>, TypstWrapperError> {
+    let processed = form_processor::validate_and_preprocess_for_source_map(json_input)?;
+    let document = TypstWrapper::compile(&TypstWrapper::form_world(processed)?, &mut ())?;
+    Ok(document_source_map(&document))
+}
+
+/// A marker that is open where the frames are being walked
+#[derive(Debug, Clone)]
+enum Marker {
+    Field(String),
+    Op(usize),
+    /// Metadata of some other value, kept so its end still pairs up
+    Other,
+}
+
+/// Markers stay open across page breaks, as a long body does
+fn document_source_map(document: &PagedDocument) -> Vec<PageSourceMap> {
+    let mut open = Vec::new();
+    document.pages.iter().map(|page| page_source_map(page, &mut open)).collect()
+}
+
+fn page_source_map(page: &Page, open: &mut Vec<Marker>) -> PageSourceMap {
+    let mut bounds = Vec::new();
+    collect(&page.frame, Transform::identity(), open, &mut bounds);
+    PageSourceMap {
+        width: round(page.frame.width()),
+        height: round(page.frame.height()),
+        regions: bounds
+            .into_iter()
+            .map(|((field, op), (min, max)): (_, (Point, Point))| SourceRegion {
+                field,
+                op,
+                x: round(min.x),
+                y: round(min.y),
+                width: round(max.x - min.x),
+                height: round(max.y - min.y),
+            })
+            .collect(),
+    }
+}
+
+type Bounds = Vec<((String, Option<usize>), (Point, Point))>;
+
+fn collect(frame: &Frame, transform: Transform, open: &mut Vec<Marker>, bounds: &mut Bounds) {
+    for (pos, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => collect(&group.frame, transform.pre_concat(group.transform), open, bounds),
+            FrameItem::Tag(Tag::Start(content)) => {
+                let Some(metadata) = content.to_packed::<MetadataElem>() else {
+                    continue;
+                };
+                match content.label().map(|label| label.resolve()) {
+                    Some(label) if label.as_str() == SOURCE_START_LABEL => open.push(match &metadata.value {
+                        Value::Str(field) => Marker::Field(field.to_string()),
+                        Value::Int(op) => usize::try_from(*op).map_or(Marker::Other, Marker::Op),
+                        _ => Marker::Other,
+                    }),
+                    Some(label) if label.as_str() == SOURCE_END_LABEL => {
+                        open.pop();
+                    }
+                    _ => {}
+                }
+            }
+            FrameItem::Text(text) if !text.text.trim().is_empty() => {
+                extend(open, bounds, text_bounds(text, transform, Abs::zero(), text.width()));
+            }
+            FrameItem::Image(_, size, _) => {
+                let corners = [Point::zero(), Point::new(size.x, size.y)].map(|corner| corner.transform(transform));
+                extend(open, bounds, (corners[0].min(corners[1]), corners[0].max(corners[1])));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Grow the region of the innermost open field, and of the op within it
+fn extend(open: &[Marker], bounds: &mut Bounds, (min, max): (Point, Point)) {
+    let mut op = None;
+    let field = open.iter().rev().find_map(|marker| match marker {
+        Marker::Field(field) => Some(field),
+        Marker::Op(index) => {
+            op = op.or(Some(*index));
+            None
+        }
+        Marker::Other => None,
+    });
+    let Some(field) = field else {
+        return;
+    };
+    let key = (field.clone(), op);
+    match bounds.iter_mut().find(|(region, _)| *region == key) {
+        Some((_, (region_min, region_max))) => {
+            *region_min = region_min.min(min);
+            *region_max = region_max.max(max);
+        }
+        None => bounds.push((key, (min, max))),
+    }
+}
+
+#[cfg(all(test, feature = "font-times"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal().memo_for(["FIRST/SYMBOL", "SECOND/SYMBOL"]).subject("Mapped")
+    }
+
+    fn region<'a>(page: &'a PageSourceMap, field: &str) -> &'a SourceRegion {
+        page.regions.iter().find(|region| region.field == field).unwrap_or_else(|| panic!("no {} in {:?}", field, page))
+    }
+
+    #[test]
+    fn test_form_source_map() {
+        let pages = form_source_map(&form().to_json()).unwrap();
+        assert_eq!(pages.len(), 1);
+        let page = &pages[0];
+        let (first, second) = (region(page, "/memo-for/0"), region(page, "/memo-for/1"));
+        assert!(second.x > first.x + first.width - 0.01, "{:?} {:?}", first, second);
+        let (subject, body) = (region(page, "/subject"), region(page, "/body_raw"));
+        assert!(body.y > subject.y, "{:?} {:?}", subject, body);
+        assert!(region(page, "/signature-block/1").y > body.y);
+        region(page, "/letterhead-title");
+        region(page, "/from-block/0");
+        assert!(page.regions.iter().all(|region| region.op.is_none() && region.width > 0.0));
+
+        // Marking the fields does not move them
+        let plain = crate::form_text_layout(&form().to_json()).unwrap();
+        let subject_run = plain[0].runs.iter().find(|run| run.text.ends_with("Mapped")).unwrap();
+        let end = |x: f64, width: f64| x + width;
+        assert!((end(subject_run.x, subject_run.width) - end(subject.x, subject.width)).abs() < 0.02, "{:?}", subject_run);
+        assert!((subject_run.y - subject.y).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_delta_ops_are_mapped() {
+        let delta = r#"{"ops":[{"insert":"First "},{"insert":"bold","attributes":{"bold":true}},{"insert":"\nSecond\n"}]}"#;
+        let pages = form_source_map(&form().body_delta(delta).to_json()).unwrap();
+        let ops: Vec<_> = pages[0].regions.iter().filter(|region| region.field == "/body").collect();
+        assert_eq!(ops.iter().map(|region| region.op).collect::<Vec<_>>(), [Some(0), Some(1), Some(2)]);
+        assert!(ops[1].x > ops[0].x && ops[2].x > ops[1].x, "{:?}", ops);
+    }
+}
@@ -0,0 +1,142 @@
+//! Style tokens for memo forms.
+//!
+//! A [`StyleConfig`] adjusts the look of a memo without editing Typst: the
+//! body and letterhead font sizes, line and paragraph spacing, the size of
+//! the seal and an accent color with the elements it applies to. It is set
+//! with [`RenderConfig::style`](crate::RenderConfig::style) or the `style`
+//! field of the form JSON; the config replaces the form field.
+//!
+//! The template receives the tokens as the dictionary `input.style`, with
+//! kebab-case keys (`font-size`, `seal-size`, ...), and only the tokens that
+//! are set, so [partials](crate::template#partials) can fall back to their
+//! own defaults with `style.at("font-size", default: 12)`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::typst_wrapper::{RenderConfig, TypstWrapperError};
+
+/// Cosmetic settings of a memo form
+///
+/// Sizes are numbers rather than Typst lengths so the same object works in
+/// form JSON: font sizes in points, spacings in em and the seal width in
+/// inches. Unset tokens keep the template's defaults.
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_form, AccentTarget, RenderConfig, StyleConfig};
+///
+/// let config = RenderConfig {
+///     style: Some(StyleConfig {
+///         font_size: Some(11.0),
+///         seal_size: Some(1.25),
+///         accent_color: Some("#00308f".to_string()),
+///         accent_on: vec![AccentTarget::Letterhead, AccentTarget::Headings],
+///         ..StyleConfig::default()
+///     }),
+///     ..RenderConfig::default()
+/// };
+/// let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Style",
+///     "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+/// let pages = render_form(form, Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct StyleConfig {
+    /// Size of the body text in points (12 by default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    /// Size of the letterhead title in points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub letterhead_font_size: Option<f64>,
+    /// Space between the lines of a paragraph in em
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_spacing: Option<f64>,
+    /// Space between paragraphs in em
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paragraph_spacing: Option<f64>,
+    /// Width of the letterhead seal in inches (1 by default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seal_size: Option<f64>,
+    /// Accent color as a hex code, `#rgb` or `#rrggbb`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<String>,
+    /// Elements drawn in the accent color; the letterhead when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub accent_on: Vec<AccentTarget>,
+}
+
+/// An element of the memo the accent color applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccentTarget {
+    /// Letterhead title and caption
+    Letterhead,
+    /// Headings in the body
+    Headings,
+}
+
+/// Move the style of `config` into form JSON as its `style` field, so the
+/// template applies it
+pub(crate) fn move_into_form(json_input: &str, config: &mut RenderConfig) -> Result<String, TypstWrapperError> {
+    let Some(style) = config.style.take() else {
+        return Ok(json_input.to_string());
+    };
+    let mut form: JsonValue = serde_json::from_str(json_input)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid form JSON: {}", e)))?;
+    let Some(fields) = form.as_object_mut() else {
+        return Ok(json_input.to_string());
+    };
+    let value = serde_json::to_value(style).map_err(|e| TypstWrapperError::Validation(e.to_string()))?;
+    fields.insert("style".to_string(), value);
+    Ok(form.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use serde_json::json;
+
+    fn form() -> MemoBuilder {
+        MemoBuilder::minimal().subject("Style").body_raw("= Heading\nBody")
+    }
+
+    #[test]
+    fn test_style_serialization() {
+        let style = StyleConfig { font_size: Some(11.0), accent_on: vec![AccentTarget::Headings], ..StyleConfig::default() };
+        let json = serde_json::to_value(&style).unwrap();
+        assert_eq!(json, serde_json::json!({"font-size": 11.0, "accent-on": ["headings"]}));
+        assert_eq!(serde_json::from_value::<StyleConfig>(json).unwrap(), style);
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_style_changes_form_renders() {
+        let plain = crate::render_form(&form().to_json(), None).unwrap();
+        let styled = |style: StyleConfig| {
+            let config = RenderConfig { style: Some(style), ..RenderConfig::default() };
+            crate::render_form(&form().to_json(), Some(config)).unwrap()
+        };
+        assert_eq!(styled(StyleConfig::default()), plain);
+        assert_ne!(styled(StyleConfig { font_size: Some(14.0), ..StyleConfig::default() }), plain);
+        assert_ne!(styled(StyleConfig { seal_size: Some(1.5), ..StyleConfig::default() }), plain);
+        let accent = StyleConfig { accent_color: Some("#00308f".to_string()), ..StyleConfig::default() };
+        let headings = StyleConfig { accent_on: vec![AccentTarget::Headings], ..accent.clone() };
+        assert_ne!(styled(accent.clone()), plain);
+        assert_ne!(styled(headings), styled(accent));
+
+        // The form field applies when the config has no style
+        let form = form().set("style", serde_json::json!({"font-size": 14})).to_json();
+        assert_eq!(crate::render_form(&form, None).unwrap(), styled(StyleConfig { font_size: Some(14.0), ..StyleConfig::default() }));
+    }
+
+    #[test]
+    fn test_invalid_style_is_rejected() {
+        for style in [json!({"font-size": 0}), json!({"accent-color": "blue"}), json!({"seal": 1})] {
+            let form = form().set("style", style.clone()).to_json();
+            let result = crate::render_form(&form, None);
+            assert!(matches!(result, Err(TypstWrapperError::Field { ref field, .. }) if field.starts_with("/style")), "accepted {}", style);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! SVG exports of unchanged pages, kept between the renders of a session.
+//!
+//! Exporting pages to SVG takes most of a render. An edit usually changes
+//! one or two pages of a document, so a [`RenderSession`](crate::RenderSession)
+//! keeps the plain SVG of every page it exported, by the hash of the page,
+//! and exports only the pages that differ from the previous render. The
+//! per-render additions (text, links, annotations, ids and scale) are still
+//! applied to every page.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use typst::layout::Page;
+
+/// Hash of what `page` looks like: its frame and its fill
+pub(crate) fn page_hash(page: &Page) -> u128 {
+    typst::utils::hash128(&(&page.frame, page.fill_or_white()))
+}
+
+/// Plain SVGs of the pages of the last two renders, by page hash
+#[derive(Debug, Default)]
+pub(crate) struct SvgCache {
+    /// Pages of the current render
+    current: Mutex<HashMap<u128, String>>,
+    /// Pages of the previous render not yet seen in the current one
+    previous: Mutex<HashMap<u128, String>>,
+}
+
+impl SvgCache {
+    /// Start a render, forgetting pages the previous render did not have.
+    /// Renders served from the output cache export nothing and keep the
+    /// pages of the render before them.
+    pub(crate) fn begin_render(&mut self) {
+        let current = std::mem::take(self.current.get_mut().unwrap_or_else(PoisonError::into_inner));
+        if !current.is_empty() {
+            *self.previous.get_mut().unwrap_or_else(PoisonError::into_inner) = current;
+        }
+    }
+
+    /// The plain SVG of `page`, exported only if no recent render had it
+    pub(crate) fn svg(&self, page: &Page) -> String {
+        let key = page_hash(page);
+        if let Some(svg) = self.current.lock().unwrap_or_else(PoisonError::into_inner).get(&key) {
+            return svg.clone();
+        }
+        let svg = self
+            .previous
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&key)
+            .unwrap_or_else(|| typst_svg::svg(page));
+        self.current.lock().unwrap_or_else(PoisonError::into_inner).insert(key, svg.clone());
+        svg
+    }
+}
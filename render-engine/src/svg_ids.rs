@@ -0,0 +1,91 @@
+//! Unique ids in SVG output.
+//!
+//! The ids of an SVG page's glyph, clip path and gradient definitions are
+//! derived from their content, so two pages inlined in one HTML document
+//! define the same id differently once they differ in font size or
+//! transform, and one page's glyphs show up in the other. With
+//! [`RenderConfig::id_prefix`](crate::RenderConfig::id_prefix) set, every
+//! `id` in the output, and every `href="#..."` and `url(#...)` pointing at
+//! one, starts with the prefix. This covers the `page-N` ids and links of
+//! the engine as well.
+
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Check that `prefix` can start an XML id
+pub(crate) fn validate(prefix: &str) -> Result<(), TypstWrapperError> {
+    let mut chars = prefix.chars();
+    let valid = chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(TypstWrapperError::Validation(format!(
+            "Invalid id prefix '{}': start with a letter or underscore and use only letters, digits, '-', '_' and '.'",
+            prefix
+        )))
+    }
+}
+
+/// `svg` with `prefix` put in front of every id and every reference to one
+pub(crate) fn prefixed(svg: String, prefix: &str) -> String {
+    if prefix.is_empty() {
+        return svg;
+    }
+    let mut result = String::with_capacity(svg.len() + svg.len() / 16);
+    let mut rest = svg.as_str();
+    // Only markup is rewritten; text content may mention ids freely
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>').map_or(rest.len(), |end| start + end + 1);
+        result.push_str(&rest[..start]);
+        let mut tag = rest[start..end].to_string();
+        for pattern in [" id=\"", "href=\"#", "url(#"] {
+            tag = tag.replace(pattern, &format!("{}{}", pattern, prefix));
+        }
+        result.push_str(&tag);
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_markup, RenderConfig};
+
+    #[cfg(feature = "font-times")]
+    const MARKUP: &str = "#set page(width: 200pt, height: 100pt)\n#box(clip: true, width: 50pt)[Clipped text]\n#link(<two>)[Next]\n#pagebreak()\n= Two <two>";
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_ids_and_references_are_prefixed() {
+        let config = RenderConfig { id_prefix: Some("memo1-".to_string()), ..RenderConfig::default() };
+        let pages = render_markup(MARKUP, Some(config)).unwrap();
+        let svg = String::from_utf8(pages[0].clone()).unwrap();
+        let ids = svg.matches(" id=\"").count();
+        assert!(ids > 0 && svg.matches(" id=\"memo1-").count() == ids, "{}", svg);
+        assert_eq!(svg.matches("href=\"#").count(), svg.matches("href=\"#memo1-").count());
+        assert!(svg.contains("url(#memo1-") && !svg.contains("url(#c"));
+        assert!(svg.contains("href=\"#memo1-page-2\""));
+
+        let plain = String::from_utf8(render_markup(MARKUP, None).unwrap().remove(0)).unwrap();
+        assert_eq!(prefixed(plain.clone(), "memo1-"), svg);
+        assert_eq!(prefixed(plain.clone(), ""), plain);
+    }
+
+    #[test]
+    fn test_text_is_left_alone() {
+        let svg = "<svg id=\"a\"><text>see url(#x) and id=\"y\"</text></svg>".to_string();
+        assert_eq!(prefixed(svg, "p-"), "<svg id=\"p-a\"><text>see url(#x) and id=\"y\"</text></svg>");
+    }
+
+    #[test]
+    fn test_invalid_prefixes_fail() {
+        for prefix in ["", "1st", "a b", "a\"b"] {
+            assert!(validate(prefix).is_err(), "{}", prefix);
+        }
+        assert!(validate("_memo.1-").is_ok());
+        let config = RenderConfig { id_prefix: Some("a b".to_string()), ..RenderConfig::default() };
+        assert!(matches!(render_markup("Hello", Some(config)), Err(TypstWrapperError::Validation(_))));
+    }
+}
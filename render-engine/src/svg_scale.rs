@@ -0,0 +1,99 @@
+//! Display size of SVG pages.
+//!
+//! SVG pages are exported at their size in points, which browsers show at
+//! 1.33 CSS pixels per point and rasterize at the screen's pixel density.
+//! [`RenderConfig::scale`](crate::RenderConfig::scale) multiplies the
+//! `width` and `height` of every SVG page while keeping its `viewBox`, so
+//! the same render pipeline produces small thumbnails (`0.25`) or sharp
+//! high-DPI previews (`2`). Coordinates inside the page, such as those of
+//! links, annotations, text layouts and source maps, stay in points.
+
+use typst::layout::Size;
+
+use crate::typst_wrapper::TypstWrapperError;
+
+/// Largest scale accepted
+const MAX_SCALE: f64 = 16.0;
+
+/// Check that `scale` can size a page
+pub(crate) fn validate(scale: f64) -> Result<(), TypstWrapperError> {
+    if scale.is_finite() && scale > 0.0 && scale <= MAX_SCALE {
+        Ok(())
+    } else {
+        Err(TypstWrapperError::Validation(format!(
+            "Invalid scale {}: must be greater than 0 and at most {}",
+            scale, MAX_SCALE
+        )))
+    }
+}
+
+/// An SVG of `size` displayed at `scale` times its size
+pub(crate) fn scaled(svg: String, size: Size, scale: f64) -> String {
+    if scale == 1.0 {
+        return svg;
+    }
+    with_root_attributes(
+        svg,
+        &[("width", format!("{}pt", size.x.to_pt() * scale)), ("height", format!("{}pt", size.y.to_pt() * scale))],
+    )
+}
+
+/// `svg` with the given attributes of its root `<svg>` element set,
+/// replacing their values or adding them
+pub(crate) fn with_root_attributes(svg: String, attributes: &[(&str, String)]) -> String {
+    let Some(start) = svg.find("<svg ") else {
+        return svg;
+    };
+    let Some(end) = svg[start..].find('>').map(|end| start + end) else {
+        return svg;
+    };
+    let mut tag = svg[start..end].to_string();
+    for (name, value) in attributes {
+        let attribute = format!(" {}=\"", name);
+        match tag.find(&attribute).map(|at| at + attribute.len()) {
+            Some(value_start) => {
+                if let Some(value_end) = tag[value_start..].find('"').map(|end| value_start + end) {
+                    tag.replace_range(value_start..value_end, value);
+                }
+            }
+            None => tag.push_str(&format!("{}{}\"", attribute, value)),
+        }
+    }
+    format!("{}{}{}", &svg[..start], tag, &svg[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_markup, RenderConfig};
+
+    /// The `width`, `height` and `viewBox` of an SVG page
+    fn dimensions(svg: &[u8]) -> (String, String, String) {
+        let svg = String::from_utf8_lossy(svg);
+        let tag = &svg[svg.find("<svg ").unwrap()..];
+        let tag = &tag[..tag.find('>').unwrap()];
+        let attribute = |name: &str| {
+            let start = tag.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+            tag[start..start + tag[start..].find('"').unwrap()].to_string()
+        };
+        (attribute("width"), attribute("height"), attribute("viewBox"))
+    }
+
+    #[test]
+    fn test_scaled_pages_keep_their_view_box() {
+        let markup = "#set page(width: 200pt, height: 100pt)\nHello";
+        let plain = render_markup(markup, None).unwrap();
+        let config = RenderConfig { scale: 2.0, ..RenderConfig::default() };
+        let scaled = render_markup(markup, Some(config)).unwrap();
+        assert_eq!(dimensions(&plain[0]), ("200pt".to_string(), "100pt".to_string(), "0 0 200 100".to_string()));
+        assert_eq!(dimensions(&scaled[0]), ("400pt".to_string(), "200pt".to_string(), "0 0 200 100".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_scales_fail() {
+        for scale in [0.0, -1.0, f64::NAN, 100.0] {
+            let config = RenderConfig { scale, ..RenderConfig::default() };
+            assert!(matches!(render_markup("Hello", Some(config)), Err(TypstWrapperError::Validation(_))), "{}", scale);
+        }
+    }
+}
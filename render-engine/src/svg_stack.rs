@@ -0,0 +1,93 @@
+//! SVG pages stacked into one SVG.
+//!
+//! Many embedding contexts, such as `<img>` tags and rich text fields, take
+//! a single SVG element. With
+//! [`RenderConfig::stack_pages`](crate::RenderConfig::stack_pages), SVG
+//! output is one SVG with the exported pages stacked top to bottom,
+//! separated by gray bands [`GAP`] points high. Each page is a nested
+//! `<svg>` with the id `page-N` (after the
+//! [`id_prefix`](crate::RenderConfig::id_prefix)) that links within the
+//! document point at, so coordinates inside a page stay in the points of
+//! that page.
+
+use crate::metrics::PageSize;
+use crate::svg_scale;
+
+/// Height of the band between two pages, in points
+const GAP: f64 = 12.0;
+/// Color of the band between two pages
+const SEPARATOR_COLOR: &str = "#d9d9d9";
+
+/// Stack the SVG `pages` of `sizes`, the first of which is page `first` of
+/// the document (counted from 0), into one SVG shown at `scale` times its
+/// size
+pub(crate) fn stack(pages: &[Vec<u8>], sizes: &[PageSize], first: usize, scale: f64, id_prefix: &str) -> Vec<u8> {
+    let width = sizes.iter().map(|size| size.width).fold(0.0, f64::max);
+    let height = sizes.iter().map(|size| size.height).sum::<f64>() + GAP * sizes.len().saturating_sub(1) as f64;
+    let mut svg = format!(
+        "<svg class=\"typst-doc\" viewBox=\"0 0 {width} {height}\" width=\"{}pt\" height=\"{}pt\" \
+         xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\">\n",
+        width * scale,
+        height * scale,
+    );
+    let mut y = 0.0;
+    for (index, (page, size)) in pages.iter().zip(sizes).enumerate() {
+        if index > 0 {
+            svg.push_str(&format!(
+                "<rect x=\"0\" y=\"{}\" width=\"{width}\" height=\"{GAP}\" fill=\"{SEPARATOR_COLOR}\"/>\n",
+                y - GAP
+            ));
+        }
+        let attributes = [
+            ("id", format!("{}page-{}", id_prefix, first + index + 1)),
+            ("x", "0".to_string()),
+            ("y", y.to_string()),
+            ("width", size.width.to_string()),
+            ("height", size.height.to_string()),
+        ];
+        svg.push_str(&svg_scale::with_root_attributes(String::from_utf8_lossy(page).into_owned(), &attributes));
+        svg.push('\n');
+        y += size.height + GAP;
+    }
+    svg.push_str("</svg>\n");
+    svg.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{render_markup, OutputFormat, RenderConfig};
+
+    const MARKUP: &str = "#set page(width: 200pt, height: 100pt)\n= One\n#link(<three>)[Last]\n#pagebreak()\nTwo\n#pagebreak()\n= Three <three>";
+
+    #[test]
+    fn test_pages_are_stacked() {
+        let config = RenderConfig { stack_pages: true, ..RenderConfig::default() };
+        let output = render_markup(MARKUP, Some(config)).unwrap();
+        assert_eq!(output.len(), 1);
+        let svg = String::from_utf8(output[0].clone()).unwrap();
+        assert!(svg.starts_with("<svg class=\"typst-doc\" viewBox=\"0 0 200 324\" width=\"200pt\" height=\"324pt\""), "{}", &svg[..200]);
+        for (page, y) in [(1, 0), (2, 112), (3, 224)] {
+            assert!(svg.contains(&format!("id=\"page-{}\" x=\"0\" y=\"{}\"", page, y)), "page {}", page);
+        }
+        assert_eq!(svg.matches("width=\"200\" height=\"100\"").count(), 3);
+        assert_eq!(svg.matches("fill=\"#d9d9d9\"").count(), 2);
+        assert!(svg.contains("href=\"#page-3\""));
+    }
+
+    #[test]
+    fn test_stacks_follow_scale_and_page_range() {
+        let config = RenderConfig {
+            stack_pages: true,
+            scale: 0.5,
+            page_range: Some(1..3),
+            id_prefix: Some("m-".to_string()),
+            ..RenderConfig::default()
+        };
+        let svg = String::from_utf8(render_markup(MARKUP, Some(config)).unwrap().remove(0)).unwrap();
+        assert!(svg.contains("viewBox=\"0 0 200 212\" width=\"100pt\" height=\"106pt\""));
+        assert!(!svg.contains("id=\"m-page-1\"") && svg.contains("id=\"m-page-2\" x=\"0\" y=\"0\""));
+
+        let pdf = RenderConfig { format: OutputFormat::Pdf, stack_pages: true, ..RenderConfig::default() };
+        assert!(render_markup(MARKUP, Some(pdf)).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Selectable text in SVG pages.
+//!
+//! SVG pages draw text as glyph outlines: they look exactly like the PDF,
+//! but a browser cannot select, search or copy their text. With
+//! [`RenderConfig::svg_text`](crate::RenderConfig::svg_text) set to
+//! [`SvgTextMode::Selectable`], each page also gets its text as real
+//! `<text>` elements, one per text run, laid over the outlines with a
+//! transparent fill. The outlines keep the page pixel-perfect without
+//! embedding fonts, and `textLength` stretches each run's text to the width
+//! of its outlines in whatever font the browser picks, so selections line
+//! up with the glyphs.
+//!
+//! The text layer lies beneath the links of the page, which stay clickable.
+
+use serde::{Deserialize, Serialize};
+use typst::layout::{Frame, FrameItem, Page, Transform};
+
+use crate::alt_text::escape;
+
+/// How SVG pages render text
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, RenderConfig, SvgTextMode};
+///
+/// let config = RenderConfig { svg_text: SvgTextMode::Selectable, ..RenderConfig::default() };
+/// let pages = render_markup("Hello", Some(config)).unwrap();
+/// assert!(String::from_utf8_lossy(&pages[0]).contains(">Hello</text>"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SvgTextMode {
+    /// Glyph outlines only: pixel-perfect, but not selectable
+    #[default]
+    Outlines,
+    /// Glyph outlines with transparent real text over them, which browsers
+    /// select, search and copy
+    Selectable,
+}
+
+/// A page's SVG with a transparent `<text>` element over every text run
+pub(crate) fn svg_with_text(svg: String, page: &Page) -> String {
+    let mut layer = String::new();
+    collect(&page.frame, Transform::identity(), &mut layer);
+    if layer.is_empty() {
+        return svg;
+    }
+    match svg.rfind("</svg>") {
+        Some(end) => {
+            let mut svg = svg;
+            svg.insert_str(
+                end,
+                &format!("<g class=\"typst-text-layer\" fill=\"transparent\" style=\"white-space: pre\">{}</g>", layer),
+            );
+            svg
+        }
+        None => svg,
+    }
+}
+
+/// Append a `<text>` element for every text run in `frame`, drawn on its
+/// baseline
+fn collect(frame: &Frame, transform: Transform, layer: &mut String) {
+    for (pos, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => collect(&group.frame, transform.pre_concat(group.transform), layer),
+            FrameItem::Text(text) if !text.text.trim().is_empty() => {
+                layer.push_str(&format!(
+                    "<text transform=\"matrix({} {} {} {} {} {})\" font-family=\"{}\" font-size=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\">{}</text>",
+                    transform.sx.get(),
+                    transform.ky.get(),
+                    transform.kx.get(),
+                    transform.sy.get(),
+                    transform.tx.to_pt(),
+                    transform.ty.to_pt(),
+                    escape(&text.font.info().family),
+                    text.size.to_pt(),
+                    text.width().to_pt(),
+                    escape(&text.text),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render_markup, OutputFormat, RenderConfig};
+
+    fn selectable() -> RenderConfig {
+        RenderConfig { svg_text: SvgTextMode::Selectable, ..RenderConfig::default() }
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_text_layer() {
+        let markup = "#set page(width: 200pt, height: 100pt, margin: 10pt)\n#set text(size: 10pt)\nA < B & C\n#link(\"https://example.com\")[Link]";
+        let svg = String::from_utf8(render_markup(markup, Some(selectable())).unwrap().remove(0)).unwrap();
+        let layer = &svg[svg.find("<g class=\"typst-text-layer\"").unwrap()..];
+        assert!(layer.contains(">A &lt; B &amp; C") && layer.contains(">Link</text>"), "{}", layer);
+        assert!(layer.contains("font-size=\"10\"") && layer.contains("matrix(1 0 0 1 10 "), "{}", layer);
+        // Links stay on top of the text
+        assert!(svg.rfind("<a ").unwrap() > svg.find("typst-text-layer").unwrap());
+
+        let plain = String::from_utf8(render_markup(markup, None).unwrap().remove(0)).unwrap();
+        assert!(!plain.contains("<text"));
+    }
+
+    #[test]
+    fn test_mode_serialization() {
+        let config: RenderConfig = serde_json::from_str(r#"{"svg_text": "selectable"}"#).unwrap();
+        assert_eq!(config.svg_text, SvgTextMode::Selectable);
+        let pdf = RenderConfig { format: OutputFormat::Pdf, ..selectable() };
+        assert!(RenderConfig::builder().format(OutputFormat::Pdf).svg_text(SvgTextMode::Selectable).build().is_err());
+        assert!(render_markup("Hello", Some(pdf)).unwrap()[0].starts_with(b"%PDF"));
+    }
+}
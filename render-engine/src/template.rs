@@ -0,0 +1,357 @@
+//! Custom memo templates.
+//!
+//! Forms render with the embedded memo-loader template, which reads the
+//! form and lays it out with the memo package. An organization whose
+//! letterhead goes beyond what the form fields control can supply its own
+//! `main.typ`, together with the files it imports or shows, and forms of the
+//! current [`RenderEngine`](crate::RenderEngine) render with it instead:
+//!
+//! ```
+//! use render_engine::template::{reset_memo_template, set_memo_template};
+//!
+//! let main = "#let input = json(\"input.json\")\n#import \"letterhead.typ\": letterhead\n#letterhead\n= #input.subject";
+//! set_memo_template(vec![
+//!     ("main.typ".to_string(), main.as_bytes().to_vec()),
+//!     ("letterhead.typ".to_string(), b"#let letterhead = [42nd Wing]".to_vec()),
+//! ])
+//! .unwrap();
+//! let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Custom",
+//!     "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+//! assert!(render_engine::render_form(form, None).is_ok());
+//! assert!(reset_memo_template());
+//! ```
+//!
+//! Like the embedded template, `main.typ` reads the preprocessed form with
+//! the line `#let input = json("input.json")`. Companion files are served at
+//! their paths relative to `main.typ` and take precedence over embedded
+//! assets of the same path. A template is checked by rendering a minimal
+//! memo with it before it replaces the current one.
+//!
+//! Hosts that switch between several templates, such as an admin UI trying
+//! out a revision, check each once as a [`MemoTemplate`] and activate it
+//! with [`use_memo_template`]. Cached output and parsed sources are keyed by
+//! the template's files, so renders with one template never see another's.
+//!
+//! # Partials
+//!
+//! Small changes do not need a whole template. The embedded `main.typ`
+//! composes the memo from four [`Partial`]s, the letterhead, body, signature
+//! block and page furniture, each imported from its own file under
+//! `partials/`. [`set_template_partial`] overrides one of them, keeping the
+//! rest:
+//!
+//! ```
+//! use render_engine::template::{reset_template_partial, set_template_partial, Partial};
+//!
+//! let letterhead = "#let letterhead(input) = (letterhead-title: \"DEPARTMENT OF THE SPACE FORCE\", \
+//!                   letterhead-caption: input.at(\"letterhead-caption\", default: \"\"))";
+//! set_template_partial(Partial::Letterhead, letterhead).unwrap();
+//! let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Partial",
+//!     "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+//! assert!(render_engine::render_form(form, None).is_ok());
+//! assert!(reset_template_partial(Partial::Letterhead));
+//! ```
+//!
+//! An override has to define what `main.typ` imports from the partial:
+//! `letterhead(input)` returning the letterhead arguments of the memo
+//! package, `body(input)`, `signature-block(input)` returning the lines of
+//! the signature block, or `footer(input, date)` returning a show rule for
+//! the whole memo. Overrides take precedence over the files of a custom
+//! template, whose `main.typ` can import the partials as well.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use typst::diag::{FileError, FileResult};
+
+use crate::assets;
+use crate::engine::{self, PackageFiles, TemplatePartials};
+use crate::packages;
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// Path of the main file among the files of a template
+pub const MAIN_FILE: &str = "main.typ";
+
+/// A part of the memo template that can be overridden on its own, see
+/// [Partials](self#partials)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Partial {
+    /// Letterhead title, caption, seal and font
+    Letterhead,
+    /// The memo body, bookmarked by its subject
+    Body,
+    /// Lines of the signature block, with the signature and date fields
+    Signature,
+    /// Page numbers and running headers and footers
+    Footer,
+}
+
+impl Partial {
+    /// Every partial, in the order the memo uses them
+    pub const ALL: [Partial; 4] = [Partial::Letterhead, Partial::Body, Partial::Signature, Partial::Footer];
+
+    /// The name of the partial, e.g. `letterhead`
+    pub fn name(self) -> &'static str {
+        match self {
+            Partial::Letterhead => "letterhead",
+            Partial::Body => "body",
+            Partial::Signature => "signature",
+            Partial::Footer => "footer",
+        }
+    }
+
+    /// The path `main.typ` imports the partial from, e.g.
+    /// `partials/letterhead.typ`
+    pub fn path(self) -> &'static str {
+        match self {
+            Partial::Letterhead => "partials/letterhead.typ",
+            Partial::Body => "partials/body.typ",
+            Partial::Signature => "partials/signature.typ",
+            Partial::Footer => "partials/footer.typ",
+        }
+    }
+
+    /// The source of the embedded partial
+    pub fn embedded(self) -> &'static str {
+        assets::load_string_asset(&format!("memo-loader-{}", self.name()))
+            .expect("embedded partials are registered")
+            .content
+    }
+
+    /// The partial served at `path`, if any
+    pub(crate) fn from_path(path: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|partial| partial.path() == path)
+    }
+}
+
+impl std::str::FromStr for Partial {
+    type Err = TypstWrapperError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|partial| partial.name() == name).ok_or_else(|| {
+            TypstWrapperError::Validation(format!(
+                "Unknown template partial '{}': use letterhead, body, signature or footer",
+                name
+            ))
+        })
+    }
+}
+
+/// A memo template that compiled, cheap to clone
+#[derive(Debug, Clone)]
+pub struct MemoTemplate {
+    files: PackageFiles,
+}
+
+impl MemoTemplate {
+    /// Check a template, see [`set_memo_template`]
+    pub fn new(files: Vec<(String, Vec<u8>)>) -> Result<Self, TypstWrapperError> {
+        Ok(Self { files: prepare(files)? })
+    }
+
+    /// The paths of the template's files, sorted
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.files.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+}
+
+/// Render forms of the current [`RenderEngine`](crate::RenderEngine) with
+/// `template`, or the embedded template for `None`, returning the template
+/// used before
+pub fn use_memo_template(template: Option<MemoTemplate>) -> Option<MemoTemplate> {
+    let previous = engine::current().set_memo_template_files(template.map(|template| template.files));
+    previous.map(|files| MemoTemplate { files })
+}
+
+/// The custom memo template of the current engine, if one is set
+pub fn current_memo_template() -> Option<MemoTemplate> {
+    engine::current().memo_template().map(|files| MemoTemplate { files })
+}
+
+/// Replace the memo template of the current
+/// [`RenderEngine`](crate::RenderEngine) with `files`, which pair paths
+/// relative to the template root with their content
+///
+/// Fails, keeping the current template, if `main.typ` is missing or not
+/// UTF-8, a path leaves the template root, or a minimal memo does not
+/// compile with the template.
+pub fn set_memo_template(files: Vec<(String, Vec<u8>)>) -> Result<(), TypstWrapperError> {
+    let template = MemoTemplate::new(files)?;
+    info!(files = template.files.len(), "custom memo template set");
+    use_memo_template(Some(template));
+    Ok(())
+}
+
+/// Go back to the embedded memo template, returning whether a custom one
+/// was set
+pub fn reset_memo_template() -> bool {
+    use_memo_template(None).is_some()
+}
+
+/// Whether the current engine renders forms with a custom memo template
+pub fn has_custom_memo_template() -> bool {
+    current_memo_template().is_some()
+}
+
+/// Override one partial of the memo template of the current
+/// [`RenderEngine`](crate::RenderEngine) with `source`
+///
+/// Fails, keeping the current partial, if a minimal memo does not compile
+/// with the override.
+pub fn set_template_partial(partial: Partial, source: &str) -> Result<(), TypstWrapperError> {
+    let engine = engine::current();
+    let mut partials = BTreeMap::clone(&engine.template_partials());
+    partials.insert(partial, source.to_string());
+    TypstWrapper::check_memo_template(engine.memo_template(), Arc::new(partials))?;
+    info!(partial = partial.name(), size = source.len(), "template partial overridden");
+    engine.set_template_partial_source(partial, Some(source.to_string()));
+    Ok(())
+}
+
+/// Go back to the embedded source of a partial, returning whether it was
+/// overridden
+pub fn reset_template_partial(partial: Partial) -> bool {
+    engine::current().set_template_partial_source(partial, None).is_some()
+}
+
+/// The partials the current engine overrides, with their sources
+pub fn template_partial_overrides() -> Vec<(Partial, String)> {
+    engine::current().template_partials().iter().map(|(partial, source)| (*partial, source.clone())).collect()
+}
+
+/// The source of `partial`: its override, the file at its path in a custom
+/// template, or the embedded partial
+pub(crate) fn partial_source(
+    partial: Partial,
+    overrides: &TemplatePartials,
+    template: Option<&PackageFiles>,
+) -> FileResult<String> {
+    if let Some(source) = overrides.get(&partial) {
+        return Ok(source.clone());
+    }
+    match template.and_then(|files| files.get(partial.path())) {
+        Some(data) => std::str::from_utf8(data).map(str::to_string).map_err(|_| FileError::InvalidUtf8),
+        None => Ok(partial.embedded().to_string()),
+    }
+}
+
+/// Check the files of a template, including a test compile
+fn prepare(files: Vec<(String, Vec<u8>)>) -> Result<PackageFiles, TypstWrapperError> {
+    let template = template_files(files)?;
+    TypstWrapper::check_memo_template(Some(template.clone()), engine::current().template_partials())?;
+    Ok(template)
+}
+
+/// Check the paths and main file of a template without compiling it, for
+/// templates that compiled when they were set
+pub(crate) fn template_files(files: Vec<(String, Vec<u8>)>) -> Result<PackageFiles, TypstWrapperError> {
+    let invalid = |message: String| TypstWrapperError::Validation(format!("Invalid memo template: {}", message));
+    let files = packages::normalize_files(files).map_err(|path| invalid(format!("'{}' is not a relative path", path)))?;
+    let main = files.get(MAIN_FILE).ok_or_else(|| invalid(format!("{} is missing", MAIN_FILE)))?;
+    std::str::from_utf8(main).map_err(|_| invalid(format!("{} is not UTF-8", MAIN_FILE)))?;
+    Ok(std::sync::Arc::new(files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MemoBuilder;
+    use crate::RenderEngine;
+
+    fn form() -> String {
+        MemoBuilder::minimal().subject("Template").to_json()
+    }
+
+    fn template(main: &str) -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("main.typ".to_string(), main.as_bytes().to_vec()),
+            ("parts/heading.typ".to_string(), b"#let heading-text(subject) = upper(subject)".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn test_custom_template_replaces_memo_loader() {
+        let engine = RenderEngine::new();
+        let embedded = engine.render_form(&form(), None).unwrap();
+        let main = "#let input = json(\"input.json\")\n#import \"parts/heading.typ\": heading-text\n\
+                    #set page(width: 200pt, height: 100pt)\n#heading-text(input.subject)";
+        engine.set_memo_template(template(main)).unwrap();
+        assert!(engine.scope(has_custom_memo_template));
+        let custom = engine.render_form(&form(), None).unwrap();
+        assert_ne!(custom, embedded);
+        assert!(String::from_utf8_lossy(&custom[0]).contains("width=\"200pt\""));
+        // Other engines keep the embedded template
+        assert_eq!(RenderEngine::new().render_form(&form(), None).unwrap(), embedded);
+
+        assert!(engine.reset_memo_template());
+        assert!(!engine.reset_memo_template());
+        assert_eq!(engine.render_form(&form(), None).unwrap(), embedded);
+    }
+
+    #[test]
+    fn test_switching_templates() {
+        let engine = RenderEngine::new();
+        let sized = |width: u32| {
+            let main = format!("#let input = json(\"input.json\")\n#set page(width: {}pt, height: 100pt)\n#input.subject", width);
+            MemoTemplate::new(template(&main)).unwrap()
+        };
+        let (narrow, wide) = (sized(200), sized(300));
+        assert_eq!(wide.paths(), ["main.typ", "parts/heading.typ"]);
+        let width = |pages: Vec<Vec<u8>>| String::from_utf8_lossy(&pages[0]).contains("width=\"300pt\"");
+
+        engine.scope(|| assert!(use_memo_template(Some(wide.clone())).is_none()));
+        assert!(width(engine.render_form(&form(), None).unwrap()));
+        let previous = engine.scope(|| use_memo_template(Some(narrow)));
+        assert_eq!(previous.map(|template| template.paths().len()), Some(2));
+        assert!(!width(engine.render_form(&form(), None).unwrap()));
+        engine.scope(|| use_memo_template(Some(wide)));
+        assert!(width(engine.render_form(&form(), None).unwrap()));
+    }
+
+    #[test]
+    fn test_broken_templates_are_rejected() {
+        let engine = RenderEngine::new();
+        let good = "#let input = json(\"input.json\")\n#input.subject";
+        engine.set_memo_template(template(good)).unwrap();
+
+        let result = engine.set_memo_template(template("#let input = json(\"input.json\")\n#undefined-function()"));
+        assert!(matches!(result, Err(TypstWrapperError::Excerpt { ref error, .. }) if matches!(**error, TypstWrapperError::Compilation(_))), "{:?}", result);
+        let mut escaping = template(good);
+        escaping.push(("../outside.typ".to_string(), Vec::new()));
+        assert!(matches!(engine.set_memo_template(escaping), Err(TypstWrapperError::Validation(_))));
+        assert!(matches!(engine.set_memo_template(Vec::new()), Err(TypstWrapperError::Validation(_))));
+        // The working template is kept
+        let pages = engine.render_form(&form(), None).unwrap();
+        assert!(String::from_utf8_lossy(&pages[0]).contains("<svg"));
+        assert!(engine.scope(has_custom_memo_template));
+    }
+
+    #[test]
+    fn test_partials_are_overridden_individually() {
+        let engine = RenderEngine::new();
+        let embedded = engine.render_form(&form(), None).unwrap();
+        let signature = "#let signature-block(input) = input.signature-block.map(upper)";
+        engine.set_template_partial(Partial::Signature, signature).unwrap();
+        let overridden = engine.render_form(&form(), None).unwrap();
+        assert_ne!(overridden, embedded);
+        assert_eq!(engine.scope(template_partial_overrides), vec![(Partial::Signature, signature.to_string())]);
+        // Other engines keep the embedded partial
+        assert_eq!(RenderEngine::new().render_form(&form(), None).unwrap(), embedded);
+
+        // A partial that does not define what main.typ imports is rejected
+        let result = engine.set_template_partial(Partial::Footer, "#let page-footer = none");
+        assert!(matches!(result, Err(TypstWrapperError::Excerpt { ref error, .. }) if matches!(**error, TypstWrapperError::Compilation(_))), "{:?}", result);
+        assert_eq!(engine.render_form(&form(), None).unwrap(), overridden);
+
+        assert_eq!("letterhead".parse::<Partial>().unwrap(), Partial::Letterhead);
+        assert!("header".parse::<Partial>().is_err());
+        assert!(engine.reset_template_partial(Partial::Signature));
+        assert!(!engine.reset_template_partial(Partial::Signature));
+        assert_eq!(engine.render_form(&form(), None).unwrap(), embedded);
+    }
+}
@@ -0,0 +1,242 @@
+//! Memo fixtures for tests (feature `test-utils`, and always in the
+//! engine's own tests).
+//!
+//! Builders for memo form JSON so integrators do not have to hand-write
+//! valid forms in their tests:
+//!
+//! ```
+//! use render_engine::test_utils::{InvalidMemo, MemoBuilder};
+//!
+//! let form = MemoBuilder::minimal().subject("Quarterly Report").to_json();
+//! assert!(render_engine::render_form(&form, None).is_ok());
+//!
+//! for invalid in InvalidMemo::ALL {
+//!     assert!(render_engine::render_form(&invalid.to_json(), None).is_err(), "{}", invalid.description());
+//! }
+//! ```
+
+use serde_json::{json, Map, Value as JsonValue};
+
+/// Builder for memo form JSON
+///
+/// Start from [`MemoBuilder::minimal`], [`MemoBuilder::maximal`] or
+/// [`MemoBuilder::delta`] and override fields as needed.
+#[derive(Debug, Clone)]
+pub struct MemoBuilder {
+    fields: Map<String, JsonValue>,
+}
+
+impl MemoBuilder {
+    /// Only the required fields, with a markup body
+    pub fn minimal() -> Self {
+        Self { fields: Map::new() }
+            .memo_for(["ORG/SYMBOL"])
+            .from_block(["ORG/SYMBOL", "Organization Name", "Street Address", "City ST 12345-6789"])
+            .subject("Test Memorandum")
+            .signature_block(["FIRST M. LAST, Rank, USAF", "Duty Title"])
+            .body_raw("This is the body of a test memorandum.")
+    }
+
+    /// Every field the schema allows except `letterhead-background`, which
+    /// needs a registered image
+    pub fn maximal() -> Self {
+        Self::minimal()
+            .letterhead_title("DEPARTMENT OF THE AIR FORCE")
+            .letterhead_caption("123RD EXAMPLE SQUADRON")
+            .date("2024-01-15")
+            .memo_for(["ORG/SYMBOL", "ORG/SYMBOL", "ORG/SYMBOL"])
+            .references(["AFI 33-360, Publications and Forms Management, 1 December 2015"])
+            .co_signers([&["JOHN A. DOE, Col, USAF", "Coordinating Official"]])
+            .body_markup("This memorandum sets *every* optional field.\n\n+ First point\n+ Second point")
+    }
+
+    /// Required fields with a Quill Delta body
+    pub fn delta() -> Self {
+        Self::minimal().body_delta(
+            r#"{"ops":[{"insert":"A "},{"insert":"Delta","attributes":{"bold":true}},{"insert":" body.\n"}]}"#,
+        )
+    }
+
+    pub fn letterhead_title(self, title: &str) -> Self {
+        self.set("letterhead-title", title)
+    }
+
+    pub fn letterhead_caption(self, caption: &str) -> Self {
+        self.set("letterhead-caption", caption)
+    }
+
+    /// Full-page background by registered letterhead name, with its opacity
+    pub fn letterhead_background(self, name: &str, opacity: f64) -> Self {
+        self.set("letterhead-background", name)
+            .set("letterhead-background-opacity", opacity)
+    }
+
+    /// Memo date in ISO format (`YYYY-MM-DD`)
+    pub fn date(self, date: &str) -> Self {
+        self.set("date", date)
+    }
+
+    pub fn memo_for<const N: usize>(self, recipients: [&str; N]) -> Self {
+        self.set("memo-for", recipients.to_vec())
+    }
+
+    pub fn from_block<const N: usize>(self, lines: [&str; N]) -> Self {
+        self.set("from-block", lines.to_vec())
+    }
+
+    pub fn subject(self, subject: &str) -> Self {
+        self.set("subject", subject)
+    }
+
+    pub fn references<const N: usize>(self, references: [&str; N]) -> Self {
+        self.set("references", references.to_vec())
+    }
+
+    pub fn signature_block<const N: usize>(self, lines: [&str; N]) -> Self {
+        self.set("signature-block", lines.to_vec())
+    }
+
+    /// Signature blocks of co-signers
+    pub fn co_signers<const N: usize>(self, blocks: [&[&str]; N]) -> Self {
+        self.set("co-signers", blocks.iter().map(|lines| lines.to_vec()).collect::<Vec<_>>())
+    }
+
+    /// Typst markup body through `body_raw`, replacing any `body`
+    pub fn body_raw(self, markup: &str) -> Self {
+        self.remove("body").set("body_raw", markup)
+    }
+
+    /// Typst markup body through a `body` content object, replacing any `body_raw`
+    pub fn body_markup(self, markup: &str) -> Self {
+        self.remove("body_raw")
+            .set("body", json!({ "format": "markup", "data": markup }))
+    }
+
+    /// Quill Delta body (JSON string) through a `body` content object,
+    /// replacing any `body_raw`
+    pub fn body_delta(self, delta_json: &str) -> Self {
+        self.remove("body_raw")
+            .set("body", json!({ "format": "delta", "data": delta_json }))
+    }
+
+    /// Set any field, including ones the schema does not allow
+    pub fn set(mut self, key: &str, value: impl Into<JsonValue>) -> Self {
+        self.fields.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Remove a field
+    pub fn remove(mut self, key: &str) -> Self {
+        self.fields.remove(key);
+        self
+    }
+
+    /// The form as a JSON value
+    pub fn build(&self) -> JsonValue {
+        JsonValue::Object(self.fields.clone())
+    }
+
+    /// The form as a JSON string, ready for `render_form`
+    pub fn to_json(&self) -> String {
+        self.build().to_string()
+    }
+}
+
+impl Default for MemoBuilder {
+    fn default() -> Self {
+        Self::minimal()
+    }
+}
+
+/// Forms that the engine must reject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidMemo {
+    /// The required `subject` field is absent
+    MissingSubject,
+    /// Neither `body` nor `body_raw` is present
+    MissingBody,
+    /// `memo-for` is a string instead of an array
+    WrongFieldType,
+    /// A field the schema does not define
+    UnknownField,
+    /// The Delta body is not valid Quill Delta
+    MalformedDelta,
+    /// The text is not valid JSON
+    MalformedJson,
+}
+
+impl InvalidMemo {
+    /// Every invalid variant
+    pub const ALL: [InvalidMemo; 6] = [
+        InvalidMemo::MissingSubject,
+        InvalidMemo::MissingBody,
+        InvalidMemo::WrongFieldType,
+        InvalidMemo::UnknownField,
+        InvalidMemo::MalformedDelta,
+        InvalidMemo::MalformedJson,
+    ];
+
+    /// The invalid form as a JSON string
+    pub fn to_json(self) -> String {
+        match self {
+            InvalidMemo::MissingSubject => MemoBuilder::minimal().remove("subject").to_json(),
+            InvalidMemo::MissingBody => MemoBuilder::minimal().remove("body_raw").to_json(),
+            InvalidMemo::WrongFieldType => MemoBuilder::minimal().set("memo-for", "ORG/SYMBOL").to_json(),
+            InvalidMemo::UnknownField => MemoBuilder::minimal().set("classification", "UNCLASSIFIED").to_json(),
+            InvalidMemo::MalformedDelta => MemoBuilder::minimal().body_delta(r#"{"ops":"not an array"}"#).to_json(),
+            InvalidMemo::MalformedJson => {
+                let json = MemoBuilder::minimal().to_json();
+                json[..json.len() - 1].to_string()
+            }
+        }
+    }
+
+    /// What makes the form invalid
+    pub fn description(self) -> &'static str {
+        match self {
+            InvalidMemo::MissingSubject => "missing required subject",
+            InvalidMemo::MissingBody => "missing body and body_raw",
+            InvalidMemo::WrongFieldType => "memo-for is not an array",
+            InvalidMemo::UnknownField => "field not defined by the schema",
+            InvalidMemo::MalformedDelta => "Delta body without an ops array",
+            InvalidMemo::MalformedJson => "truncated JSON",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::form_processor::validate_and_preprocess_form_json;
+
+    #[test]
+    fn test_valid_fixtures() {
+        for builder in [MemoBuilder::minimal(), MemoBuilder::maximal(), MemoBuilder::delta()] {
+            let json = builder.to_json();
+            assert!(validate_and_preprocess_form_json(&json).is_ok(), "{}", json);
+        }
+        assert!(crate::render_form(&MemoBuilder::maximal().to_json(), None).is_ok());
+    }
+
+    #[test]
+    fn test_body_setters_replace_each_other() {
+        let form = MemoBuilder::minimal().body_delta("{\"ops\":[]}").build();
+        assert!(form.get("body_raw").is_none());
+        assert_eq!(form["body"]["format"], "delta");
+
+        let form = MemoBuilder::delta().body_raw("Plain").build();
+        assert!(form.get("body").is_none());
+        assert_eq!(form["body_raw"], "Plain");
+    }
+
+    #[test]
+    fn test_invalid_fixtures() {
+        for invalid in InvalidMemo::ALL {
+            assert!(
+                validate_and_preprocess_form_json(&invalid.to_json()).is_err(),
+                "{} was accepted",
+                invalid.description()
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Positions of the text on rendered pages.
+//!
+//! SVG pages draw glyphs as paths, so a preview cannot select, search or
+//! copy their text. The text runs of each page, with their bounding boxes in
+//! the page's coordinates, let a host lay invisible text or highlights over
+//! the SVG. With [`RenderConfig::text_layout`](crate::RenderConfig::text_layout)
+//! an [`OutputFormat::SvgZip`](crate::OutputFormat::SvgZip) archive holds a
+//! `page-001.json` next to each `page-001.svg`.
+
+use serde::Serialize;
+use tracing::instrument;
+use typst::layout::{Abs, Frame, FrameItem, Page, PagedDocument, Point, Transform};
+use typst::text::TextItem;
+
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// The text runs of a page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageText {
+    /// Page size in points
+    pub width: f64,
+    pub height: f64,
+    /// Text runs in drawing order
+    pub runs: Vec<TextRun>,
+}
+
+/// A run of text in one font and size, with its bounding box in points from
+/// the top left corner of the page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextRun {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Font size in points
+    pub size: f64,
+}
+
+/// The text runs of every page of rendered Typst markup
+///
+/// # Examples
+/// ```
+/// use render_engine::markup_text_layout;
+///
+/// let pages = markup_text_layout("Hello").unwrap();
+/// assert_eq!(pages[0].runs[0].text, "Hello");
+/// ```
+#[instrument(skip_all)]
+pub fn markup_text_layout(markup: &str) -> Result<Vec<PageText>, TypstWrapperError> {
+    let document = TypstWrapper::compile_markup(markup)?;
+    Ok(document_text(&document))
+}
+
+/// The text runs of every page of a rendered memo form
+#[instrument(skip_all)]
+pub fn form_text_layout(json_input: &str) -> Result<Vec<PageText>, TypstWrapperError> {
+    let document = TypstWrapper::compile_form(json_input)?;
+    Ok(document_text(&document))
+}
+
+pub(crate) fn document_text(document: &PagedDocument) -> Vec<PageText> {
+    document.pages.iter().map(page_text).collect()
+}
+
+fn page_text(page: &Page) -> PageText {
+    let mut runs = Vec::new();
+    collect(&page.frame, Transform::identity(), &mut runs);
+    PageText {
+        width: round(page.frame.width()),
+        height: round(page.frame.height()),
+        runs,
+    }
+}
+
+fn collect(frame: &Frame, transform: Transform, runs: &mut Vec<TextRun>) {
+    for (pos, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        match item {
+            FrameItem::Group(group) => collect(&group.frame, transform.pre_concat(group.transform), runs),
+            FrameItem::Text(text) if !text.text.trim().is_empty() => {
+                let (min, max) = text_bounds(text, transform, Abs::zero(), text.width());
+                runs.push(TextRun {
+                    text: text.text.to_string(),
+                    x: round(min.x),
+                    y: round(min.y),
+                    width: round(max.x - min.x),
+                    height: round(max.y - min.y),
+                    size: round(text.size),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Page bounds `(min, max)` of the part of a text run from `from` to `to`
+/// along its baseline, spanning the font's ascender and descender
+pub(crate) fn text_bounds(text: &TextItem, transform: Transform, from: Abs, to: Abs) -> (Point, Point) {
+    let metrics = text.font.metrics();
+    let top = -metrics.ascender.at(text.size);
+    let bottom = -metrics.descender.at(text.size);
+    // The run may be rotated or scaled
+    let corners = [
+        Point::new(from, top),
+        Point::new(to, top),
+        Point::new(from, bottom),
+        Point::new(to, bottom),
+    ]
+    .map(|corner| corner.transform(transform));
+    let min = corners.iter().fold(corners[0], |min, corner| min.min(*corner));
+    let max = corners.iter().fold(corners[0], |max, corner| max.max(*corner));
+    (min, max)
+}
+
+/// Points to a hundredth, which keeps the JSON short
+pub(crate) fn round(value: Abs) -> f64 {
+    (value.to_pt() * 100.0).round() / 100.0
+}
+
+#[cfg(all(test, feature = "font-times"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markup_text_layout() {
+        let markup = "#set page(width: 200pt, height: 100pt, margin: 10pt)\n#set text(size: 10pt)\nHello\n#pagebreak()\n#v(20pt)\nWorld";
+        let pages = markup_text_layout(markup).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!((pages[0].width, pages[0].height), (200.0, 100.0));
+        let hello = &pages[0].runs[0];
+        assert_eq!(hello.text, "Hello");
+        assert_eq!(hello.size, 10.0);
+        assert!((hello.x - 10.0).abs() < 0.01 && hello.y >= 9.0 && hello.y < 11.0, "{:?}", hello);
+        assert!(hello.width > 15.0 && hello.height > 9.0 && hello.height < 13.0, "{:?}", hello);
+        assert!(pages[1].runs[0].y > 28.0, "{:?}", pages[1].runs[0]);
+    }
+
+    #[test]
+    fn test_form_text_layout() {
+        let form = crate::test_utils::MemoBuilder::minimal().subject("Text Layout").to_json();
+        let pages = form_text_layout(&form).unwrap();
+        assert!(pages[0].runs.iter().any(|run| run.text.contains("Text Layout")));
+    }
+}
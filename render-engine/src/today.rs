@@ -0,0 +1,230 @@
+//! The date templates see as today.
+//!
+//! `datetime.today()` returns the date of
+//! [`RenderConfig::today`](crate::RenderConfig::today) or, for memo forms
+//! without one, the `date` field of the form, so a memo and everything its
+//! template dates with `datetime.today()` agree on one date. Without either,
+//! it returns the current date at the offset from UTC the template asks
+//! for, or in the host's local time. Output caches and sessions key renders
+//! by the local date, so they do not repeat renders that asked for an
+//! offset.
+//!
+//! The engine reads the time from the system clock, in UTC. Hosts that know
+//! better, such as a browser with the user's time zone, install a clock with
+//! [`set_clock`] returning a [`HostTime`], so a memo rendered shortly after
+//! midnight is dated with the local date rather than the UTC one. The clock
+//! also dates drafts and PDF/X output. Like registrations, clocks belong to
+//! the current [`RenderEngine`](crate::RenderEngine).
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use typst::foundations::Datetime;
+
+use crate::engine;
+use crate::metadata::parse_date;
+use crate::print;
+use crate::typst_wrapper::TypstWrapperError;
+
+/// The current time as the host sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostTime {
+    /// Milliseconds since 1970-01-01T00:00:00Z, as `Date.now()` returns
+    pub timestamp_ms: i64,
+    /// How far local time is ahead of UTC in minutes, e.g. `-300` for New
+    /// York in winter
+    pub utc_offset_minutes: i32,
+}
+
+/// A host clock installed with [`set_clock`]
+#[derive(Clone)]
+pub(crate) struct Clock(Arc<dyn Fn() -> HostTime + Send + Sync>);
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+impl Clock {
+    pub(crate) fn new(clock: impl Fn() -> HostTime + Send + Sync + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+}
+
+/// Read the current time from `clock` in every subsequent render with the
+/// current [`RenderEngine`](crate::RenderEngine) instead of the system clock,
+/// replacing any previous clock
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, set_clock, clear_clock, HostTime};
+///
+/// // 23:30 UTC on 31 December 2024 is already 2025 in Berlin
+/// set_clock(|| HostTime { timestamp_ms: 1_735_687_800_000, utc_offset_minutes: 60 });
+/// let pages = render_markup("#datetime.today().display()", None).unwrap();
+/// clear_clock();
+/// ```
+pub fn set_clock<F>(clock: F)
+where
+    F: Fn() -> HostTime + Send + Sync + 'static,
+{
+    engine::current().set_clock(clock)
+}
+
+/// Go back to the system clock in UTC after [`set_clock`]
+pub fn clear_clock() {
+    engine::current().clear_clock()
+}
+
+/// The current time from the current engine's clock, or the system clock in
+/// UTC
+pub(crate) fn now() -> Option<HostTime> {
+    engine::current().now()
+}
+
+/// The current time from `clock`, or the system clock in UTC
+pub(crate) fn time(clock: Option<&Clock>) -> Option<HostTime> {
+    match clock {
+        Some(clock) => Some((clock.0)()),
+        None => {
+            let elapsed = web_time::SystemTime::now().duration_since(web_time::UNIX_EPOCH).ok()?;
+            Some(HostTime { timestamp_ms: i64::try_from(elapsed.as_millis()).ok()?, utc_offset_minutes: 0 })
+        }
+    }
+}
+
+/// The date of `today`, `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS[Z]`, without
+/// its time
+pub(crate) fn date(today: &str) -> Result<Datetime, TypstWrapperError> {
+    let datetime = parse_date(today, "today date")?;
+    datetime
+        .year()
+        .zip(datetime.month())
+        .zip(datetime.day())
+        .and_then(|((year, month), day)| Datetime::from_ymd(year, month, day))
+        .ok_or_else(|| TypstWrapperError::Validation(format!("Invalid today date '{}': use YYYY-MM-DD", today)))
+}
+
+/// The current date `offset_hours` ahead of UTC, or in local time
+pub(crate) fn current(offset_hours: Option<i64>) -> Option<Datetime> {
+    date_at(now()?, offset_hours)
+}
+
+/// The date at `time`, `offset_hours` ahead of UTC or in its local time
+pub(crate) fn date_at(time: HostTime, offset_hours: Option<i64>) -> Option<Datetime> {
+    let offset_seconds = match offset_hours {
+        Some(hours) => hours.checked_mul(3600)?,
+        None => i64::from(time.utc_offset_minutes) * 60,
+    };
+    let datetime = print::utc_datetime(time.timestamp_ms.div_euclid(1000).checked_add(offset_seconds)?)?;
+    Datetime::from_ymd(datetime.year()?, datetime.month()?, datetime.day()?)
+}
+
+/// The `date` field of preprocessed form JSON, if it is a valid date; the
+/// template reports invalid ones
+pub(crate) fn form_today(processed_input: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Form {
+        date: Option<String>,
+    }
+
+    let form: Form = serde_json::from_str(processed_input).ok()?;
+    form.date.filter(|today| date(today).is_ok())
+}
+
+/// The date of today for a render with `today` in its config, for cache
+/// keys: renders without one change every day
+pub(crate) fn key(today: Option<&str>) -> Option<Datetime> {
+    today.and_then(|today| date(today).ok()).or_else(|| current(None))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "font-times")]
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use super::*;
+    #[cfg(feature = "font-times")]
+    use crate::test_utils::MemoBuilder;
+    use crate::{render_markup, RenderConfig};
+
+    /// Config rendering SVG pages with their text selectable
+    #[cfg(feature = "font-times")]
+    fn config(today: Option<&str>) -> RenderConfig {
+        RenderConfig { today: today.map(str::to_string), svg_text: crate::SvgTextMode::Selectable, ..RenderConfig::default() }
+    }
+
+    #[cfg(feature = "font-times")]
+    fn today_text(today: Option<&str>) -> String {
+        let svg = render_markup("#datetime.today().display()", Some(config(today))).unwrap().remove(0);
+        String::from_utf8(svg).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_configured_today() {
+        assert!(today_text(Some("2025-03-07")).contains("2025-03-07"));
+        assert!(today_text(Some("2025-03-07T23:30:00Z")).contains("2025-03-07"));
+    }
+
+    #[test]
+    fn test_dates_near_midnight_follow_the_offset() {
+        // 2024-12-31T23:30:00Z
+        let berlin = HostTime { timestamp_ms: 1_735_687_800_000, utc_offset_minutes: 60 };
+        assert_eq!(date_at(berlin, None), Datetime::from_ymd(2025, 1, 1));
+        assert_eq!(date_at(berlin, Some(0)), Datetime::from_ymd(2024, 12, 31));
+        assert_eq!(date_at(HostTime { utc_offset_minutes: -300, ..berlin }, None), Datetime::from_ymd(2024, 12, 31));
+        assert_eq!(date_at(berlin, Some(-24)), Datetime::from_ymd(2024, 12, 30));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_host_clock() {
+        // The clock serves every render of its engine, so other tests keep the system clock
+        let engine = crate::RenderEngine::new();
+        engine.set_clock(|| HostTime { timestamp_ms: 1_735_687_800_000, utc_offset_minutes: 60 });
+        let local = engine.scope(|| today_text(None));
+        assert!(local.contains("2025-01-01"), "{}", local);
+
+        engine.clear_clock();
+        let now = current(None).unwrap();
+        let expected = format!("{}-{:02}-{:02}", now.year().unwrap(), now.month().unwrap(), now.day().unwrap());
+        assert!(engine.scope(|| today_text(None)).contains(&expected));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_dates_at_an_offset_are_not_repeated() {
+        // 2024-12-31T10:00:00Z, then 13:00, when it is already 2025 twelve hours ahead
+        let timestamp_ms = Arc::new(AtomicI64::new(1_735_639_200_000));
+        let engine = crate::RenderEngine::new();
+        let clock = timestamp_ms.clone();
+        engine.set_clock(move || HostTime { timestamp_ms: clock.load(Ordering::Relaxed), utc_offset_minutes: 0 });
+
+        let mut session = engine.session(Some(config(None)));
+        let mut render = || String::from_utf8(session.render_markup("#datetime.today(offset: 12).display()").unwrap().remove(0)).unwrap();
+        assert!(render().contains("2024-12-31"));
+        timestamp_ms.store(1_735_650_000_000, Ordering::Relaxed);
+        let svg = render();
+        assert!(svg.contains("2025-01-01"), "{}", svg);
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_form_date_is_today() {
+        let form = MemoBuilder::minimal().body_raw("Issued #datetime.today().display()").date("2024-02-01").to_json();
+        let processed = crate::typst_wrapper::TypstWrapper::validate_form(&form).unwrap();
+        assert_eq!(form_today(&processed).as_deref(), Some("2024-02-01"));
+        let svg = String::from_utf8(crate::render_form(&form, Some(config(None))).unwrap().remove(0)).unwrap();
+        assert!(svg.contains("Issued 2024-02-01"), "{}", svg);
+    }
+
+    #[test]
+    fn test_invalid_today_fails() {
+        let config = RenderConfig { today: Some("07.03.2025".to_string()), ..RenderConfig::default() };
+        assert!(matches!(render_markup("Hello", Some(config)), Err(TypstWrapperError::Validation(_))));
+        assert_eq!(key(Some("2025-03-07T10:00:00")), Datetime::from_ymd(2025, 3, 7));
+    }
+}
@@ -1,61 +1,109 @@
-use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
 
-use crate::assets;
+use crate::alt_text;
+use crate::annotations::{self, Annotation};
+use crate::archive;
+use crate::attribution;
+use crate::assets::{self, RegistrationError};
+use crate::bookmarks;
+use crate::cache::{self, CachedRender};
+use crate::color_mode::{self, ColorMode};
+use crate::diagnostics::{self, Diagnostic, DiagnosticSource, RelatedInformation, Severity};
+use crate::duplex;
+use crate::excerpt;
+use crate::engine::{self, Letterheads, LocalPackages, PackageFiles, PackageSources, RegisteredAssets, RenderEngine, TemplatePartials};
+use crate::form_fields;
+use crate::fonts::{FontSet, FontSubstitution, SubstitutedBook};
+use crate::delta_parser::ParserError;
 use crate::form_processor;
-use typst::diag::{FileError, FileResult};
+use crate::links;
+use crate::packages;
+use crate::metadata::{self, DocumentMetadata};
+use crate::metrics::{self, RenderMetrics, RenderOutput};
+use crate::observer::RenderObserver;
+use crate::print::{self, PrintProfile};
+use crate::resolver;
+use crate::running::{self, RunningText};
+use crate::draft;
+use crate::embedded_form;
+use crate::style::{self, StyleConfig};
+use crate::svg_cache::{self, SvgCache};
+use crate::svg_ids;
+use crate::svg_scale;
+use crate::svg_stack;
+use crate::svg_text::{self, SvgTextMode};
+use crate::today;
+use crate::template::{self, Partial};
+use crate::text_layout;
+use crate::watermark::{self, Watermark};
+use typst::diag::{FileError, FileResult, PackageError, SourceDiagnostic, Warned};
 use typst::foundations::{Bytes, Datetime};
 use typst::layout::PagedDocument;
-use typst::syntax::{FileId, Source, VirtualPath, package::PackageSpec};
-use typst::text::{Font, FontBook, FontInfo};
+use typst::syntax::package::{PackageSpec, PackageVersion};
+use typst::syntax::{FileId, LinkedNode, Side, Source, Span, VirtualPath};
+use typst::text::{Font, FontBook};
 use typst::utils::LazyHash;
+use typst::visualize::ImageFormat;
 use typst::{Library, World};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, debug_span, info, instrument, warn};
+use web_time::Instant;
 
-// Static font collections initialized at compile time
-static FONT_BOOK: LazyLock<LazyHash<FontBook>> = LazyLock::new(|| {
-    let mut book = FontBook::new();
-    
-    // Load all embedded fonts from assets
-    let font_assets = assets::get_font_assets();
-    
-    for font_asset in font_assets {
-        // Single font files (ttf, otf)
-        if font_asset.path.ends_with(".ttf") || font_asset.path.ends_with(".otf") {
-            if let Some(info) = FontInfo::new(font_asset.content, 0) {
-                book.push(info);
-            }
-        }
-        // Font collections (ttc) - try multiple indices
-        else if font_asset.path.ends_with(".ttc") {
-            for i in 0..10 {
-                if let Some(info) = FontInfo::new(font_asset.content, i) {
-                    book.push(info);
-                } else {
-                    break;
-                }
-            }
-        }
-    }
-    
-    LazyHash::new(book)
-});
+/// Name used for the memo-loader template in diagnostics
+const MEMO_TEMPLATE_PATH: &str = "memo-loader/main.typ";
+
+/// Line of a memo template that reads the form, pointed at the form of
+/// each render
+const INPUT_LINE: &str = "#let input = json(\"input.json\")";
+
+/// Minimal memo compiled by `prewarm` to load the template and its package
+const PREWARM_FORM: &str = r#"{
+    "memo-for": ["ORG/SYMBOL"],
+    "from-block": ["ORG/SYMBOL"],
+    "subject": "Prewarm",
+    "signature-block": ["NAME", "TITLE"],
+    "body_raw": "Prewarm"
+}"#;
+
+// Typst standard library, shared by every world
+static LIBRARY: LazyLock<LazyHash<Library>> = LazyLock::new(|| LazyHash::new(Library::default()));
+
+// Font book describing `FONTS`, so book indices always match font indices
+static FONT_BOOK: LazyLock<LazyHash<FontBook>> = LazyLock::new(|| LazyHash::new(FontBook::from_fonts(FONTS.iter())));
 
 // Static font vector for font() method access
 static FONTS: LazyLock<Vec<Font>> = LazyLock::new(|| {
-    let mut fonts = Vec::new();
-    
-    // Load all embedded fonts using Font::iter from assets
+    // Load all embedded fonts (every face of collections) from assets
     let font_assets = assets::get_font_assets();
+    let faces = |content: &'static [u8]| Font::iter(Bytes::new(content));
     
-    for font_asset in font_assets {
-        for font in Font::iter(Bytes::new(font_asset.content)) {
-            fonts.push(font);
-        }
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        font_assets
+            .par_iter()
+            .flat_map_iter(|font_asset| faces(font_asset.content))
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        font_assets
+            .iter()
+            .flat_map(|font_asset| faces(font_asset.content))
+            .collect()
     }
-    
-    fonts
 });
 
+/// The fonts embedded in the engine
+pub(crate) fn embedded_fonts() -> &'static [Font] {
+    &FONTS
+}
+
 /// Error types for the Typst wrapper
 #[derive(Debug)]
 pub enum TypstWrapperError {
@@ -65,6 +113,28 @@ pub enum TypstWrapperError {
     FileNotFound(String),
     Io(std::io::Error),
     Validation(String),
+    Registration(RegistrationError),
+    /// A render observer stopped the render
+    Cancelled,
+    /// A job of a batch render failed
+    Batch {
+        file_name: String,
+        error: Box<TypstWrapperError>,
+    },
+    /// A form failed to validate or compile because of one of its fields
+    Field {
+        /// JSON pointer of the field, such as `/subject` or `/body`
+        field: String,
+        /// Index of the Delta op of a Delta body the error lies in
+        op: Option<usize>,
+        error: Box<TypstWrapperError>,
+    },
+    /// A form failed to compile, with the numbered lines of the generated
+    /// Typst around the error (see [`excerpt`](crate::excerpt))
+    Excerpt {
+        excerpt: String,
+        error: Box<TypstWrapperError>,
+    },
 }
 
 impl std::fmt::Display for TypstWrapperError {
@@ -76,41 +146,187 @@ impl std::fmt::Display for TypstWrapperError {
             TypstWrapperError::FileNotFound(msg) => write!(f, "File not found: {}", msg),
             TypstWrapperError::Io(e) => write!(f, "IO error: {}", e),
             TypstWrapperError::Validation(msg) => write!(f, "Validation failed: {}", msg),
+            TypstWrapperError::Registration(e) => write!(f, "Registration rejected: {}", e),
+            TypstWrapperError::Cancelled => write!(f, "Render cancelled"),
+            TypstWrapperError::Batch { file_name, error } => write!(f, "{}: {}", file_name, error),
+            TypstWrapperError::Field { field, op: Some(op), error } => write!(f, "{} (Delta op {}): {}", field, op, error),
+            TypstWrapperError::Field { field, op: None, error } => write!(f, "{}: {}", field, error),
+            TypstWrapperError::Excerpt { excerpt, error } => write!(f, "{}\n{}", error, excerpt),
         }
     }
 }
 
 impl std::error::Error for TypstWrapperError {}
 
+/// Schema errors of a value within the form name its field
+impl From<ParserError> for TypstWrapperError {
+    fn from(error: ParserError) -> Self {
+        match error {
+            ParserError::Schema { path, message } if !path.is_empty() => TypstWrapperError::Field {
+                field: path,
+                op: None,
+                error: Box::new(TypstWrapperError::Validation(format!("Form JSON does not match schema: {}", message))),
+            },
+            error => TypstWrapperError::Validation(error.to_string()),
+        }
+    }
+}
+
 impl From<std::io::Error> for TypstWrapperError {
     fn from(error: std::io::Error) -> Self {
         TypstWrapperError::Io(error)
     }
 }
 
-/// Output format configuration
-#[derive(Debug, Clone, Copy)]
+impl From<RegistrationError> for TypstWrapperError {
+    fn from(error: RegistrationError) -> Self {
+        TypstWrapperError::Registration(error)
+    }
+}
+
+/// Output format configuration, written `"svg"`, `"pdf"` or `"svg-zip"`
+/// in serialized configs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum OutputFormat {
+    #[default]
     Svg,
     Pdf,
+    /// One ZIP archive holding `page-001.svg`, `page-002.svg`, ... and a
+    /// `manifest.json` with each page's file name and size in points
+    SvgZip,
 }
 
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Svg
+impl OutputFormat {
+    /// Name of the format in serialized configs and the wasm, server and
+    /// CLI interfaces
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::SvgZip => "svg-zip",
+        }
     }
 }
 
 /// Render configuration
-#[derive(Debug, Clone)]
+///
+/// Configs serialize to JSON or TOML with the field names below, and fields
+/// missing from a serialized config take their default values:
+///
+/// ```
+/// use render_engine::{OutputFormat, RenderConfig};
+///
+/// let config: RenderConfig = serde_json::from_str(r#"{"format": "pdf", "watermark": {"text": "DRAFT"}}"#).unwrap();
+/// assert_eq!(config.format, OutputFormat::Pdf);
+/// assert!(config.svg_links);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RenderConfig {
     pub format: OutputFormat,
+    /// Text stamped diagonally across every page, overriding the
+    /// `watermark` field of a form
+    pub watermark: Option<Watermark>,
+    /// Running header, replacing the `running-header` field of a form
+    pub header: Option<RunningText>,
+    /// Running footer, replacing the `running-footer` field of a form
+    pub footer: Option<RunningText>,
+    /// Insert blank pages so that every element labelled `<section-start>`
+    /// starts on an odd page, for double-sided printing
+    pub duplex: bool,
+    /// Export PDF as PDF/X for this printing condition, with transparency
+    /// flattened (see [`print`](crate::print))
+    pub print: Option<PrintProfile>,
+    /// Make links clickable in SVG pages, as `<a>` elements over the link
+    /// areas; turn off for print
+    pub svg_links: bool,
+    /// Render SVG text as glyph outlines only or with selectable text over
+    /// them (see [`svg_text`](crate::svg_text))
+    pub svg_text: SvgTextMode,
+    /// Display size of SVG pages relative to their size in points, e.g.
+    /// `0.25` for thumbnails or `2` for high-DPI previews; the `viewBox` and
+    /// all coordinates inside a page stay in points
+    pub scale: f64,
+    /// Add each page's text runs and their bounding boxes to SVG ZIPs as
+    /// `page-001.json`, ... (see [`text_layout`](crate::text_layout))
+    pub text_layout: bool,
+    /// Review comments drawn over SVG pages or added to PDF output as
+    /// annotations (see [`annotations`](crate::annotations))
+    pub annotations: Vec<Annotation>,
+    /// Add fillable signature and date fields to PDF output at the elements
+    /// labelled `<signature-field>` and `<date-field>`, for e-signature tools
+    pub form_fields: bool,
+    /// Attach the form JSON to the PDF output of form renders, so it can be
+    /// read back with [`extract_form`](crate::embedded_form::extract_form)
+    pub embed_form: bool,
+    /// Title, author, subject, keywords and creation date of PDF output,
+    /// taking precedence over the `metadata` field of a form (see
+    /// [`metadata`](crate::metadata))
+    pub metadata: Option<DocumentMetadata>,
+    /// Date `datetime.today()` returns in templates, as `YYYY-MM-DD`,
+    /// taking precedence over the `date` field of a form; the current date
+    /// when unset (see [`today`](crate::today))
+    pub today: Option<String>,
+    /// Bookmark the headings, labelled sections and numbered memo
+    /// paragraphs of PDF output (see [`bookmarks`](crate::bookmarks)); turn
+    /// off for PDFs without an outline
+    pub generate_outline: bool,
+    /// Pages to export, counted from 0, e.g. `0..1` for the first page
+    /// only. The page sizes in the output still list every page of the
+    /// document.
+    pub page_range: Option<Range<usize>>,
+    /// Stack the pages of SVG output into one SVG, separated by gray bands
+    /// (see [`svg_stack`](crate::svg_stack))
+    pub stack_pages: bool,
+    /// Put this in front of every id in SVG output and every reference to
+    /// one, so pages of several documents can be inlined in one HTML page
+    /// (see [`svg_ids`](crate::svg_ids))
+    pub id_prefix: Option<String>,
+    /// Font families to render with the fonts of another family, e.g.
+    /// `"Times New Roman"` → `"Times"` (see [`fonts`](crate::fonts))
+    pub font_substitutions: BTreeMap<String, String>,
+    /// Serve the render from the output caches when possible and store it
+    /// there (see [`cache`](crate::cache)); turn off to always render
+    pub cache: bool,
+    /// Fail on package imports whose exact version is not embedded instead
+    /// of using the closest compatible embedded version with a warning
+    pub strict_packages: bool,
+    /// Style tokens for form renders, replacing the `style` field of a form
+    /// (see [`style`](crate::style))
+    pub style: Option<StyleConfig>,
+    /// Render in grayscale or high contrast, for monochrome printers and
+    /// readers who need it (see [`color_mode`](crate::color_mode))
+    pub color_mode: ColorMode,
 }
 
 impl Default for RenderConfig {
     fn default() -> Self {
         Self {
             format: OutputFormat::Svg,
+            watermark: None,
+            header: None,
+            footer: None,
+            duplex: false,
+            print: None,
+            svg_links: true,
+            svg_text: SvgTextMode::Outlines,
+            scale: 1.0,
+            text_layout: false,
+            annotations: Vec::new(),
+            form_fields: false,
+            embed_form: false,
+            metadata: None,
+            today: None,
+            generate_outline: true,
+            page_range: None,
+            stack_pages: false,
+            id_prefix: None,
+            font_substitutions: BTreeMap::new(),
+            cache: true,
+            strict_packages: false,
+            style: None,
+            color_mode: ColorMode::Color,
         }
     }
 }
@@ -130,15 +346,52 @@ impl TypstWrapper {
         markup: &str,
         config: Option<RenderConfig>,
     ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
-        let mut world = TypstWorld::new();
-        
-        let source = Source::new(
-            FileId::new(None, VirtualPath::new("main.typ")),
-            assets::rewrite_latest_imports(markup),
-        );
-        world.insert_source(source);
-        
-        Self::render_file(world, config)
+        Self::render_markup_with_metrics(markup, config).map(|output| output.pages)
+    }
+    
+    /// Render Typst markup, returning the pages with the render's metrics
+    pub fn render_markup_with_metrics(
+        markup: &str,
+        config: Option<RenderConfig>,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        Self::render_markup_observed(markup, config, &mut ())
+    }
+    
+    /// Render Typst markup, reporting progress to `observer`
+    #[instrument(name = "render_markup", skip_all, fields(markup_len = markup.len()))]
+    pub fn render_markup_observed(
+        markup: &str,
+        config: Option<RenderConfig>,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        Self::render_markup_in(&mut None, None, markup, config.unwrap_or_default(), observer)
+    }
+    
+    /// Render Typst markup in `warm`, the world of a
+    /// [`RenderSession`](crate::RenderSession)'s previous render, which gets
+    /// the markup in place of its own, or in a new world it leaves there.
+    /// SVG pages come from `svgs` where they did not change.
+    pub(crate) fn render_markup_in(
+        warm: &mut Option<TypstWorld>,
+        svgs: Option<&SvgCache>,
+        markup: &str,
+        config: RenderConfig,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        let started = Instant::now();
+        let key = cache::key("markup", markup, &config);
+        if let Some(result) = Self::from_cache(key.as_deref(), started, observer) {
+            return Self::finish(result, observer);
+        }
+        let world = match warm.take() {
+            Some(mut world) if world.form_input().is_none() => {
+                world.replace_main(markup);
+                world
+            }
+            _ => Self::markup_world(markup),
+        };
+        let result = Self::render_kept(warm, world, svgs, &config, started, observer);
+        Self::finish(Self::store(key.as_deref(), warm.as_ref(), result), observer)
     }
     
     /// Render form using JSON input and memo-loader template
@@ -146,39 +399,353 @@ impl TypstWrapper {
         json_input: &str,
         config: Option<RenderConfig>,
     ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
-        // Create a completely fresh world for each render to avoid state pollution
-        let mut world = TypstWorld::new();
-
+        Self::render_form_with_metrics(json_input, config).map(|output| output.pages)
+    }
+    
+    /// Render a form, returning the pages with the render's metrics
+    pub fn render_form_with_metrics(
+        json_input: &str,
+        config: Option<RenderConfig>,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        Self::render_form_observed(json_input, config, &mut ())
+    }
+    
+    /// Render a form, reporting progress to `observer`
+    #[instrument(name = "render_form", skip_all, fields(json_len = json_input.len()))]
+    pub fn render_form_observed(
+        json_input: &str,
+        config: Option<RenderConfig>,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        Self::render_form_in(&mut None, None, json_input, config.unwrap_or_default(), observer)
+    }
+    
+    /// Render a form in `warm`, the world of a
+    /// [`RenderSession`](crate::RenderSession)'s previous render, which gets
+    /// the form's JSON in place of its own, or in a new world it leaves
+    /// there. SVG pages come from `svgs` where they did not change.
+    pub(crate) fn render_form_in(
+        warm: &mut Option<TypstWorld>,
+        svgs: Option<&SvgCache>,
+        json_input: &str,
+        mut config: RenderConfig,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        let started = Instant::now();
+        // A draft shows when it was rendered, so it is never cached
+        config.cache &= !draft::is_draft(json_input);
+        let key = cache::key("form", json_input, &config);
+        if let Some(result) = Self::from_cache(key.as_deref(), started, observer) {
+            return Self::finish(result, observer);
+        }
+        let result = Self::prepare_form(json_input, &mut config).and_then(|(processed_input, source_form)| {
+            let mut world = match warm.take() {
+                Some(mut world) if world.form_input().is_some() => {
+                    world.replace_form_input(processed_input);
+                    world
+                }
+                _ => Self::form_world(processed_input)?,
+            };
+            world.source_form = source_form;
+            Self::render_kept(warm, world, svgs, &config, started, observer)
+        });
+        Self::finish(Self::store(key.as_deref(), warm.as_ref(), result), observer)
+    }
+    
+    /// Validate and preprocess form JSON for a render, moving the render
+    /// settings the form carries into `config`. Returns the processed JSON
+    /// and, with [`RenderConfig::embed_form`], the form to attach to PDF
+    /// output.
+    fn prepare_form(json_input: &str, config: &mut RenderConfig) -> Result<(String, Option<String>), TypstWrapperError> {
+        let json_input = running::move_into_form(json_input, config)?;
+        let json_input = style::move_into_form(&json_input, config)?;
+        let processed_input = Self::validate_form(&json_input)?;
+        if config.watermark.is_none() {
+            config.watermark = watermark::form_watermark(&processed_input)?;
+        }
+        config.metadata = metadata::form_metadata(&processed_input, config.metadata.take())?;
+        if config.today.is_none() {
+            config.today = today::form_today(&processed_input);
+        }
+        let source_form = config.embed_form.then(|| embedded_form::canonical_form(&json_input)).transpose()?;
+        Ok((processed_input, source_form))
+    }
+    
+    /// Serve a render from the output caches, reporting its pages to
+    /// `observer` as if they were exported. `None` on a miss.
+    fn from_cache(
+        key: Option<&str>,
+        started: Instant,
+        observer: &mut dyn RenderObserver,
+    ) -> Option<Result<RenderOutput, TypstWrapperError>> {
+        let cached = cache::load(key?)?;
+        Some(Self::replay(cached, started, observer))
+    }
+    
+    /// Serve the output of an earlier render, reporting its pages to
+    /// `observer` as if they were exported
+    pub(crate) fn replay(
+        cached: CachedRender,
+        started: Instant,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        for (index, page) in cached.pages.iter().enumerate() {
+            if observer.on_page_exported(index, cached.pages.len(), page).is_break() {
+                return Err(TypstWrapperError::Cancelled);
+            }
+        }
+        let metrics = RenderMetrics {
+            total_ms: metrics::elapsed_ms(started),
+            pages: cached.page_sizes.len(),
+            bytes: cached.pages.iter().map(Vec::len).sum(),
+            from_cache: true,
+            ..RenderMetrics::default()
+        };
+        metrics::report(&metrics);
+        Ok(RenderOutput {
+            pages: cached.pages,
+            page_sizes: cached.page_sizes,
+            font_substitutions: cached.font_substitutions,
+            metrics,
+        })
+    }
+    
+    /// Store a successful render of `world` in the output caches under
+    /// `key`, unless it dated itself at an offset from UTC the key does not
+    /// cover
+    fn store(
+        key: Option<&str>,
+        world: Option<&TypstWorld>,
+        result: Result<RenderOutput, TypstWrapperError>,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        if let (Some(key), Ok(output)) = (key, &result) {
+            if !world.is_some_and(TypstWorld::dated_at_offset) {
+                cache::store(key, output);
+            }
+        }
+        result
+    }
+    
+    /// Validate and preprocess form JSON for rendering
+    pub(crate) fn validate_form(json_input: &str) -> Result<String, TypstWrapperError> {
         // Validate and preprocess the form JSON (populate body_raw if needed)
-        let processed_input = form_processor::validate_and_preprocess_form_json(json_input)
-            .map_err(|e| TypstWrapperError::Validation(format!("{}", e)))?;
+        debug_span!("validate_form")
+            .in_scope(|| form_processor::validate_and_preprocess_form_json(json_input))
+            .map_err(|e| {
+                debug!(error = %e, "form validation failed");
+                TypstWrapperError::from(e)
+            })
+            .and_then(draft::prepare)
+    }
+    
+    /// Report the end of a render to `observer`
+    pub(crate) fn finish(
+        result: Result<RenderOutput, TypstWrapperError>,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        observer.on_finish(result.as_ref().map(|output| &output.metrics));
+        result
+    }
+    
+    /// Collect diagnostics for Typst markup without exporting it
+    #[instrument(skip_all, fields(markup_len = markup.len()))]
+    pub fn diagnose_markup(markup: &str) -> Vec<Diagnostic> {
+        Self::compile_diagnostics(&Self::markup_world(markup))
+    }
+    
+    /// Collect diagnostics for a memo form: schema and Delta problems first,
+    /// then Typst errors from compiling it with the memo-loader template
+    #[instrument(skip_all, fields(json_len = json_input.len()))]
+    pub fn diagnose_form(json_input: &str) -> Vec<Diagnostic> {
+        let (form_source, mut found) = diagnostics::check_form(json_input);
+        if !found.is_empty() {
+            return found;
+        }
+        
+        let world = form_processor::preprocess_form_json(json_input)
+            .map_err(|e| TypstWrapperError::Validation(e.to_string()))
+            .and_then(Self::form_world);
+        match world {
+            Ok(world) => found = Self::compile_diagnostics(&world),
+            Err(e) => {
+                let form_start = diagnostics::source_range(&form_source, 0..0);
+                found.push(Diagnostic::error(form_start, DiagnosticSource::Schema, e.to_string()));
+            }
+        }
+        found
+    }
+    
+    /// Compile a world and convert its errors and warnings to diagnostics
+    fn compile_diagnostics(world: &TypstWorld) -> Vec<Diagnostic> {
+        let Warned { output, mut warnings } = typst::compile::<PagedDocument>(world);
+        warnings.extend(world.package_warnings());
+        if let Ok(document) = &output {
+            warnings.extend(alt_text::missing_alt_text(document));
+        }
+        let errors = output.err().unwrap_or_default();
+        Self::to_diagnostics(world, &errors, &warnings)
+    }
+    
+    /// Convert Typst errors and warnings from compiling `world` to diagnostics
+    ///
+    /// Each diagnostic is located at its span when that lies in the main file,
+    /// otherwise at the innermost call in the main file that led to it. Form
+    /// diagnostics are not located in the form, so they are reported at its
+    /// start with the template location as related information.
+    fn to_diagnostics(
+        world: &TypstWorld,
+        errors: &[SourceDiagnostic],
+        warnings: &[SourceDiagnostic],
+    ) -> Vec<Diagnostic> {
+        let main_path = world.main_path;
+        let main = world.main();
+        let main_source = world.source(main).expect("main source is always present");
+        let locate = |span: Span| -> Option<(FileId, Source, Range<usize>)> {
+            let id = span.id()?;
+            let source = world.source(id).ok()?;
+            let bytes = source.range(span)?;
+            Some((id, source, bytes))
+        };
+        let path_of = |id: FileId| match id.package() {
+            _ if id == main => main_path.to_string(),
+            Some(spec) => format!("{}{}", spec, id.vpath().as_rooted_path().display()),
+            None => id.vpath().as_rootless_path().display().to_string(),
+        };
+
+        errors
+            .iter()
+            .chain(warnings.iter())
+            .map(|diag| {
+                let range = std::iter::once(diag.span)
+                    .chain(diag.trace.iter().map(|point| point.span))
+                    .find_map(|span| locate(span).filter(|(id, ..)| *id == main))
+                    .map_or(0..0, |(_, _, bytes)| bytes);
 
+                let mut related = Vec::new();
+                if let Some((id, source, bytes)) = locate(diag.span).filter(|(id, ..)| *id != main) {
+                    related.push(RelatedInformation {
+                        message: "error raised here".to_string(),
+                        path: path_of(id),
+                        range: diagnostics::source_range(&source, bytes),
+                    });
+                }
+                related.extend(diag.trace.iter().filter_map(|point| {
+                    let (id, source, bytes) = locate(point.span)?;
+                    Some(RelatedInformation {
+                        message: point.v.to_string(),
+                        path: path_of(id),
+                        range: diagnostics::source_range(&source, bytes),
+                    })
+                }));
+
+                let mut range = diagnostics::source_range(&main_source, range);
+                if main_path == MEMO_TEMPLATE_PATH {
+                    let form_start = diagnostics::source_range(&main_source, 0..0);
+                    related.insert(0, RelatedInformation {
+                        message: "raised by the memo template".to_string(),
+                        path: MEMO_TEMPLATE_PATH.to_string(),
+                        range: std::mem::replace(&mut range, form_start),
+                    });
+                }
 
+                Diagnostic {
+                    range,
+                    severity: match diag.severity {
+                        typst::diag::Severity::Error => Severity::Error,
+                        typst::diag::Severity::Warning => Severity::Warning,
+                    },
+                    source: DiagnosticSource::Typst,
+                    message: diag.message.to_string(),
+                    hints: diag.hints.iter().map(ToString::to_string).collect(),
+                    related,
+                }
+            })
+            .collect()
+    }
+    
+    /// Do the one-time work of the first render up front: parse the embedded
+    /// fonts, build the standard library, compile the form schema and compile
+    /// a minimal memo so the template and its package are parsed and cached
+    #[instrument]
+    pub fn prewarm() -> Result<(), TypstWrapperError> {
+        LazyLock::force(&FONT_BOOK);
+        LazyLock::force(&FONTS);
+        LazyLock::force(&LIBRARY);
         
-        // Use unique identifiers to ensure file IDs don't collide between renders
-        // In WASM environments, SystemTime is not available, so we use a simple hash
+        let processed_input = form_processor::validate_and_preprocess_form_json(PREWARM_FORM)
+            .map_err(|e| TypstWrapperError::Validation(format!("{}", e)))?;
+        Self::compile(&Self::form_world(processed_input)?, &mut ())?;
+        info!("render engine is warm");
+        Ok(())
+    }
+    
+    /// Check that a minimal memo compiles with a memo template and partial
+    /// overrides
+    pub(crate) fn check_memo_template(template: Option<PackageFiles>, partials: TemplatePartials) -> Result<(), TypstWrapperError> {
+        let world = Self::template_world(Self::validate_form(PREWARM_FORM)?, template, partials)?;
+        Self::compile(&world, &mut ())?;
+        Ok(())
+    }
+    
+    /// Compile a minimal memo and return every package source parsed so far,
+    /// which includes all the sources the memo template needs
+    pub(crate) fn template_sources() -> Result<Vec<Source>, TypstWrapperError> {
+        Self::compile(&Self::form_world(Self::validate_form(PREWARM_FORM)?)?, &mut ())?;
+        Ok(engine::current().package_sources().values().cloned().collect())
+    }
+    
+    /// Add a parsed package source to the cache of the current engine
+    pub(crate) fn cache_package_source(source: Source) {
+        engine::current().cache_package_source(source);
+    }
+    
+    /// Build a world whose main file is the given markup
+    pub(crate) fn markup_world(markup: &str) -> TypstWorld {
+        let mut world = TypstWorld::new("main.typ");
+        
+        let source = Source::new(
+            FileId::new(None, VirtualPath::new("main.typ")),
+            assets::rewrite_latest_imports(markup),
+        );
+        world.insert_source(source);
+        world
+    }
+    
+    /// Build a world that renders preprocessed form JSON with the memo
+    /// template of the current engine
+    pub(crate) fn form_world(processed_input: String) -> Result<TypstWorld, TypstWrapperError> {
+        let engine = engine::current();
+        Self::template_world(processed_input, engine.memo_template(), engine.template_partials())
+    }
+    
+    /// Build a world that renders preprocessed form JSON with a custom memo
+    /// template (see [`template`](crate::template)), or the embedded
+    /// memo-loader template, and the given partial overrides
+    fn template_world(
+        processed_input: String,
+        template: Option<PackageFiles>,
+        partials: TemplatePartials,
+    ) -> Result<TypstWorld, TypstWrapperError> {
+        // Create a completely fresh world for each render to avoid state pollution
+        let mut world = TypstWorld::new(MEMO_TEMPLATE_PATH);
+
+        let processed_input = world.take_delta_images(processed_input);
+
+        // Name the files after a hash of the input so file IDs don't collide
+        // between different inputs. A content hash rather than a timestamp
+        // keeps renders deterministic and avoids `SystemTime`, which some
+        // runtimes (wasm32, Cloudflare Workers) do not provide.
         let timestamp = {
-            #[cfg(target_arch = "wasm32")]
-            {
-                // Use a deterministic hash instead of random DefaultHasher
-                // Simple FNV-1a hash implementation for deterministic results
-                let mut hash: u64 = 0xcbf29ce484222325; // FNV offset basis
-                for byte in json_input.bytes() {
-                    hash ^= byte as u64;
-                    hash = hash.wrapping_mul(0x100000001b3); // FNV prime
-                }
-                // Add length for additional uniqueness
-                hash ^= json_input.len() as u64;
-                hash = hash.wrapping_mul(0x100000001b3);
-                hash as u128
-            }
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_nanos()
+            // Simple FNV-1a hash implementation for deterministic results
+            let mut hash: u64 = 0xcbf29ce484222325; // FNV offset basis
+            for byte in processed_input.bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3); // FNV prime
             }
+            // Add length for additional uniqueness
+            hash ^= processed_input.len() as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+            hash
         };
         
         // Use unique filenames but keep them in root to preserve asset paths
@@ -190,13 +757,9 @@ impl TypstWrapper {
     let json_source = Source::new(json_file_id, processed_input);
         world.insert_source(json_source);
         
-        // Load the memo-loader main template
-        let memo_loader_asset = assets::load_string_asset("memo-loader-main")
-            .ok_or_else(|| TypstWrapperError::FileNotFound("memo-loader main template not found".to_string()))?;
-        
         // Modify the template to reference the unique JSON filename
-        let template_content = memo_loader_asset.content.replace(
-            "#let input = json(\"input.json\")",
+        let template_content = Self::main_template(template.as_ref())?.replace(
+            INPUT_LINE,
             &format!("#let input = json(\"{}\")", json_filename)
         );
         
@@ -205,53 +768,408 @@ impl TypstWrapper {
         let memo_loader_file_id = FileId::new(None, VirtualPath::new(&main_filename));
         let memo_loader_source = Source::new(memo_loader_file_id, assets::rewrite_latest_imports(&template_content));
         world.insert_source(memo_loader_source);
+        world.template_files = template;
+        world.template_partials = Some(partials);
         
-        Self::render_file(world, config)
+        Ok(world)
     }
     
-    /// Internal function to render a prepared world with sources
-    fn render_file(
-        world: TypstWorld,
-        config: Option<RenderConfig>,
-    ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
-        let config = config.unwrap_or_default();
-        
-        // Compile the document
-        let document = match typst::compile::<PagedDocument>(&world).output {
-            Ok(doc) => doc,
-            Err(errors) => {
+    /// The main file of a custom memo template, or of the embedded
+    /// memo-loader template
+    fn main_template(template: Option<&PackageFiles>) -> Result<&str, TypstWrapperError> {
+        match template {
+            Some(files) => files
+                .get(template::MAIN_FILE)
+                .and_then(|main| std::str::from_utf8(main).ok())
+                .ok_or_else(|| TypstWrapperError::FileNotFound("custom memo template has no main.typ".to_string())),
+            None => Ok(assets::load_string_asset("memo-loader-main")
+                .ok_or_else(|| TypstWrapperError::FileNotFound("memo-loader main template not found".to_string()))?
+                .content),
+        }
+    }
+    
+    /// The Typst source a form renders as: the main file of the memo
+    /// template with the preprocessed form inlined in place of `input.json`
+    /// and the partials it imports inlined in place of their imports
+    #[instrument(skip_all, fields(json_len = json_input.len()))]
+    pub fn generate_typst(json_input: &str) -> Result<String, TypstWrapperError> {
+        let processed_input = Self::validate_form(json_input)?;
+        let engine = engine::current();
+        let template = engine.memo_template();
+        let mut source = Self::main_template(template.as_ref())?.replace(
+            INPUT_LINE,
+            &format!("#let input = json(bytes({}))", watermark::typst_string(&processed_input)),
+        );
+        let partials = engine.template_partials();
+        for partial in Partial::ALL {
+            let import = format!("#import \"{}\"", partial.path());
+            let Some(start) = source.find(&import) else {
+                continue;
+            };
+            let end = source[start..].find('\n').map_or(source.len(), |end| start + end);
+            let partial_source = template::partial_source(partial, &partials, template.as_ref())
+                .map_err(|e| TypstWrapperError::FileNotFound(format!("{}: {}", partial.path(), e)))?;
+            source.replace_range(start..end, partial_source.trim_end());
+        }
+        Ok(assets::rewrite_latest_imports(&source))
+    }
+    
+    /// Register a full-page letterhead background image that forms can select
+    /// by name through the `letterhead-background` field
+    pub fn register_letterhead(name: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+        Self::check_letterhead(name, &data)?;
+        let size = data.len();
+        assets::register_letterhead(name, data)?;
+        info!(name, size, "registered letterhead background");
+        Ok(())
+    }
+    
+    /// Register an image or other file that markup and form bodies can read
+    /// at `path`
+    pub fn register_asset(path: &str, data: Vec<u8>) -> Result<(), TypstWrapperError> {
+        let path = Self::check_asset_path(path)?;
+        let size = data.len();
+        assets::register_asset(&path, data)?;
+        info!(path, size, "registered asset");
+        Ok(())
+    }
+    
+    /// The rootless form of a registered file's `path`, if it is usable
+    pub(crate) fn check_asset_path(path: &str) -> Result<String, TypstWrapperError> {
+        let rootless = path.strip_prefix('/').unwrap_or(path);
+        let valid = rootless.split('/').all(|component| {
+            !component.is_empty()
+                && component != "."
+                && component != ".."
+                && component.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+        if !valid {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid asset path '{}': use '/'-separated names of letters, digits, '-', '_' or '.'",
+                path
+            )));
+        }
+        if rootless.starts_with(&format!("{}/", assets::LETTERHEAD_DIR)) {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid asset path '{}': register letterhead backgrounds with register_letterhead",
+                path
+            )));
+        }
+        Ok(rootless.to_string())
+    }
+    
+    /// Check that a letterhead background has a usable name and is an image
+    pub(crate) fn check_letterhead(name: &str, data: &[u8]) -> Result<(), TypstWrapperError> {
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid letterhead name '{}': use letters, digits, '-', '_' or '.'",
+                name
+            )));
+        }
+        if ImageFormat::detect(data).is_none() {
+            return Err(TypstWrapperError::Validation(format!(
+                "Letterhead '{}' is not a supported image (PNG, JPEG, GIF, WebP or SVG)",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Compile Typst markup without exporting it
+    pub(crate) fn compile_markup(markup: &str) -> Result<PagedDocument, TypstWrapperError> {
+        Self::compile(&Self::markup_world(markup), &mut ())
+    }
+    
+    /// Compile a memo form with the memo-loader template without exporting it
+    pub(crate) fn compile_form(json_input: &str) -> Result<PagedDocument, TypstWrapperError> {
+        Self::compile(&Self::form_world(Self::validate_form(json_input)?)?, &mut ())
+    }
+    
+    /// Compile a prepared world into a paged document, passing any errors
+    /// and warnings to `observer`
+    #[instrument(level = "debug", skip_all)]
+    pub(crate) fn compile(world: &TypstWorld, observer: &mut dyn RenderObserver) -> Result<PagedDocument, TypstWrapperError> {
+        let Warned { output, mut warnings } = typst::compile::<PagedDocument>(world);
+        warnings.extend(world.package_warnings());
+        if let Ok(document) = &output {
+            warnings.extend(alt_text::missing_alt_text(document));
+        }
+        for warning in &warnings {
+            warn!(warning = %warning.message, "typst warning");
+        }
+        let errors = output.as_ref().err().map_or(&[][..], |errors| &errors[..]);
+        if !errors.is_empty() || !warnings.is_empty() {
+            observer.on_diagnostics(&Self::to_diagnostics(world, errors, &warnings));
+        }
+        output
+            .inspect(|document| debug!(pages = document.pages.len(), "compiled"))
+            .map_err(|errors| {
+                debug!(errors = errors.len(), "compilation failed");
                 let error_msg = errors
-                    .into_iter()
+                    .iter()
                     .map(|e| format!("{:?}", e))
                     .collect::<Vec<_>>()
                     .join("; ");
-                return Err(TypstWrapperError::Compilation(error_msg));
-            }
-        };
-        
-        // Generate output based on format
-        match config.format {
-            OutputFormat::Svg => {
-                // Render all pages as SVG
-                let mut svg_pages = Vec::new();
-                for page in &document.pages {
-                    let svg = typst_svg::svg(page);
-                    svg_pages.push(svg.into_bytes());
+                let mut error = TypstWrapperError::Compilation(error_msg);
+                let attributed = attribution::attribute(world, &errors);
+                if let Some(excerpt) = excerpt::form_excerpt(world, &errors, attributed.as_ref().map(|(field, _)| field.as_str())) {
+                    error = TypstWrapperError::Excerpt { excerpt, error: Box::new(error) };
                 }
-                
-                if svg_pages.is_empty() {
-                    Err(TypstWrapperError::Compilation("No pages to render".to_string()))
-                } else {
-                    Ok(svg_pages)
+                match attributed {
+                    Some((field, op)) => TypstWrapperError::Field { field, op, error: Box::new(error) },
+                    None => error,
                 }
+            })
+    }
+    
+    /// Render `world` with `config` and keep it in `warm` for the next
+    /// render, whether or not this one succeeds
+    fn render_kept(
+        warm: &mut Option<TypstWorld>,
+        world: TypstWorld,
+        svgs: Option<&SvgCache>,
+        config: &RenderConfig,
+        started: Instant,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        let world = warm.insert(world);
+        world.begin_render();
+        Self::render_file(world, config, svgs, started, observer)
+    }
+    
+    /// Internal function to render a prepared world with sources
+    ///
+    /// `started` marks the beginning of the render; everything up to this
+    /// call counts as parsing in the metrics.
+    fn render_file(
+        world: &mut TypstWorld,
+        config: &RenderConfig,
+        svgs: Option<&SvgCache>,
+        started: Instant,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<RenderOutput, TypstWrapperError> {
+        svg_scale::validate(config.scale)?;
+        config.id_prefix.as_deref().map(svg_ids::validate).transpose()?;
+        world.configure(config)?;
+        let parse_ms = metrics::elapsed_ms(started);
+        
+        // Compile the document
+        if observer.on_compile_start().is_break() {
+            return Err(TypstWrapperError::Cancelled);
+        }
+        let compile_start = Instant::now();
+        let mut document = Self::compile(world, observer)?;
+        if config.header.is_some() || config.footer.is_some() {
+            running::apply(&mut document, config)?;
+        }
+        if let Some(watermark) = &config.watermark {
+            watermark::apply(&mut document, watermark, config.print.is_some())?;
+        }
+        if config.duplex {
+            duplex::insert_blank_pages(&mut document);
+        }
+        color_mode::apply(&mut document, config.color_mode)?;
+        if let Some(metadata) = &config.metadata {
+            metadata::apply(&mut document, metadata)?;
+        }
+        print::prepare(&mut document, config)?;
+        annotations::check(&document, &config.annotations)?;
+        let compile_ms = metrics::elapsed_ms(compile_start);
+        // Sessions compare the pages of their renders
+        if svgs.is_some() {
+            world.page_hashes = document.pages.iter().map(svg_cache::page_hash).collect();
+        }
+        
+        let export_start = Instant::now();
+        let pages = Self::export(&document, config, world.source_form.as_deref(), svgs, observer)?;
+        let export_ms = metrics::elapsed_ms(export_start);
+        
+        let metrics = RenderMetrics {
+            parse_ms,
+            compile_ms,
+            export_ms,
+            total_ms: metrics::elapsed_ms(started),
+            pages: document.pages.len(),
+            bytes: pages.iter().map(Vec::len).sum(),
+            cache_hits: world.cache_hits.load(Ordering::Relaxed),
+            cache_misses: world.cache_misses.load(Ordering::Relaxed),
+            from_cache: false,
+        };
+        debug!(?metrics, "render finished");
+        metrics::report(&metrics);
+        Ok(RenderOutput {
+            pages,
+            page_sizes: metrics::page_sizes(&document),
+            font_substitutions: world.font_substitutions(&document),
+            metrics,
+        })
+    }
+    
+    /// Export a compiled document in the configured format, reporting each
+    /// page to `observer`. PDF output of forms rendered with
+    /// `config.embed_form` gets their `form` JSON attached. SVG pages come
+    /// from `svgs` where they did not change.
+    pub(crate) fn export(
+        document: &PagedDocument,
+        config: &RenderConfig,
+        form: Option<&str>,
+        svgs: Option<&SvgCache>,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        let _span = debug_span!("export", format = ?config.format).entered();
+        let range = Self::exported_pages(document, config)?;
+        match config.format {
+            OutputFormat::Svg if config.stack_pages => {
+                let svg_pages = Self::export_svg(document, range.clone(), config, svgs, observer)?;
+                let sizes = &metrics::page_sizes(document)[range.clone()];
+                let id_prefix = config.id_prefix.as_deref().unwrap_or_default();
+                Ok(vec![svg_stack::stack(&svg_pages, sizes, range.start, config.scale, id_prefix)])
+            }
+            OutputFormat::Svg => Self::export_svg(document, range, config, svgs, observer),
+            OutputFormat::SvgZip => {
+                let svg_pages = Self::export_svg(document, range.clone(), config, svgs, observer)?;
+                let sizes = metrics::page_sizes(document)[range.clone()].iter().map(|size| (size.width, size.height)).collect::<Vec<_>>();
+                let text = config.text_layout.then(|| text_layout::document_text(document).drain(range).collect::<Vec<_>>());
+                Ok(vec![archive::svg_zip(&svg_pages, sizes, text.as_deref())?])
             }
             OutputFormat::Pdf => {
-                let pdf = typst_pdf::pdf(&document, &typst_pdf::PdfOptions::default())
+                // PDF/X builds on the embedded fonts and output intent of PDF/A
+                let standards = match config.print {
+                    Some(_) => typst_pdf::PdfStandards::new(&[typst_pdf::PdfStandard::A_2b]),
+                    None => Ok(typst_pdf::PdfStandards::default()),
+                };
+                // Page ranges count from 1 and include their end
+                let page_ranges = config.page_range.as_ref().map(|_| {
+                    typst::layout::PageRanges::new(vec![NonZeroUsize::new(range.start + 1)..=NonZeroUsize::new(range.end)])
+                });
+                let options = typst_pdf::PdfOptions {
+                    standards: standards.map_err(|e| TypstWrapperError::OutputFormat(e.to_string()))?,
+                    timestamp: config.print.as_ref().and_then(|_| print::timestamp()),
+                    page_ranges,
+                    ..typst_pdf::PdfOptions::default()
+                };
+                let mut pdf = typst_pdf::pdf(document, &options)
                     .map_err(|e| TypstWrapperError::Compilation(format!("PDF generation failed: {:?}", e)))?;
+                pdf = if config.generate_outline {
+                    bookmarks::add_outline(document, pdf, range.clone())?
+                } else {
+                    bookmarks::remove_outline(pdf)?
+                };
+                pdf = annotations::add_to_pdf(document, pdf, &config.annotations, range.clone())?;
+                if config.form_fields {
+                    pdf = form_fields::add_fields(document, pdf, range)?;
+                }
+                if let Some(form) = form.filter(|_| config.embed_form) {
+                    pdf = embedded_form::attach(pdf, form)?;
+                }
+                if let Some(profile) = &config.print {
+                    pdf = print::finish_pdf(&pdf, profile)?;
+                }
+                if observer.on_page_exported(0, 1, &pdf).is_break() {
+                    return Err(TypstWrapperError::Cancelled);
+                }
                 Ok(vec![pdf])
             }
         }
     }
+    
+    /// Export page `index` as SVG, with its links unless they are turned off
+    /// and its annotations
+    pub(crate) fn page_svg(document: &PagedDocument, index: usize, config: &RenderConfig) -> Vec<u8> {
+        Self::page_svg_from(document, index, config, None)
+    }
+    
+    /// Export page `index` as SVG, taking its plain SVG from `svgs` if it
+    /// did not change
+    fn page_svg_from(document: &PagedDocument, index: usize, config: &RenderConfig, svgs: Option<&SvgCache>) -> Vec<u8> {
+        let page = &document.pages[index];
+        let svg = match svgs {
+            Some(svgs) => svgs.svg(page),
+            None => typst_svg::svg(page),
+        };
+        let mut svg = alt_text::svg_with_alt_text(svg, page);
+        if config.svg_text == SvgTextMode::Selectable {
+            svg = svg_text::svg_with_text(svg, page);
+        }
+        if config.svg_links {
+            svg = links::svg_with_links(svg, page, &document.introspector);
+        }
+        if !config.annotations.is_empty() {
+            svg = annotations::svg_with_annotations(svg, document, index, &config.annotations);
+        }
+        if let Some(prefix) = &config.id_prefix {
+            svg = svg_ids::prefixed(svg, prefix);
+        }
+        svg_scale::scaled(svg, page.frame.size(), config.scale).into_bytes()
+    }
+    
+    /// Export every page as SVG, reporting each page to `observer`
+    fn export_svg(
+        document: &PagedDocument,
+        range: Range<usize>,
+        config: &RenderConfig,
+        svgs: Option<&SvgCache>,
+        observer: &mut dyn RenderObserver,
+    ) -> Result<Vec<Vec<u8>>, TypstWrapperError> {
+        #[cfg(feature = "parallel")]
+        let svg_pages = {
+            // Pages are reported to the observer once all are exported
+            use rayon::prelude::*;
+            let svg_pages: Vec<Vec<u8>> = range
+                .into_par_iter()
+                .map(|index| Self::page_svg_from(document, index, config, svgs))
+                .collect();
+            for (index, svg) in svg_pages.iter().enumerate() {
+                if observer.on_page_exported(index, svg_pages.len(), svg).is_break() {
+                    return Err(TypstWrapperError::Cancelled);
+                }
+            }
+            svg_pages
+        };
+        #[cfg(not(feature = "parallel"))]
+        let svg_pages = {
+            let mut svg_pages = Vec::new();
+            let count = range.len();
+            for (index, page) in range.enumerate() {
+                let svg = Self::page_svg_from(document, page, config, svgs);
+                if observer.on_page_exported(index, count, &svg).is_break() {
+                    return Err(TypstWrapperError::Cancelled);
+                }
+                svg_pages.push(svg);
+            }
+            svg_pages
+        };
+        
+        if svg_pages.is_empty() {
+            Err(TypstWrapperError::Compilation("No pages to render".to_string()))
+        } else {
+            Ok(svg_pages)
+        }
+    }
+
+    /// The pages of `document` to export: `config.page_range`, checked
+    /// against the document and the output format, or every page
+    pub(crate) fn exported_pages(document: &PagedDocument, config: &RenderConfig) -> Result<Range<usize>, TypstWrapperError> {
+        if config.stack_pages && !matches!(config.format, OutputFormat::Svg) {
+            return Err(TypstWrapperError::Validation("Stacking pages needs SVG output".to_string()));
+        }
+        let Some(range) = config.page_range.clone() else {
+            return Ok(0..document.pages.len());
+        };
+        if range.is_empty() || range.end > document.pages.len() {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid page range {}..{}: the document has {} pages",
+                range.start,
+                range.end,
+                document.pages.len()
+            )));
+        }
+        Ok(range)
+    }
 }
 
 impl Default for TypstWrapper {
@@ -261,18 +1179,86 @@ impl Default for TypstWrapper {
 }
 
 /// Internal Typst world implementation
-struct TypstWorld {
-    library: LazyHash<Library>,
+pub(crate) struct TypstWorld {
     sources: HashMap<FileId, Source>,
-    package_sources: HashMap<FileId, Source>,
+    /// Name of the main file in diagnostics
+    main_path: &'static str,
+    /// The engine the world was created for, which keeps the sources it
+    /// parses
+    engine: RenderEngine,
+    /// The engine's letterheads, files, local packages and parsed package
+    /// sources when the world was created, read without locking
+    letterheads: Letterheads,
+    assets: RegisteredAssets,
+    local_packages: LocalPackages,
+    package_sources: PackageSources,
+    /// Files of the custom memo template a form world renders with, served
+    /// at their paths
+    template_files: Option<PackageFiles>,
+    /// Partial overrides of a form world; form worlds serve the embedded
+    /// partials that are not overridden
+    template_partials: Option<TemplatePartials>,
+    /// The embedded and registered fonts when the world was created, if
+    /// any fonts were registered
+    font_set: Option<Arc<FontSet>>,
+    /// Font book with the configured family substitutions, if any, and the
+    /// substitutions it was built for
+    substituted_book: Option<SubstitutedBook>,
+    substitutions: BTreeMap<String, String>,
+    /// Images a form's Delta body inlines as data URLs, by rootless path
+    delta_images: HashMap<String, Bytes>,
+    /// Whether only embedded package versions may be imported
+    strict_packages: bool,
+    /// Date `datetime.today()` returns instead of the current date
+    today: Option<Datetime>,
+    /// Whether the render asked for the current date at an explicit offset
+    /// from UTC, which changes at another hour than the cache keys' date
+    today_at_offset: AtomicBool,
+    /// Package versions imported in place of the requested ones
+    negotiated_packages: Mutex<Vec<(PackageSpec, PackageVersion)>>,
+    /// Files the host's resolver was asked for, and the sources parsed
+    /// from them
+    resolved_files: Mutex<HashMap<FileId, Option<Bytes>>>,
+    resolved_sources: Mutex<HashMap<FileId, Source>>,
+    /// Hashes of the pages of the world's last render in a
+    /// [`RenderSession`](crate::RenderSession)
+    pub(crate) page_hashes: Vec<u128>,
+    /// Form JSON of a form world to attach to PDF output, see
+    /// [`RenderConfig::embed_form`]
+    pub(crate) source_form: Option<String>,
+    /// Package source lookups served from the engine's parsed sources
+    pub(crate) cache_hits: AtomicUsize,
+    /// Package source lookups that parsed the source
+    pub(crate) cache_misses: AtomicUsize,
 }
 
 impl TypstWorld {
-    fn new() -> Self {
+    fn new(main_path: &'static str) -> Self {
+        let engine = engine::current();
         Self {
-            library: LazyHash::new(Library::default()),
             sources: HashMap::new(),
-            package_sources: HashMap::new(),
+            main_path,
+            letterheads: engine.letterheads(),
+            assets: engine.assets(),
+            local_packages: engine.local_packages(),
+            package_sources: engine.package_sources(),
+            font_set: engine.font_set(),
+            engine,
+            template_files: None,
+            template_partials: None,
+            substituted_book: None,
+            substitutions: BTreeMap::new(),
+            delta_images: HashMap::new(),
+            strict_packages: false,
+            today: None,
+            today_at_offset: AtomicBool::new(false),
+            negotiated_packages: Mutex::new(Vec::new()),
+            resolved_files: Mutex::new(HashMap::new()),
+            resolved_sources: Mutex::new(HashMap::new()),
+            page_hashes: Vec::new(),
+            source_form: None,
+            cache_hits: AtomicUsize::new(0),
+            cache_misses: AtomicUsize::new(0),
         }
     }
     
@@ -280,62 +1266,217 @@ impl TypstWorld {
         self.sources.insert(source.id(), source);
     }
     
-    fn resolve_asset(&self, path: &str) -> Option<&'static [u8]> {
-        assets::resolve_binary_asset(path)
+    /// Serve the images of a Delta body that `processed_input` carries,
+    /// returning the JSON without them
+    fn take_delta_images(&mut self, processed_input: String) -> String {
+        let (processed_input, images) = form_processor::take_delta_images(processed_input);
+        self.delta_images = images.into_iter().map(|(path, data)| (path, Bytes::new(data))).collect();
+        processed_input
     }
     
-    fn resolve_package_file(&self, spec: &PackageSpec, path: &str) -> Option<&'static str> {
-        assets::resolve_package_file(spec, path)
+    /// Replace the markup of a markup world. Typst reparses only the edited
+    /// part, so later compiles reuse the work on the rest.
+    pub(crate) fn replace_main(&mut self, markup: &str) {
+        let main = self.main();
+        if let Some(source) = self.sources.get_mut(&main) {
+            source.replace(&assets::rewrite_latest_imports(markup));
+        }
     }
-}
-
-impl World for TypstWorld {
-    fn library(&self) -> &LazyHash<Library> {
-        &self.library
+    
+    /// Replace the preprocessed form JSON of a form world, keeping its
+    /// template sources
+    pub(crate) fn replace_form_input(&mut self, processed_input: String) {
+        let processed_input = self.take_delta_images(processed_input);
+        let input = self
+            .sources
+            .values_mut()
+            .find(|source| source.id().vpath().as_rootless_path().extension().is_some_and(|ext| ext == "json"));
+        if let Some(source) = input {
+            source.replace(&processed_input);
+        }
     }
     
-    fn book(&self) -> &LazyHash<FontBook> {
-        &FONT_BOOK
+    /// Forget what the previous render of a kept world looked up: package
+    /// version warnings, resolver answers and cache counts
+    pub(crate) fn begin_render(&mut self) {
+        self.negotiated_packages.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.resolved_files.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        self.resolved_sources.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+        *self.cache_hits.get_mut() = 0;
+        *self.cache_misses.get_mut() = 0;
+        *self.today_at_offset.get_mut() = false;
+    }
+
+    /// Whether the last render dated itself at an explicit offset from UTC,
+    /// so its output must not be repeated under a key of the local date
+    pub(crate) fn dated_at_offset(&self) -> bool {
+        self.today_at_offset.load(Ordering::Relaxed)
     }
     
-    fn main(&self) -> FileId {
-        // Find the main Typst file (not JSON files)
+    /// The preprocessed form JSON of a form world
+    pub(crate) fn form_input(&self) -> Option<&str> {
+        if self.main_path != MEMO_TEMPLATE_PATH {
+            return None;
+        }
         self.sources
             .values()
-            .find(|source| {
-                source.id().package().is_none() && 
-                source.id().vpath().as_rootless_path().extension()
-                    .map_or(false, |ext| ext == "typ")
+            .find(|source| source.id().vpath().as_rootless_path().extension().is_some_and(|ext| ext == "json"))
+            .map(|source| source.text())
+    }
+    
+    /// Apply the parts of `config` that affect compilation: font
+    /// substitutions (see [`fonts`](crate::fonts)), strict package imports
+    /// and the date of today
+    pub(crate) fn configure(&mut self, config: &RenderConfig) -> Result<(), TypstWrapperError> {
+        if self.substituted_book.is_none() || self.substitutions != config.font_substitutions {
+            self.substituted_book = match config.font_substitutions.is_empty() {
+                true => None,
+                false => Some(SubstitutedBook::new(self.fonts(), &config.font_substitutions)?),
+            };
+            self.substitutions.clone_from(&config.font_substitutions);
+        }
+        self.strict_packages = config.strict_packages;
+        self.today = config.today.as_deref().map(today::date).transpose()?;
+        Ok(())
+    }
+    
+    /// The font substitutions the text of `document`, compiled in this
+    /// world, uses
+    pub(crate) fn font_substitutions(&self, document: &PagedDocument) -> Vec<FontSubstitution> {
+        self.substituted_book
+            .as_ref()
+            .map(|book| book.report(document, self.fonts()))
+            .unwrap_or_default()
+    }
+    
+    /// The fonts of the world's book: the embedded ones, followed by the
+    /// registered ones
+    fn fonts(&self) -> &[Font] {
+        match &self.font_set {
+            Some(font_set) => &font_set.fonts,
+            None => &FONTS,
+        }
+    }
+    
+    fn resolve_asset(&self, path: &str) -> Option<&'static [u8]> {
+        assets::resolve_binary_asset(path)
+    }
+    
+    /// A file of a registered `@local` package
+    fn local_package_file(&self, spec: &PackageSpec, path: &str) -> FileResult<Bytes> {
+        let files = self.local_packages.get(spec).ok_or_else(|| PackageError::NotFound(spec.clone()))?;
+        files.get(path).cloned().ok_or_else(|| FileError::NotFound(path.into()))
+    }
+    
+    /// The embedded version to serve for the package of `spec`, noting it
+    /// when it is not the requested one
+    fn package_version(&self, spec: &PackageSpec) -> FileResult<PackageVersion> {
+        let latest = assets::latest_package_version(spec).ok_or_else(|| PackageError::NotFound(spec.clone()))?;
+        match assets::compatible_package_version(spec) {
+            Some(version) if version == spec.version => Ok(version),
+            Some(version) if !self.strict_packages => {
+                let mut negotiated = self.negotiated_packages.lock().unwrap_or_else(PoisonError::into_inner);
+                if !negotiated.iter().any(|(requested, _)| requested == spec) {
+                    negotiated.push((spec.clone(), version));
+                }
+                Ok(version)
+            }
+            _ => Err(PackageError::VersionNotFound(spec.clone(), latest).into()),
+        }
+    }
+    
+    /// A file of the package of `spec`, from the embedded version serving it
+    ///
+    /// The manifest of a compatible version stands in with the requested
+    /// version, which Typst checks it for.
+    fn resolve_package_file(&self, spec: &PackageSpec, path: &str) -> FileResult<Cow<'static, str>> {
+        let version = self.package_version(spec)?;
+        let embedded = PackageSpec { version, ..spec.clone() };
+        let content = assets::resolve_package_file(&embedded, path)
+            .ok_or_else(|| FileError::NotFound(path.into()))?;
+        Ok(match path {
+            "typst.toml" if version != spec.version => Cow::Owned(assets::manifest_with_version(content, spec.version)),
+            _ => Cow::Borrowed(content),
+        })
+    }
+    
+    /// A warning for each package import served by a compatible version,
+    /// located at the import in the main file when it is written there
+    fn package_warnings(&self) -> Vec<SourceDiagnostic> {
+        let main = self.sources.get(&self.main());
+        let negotiated = self.negotiated_packages.lock().unwrap_or_else(PoisonError::into_inner);
+        negotiated
+            .iter()
+            .map(|(spec, version)| {
+                let span = main
+                    .and_then(|source| {
+                        let offset = source.text().find(&spec.to_string())?;
+                        LinkedNode::new(source.root()).leaf_at(offset + 1, Side::After)
+                    })
+                    .map_or(Span::detached(), |node| node.span());
+                warn!(package = %spec, version = %version, "package version not embedded, using a compatible one");
+                SourceDiagnostic::warning(span, format!("package {} is not embedded, using compatible version {}", spec, version))
+                    .with_hint("set strict package imports to fail instead")
             })
-            .unwrap()
-            .id()
+            .collect()
     }
     
-    fn source(&self, id: FileId) -> FileResult<Source> {
+    /// The source of `id` from the world's sources, the template, packages
+    /// and registrations
+    fn lookup_source(&self, id: FileId) -> FileResult<Source> {
         // Check main sources first
         if let Some(source) = self.sources.get(&id) {
             return Ok(source.clone());
         }
         
-        // Check package sources
-        if let Some(source) = self.package_sources.get(&id) {
-            return Ok(source.clone());
+        // Partials of form worlds, then companion sources of a custom memo
+        // template
+        if id.package().is_none() {
+            let path = id.vpath().as_rootless_path().to_string_lossy();
+            if let (Some(overrides), Some(partial)) = (&self.template_partials, Partial::from_path(&path)) {
+                return Ok(Source::new(id, template::partial_source(partial, overrides, self.template_files.as_ref())?));
+            }
+            if let Some(data) = self.template_files.as_ref().and_then(|files| files.get(path.as_ref())) {
+                let text = std::str::from_utf8(data).map_err(|_| FileError::InvalidUtf8)?;
+                return Ok(Source::new(id, text.to_string()));
+            }
+        }
+        
+        // Local packages are parsed anew, as they may be replaced
+        if let Some(spec) = id.package().filter(|spec| spec.namespace == packages::LOCAL_NAMESPACE) {
+            let path = id.vpath().as_rootless_path().to_string_lossy();
+            let data = self.local_package_file(spec, &path)?;
+            let text = std::str::from_utf8(&data).map_err(|_| FileError::InvalidUtf8)?;
+            return Ok(Source::new(id, text.to_string()));
+        }
+        
+        // Negotiate the package version before serving any of its sources
+        if let Some(spec) = id.package() {
+            self.package_version(spec)?;
+        }
+        
+        // Check package sources parsed by earlier renders, then those parsed
+        // since this world was created
+        if let Some(source) = self.package_sources.get(&id).cloned().or_else(|| self.engine.package_sources().get(&id).cloned()) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(source);
         }
         
         // Try to load package source
         if let Some(spec) = id.package() {
             let path = id.vpath().as_rootless_path().to_string_lossy();
-            if let Some(content) = self.resolve_package_file(&spec, &path) {
-                let source = Source::new(id, content.to_string());
-                // We can't mutate self here, but we can return the source
-                return Ok(source);
-            }
+            let source = Source::new(id, self.resolve_package_file(spec, &path)?.into_owned());
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+            self.engine.cache_package_source(source.clone());
+            return Ok(source);
         }
         
         Err(FileError::NotFound(id.vpath().as_rootless_path().to_path_buf()))
     }
     
-    fn file(&self, id: FileId) -> FileResult<Bytes> {
+    /// The file `id` from the world's sources, registrations, the template,
+    /// embedded assets and packages
+    fn lookup_file(&self, id: FileId) -> FileResult<Bytes> {
         let path = id.vpath().as_rootless_path().to_string_lossy();
         
         // Check if this is a virtual source file (like memo-loader/input.json)
@@ -343,36 +1484,121 @@ impl World for TypstWorld {
             return Ok(Bytes::new(source.text().to_string().into_bytes()));
         }
         
-        // Try to resolve as embedded asset
+        // Local package files are not embedded assets, whatever their path
+        if let Some(spec) = id.package().filter(|spec| spec.namespace == packages::LOCAL_NAMESPACE) {
+            return self.local_package_file(spec, &path);
+        }
+        
+        // Try runtime-registered letterhead backgrounds
+        let letterhead = path.strip_prefix(assets::LETTERHEAD_DIR).and_then(|name| name.strip_prefix('/'));
+        if let Some(data) = letterhead.and_then(|name| self.letterheads.get(name)) {
+            return Ok(data.clone());
+        }
+        
+        // Try companion files of a custom memo template, then embedded assets
+        let template_file = self.template_files.as_ref().filter(|_| id.package().is_none()).and_then(|files| files.get(path.as_ref()));
+        if let Some(data) = template_file {
+            return Ok(data.clone());
+        }
         if let Some(data) = self.resolve_asset(&path) {
             return Ok(Bytes::new(data));
         }
         
+        // Try runtime-registered files, which do not shadow the template's,
+        // then the images of a Delta body
+        let registered = self.assets.get(path.as_ref()).or_else(|| self.delta_images.get(path.as_ref()));
+        if let Some(data) = registered.filter(|_| id.package().is_none()) {
+            return Ok(data.clone());
+        }
+        
         // Try package files
         if let Some(spec) = id.package() {
-            if let Some(content) = self.resolve_package_file(&spec, &path) {
-                return Ok(Bytes::new(content.as_bytes()));
-            }
+            return Ok(match self.resolve_package_file(spec, &path)? {
+                Cow::Borrowed(content) => Bytes::new(content.as_bytes()),
+                Cow::Owned(content) => Bytes::from_string(content),
+            });
         }
         
         // File not found
         Err(FileError::NotFound(id.vpath().as_rootless_path().to_path_buf()))
     }
     
+    /// The file `id` from the host's [`FileResolver`](crate::FileResolver),
+    /// asked once per world
+    fn resolved_file(&self, id: FileId) -> Option<Bytes> {
+        let mut resolved = self.resolved_files.lock().unwrap_or_else(PoisonError::into_inner);
+        resolved.entry(id).or_insert_with(|| resolver::resolve(id)).clone()
+    }
+}
+
+impl World for TypstWorld {
+    fn library(&self) -> &LazyHash<Library> {
+        &LIBRARY
+    }
+    
+    fn book(&self) -> &LazyHash<FontBook> {
+        match &self.substituted_book {
+            Some(substituted) => &substituted.book,
+            None => match &self.font_set {
+                Some(font_set) => &font_set.book,
+                None => &FONT_BOOK,
+            },
+        }
+    }
+    
+    fn main(&self) -> FileId {
+        // Find the main Typst file (not JSON files)
+        self.sources
+            .values()
+            .find(|source| {
+                source.id().package().is_none() && 
+                source.id().vpath().as_rootless_path().extension()
+                    .is_some_and(|ext| ext == "typ")
+            })
+            .unwrap()
+            .id()
+    }
+    
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        self.lookup_source(id).or_else(|error| {
+            if let Some(source) = self.resolved_sources.lock().unwrap_or_else(PoisonError::into_inner).get(&id) {
+                return Ok(source.clone());
+            }
+            let data = self.resolved_file(id).ok_or(error)?;
+            let text = std::str::from_utf8(&data).map_err(|_| FileError::InvalidUtf8)?;
+            let source = Source::new(id, text.to_string());
+            self.resolved_sources.lock().unwrap_or_else(PoisonError::into_inner).insert(id, source.clone());
+            Ok(source)
+        })
+    }
+    
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.lookup_file(id).or_else(|error| self.resolved_file(id).ok_or(error))
+    }
+    
     fn font(&self, index: usize) -> Option<Font> {
-        FONTS.get(index).cloned()
+        let index = match &self.substituted_book {
+            Some(substituted) => substituted.font_index(index)?,
+            None => index,
+        };
+        self.fonts().get(index).cloned()
     }
     
-    fn today(&self, _offset: Option<i64>) -> Option<Datetime> {
-        // Return a fixed date since we don't need dynamic dates for this use case
-        // You can change this to the current date or make it configurable if needed
-        Datetime::from_ymd(2024, 1, 1)
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        // Typst may ask from another thread, outside the engine's scope
+        self.today.or_else(|| {
+            if offset.is_some() {
+                self.today_at_offset.store(true, Ordering::Relaxed);
+            }
+            today::date_at(self.engine.now()?, offset)
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::MemoBuilder;
     
     #[test]
     fn test_wrapper_creation() {
@@ -412,6 +1638,7 @@ mod tests {
         
         let config = RenderConfig {
             format: OutputFormat::Pdf,
+            ..Default::default()
         };
         
         let result = TypstWrapper::render_markup(markup, Some(config));
@@ -425,6 +1652,38 @@ mod tests {
         assert!(pages[0].starts_with(b"%PDF"));
     }
     
+    #[test]
+    fn test_page_range() {
+        let markup = "= One\n#pagebreak()\n= Two\n#pagebreak()\n= Three";
+        let all = TypstWrapper::render_markup(markup, None).unwrap();
+        let config = RenderConfig { page_range: Some(1..3), ..Default::default() };
+        let output = TypstWrapper::render_markup_with_metrics(markup, Some(config)).unwrap();
+        assert_eq!(output.pages, all[1..3]);
+        assert_eq!(output.page_sizes.len(), 3);
+
+        let zip = RenderConfig { format: OutputFormat::SvgZip, page_range: Some(0..1), ..Default::default() };
+        let archive = TypstWrapper::render_markup(markup, Some(zip)).unwrap().remove(0);
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        assert!(archive.by_name("page-001.svg").is_ok() && archive.by_name("page-002.svg").is_err());
+
+        // PDF output has the pages of the range, with bookmarks to them only
+        let outlined = "#metadata(\"First\") <bookmark>\n#pagebreak()\n#pagebreak()\n#metadata(\"Third\") <bookmark>";
+        let config = RenderConfig { format: OutputFormat::Pdf, page_range: Some(1..3), ..Default::default() };
+        let pdf = TypstWrapper::render_markup(outlined, Some(config)).unwrap().remove(0);
+        let update = crate::pdf::PdfUpdate::parse(&pdf).unwrap();
+        let pages = update.pages().unwrap();
+        assert_eq!(pages.len(), 2);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Title (Third)") && !text.contains("/Title (First)"));
+        assert!(text.contains(&format!("/Dest [{} 0 R", pages[1])));
+
+        for (format, range) in [(OutputFormat::Svg, 2..4), (OutputFormat::Svg, 1..1), (OutputFormat::Pdf, 0..4)] {
+            let config = RenderConfig { format, page_range: Some(range.clone()), ..Default::default() };
+            let result = TypstWrapper::render_markup(markup, Some(config));
+            assert!(matches!(result, Err(TypstWrapperError::Validation(_))), "{:?}", range);
+        }
+    }
+
     #[test]
     fn test_package_import() {
         // Test that the package system works
@@ -447,6 +1706,25 @@ mod tests {
         assert!(!pages[0].is_empty());
     }
     
+    #[test]
+    fn test_compatible_package_version() {
+        let markup = "#import \"@preview/tonguetoquill-usaf-memo:0.1.9\": official-memorandum\nNewer patch";
+        let diagnostics = TypstWrapper::diagnose_markup(markup);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("0.1.0"), "{}", diagnostics[0].message);
+        assert_eq!(diagnostics[0].range.start.character, 8);
+        assert!(TypstWrapper::render_markup(markup, None).is_ok());
+        
+        let strict = RenderConfig { strict_packages: true, ..Default::default() };
+        let result = TypstWrapper::render_markup(markup, Some(strict));
+        assert!(matches!(result, Err(TypstWrapperError::Compilation(message)) if message.contains("0.1.9")));
+        
+        // Incompatible versions fail either way
+        let markup = "#import \"@preview/tonguetoquill-usaf-memo:0.2.0\": official-memorandum";
+        assert!(TypstWrapper::render_markup(markup, None).is_err());
+    }
+    
     #[test]
     fn test_asset_loading() {
         // Test that embedded assets can be loaded
@@ -501,6 +1779,7 @@ mod tests {
         
         let config = RenderConfig {
             format: OutputFormat::Pdf,
+            ..Default::default()
         };
         
         let result = TypstWrapper::render_form(json_input, Some(config));
@@ -513,4 +1792,202 @@ mod tests {
         // PDF files start with %PDF
         assert!(pages[0].starts_with(b"%PDF"));
     }
+    
+    #[test]
+    fn test_render_form_with_letterhead_background() {
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        TypstWrapper::register_letterhead("test-stationery", seal).unwrap();
+        
+        let json_input = r#"{
+            "memo-for": ["Test Recipient"],
+            "from-block": ["Test Sender", "Test Title"],
+            "subject": "Letterhead Background Test",
+            "signature-block": ["Test Signature", "Test Title"],
+            "letterhead-background": "test-stationery",
+            "letterhead-background-opacity": 0.2,
+            "body_raw": "This memo is printed on simulated stationery."
+        }"#;
+        
+        let result = TypstWrapper::render_form(json_input, None);
+        assert!(result.is_ok(), "Letterhead render should work: {:?}", result.err());
+        
+        let pages = result.unwrap();
+        assert!(!pages.is_empty());
+        
+        // The background adds an image on top of the letterhead seal
+        let plain = TypstWrapper::render_form(&json_input.replace("\"test-stationery\"", "null"), None).unwrap();
+        let count_images = |page: &[u8]| String::from_utf8_lossy(page).matches("<image").count();
+        assert!(count_images(&pages[0]) > count_images(&plain[0]));
+    }
+    
+    #[test]
+    fn test_register_letterhead_rejects_invalid_input() {
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        assert!(TypstWrapper::register_letterhead("../escape", seal).is_err());
+        assert!(TypstWrapper::register_letterhead("not-an-image", b"plain text".to_vec()).is_err());
+    }
+    
+    #[test]
+    fn test_registered_assets_are_served_at_their_path() {
+        let engine = RenderEngine::new();
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content.to_vec();
+        let markup = "#image(\"emblems/unit.gif\", alt: \"Unit emblem\")";
+        assert!(engine.render_markup(markup, None).is_err());
+        engine.register_asset("/emblems/unit.gif", seal).unwrap();
+        assert!(engine.render_markup(markup, None).is_ok());
+        assert!(crate::render_markup(markup, None).is_err());
+
+        let form = MemoBuilder::minimal().subject("Emblem").body_raw("#image(\"/emblems/unit.gif\", alt: \"Unit emblem\")");
+        let pages = engine.render_form(&form.to_json(), None).unwrap();
+        assert!(String::from_utf8_lossy(&pages[0]).contains("aria-label=\"Unit emblem\""));
+
+        assert_eq!(engine.scope(crate::registered_asset_paths), ["emblems/unit.gif"]);
+        assert!(engine.unregister_asset("emblems/unit.gif"));
+        assert!(engine.render_markup(markup, None).is_err());
+        for path in ["", "../escape.gif", "emblems//unit.gif", "emblems/unit gif", "letterheads/unit.gif"] {
+            assert!(matches!(engine.register_asset(path, vec![1]), Err(TypstWrapperError::Validation(_))), "{}", path);
+        }
+    }
+    
+    #[test]
+    fn test_delta_body_with_data_url_image() {
+        use base64::Engine as _;
+        let seal = assets::load_binary_asset("dod_seal.gif").unwrap().content;
+        let url = format!("data:image/gif;base64,{}", base64::engine::general_purpose::STANDARD.encode(seal));
+        let delta = serde_json::json!({"ops": [
+            {"insert": {"image": url}, "attributes": {"alt": "Inlined seal"}},
+            {"insert": "\n"}
+        ]});
+        let form = MemoBuilder::minimal().subject("Inlined image").body_delta(&delta.to_string());
+        let pages = TypstWrapper::render_form(&form.to_json(), None).unwrap();
+        assert!(String::from_utf8_lossy(&pages[0]).contains("aria-label=\"Inlined seal\""));
+        assert!(assets::registered_asset_paths().iter().all(|path| !path.starts_with("delta-images/")));
+    }
+
+    #[test]
+    fn test_render_form_with_unknown_letterhead_background() {
+        let json_input = r#"{
+            "memo-for": ["Test Recipient"],
+            "from-block": ["Test Sender"],
+            "subject": "Missing Letterhead",
+            "signature-block": ["Test Signature", "Test Title"],
+            "letterhead-background": "does-not-exist",
+            "body_raw": "Body"
+        }"#;
+        
+        let result = TypstWrapper::render_form(json_input, None);
+        assert!(matches!(result, Err(TypstWrapperError::Validation(_))));
+    }
+    
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_render_form_with_page_numbering() {
+        fn texts(frame: &typst::layout::Frame, found: &mut Vec<String>) {
+            for (_, item) in frame.items() {
+                match item {
+                    typst::layout::FrameItem::Group(group) => texts(&group.frame, found),
+                    typst::layout::FrameItem::Text(text) => found.push(text.text.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        let page_texts = |json_input: &str| -> Vec<String> {
+            TypstWrapper::compile_form(json_input)
+                .unwrap()
+                .pages
+                .iter()
+                .map(|page| {
+                    let mut found = Vec::new();
+                    texts(&page.frame, &mut found);
+                    found.join("\n")
+                })
+                .collect()
+        };
+        let json_input = |numbering: &str| {
+            format!(
+                r#"{{
+                    "memo-for": ["Test Recipient"],
+                    "from-block": ["Test Sender"],
+                    "subject": "Numbered",
+                    "signature-block": ["Test Signature", "Test Title"],
+                    "page-numbering": {},
+                    "body_raw": "First page #pagebreak() Second page"
+                }}"#,
+                numbering
+            )
+        };
+
+        let pages = page_texts(&json_input(r#"{"format": "Page {page} of {total}"}"#));
+        assert_eq!(pages.len(), 2);
+        assert!(!pages[0].contains("Page 1 of 2"));
+        assert!(pages[1].contains("Page 2 of 2"));
+
+        let pages = page_texts(&json_input(r#"{"format": "- {page} -", "start": 5, "first-page": true, "position": "top-left"}"#));
+        assert!(pages[0].contains("- 5 -"));
+        assert!(pages[1].contains("- 6 -"));
+    }
+
+    #[test]
+    fn test_diagnose_markup() {
+        assert!(TypstWrapper::diagnose_markup("= Clean").is_empty());
+        
+        let diagnostics = TypstWrapper::diagnose_markup("= Title\n\n#text(fill: 3)[a]");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].source, DiagnosticSource::Typst);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert_eq!(diagnostics[0].range.start.character, 12);
+    }
+    
+    #[test]
+    #[cfg(all(feature = "font-times", feature = "font-copperplate"))]
+    fn test_diagnose_form() {
+        assert!(TypstWrapper::diagnose_form(&MemoBuilder::minimal().to_json()).is_empty());
+        
+        // Schema problems are reported without compiling
+        let diagnostics = TypstWrapper::diagnose_form(r#"{"subject":"S"}"#);
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.source == DiagnosticSource::Schema));
+        
+        // Template errors point at the form start and reference the template
+        let broken = MemoBuilder::minimal().body_raw("#undefined-fn()").to_json();
+        let diagnostics = TypstWrapper::diagnose_form(&broken);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source, DiagnosticSource::Typst);
+        assert_eq!(diagnostics[0].range.start.line, 0);
+        assert_eq!(diagnostics[0].related[0].path, MEMO_TEMPLATE_PATH);
+    }
+    
+    #[test]
+    fn test_prewarm() {
+        TypstWrapper::prewarm().unwrap();
+        assert!(TypstWrapper::render_form(PREWARM_FORM, None).is_ok());
+    }
+    
+    #[test]
+    fn test_generate_typst() {
+        let engine = RenderEngine::new();
+        let signature = "#let signature-block(input) = input.signature-block.map(upper)";
+        engine.set_template_partial(Partial::Signature, signature).unwrap();
+        let source = engine.generate_typst(PREWARM_FORM).unwrap();
+        assert!(source.contains(signature));
+        assert!(!source.contains("partials/") && !source.contains("input.json") && !source.contains(":latest"));
+        
+        // The source renders like the form
+        let form = engine.render_form(PREWARM_FORM, None).unwrap();
+        assert_eq!(engine.render_markup(&source, None).unwrap(), form);
+        assert!(matches!(TypstWrapper::generate_typst(r#"{"subject":"S"}"#), Err(TypstWrapperError::Validation(_))));
+    }
+    
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_normalize_form_renders_alike() {
+        let form = r#"{"memo-for":["ORG/SYMBOL"],"from-block":["ORG/SYMBOL"],"subject":"Defaults","date":"2024-01-15",
+            "signature-block":["NAME","TITLE"],"body":{"data":"Hello"},"page-numbering":true,"watermark":{"text":"DRAFT"}}"#;
+        let normalized = crate::normalize_form(form).unwrap();
+        assert_ne!(normalized, form);
+        let config = || Some(RenderConfig { cache: false, ..Default::default() });
+        assert_eq!(TypstWrapper::render_form(&normalized, config()).unwrap(), TypstWrapper::render_form(form, config()).unwrap());
+        assert!(matches!(crate::normalize_form(r#"{"subject":"S"}"#), Err(TypstWrapperError::Validation(_))));
+    }
 }
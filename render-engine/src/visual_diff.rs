@@ -0,0 +1,313 @@
+//! Visual comparison of two versions of a document.
+//!
+//! A reviewer looking at a revised memo wants to see what changed without
+//! reading both versions side by side. Both versions are compiled and the
+//! text runs, shapes and images of all their pages are matched in drawing
+//! order, ignoring where they landed, so text that merely moved down or onto
+//! the next page is not reported. What is left are the regions removed from
+//! the draft and added in the revision, highlighted in red and green on top
+//! of each page's SVG.
+
+use std::fmt::Write;
+
+use serde::Serialize;
+use tracing::instrument;
+use typst::layout::{Abs, Frame, FrameItem, PagedDocument, Point, Transform};
+use typst::utils::hash128;
+
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+use crate::RenderConfig;
+
+/// Highlight color of regions removed from the draft
+const REMOVED_COLOR: &str = "#d32f2f";
+/// Highlight color of regions added in the revision
+const ADDED_COLOR: &str = "#2e7d32";
+/// Regions closer than this many points are merged into one
+const MERGE_DISTANCE: f64 = 2.0;
+/// Largest number of items compared one by one after skipping the common
+/// start and end; beyond it all remaining items count as changed
+const MAX_COMPARED_CELLS: usize = 16_000_000;
+
+/// The changes between two versions of a document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VisualDiff {
+    /// Pages of the draft, with the regions that were removed
+    pub draft: Vec<PageChanges>,
+    /// Pages of the revision, with the regions that were added
+    pub revised: Vec<PageChanges>,
+}
+
+impl VisualDiff {
+    /// Whether the two versions look the same
+    pub fn is_unchanged(&self) -> bool {
+        self.draft.iter().chain(&self.revised).all(|page| page.regions.is_empty())
+    }
+}
+
+/// The changed regions of a page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageChanges {
+    /// Page size in points
+    pub width: f64,
+    pub height: f64,
+    /// Changed regions, in points from the top left corner of the page
+    pub regions: Vec<DiffRegion>,
+    /// The page as SVG with the changed regions highlighted
+    pub svg: String,
+}
+
+/// A rectangle of a page that changed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DiffRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Compare two versions of Typst markup
+///
+/// # Examples
+/// ```
+/// use render_engine::diff_markup;
+///
+/// let diff = diff_markup("Hello World", "Hello Reader").unwrap();
+/// assert!(!diff.is_unchanged());
+/// assert_eq!(diff.revised[0].regions.len(), 1);
+/// ```
+#[instrument(skip_all)]
+pub fn diff_markup(draft: &str, revised: &str) -> Result<VisualDiff, TypstWrapperError> {
+    let draft = TypstWrapper::compile_markup(draft)?;
+    let revised = TypstWrapper::compile_markup(revised)?;
+    Ok(diff_documents(&draft, &revised))
+}
+
+/// Compare two versions of a memo form
+#[instrument(skip_all)]
+pub fn diff_forms(draft_json: &str, revised_json: &str) -> Result<VisualDiff, TypstWrapperError> {
+    let draft = TypstWrapper::compile_form(draft_json)?;
+    let revised = TypstWrapper::compile_form(revised_json)?;
+    Ok(diff_documents(&draft, &revised))
+}
+
+/// A drawn item, identified by what it draws rather than where
+struct Item {
+    key: u128,
+    page: usize,
+    /// Bounds on the page as `(min, max)`
+    bounds: (Point, Point),
+}
+
+fn diff_documents(draft: &PagedDocument, revised: &PagedDocument) -> VisualDiff {
+    let draft_items = items(draft);
+    let revised_items = items(revised);
+    let (removed, added) = unmatched(&draft_items, &revised_items);
+    VisualDiff {
+        draft: page_changes(draft, &draft_items, &removed, REMOVED_COLOR),
+        revised: page_changes(revised, &revised_items, &added, ADDED_COLOR),
+    }
+}
+
+/// All items of a document in drawing order
+fn items(document: &PagedDocument) -> Vec<Item> {
+    let mut items = Vec::new();
+    for (page, content) in document.pages.iter().enumerate() {
+        collect(&content.frame, Transform::identity(), page, &mut items);
+    }
+    items
+}
+
+fn collect(frame: &Frame, transform: Transform, page: usize, items: &mut Vec<Item>) {
+    for (pos, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+        let (key, min, max) = match item {
+            FrameItem::Group(group) => {
+                collect(&group.frame, transform.pre_concat(group.transform), page, items);
+                continue;
+            }
+            // Glyphs carry source spans, which differ between the versions
+            FrameItem::Text(text) if !text.text.trim().is_empty() => {
+                let metrics = text.font.metrics();
+                (
+                    hash128(&(&text.text, &text.font, text.size, &text.fill, &text.stroke)),
+                    Point::new(Abs::zero(), -metrics.ascender.at(text.size)),
+                    Point::new(text.width(), -metrics.descender.at(text.size)),
+                )
+            }
+            FrameItem::Shape(shape, _) => {
+                let size = shape.geometry.bbox_size();
+                (hash128(shape), Point::zero(), size.to_point())
+            }
+            FrameItem::Image(image, size, _) => (hash128(&(image, size)), Point::zero(), size.to_point()),
+            _ => continue,
+        };
+        let corners = [min, Point::new(max.x, min.y), Point::new(min.x, max.y), max].map(|corner| corner.transform(transform));
+        let min = corners.iter().fold(corners[0], |min, corner| min.min(*corner));
+        let max = corners.iter().fold(corners[0], |max, corner| max.max(*corner));
+        items.push(Item { key, page, bounds: (min, max) });
+    }
+}
+
+/// Indices of the draft items missing from the revision and of the revision
+/// items missing from the draft, matched by their longest common subsequence
+fn unmatched(draft: &[Item], revised: &[Item]) -> (Vec<usize>, Vec<usize>) {
+    let prefix = draft.iter().zip(revised).take_while(|(a, b)| a.key == b.key).count();
+    let suffix = draft[prefix..]
+        .iter()
+        .rev()
+        .zip(revised[prefix..].iter().rev())
+        .take_while(|(a, b)| a.key == b.key)
+        .count();
+    let a = &draft[prefix..draft.len() - suffix];
+    let b = &revised[prefix..revised.len() - suffix];
+    let (n, m) = (a.len(), b.len());
+    if n.saturating_mul(m) > MAX_COMPARED_CELLS {
+        return ((prefix..prefix + n).collect(), (prefix..prefix + m).collect());
+    }
+
+    // lengths[i * (m + 1) + j] is the length of the longest common
+    // subsequence of a[i..] and b[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if a[i].key == b[j].key {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i].key == b[j].key {
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            removed.push(prefix + i);
+            i += 1;
+        } else {
+            added.push(prefix + j);
+            j += 1;
+        }
+    }
+    removed.extend((i..n).map(|i| prefix + i));
+    added.extend((j..m).map(|j| prefix + j));
+    (removed, added)
+}
+
+/// The changed regions of every page, from the indices of changed items
+fn page_changes(document: &PagedDocument, items: &[Item], changed: &[usize], color: &str) -> Vec<PageChanges> {
+    let config = RenderConfig::default();
+    document
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let bounds = changed.iter().map(|&changed| &items[changed]).filter(|item| item.page == index).map(|item| item.bounds);
+            let regions: Vec<DiffRegion> = merge(bounds.collect())
+                .into_iter()
+                .map(|(min, max)| DiffRegion {
+                    x: round(min.x),
+                    y: round(min.y),
+                    width: round(max.x - min.x),
+                    height: round(max.y - min.y),
+                })
+                .collect();
+            let svg = String::from_utf8_lossy(&TypstWrapper::page_svg(document, index, &config)).into_owned();
+            let size = page.frame.size();
+            PageChanges {
+                width: round(size.x),
+                height: round(size.y),
+                svg: highlight(svg, &regions, color),
+                regions,
+            }
+        })
+        .collect()
+}
+
+/// Merge bounds that overlap or nearly touch, in reading order
+fn merge(mut bounds: Vec<(Point, Point)>) -> Vec<(Point, Point)> {
+    let distance = Abs::pt(MERGE_DISTANCE);
+    let near = |a: &(Point, Point), b: &(Point, Point)| {
+        a.0.x - distance <= b.1.x && b.0.x - distance <= a.1.x && a.0.y - distance <= b.1.y && b.0.y - distance <= a.1.y
+    };
+    let mut merged: Vec<(Point, Point)> = Vec::new();
+    while let Some(mut current) = bounds.pop() {
+        // Absorb everything near the growing region, then check again
+        while let Some(index) = bounds.iter().position(|other| near(&current, other)) {
+            let other = bounds.swap_remove(index);
+            current = (current.0.min(other.0), current.1.max(other.1));
+        }
+        match merged.iter().position(|other| near(&current, other)) {
+            Some(index) => {
+                let other = merged.swap_remove(index);
+                bounds.push((current.0.min(other.0), current.1.max(other.1)));
+            }
+            None => merged.push(current),
+        }
+    }
+    merged.sort_by(|a, b| a.0.y.cmp(&b.0.y).then(a.0.x.cmp(&b.0.x)));
+    merged
+}
+
+/// A page's SVG with translucent rectangles over `regions`
+fn highlight(svg: String, regions: &[DiffRegion], color: &str) -> String {
+    let mut rects = String::new();
+    for region in regions {
+        let _ = write!(
+            rects,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{color}\" fill-opacity=\"0.25\" \
+             stroke=\"{color}\" stroke-width=\"0.5\"/>",
+            region.x, region.y, region.width, region.height,
+        );
+    }
+    match svg.rfind("</svg>") {
+        Some(end) if !rects.is_empty() => {
+            let mut svg = svg;
+            svg.insert_str(end, &rects);
+            svg
+        }
+        _ => svg,
+    }
+}
+
+/// Points to a hundredth, which keeps the JSON short
+fn round(value: Abs) -> f64 {
+    (value.to_pt() * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_diff_markup() {
+        let draft = "#set page(width: 200pt, height: 100pt, margin: 10pt)\nFirst\n\nSecond\n\nThird";
+        let revised = "#set page(width: 200pt, height: 100pt, margin: 10pt)\nFirst\n\nInserted\n\nSecond\n\nThird";
+        let diff = diff_markup(draft, revised).unwrap();
+        assert!(!diff.is_unchanged());
+        // The moved lines are not reported, only the inserted one
+        assert!(diff.draft[0].regions.is_empty(), "{:?}", diff.draft[0].regions);
+        assert_eq!(diff.revised[0].regions.len(), 1, "{:?}", diff.revised[0].regions);
+        let region = diff.revised[0].regions[0];
+        assert!(region.x >= 9.0 && region.y > 15.0 && region.y < 40.0, "{:?}", region);
+        assert!(diff.revised[0].svg.contains(ADDED_COLOR));
+        assert!(!diff.draft[0].svg.contains(REMOVED_COLOR));
+
+        assert!(diff_markup(draft, draft).unwrap().is_unchanged());
+    }
+
+    #[test]
+    fn test_merge_nearby_regions() {
+        let point = |x: f64, y: f64| Point::new(Abs::pt(x), Abs::pt(y));
+        let merged = merge(vec![
+            (point(0.0, 0.0), point(10.0, 10.0)),
+            (point(50.0, 50.0), point(60.0, 60.0)),
+            (point(11.0, 0.0), point(20.0, 10.0)),
+        ]);
+        assert_eq!(merged, [(point(0.0, 0.0), point(20.0, 10.0)), (point(50.0, 50.0), point(60.0, 60.0))]);
+    }
+}
@@ -0,0 +1,243 @@
+//! Watermark overlays.
+//!
+//! A [`Watermark`] stamps diagonal, semi-transparent text such as `DRAFT` or
+//! `UNCONTROLLED COPY` across every page. It is set with
+//! [`RenderConfig::watermark`](crate::RenderConfig::watermark) or, for memo
+//! forms, the `watermark` field of the form JSON; the config takes precedence.
+//! Memo forms with `"draft": true` and neither get a `DRAFT` watermark.
+//!
+//! The watermark is laid out as its own small document per page size and
+//! drawn on top of the compiled pages, so it looks the same in SVG and PDF
+//! output and does not change the layout or the diagnostics of the source.
+
+use serde::{Deserialize, Serialize};
+use typst::layout::{PagedDocument, Point, Size};
+
+use crate::draft;
+use crate::typst_wrapper::{TypstWrapper, TypstWrapperError};
+
+/// Font size the watermark is measured at before it is scaled to the page
+const MEASURE_SIZE: f64 = 100.0;
+/// Share of the page diagonal the watermark text spans
+const DIAGONAL_SHARE: f64 = 0.7;
+/// Largest scale applied to [`MEASURE_SIZE`], so short words stay readable
+const MAX_SCALE: f64 = 1.5;
+
+/// Diagonal text drawn across every page
+///
+/// # Examples
+/// ```
+/// use render_engine::{render_markup, OutputFormat, RenderConfig, Watermark};
+///
+/// let config = RenderConfig {
+///     format: OutputFormat::Pdf,
+///     watermark: Some(Watermark { opacity: 0.15, ..Watermark::new("UNCONTROLLED COPY") }),
+///     ..RenderConfig::default()
+/// };
+/// let pdf = render_markup("= Hello", Some(config)).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermark {
+    /// Text of the watermark
+    pub text: String,
+    /// Color as a hex code, `#rgb` or `#rrggbb`
+    pub color: String,
+    /// Opacity from 0 (invisible) to 1 (opaque)
+    pub opacity: f64,
+}
+
+impl Watermark {
+    /// A gray watermark with the given text at the default opacity
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            color: "#808080".to_string(),
+            opacity: 0.25,
+        }
+    }
+
+    /// Check that the watermark can be drawn
+    pub(crate) fn validate(&self) -> Result<(), TypstWrapperError> {
+        if self.text.trim().is_empty() {
+            return Err(TypstWrapperError::Validation("Watermark text is empty".to_string()));
+        }
+        let hex = self.color.strip_prefix('#').unwrap_or("");
+        if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid watermark color '{}': use #rgb or #rrggbb",
+                self.color
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(TypstWrapperError::Validation(format!(
+                "Invalid watermark opacity {}: use a number from 0 to 1",
+                self.opacity
+            )));
+        }
+        Ok(())
+    }
+
+    /// Typst markup for one transparent page of `size` showing the watermark
+    /// along its diagonal
+    fn markup(&self, size: Size) -> String {
+        let (width, height) = (size.x.to_pt(), size.y.to_pt());
+        let angle = height.atan2(width).to_degrees();
+        let target = width.hypot(height) * DIAGONAL_SHARE;
+        let text = typst_string(&self.text);
+        format!(
+            "#set page(width: {width}pt, height: {height}pt, margin: 0pt, fill: none)\n\
+             #set text(font: \"Arial\", fill: rgb(\"{color}\").transparentize({clear}%))\n\
+             #context {{\n\
+             \x20 let size = {MEASURE_SIZE}pt * calc.min({target} / measure(text(size: {MEASURE_SIZE}pt, {text})).width.pt(), {MAX_SCALE})\n\
+             \x20 place(center + horizon, rotate(-{angle}deg, text(size: size, {text})))\n\
+             }}\n",
+            color = self.color,
+            clear = (1.0 - self.opacity) * 100.0,
+        )
+    }
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self::new("DRAFT")
+    }
+}
+
+/// The `watermark` field of a form: the text alone or every setting
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FormWatermark {
+    Text(String),
+    Full(Watermark),
+}
+
+/// Read the `watermark` field of preprocessed form JSON, falling back to a
+/// `DRAFT` watermark for drafts
+pub(crate) fn form_watermark(processed_input: &str) -> Result<Option<Watermark>, TypstWrapperError> {
+    #[derive(Deserialize)]
+    struct Form {
+        watermark: Option<FormWatermark>,
+        #[serde(default)]
+        draft: bool,
+    }
+
+    let form: Form = serde_json::from_str(processed_input)
+        .map_err(|e| TypstWrapperError::Validation(format!("Invalid watermark: {}", e)))?;
+    let watermark = form.watermark.map(|watermark| match watermark {
+        FormWatermark::Text(text) => Watermark::new(text),
+        FormWatermark::Full(watermark) => watermark,
+    });
+    // Drafts without a watermark of their own are marked as such
+    Ok(watermark.or_else(|| form.draft.then(|| Watermark::new(draft::WATERMARK))))
+}
+
+/// Draw `watermark` on top of every page of `document`, or beneath the page
+/// content when `beneath` is set
+pub(crate) fn apply(document: &mut PagedDocument, watermark: &Watermark, beneath: bool) -> Result<(), TypstWrapperError> {
+    watermark.validate()?;
+    // Memos rarely mix page sizes, so lay the watermark out once per size
+    let mut overlays: Vec<(Size, typst::layout::Frame)> = Vec::new();
+    for page in &mut document.pages {
+        let size = page.frame.size();
+        let overlay = match overlays.iter().find(|(overlay_size, _)| *overlay_size == size) {
+            Some((_, overlay)) => overlay.clone(),
+            None => {
+                let overlay = TypstWrapper::compile_markup(&watermark.markup(size))?
+                    .pages
+                    .swap_remove(0)
+                    .frame;
+                overlays.push((size, overlay.clone()));
+                overlay
+            }
+        };
+        if beneath {
+            page.frame.prepend_frame(Point::zero(), overlay);
+        } else {
+            page.frame.push_frame(Point::zero(), overlay);
+        }
+    }
+    Ok(())
+}
+
+/// A Typst string literal with the given contents
+pub(crate) fn typst_string(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str("\\n"),
+            '\r' => {}
+            '\t' => literal.push_str("\\t"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "font-times")]
+    use crate::test_utils::MemoBuilder;
+    use crate::{render_markup, OutputFormat, RenderConfig};
+    #[cfg(feature = "font-times")]
+    use serde_json::json;
+
+    fn config(format: OutputFormat, watermark: Watermark) -> Option<RenderConfig> {
+        Some(RenderConfig { format, watermark: Some(watermark), ..RenderConfig::default() })
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_watermark_on_every_page() {
+        let markup = "= One\n#pagebreak()\n= Two";
+        let plain = render_markup(markup, None).unwrap();
+        let watermark = Watermark { color: "#c00".to_string(), opacity: 0.3, ..Watermark::new("DRAFT \"1\"") };
+        let marked = render_markup(markup, config(OutputFormat::Svg, watermark.clone())).unwrap();
+        assert_eq!(marked.len(), 2);
+        for (plain, marked) in plain.iter().zip(&marked) {
+            let marked = String::from_utf8_lossy(marked);
+            assert!(marked.len() > plain.len());
+            assert!(marked.contains("matrix("));
+            assert!(marked.contains("fill=\"#cc00004d\""));
+        }
+
+        let pdf = render_markup(markup, config(OutputFormat::Pdf, watermark)).unwrap();
+        assert!(pdf[0].starts_with(b"%PDF"));
+    }
+
+    #[test]
+    #[cfg(feature = "font-times")]
+    fn test_form_watermark_field() {
+        let form = |watermark: serde_json::Value| MemoBuilder::minimal().subject("Marked").set("watermark", watermark).to_json();
+        let processed = TypstWrapper::validate_form(&form(json!("UNCONTROLLED COPY"))).unwrap();
+        assert_eq!(form_watermark(&processed).unwrap(), Some(Watermark::new("UNCONTROLLED COPY")));
+        let processed = TypstWrapper::validate_form(&form(json!({"text": "DRAFT", "opacity": 0.5}))).unwrap();
+        assert_eq!(form_watermark(&processed).unwrap(), Some(Watermark { opacity: 0.5, ..Watermark::default() }));
+
+        let plain = crate::render_form(&form(json!(null)), None).unwrap();
+        let marked = crate::render_form(&form(json!("DRAFT")), None).unwrap();
+        assert!(marked[0].len() > plain[0].len());
+        assert!(TypstWrapper::validate_form(&form(json!({"text": "DRAFT", "color": "red"}))).is_err());
+    }
+
+    #[test]
+    fn test_invalid_watermarks_are_rejected() {
+        for watermark in [
+            Watermark::new(" "),
+            Watermark { color: "#12345".to_string(), ..Watermark::default() },
+            Watermark { color: "red\")".to_string(), ..Watermark::default() },
+            Watermark { opacity: 1.5, ..Watermark::default() },
+            Watermark { opacity: f64::NAN, ..Watermark::default() },
+        ] {
+            let result = render_markup("Hi", config(OutputFormat::Svg, watermark));
+            assert!(matches!(result, Err(TypstWrapperError::Validation(_))));
+        }
+        assert_eq!(typst_string("a \"b\" \\ c\n"), r#""a \"b\" \\ c\n""#);
+    }
+}
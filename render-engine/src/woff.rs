@@ -0,0 +1,124 @@
+//! WOFF font decoding.
+//!
+//! Web fonts are often only at hand as WOFF, which wraps the tables of a
+//! TrueType or OpenType font with each table compressed on its own. Typst
+//! reads only the plain format, so [`decode`] unpacks WOFF data into it.
+//! WOFF2 uses Brotli and transformed glyph tables and is not supported.
+
+/// Signature of WOFF data
+const WOFF: &[u8; 4] = b"wOFF";
+/// Signature of WOFF2 data
+const WOFF2: &[u8; 4] = b"wOF2";
+/// Size of the WOFF header and of one of its table entries
+const HEADER_LEN: usize = 44;
+const ENTRY_LEN: usize = 20;
+
+/// Whether `data` is WOFF2
+pub(crate) fn is_woff2(data: &[u8]) -> bool {
+    data.starts_with(WOFF2)
+}
+
+/// The TrueType or OpenType font in WOFF `data`; `None` if `data` is not
+/// WOFF, an error if it is damaged
+pub(crate) fn decode(data: &[u8]) -> Option<Result<Vec<u8>, String>> {
+    data.starts_with(WOFF).then(|| unpack(data))
+}
+
+fn unpack(data: &[u8]) -> Result<Vec<u8>, String> {
+    let u16_at = |at: usize| data.get(at..at + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let u32_at = |at: usize| data.get(at..at + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    let truncated = || "truncated WOFF data".to_string();
+
+    let flavor = u32_at(4).ok_or_else(truncated)?;
+    let count = usize::from(u16_at(12).ok_or_else(truncated)?);
+    let mut tables = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = HEADER_LEN + index * ENTRY_LEN;
+        let field = |at: usize| u32_at(entry + at).ok_or_else(truncated);
+        let (tag, offset, compressed_len, len, checksum) = (field(0)?, field(4)?, field(8)?, field(12)?, field(16)?);
+        let end = (offset as usize).checked_add(compressed_len as usize).ok_or_else(truncated)?;
+        let stored = data.get(offset as usize..end).ok_or_else(truncated)?;
+        let table = if compressed_len < len {
+            miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(stored, len as usize)
+                .map_err(|e| format!("damaged WOFF table: {:?}", e.status))?
+        } else {
+            stored.to_vec()
+        };
+        if table.len() != len as usize {
+            return Err("damaged WOFF table: wrong length".to_string());
+        }
+        tables.push((tag, checksum, table));
+    }
+
+    // The plain font: offset table, table records, then the 4-byte aligned
+    // tables in the same order
+    let count = tables.len() as u32;
+    let entry_selector = count.checked_ilog2().unwrap_or(0);
+    let search_range = (1 << entry_selector) * 16;
+    let mut font = Vec::new();
+    font.extend(flavor.to_be_bytes());
+    for value in [count, search_range, entry_selector, count * 16 - search_range] {
+        let value = value as u16;
+        font.extend(value.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, checksum, table) in &tables {
+        for value in [*tag, *checksum, offset as u32, table.len() as u32] {
+            font.extend(value.to_be_bytes());
+        }
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, _, table) in &tables {
+        font.extend(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    Ok(font)
+}
+
+/// `font` packed as WOFF, with the tables that shrink compressed
+#[cfg(all(test, feature = "font-arial"))]
+pub(crate) fn pack(font: &[u8]) -> Vec<u8> {
+    let u16_at = |at: usize| usize::from(u16::from_be_bytes([font[at], font[at + 1]]));
+    let u32_at = |at: usize| u32::from_be_bytes(font[at..at + 4].try_into().unwrap()) as usize;
+    let count = u16_at(4);
+    let mut entries = Vec::new();
+    let mut tables = Vec::new();
+    let mut offset = HEADER_LEN + count * ENTRY_LEN;
+    for index in 0..count {
+        let record = 12 + index * 16;
+        let (start, len) = (u32_at(record + 8), u32_at(record + 12));
+        let table = &font[start..start + len];
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(table, 6);
+        let stored = if compressed.len() < len { compressed } else { table.to_vec() };
+        for value in [u32_at(record), offset, stored.len(), len, u32_at(record + 4)] {
+            entries.extend((value as u32).to_be_bytes());
+        }
+        offset += stored.len().next_multiple_of(4);
+        tables.extend(&stored);
+        tables.resize(tables.len().next_multiple_of(4), 0);
+    }
+    let mut woff = WOFF.to_vec();
+    woff.extend(&font[0..4]);
+    woff.extend((offset as u32).to_be_bytes());
+    woff.extend((count as u16).to_be_bytes());
+    woff.resize(HEADER_LEN, 0);
+    woff.extend(entries);
+    woff.extend(tables);
+    woff
+}
+
+#[cfg(all(test, feature = "font-arial"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_woff_unpacks_to_the_same_font() {
+        let arial = crate::assets::load_binary_asset("arial.ttf").unwrap().content;
+        let font = decode(&pack(arial)).unwrap().unwrap();
+        let info = |data: &[u8]| typst::text::FontInfo::new(data, 0).unwrap();
+        assert_eq!(info(&font), info(arial));
+        assert!(decode(arial).is_none());
+        assert!(decode(&pack(arial)[..60]).unwrap().is_err());
+        assert!(is_woff2(b"wOF2\0\x01\0\0"));
+    }
+}
@@ -0,0 +1,65 @@
+//! Persistent cache provider round trips.
+//!
+//! The provider is process-wide, so these tests live in their own binary.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use render_engine::{
+    clear_cache_provider, render_markup_with_metrics, set_cache_provider, CacheProvider, DiskCache, OutputFormat,
+    RenderConfig,
+};
+
+/// In-memory provider that shares its entries with the test
+#[derive(Clone, Default)]
+struct MemoryCache(Arc<Mutex<HashMap<String, Vec<u8>>>>);
+
+impl CacheProvider for MemoryCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.0.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) {
+        self.0.lock().unwrap().insert(key.to_string(), bytes.to_vec());
+    }
+}
+
+#[test]
+fn test_cache_providers() {
+    // Both providers run in one test because the provider is global
+    let memory = MemoryCache::default();
+    set_cache_provider(memory.clone());
+
+    let markup = "= Cached\n#pagebreak()\n= Twice";
+    let first = render_markup_with_metrics(markup, None).unwrap();
+    assert!(!first.metrics.from_cache);
+    assert_eq!(memory.0.lock().unwrap().len(), 1);
+
+    let second = render_markup_with_metrics(markup, None).unwrap();
+    assert!(second.metrics.from_cache);
+    assert_eq!(second.metrics.pages, 2);
+    assert_eq!(second.pages, first.pages);
+
+    // Another format is another entry
+    let pdf = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
+    assert!(!render_markup_with_metrics(markup, Some(pdf)).unwrap().metrics.from_cache);
+    assert_eq!(memory.0.lock().unwrap().len(), 2);
+
+    // Dates at an offset from UTC change at another hour than the keys' date
+    let offset = "#datetime.today(offset: 12).display()";
+    render_markup_with_metrics(offset, None).unwrap();
+    assert!(!render_markup_with_metrics(offset, None).unwrap().metrics.from_cache);
+    assert_eq!(memory.0.lock().unwrap().len(), 2);
+
+    let dir = std::path::Path::new("target/tmp/disk-cache");
+    let _ = std::fs::remove_dir_all(dir);
+    set_cache_provider(DiskCache::new(dir).unwrap());
+    let first = render_markup_with_metrics(markup, None).unwrap();
+    let second = render_markup_with_metrics(markup, None).unwrap();
+    clear_cache_provider();
+
+    assert!(!first.metrics.from_cache);
+    assert!(second.metrics.from_cache);
+    assert_eq!(second.pages, first.pages);
+    assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+}
@@ -39,6 +39,7 @@ fn test_usaf_template_render() {
     // Test PDF rendering
     let pdf_config = RenderConfig {
         format: OutputFormat::Pdf,
+        ..Default::default()
     };
     
     let pdf_result = render_markup(usaf_template, Some(pdf_config));
@@ -96,7 +97,7 @@ fn test_render_form_with_provided_input() {
         assert!(!svg_pages[0].is_empty(), "First SVG page should have content");
 
         // Render as PDF
-        let pdf_config = RenderConfig { format: OutputFormat::Pdf };
+        let pdf_config = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
         let pdf_result = render_form(json_input, Some(pdf_config));
         assert!(pdf_result.is_ok(), "PDF render_form failed: {:?}", pdf_result.err());
         let pdf_pages = pdf_result.unwrap();
@@ -111,7 +112,7 @@ fn test_delta_parsing() {
     // The specific Delta JSON to test
     let delta_json = r#"{"ops":[{"insert":"This is the body content of the memorandum.\n\nYou can format text with "},{"attributes":{"bold":true},"insert":"bold"},{"insert":", "},{"attributes":{"italic":true},"insert":"italic"},{"insert":", and "},{"attributes":{"underline":true},"insert":"underlined"},{"insert":" text.\n\nYou can also create:"},{"attributes":{"header":2},"insert":"\n"},{"insert":"Numbered lists"},{"attributes":{"list":"ordered"},"insert":"\n"},{"insert":"Bullet points"},{"attributes":{"list":"bullet"},"insert":"\n"},{"insert":"And much more!"},{"attributes":{"list":"bullet"},"insert":"\n"}]}"#;
     
-    let mut parser = DeltaParser::new();
+    let parser = DeltaParser::new();
     let result = parser.parse(delta_json);
     
     assert!(result.is_ok(), "Delta parsing should succeed: {:?}", result.err());
@@ -151,7 +152,7 @@ fn test_delta_parse() {
     println!("{}", delta_json);
     
     // Parse using DeltaParser directly
-    let mut parser = DeltaParser::new();
+    let parser = DeltaParser::new();
     let parse_result = parser.parse(delta_json);
     
     assert!(parse_result.is_ok(), "Delta parsing should succeed: {:?}", parse_result.err());
@@ -171,3 +172,56 @@ fn test_delta_parse() {
     
     println!("=== END DELTA PARSER DIRECT TEST ===");
 }
+
+#[test]
+fn test_svg_string_renders() {
+    use render_engine::{render_form_svg_strings, render_markup_svg_strings};
+
+    let markup = "= Page one\n#pagebreak()\n= Page two";
+    let strings = render_markup_svg_strings(markup, None).unwrap();
+    let bytes = render_markup(markup, None).unwrap();
+    assert_eq!(strings.iter().map(|page| page.as_bytes()).collect::<Vec<_>>(), bytes.iter().map(Vec::as_slice).collect::<Vec<_>>());
+
+    // The format of the config is ignored
+    let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "Strings",
+        "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+    let pdf = RenderConfig { format: OutputFormat::Pdf, ..Default::default() };
+    let pages = render_form_svg_strings(form, Some(pdf)).unwrap();
+    assert!(pages[0].starts_with("<svg"));
+}
+
+#[test]
+#[cfg(feature = "font-times")]
+fn test_structured_references() {
+    use render_engine::form_text_layout;
+
+    let form = r#"{"memo-for": ["ORG/SYMBOL"], "from-block": ["ORG/SYMBOL"], "subject": "References",
+        "signature-block": ["NAME", "TITLE"], "body_raw": "Body",
+        "references": ["AFH 33-337, The Tongue and Quill",
+            {"type": "AFI 33-360", "title": "Publications and Forms Management", "date": "2015-12-01",
+             "link": "https://www.e-publishing.af.mil/"}]}"#;
+    let layout = form_text_layout(form).unwrap();
+    // Links start runs of their own
+    let text = layout[0].runs.iter().map(|run| run.text.trim()).collect::<Vec<_>>().join(" ").replace(" ,", ",");
+    assert!(text.contains("AFH 33-337, The Tongue and Quill"), "{}", text);
+    assert!(text.contains("AFI 33-360 Publications and Forms Management, 1 December 2015"), "{}", text);
+
+    let svg = String::from_utf8(render_form(form, None).unwrap().remove(0)).unwrap();
+    assert!(svg.contains("https://www.e-publishing.af.mil/"));
+
+    let bad_date = form.replace("2015-12-01", "1 Dec 2015");
+    assert!(render_form(&bad_date, None).is_err());
+}
+
+#[test]
+#[cfg(feature = "font-times")]
+fn test_structured_recipients() {
+    use render_engine::form_text_layout;
+
+    let form = r#"{"memo-for": ["ORG/SYMBOL", {"organization": "HQ AETC", "office-symbol": "A1"},
+            {"office-symbol": "CC", "location": "Maxwell AFB AL"}],
+        "from-block": ["ORG/SYMBOL"], "subject": "Recipients", "signature-block": ["NAME", "TITLE"], "body_raw": "Body"}"#;
+    let layout = form_text_layout(form).unwrap();
+    let text = layout[0].runs.iter().map(|run| run.text.trim()).collect::<Vec<_>>().join(" ");
+    assert!(text.contains("ORG/SYMBOL, HQ AETC/A1, CC, Maxwell AFB AL"), "{}", text);
+}
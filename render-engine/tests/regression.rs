@@ -0,0 +1,28 @@
+//! Golden-image regression run over `tests/regression/fixtures`.
+//!
+//! Golden images are platform independent (all fonts are embedded) and are
+//! committed in `tests/regression/golden`. The first command compares against
+//! them; after an intended layout change, record them again with the second
+//! and commit the new images:
+//!
+//! ```sh
+//! cargo test -p render-engine --features regression --test regression
+//! REGRESSION_UPDATE=1 cargo test -p render-engine --features regression --test regression
+//! ```
+//!
+//! Failing pages leave `*.actual.png` and `*.diff.png` in `target/regression`.
+#![cfg(feature = "regression")]
+
+use render_engine::regression::{run, RegressionConfig};
+
+#[test]
+fn golden_images_match() {
+    let config = RegressionConfig {
+        update: std::env::var_os("REGRESSION_UPDATE").is_some(),
+        ..RegressionConfig::default()
+    };
+
+    let report = run(&config).expect("regression harness failed");
+    assert!(!report.fixtures.is_empty(), "no fixtures found in {}", config.fixtures_dir.display());
+    assert!(report.passed(), "{}", report);
+}
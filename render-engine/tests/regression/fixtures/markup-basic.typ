@@ -0,0 +1,18 @@
+#set page(width: 8.5in, height: 11in, margin: 1in)
+#set text(font: "Times New Roman", size: 12pt)
+
+= Markup Fixture
+
+This page covers plain markup without the memo template: *strong*, _emphasis_
+and `raw` text, lists and a table.
+
+- First bullet
+- Second bullet
+  + Nested numbered item
+
+#table(
+  columns: 3,
+  [Name], [Grade], [Office],
+  [Alpha], [O-3], [A1],
+  [Bravo], [E-7], [B2],
+)
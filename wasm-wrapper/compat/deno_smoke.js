@@ -0,0 +1,24 @@
+// Smoke test for the Deno build of the WASM wrapper.
+//
+//   ./build_wasm.sh release --runtime deno
+//   deno run --allow-read wasm-wrapper/compat/deno_smoke.js
+
+import { render_markup, RenderJob } from "../pkg/wasm_wrapper.js";
+
+const svg = new TextDecoder().decode(render_markup("= Deno smoke test"));
+if (!svg.startsWith("<svg")) {
+  throw new Error("render_markup did not return an SVG");
+}
+
+const job = RenderJob.markup("= Stepped\n#pagebreak()\n= Render", "pdf");
+let pdf;
+let steps = 0;
+while (!(pdf = job.step())) {
+  steps += 1;
+  await new Promise((resolve) => setTimeout(resolve, 0));
+}
+if (!job.finished || new TextDecoder().decode(pdf.subarray(0, 4)) !== "%PDF") {
+  throw new Error("RenderJob did not produce a PDF");
+}
+
+console.log(`ok: svg ${svg.length} chars, pdf ${pdf.length} bytes after ${steps + 1} steps`);
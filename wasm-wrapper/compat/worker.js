@@ -0,0 +1,31 @@
+// Smoke test for the Cloudflare Workers build of the WASM wrapper.
+//
+//   ./build_wasm.sh release --runtime workers
+//   cd wasm-wrapper/compat && npx wrangler dev
+//   curl -s localhost:8787 -o smoke.pdf
+//
+// The render yields between steps so no single turn of the event loop
+// exceeds the Workers CPU limit.
+
+import { initSync, RenderJob } from "../pkg/wasm_wrapper.js";
+import wasmModule from "../pkg/wasm_wrapper_bg.wasm";
+
+initSync({ module: wasmModule });
+
+export default {
+  async fetch(request) {
+    const markup = request.method === "POST" ? await request.text() : "= Workers smoke test";
+    const job = RenderJob.markup(markup, "pdf");
+    try {
+      let pdf;
+      while (!(pdf = job.step())) {
+        await scheduler.wait(0);
+      }
+      return new Response(pdf, { headers: { "content-type": "application/pdf" } });
+    } catch (error) {
+      return new Response(String(error), { status: 422 });
+    } finally {
+      job.free();
+    }
+  },
+};
@@ -0,0 +1,19 @@
+// Browser glue for the byte-handling helpers in src/helpers.rs.
+// wasm-bindgen copies this file into the package as a snippet.
+
+export function bytesToBlob(bytes, type) {
+  return new Blob([bytes], { type });
+}
+
+export function downloadBlob(blob, filename) {
+  const url = URL.createObjectURL(blob);
+  const link = document.createElement("a");
+  link.href = url;
+  link.download = filename;
+  link.style.display = "none";
+  document.body.appendChild(link);
+  link.click();
+  link.remove();
+  // Revoke after the click has been handled so the download can start
+  setTimeout(() => URL.revokeObjectURL(url), 0);
+}
@@ -0,0 +1,107 @@
+//! Persistent cache provider backed by a JavaScript object, and the
+//! in-memory output cache.
+
+use std::cell::RefCell;
+
+use render_engine::{
+    clear_cache_provider, clear_output_cache as engine_clear_output_cache, set_cache_provider as engine_set_cache_provider,
+    set_output_cache_limits as engine_set_output_cache_limits, CacheProvider, OutputCacheLimits,
+};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+thread_local! {
+    /// Host object with `get(key)` and `put(key, bytes)` methods
+    static PROVIDER: RefCell<Option<JsValue>> = const { RefCell::new(None) };
+}
+
+/// Forwards engine cache lookups to the object in `PROVIDER`. JS values
+/// cannot cross threads, so the engine holds this stateless handle instead.
+struct JsCache;
+
+impl JsCache {
+    /// Call method `name` of the provider object with `args`
+    fn call(name: &str, args: &[JsValue]) -> Option<JsValue> {
+        PROVIDER.with(|slot| {
+            let provider = slot.borrow();
+            let provider = provider.as_ref()?;
+            let method: js_sys::Function = js_sys::Reflect::get(provider, &JsValue::from_str(name))
+                .ok()?
+                .dyn_into()
+                .ok()?;
+            method
+                .apply(provider, &args.iter().collect::<js_sys::Array>())
+                .ok()
+        })
+    }
+}
+
+impl CacheProvider for JsCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let value = Self::call("get", &[JsValue::from_str(key)])?;
+        value.dyn_into::<js_sys::Uint8Array>().ok().map(|bytes| bytes.to_vec())
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) {
+        Self::call("put", &[JsValue::from_str(key), js_sys::Uint8Array::from(bytes).into()]);
+    }
+}
+
+/// Persist rendered output across sessions through a host cache.
+/// 
+/// `provider` is an object with synchronous `get(key)` and `put(key, bytes)`
+/// methods. `get` returns a `Uint8Array` or `undefined`; exceptions count as
+/// misses. Renders are looked up before compiling and stored after success,
+/// so repeat renders of a saved memo skip compilation. Pass `null` or
+/// nothing to stop caching.
+/// 
+/// Renders are synchronous, so an IndexedDB-backed provider keeps a memory
+/// mirror: load the entries at startup and write new ones through in the
+/// background.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const entries = new Map(await idbEntries('render-cache'));
+/// set_cache_provider({
+///   get: key => entries.get(key),
+///   put: (key, bytes) => { entries.set(key, bytes); idbPut('render-cache', key, bytes); },
+/// });
+/// ```
+#[wasm_bindgen]
+pub fn set_cache_provider(provider: JsValue) {
+    let enabled = !provider.is_undefined() && !provider.is_null();
+    PROVIDER.with(|slot| *slot.borrow_mut() = enabled.then_some(provider));
+    if enabled {
+        engine_set_cache_provider(JsCache);
+    } else {
+        clear_cache_provider();
+    }
+}
+
+/// Keep recent renders in memory, so repeating one is instant.
+/// 
+/// The least recently used renders are dropped beyond `max_entries` renders
+/// or `max_bytes` of output. Omitted parameters fall back to the engine
+/// defaults (64 renders, 64 MiB). Renders are looked up in memory before the
+/// `set_cache_provider` object.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// set_output_cache_limits(16, 32 * 1024 * 1024);
+/// ```
+#[wasm_bindgen]
+pub fn set_output_cache_limits(max_entries: Option<usize>, max_bytes: Option<usize>) {
+    let defaults = OutputCacheLimits::default();
+    engine_set_output_cache_limits(OutputCacheLimits {
+        max_entries: max_entries.unwrap_or(defaults.max_entries),
+        max_bytes: max_bytes.unwrap_or(defaults.max_bytes),
+    });
+}
+
+/// Stop keeping renders in memory and free the ones kept.
+#[wasm_bindgen]
+pub fn clear_output_cache() {
+    engine_clear_output_cache();
+}
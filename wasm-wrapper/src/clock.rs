@@ -0,0 +1,55 @@
+//! Host clock for `datetime.today()`, drafts and PDF/X dates.
+
+use std::cell::RefCell;
+
+use render_engine::{clear_clock as engine_clear_clock, set_clock as engine_set_clock, HostTime};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+thread_local! {
+    /// Host function returning the current time as a `Date`
+    static CLOCK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+/// The time of the `Date` the host clock returns, in the local time zone of
+/// the JavaScript runtime. Falls back to the runtime's current time on
+/// threads without the clock and when it returns something else.
+fn host_time() -> HostTime {
+    let date = CLOCK
+        .with(|slot| slot.borrow().as_ref().and_then(|clock| clock.call0(&JsValue::NULL).ok()))
+        .and_then(|value| value.dyn_into::<js_sys::Date>().ok())
+        .unwrap_or_else(js_sys::Date::new_0);
+    HostTime {
+        timestamp_ms: date.get_time() as i64,
+        // `getTimezoneOffset` counts minutes behind UTC
+        utc_offset_minutes: -(date.get_timezone_offset() as i32),
+    }
+}
+
+/// Date renders with the host's clock and time zone.
+///
+/// Without a clock, `datetime.today()` in templates, memos without a `date`
+/// and draft timestamps use the current time in UTC, so a memo rendered
+/// shortly after local midnight carries yesterday's date. `clock` is a
+/// function returning the current time as a `Date`; its time zone offset is
+/// the user's, so `datetime.today()` returns the local date, and a
+/// `datetime.today(offset: n)` in a template the date `n` hours ahead of
+/// UTC. Pass `null` or nothing to go back to UTC.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// set_clock(() => new Date());
+/// // Reproduce a render as of a fixed time
+/// set_clock(() => new Date('2024-12-31T23:30:00Z'));
+/// ```
+#[wasm_bindgen]
+pub fn set_clock(clock: Option<js_sys::Function>) {
+    let enabled = clock.is_some();
+    CLOCK.with(|slot| *slot.borrow_mut() = clock);
+    if enabled {
+        engine_set_clock(host_time);
+    } else {
+        engine_clear_clock();
+    }
+}
@@ -0,0 +1,124 @@
+//! Fonts fetched on demand for modules built without embedded fonts.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use render_engine::{register_font, unknown_font_families, Diagnostic};
+use tracing::{debug, warn};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+thread_local! {
+    /// Host function resolving a family name to font bytes
+    static LOADER: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+    /// Families the loader had no usable font for, not asked for again
+    static UNAVAILABLE: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// Fetch the fonts renders name but the engine lacks from `loader`.
+///
+/// `loader(family)` is called with a family name in lower case, e.g.
+/// `"times new roman"`, and returns, or resolves to, the font's bytes as a
+/// `Uint8Array` or `ArrayBuffer` (TrueType, OpenType or WOFF), or `null` when
+/// it has none. `render_markup_async` and `render_form_async` ask it for the
+/// families the document is missing and register what comes back before
+/// rendering, so a module built without fonts (`build_wasm.sh --no-fonts`)
+/// downloads only the fonts its documents use. Families the loader has no
+/// font for are not asked for again until the next `set_font_loader`; failed
+/// fetches are retried with the next render. Pass `null` or nothing to stop
+/// fetching.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// set_font_loader(async family => {
+///   const response = await fetch(`/fonts/${encodeURIComponent(family)}.woff`);
+///   return response.ok ? response.arrayBuffer() : null;
+/// });
+/// const svg = await render_form_async(JSON.stringify(formData));
+/// ```
+#[wasm_bindgen]
+pub fn set_font_loader(loader: Option<js_sys::Function>) {
+    LOADER.with(|slot| *slot.borrow_mut() = loader);
+    UNAVAILABLE.with(|unavailable| unavailable.borrow_mut().clear());
+}
+
+/// Fetch `families` with the font loader and register them, resolving to
+/// the families of the registered fonts. Hosts call it ahead of synchronous
+/// renders, e.g. with the memo template's fonts.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// await load_fonts(['times new roman', 'copperplate cc']);
+/// const pdf = render_form(JSON.stringify(formData), 'pdf');
+/// ```
+#[wasm_bindgen]
+pub async fn load_fonts(families: Vec<String>) -> Result<Vec<String>, JsValue> {
+    let Some(loader) = LOADER.with(|slot| slot.borrow().clone()) else {
+        return Err(JsValue::from_str("No font loader set; call set_font_loader first"));
+    };
+    let mut registered = Vec::new();
+    for family in families {
+        let family = family.to_lowercase();
+        if UNAVAILABLE.with(|unavailable| unavailable.borrow().contains(&family)) {
+            continue;
+        }
+        match fetch(&loader, &family).await {
+            Ok(Some(data)) => match register_font(data) {
+                Ok(families) if families.iter().any(|name| name.to_lowercase() == family) => {
+                    debug!(family = %family, "registered fetched font");
+                    registered.extend(families);
+                }
+                Ok(families) => {
+                    warn!(family = %family, provided = ?families, "fetched font is of another family");
+                    registered.extend(families);
+                    mark_unavailable(family);
+                }
+                Err(e) => {
+                    warn!(family = %family, error = ?e, "fetched font could not be registered");
+                    mark_unavailable(family);
+                }
+            },
+            Ok(None) => mark_unavailable(family),
+            Err(e) => warn!(family = %family, error = ?e, "font loader failed"),
+        }
+    }
+    registered.sort();
+    registered.dedup();
+    Ok(registered)
+}
+
+/// Fetch and register the families `diagnose` reports as unknown, if a font
+/// loader is set
+pub(crate) async fn load_missing_fonts(diagnose: impl FnOnce() -> Vec<Diagnostic>) -> Result<(), JsValue> {
+    if LOADER.with(|slot| slot.borrow().is_none()) {
+        return Ok(());
+    }
+    let missing: Vec<String> = unknown_font_families(&diagnose())
+        .into_iter()
+        .filter(|family| !UNAVAILABLE.with(|unavailable| unavailable.borrow().contains(family)))
+        .collect();
+    if !missing.is_empty() {
+        load_fonts(missing).await?;
+    }
+    Ok(())
+}
+
+/// The bytes `loader` returns or resolves to for `family`, `None` for
+/// `null` and `undefined`
+async fn fetch(loader: &js_sys::Function, family: &str) -> Result<Option<Vec<u8>>, JsValue> {
+    let value = loader.call1(&JsValue::NULL, &JsValue::from_str(family))?;
+    let value = JsFuture::from(js_sys::Promise::resolve(&value)).await?;
+    if value.is_null() || value.is_undefined() {
+        return Ok(None);
+    }
+    if !value.is_instance_of::<js_sys::Uint8Array>() && !value.is_instance_of::<js_sys::ArrayBuffer>() {
+        return Err(JsValue::from_str("Font loader must return a Uint8Array, an ArrayBuffer or null"));
+    }
+    Ok(Some(js_sys::Uint8Array::new(&value).to_vec()))
+}
+
+fn mark_unavailable(family: String) {
+    UNAVAILABLE.with(|unavailable| unavailable.borrow_mut().insert(family));
+}
@@ -0,0 +1,119 @@
+//! Memo forms given as JavaScript objects or JSON strings.
+
+use render_engine::TypstWrapperError;
+use serde_json::{Map, Number, Value};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Largest integer a JS number holds exactly, `Number.MAX_SAFE_INTEGER`
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Deepest nesting of arrays and objects read from a form, `serde_json`'s
+/// recursion limit, so forms the engine would reject fail before they can
+/// exhaust the stack
+const MAX_DEPTH: usize = 128;
+
+/// The JSON text of a form given as an object or a string
+///
+/// Objects are read directly rather than through `JSON.stringify`, the way
+/// `JSON.stringify` would write them: properties set to `undefined` are
+/// left out and objects with a `toJSON` method, such as dates, become what
+/// it returns. Values JSON has no place for, which `JSON.stringify` turns
+/// into `null` or drops without a word, fail naming their field instead.
+/// So do objects that contain themselves and nesting deeper than
+/// [`MAX_DEPTH`], where `JSON.stringify` throws.
+pub(crate) fn form_json(form: &JsValue) -> Result<String, TypstWrapperError> {
+    if let Some(text) = form.as_string() {
+        return Ok(text);
+    }
+    Ok(json_value(form, "", &mut Vec::new())?.to_string())
+}
+
+/// `value` as JSON, `pointer` being its JSON pointer within the form and
+/// `ancestors` the arrays and objects containing it
+fn json_value(value: &JsValue, pointer: &str, ancestors: &mut Vec<JsValue>) -> Result<Value, TypstWrapperError> {
+    // Like `JSON.stringify`, call `toJSON` once and write what it returns as is
+    if value.is_object() {
+        let to_json = js_sys::Reflect::get(value, &JsValue::from_str("toJSON")).unwrap_or(JsValue::UNDEFINED);
+        if let Some(to_json) = to_json.dyn_ref::<js_sys::Function>() {
+            let json = to_json.call0(value).map_err(|_| invalid(pointer, "An object whose toJSON throws"))?;
+            return plain_value(&json, pointer, ancestors);
+        }
+    }
+    plain_value(value, pointer, ancestors)
+}
+
+/// `value` as JSON without calling its `toJSON`
+fn plain_value(value: &JsValue, pointer: &str, ancestors: &mut Vec<JsValue>) -> Result<Value, TypstWrapperError> {
+    if value.is_null() {
+        return Ok(Value::Null);
+    }
+    if let Some(flag) = value.as_bool() {
+        return Ok(Value::Bool(flag));
+    }
+    if let Some(number) = value.as_f64() {
+        if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER {
+            return Ok(Value::from(number as i64));
+        }
+        return Number::from_f64(number).map(Value::Number).ok_or_else(|| invalid(pointer, &number.to_string()));
+    }
+    if let Some(text) = value.as_string() {
+        return Ok(Value::String(text));
+    }
+    if value.is_undefined() {
+        return Err(invalid(pointer, "undefined"));
+    }
+    if value.is_function() {
+        return Err(invalid(pointer, "A function"));
+    }
+    if !value.is_object() {
+        return Err(invalid(pointer, &format!("A {}", value.js_typeof().as_string().unwrap_or_default())));
+    }
+    if ancestors.iter().any(|ancestor| js_sys::Object::is(ancestor, value)) {
+        return Err(field_error(pointer, "Object contains itself, so it has no JSON form".to_string()));
+    }
+    if ancestors.len() >= MAX_DEPTH {
+        return Err(field_error(pointer, format!("Form nests deeper than {} levels", MAX_DEPTH)));
+    }
+    ancestors.push(value.clone());
+    let json = match value.dyn_ref::<js_sys::Array>() {
+        Some(array) => array
+            .iter()
+            .enumerate()
+            .map(|(index, item)| json_value(&item, &format!("{}/{}", pointer, index), ancestors))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        None => object_value(value, pointer, ancestors),
+    };
+    ancestors.pop();
+    json
+}
+
+/// The error for a value JSON cannot hold, described by `what`, at `pointer`
+fn invalid(pointer: &str, what: &str) -> TypstWrapperError {
+    field_error(pointer, format!("{} is not a JSON value", what))
+}
+
+/// A validation error with `message` about the field at `pointer`
+fn field_error(pointer: &str, message: String) -> TypstWrapperError {
+    let error = TypstWrapperError::Validation(message);
+    match pointer {
+        "" => error,
+        field => TypstWrapperError::Field { field: field.to_string(), op: None, error: Box::new(error) },
+    }
+}
+
+/// The object `value` as JSON, skipping properties set to `undefined`
+fn object_value(value: &JsValue, pointer: &str, ancestors: &mut Vec<JsValue>) -> Result<Value, TypstWrapperError> {
+    let mut object = Map::new();
+    for entry in js_sys::Object::entries(value.unchecked_ref()).iter() {
+        let entry: js_sys::Array = entry.unchecked_into();
+        let (key, item) = (entry.get(0).as_string().unwrap_or_default(), entry.get(1));
+        if item.is_undefined() {
+            continue;
+        }
+        let field = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+        object.insert(key, json_value(&item, &field, ancestors)?);
+    }
+    Ok(Value::Object(object))
+}
@@ -0,0 +1,86 @@
+//! Blob, download and data URL helpers for browser apps.
+//!
+//! These wrap the byte-returning render functions so apps don't each
+//! rewrite the same `Blob`/object URL boilerplate. The DOM parts live in
+//! `js/helpers.js`.
+
+use render_engine::{render_form as engine_render_form, OutputFormat, RenderConfig};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen(module = "/js/helpers.js")]
+extern "C" {
+    /// A browser `Blob`
+    #[wasm_bindgen(typescript_type = "Blob")]
+    pub type Blob;
+
+    #[wasm_bindgen(js_name = bytesToBlob)]
+    fn bytes_to_blob(bytes: &[u8], mime_type: &str) -> Blob;
+
+    #[wasm_bindgen(js_name = downloadBlob, catch)]
+    fn download_blob(blob: &Blob, filename: &str) -> Result<(), JsValue>;
+}
+
+/// Render a memo form to a `Blob` of the given format ("pdf" by default, or
+/// "svg" for the first page).
+///
+/// `form` may be the form object itself or its JSON string.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const blob = renderFormToBlob(formData);
+/// preview.src = URL.createObjectURL(blob);
+/// ```
+#[wasm_bindgen(js_name = renderFormToBlob)]
+pub fn render_form_to_blob(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, format: Option<String>) -> Result<Blob, JsValue> {
+    let (format, mime_type) = match format.as_deref() {
+        Some("svg") | Some("SVG") => (OutputFormat::Svg, "image/svg+xml"),
+        _ => (OutputFormat::Pdf, "application/pdf"),
+    };
+    let pages = crate::form_json(&form)
+        .and_then(|input_json| engine_render_form(&input_json, Some(RenderConfig { format, ..Default::default() })))
+        .map_err(|e| JsValue::from_str(&format!("Form render failed: {:?}", e)))?;
+    let page = pages
+        .first()
+        .ok_or_else(|| JsValue::from_str("Error: No pages generated"))?;
+    Ok(bytes_to_blob(page, mime_type))
+}
+
+/// Render a memo form to PDF and let the browser download it as `filename`
+/// (default `memo.pdf`).
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// saveButton.onclick = () => downloadPdf(formData, `${formData.subject}.pdf`);
+/// ```
+#[wasm_bindgen(js_name = downloadPdf)]
+pub fn download_pdf(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, filename: Option<String>) -> Result<(), JsValue> {
+    let blob = render_form_to_blob(form, Some("pdf".to_string()))?;
+    download_blob(&blob, filename.as_deref().unwrap_or("memo.pdf"))
+}
+
+/// Turn an SVG page (the bytes returned by the render functions, or a
+/// string) into a `data:` URL usable as an `<img>` source or CSS background.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// img.src = svgToDataUrl(render_form(JSON.stringify(formData), 'svg'));
+/// ```
+#[wasm_bindgen(js_name = svgToDataUrl)]
+pub fn svg_to_data_url(svg: JsValue) -> Result<String, JsValue> {
+    let text = match svg.as_string() {
+        Some(text) => text,
+        None => {
+            let bytes = svg
+                .dyn_into::<js_sys::Uint8Array>()
+                .map_err(|_| JsValue::from_str("Expected SVG as a string or Uint8Array"))?
+                .to_vec();
+            String::from_utf8(bytes).map_err(|_| JsValue::from_str("SVG is not valid UTF-8"))?
+        }
+    };
+    let encoded: String = js_sys::encode_uri_component(&text).into();
+    Ok(format!("data:image/svg+xml;charset=utf-8,{}", encoded))
+}
@@ -0,0 +1,170 @@
+//! Stepped renders for hosts that must yield to their event loop.
+
+use render_engine::{
+    diagnose_form, diagnose_markup, OutputFormat, RenderConfig, RenderJob as EngineRenderJob, TypstWrapperError,
+};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::font_loader;
+use crate::form_input::form_json;
+use crate::observer::aborted;
+
+/// A render performed in short steps.
+///
+/// Cloudflare Workers, Deno Deploy and busy UI threads cannot block on one
+/// long render. A `RenderJob` prepares the sources, compiles, and exports one
+/// SVG page per call to `step()`, so the host can yield in between. PDF output
+/// is exported in a single step.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const job = RenderJob.form(formData, 'pdf');
+/// let pdf;
+/// while (!(pdf = job.step())) {
+///   await new Promise(resolve => setTimeout(resolve, 0));
+/// }
+/// ```
+#[wasm_bindgen(js_name = RenderJob)]
+pub struct WasmRenderJob {
+    job: EngineRenderJob,
+    kind: &'static str,
+}
+
+#[wasm_bindgen(js_class = RenderJob)]
+impl WasmRenderJob {
+    /// Prepare a stepped render of Typst markup ("svg" by default, or "pdf")
+    pub fn markup(markup: &str, format: Option<String>) -> WasmRenderJob {
+        Self {
+            job: EngineRenderJob::markup(markup, Some(config(format))),
+            kind: "Markup",
+        }
+    }
+
+    /// Prepare a stepped render of a memo form (object or JSON string)
+    pub fn form(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, format: Option<String>) -> Result<WasmRenderJob, JsValue> {
+        let input_json = form_json(&form).map_err(|e| JsValue::from_str(&format!("Form render failed: {:?}", e)))?;
+        Ok(Self {
+            job: EngineRenderJob::form(&input_json, Some(config(format))),
+            kind: "Form",
+        })
+    }
+
+    /// Whether the job has produced its output or failed
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.job.is_finished()
+    }
+
+    /// Perform the next step. Returns `undefined` while work remains and the
+    /// first page (SVG) or the document (PDF) once the render is complete.
+    /// Throws on render errors and when called after the job finished.
+    pub fn step(&mut self) -> Result<Option<Vec<u8>>, JsValue> {
+        if self.job.is_finished() {
+            return Err(JsValue::from_str("Render job already finished"));
+        }
+        match self.job.step() {
+            Ok(None) => Ok(None),
+            Ok(Some(output)) => output
+                .pages
+                .into_iter()
+                .next()
+                .map(Some)
+                .ok_or_else(|| JsValue::from_str("Error: No pages generated")),
+            Err(e) => Err(JsValue::from_str(&format!("{} render failed: {:?}", self.kind, e))),
+        }
+    }
+}
+
+/// Render Typst markup without blocking the event loop ("svg" by default,
+/// "pdf" or "svg-zip").
+///
+/// Runs a `RenderJob` to completion, yielding to the event loop after
+/// preparing the sources, after compiling and after each SVG page, so
+/// input and animations keep being handled during long renders. Resolves
+/// with the same bytes as `render_markup`. With a font loader set, the
+/// fonts the markup names but the engine lacks are fetched first (see
+/// `set_font_loader`).
+///
+/// Aborting `signal`, an `AbortSignal`, abandons the render at its next
+/// step, which rejects with "Render cancelled", so a preview the user has
+/// typed past stops using the thread.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// let controller = new AbortController();
+/// editor.on('change', async () => {
+///   controller.abort();
+///   controller = new AbortController();
+///   preview.innerHTML = new TextDecoder().decode(
+///     await render_markup_async(editor.getValue(), 'svg', controller.signal));
+/// });
+/// ```
+#[wasm_bindgen]
+pub async fn render_markup_async(
+    markup: String,
+    format: Option<String>,
+    signal: Option<js_sys::Object>,
+) -> Result<Vec<u8>, JsValue> {
+    font_loader::load_missing_fonts(|| diagnose_markup(&markup)).await?;
+    run(WasmRenderJob::markup(&markup, format), signal).await
+}
+
+/// Render a memo form (object or JSON string) without blocking the event
+/// loop, see `render_markup_async`
+#[wasm_bindgen]
+pub async fn render_form_async(
+    #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue,
+    format: Option<String>,
+    signal: Option<js_sys::Object>,
+) -> Result<Vec<u8>, JsValue> {
+    let input_json = form_json(&form).map_err(|e| JsValue::from_str(&format!("Form render failed: {:?}", e)))?;
+    font_loader::load_missing_fonts(|| diagnose_form(&input_json)).await?;
+    let job = EngineRenderJob::form(&input_json, Some(config(format)));
+    run(WasmRenderJob { job, kind: "Form" }, signal).await
+}
+
+/// Step `job` to completion, yielding to the event loop between steps,
+/// unless `signal` is aborted before a step
+async fn run(mut job: WasmRenderJob, signal: Option<js_sys::Object>) -> Result<Vec<u8>, JsValue> {
+    loop {
+        if signal.as_ref().is_some_and(|signal| aborted(signal)) {
+            return Err(JsValue::from_str(&format!("{} render failed: {}", job.kind, TypstWrapperError::Cancelled)));
+        }
+        if let Some(output) = job.step()? {
+            return Ok(output);
+        }
+        yield_to_event_loop().await;
+    }
+}
+
+/// Resolve on a later turn of the event loop, after pending input, timers
+/// and rendering. Hosts without `setTimeout` only yield to queued
+/// microtasks.
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let global = js_sys::global();
+        let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|function| function.dyn_into::<js_sys::Function>().ok());
+        let _ = match set_timeout {
+            Some(set_timeout) => set_timeout.call2(&global, &resolve, &JsValue::from(0)),
+            None => resolve.call0(&JsValue::UNDEFINED),
+        };
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+/// Parse the optional format parameter (case-insensitive, defaults to SVG)
+pub(crate) fn config(format: Option<String>) -> RenderConfig {
+    RenderConfig {
+        format: [OutputFormat::Pdf, OutputFormat::SvgZip]
+            .into_iter()
+            .find(|candidate| format.as_deref().is_some_and(|format| format.eq_ignore_ascii_case(candidate.name())))
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
@@ -7,8 +7,50 @@
 //! 
 //! - Render arbitrary Typst markup to SVG or PDF
 //! - Render structured memo forms from JSON input
-//! - Debug logging support (enabled with "debug" feature)
+//! - SVG pages as strings (`render_markup_svg_strings`,
+//!   `render_form_svg_strings`) and every page of a markup render as bytes
+//!   (`render_markup_pages`)
+//! - Render results with the pages, page count, format, compile warnings
+//!   and timings (`RenderResult`, from `render_markup_result` and
+//!   `render_form_result`)
+//! - A report of the engine's formats, fonts, templates, packages and
+//!   optional features (`capabilities`)
+//! - Structured logging forwarded to the console or a host callback
+//!   (`init_logging`, automatic at debug level with the "debug" feature)
 //! - Optimized for web deployment with wasm-bindgen
+//! - Multi-threaded SVG export and font parsing on a Web Worker pool
+//!   (enabled with the "wasm-threads" feature, see below)
+//! - `renderFormToBlob`, `downloadPdf` and `svgToDataUrl` helpers for
+//!   browser apps (enabled with the default "js-helpers" feature)
+//! - Persistent render cache hooks for IndexedDB or other host storage
+//!   (`set_cache_provider`) and an in-memory cache of recent renders
+//!   (`set_output_cache_limits`)
+//! - Stepped renders (`RenderJob`), renders that yield to the event loop
+//!   (`render_markup_async`, `render_form_async`) and can be abandoned with
+//!   an `AbortSignal`, and a compatibility build for Cloudflare Workers and
+//!   Deno (see below)
+//! - A render queue (`RenderQueue`) that renders previews before exports and
+//!   drops previews superseded by newer ones
+//! - Render sessions (`RenderSession`) that keep the Typst world of the last
+//!   render, so live previews re-render only what an edit changed
+//! - Output hashes for deduplicating stored renders (`hash_markup_render`,
+//!   `hash_form_render`)
+//! - Named memo templates uploaded and activated at runtime (`set_template`,
+//!   `use_template`, `reset_template`)
+//! - The generated Typst source of a form render (`generate_typst`)
+//! - Source maps from rendered pages back to form fields and Delta ops
+//!   (`form_source_map`)
+//! - Schema validation with machine-readable errors (`validate_form`)
+//! - The memo form embedded in a rendered PDF (`extract_form`)
+//! - Memo dates in the user's time zone from a host clock (`set_clock`)
+//! - Files the engine does not have resolved by the host
+//!   (`set_file_resolver`)
+//! - Images and other files registered at runtime (`register_asset`,
+//!   `unregister_asset`)
+//! - Fonts registered at runtime (`register_font`, `unregister_font`) and
+//!   the families and faces renders can select (`list_fonts`)
+//! - Fonts fetched on demand from a host callback (`set_font_loader`,
+//!   `load_fonts`)
 //! 
 //! ## Usage
 //! 
@@ -31,42 +73,87 @@
 //! };
 //! const pdf = render_form(JSON.stringify(formData), 'pdf');
 //! ```
+//! 
+//! ## Threads
+//! 
+//! Builds with the "wasm-threads" feature (`build_wasm.sh --threads`) export
+//! `initThreadPool`. They require a cross-origin isolated page (COOP/COEP
+//! headers) so `SharedArrayBuffer` is available. Start the pool once before
+//! rendering:
+//! 
+//! ```javascript
+//! await init();
+//! if (crossOriginIsolated) {
+//!   await initThreadPool(navigator.hardwareConcurrency);
+//! }
+//! ```
+//! 
+//! ## Workers and Deno
+//! 
+//! `build_wasm.sh --runtime workers` (or `deno`) builds without default
+//! features, so no panic hook is installed, and emits bindings for that
+//! runtime. Renders read the time from `Date.now()` unless `set_clock`
+//! installs a clock, and `RenderJob` splits a render into steps so request
+//! handlers can yield between them and stay within per-turn CPU limits.
+//! `compat/` holds a smoke test for each runtime.
+//! 
+//! ## Fonts
+//! 
+//! Times, Times New Roman, Arial and Copperplate CC are embedded by the
+//! default "fonts" feature and account for megabytes of the module. Hosts
+//! that upload their fonts with `register_font` build without them
+//! (`build_wasm.sh --no-fonts`), or keep single families with the
+//! "font-times", "font-arial" and "font-copperplate" features. Until the
+//! template's fonts are registered, text falls back to the fonts the module
+//! has, and without any no text is rendered. `set_font_loader` fetches the
+//! missing fonts on demand:
+//! 
+//! ```javascript
+//! set_font_loader(family => fetch(`/fonts/${family}.woff`).then(r => r.ok ? r.arrayBuffer() : null));
+//! const svg = await render_form_async(JSON.stringify(formData));
+//! ```
 
-use wasm_bindgen::prelude::*;
-use render_engine::{render_markup as engine_render_markup, render_form as engine_render_form, RenderConfig, OutputFormat};
-
-/// Import the `console.log` function from the `console` module.
-/// Only available in debug builds to reduce binary size in production.
-#[cfg(feature = "debug")]
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
+mod cache;
+mod clock;
+mod font_loader;
+mod form_input;
+#[cfg(feature = "js-helpers")]
+mod helpers;
+mod job;
+mod logging;
+mod observer;
+mod queue;
+mod resolver;
+mod result;
+mod session;
+mod template;
+mod types;
+#[cfg(feature = "signing")]
+mod signing;
 
-/// Macro for convenient console logging in debug builds.
-/// 
-/// In debug builds, this forwards to `console.log()` for debugging.
-/// In release builds, this becomes a no-op to minimize overhead.
-/// 
-/// # Usage
-/// 
-/// ```rust
-/// console_log!("Debug message: {}", value);
-/// ```
-#[cfg(feature = "debug")]
-#[macro_export]
-macro_rules! console_log {
-    ($($t:tt)*) => (unsafe { log(&format_args!($($t)*).to_string()) })
-}
+pub use cache::{clear_output_cache, set_cache_provider, set_output_cache_limits};
+pub use clock::set_clock;
+pub use font_loader::{load_fonts, set_font_loader};
+#[cfg(feature = "js-helpers")]
+pub use helpers::{download_pdf, render_form_to_blob, svg_to_data_url};
+pub use job::{render_form_async, render_markup_async, WasmRenderJob};
+pub use queue::WasmRenderQueue;
+pub use resolver::set_file_resolver;
+pub use result::{render_form_result, render_markup_result, RenderResult};
+pub use session::WasmRenderSession;
+pub use template::{active_template, list_templates, remove_template, reset_template, set_template, use_template};
+#[cfg(feature = "signing")]
+pub use signing::{sign_pdf, stamp_form_signature, PdfSignature};
 
-/// No-op console logging for release builds.
-/// This ensures debug logging has zero runtime cost in production.
-#[cfg(not(feature = "debug"))]
-#[macro_export]
-macro_rules! console_log {
-    ($($t:tt)*) => {}
-}
+use tracing::debug;
+#[cfg(all(feature = "wasm-threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use serde::Serialize;
+use form_input::form_json;
+use render_engine::assets::get_asset_manifest;
+use render_engine::{render_markup as engine_render_markup, render_form as engine_render_form, diagnose_markup as engine_diagnose_markup, diagnose_form as engine_diagnose_form, prewarm as engine_prewarm, export_template_artifact as engine_export_template_artifact, import_template_artifact as engine_import_template_artifact, render_markup_observed as engine_render_markup_observed, render_form_observed as engine_render_form_observed, set_metrics_callback as engine_set_metrics_callback, clear_metrics_callback as engine_clear_metrics_callback, register_letterhead as engine_register_letterhead, render_batch as engine_render_batch, render_form_eml as engine_render_form_eml, EmlOptions, FormJob, set_registration_limits as engine_set_registration_limits, RegistrationLimits, RenderConfig, RenderObserver, OutputFormat};
 
 /// Initialize the WASM module with enhanced error handling.
 /// 
@@ -78,10 +165,58 @@ macro_rules! console_log {
 /// - Installs `console_error_panic_hook` for readable panic messages in browser console
 /// - Only active when the "console_error_panic_hook" feature is enabled
 /// - Improves developer experience by showing Rust panic traces in JavaScript
+/// - With the "debug" feature, forwards engine logs to the console at debug level
 #[wasm_bindgen(start)]
 pub fn main() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
+
+    #[cfg(feature = "debug")]
+    let _ = logging::init(tracing::Level::DEBUG, None);
+}
+
+/// Forward render engine logs to the console or a host callback.
+/// 
+/// The engine instruments parsing, compilation and export with `tracing`
+/// spans and events. Without a callback, events at or above `level` go to
+/// `console.error`/`warn`/`info`/`debug`. With a callback, each event is
+/// passed to it as an object instead:
+/// 
+/// ```json
+/// {
+///   "level": "DEBUG",
+///   "target": "render_engine::typst_wrapper",
+///   "message": "compiled",
+///   "fields": { "pages": "2" },
+///   "spans": ["render_form", "compile"]
+/// }
+/// ```
+/// 
+/// Field values are strings. Calling this again changes the level and
+/// callback.
+/// 
+/// # Parameters
+/// 
+/// - `level`: `"error"`, `"warn"`, `"info"` (default), `"debug"` or `"trace"`
+/// - `callback`: Optional function receiving each event object
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// // Log everything to the devtools console
+/// init_logging('debug');
+/// 
+/// // Ship warnings and errors to telemetry
+/// init_logging('warn', event => telemetry.log(event.level, event.message, event.fields));
+/// ```
+#[wasm_bindgen]
+pub fn init_logging(level: Option<String>, callback: Option<js_sys::Function>) -> Result<(), JsValue> {
+    let level = match level.as_deref() {
+        None => tracing::Level::INFO,
+        Some(name) => logging::parse_level(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown log level: {}", name)))?,
+    };
+    logging::init(level, callback).map_err(|e| JsValue::from_str(&e))
 }
 
 /// Render arbitrary Typst markup to SVG or PDF format.
@@ -92,11 +227,15 @@ pub fn main() {
 /// # Parameters
 /// 
 /// - `markup`: Typst markup code as a string (e.g., "= Title\nContent here")
-/// - `format`: Output format, either "pdf" or "svg" (defaults to SVG if not specified)
+/// - `format`: Output format, "pdf", "svg" or "svg-zip" (defaults to SVG if not specified).
+///   "svg-zip" returns one ZIP with every page (`page-001.svg`, ...) and a
+///   `manifest.json` listing each page's file and size in points.
 /// 
 /// # Returns
 /// 
 /// Returns `Ok(Vec<u8>)` containing the rendered document bytes, or `Err(JsValue)` on failure.
+/// SVG output is the first page only; `render_markup_pages` returns every
+/// page.
 /// 
 /// # JavaScript Usage
 /// 
@@ -125,16 +264,18 @@ pub fn render_markup(markup: &str, format: Option<String>) -> Result<Vec<u8>, Js
     // Parse format parameter - defaults to SVG for web compatibility
     let output_format = match format.as_deref() {
         Some("pdf") => OutputFormat::Pdf,
+        Some("svg-zip") => OutputFormat::SvgZip,
         _ => OutputFormat::Svg,
     };
     
     let config = RenderConfig {
         format: output_format,
+        ..Default::default()
     };
     
     match engine_render_markup(markup, Some(config)) {
         Ok(pages) => {
-            console_log!("Markup render successful! Generated {} page(s)", pages.len());
+            debug!(pages = pages.len(), "markup render succeeded");
             
             // Return the first page as bytes (SVG text or PDF binary data)
             if !pages.is_empty() {
@@ -144,12 +285,64 @@ pub fn render_markup(markup: &str, format: Option<String>) -> Result<Vec<u8>, Js
             }
         }
         Err(e) => {
-            console_log!("Markup render failed: {:?}", e);
+            debug!(error = %e, "markup render failed");
             Err(JsValue::from_str(&format!("Markup render failed: {:?}", e)))
         }
     }
 }
 
+/// Render Typst markup, returning every page.
+/// 
+/// Takes the same `format` as `render_markup` and returns an array with one
+/// `Uint8Array` per page: one per SVG page, or a single PDF or SVG ZIP.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const pages = render_markup_pages(markup);
+/// const decoder = new TextDecoder();
+/// showPage = (index) => { preview.innerHTML = decoder.decode(pages[index]); };
+/// pageCount.textContent = pages.length;
+/// ```
+#[wasm_bindgen]
+pub fn render_markup_pages(markup: &str, format: Option<String>) -> Result<js_sys::Array, JsValue> {
+    let output_format = match format.as_deref() {
+        Some("pdf") => OutputFormat::Pdf,
+        Some("svg-zip") => OutputFormat::SvgZip,
+        _ => OutputFormat::Svg,
+    };
+    let config = RenderConfig {
+        format: output_format,
+        ..Default::default()
+    };
+    let pages = engine_render_markup(markup, Some(config)).map_err(|e| {
+        debug!(error = %e, "markup render failed");
+        JsValue::from_str(&format!("Markup render failed: {:?}", e))
+    })?;
+    debug!(pages = pages.len(), "markup render succeeded");
+    if pages.is_empty() {
+        return Err(JsValue::from_str("Error: No pages generated"));
+    }
+    Ok(pages.iter().map(|page| js_sys::Uint8Array::from(page.as_slice())).collect())
+}
+
+/// Render Typst markup to SVG, returning every page as a string.
+/// 
+/// Saves decoding the bytes of `render_markup`, and returns all pages
+/// rather than the first.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const pages = render_markup_svg_strings('= Hello\nThis is a test document.');
+/// preview.innerHTML = pages.join('');
+/// ```
+#[wasm_bindgen]
+pub fn render_markup_svg_strings(markup: &str) -> Result<Vec<String>, JsValue> {
+    render_engine::render_markup_svg_strings(markup, None)
+        .map_err(|e| JsValue::from_str(&format!("Markup render failed: {:?}", e)))
+}
+
 /// Render structured form data to official memorandum format.
 /// 
 /// This function takes JSON input conforming to the official memorandum schema
@@ -158,8 +351,11 @@ pub fn render_markup(markup: &str, format: Option<String>) -> Result<Vec<u8>, Js
 /// 
 /// # Parameters
 /// 
-/// - `input_json`: JSON string matching the official memorandum schema
-/// - `format`: Output format, "pdf" or "svg" (case-insensitive, defaults to SVG)
+/// - `form`: The form object, or its JSON string, matching the official
+///   memorandum schema. Objects are read directly, and values JSON cannot
+///   hold, such as functions or `NaN`, fail naming their field.
+/// - `format`: Output format, "pdf", "svg" or "svg-zip" (defaults to SVG);
+///   see `render_markup` for "svg-zip"
 /// 
 /// # JSON Schema
 /// 
@@ -202,7 +398,7 @@ pub fn render_markup(markup: &str, format: Option<String>) -> Result<Vec<u8>, Js
 ///   }
 /// };
 /// 
-/// const pdfBytes = render_form(JSON.stringify(formData), 'pdf');
+/// const pdfBytes = render_form(formData, 'pdf');
 /// const svgBytes = render_form(JSON.stringify(formData), 'svg');
 /// ```
 /// 
@@ -215,23 +411,22 @@ pub fn render_markup(markup: &str, format: Option<String>) -> Result<Vec<u8>, Js
 /// - Template rendering errors
 /// - Empty document generation
 #[wasm_bindgen]
-pub fn render_form(input_json: &str, format: Option<String>) -> Result<Vec<u8>, JsValue> {
+pub fn render_form(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, format: Option<String>) -> Result<Vec<u8>, JsValue> {
     // Parse format parameter - case insensitive, defaults to SVG
     let output_format = match format.as_deref() {
         Some("pdf") | Some("PDF") => OutputFormat::Pdf,
+        Some("svg-zip") | Some("SVG-ZIP") => OutputFormat::SvgZip,
         _ => OutputFormat::Svg,
     };
     
     let config = RenderConfig {
         format: output_format,
+        ..Default::default()
     };
     
-    console_log!("Attempting to render form with JSON: {}", input_json);
-    console_log!("Output format: {:?}", output_format);
-    
-    match engine_render_form(input_json, Some(config)) {
+    match form_json(&form).and_then(|input_json| engine_render_form(&input_json, Some(config))) {
         Ok(pages) => {
-            console_log!("Form render successful! Generated {} page(s)", pages.len());
+            debug!(pages = pages.len(), "form render succeeded");
             
             // Return the first page as bytes
             if !pages.is_empty() {
@@ -241,10 +436,881 @@ pub fn render_form(input_json: &str, format: Option<String>) -> Result<Vec<u8>,
             }
         }
         Err(e) => {
-            console_log!("Form render failed: {:?}", e);
+            debug!(error = %e, "form render failed");
             Err(JsValue::from_str(&format!("Form render failed: {:?}", e)))
         }
     }
 }
 
+/// Render a memo form (object or JSON string) to SVG, returning every page
+/// as a string.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const pages = render_form_svg_strings(formData);
+/// pages.forEach((svg, i) => { previews[i].innerHTML = svg; });
+/// ```
+#[wasm_bindgen]
+pub fn render_form_svg_strings(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<Vec<String>, JsValue> {
+    form_json(&form)
+        .and_then(|input_json| render_engine::render_form_svg_strings(&input_json, None))
+        .map_err(|e| JsValue::from_str(&format!("Form render failed: {:?}", e)))
+}
+
+/// Render many memo forms into one ZIP archive.
+/// 
+/// Each job is an object `{ fileName, form, format }`: `form` is the form
+/// object or its JSON string and `format` is "svg" (default) or "pdf". The
+/// archive holds each output under its `fileName`; multi-page SVG renders are
+/// stored as `<name>-001.svg`, `<name>-002.svg`, ... The call fails naming
+/// the first job that could not be rendered.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const zip = render_batch(memos.map(m => ({ fileName: `${m.id}.pdf`, form: m, format: 'pdf' })));
+/// const blob = new Blob([zip], { type: 'application/zip' });
+/// ```
+#[wasm_bindgen]
+pub fn render_batch(#[wasm_bindgen(unchecked_param_type = "BatchJob[]")] jobs: Vec<JsValue>) -> Result<Vec<u8>, JsValue> {
+    let get = |job: &JsValue, name: &str| js_sys::Reflect::get(job, &JsValue::from_str(name));
+    let jobs = jobs
+        .iter()
+        .map(|job| {
+            let file_name = get(job, "fileName")?
+                .as_string()
+                .ok_or_else(|| JsValue::from_str("Batch job needs a fileName string"))?;
+            let format = match get(job, "format")?.as_string().as_deref() {
+                Some("pdf") | Some("PDF") => OutputFormat::Pdf,
+                _ => OutputFormat::Svg,
+            };
+            let json = form_json(&get(job, "form")?).map_err(|error| {
+                let error = render_engine::TypstWrapperError::Batch { file_name: file_name.clone(), error: Box::new(error) };
+                JsValue::from_str(&format!("Batch render failed: {}", error))
+            })?;
+            Ok(FormJob {
+                file_name,
+                json,
+                config: Some(RenderConfig { format, ..Default::default() }),
+            })
+        })
+        .collect::<Result<Vec<_>, JsValue>>()?;
+    engine_render_batch(jobs).map_err(|e| JsValue::from_str(&format!("Batch render failed: {}", e)))
+}
+
+/// Render a memo form (object or JSON string) to PDF and return an email
+/// (`.eml`) with it attached.
+/// 
+/// The subject is taken from the memo. `options` may set `from`, `to` and
+/// `cc` (arrays of mailboxes), `date` (an RFC 5322 date; the submission
+/// server adds one when omitted), `body` (plain text) and `filename` (default
+/// `memo.pdf`).
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const eml = render_form_eml(formData, {
+///   from: 'Jane Doe <jane.doe@example.mil>',
+///   to: ['ops@example.mil'],
+///   date: new Date().toUTCString(),
+/// });
+/// await fetch('/api/send', { method: 'POST', body: eml });
+/// ```
+#[wasm_bindgen]
+pub fn render_form_eml(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+    let get = |name: &str| -> JsValue {
+        if options.is_object() {
+            js_sys::Reflect::get(&options, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+        } else {
+            JsValue::UNDEFINED
+        }
+    };
+    let list = |name: &str| -> Vec<String> {
+        get(name)
+            .dyn_into::<js_sys::Array>()
+            .map(|values| values.iter().filter_map(|value| value.as_string()).collect())
+            .unwrap_or_default()
+    };
+    let options = EmlOptions {
+        from: get("from").as_string(),
+        to: list("to"),
+        cc: list("cc"),
+        date: get("date").as_string(),
+        body: get("body").as_string(),
+        filename: get("filename").as_string(),
+    };
+    form_json(&form)
+        .and_then(|input_json| engine_render_form_eml(&input_json, &options))
+        .map_err(|e| JsValue::from_str(&format!("Email render failed: {:?}", e)))
+}
+
+/// Import a pasted plain-text memo as best-effort form data.
+/// 
+/// Recognizes the letterhead and date, `MEMORANDUM FOR`, `FROM:`,
+/// `SUBJECT:`, `References:`, numbered paragraphs and the signature block.
+/// Returns `{ form, report }` where `report` is
+/// `{ confidence, fields: { [field]: confidence }, warnings: [...] }` with
+/// confidences from 0 to 1. Review the form before rendering it: required
+/// fields that were not found are missing.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const { form, report } = import_text(pastedText);
+/// if (report.confidence < 0.8) showReview(form, report.warnings);
+/// ```
+#[wasm_bindgen]
+pub fn import_text(text: &str) -> Result<JsValue, JsValue> {
+    let result = render_engine::import::import_text(text);
+    let value = serde_json::json!({ "form": result.form, "report": result.report });
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&format!("Import failed: {:?}", e)))
+}
+
+/// Import a Word (.docx) memo as memo form JSON
+///
+/// Returns `{ form, report }` like [`import_text`]. Body paragraphs keep
+/// bold, italic, underline and strikeout; tables and page headers are
+/// skipped. Needs the `docx` feature.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const bytes = new Uint8Array(await file.arrayBuffer());
+/// const { form, report } = import_docx(bytes);
+/// ```
+#[cfg(feature = "docx")]
+#[wasm_bindgen]
+pub fn import_docx(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    use render_engine::import::Importer;
+
+    let result = render_engine::import::DocxImporter
+        .import(bytes)
+        .map_err(|e| JsValue::from_str(&format!("Import failed: {:?}", e)))?;
+    let value = serde_json::json!({ "form": result.form, "report": result.report });
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&format!("Import failed: {:?}", e)))
+}
+
+/// Register a full-page letterhead background for form rendering.
+/// 
+/// Forms select the background by name through the `letterhead-background`
+/// field. It is drawn behind every page at a print-safe strength controlled by
+/// `letterhead-background-opacity` (0 to 1, default 0.3).
+/// 
+/// # Parameters
+/// 
+/// - `name`: Name forms use to reference the background (letters, digits, `-`, `_`, `.`)
+/// - `data`: Image bytes (PNG, JPEG, GIF, WebP or SVG)
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const bytes = new Uint8Array(await (await fetch('/stationery/412tw.png')).arrayBuffer());
+/// register_letterhead('412tw', bytes);
+/// 
+/// const pdfBytes = render_form(JSON.stringify({
+///   ...formData,
+///   "letterhead-background": "412tw"
+/// }), 'pdf');
+/// ```
+#[wasm_bindgen]
+pub fn register_letterhead(name: &str, data: Vec<u8>) -> Result<(), JsValue> {
+    match engine_register_letterhead(name, data) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            debug!(name, error = %e, "letterhead registration failed");
+            Err(JsValue::from_str(&format!("Letterhead registration failed: {:?}", e)))
+        }
+    }
+}
+
+/// Register an image or other file that renders read at `path`, such as a
+/// unit emblem, a signature image or an attachment.
+/// 
+/// Markup reads it at `path`; form bodies use the path from the root,
+/// e.g. `#image("/emblems/unit.png")`. Registering an existing path replaces
+/// the previous file. Files share the registration limits with letterhead
+/// backgrounds, local packages and fonts.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const bytes = new Uint8Array(await (await fetch('/emblems/unit.png')).arrayBuffer());
+/// register_asset('emblems/unit.png', bytes);
+/// const svg = render_markup('#image("emblems/unit.png", alt: "Unit emblem")', 'svg');
+/// ```
+#[wasm_bindgen]
+pub fn register_asset(path: &str, bytes: Vec<u8>) -> Result<(), JsValue> {
+    render_engine::register_asset(path, bytes).map_err(|e| {
+        debug!(path, error = %e, "asset registration failed");
+        JsValue::from_str(&format!("Asset registration failed: {}", e))
+    })
+}
+
+/// Remove a registered file, returning whether it existed.
+#[wasm_bindgen]
+pub fn unregister_asset(path: &str) -> bool {
+    render_engine::unregister_asset(path)
+}
+
+/// Register a font for rendering, returning the font families it holds.
+/// 
+/// `bytes` is a TrueType (`.ttf`), OpenType (`.otf`, also collections) or
+/// WOFF font; WOFF2 is not supported. Renders started afterwards can select
+/// the families by name, e.g. `#set text(font: "Corporate Sans")` in markup
+/// or a memo template. Fonts share the registration limits with letterhead
+/// backgrounds and local packages.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const bytes = new Uint8Array(await (await fetch('/fonts/unit-serif.woff')).arrayBuffer());
+/// const families = register_font(bytes); // ["Unit Serif"]
+/// ```
+#[wasm_bindgen]
+pub fn register_font(bytes: &[u8]) -> Result<Vec<String>, JsValue> {
+    render_engine::register_font(bytes.to_vec()).map_err(|e| {
+        debug!(error = %e, "font registration failed");
+        JsValue::from_str(&format!("Font registration failed: {}", e))
+    })
+}
+
+/// Remove the registered fonts of a family (ignoring case), returning
+/// whether there were any.
+#[wasm_bindgen]
+pub fn unregister_font(family: &str) -> bool {
+    render_engine::unregister_font(family)
+}
 
+/// Register a local template package for `#import "@local/<name>:<version>"`.
+/// 
+/// `files` maps paths within the package to their content as a
+/// `Uint8Array` or string, with the `typst.toml` manifest at the root.
+/// Returns the package spec, e.g. `"@local/org-kit:1.0.0"`. Registering the
+/// same name and version again replaces the package.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const spec = register_local_package({
+///   'typst.toml': '[package]\nname = "org-kit"\nversion = "1.0.0"\nentrypoint = "lib.typ"',
+///   'lib.typ': '#let unit = [42nd Wing]',
+/// });
+/// const svg = render_markup(`#import "${spec}": unit\n#unit`, 'svg');
+/// ```
+#[wasm_bindgen]
+pub fn register_local_package(files: js_sys::Object) -> Result<String, JsValue> {
+    render_engine::register_local_package(object_files(&files))
+        .map_err(|e| JsValue::from_str(&format!("Local package registration failed: {}", e)))
+}
+
+/// The entries of an object mapping paths to `Uint8Array` or string content
+pub(crate) fn object_files(files: &js_sys::Object) -> Vec<(String, Vec<u8>)> {
+    js_sys::Object::entries(files)
+        .iter()
+        .map(|entry| {
+            let entry: js_sys::Array = entry.unchecked_into();
+            let path = entry.get(0).as_string().unwrap_or_default();
+            let content = entry.get(1);
+            let data = match content.as_string() {
+                Some(text) => text.into_bytes(),
+                None => js_sys::Uint8Array::new(&content).to_vec(),
+            };
+            (path, data)
+        })
+        .collect()
+}
+
+/// Remove a local package registered with `register_local_package`,
+/// returning whether it was registered.
+#[wasm_bindgen]
+pub fn unregister_local_package(spec: &str) -> bool {
+    render_engine::unregister_local_package(spec)
+}
+
+/// List the assets bundled with (or registered into) the render engine.
+/// 
+/// Web UIs can use this to show what is available and to validate user
+/// references (fonts, seals, letterhead backgrounds) before rendering, and
+/// build tooling to see how much of the module the embedded assets take.
+/// 
+/// # Returns
+/// 
+/// An object with the following shape:
+/// 
+/// ```json
+/// {
+///   "assets": [{
+///     "name": "dod_seal.gif", "kind": "image", "path": "memo-loader/assets/dod_seal.gif",
+///     "mime": "image/gif", "size": 41231
+///   }],
+///   "embedded_size": 2153604,
+///   "fonts": ["Arial", "Copperplate CC", "Times New Roman"],
+///   "seals": ["dod_seal.gif"],
+///   "letterheads": ["412tw"]
+/// }
+/// ```
+/// 
+/// `kind` is one of `template`, `schema`, `package`, `font`, `image`,
+/// `letterhead` or `file`. `path` is the source path of an embedded asset
+/// and the path documents read a registered one from. `size` and
+/// `embedded_size`, the total of the embedded assets, are in bytes.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const manifest = list_assets();
+/// if (!manifest.letterheads.includes(form["letterhead-background"])) {
+///   showError('Unknown letterhead');
+/// }
+/// ```
+#[wasm_bindgen]
+pub fn list_assets() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&get_asset_manifest())
+        .map_err(|e| JsValue::from_str(&format!("Asset listing failed: {:?}", e)))
+}
+
+/// Configure the caps applied to runtime asset registration.
+/// 
+/// Registrations that would exceed a cap are rejected with a descriptive error,
+/// protecting the module's memory from oversized or excessive uploads.
+/// Omitted parameters fall back to the engine defaults (16 MiB per asset,
+/// 64 MiB total, 64 assets).
+/// 
+/// # Parameters
+/// 
+/// - `max_asset_bytes`: Maximum size of a single registered asset
+/// - `max_total_bytes`: Maximum combined size of all registered assets
+/// - `max_count`: Maximum number of registered assets
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// // Allow at most 8 letterheads of 2 MiB each
+/// set_registration_limits(2 * 1024 * 1024, 16 * 1024 * 1024, 8);
+/// ```
+#[wasm_bindgen]
+pub fn set_registration_limits(
+    max_asset_bytes: Option<usize>,
+    max_total_bytes: Option<usize>,
+    max_count: Option<usize>,
+) {
+    let defaults = RegistrationLimits::default();
+    engine_set_registration_limits(RegistrationLimits {
+        max_asset_bytes: max_asset_bytes.unwrap_or(defaults.max_asset_bytes),
+        max_total_bytes: max_total_bytes.unwrap_or(defaults.max_total_bytes),
+        max_count: max_count.unwrap_or(defaults.max_count),
+    });
+}
+
+/// Collect editor diagnostics for Typst markup.
+/// 
+/// Compiles the markup without exporting it and returns every error and
+/// warning, ready for underlining in an editor. An empty array means the
+/// markup compiles cleanly.
+/// 
+/// # Returns
+/// 
+/// An array of diagnostics with the following shape:
+/// 
+/// ```json
+/// [{
+///   "range": { "start": { "line": 2, "character": 12 }, "end": { "line": 2, "character": 13 } },
+///   "severity": "error",
+///   "source": "typst",
+///   "message": "expected color, gradient, or tiling, found integer",
+///   "hints": [],
+///   "related": [{ "message": "...", "path": "main.typ", "range": { ... } }]
+/// }]
+/// ```
+/// 
+/// Lines and characters are zero-based; characters count UTF-16 code units,
+/// matching JavaScript string indices and the Language Server Protocol.
+/// `severity` is `error` or `warning`; `source` is `typst`, `schema` or `delta`.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const markers = diagnose_markup(editor.getValue()).map(d => ({
+///   startLineNumber: d.range.start.line + 1,
+///   startColumn: d.range.start.character + 1,
+///   endLineNumber: d.range.end.line + 1,
+///   endColumn: d.range.end.character + 1,
+///   message: d.message,
+/// }));
+/// ```
+#[wasm_bindgen(unchecked_return_type = "Diagnostic[]")]
+pub fn diagnose_markup(markup: &str) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&engine_diagnose_markup(markup))
+        .map_err(|e| JsValue::from_str(&format!("Diagnostics failed: {:?}", e)))
+}
+
+/// Collect editor diagnostics for a memo form (object or JSON string).
+/// 
+/// Returns diagnostics in the same shape as `diagnose_markup`, with ranges in
+/// the form JSON text (for an object, in its compact JSON). JSON syntax errors, schema violations, unknown
+/// letterhead backgrounds and Delta conversion failures point at the
+/// offending value; Typst errors raised by the memo template point at the
+/// start of the form.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const problems = diagnose_form(jsonEditor.getValue());
+/// submitButton.disabled = problems.some(d => d.severity === 'error');
+/// ```
+#[wasm_bindgen(unchecked_return_type = "Diagnostic[]")]
+pub fn diagnose_form(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<JsValue, JsValue> {
+    let input_json = form_json(&form).map_err(|e| JsValue::from_str(&format!("Diagnostics failed: {}", e)))?;
+    serde_wasm_bindgen::to_value(&engine_diagnose_form(&input_json))
+        .map_err(|e| JsValue::from_str(&format!("Diagnostics failed: {:?}", e)))
+}
+
+/// Generate the Typst source a memo form (object or JSON string) renders as.
+/// 
+/// The preprocessed form, with a Delta body converted to markup, is inlined
+/// into the memo template, so the source can be kept for audit, compiled
+/// elsewhere or inspected when debugging layout. Throws if the form is
+/// invalid.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const source = generate_typst(formData);
+/// await navigator.clipboard.writeText(source);
+/// ```
+#[wasm_bindgen]
+pub fn generate_typst(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<String, JsValue> {
+    form_json(&form)
+        .and_then(|input_json| render_engine::generate_typst(&input_json))
+        .map_err(|e| JsValue::from_str(&format!("Typst generation failed: {}", e)))
+}
+
+/// Fill in the defaults of the fields a memo form (object or JSON string)
+/// leaves out.
+/// 
+/// Returns the form as a JSON string with every schema default added, such
+/// as `"references": null`, the `format` of the body and the color of a
+/// watermark, and `"page-numbering": true` expanded to its settings, so
+/// editors can show the effective document. Throws if the form is invalid.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const effective = JSON.parse(normalize_form(formData));
+/// letterheadPreview.textContent = effective['letterhead-title'];
+/// ```
+#[wasm_bindgen]
+pub fn normalize_form(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<String, JsValue> {
+    form_json(&form)
+        .and_then(|input_json| render_engine::normalize_form(&input_json))
+        .map_err(|e| JsValue::from_str(&format!("Normalization failed: {}", e)))
+}
+
+/// Map the rendered pages of a memo form (object or JSON string) back to the
+/// form.
+/// 
+/// Returns an array with one object per page, `{ width, height, regions }`,
+/// where each region is `{ field, op, x, y, width, height }` in points from
+/// the page's top left corner. `field` is a JSON pointer into the form
+/// (`/subject`, `/memo-for/0`, `/body`) and `op` the index of the Delta op
+/// of a Delta body. Throws if the form is invalid.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const pages = form_source_map(formData);
+/// preview.onclick = (e) => {
+///     const hit = pages[pageIndex].regions.find(r =>
+///         e.x >= r.x && e.x <= r.x + r.width && e.y >= r.y && e.y <= r.y + r.height);
+///     if (hit?.op !== undefined) quill.setSelection(opOffsets[hit.op]);
+///     else if (hit) focusField(hit.field);
+/// };
+/// ```
+#[wasm_bindgen]
+pub fn form_source_map(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<JsValue, JsValue> {
+    let pages = form_json(&form)
+        .and_then(|input_json| render_engine::form_source_map(&input_json))
+        .map_err(|e| JsValue::from_str(&format!("Source map failed: {}", e)))?;
+    serde_wasm_bindgen::to_value(&pages).map_err(|e| JsValue::from_str(&format!("Source map failed: {:?}", e)))
+}
+
+/// Check a memo form (object or JSON string) against the memo schema.
+/// 
+/// Returns `{ valid, errors }` with every schema violation of the form, so
+/// editors can mark all invalid fields before rendering. Each error is
+/// `{ kind, code, message, path, severity }`, with `path` the JSON pointer
+/// of the offending field and `severity` `"error"`; the shape is that of
+/// render errors in the HTTP service. Forms that are not JSON give one
+/// error without a path.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const { valid, errors } = validate_form(formData);
+/// for (const error of errors) {
+///     if (error.code === 'schema_mismatch') markInvalid(error.path, error.message);
+/// }
+/// ```
+#[wasm_bindgen(unchecked_return_type = "{ valid: boolean; errors: FormError[] }")]
+pub fn validate_form(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<JsValue, JsValue> {
+    /// An error payload with the severity diagnostics carry
+    #[derive(Serialize)]
+    struct FormError {
+        #[serde(flatten)]
+        payload: render_engine::ErrorPayload,
+        severity: render_engine::Severity,
+    }
+
+    let payloads: Vec<render_engine::ErrorPayload> = match form_json(&form) {
+        Ok(input_json) => render_engine::form_processor::official_memo_schema_errors(&input_json)
+            .iter()
+            .map(render_engine::ErrorPayload::from)
+            .collect(),
+        Err(e) => vec![render_engine::ErrorPayload::from(&e)],
+    };
+    let errors: Vec<FormError> = payloads
+        .into_iter()
+        .map(|payload| FormError { payload, severity: render_engine::Severity::Error })
+        .collect();
+    let result = js_sys::Object::new();
+    js_sys::Reflect::set(&result, &"valid".into(), &JsValue::from_bool(errors.is_empty()))?;
+    let errors = errors
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&format!("Validation failed: {:?}", e)))?;
+    js_sys::Reflect::set(&result, &"errors".into(), &errors)?;
+    Ok(result.into())
+}
+
+/// Read back the memo form embedded in a PDF rendered with `embed_form`.
+/// 
+/// Returns `{ form, schema_version }`, where `form` is the form JSON string
+/// the PDF was rendered from. Throws if the PDF has no embedded form.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const pdf = new Uint8Array(await file.arrayBuffer());
+/// const { form, schema_version } = extract_form(pdf);
+/// editor.load(JSON.parse(form));
+/// ```
+#[wasm_bindgen]
+pub fn extract_form(pdf: &[u8]) -> Result<JsValue, JsValue> {
+    let embedded = render_engine::embedded_form::extract_form(pdf)
+        .map_err(|e| JsValue::from_str(&format!("Form extraction failed: {}", e)))?;
+    let result = js_sys::Object::new();
+    js_sys::Reflect::set(&result, &"form".into(), &JsValue::from_str(&embedded.form))?;
+    let version = embedded.schema_version.as_deref().map_or(JsValue::NULL, JsValue::from_str);
+    js_sys::Reflect::set(&result, &"schema_version".into(), &version)?;
+    Ok(result.into())
+}
+
+/// Report what this build of the engine can do.
+/// 
+/// Returns `{ version, output_formats, fonts, memo_template,
+/// template_partials, overridden_partials, packages, letterheads, assets,
+/// features }`, where `features` holds booleans such as `pdf_x`, `pdf_a`,
+/// `signing` and `system_fonts`. Feature-detect with it rather than comparing versions.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const { output_formats, features } = capabilities();
+/// exportZipButton.hidden = !output_formats.includes('svg-zip');
+/// signButton.hidden = !features.signing;
+/// ```
+#[wasm_bindgen]
+pub fn capabilities() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&render_engine::capabilities())
+        .map_err(|e| JsValue::from_str(&format!("Capabilities failed: {:?}", e)))
+}
+
+/// List the font families renders can select, with their faces.
+/// 
+/// Returns the embedded families and those added with `register_font`,
+/// sorted by name:
+/// 
+/// ```json
+/// [{ "name": "Times", "faces": [
+///   { "style": "normal", "weight": 400, "stretch": 1, "registered": false },
+///   { "style": "italic", "weight": 700, "stretch": 1, "registered": false }
+/// ] }]
+/// ```
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// fontPicker.replaceChildren(...list_fonts().map(family => new Option(family.name)));
+/// ```
+#[wasm_bindgen]
+pub fn list_fonts() -> Result<JsValue, JsValue> {
+    render_engine::list_fonts()
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&format!("Font listing failed: {:?}", e)))
+}
+
+/// Warm up the engine ahead of the first render.
+/// 
+/// The first render otherwise pays for font parsing, template parsing and
+/// package resolution all at once. Calling this once after initialization,
+/// ideally during idle time, makes the first user-visible render fast.
+/// Calling it again is cheap.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// await init();
+/// requestIdleCallback(() => prewarm());
+/// ```
+#[wasm_bindgen]
+pub fn prewarm() -> Result<(), JsValue> {
+    engine_prewarm().map_err(|e| JsValue::from_str(&format!("Prewarm failed: {:?}", e)))
+}
+
+/// Serialize the memo template's package sources for caching by the host.
+/// 
+/// Store the bytes (e.g. in IndexedDB) and pass them to
+/// `import_template_artifact` on the next start, so the first render skips
+/// package resolution and finds the template sources already parsed.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// await init();
+/// const cached = await idbGet('template-artifact');
+/// try {
+///   import_template_artifact(cached);
+/// } catch {
+///   // Missing, or from another engine build
+///   await idbPut('template-artifact', export_template_artifact());
+/// }
+/// ```
+#[wasm_bindgen]
+pub fn export_template_artifact() -> Result<Vec<u8>, JsValue> {
+    engine_export_template_artifact().map_err(|e| JsValue::from_str(&format!("Template artifact export failed: {:?}", e)))
+}
+
+/// Load a template artifact from `export_template_artifact`, returning the
+/// number of sources parsed.
+/// 
+/// Throws, without loading anything, for artifacts that are malformed or
+/// were exported by another engine build.
+#[wasm_bindgen]
+pub fn import_template_artifact(bytes: &[u8]) -> Result<usize, JsValue> {
+    engine_import_template_artifact(bytes).map_err(|e| JsValue::from_str(&format!("Template artifact import failed: {:?}", e)))
+}
+
+/// Serialize the engine state: registered letterheads, files, packages and
+/// fonts, registration limits and the template sources in the parse cache.
+/// 
+/// Keep the bytes (e.g. in IndexedDB) and pass them to
+/// `restore_engine_snapshot` on the next page load instead of registering
+/// everything again.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// await init();
+/// const snapshot = await idbGet('engine-snapshot');
+/// try {
+///   restore_engine_snapshot(snapshot);
+/// } catch {
+///   // Missing, or from another engine build: set up and snapshot again
+///   register_letterhead('wing.png', wingBytes);
+///   await idbPut('engine-snapshot', engine_snapshot());
+/// }
+/// ```
+#[wasm_bindgen]
+pub fn engine_snapshot() -> Result<Vec<u8>, JsValue> {
+    render_engine::engine_snapshot().map_err(|e| JsValue::from_str(&format!("Engine snapshot failed: {:?}", e)))
+}
+
+/// Restore a snapshot from `engine_snapshot`, returning
+/// `{ letterheads, assets, local_packages, fonts, memo_template,
+/// template_partials, template_sources }` with what was brought back.
+/// 
+/// Throws, without registering anything, for snapshots that are malformed
+/// or were taken by another engine build.
+#[wasm_bindgen]
+pub fn restore_engine_snapshot(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let restored = render_engine::restore_engine_snapshot(bytes)
+        .map_err(|e| JsValue::from_str(&format!("Engine snapshot restore failed: {:?}", e)))?;
+    serde_wasm_bindgen::to_value(&restored).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Hash what `render_markup` would return for `markup` in `format`, without
+/// rendering it.
+/// 
+/// Equal hashes (32 hexadecimal digits) mean equal output for this engine
+/// build, so hosts can deduplicate stored renders and skip renders they
+/// already have.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const hash = hash_markup_render(markup, 'pdf');
+/// const pdf = (await storage.get(hash)) ?? render_markup(markup, 'pdf');
+/// ```
+#[wasm_bindgen]
+pub fn hash_markup_render(markup: &str, format: Option<String>) -> String {
+    render_engine::hash_markup_render(markup, Some(&job::config(format)))
+}
+
+/// Hash what `render_form` would return for `form` (object or JSON string)
+/// in `format`, see `hash_markup_render`. Forms that only differ in
+/// whitespace or key order hash the same. Throws if an object form has
+/// values JSON cannot hold.
+#[wasm_bindgen]
+pub fn hash_form_render(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, format: Option<String>) -> Result<String, JsValue> {
+    let input_json = form_json(&form).map_err(|e| JsValue::from_str(&format!("Hashing failed: {}", e)))?;
+    Ok(render_engine::hash_form_render(&input_json, Some(&job::config(format))))
+}
+
+thread_local! {
+    /// Host callback receiving render metrics
+    static METRICS_CALLBACK: std::cell::RefCell<Option<js_sys::Function>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Receive performance metrics after every successful render.
+/// 
+/// The callback is called with an object describing the render; timings are
+/// in milliseconds and `bytes` is the total output size. Pass nothing to stop
+/// receiving metrics.
+/// 
+/// ```json
+/// {
+///   "parse_ms": 1.2, "compile_ms": 48.0, "export_ms": 6.5, "total_ms": 55.7,
+///   "pages": 2, "bytes": 183211, "cache_hits": 412, "cache_misses": 0,
+///   "from_cache": false
+/// }
+/// ```
+/// 
+/// `cache_hits` and `cache_misses` count template package source lookups
+/// served from (or added to) the engine's parse cache.
+/// `from_cache` is set when the render was served by the provider installed
+/// with `set_cache_provider`.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// set_metrics_callback(m => analytics.timing('memo_render', m.total_ms, { pages: m.pages }));
+/// ```
+#[wasm_bindgen]
+pub fn set_metrics_callback(callback: Option<js_sys::Function>) {
+    let enabled = callback.is_some();
+    METRICS_CALLBACK.with(|slot| *slot.borrow_mut() = callback);
+    if !enabled {
+        engine_clear_metrics_callback();
+        return;
+    }
+    engine_set_metrics_callback(|metrics| {
+        let Ok(value) = serde_wasm_bindgen::to_value(metrics) else {
+            return;
+        };
+        METRICS_CALLBACK.with(|slot| {
+            if let Some(callback) = slot.borrow().as_ref() {
+                let _ = callback.call1(&JsValue::NULL, &value);
+            }
+        });
+    });
+}
+
+/// Render Typst markup to SVG or PDF, reporting progress through hooks.
+/// 
+/// Works like `render_markup`, calling the optional methods of `hooks` as the
+/// render progresses:
+/// 
+/// - `onCompileStart()` — compilation is about to start
+/// - `onDiagnostics(diagnostics)` — compile warnings or errors, in the shape
+///   returned by `diagnose_markup`
+/// - `onPageExported(index, count, bytes)` — a page was exported (PDF output
+///   reports the whole document once as page 0 of 1)
+/// - `onFinish(metrics, error)` — always called last; `metrics` is the object
+///   passed to `set_metrics_callback` on success and `error` a message on failure
+/// 
+/// Returning `false` from `onCompileStart` or `onPageExported` cancels the
+/// render, which then fails with "Render cancelled", and so does aborting an
+/// `AbortSignal` passed as `hooks.signal` before either is due.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const svg = render_markup_observed(markup, 'svg', {
+///   onPageExported: (index, count) => { progress.value = (index + 1) / count; },
+///   onFinish: (metrics, error) => { progress.hidden = true; },
+/// });
+/// ```
+#[wasm_bindgen]
+pub fn render_markup_observed(
+    markup: &str,
+    format: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "RenderHooks")] hooks: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let config = RenderConfig {
+        format: match format.as_deref() {
+            Some("pdf") => OutputFormat::Pdf,
+            Some("svg-zip") => OutputFormat::SvgZip,
+            _ => OutputFormat::Svg,
+        },
+        ..Default::default()
+    };
+    let mut observer = observer::JsObserver::new(hooks);
+    match engine_render_markup_observed(markup, Some(config), &mut observer) {
+        Ok(output) => output
+            .pages
+            .into_iter()
+            .next()
+            .ok_or_else(|| JsValue::from_str("Error: No pages generated")),
+        Err(e) => Err(JsValue::from_str(&format!("Markup render failed: {:?}", e))),
+    }
+}
+
+/// Render a memo form (object or JSON string) to SVG or PDF, reporting progress through hooks.
+/// 
+/// Works like `render_form`, with the same `hooks` as `render_markup_observed`.
+/// Forms that fail validation only receive `onFinish` with the error.
+/// 
+/// # JavaScript Usage
+/// 
+/// ```javascript
+/// const revision = editor.revision;
+/// const svg = render_form_observed(formData, 'svg', {
+///   onCompileStart: () => editor.revision === revision, // skip stale previews
+///   onDiagnostics: diagnostics => showWarnings(diagnostics),
+/// });
+/// ```
+#[wasm_bindgen]
+pub fn render_form_observed(
+    #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue,
+    format: Option<String>,
+    #[wasm_bindgen(unchecked_param_type = "RenderHooks")] hooks: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let config = RenderConfig {
+        format: match format.as_deref() {
+            Some("pdf") | Some("PDF") => OutputFormat::Pdf,
+            Some("svg-zip") | Some("SVG-ZIP") => OutputFormat::SvgZip,
+            _ => OutputFormat::Svg,
+        },
+        ..Default::default()
+    };
+    let mut observer = observer::JsObserver::new(hooks);
+    let rendered = match form_json(&form) {
+        Ok(input_json) => engine_render_form_observed(&input_json, Some(config), &mut observer),
+        Err(e) => {
+            observer.on_finish(Err(&e));
+            Err(e)
+        }
+    };
+    match rendered {
+        Ok(output) => output
+            .pages
+            .into_iter()
+            .next()
+            .ok_or_else(|| JsValue::from_str("Error: No pages generated")),
+        Err(e) => Err(JsValue::from_str(&format!("Form render failed: {:?}", e))),
+    }
+}
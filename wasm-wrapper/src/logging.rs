@@ -0,0 +1,233 @@
+//! Bridge from `tracing` to JavaScript.
+//!
+//! The render engine reports its work through `tracing` spans and events.
+//! `JsBridgeLayer` forwards those events either to the browser `console`
+//! (`console.debug`/`info`/`warn`/`error` by level) or, when the host
+//! supplies one, to a callback that receives each event as an object:
+//!
+//! ```json
+//! {
+//!   "level": "DEBUG",
+//!   "target": "render_engine::typst_wrapper",
+//!   "message": "compiled",
+//!   "fields": { "pages": "2" },
+//!   "spans": ["render_form", "compile"]
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Once;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn console_debug(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn console_info(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(s: &str);
+}
+
+/// Most verbose level forwarded, as a `level_rank`
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(2);
+
+static INSTALL: Once = Once::new();
+
+thread_local! {
+    /// Host callback receiving events instead of the console
+    static CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+/// Rank levels from least (ERROR = 0) to most (TRACE = 4) verbose
+fn level_rank(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+/// Parse a level name such as `"debug"` (case-insensitive)
+pub(crate) fn parse_level(name: &str) -> Option<Level> {
+    name.parse().ok()
+}
+
+/// Install the bridge as the global subscriber (once) and configure it
+///
+/// Later calls only change the level and callback.
+pub(crate) fn init(level: Level, callback: Option<js_sys::Function>) -> Result<(), String> {
+    MAX_LEVEL.store(level_rank(&level), Ordering::Relaxed);
+    CALLBACK.with(|slot| *slot.borrow_mut() = callback);
+
+    let mut result = Ok(());
+    INSTALL.call_once(|| {
+        result = tracing::subscriber::set_global_default(Registry::default().with(JsBridgeLayer))
+            .map_err(|e| format!("Failed to install logger: {}", e));
+    });
+    result
+}
+
+/// Formatted fields of a span, stored in its extensions
+struct SpanFields(String);
+
+/// Collects the message and remaining fields of an event or span
+#[derive(Default)]
+struct FieldCollector {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl FieldCollector {
+    /// Fields as `key=value` pairs separated by spaces
+    fn format_fields(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.fields {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            let _ = write!(out, "{}={}", name, value);
+        }
+        out
+    }
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push((field.name(), format!("{:?}", value)));
+        }
+    }
+}
+
+/// Layer forwarding events to the console or the host callback
+pub(crate) struct JsBridgeLayer;
+
+impl<S> Layer<S> for JsBridgeLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The level can change at runtime, so ask `enabled` every time
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        level_rank(metadata.level()) <= MAX_LEVEL.load(Ordering::Relaxed)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut collector = FieldCollector::default();
+        attrs.record(&mut collector);
+        span.extensions_mut().insert(SpanFields(collector.format_fields()));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut collector = FieldCollector::default();
+        event.record(&mut collector);
+
+        let spans: Vec<(&'static str, String)> = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| {
+                        let fields = span
+                            .extensions()
+                            .get::<SpanFields>()
+                            .map(|fields| fields.0.clone())
+                            .unwrap_or_default();
+                        (span.name(), fields)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let metadata = event.metadata();
+        let forwarded = CALLBACK.with(|slot| match slot.borrow().as_ref() {
+            Some(callback) => {
+                let _ = callback.call1(&JsValue::NULL, &event_object(metadata, &collector, &spans));
+                true
+            }
+            None => false,
+        });
+        if forwarded {
+            return;
+        }
+
+        let line = format_line(metadata, &collector, &spans);
+        match *metadata.level() {
+            Level::ERROR => console_error(&line),
+            Level::WARN => console_warn(&line),
+            Level::INFO => console_info(&line),
+            _ => console_debug(&line),
+        }
+    }
+}
+
+/// Console line such as `DEBUG render_engine::typst_wrapper: render_form{json_len=312}: compile: compiled pages=2`
+fn format_line(metadata: &Metadata<'_>, collector: &FieldCollector, spans: &[(&'static str, String)]) -> String {
+    let mut line = format!("{} {}: ", metadata.level(), metadata.target());
+    for (name, fields) in spans {
+        line.push_str(name);
+        if !fields.is_empty() {
+            let _ = write!(line, "{{{}}}", fields);
+        }
+        line.push_str(": ");
+    }
+    line.push_str(&collector.message);
+    let fields = collector.format_fields();
+    if !fields.is_empty() {
+        line.push(' ');
+        line.push_str(&fields);
+    }
+    line
+}
+
+/// Event object passed to the host callback
+fn event_object(metadata: &Metadata<'_>, collector: &FieldCollector, spans: &[(&'static str, String)]) -> JsValue {
+    let fields = js_sys::Object::new();
+    for (name, value) in &collector.fields {
+        let _ = js_sys::Reflect::set(&fields, &JsValue::from_str(name), &JsValue::from_str(value));
+    }
+    let span_names: js_sys::Array = spans.iter().map(|(name, _)| JsValue::from_str(name)).collect();
+
+    let object = js_sys::Object::new();
+    let entries: [(&str, JsValue); 5] = [
+        ("level", JsValue::from_str(metadata.level().as_str())),
+        ("target", JsValue::from_str(metadata.target())),
+        ("message", JsValue::from_str(&collector.message)),
+        ("fields", fields.into()),
+        ("spans", span_names.into()),
+    ];
+    for (key, value) in entries {
+        let _ = js_sys::Reflect::set(&object, &JsValue::from_str(key), &value);
+    }
+    object.into()
+}
@@ -0,0 +1,91 @@
+//! Render lifecycle hooks backed by a JavaScript object.
+
+use std::ops::ControlFlow;
+
+use render_engine::{Diagnostic, RenderMetrics, RenderObserver, TypstWrapperError};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Forwards lifecycle hooks to the optional methods of a JS object:
+/// `onCompileStart()`, `onDiagnostics(diagnostics)`,
+/// `onPageExported(index, count, bytes)` and `onFinish(metrics, error)`.
+/// `onCompileStart` and `onPageExported` cancel the render by returning `false`,
+/// and so does an aborted `AbortSignal` in the object's `signal`.
+pub(crate) struct JsObserver {
+    hooks: JsValue,
+}
+
+impl JsObserver {
+    pub(crate) fn new(hooks: JsValue) -> Self {
+        Self { hooks }
+    }
+
+    /// The hook method called `name`, if the object defines one
+    fn hook(&self, name: &str) -> Option<js_sys::Function> {
+        js_sys::Reflect::get(&self.hooks, &JsValue::from_str(name))
+            .ok()?
+            .dyn_into()
+            .ok()
+    }
+
+    /// Continue unless the hook returned exactly `false`
+    fn control(result: Result<JsValue, JsValue>) -> ControlFlow<()> {
+        match result {
+            Ok(value) if value == JsValue::FALSE => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    /// Cancel if the object's `signal` was aborted, else call the hook `name`
+    fn check(&self, name: &str, call: impl FnOnce(js_sys::Function) -> Result<JsValue, JsValue>) -> ControlFlow<()> {
+        let signal = js_sys::Reflect::get(&self.hooks, &JsValue::from_str("signal")).unwrap_or(JsValue::UNDEFINED);
+        if aborted(&signal) {
+            return ControlFlow::Break(());
+        }
+        match self.hook(name) {
+            Some(hook) => Self::control(call(hook)),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// Whether `signal` is an `AbortSignal`, or any object, whose `aborted` is
+/// true; `undefined` and `null` are never aborted
+pub(crate) fn aborted(signal: &JsValue) -> bool {
+    signal.is_object()
+        && js_sys::Reflect::get(signal, &JsValue::from_str("aborted")).is_ok_and(|aborted| aborted == JsValue::TRUE)
+}
+
+impl RenderObserver for JsObserver {
+    fn on_compile_start(&mut self) -> ControlFlow<()> {
+        self.check("onCompileStart", |hook| hook.call0(&self.hooks))
+    }
+
+    fn on_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+        if let (Some(hook), Ok(value)) = (self.hook("onDiagnostics"), serde_wasm_bindgen::to_value(diagnostics)) {
+            let _ = hook.call1(&self.hooks, &value);
+        }
+    }
+
+    fn on_page_exported(&mut self, index: usize, count: usize, page: &[u8]) -> ControlFlow<()> {
+        self.check("onPageExported", |hook| {
+            hook.call3(
+                &self.hooks,
+                &JsValue::from(index as u32),
+                &JsValue::from(count as u32),
+                &js_sys::Uint8Array::from(page),
+            )
+        })
+    }
+
+    fn on_finish(&mut self, result: Result<&RenderMetrics, &TypstWrapperError>) {
+        let Some(hook) = self.hook("onFinish") else {
+            return;
+        };
+        let (metrics, error) = match result {
+            Ok(metrics) => (serde_wasm_bindgen::to_value(metrics).unwrap_or(JsValue::NULL), JsValue::NULL),
+            Err(e) => (JsValue::NULL, JsValue::from_str(&e.to_string())),
+        };
+        let _ = hook.call2(&self.hooks, &metrics, &error);
+    }
+}
@@ -0,0 +1,129 @@
+//! A prioritized render queue for editors.
+
+use render_engine::{Priority, QueueEvent, RenderQueue as EngineRenderQueue, Ticket};
+use wasm_bindgen::prelude::*;
+
+use crate::form_input::form_json;
+use crate::job::config;
+
+/// Renders requests one step at a time, most urgent first.
+///
+/// Previews are submitted with priority `"high"` and a coalescing key, so a
+/// newer preview of the same document replaces one still waiting or
+/// rendering; exports use `"normal"`. `step()` advances the most urgent
+/// request and returns an event object when a request finished, failed or
+/// was superseded:
+/// `{ ticket, status: "finished" | "failed" | "superseded", output?,
+/// page_sizes?, error? }`, where `output` is the first page (SVG) or the
+/// document (PDF) and `page_sizes` the `{ width, height }` of every page in
+/// points.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const queue = new RenderQueue();
+/// editor.onChange(text => queue.submit_markup(text, 'svg', 'high', 'preview'));
+/// exportButton.onclick = () => queue.submit_markup(editor.text, 'pdf', 'normal');
+/// setInterval(() => {
+///   const event = queue.step();
+///   if (event?.status === 'finished') show(event.ticket, event.output);
+/// }, 0);
+/// ```
+#[wasm_bindgen(js_name = RenderQueue)]
+#[derive(Default)]
+pub struct WasmRenderQueue {
+    queue: EngineRenderQueue,
+}
+
+#[wasm_bindgen(js_class = RenderQueue)]
+impl WasmRenderQueue {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmRenderQueue {
+        Self::default()
+    }
+
+    /// Queue a render of Typst markup and return its ticket. `priority` is
+    /// "low", "normal" (default) or "high"; a request with the same `key`
+    /// that has not finished is superseded.
+    pub fn submit_markup(
+        &mut self,
+        markup: &str,
+        format: Option<String>,
+        priority: Option<String>,
+        key: Option<String>,
+    ) -> Result<f64, JsValue> {
+        let priority = parse_priority(priority)?;
+        Ok(self.queue.submit_markup(markup, Some(config(format)), priority, key.as_deref()) as f64)
+    }
+
+    /// Queue a render of a memo form (object or JSON string), see
+    /// `submit_markup`
+    pub fn submit_form(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue,
+        format: Option<String>,
+        priority: Option<String>,
+        key: Option<String>,
+    ) -> Result<f64, JsValue> {
+        let priority = parse_priority(priority)?;
+        let input_json = form_json(&form).map_err(|e| JsValue::from_str(&format!("Form render failed: {:?}", e)))?;
+        Ok(self.queue.submit_form(&input_json, Some(config(format)), priority, key.as_deref()) as f64)
+    }
+
+    /// Drop a request without an event, returning whether it was queued
+    pub fn cancel(&mut self, ticket: f64) -> bool {
+        self.queue.cancel(ticket as Ticket)
+    }
+
+    /// Number of requests still to be rendered
+    #[wasm_bindgen(getter)]
+    pub fn pending(&self) -> usize {
+        self.queue.pending()
+    }
+
+    /// Whether there is nothing left to render or report
+    #[wasm_bindgen(getter)]
+    pub fn idle(&self) -> bool {
+        self.queue.is_idle()
+    }
+
+    /// Advance the queue by one step, returning an event or `undefined`
+    pub fn step(&mut self) -> JsValue {
+        match self.queue.step() {
+            Some(event) => event_object(event),
+            None => JsValue::UNDEFINED,
+        }
+    }
+}
+
+fn parse_priority(priority: Option<String>) -> Result<Priority, JsValue> {
+    match priority.as_deref() {
+        Some("low") => Ok(Priority::Low),
+        None | Some("normal") => Ok(Priority::Normal),
+        Some("high") => Ok(Priority::High),
+        Some(other) => Err(JsValue::from_str(&format!("Unknown priority '{}': use low, normal or high", other))),
+    }
+}
+
+/// The JS object describing `event`
+fn event_object(event: QueueEvent) -> JsValue {
+    let object = js_sys::Object::new();
+    let set = |key: &str, value: JsValue| {
+        let _ = js_sys::Reflect::set(&object, &JsValue::from_str(key), &value);
+    };
+    set("ticket", JsValue::from(event.ticket() as f64));
+    match event {
+        QueueEvent::Finished { output, .. } => {
+            set("status", JsValue::from_str("finished"));
+            let page = output.pages.first().map(|page| js_sys::Uint8Array::from(page.as_slice()));
+            set("output", page.map_or(JsValue::UNDEFINED, JsValue::from));
+            set("page_sizes", serde_wasm_bindgen::to_value(&output.page_sizes).unwrap_or(JsValue::UNDEFINED));
+        }
+        QueueEvent::Failed { error, .. } => {
+            set("status", JsValue::from_str("failed"));
+            set("error", JsValue::from_str(&error.to_string()));
+        }
+        QueueEvent::Superseded { .. } => set("status", JsValue::from_str("superseded")),
+    }
+    object.into()
+}
@@ -0,0 +1,62 @@
+//! Host file resolver for files the engine does not have.
+
+use std::cell::RefCell;
+
+use render_engine::{clear_file_resolver, set_file_resolver as engine_set_file_resolver, FileResolver};
+use typst::syntax::package::PackageSpec;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+thread_local! {
+    /// Host function resolving a path to file contents
+    static RESOLVER: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+/// Forwards engine file lookups to the function in `RESOLVER`. JS values
+/// cannot cross threads, so the engine holds this stateless handle instead.
+struct JsResolver;
+
+impl FileResolver for JsResolver {
+    fn resolve(&self, path: &str, package: Option<&PackageSpec>) -> Option<Vec<u8>> {
+        let package = package.map_or(JsValue::NULL, |spec| JsValue::from_str(&spec.to_string()));
+        let value = RESOLVER.with(|slot| slot.borrow().as_ref()?.call2(&JsValue::NULL, &JsValue::from_str(path), &package).ok())?;
+        if let Some(text) = value.as_string() {
+            return Some(text.into_bytes());
+        }
+        value.dyn_into::<js_sys::Uint8Array>().ok().map(|bytes| bytes.to_vec())
+    }
+}
+
+/// Resolve the files renders need that the engine does not have.
+///
+/// `resolver(path, pkg)` is called with the path of a file a document
+/// imports, includes or reads, relative to the root (e.g.
+/// `"data/units.json"`), and `pkg` is the package spec, such as
+/// `"@preview/unit-kit:1.0.0"`, for package files and `null` otherwise. It
+/// returns the contents as a `Uint8Array` or string, or `null`/`undefined`
+/// when it does not have the file; exceptions count as missing files. It is
+/// asked once per file and render, and only for files the engine neither
+/// embeds nor has registered. Pass `null` or nothing to stop resolving.
+///
+/// Renders are synchronous, so the resolver answers from memory: fetch or
+/// read from IndexedDB what documents need before rendering. Cached renders
+/// are keyed by their input, not by resolved files, so render with the
+/// cache turned off when those files change.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const files = new Map(await idbEntries('unit-files'));
+/// set_file_resolver((path, pkg) => pkg === null ? files.get(path) ?? null : null);
+/// const svg = render_markup('#import "units.typ": wing\n#wing', 'svg');
+/// ```
+#[wasm_bindgen]
+pub fn set_file_resolver(resolver: Option<js_sys::Function>) {
+    let enabled = resolver.is_some();
+    RESOLVER.with(|slot| *slot.borrow_mut() = resolver);
+    if enabled {
+        engine_set_file_resolver(JsResolver);
+    } else {
+        clear_file_resolver();
+    }
+}
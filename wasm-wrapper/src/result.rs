@@ -0,0 +1,129 @@
+//! Render results with their pages, warnings and timings.
+
+use render_engine::{
+    render_form_observed as engine_render_form_observed, render_markup_observed as engine_render_markup_observed,
+    Diagnostic, OutputFormat, RenderObserver, RenderOutput, Severity, TypstWrapperError,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::form_input::form_json;
+use crate::job::config;
+
+/// The outcome of a render: its pages, the output format, the compile
+/// warnings and the render's timings.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const result = render_form_result(JSON.stringify(formData));
+/// pageLabel.textContent = `${current + 1} of ${result.page_count}`;
+/// preview.innerHTML = new TextDecoder().decode(result.page(current));
+/// for (const warning of result.warnings) console.warn(warning.message, warning.range);
+/// console.log(`rendered in ${result.timing.total_ms} ms`);
+/// ```
+#[wasm_bindgen]
+pub struct RenderResult {
+    output: RenderOutput,
+    format: OutputFormat,
+    warnings: Vec<Diagnostic>,
+}
+
+#[wasm_bindgen]
+impl RenderResult {
+    /// Number of pages in the document, also for PDF and SVG ZIP output
+    #[wasm_bindgen(getter)]
+    pub fn page_count(&self) -> usize {
+        self.output.page_sizes.len()
+    }
+
+    /// Output format: "svg", "pdf" or "svg-zip"
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        self.format.name().to_string()
+    }
+
+    /// Every output item as a `Uint8Array`: one per SVG page, or a single
+    /// PDF or SVG ZIP
+    #[wasm_bindgen(getter)]
+    pub fn pages(&self) -> js_sys::Array {
+        self.output.pages.iter().map(|page| js_sys::Uint8Array::from(page.as_slice())).collect()
+    }
+
+    /// Output item `index`, or `undefined` past the last one
+    pub fn page(&self, index: usize) -> Option<Vec<u8>> {
+        self.output.pages.get(index).cloned()
+    }
+
+    /// `{ width, height }` of every page in points
+    #[wasm_bindgen(getter)]
+    pub fn page_sizes(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.output.page_sizes)
+    }
+
+    /// Compile warnings, located as by `diagnose_markup` and `diagnose_form`;
+    /// empty for renders served from the output cache
+    #[wasm_bindgen(getter)]
+    pub fn warnings(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.warnings)
+    }
+
+    /// `{ parse_ms, compile_ms, export_ms, total_ms, ... }` of the render
+    #[wasm_bindgen(getter)]
+    pub fn timing(&self) -> Result<JsValue, JsValue> {
+        to_value(&self.output.metrics)
+    }
+}
+
+/// Render Typst markup ("svg" by default, "pdf" or "svg-zip"), returning a
+/// `RenderResult`
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const result = render_markup_result(markup);
+/// if (result.warnings.length) showWarnings(result.warnings);
+/// ```
+#[wasm_bindgen]
+pub fn render_markup_result(markup: &str, format: Option<String>) -> Result<RenderResult, JsValue> {
+    let config = config(format);
+    render("Markup", config.format, |observer| engine_render_markup_observed(markup, Some(config), observer))
+}
+
+/// Render a memo form (object or JSON string), returning a `RenderResult`,
+/// see `render_markup_result`
+#[wasm_bindgen]
+pub fn render_form_result(#[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, format: Option<String>) -> Result<RenderResult, JsValue> {
+    let config = config(format);
+    render("Form", config.format, |observer| {
+        engine_render_form_observed(&form_json(&form)?, Some(config), observer)
+    })
+}
+
+/// Keeps the warnings a render reports
+#[derive(Default)]
+struct Warnings(Vec<Diagnostic>);
+
+impl RenderObserver for Warnings {
+    fn on_diagnostics(&mut self, diagnostics: &[Diagnostic]) {
+        self.0.extend(diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning).cloned());
+    }
+}
+
+/// Run `render` of a `kind` render to `format`, keeping its warnings
+pub(crate) fn render(
+    kind: &str,
+    format: OutputFormat,
+    render: impl FnOnce(&mut dyn RenderObserver) -> Result<RenderOutput, TypstWrapperError>,
+) -> Result<RenderResult, JsValue> {
+    let mut warnings = Warnings::default();
+    let output = render(&mut warnings)
+        .map_err(|e| JsValue::from_str(&format!("{} render failed: {:?}", kind, e)))?;
+    if output.pages.is_empty() {
+        return Err(JsValue::from_str("Error: No pages generated"));
+    }
+    Ok(RenderResult { output, format, warnings: warnings.0 })
+}
+
+fn to_value<T: serde::Serialize + ?Sized>(value: &T) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(value).map_err(|e| JsValue::from_str(&format!("Serialization failed: {:?}", e)))
+}
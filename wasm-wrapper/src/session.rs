@@ -0,0 +1,112 @@
+//! Render sessions keeping their Typst world between renders.
+
+use render_engine::{render_markup_diff_observed, RenderSession as EngineRenderSession};
+use wasm_bindgen::prelude::*;
+
+use crate::form_input::form_json;
+use crate::job::config;
+use crate::result::{render, RenderResult};
+
+/// Renders of a live preview that reuse the work of the previous render.
+///
+/// A session keeps the Typst world and the SVG pages of its last render, so
+/// re-rendering the document after an edit reparses only what changed and
+/// exports only the pages that look different. Use one session per preview
+/// and render every revision of the document with it; registering
+/// letterheads, files, fonts or templates makes the next render start over.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const session = new RenderSession('svg');
+/// editor.on('change', () => {
+///   const result = session.render(editor.getValue());
+///   for (const index of session.changed_pages) {
+///     pages[index].innerHTML = new TextDecoder().decode(result.page(index));
+///   }
+/// });
+/// ```
+#[wasm_bindgen(js_name = RenderSession)]
+pub struct WasmRenderSession {
+    session: EngineRenderSession,
+}
+
+#[wasm_bindgen(js_class = RenderSession)]
+impl WasmRenderSession {
+    /// Start a session rendering to `format`: "svg" by default, "pdf" or
+    /// "svg-zip"
+    #[wasm_bindgen(constructor)]
+    pub fn new(format: Option<String>) -> WasmRenderSession {
+        Self { session: EngineRenderSession::new(Some(config(format))) }
+    }
+
+    /// Render a memo form (object or JSON string) or Typst markup: objects
+    /// and strings starting with `{`, after any whitespace, are forms
+    pub fn render(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] input: JsValue,
+    ) -> Result<RenderResult, JsValue> {
+        match input.as_string() {
+            Some(markup) if !markup.trim_start().starts_with('{') => self.render_markup(&markup),
+            _ => self.render_form(input),
+        }
+    }
+
+    /// Render Typst markup, returning a `RenderResult`
+    pub fn render_markup(&mut self, markup: &str) -> Result<RenderResult, JsValue> {
+        let format = self.session.config().format;
+        render("Markup", format, |observer| self.session.render_markup_observed(markup, observer))
+    }
+
+    /// Render a memo form (object or JSON string), returning a `RenderResult`
+    pub fn render_form(&mut self, #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue) -> Result<RenderResult, JsValue> {
+        let format = self.session.config().format;
+        render("Form", format, |observer| self.session.render_form_observed(&form_json(&form)?, observer))
+    }
+
+    /// Indices of the pages of the last render that look different from the
+    /// render before it, including added pages; every page after the first
+    /// render. Rendering unchanged input again changes no pages.
+    #[wasm_bindgen(getter)]
+    pub fn changed_pages(&self) -> Vec<u32> {
+        self.session.changed_pages().iter().map(|&index| index as u32).collect()
+    }
+
+    /// Drop the kept world, so the next render starts from scratch
+    pub fn reset(&mut self) {
+        self.session.reset();
+    }
+}
+
+/// Render `new_markup` ("svg" by default, "pdf" or "svg-zip") and report
+/// which of its pages differ from those of `old_markup`.
+///
+/// Returns `{ changed_pages, result }`: the indices of the pages of the new
+/// version that look different, including added pages, and its
+/// `RenderResult`. Editors rendering every revision use a `RenderSession`
+/// and its `changed_pages` instead, which do not render the old version
+/// again.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const { changed_pages, result } = render_markup_diff(previous, markup);
+/// for (const index of changed_pages) {
+///   pages[index].innerHTML = new TextDecoder().decode(result.page(index));
+/// }
+/// ```
+#[wasm_bindgen]
+pub fn render_markup_diff(old_markup: &str, new_markup: &str, format: Option<String>) -> Result<JsValue, JsValue> {
+    let config = config(format);
+    let mut changed_pages = Vec::new();
+    let result = render("Markup", config.format, |observer| {
+        let diff = render_markup_diff_observed(old_markup, new_markup, Some(config), observer)?;
+        changed_pages = diff.changed_pages;
+        Ok(diff.output)
+    })?;
+    let changed: js_sys::Array = changed_pages.into_iter().map(|index| JsValue::from(index as u32)).collect();
+    let diff = js_sys::Object::new();
+    js_sys::Reflect::set(&diff, &"changed_pages".into(), &changed)?;
+    js_sys::Reflect::set(&diff, &"result".into(), &JsValue::from(result))?;
+    Ok(diff.into())
+}
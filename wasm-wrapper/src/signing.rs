@@ -0,0 +1,166 @@
+//! PAdES signatures of rendered PDFs.
+
+use render_engine::signing::{self, KeySigner, PreparedSignature, SignOptions, SignatureAlgorithm, SignatureStamp};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::form_input::form_json;
+
+/// Sign a rendered PDF with an RSA key.
+///
+/// `keyPem` is a PKCS#8 or PKCS#1 private key and `certificatesPem` the
+/// signing certificate followed by its chain. `options` may set `name`,
+/// `reason`, `location`, `contactInfo` and `signingTime`
+/// (`YYYY-MM-DDTHH:MM:SSZ`; it is not taken from the engine's clock).
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const signed = sign_pdf(pdfBytes, keyPem, chainPem, {
+///   reason: 'Approved for release',
+///   signingTime: new Date().toISOString().replace(/\.\d+Z$/, 'Z'),
+/// });
+/// ```
+#[wasm_bindgen]
+pub fn sign_pdf(pdf: &[u8], key_pem: &str, certificates_pem: &str, options: JsValue) -> Result<Vec<u8>, JsValue> {
+    let signer = KeySigner::from_pem(key_pem, certificates_pem).map_err(error)?;
+    signing::sign_pdf(pdf, &signer, &sign_options(&options)).map_err(error)
+}
+
+/// A PDF waiting for a signature from an external signer.
+///
+/// For keys that never leave WebCrypto, a smart card middleware or a remote
+/// signing service: `prepare` adds the signature field, the signer signs
+/// `toBeSigned` with SHA-256, and `finish` embeds the signature. ECDSA
+/// signatures may be DER or the raw `r || s` form WebCrypto returns.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const pending = PdfSignature.prepare(pdfBytes, [certificateDer], 'ecdsa', { reason: 'Approved' });
+/// const signature = await crypto.subtle.sign({ name: 'ECDSA', hash: 'SHA-256' }, key, pending.toBeSigned);
+/// const signed = pending.finish(new Uint8Array(signature));
+/// ```
+#[wasm_bindgen]
+pub struct PdfSignature {
+    prepared: PreparedSignature,
+    algorithm: SignatureAlgorithm,
+}
+
+#[wasm_bindgen]
+impl PdfSignature {
+    /// Add an empty signature field for the DER `certificates` (signing
+    /// certificate first); `algorithm` is "rsa" (default) or "ecdsa"
+    pub fn prepare(
+        pdf: &[u8],
+        certificates: Vec<js_sys::Uint8Array>,
+        algorithm: Option<String>,
+        options: JsValue,
+    ) -> Result<PdfSignature, JsValue> {
+        let algorithm = match algorithm.as_deref() {
+            None | Some("rsa") | Some("RSA") => SignatureAlgorithm::RsaSha256,
+            Some("ecdsa") | Some("ECDSA") => SignatureAlgorithm::EcdsaSha256,
+            Some(other) => return Err(JsValue::from_str(&format!("Unknown signature algorithm: {}", other))),
+        };
+        let certificates: Vec<Vec<u8>> = certificates.iter().map(js_sys::Uint8Array::to_vec).collect();
+        let prepared = PreparedSignature::prepare(pdf, &certificates, algorithm, &sign_options(&options)).map_err(error)?;
+        Ok(PdfSignature { prepared, algorithm })
+    }
+
+    /// The bytes the signer hashes with SHA-256 and signs
+    #[wasm_bindgen(getter, js_name = toBeSigned)]
+    pub fn to_be_signed(&self) -> Vec<u8> {
+        self.prepared.to_be_signed().to_vec()
+    }
+
+    /// The signed PDF
+    pub fn finish(self, signature: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let signature = match self.algorithm {
+            SignatureAlgorithm::EcdsaSha256 if signature.first() != Some(&0x30) => ecdsa_der(signature),
+            _ => signature.to_vec(),
+        };
+        self.prepared.finish(&signature).map_err(error)
+    }
+}
+
+/// Stamp a signature image, name and date over the signature block.
+///
+/// `pdf` is the (signed or unsigned) PDF rendered from the memo form `form`
+/// (object or JSON string). `stamp` may set `image` (PNG or JPEG bytes),
+/// `name`, `date`, and the `width` and `height` in points (144 by 40 by
+/// default).
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// const stamped = stamp_form_signature(signedPdf, formData, {
+///   image: new Uint8Array(await signatureImage.arrayBuffer()),
+///   name: 'FIRST M. LAST',
+///   date: '9 September 2025',
+/// });
+/// ```
+#[wasm_bindgen]
+pub fn stamp_form_signature(pdf: &[u8], #[wasm_bindgen(unchecked_param_type = "MemoForm | string")] form: JsValue, stamp: JsValue) -> Result<Vec<u8>, JsValue> {
+    let get = |name: &str| -> JsValue {
+        if stamp.is_object() {
+            js_sys::Reflect::get(&stamp, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+        } else {
+            JsValue::UNDEFINED
+        }
+    };
+    let defaults = SignatureStamp::default();
+    let stamp = SignatureStamp {
+        image: get("image").dyn_into::<js_sys::Uint8Array>().ok().map(|image| image.to_vec()),
+        name: get("name").as_string(),
+        date: get("date").as_string(),
+        width: get("width").as_f64().unwrap_or(defaults.width),
+        height: get("height").as_f64().unwrap_or(defaults.height),
+    };
+    let input_json = form_json(&form).map_err(|e| error(signing::SigningError::InvalidStamp(e.to_string())))?;
+    signing::stamp_form_signature(pdf, &input_json, &stamp).map_err(error)
+}
+
+/// `SignOptions` from the optional fields of a JS object
+fn sign_options(options: &JsValue) -> SignOptions {
+    let get = |name: &str| -> Option<String> {
+        if options.is_object() {
+            js_sys::Reflect::get(options, &JsValue::from_str(name)).ok()?.as_string()
+        } else {
+            None
+        }
+    };
+    SignOptions {
+        name: get("name"),
+        reason: get("reason"),
+        location: get("location"),
+        contact_info: get("contactInfo"),
+        signing_time: get("signingTime"),
+    }
+}
+
+/// DER `ECDSA-Sig-Value` of a raw `r || s` signature
+fn ecdsa_der(raw: &[u8]) -> Vec<u8> {
+    let integer = |bytes: &[u8]| {
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len() - 1);
+        let mut value = bytes[start..].to_vec();
+        if value[0] & 0x80 != 0 {
+            value.insert(0, 0);
+        }
+        let mut encoded = vec![0x02, value.len() as u8];
+        encoded.extend(value);
+        encoded
+    };
+    let (r, s) = raw.split_at(raw.len() / 2);
+    let body = [integer(r), integer(s)].concat();
+    // P-521 signatures need the long length form
+    let mut der = match body.len() {
+        len @ 0..=127 => vec![0x30, len as u8],
+        len => vec![0x30, 0x81, len as u8],
+    };
+    der.extend(body);
+    der
+}
+
+fn error(e: signing::SigningError) -> JsValue {
+    JsValue::from_str(&format!("Signing failed: {}", e))
+}
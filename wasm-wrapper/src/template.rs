@@ -0,0 +1,99 @@
+//! Named memo templates that replace the embedded memo-loader template.
+//!
+//! A web admin UI uploads a revised template with `set_template(name,
+//! files)`, which checks it with a test compile and activates it for form
+//! renders. Earlier uploads stay available under their names, so the UI can
+//! switch back with `use_template(name)` or roll back to the embedded
+//! template with `reset_template()`. Each name holds its own files, and
+//! cached renders are keyed by the files of the template they used, so
+//! sessions working on different templates never see each other's output.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use render_engine::template::{use_memo_template, MemoTemplate};
+use tracing::debug;
+use wasm_bindgen::prelude::*;
+
+thread_local! {
+    /// Uploaded templates by name
+    static TEMPLATES: RefCell<BTreeMap<String, MemoTemplate>> = const { RefCell::new(BTreeMap::new()) };
+    /// Name of the template forms render with, `None` for the embedded one
+    static ACTIVE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Upload a memo template under `name` and render forms with it.
+///
+/// `files` maps paths to their content as a `Uint8Array` or string and must
+/// hold `main.typ`, which reads the form with
+/// `#let input = json("input.json")`. Uploading a name again replaces its
+/// files. Throws, keeping the active template, if the template does not
+/// compile a minimal memo.
+///
+/// # JavaScript Usage
+///
+/// ```javascript
+/// set_template('wing-2025', {
+///   'main.typ': await (await fetch('/templates/wing/main.typ')).text(),
+///   'assets/wing-seal.png': new Uint8Array(await (await fetch('/templates/wing/seal.png')).arrayBuffer()),
+/// });
+/// const pdf = render_form(JSON.stringify(formData), 'pdf');
+/// reset_template(); // back to the embedded template
+/// ```
+#[wasm_bindgen]
+pub fn set_template(name: &str, files: js_sys::Object) -> Result<(), JsValue> {
+    let template = MemoTemplate::new(crate::object_files(&files)).map_err(|e| {
+        debug!(name, error = %e, "template rejected");
+        JsValue::from_str(&format!("Template '{}' rejected: {}", name, e))
+    })?;
+    TEMPLATES.with(|templates| templates.borrow_mut().insert(name.to_string(), template.clone()));
+    activate(Some(name.to_string()), Some(template));
+    Ok(())
+}
+
+/// Render forms with the template uploaded under `name`.
+#[wasm_bindgen]
+pub fn use_template(name: &str) -> Result<(), JsValue> {
+    let template = TEMPLATES
+        .with(|templates| templates.borrow().get(name).cloned())
+        .ok_or_else(|| JsValue::from_str(&format!("No template named '{}'", name)))?;
+    activate(Some(name.to_string()), Some(template));
+    Ok(())
+}
+
+/// Render forms with the embedded template again. Uploaded templates are
+/// kept for `use_template`.
+#[wasm_bindgen]
+pub fn reset_template() {
+    activate(None, None);
+}
+
+/// Forget the template uploaded under `name`, returning whether there was
+/// one. Removing the active template rolls back to the embedded one.
+#[wasm_bindgen]
+pub fn remove_template(name: &str) -> bool {
+    let removed = TEMPLATES.with(|templates| templates.borrow_mut().remove(name).is_some());
+    if ACTIVE.with(|active| active.borrow().as_deref() == Some(name)) {
+        activate(None, None);
+    }
+    removed
+}
+
+/// Name of the template forms render with, or `undefined` for the embedded
+/// template.
+#[wasm_bindgen]
+pub fn active_template() -> Option<String> {
+    ACTIVE.with(|active| active.borrow().clone())
+}
+
+/// Names of the uploaded templates, sorted.
+#[wasm_bindgen]
+pub fn list_templates() -> Vec<String> {
+    TEMPLATES.with(|templates| templates.borrow().keys().cloned().collect())
+}
+
+fn activate(name: Option<String>, template: Option<MemoTemplate>) {
+    debug!(name = name.as_deref().unwrap_or("embedded"), "template activated");
+    use_memo_template(template);
+    ACTIVE.with(|active| *active.borrow_mut() = name);
+}
@@ -0,0 +1,192 @@
+//! TypeScript types of memo forms and render hooks.
+//!
+//! wasm-bindgen types `JsValue` parameters as `any`. The interfaces below go
+//! into the package's `.d.ts` and type the form, batch job and hooks
+//! parameters and the validation and diagnostics results instead. `MemoForm` follows
+//! DESIGN/official-memorandum-schema.json and changes with it.
+
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &'static str = r##"
+/** A memo form, as checked by `validate_form` */
+export interface MemoForm {
+  /** Letterhead title, e.g. "DEPARTMENT OF THE AIR FORCE" */
+  "letterhead-title"?: string;
+  /** Letterhead caption, e.g. the unit name */
+  "letterhead-caption"?: string;
+  /** Name of a letterhead background registered with `register_letterhead` */
+  "letterhead-background"?: string | null;
+  /** Opacity of the letterhead background, from 0 to 1 (default 0.3) */
+  "letterhead-background-opacity"?: number;
+  /** `true` for the default page numbers, `false` for none, or settings */
+  "page-numbering"?: boolean | PageNumbering | null;
+  /** Text in the page header, e.g. a CUI marking */
+  "running-header"?: RunningText | null;
+  /** Text in the page footer, e.g. office symbol and subject */
+  "running-footer"?: RunningText | null;
+  /** Text stamped diagonally across every page, e.g. "DRAFT" */
+  watermark?: string | Watermark | null;
+  /** PDF document information; the title defaults to the subject */
+  metadata?: DocumentMetadata | null;
+  /** A DRAFT watermark unless `watermark` is set, no //SIGNED// markers and the render time on every page */
+  draft?: boolean;
+  /** Style tokens adjusting the look of the memo */
+  style?: MemoStyle | null;
+  /** Main body of the memo; either `body` or `body_raw` is required */
+  body?: Content;
+  /** Typst markup used as the body in place of `body` */
+  body_raw?: string;
+  /** Date as YYYY-MM-DD; today when omitted */
+  date?: string | null;
+  /** Recipients, at least one */
+  "memo-for": (string | Recipient)[];
+  /** Sender office and address lines, at least one */
+  "from-block": string[];
+  /** Subject line */
+  subject: string;
+  /** Referenced documents */
+  references?: (string | Reference)[] | null;
+  /** Signature lines, at least two */
+  "signature-block": string[];
+  /** Signature blocks of one to three coordinating officials */
+  "co-signers"?: string[][];
+}
+
+/** Text in a given format, such as the memo body */
+export interface Content {
+  /** "markup" for Typst markup (default) or "delta" for Quill Delta JSON */
+  format?: "markup" | "delta";
+  /** The text, or the Delta as a JSON string */
+  data: string;
+}
+
+/** A recipient rendered as ORGANIZATION/OFFICE-SYMBOL, then the location */
+export interface Recipient {
+  /** Office symbol, such as CC or A1 */
+  "office-symbol"?: string;
+  /** Organization, such as HQ AETC or 42 ABW */
+  organization?: string;
+  /** Location, such as Maxwell AFB AL, for recipients on another installation */
+  location?: string;
+}
+
+/** A referenced document */
+export interface Reference {
+  title: string;
+  /** Date as YYYY-MM-DD */
+  date?: string;
+  /** URL the title links to */
+  link?: string;
+  /** Kind or designation, such as AFI 33-360 or Memo, shown before the title */
+  type?: string;
+}
+
+export interface PageNumbering {
+  /** Number of the first page (default 1) */
+  start?: number;
+  /** Page number text with {page} and {total}, e.g. "Page {page} of {total}" */
+  format?: string;
+  position?: "top-left" | "top-center" | "top-right" | "bottom-left" | "bottom-center" | "bottom-right";
+  /** Whether the first page shows its number (default false) */
+  "first-page"?: boolean;
+}
+
+/** Running header or footer text, with the placeholders {page}, {total}, {date}, {office-symbol} and {subject} */
+export interface RunningText {
+  left?: string | null;
+  center?: string | null;
+  right?: string | null;
+  /** Pages showing the text (default "all") */
+  pages?: "all" | "first" | "subsequent" | number[] | null;
+}
+
+export interface Watermark {
+  text: string;
+  /** Hex color, #rgb or #rrggbb (default "#808080") */
+  color?: string;
+  /** From 0 to 1 (default 0.25) */
+  opacity?: number;
+}
+
+export interface DocumentMetadata {
+  title?: string;
+  author?: string[];
+  subject?: string;
+  keywords?: string[];
+  /** Creation date as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS in UTC */
+  date?: string;
+}
+
+/** Style tokens; unset tokens keep the template defaults */
+export interface MemoStyle {
+  /** Body text size in points, 6 to 24 */
+  "font-size"?: number;
+  /** Letterhead title size in points, 6 to 36 */
+  "letterhead-font-size"?: number;
+  /** Space between the lines of a paragraph in em, 0 to 3 */
+  "line-spacing"?: number;
+  /** Space between paragraphs in em, 0 to 5 */
+  "paragraph-spacing"?: number;
+  /** Width of the letterhead seal in inches, up to 3 */
+  "seal-size"?: number;
+  /** Hex color, #rgb or #rrggbb */
+  "accent-color"?: string;
+  /** Elements drawn in the accent color; the letterhead when omitted */
+  "accent-on"?: ("letterhead" | "headings")[];
+}
+
+/** A job of `render_batch` */
+export interface BatchJob {
+  fileName: string;
+  form: MemoForm | string;
+  format?: "svg" | "pdf";
+}
+
+/** A problem `validate_form` found, with the JSON pointer of its field */
+export interface FormError {
+  /** Broad class, such as "validation" or "parser" */
+  kind: string;
+  /** Stable code within the kind, such as "schema_mismatch" */
+  code: string;
+  message: string;
+  path?: string;
+  severity: "error" | "warning";
+}
+
+/** Zero-based line and UTF-16 column */
+export interface Position {
+  line: number;
+  character: number;
+}
+
+export interface Range {
+  start: Position;
+  end: Position;
+}
+
+/** A problem found in markup or a form, located as in the Language Server Protocol */
+export interface Diagnostic {
+  range: Range;
+  severity: "error" | "warning";
+  /** The stage that found the problem */
+  source: "typst" | "schema" | "delta";
+  message: string;
+  /** Suggestions for fixing the problem */
+  hints: string[];
+  related: { message: string; path: string; range: Range }[];
+}
+
+/** Lifecycle hooks of `render_markup_observed` and `render_form_observed` */
+export interface RenderHooks {
+  /** Return `false` to cancel before compiling */
+  onCompileStart?(): boolean | void;
+  onDiagnostics?(diagnostics: Diagnostic[]): void;
+  /** Return `false` to cancel the remaining export */
+  onPageExported?(index: number, count: number, bytes: Uint8Array): boolean | void;
+  /** Always called last, with the render metrics or the error message */
+  onFinish?(metrics: object | null, error: string | null): void;
+  /** Cancels the render at the next hook once aborted */
+  signal?: AbortSignal;
+}
+"##;